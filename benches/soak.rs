@@ -0,0 +1,77 @@
+//! Sends a million requests through the headless bridge and fails if the
+//! resident set size keeps growing, to catch leaks in the pending map and
+//! the per-connection writers.
+//!
+//!     cargo bench --features bench --bench soak
+//!
+//! Clients reconnect periodically, like the Python side which opens a
+//! connection per request. RSS is read from /proc, so the check only runs
+//! on Linux.
+
+use pygcc::headless::{spawn_echo_server, Client};
+use serde_json::json;
+
+const TOTAL_REQUESTS: usize = 1_000_000;
+const WARMUP_REQUESTS: usize = 100_000;
+const CONNECTIONS: usize = 8;
+const REQUESTS_PER_CONNECTION: usize = 1_000;
+/// Allowed RSS growth between the end of the warm-up and the end of the run.
+const MAX_GROWTH_KB: u64 = 16 * 1024;
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+
+    rt.block_on(async {
+        let addr = spawn_echo_server().await.expect("echo server");
+
+        send(addr, WARMUP_REQUESTS).await;
+        let Some(baseline) = rss_kb() else {
+            println!("soak: RSS not available on this platform, skipping");
+            return;
+        };
+
+        send(addr, TOTAL_REQUESTS - WARMUP_REQUESTS).await;
+        let after = rss_kb().unwrap_or(baseline);
+
+        let growth = after.saturating_sub(baseline);
+        println!(
+            "soak: rss after warm-up {baseline} kB, after {TOTAL_REQUESTS} requests {after} kB"
+        );
+        assert!(
+            growth <= MAX_GROWTH_KB,
+            "RSS grew by {growth} kB over {} requests",
+            TOTAL_REQUESTS - WARMUP_REQUESTS
+        );
+    });
+}
+
+async fn send(addr: std::net::SocketAddr, requests: usize) {
+    let per_task = requests / CONNECTIONS;
+    let tasks: Vec<_> = (0..CONNECTIONS)
+        .map(|id| {
+            tokio::spawn(async move {
+                let args = json!(["ping"]);
+                let mut sent = 0;
+                while sent < per_task {
+                    let mut client = Client::connect(addr).await.expect("connect");
+                    for _ in 0..REQUESTS_PER_CONNECTION.min(per_task - sent) {
+                        client.call(id as u8, "echo", &args).await.expect("call");
+                        sent += 1;
+                    }
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("client task");
+    }
+}
+
+fn rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
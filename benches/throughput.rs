@@ -0,0 +1,89 @@
+//! Requests/second and round-trip latency of the TCP bridge.
+//!
+//! Runs the headless echo server, so the numbers cover framing, the
+//! in-flight bound, the pending map and the writer task, but not the tao
+//! event loop.
+//!
+//!     cargo bench --features bench --bench throughput
+//!
+//! Only JSON is measured; add a codec column once a binary encoding exists.
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use pygcc::headless::{spawn_echo_server, Client};
+use serde_json::{json, Value};
+
+const CONNECTIONS: [usize; 3] = [1, 8, 64];
+const RUN_TIME: Duration = Duration::from_secs(3);
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+
+    rt.block_on(async {
+        let addr = spawn_echo_server().await.expect("echo server");
+        let small = json!(["ping"]);
+        let large = json!(["x".repeat(1024 * 1024)]);
+
+        println!(
+            "{:<8} {:<6} {:>6} {:>12} {:>10} {:>10}",
+            "payload", "codec", "conns", "req/s", "p50", "p99"
+        );
+        for (name, payload) in [("small", &small), ("1MB", &large)] {
+            for connections in CONNECTIONS {
+                let (rps, p50, p99) = run(addr, connections, payload).await;
+                println!(
+                    "{:<8} {:<6} {:>6} {:>12.0} {:>10?} {:>10?}",
+                    name, "json", connections, rps, p50, p99
+                );
+            }
+        }
+    });
+}
+
+async fn run(addr: SocketAddr, connections: usize, payload: &Value) -> (f64, Duration, Duration) {
+    let deadline = Instant::now() + RUN_TIME;
+    let mut tasks = Vec::with_capacity(connections);
+
+    for id in 0..connections {
+        let payload = payload.clone();
+        tasks.push(tokio::spawn(async move {
+            // Request ids key the server's pending map, so every connection
+            // keeps its own id.
+            let mut client = Client::connect(addr).await.expect("connect");
+            let mut latencies = Vec::new();
+            while Instant::now() < deadline {
+                let start = Instant::now();
+                client.call(id as u8, "echo", &payload).await.expect("call");
+                latencies.push(start.elapsed());
+            }
+            latencies
+        }));
+    }
+
+    let mut latencies = Vec::new();
+    for task in tasks {
+        latencies.extend(task.await.expect("client task"));
+    }
+    latencies.sort();
+
+    let rps = latencies.len() as f64 / RUN_TIME.as_secs_f64();
+    (
+        rps,
+        percentile(&latencies, 0.50),
+        percentile(&latencies, 0.99),
+    )
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index]
+}
@@ -0,0 +1,116 @@
+//! Generates `register_api_instances` from the `#[api]` functions in `src/api.rs`,
+//! so a new API only has to be written once.
+
+use std::{env, fmt::Write, fs, path::Path};
+
+const API_SOURCE: &str = "src/api.rs";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", API_SOURCE);
+    println!("cargo::rustc-check-cfg=cfg(tokio_unstable)");
+
+    let source = fs::read_to_string(API_SOURCE).expect("read src/api.rs");
+    let file = syn::parse_file(&source).expect("parse src/api.rs");
+
+    let mut body = String::new();
+    for item in &file.items {
+        let syn::Item::Fn(f) = item else {
+            continue;
+        };
+        if f.attrs.iter().any(|a| a.path().is_ident("api")) {
+            let name = &f.sig.ident;
+            let register = if returns_deferred(&f.sig.output) {
+                "register_deferred_api"
+            } else {
+                "register_api"
+            };
+            writeln!(
+                body,
+                "    api_manager.{}({:?}, {});",
+                register,
+                name.to_string(),
+                name
+            )
+            .unwrap();
+            let options = options(f);
+            if !options.replay {
+                writeln!(
+                    body,
+                    "    api_manager.without_replay({:?});",
+                    name.to_string()
+                )
+                .unwrap();
+            }
+            if let Some(warning) = options.deprecated {
+                writeln!(
+                    body,
+                    "    api_manager.deprecate({:?}, {:?});",
+                    name.to_string(),
+                    warning
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    let generated = format!(
+        "/// Generated by `build.rs` from every `#[api]` function in this file.\n\
+         pub fn register_api_instances(api_manager: &mut ApiManager) {{\n{}}}\n",
+        body
+    );
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("api_registry.rs");
+    fs::write(out, generated).expect("write api_registry.rs");
+}
+
+/// Registration options from `#[api(...)]`; the macro itself only uses
+/// `inject`.
+struct Options {
+    /// Whether retries with a known idempotency key get the cached
+    /// response, i.e. there is no `replay = false`.
+    replay: bool,
+    deprecated: Option<String>,
+}
+
+fn options(f: &syn::ItemFn) -> Options {
+    let mut options = Options {
+        replay: true,
+        deprecated: None,
+    };
+    for attr in &f.attrs {
+        if !attr.path().is_ident("api") || !matches!(attr.meta, syn::Meta::List(_)) {
+            continue;
+        }
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("replay") {
+                options.replay = meta.value()?.parse::<syn::LitBool>()?.value;
+            } else if meta.path.is_ident("deprecated") {
+                options.deprecated = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+            } else {
+                meta.value()?.parse::<syn::Expr>()?;
+            }
+            Ok(())
+        });
+    }
+    options
+}
+
+/// Whether the handler is declared as `-> Result<Deferred>`.
+fn returns_deferred(output: &syn::ReturnType) -> bool {
+    let syn::ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    let syn::Type::Path(path) = ty.as_ref() else {
+        return false;
+    };
+    let Some(result) = path.path.segments.last() else {
+        return false;
+    };
+    let syn::PathArguments::AngleBracketed(args) = &result.arguments else {
+        return false;
+    };
+    matches!(
+        args.args.first(),
+        Some(syn::GenericArgument::Type(syn::Type::Path(inner)))
+            if inner.path.segments.last().is_some_and(|s| s.ident == "Deferred")
+    )
+}
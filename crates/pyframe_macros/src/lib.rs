@@ -1,50 +1,225 @@
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::{
-    FnArg, ItemFn, Pat, Stmt, Type, parse_macro_input, parse_quote, punctuated::Punctuated,
+    FnArg, Ident, ItemFn, LitBool, LitStr, Pat, Path, Stmt, Token, Type, bracketed,
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
     token::Comma,
 };
 
-fn is_option_type(ty: &Type) -> bool {
-    if let Type::Path(type_path) = ty {
-        if let Some(segment) = type_path.path.segments.last() {
-            return segment.ident == "Option";
+/// `#[api(inject = [MyDatabase, MyHttpClient], replay = false, deprecated = "...")]`
+#[derive(Default)]
+struct ApiAttr {
+    inject: Vec<Path>,
+}
+
+impl Parse for ApiAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut attr = Self::default();
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if key == "inject" {
+                let content;
+                bracketed!(content in input);
+                attr.inject = Punctuated::<Path, Comma>::parse_terminated(&content)?
+                    .into_iter()
+                    .collect();
+            } else if key == "replay" {
+                // Read by `build.rs`, which registers the handler.
+                input.parse::<LitBool>()?;
+            } else if key == "deprecated" {
+                // Also read by `build.rs`.
+                input.parse::<LitStr>()?;
+            } else {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "expected `inject = [...]`, `replay = false` or `deprecated = \"...\"`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<Comma>()?;
+            }
+        }
+        Ok(attr)
+    }
+}
+
+/// `MyHttpClient` -> `my_http_client`
+fn snake_case(ident: &Ident) -> Ident {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
         }
     }
+    format_ident!("{}", name)
+}
+
+fn inject_services(inject: &[Path]) -> Vec<Stmt> {
+    inject
+        .iter()
+        .map(|path| {
+            let binding = snake_case(&path.segments.last().unwrap().ident);
+            parse_quote! {
+                let #binding: std::sync::Arc<#path> = ctx.services.require::<#path>()?;
+            }
+        })
+        .collect()
+}
+
+fn is_option_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return segment.ident == "Option";
+    }
     false
 }
 
-fn api_args(inputs: Punctuated<FnArg, Comma>) -> Option<Stmt> {
-    let len = inputs.len();
-    if len == 0 {
-        return None;
+/// `Bytes`, bound to the request's binary attachment instead of an argument.
+fn is_bytes_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return segment.ident == "Bytes";
     }
+    false
+}
+
+/// `State<T>`, bound to the service `T` instead of an argument.
+fn is_state_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return segment.ident == "State";
+    }
+    false
+}
 
+/// `RequestContext`, bound to who sent the request instead of an argument.
+fn is_context_type(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty
+        && let Some(segment) = type_path.path.segments.last()
+    {
+        return segment.ident == "RequestContext";
+    }
+    false
+}
+
+fn api_args(inputs: Punctuated<FnArg, Comma>) -> Vec<Stmt> {
+    let mut stmts = Vec::new();
+    // Last, as a parameter named `req` shadows the request.
+    let mut context = Vec::new();
     let mut names = Punctuated::<Box<Pat>, Comma>::new();
     let mut types = Punctuated::<Box<Type>, Comma>::new();
+    let mut params = Vec::new();
 
     for arg in inputs {
         if let FnArg::Typed(typed) = arg {
+            if is_bytes_type(&typed.ty) {
+                let (name, ty) = (&typed.pat, &typed.ty);
+                stmts.push(parse_quote! {
+                    let #name: #ty = req.attachment()?;
+                });
+                continue;
+            }
+            if is_state_type(&typed.ty) {
+                let (name, ty) = (&typed.pat, &typed.ty);
+                stmts.push(parse_quote! {
+                    let #name: #ty = crate::services::State::from_services(&ctx.services)?;
+                });
+                continue;
+            }
+            if is_context_type(&typed.ty) {
+                let (name, ty) = (&typed.pat, &typed.ty);
+                context.push(parse_quote! {
+                    let #name: #ty = req.context();
+                });
+                continue;
+            }
+            let param = match typed.pat.as_ref() {
+                Pat::Ident(ident) => ident.ident.to_string(),
+                pat => quote!(#pat).to_string(),
+            };
+            let optional = is_option_type(&typed.ty);
+            params.push(quote! { (#param, #optional) });
             names.push(typed.pat.clone());
             types.push(typed.ty.clone());
         }
     }
 
+    let len = names.len();
+    if len == 0 {
+        stmts.extend(context);
+        return stmts;
+    }
     let has_option = types.iter().any(|ty| is_option_type(ty));
 
-    if has_option {
-        Some(parse_quote! {
-            let (#names,) = req.args().optional::<(#types,)>(#len)?;
-        })
-    } else {
-        Some(parse_quote! {
-            let (#names,) = req.args().get::<(#types,)>()?;
-        })
-    }
+    let args: Stmt = parse_quote! {
+        let request_args = req.args().named(&[#(#params),*], ctx.config.strict_args)?;
+    };
+    stmts.insert(
+        0,
+        if has_option {
+            parse_quote! {
+                let (#names,) = request_args.optional::<(#types,)>(#len)?;
+            }
+        } else {
+            parse_quote! {
+                let (#names,) = request_args.get::<(#types,)>()?;
+            }
+        },
+    );
+    stmts.insert(0, args);
+    stmts.extend(context);
+    stmts
 }
 
+/// Turns a function into an API handler for `ApiManager::register_api`.
+///
+/// The parameters are decoded from the request's arguments, an array in
+/// their order or an object keyed by their names, where a missing `Option`
+/// is `None` and unknown names fail with the `strict_args` creation option.
+/// `ctx`, `req`, `target` and `flow` are in scope inside the body. The return type is
+/// passed through unchanged, so any `Result<T>` with `T: serde::Serialize`
+/// works: structs, vectors, maps, `()` (sent as `null`), or
+/// `Result<serde_json::Value>` for handlers that return differently shaped
+/// data. A result that fails to serialize is answered with `code::INTERNAL`.
+///
+/// Handlers declared as `-> Result<Deferred>` answer later through
+/// `ctx.responder(&req)`; `build.rs` registers them with
+/// `register_deferred_api`.
+///
+/// `#[api(inject = [MyDatabase])]` binds `my_database: Arc<MyDatabase>` from
+/// `ctx.services` before the body runs; a missing service fails the request.
+/// So does a parameter `db: State<MyDatabase>`, which isn't an argument
+/// either. Closures that own their state instead are registered with
+/// `ApiManager::register_handler`.
+///
+/// A parameter of type `Bytes` is not an argument but the binary attachment
+/// sent along with the request; a request without one fails with
+/// `code::BAD_ARGS`.
+///
+/// A parameter of type `RequestContext`, e.g. `req: RequestContext`, is
+/// who sent the request: its connection, principal, negotiated features,
+/// trace id, target window, arrival time and the extensions middleware
+/// attached. Named `req`, it shadows the request for the rest of the body.
+///
+/// `#[api(replay = false)]` runs the handler for every request, even a retry
+/// whose idempotency key already has a cached response.
+///
+/// `#[api(deprecated = "use window.set_size")]` keeps serving the API but
+/// adds the warning to its responses and to `list_apis`.
 #[proc_macro_attribute]
-pub fn api(_: TokenStream, raw: TokenStream) -> TokenStream {
+pub fn api(attr: TokenStream, raw: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as ApiAttr);
     let f = parse_macro_input!(raw as ItemFn);
     let name = f.sig.ident;
     let output = f.sig.output;
@@ -52,7 +227,8 @@ pub fn api(_: TokenStream, raw: TokenStream) -> TokenStream {
     // Statements aus Body sichern
     let body = f.block.stmts;
     // Parameter als Tuple holen
-    let args_stmt = api_args(f.sig.inputs);
+    let args_stmts = api_args(f.sig.inputs);
+    let inject_stmts = inject_services(&attr.inject);
 
     let expanded = quote! {
         fn #name(
@@ -61,7 +237,8 @@ pub fn api(_: TokenStream, raw: TokenStream) -> TokenStream {
             target: &crate::utils::FrameWindowTarget,
             flow: &mut tao::event_loop::ControlFlow,
         ) #output {
-            #args_stmt
+            #(#args_stmts)*
+            #(#inject_stmts)*
             #(#body)*
         }
     };
@@ -0,0 +1,30 @@
+import asyncio
+from pyframe import launch, command, Window
+
+
+# Manual check for click-through mode: the page is transparent apart from a
+# toolbar. Clicks anywhere else should reach the application behind the
+# window, while the toolbar buttons keep working.
+
+
+@command
+async def overlay(window: Window, enabled: bool, toolbar: dict) -> bool:
+    """
+    Toggle click-through, keeping the toolbar interactive.
+
+    :param window: Active PyFrame window
+    :param enabled: Whether the window lets clicks through
+    :param toolbar: Bounding rectangle of the toolbar in CSS pixels
+    :return: ``True`` if both settings were applied
+    """
+    await window.set_always_on_top(True)
+    regions = await window.set_interactive_regions([toolbar])
+    return regions and await window.set_ignore_cursor_events(enabled)
+
+
+if __name__ == "__main__":
+    try:
+        asyncio.run(launch(path="click_through.html", host="127.0.0.1", port=9000,
+                           options={"transparent": True}))
+    except KeyboardInterrupt:
+        print("Runtime beendet.")
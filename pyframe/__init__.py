@@ -4,11 +4,57 @@ Public API entry point for the package.
 Exports the main building blocks:
   * :func:`command` → decorator to register event commands
   * :func:`launch` → start the native runtime
+  * :func:`events` → stream the events of the Rust event loop
+  * :func:`emit` → push an event to the pages without a request
+  * :func:`on_exit` → cleanup that runs while the webframe exits
   * :class:`Window` → window control interface
+  * :class:`Timers` → native timeouts and intervals
+  * :class:`Shell` → open files with the system's applications
+  * :class:`Clipboard` → text, HTML and file lists on the system clipboard
+  * :class:`Process` → long-running programs with streamed output
+  * :class:`Dialog` → native file and folder dialogs
+  * :class:`Fonts` → installed system font families
+  * :class:`Http` → native HTTP requests without CORS restrictions
+  * :class:`WebSocket` → native outbound WebSockets
+  * :class:`Updater` → signed self-updates
+  * :class:`Store` → persistent key-value settings
+  * :class:`State` → in-memory JSON documents shared with every page
+  * :class:`Env` → environment and command line allowed by ``env_access``
+  * :class:`Fs` → file access within the configured ``fs_scope``
+  * :class:`HttpMock` → canned ``fetch`` responses for tests
+  * :class:`Permissions` → answer camera, microphone and location requests
+  * :class:`Geolocation` → positions for ``navigator.geolocation``
+  * :class:`Tray` → the tray icon's menu
+  * :class:`Dock` → the macOS dock menu and activation policy
+  * :class:`Menu` → the native menu bar
+  * :class:`Diagnostics` → logging filters and metadata of the Rust internals
+  * :class:`Audit` → recent calls of privileged APIs
 """
 
 from .pyinvoke import command
 from .control.window import Window
+from .control.timers import Timers
+from .control.shell import Shell
+from .control.clipboard import Clipboard
+from .control.process import Process
+from .control.fs import Fs
+from .control.env import Env
+from .control.store import Store
+from .control.state import State
+from .control.dialog import Dialog, FileFilter
+from .control.fonts import Fonts
+from .control.http_mock import HttpMock
+from .control.http import Http, HttpResponse
+from .control.updater import Updater
+from .control.ws import WebSocket
+from .control.permissions import Permissions
+from .control.geolocation import Geolocation
+from .control.tray import Tray
+from .control.dock import Dock
+from .control.menu import Menu
+from .control.diagnostics import Diagnostics
+from .control.audit import Audit
 from .runtime import native_runtime as launch
+from .runtime_handle import emit, events, on_exit
 
-__all__ = ["command", "launch", "Window"]
+__all__ = ["command", "launch", "events", "emit", "on_exit", "Window", "Timers", "Shell", "Clipboard", "Process", "Fs", "Env", "Store", "State", "Dialog", "FileFilter", "Fonts", "Http", "HttpResponse", "Updater", "WebSocket", "HttpMock", "Permissions", "Geolocation", "Tray", "Dock", "Menu", "Diagnostics", "Audit"]
@@ -1,15 +1,22 @@
 import asyncio
 import json
 import os
-import struct
-from typing import Any, Dict, Optional
+from typing import Any, Dict, List, Optional
 
 import websockets
 from pydantic import BaseModel
 from websockets import ServerConnection
+from pygcc import BindError
 
-from . import core
-from .pyinvoke import make_callback
+from . import core, protocol
+from .pyinvoke import make_callback, no_replay
+from .runtime_handle import make_json_safe, request_priority
+
+#: Features the WebSocket server can negotiate.
+SUPPORTED_FEATURES: List[str] = ["batch", "binary", "timing"]
+#: Responses replayed to page calls resent with the same ``idempotency_key``.
+#: Replace it to change the capacity or TTL.
+replies = protocol.ReplyCache()
 
 
 async def send_loop_event(data: Optional[Dict[str, Any]] = None) -> Optional[Dict[str, Any]]:
@@ -24,48 +31,97 @@ async def send_loop_event(data: Optional[Dict[str, Any]] = None) -> Optional[Dic
     """
     port = int(os.environ["RUSTADDR"])
     reader, writer = await asyncio.open_connection("127.0.0.1", port)
-
-    payload = json.dumps(data).encode()
-    writer.write(struct.pack(">I", len(payload)) + payload)
-    await writer.drain()
-
-    header = await reader.readexactly(4)
-    (length,) = struct.unpack(">I", header)
-
-    response_bytes = await reader.readexactly(length)
-    writer.close()
-    await writer.wait_closed()
-    return json.loads(response_bytes.decode())
+    try:
+        await protocol.handshake(reader, writer)
+        await protocol.write_frame(writer, data)
+        return await protocol.read_frame(reader)
+    finally:
+        writer.close()
+        await writer.wait_closed()
 
 
 async def handle_frontend_connections(websocket: ServerConnection) -> None:
     """
     Handle incoming WebSocket connections from frontend clients.
 
-    Each received message is validated, dispatched to the appropriate
-    callback handler via :func:`make_callback`, and the response is
-    broadcast to all connected clients.
+    The first message must be a hello (see :mod:`pyframe.protocol`); a
+    refused one closes the connection with the reason code. Every later
+    message is validated, dispatched to the appropriate callback handler
+    via :func:`make_callback`, and the response is broadcast to all
+    connected clients. A batch, if negotiated, is answered to its sender
+    only; see :func:`run_batch`. A call with an ``idempotency_key`` runs once
+    per page, see :data:`replies`. With the ``binary`` feature, a call may
+    come as a binary message (see :func:`protocol.decode_binary`) whose
+    attachment goes to the command's ``bytes`` parameter. With the ``timing``
+    feature, responses carry the call's ``meta``, see :class:`protocol.RequestTiming`.
 
     :param websocket: The client WebSocket connection.
     """
+    greeted = False
+    features: List[str] = []
+    # Replaced by the page's id from its hello.
+    client = f"connection-{id(websocket)}"
     core.connected_clients.add(websocket)
     try:
         async for message in websocket:
+            timing = protocol.RequestTiming()
             try:
-                payload = json.loads(message.strip())
+                attachment: Optional[bytes] = None
+                if isinstance(message, bytes) and message[:1] == bytes([protocol.BINARY_MARKER]):
+                    payload, attachment = protocol.decode_binary(message)
+                else:
+                    payload = json.loads(message.strip())
                 if isinstance(payload, str):
                     payload = json.loads(payload)
 
+                if not greeted:
+                    greeted = True
+                    if protocol.is_hello(payload):
+                        try:
+                            ack = protocol.negotiate(payload, SUPPORTED_FEATURES)
+                        except protocol.ProtocolError as e:
+                            await websocket.close(e.code, e.reason)
+                            break
+                        features = ack["features"]
+                        if isinstance(payload.get("client"), str):
+                            client = payload["client"]
+                        await websocket.send(json.dumps(ack))
+                        continue
+                    try:
+                        protocol.check_legacy()
+                    except protocol.ProtocolError as e:
+                        await websocket.close(e.code, e.reason)
+                        break
+                    print(f"[WARN] {websocket.remote_address} connected without a hello; protocol version 0 is deprecated")
+                    await websocket.send(json.dumps({
+                        "event": "protocol-deprecated",
+                        "data": {"message": "Connected without a hello; version 0 is removed in the next release"},
+                    }))
+
                 if not isinstance(payload, dict):
                     continue
 
+                if payload.get("type") == "batch" and "batch" in features:
+                    await websocket.send(json.dumps(await run_batch(payload, "timing" in features)))
+                    continue
+
                 if all(k in payload for k in ("cmd", "result_id", "error_id", "payload")):
-                    response = await make_callback(
-                        payload["cmd"],
-                        payload["result_id"],
-                        payload["error_id"],
-                        payload["payload"],
-                    )
+                    # Requests the command makes inherit the page's priority.
+                    priority = request_priority.set(payload.get("priority"))
+                    try:
+                        if attachment is not None and "binary" not in features:
+                            response = {"error_id": payload["error_id"], "error": "Attachments need the binary feature"}
+                        elif attachment is not None and len(attachment) > protocol.MAX_ATTACHMENT_BYTES:
+                            response = {
+                                "error_id": payload["error_id"],
+                                "error": f"Attachments are limited to {protocol.MAX_ATTACHMENT_BYTES} bytes",
+                            }
+                        else:
+                            response = await call_once(client, payload, attachment, timing)
+                    finally:
+                        request_priority.reset(priority)
+                    if "timing" in features and isinstance(response, dict):
+                        response = {**response, "meta": timing.meta()}
 
                     response_msg = (
                         response.model_dump_json(by_alias=True)
@@ -86,7 +142,73 @@ async def handle_frontend_connections(websocket: ServerConnection) -> None:
         core.connected_clients.discard(websocket)
 
 
-async def create_websocket_server(host: str = "localhost", port: int = 8765) -> None:
+async def call_once(
+    client: str,
+    payload: Dict[str, Any],
+    attachment: Optional[bytes] = None,
+    timing: Optional[protocol.RequestTiming] = None,
+) -> Any:
+    """
+    Run a page call, or answer a retry of it from :data:`replies`.
+
+    A replayed response is addressed to the retry's callbacks.
+    """
+    cmd, key = payload["cmd"], payload.get("idempotency_key")
+
+    async def call() -> Any:
+        return await make_callback(
+            cmd, payload["result_id"], payload["error_id"], payload["payload"], attachment, timing
+        )
+
+    if not isinstance(key, str) or cmd in no_replay:
+        return await call()
+    try:
+        response = await replies.run(client, key, cmd, call)
+    except ValueError as e:
+        return {"error_id": payload["error_id"], "error": str(e)}
+    if not isinstance(response, dict):
+        return response
+    ids = {k: payload[k] for k in ("result_id", "error_id") if k in response}
+    return {**response, **ids}
+
+
+async def run_batch(payload: Dict[str, Any], timing: bool = False) -> Dict[str, Any]:
+    """
+    Run the commands of a ``{"type": "batch", "id", "calls"}`` message.
+
+    Each call (``{"cmd", "payload", "priority"}``) runs on its own, so one
+    failing doesn't affect the others.
+
+    :param timing: Whether each result gets its ``meta``.
+    :return: ``{"type": "batch", "id", "results"}``, one ``{"result"}`` or
+        ``{"error"}`` per call in the same order, with ``deprecated`` for
+        deprecated commands.
+    """
+    calls = payload.get("calls") or []
+    if len(calls) > protocol.MAX_BATCH_SIZE:
+        error = f"Batches are limited to {protocol.MAX_BATCH_SIZE} calls"
+        return {"type": "batch", "id": payload.get("id"), "results": [{"error": error} for _ in calls]}
+
+    async def run(call: Any) -> Dict[str, Any]:
+        if not isinstance(call, dict) or "cmd" not in call:
+            return {"error": "A batch call needs a cmd"}
+        request_priority.set(call.get("priority"))
+        call_timing = protocol.RequestTiming()
+        response = await make_callback(call["cmd"], None, None, call.get("payload") or {}, None, call_timing)
+        result = {"error": response["error"]} if "error" in response else {"result": make_json_safe(response.get("result"))}
+        if "deprecated" in response:
+            result["deprecated"] = response["deprecated"]
+        if timing:
+            result["meta"] = call_timing.meta()
+        return result
+
+    results = await asyncio.gather(*(run(call) for call in calls))
+    return {"type": "batch", "id": payload.get("id"), "results": list(results)}
+
+
+async def create_websocket_server(
+    host: str = "localhost", port: int = 8765, ready: Optional["asyncio.Future[int]"] = None
+) -> None:
     """
     Create and start a WebSocket server.
 
@@ -94,7 +216,21 @@ async def create_websocket_server(host: str = "localhost", port: int = 8765) ->
     messages to :func:`handle_frontend_connections`.
 
     :param host: The host address to bind the server. Defaults to ``"localhost"``.
-    :param port: The port to bind the server. Defaults to ``8765``.
+    :param port: The port to bind the server. Defaults to ``8765``; ``0`` picks a free one.
+    :param ready: Resolved with the bound port, or with a :class:`pygcc.BindError`
+        naming ``port`` if it couldn't be bound.
     """
-    async with websockets.serve(handle_frontend_connections, host, port):
+    # Room for the largest attachment plus its call.
+    max_size = protocol.MAX_ATTACHMENT_BYTES + 1024 * 1024
+    try:
+        server = await websockets.serve(handle_frontend_connections, host, port, max_size=max_size)
+    except OSError as e:
+        error = BindError(f"Can't listen on port {port}: {e}", port)
+        if ready is None:
+            raise error from e
+        ready.set_exception(error)
+        return
+    async with server:
+        if ready is not None:
+            ready.set_result(server.sockets[0].getsockname()[1])
         await asyncio.Future()
@@ -0,0 +1,39 @@
+from typing import Any, Dict, Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Audit:
+    """
+    The audit log of privileged API calls, kept when the app was launched
+    with the ``audit`` option. Entries have the call's ``at`` (Unix time in
+    milliseconds), ``connection``, ``client``, ``method``, redacted ``args``,
+    result ``code``, ``error`` message and ``latency_ms``.
+    """
+
+    async def query(
+        self,
+        method: Optional[str] = None,
+        connection: Optional[int] = None,
+        client: Optional[str] = None,
+        failed: bool = False,
+        since: Optional[int] = None,
+        limit: Optional[int] = None,
+    ) -> Dict[str, Any]:
+        """
+        The newest matching entries, oldest first, as ``{"entries", "dropped"}``;
+        ``dropped`` counts entries the writer couldn't keep up with.
+
+        :param method: A method name, or a prefix ending in ``*``.
+        :param failed: Only calls that didn't succeed.
+        :param since: Only entries at or after this Unix time in milliseconds.
+        :param limit: At most this many entries; 100 by default.
+        """
+        select = {
+            "method": method,
+            "connection": connection,
+            "client": client,
+            "failed": failed,
+            "since": since,
+            "limit": limit,
+        }
+        return await eventloop_event_register_typed("audit_query", [select], result_type=Dict[str, Any])
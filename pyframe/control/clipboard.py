@@ -0,0 +1,55 @@
+from typing import List, Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Clipboard:
+    """
+    The system clipboard: plain text, HTML with a plain-text alternative,
+    and file lists.
+
+    Formats a platform doesn't support read as ``None`` and write as ``False``
+    instead of raising. On Linux the app serves what it wrote itself, so it
+    is gone once the app exits unless a clipboard manager kept a copy.
+    """
+
+    async def write_text(self, text: str) -> bool:
+        """Replace the clipboard's contents with ``text``."""
+        return await eventloop_event_register_typed("clipboard_write_text", [text], result_type=bool)
+
+    async def read_text(self) -> Optional[str]:
+        """The clipboard's text, ``None`` if it holds none."""
+        return await eventloop_event_register_typed(
+            "clipboard_read_text", result_type=lambda t: None if t is None else str(t)
+        )
+
+    async def write_html(self, html: str, alt_text: Optional[str] = None) -> bool:
+        """
+        Put ``html`` on the clipboard, e.g. a copied table, together with a
+        plain-text version for programs that only paste text.
+
+        :param html: An HTML fragment.
+        :param alt_text: The plain text; by default ``html`` with its tags
+            stripped, table cells separated by tabs and rows by newlines.
+        :return: ``False`` if the platform has no HTML clipboard.
+        """
+        return await eventloop_event_register_typed("clipboard_write_html", [html, alt_text], result_type=bool)
+
+    async def read_html(self) -> Optional[str]:
+        """The clipboard's HTML fragment, ``None`` if it holds none."""
+        return await eventloop_event_register_typed(
+            "clipboard_read_html", result_type=lambda h: None if h is None else str(h)
+        )
+
+    async def read_files(self) -> Optional[List[str]]:
+        """
+        The paths of the files copied in a file manager, for "Paste files":
+        ``CF_HDROP`` on Windows, file URLs on macOS and ``text/uri-list`` on
+        Linux. ``None`` if there are none.
+        """
+        return await eventloop_event_register_typed(
+            "clipboard_read_files", result_type=lambda fs: None if fs is None else [str(f) for f in fs]
+        )
+
+    async def formats(self) -> List[str]:
+        """What the clipboard holds now: ``"text"``, ``"html"``, ``"files"`` and ``"image"``."""
+        return await eventloop_event_register_typed("clipboard_formats", result_type=list)
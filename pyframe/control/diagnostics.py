@@ -0,0 +1,37 @@
+from typing import Any, Dict
+
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Diagnostics:
+    """
+    Logging of the Rust internals, configured at launch with the ``log``
+    option. Filters are ``EnvFilter`` directives naming modules of the
+    ``pygcc`` crate, e.g. ``"pygcc=info,pygcc::connections=debug"``.
+    """
+
+    async def set_log_filter(self, spec: str) -> str:
+        """
+        Change which events are logged without restarting, e.g. to raise the
+        verbosity on a user's machine. Returns the previous filter so it can
+        be restored. Raises :class:`ApiError` with ``ApiError.BAD_ARGS`` if
+        ``spec`` doesn't parse.
+        """
+        return await eventloop_event_register_typed("set_log_filter", [spec], result_type=str)
+
+    async def app_metadata(self) -> Dict[str, Any]:
+        """
+        The ``version`` of the native module, its ``pid`` and ``app_id``, and
+        where the webframe is reached: ``transport``, ``socket_url``, ``ws_port``
+        and ``tcp_port``, the bound ports also when ``0`` picked them.
+        """
+        return await eventloop_event_register_typed("app_metadata", [], result_type=Dict[str, Any])
+
+    async def request_context(self) -> Dict[str, Any]:
+        """
+        How the webframe sees this call: its ``connection``, ``client``,
+        whether it is ``authenticated`` by the launch token, the negotiated
+        ``features``, its ``trace_id``, ``window_id`` and ``received_at``
+        (Unix milliseconds).
+        """
+        return await eventloop_event_register_typed("request_context", [], result_type=Dict[str, Any])
@@ -0,0 +1,99 @@
+from typing import Any, Dict, List, Optional
+from pydantic import BaseModel
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class FileFilter(BaseModel):
+    """A named group of file extensions, given without dots (``["png", "jpg"]``)."""
+    name: str
+    extensions: List[str]
+
+
+def _options(**options: Any) -> Dict[str, Any]:
+    filters = options.pop("filters", None)
+    if filters:
+        options["filters"] = [FileFilter.model_validate(f).model_dump() for f in filters]
+    return {k: v for k, v in options.items() if v is not None}
+
+
+# The user may keep a dialog open for as long as they like.
+_NO_TIMEOUT = 24 * 60 * 60.0
+
+
+class Dialog:
+    """
+    Native file and folder dialogs.
+
+    Every dialog is modal to ``parent`` (a window ``id`` from
+    :meth:`Window.get_window_state`), or to the first window. Returned paths
+    are absolute and canonical; ``None`` means the dialog was cancelled.
+    Invalid options, such as extensions with a leading dot, raise before
+    a dialog opens.
+    """
+
+    async def open_file(
+        self,
+        title: Optional[str] = None,
+        directory: Optional[str] = None,
+        filters: Optional[List[FileFilter]] = None,
+        multiple: bool = False,
+        parent: Optional[int] = None,
+    ) -> Optional[List[str]]:
+        """
+        Let the user pick one or, with ``multiple``, several files.
+
+        :return: The picked paths, or ``None`` if cancelled.
+        """
+        options = _options(title=title, directory=directory, filters=filters, multiple=multiple, parent=parent)
+        return await eventloop_event_register_typed("dialog_open", [options], result_type=lambda v: v, timeout=_NO_TIMEOUT)
+
+    async def save_file(
+        self,
+        title: Optional[str] = None,
+        directory: Optional[str] = None,
+        default_name: Optional[str] = None,
+        filters: Optional[List[FileFilter]] = None,
+        confirm_overwrite: Optional[bool] = None,
+        can_create_directories: Optional[bool] = None,
+        parent: Optional[int] = None,
+    ) -> Optional[str]:
+        """
+        Ask where to save a file.
+
+        :param default_name: File name the dialog suggests.
+        :param confirm_overwrite: The native dialogs always confirm, so
+            ``False`` raises ``ApiError.UNSUPPORTED``.
+        :return: The chosen path, which may not exist yet, or ``None`` if cancelled.
+        """
+        options = _options(
+            title=title,
+            directory=directory,
+            default_name=default_name,
+            filters=filters,
+            confirm_overwrite=confirm_overwrite,
+            can_create_directories=can_create_directories,
+            parent=parent,
+        )
+        return await eventloop_event_register_typed("dialog_save", [options], result_type=lambda v: v, timeout=_NO_TIMEOUT)
+
+    async def pick_folder(
+        self,
+        title: Optional[str] = None,
+        directory: Optional[str] = None,
+        multiple: bool = False,
+        can_create_directories: Optional[bool] = None,
+        parent: Optional[int] = None,
+    ) -> Optional[List[str]]:
+        """
+        Let the user pick one or, with ``multiple``, several folders.
+
+        :return: The picked paths, or ``None`` if cancelled.
+        """
+        options = _options(
+            title=title,
+            directory=directory,
+            multiple=multiple,
+            can_create_directories=can_create_directories,
+            parent=parent,
+        )
+        return await eventloop_event_register_typed("dialog_folder", [options], result_type=lambda v: v, timeout=_NO_TIMEOUT)
@@ -0,0 +1,32 @@
+from typing import Any, Dict, List
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Dock:
+    """
+    The macOS dock. Every method raises :class:`UnsupportedError` on other
+    platforms.
+
+    Clicking the dock icon dispatches ``pyframe:dock-reopen``
+    (``{"has_visible_windows"}``) to the page and streams it by
+    :func:`pyframe.events`; a hidden window comes back first unless the app
+    was launched with ``reopen_on_dock_click=False``.
+    """
+
+    async def set_menu(self, items: List[Dict[str, Any]]) -> bool:
+        """
+        Replace the dock menu. Items are defined like :meth:`pyframe.Tray.set_menu`
+        items; choosing one dispatches ``pyframe:dock-menu``
+        (``{"id", "kind", "checked", "group"}``).
+        """
+        return await eventloop_event_register_typed("set_dock_menu", [items], result_type=bool)
+
+    async def set_activation_policy(self, policy: str) -> bool:
+        """
+        Set whether the app shows in the dock and the Cmd+Tab switcher.
+
+        :param policy: ``"regular"``, ``"accessory"`` (neither, like a menu
+            bar extra) or ``"prohibited"`` (never activated, like a background
+            agent).
+        """
+        return await eventloop_event_register_typed("set_activation_policy", [policy], result_type=bool)
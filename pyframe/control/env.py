@@ -0,0 +1,65 @@
+from typing import Dict, List, Optional
+from pydantic import BaseModel
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class EnvValue(BaseModel):
+    """A variable or argument as UTF-8."""
+    value: str
+    #: Invalid UTF-8 was replaced with U+FFFD.
+    lossy: bool
+
+
+class EnvInfo(BaseModel):
+    """What ``env_access`` exposes, from :meth:`Env.info`."""
+    #: ``False`` only if the prefix ``""`` exposes the whole environment.
+    filtered: bool
+    names: List[str]
+    prefixes: List[str]
+    args: bool
+
+
+class Env:
+    """
+    Read the native process's environment and command line.
+
+    Only what the ``env_access`` option of :func:`pyframe.launch` lists is
+    readable; by default nothing is.
+    """
+
+    async def get(self, name: str) -> Optional[EnvValue]:
+        """
+        Read one environment variable.
+
+        :param name: A name listed in ``env_access.names`` or matching one of its ``prefixes``.
+        :return: The value, or ``None`` if the variable is unset.
+        :raises ApiError: With code ``ApiError.PERMISSION_DENIED`` if the name isn't exposed.
+        """
+        return await eventloop_event_register_typed(
+            "env_get", [name], result_type=lambda v: None if v is None else EnvValue.model_validate(v)
+        )
+
+    async def all(self, prefix: str = "") -> Dict[str, EnvValue]:
+        """
+        Read every exposed variable starting with ``prefix``.
+
+        :param prefix: Only names starting with it, e.g. ``"KIOSK_"``.
+        :return: The values by name.
+        """
+        return await eventloop_event_register_typed(
+            "env_all", [prefix], result_type=lambda d: {k: EnvValue.model_validate(v) for k, v in d.items()}
+        )
+
+    async def args(self) -> List[EnvValue]:
+        """
+        Read the native process's command-line arguments, program first.
+
+        :raises ApiError: With code ``ApiError.PERMISSION_DENIED`` unless ``env_access.args`` is set.
+        """
+        return await eventloop_event_register_typed(
+            "env_args", result_type=lambda a: [EnvValue.model_validate(v) for v in a]
+        )
+
+    async def info(self) -> EnvInfo:
+        """Show what ``env_access`` exposes and whether filtering is active."""
+        return await eventloop_event_register_typed("env_info", result_type=EnvInfo)
@@ -0,0 +1,37 @@
+from typing import List
+from pydantic import BaseModel
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class FontStyle(BaseModel):
+    """One installed face of a family."""
+    weight: int
+    #: ``"normal"``, ``"italic"`` or ``"oblique"``.
+    style: str
+
+
+class FontFamily(BaseModel):
+    """An installed font family, as CSS ``font-family`` names it."""
+    family: str
+    styles: List[FontStyle]
+    monospaced: bool
+
+
+class Fonts:
+    """
+    Installed system fonts, e.g. for a font picker.
+
+    The first call enumerates the fonts, which can take a few seconds on
+    systems with many of them; later calls are answered from a cache.
+    Fonts whose family name can't be decoded are skipped.
+    """
+
+    async def list(self) -> List[FontFamily]:
+        """List the installed font families, sorted by name."""
+        return await eventloop_event_register_typed(
+            "fonts_list", result_type=lambda fs: [FontFamily.model_validate(f) for f in fs], timeout=60.0
+        )
+
+    async def exists(self, family: str) -> bool:
+        """Check whether a font family is installed, ignoring case."""
+        return await eventloop_event_register_typed("fonts_exists", [family], result_type=bool, timeout=60.0)
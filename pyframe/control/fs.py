@@ -0,0 +1,140 @@
+import base64
+from typing import Any, AsyncIterator, Dict, List, Union
+from pydantic import BaseModel
+from ..runtime_handle import call_with_events, eventloop_event_register_typed
+
+
+class DirEntry(BaseModel):
+    """An entry listed by :meth:`Fs.read_dir`."""
+    name: str
+    path: str
+    is_dir: bool
+    is_file: bool
+    is_symlink: bool
+    size: int
+
+
+class Fs:
+    """
+    File access limited to the ``fs_scope`` passed to :func:`pyframe.launch`.
+
+    Paths outside the scope, including symlinks pointing out of it, raise
+    ``ApiError`` with code ``ApiError.PERMISSION_DENIED``.
+    """
+
+    async def read_file(self, path: str, binary: bool = False) -> Union[str, bytes]:
+        """
+        Read a file.
+
+        :param path: Path of the file.
+        :param binary: Return ``bytes`` instead of decoding the file as UTF-8.
+        :return: The file's contents.
+        """
+        data = await eventloop_event_register_typed("fs_read_file", [path, binary], result_type=str)
+        return base64.b64decode(data) if binary else data
+
+    async def write_file(self, path: str, contents: Union[str, bytes], append: bool = False) -> bool:
+        """
+        Write a file, creating it if needed.
+
+        :param path: Path of the file.
+        :param contents: Text, or ``bytes`` written as they are.
+        :param append: Append instead of replacing the file's contents.
+        :return: ``True`` once written.
+        """
+        binary = isinstance(contents, bytes)
+        data = base64.b64encode(contents).decode("ascii") if binary else contents
+        return await eventloop_event_register_typed(
+            "fs_write_file", [path, data, binary, append], result_type=bool
+        )
+
+    async def write_file_binary(self, path: str, data: bytes) -> bool:
+        """
+        Write ``data`` to a file, replacing it, without base64 in between.
+
+        :param path: Path of the file.
+        :param data: At most ``MAX_ATTACHMENT_BYTES`` (64 MiB).
+        :return: ``True`` once written.
+        """
+        return await eventloop_event_register_typed(
+            "fs_write_file_binary", [path], result_type=bool, attachment=bytes(data)
+        )
+
+    async def read_dir(self, path: str) -> List[DirEntry]:
+        """
+        List a directory, sorted by name.
+
+        :param path: Path of the directory.
+        :return: Its entries; symlinks are listed, not followed.
+        """
+        return await eventloop_event_register_typed(
+            "fs_read_dir", [path], result_type=lambda entries: [DirEntry.model_validate(e) for e in entries]
+        )
+
+    async def exists(self, path: str) -> bool:
+        """
+        :param path: Path to check.
+        :return: Whether something exists at ``path``.
+        """
+        return await eventloop_event_register_typed("fs_exists", [path], result_type=bool)
+
+    async def remove(self, path: str, recursive: bool = False) -> bool:
+        """
+        Remove a file, symlink or empty directory.
+
+        :param path: Path to remove.
+        :param recursive: Also remove non-empty directories with their contents.
+        :return: ``True`` once removed.
+        """
+        return await eventloop_event_register_typed("fs_remove", [path, recursive], result_type=bool)
+
+    async def create_dir(self, path: str, recursive: bool = False) -> bool:
+        """
+        Create a directory.
+
+        :param path: Path of the new directory.
+        :param recursive: Also create missing parent directories.
+        :return: ``True`` once created.
+        """
+        return await eventloop_event_register_typed("fs_create_dir", [path, recursive], result_type=bool)
+
+    async def copy(self, source: str, destination: str) -> bool:
+        """
+        Copy a file, replacing ``destination`` if it exists.
+
+        :return: ``True`` once copied.
+        """
+        return await eventloop_event_register_typed("fs_copy", [source, destination], result_type=bool)
+
+    async def rename(self, source: str, destination: str) -> bool:
+        """
+        Move or rename a file, directory or symlink.
+
+        :return: ``True`` once moved.
+        """
+        return await eventloop_event_register_typed("fs_rename", [source, destination], result_type=bool)
+
+    async def watch(self, path: str, recursive: bool = False) -> AsyncIterator[List[Dict[str, Any]]]:
+        """
+        Watch a file or directory for changes.
+
+        Changes are coalesced per path and yielded in batches of
+        ``{"kind": "created" | "modified" | "removed", "path": ...}``. The
+        watcher stops when the iteration ends. The frontend receives the same
+        batches as ``fs-change`` events.
+
+        Usage::
+
+            async for changes in Fs().watch("frames", recursive=True):
+                ...
+
+        :param path: File or directory inside the ``fs_scope``.
+        :param recursive: Include changes in subdirectories.
+        :return: An async iterator of change batches.
+        """
+        watcher_id = None
+        async for event, data in call_with_events("fs_watch", [path, recursive]):
+            if event == "__result":
+                watcher_id = data
+            elif event == "fs-change" and data.get("id") == watcher_id:
+                yield data.get("changes", [])
@@ -0,0 +1,36 @@
+from typing import Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Geolocation:
+    """
+    Feed ``navigator.geolocation`` from Python, e.g. a fixed kiosk location
+    or a GPS receiver read with pyserial.
+
+    Needs the ``mock_geolocation`` option of :func:`pyframe.launch`. The page
+    gets access according to ``permissions.geolocation``, answered with
+    :class:`Permissions` under ``"ask"``.
+    """
+
+    async def set_mock_location(
+        self,
+        latitude: float,
+        longitude: float,
+        accuracy: float,
+        altitude: Optional[float] = None,
+        heading: Optional[float] = None,
+        speed: Optional[float] = None,
+    ) -> bool:
+        """
+        Set the position the page sees, timestamped now, and push it to every
+        ``watchPosition`` callback.
+
+        :param accuracy: Radius in meters.
+        :param heading: Degrees clockwise from true north.
+        :param speed: Meters per second.
+        :return: ``True`` once sent.
+        :raises Exception: Without ``mock_geolocation`` or with coordinates
+            out of range.
+        """
+        args = [latitude, longitude, accuracy, altitude, heading, speed]
+        return await eventloop_event_register_typed("set_mock_location", args, result_type=bool)
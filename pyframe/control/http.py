@@ -0,0 +1,92 @@
+import base64
+from typing import Any, AsyncIterator, Dict, List, Optional, Tuple, Union
+from pydantic import BaseModel
+from ..runtime_handle import ApiError, call_with_events, eventloop_event_register_typed
+
+
+class HttpResponse(BaseModel):
+    """Response of :meth:`Http.fetch`."""
+    status: int
+    #: The final URL, after redirects.
+    url: str
+    #: In the order received; repeated headers appear several times.
+    headers: List[Tuple[str, str]]
+    #: ``None`` for :meth:`Http.stream`.
+    body: Optional[Union[str, bytes]] = None
+    binary: bool = False
+    stream: Optional[int] = None
+
+    def header(self, name: str) -> Optional[str]:
+        """The first header called ``name``, ignoring case."""
+        name = name.lower()
+        return next((v for k, v in self.headers if k.lower() == name), None)
+
+
+class Http:
+    """
+    HTTP requests made by the native side, not subject to the page's CORS rules.
+
+    Only origins listed in the ``http`` option's ``allowed_origins`` are
+    reachable; others, also as redirect targets, raise ``ApiError`` with
+    ``ApiError.PERMISSION_DENIED``. Unreachable servers raise
+    ``ApiError.OFFLINE``.
+
+    Options: ``method``, ``headers``, ``body`` (``str`` or ``bytes``),
+    ``timeout`` in seconds, ``redirect`` (``"follow"``, ``"manual"`` or
+    ``"error"``) and ``max_redirects``. Invalid TLS certificates are only
+    accepted with the ``http`` option's ``accept_invalid_certs``.
+    """
+
+    async def fetch(self, url: str, binary: bool = False, **options: Any) -> HttpResponse:
+        """
+        Make a request and read the whole body.
+
+        :param url: The URL to request.
+        :param binary: Return the body as ``bytes`` instead of text.
+        :return: The response; bodies over ``max_response_bytes`` raise ``ApiError``.
+        """
+        request = _request(url, binary=binary, **options)
+        wait = request.get("timeout_ms", 30000) / 1000 + 5
+        response = await eventloop_event_register_typed(
+            "http_fetch", [request], result_type=HttpResponse.model_validate, timeout=wait
+        )
+        if binary and response.body is not None:
+            response.body = base64.b64decode(response.body)
+        return response
+
+    async def stream(self, url: str, **options: Any) -> AsyncIterator[Union[HttpResponse, bytes]]:
+        """
+        Make a request and receive the body in pieces, for large downloads.
+
+        Yields the :class:`HttpResponse` (without ``body``) first, then the
+        body as ``bytes`` chunks.
+        """
+        stream_id = None
+        async for event, data in call_with_events("http_fetch", [_request(url, binary=True, stream=True, **options)]):
+            if event == "__result":
+                response = HttpResponse.model_validate(data)
+                stream_id = response.stream
+                yield response
+            elif data.get("stream") != stream_id or stream_id is None:
+                continue
+            elif event == "http-chunk":
+                yield base64.b64decode(data["data"])
+            elif event == "http-end":
+                if data.get("error"):
+                    raise ApiError(-1, data["error"])
+                return
+
+
+def _request(url: str, body: Optional[Union[str, bytes]] = None, timeout: Optional[float] = None,
+             headers: Optional[Dict[str, str]] = None, **options: Any) -> Dict[str, Any]:
+    request: Dict[str, Any] = {"url": url, **options}
+    if headers:
+        request["headers"] = headers
+    if isinstance(body, bytes):
+        request["body"] = base64.b64encode(body).decode("ascii")
+        request["body_binary"] = True
+    elif body is not None:
+        request["body"] = body
+    if timeout is not None:
+        request["timeout_ms"] = int(timeout * 1000)
+    return request
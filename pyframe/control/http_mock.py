@@ -0,0 +1,53 @@
+from typing import Dict, Optional, Union
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class HttpMock:
+    """
+    Canned responses for ``fetch`` calls made by the page, for integration tests.
+
+    Only available when the webframe was launched with ``enable_mocking``.
+    Requests no mock matches go to the network as usual; navigations and
+    ``XMLHttpRequest`` are not mocked.
+    """
+
+    async def mock_http_request(
+        self,
+        url_pattern: str,
+        status: int = 200,
+        headers: Optional[Dict[str, str]] = None,
+        body: Union[bytes, str] = b"",
+    ) -> int:
+        """
+        Answer requests whose absolute URL matches ``url_pattern``.
+
+        The most recently added matching mock wins.
+
+        :param url_pattern: Regular expression matched against the request URL.
+        :param status: HTTP status of the response.
+        :param headers: Response headers, e.g. ``{"Content-Type": "application/json"}``.
+        :param body: Response body; text is sent as UTF-8.
+        :return: The mock id for :meth:`clear_mock`.
+        """
+        if isinstance(body, str):
+            body = body.encode()
+        return await eventloop_event_register_typed(
+            "mock_http_request", [url_pattern, status, headers or {}, list(body)], result_type=int
+        )
+
+    async def clear_mock(self, handle_id: int) -> bool:
+        """
+        Remove a mock.
+
+        :param handle_id: Id returned by :meth:`mock_http_request`.
+        :return: ``False`` if no such mock existed.
+        """
+        return await eventloop_event_register_typed("clear_mock", [handle_id], result_type=bool)
+
+    async def clear_all_mocks(self) -> bool:
+        """
+        Remove every mock.
+
+        :return: ``True``.
+        """
+        return await eventloop_event_register_typed("clear_all_mocks", result_type=bool)
@@ -0,0 +1,57 @@
+from typing import Any, Dict, List, Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Menu:
+    """
+    The native menu bar: the window's on Windows and Linux, the app's on macOS.
+
+    Items are defined like :meth:`pyframe.Tray.set_menu` items, plus an
+    optional ``accelerator`` such as ``"CmdOrCtrl+S"``; the top-level items
+    are the ``"submenu"`` entries of the bar. Choosing one dispatches
+    ``pyframe:menu`` (``{"id", "kind", "checked", "group"}``) to the page.
+    Unknown ids and accelerators that don't parse raise :class:`ApiError`
+    with the ``ApiError.BAD_ARGS`` code.
+
+    Accelerators keep working while the page has focus: the page forwards
+    matching keys that the native menu never sees, without taking plain
+    typing away from text fields.
+    """
+
+    async def set_menu(self, items: List[Dict[str, Any]]) -> bool:
+        """
+        Replace the menu bar. Passing the same items with other labels,
+        states or accelerators updates it in place.
+        """
+        return await eventloop_event_register_typed("set_menu", [items], result_type=bool)
+
+    async def update_item(
+        self,
+        id: str,
+        label: Optional[str] = None,
+        enabled: Optional[bool] = None,
+        checked: Optional[bool] = None,
+    ) -> bool:
+        """
+        Change one item in place; arguments left at ``None`` stay as they are.
+        Checking a radio item unchecks the rest of its group.
+        """
+        patch = {"label": label, "enabled": enabled, "checked": checked}
+        return await eventloop_event_register_typed("update_menu_item", [id, patch], result_type=bool)
+
+    async def set_accelerator(self, id: str, accelerator: Optional[str]) -> bool:
+        """Rebind an item's shortcut, or remove it with ``None``."""
+        return await eventloop_event_register_typed("set_menu_accelerator", [id, accelerator], result_type=bool)
+
+    async def get_state(self) -> Optional[List[Dict[str, Any]]]:
+        """The items of :meth:`set_menu` with every change applied, or ``None``."""
+        return await eventloop_event_register_typed("get_menu_state", result_type=Optional[List[Dict[str, Any]]])
+
+    async def get_accelerator_log(self) -> Dict[str, Any]:
+        """
+        How accelerators reached the app, for when one doesn't fire:
+        ``registered`` combos, whether a ``native_hook`` exists here, and
+        the latest ``deliveries`` with their ``path`` (``"native"`` or
+        ``"script"``) and whether they were ``dropped`` as duplicates.
+        """
+        return await eventloop_event_register_typed("get_accelerator_log", result_type=Dict[str, Any])
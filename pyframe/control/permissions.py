@@ -0,0 +1,34 @@
+from typing import Any, Dict
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Permissions:
+    """
+    Answer the page's camera, microphone and location requests.
+
+    Requests are decided by the ``permissions`` option of :func:`pyframe.launch`.
+    Under ``"ask"`` each one arrives as a ``permission-requested`` event
+    (``{"id", "kind", "origin", "audio", "video"}``) from :func:`pyframe.events`
+    and is denied unless :meth:`grant` answers it within ``timeout_ms``. Only
+    WebKitGTK hands requests to the app; elsewhere the WebView's own prompt
+    is shown, except for the location under ``mock_geolocation``.
+    """
+
+    async def grant(self, request_id: int, allow: bool, remember: bool = False) -> bool:
+        """
+        Allow or deny a pending request.
+
+        :param remember: Keep the answer for the request's origin in the
+            :class:`Store`, so later requests from it are decided right away.
+        :return: ``True`` once answered.
+        :raises Exception: If the request is no longer pending.
+        """
+        return await eventloop_event_register_typed("grant_permission", [request_id, allow, remember], result_type=bool)
+
+    async def policy(self) -> Dict[str, Any]:
+        """
+        Get the configured policies with ``timeout_ms``, whether requests are
+        ``routed`` to the app on this platform, the ``pending`` requests and
+        the ``remembered`` answers by kind and origin.
+        """
+        return await eventloop_event_register_typed("get_permission_policy", result_type=dict)
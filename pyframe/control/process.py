@@ -0,0 +1,49 @@
+from typing import Dict, List, Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Process:
+    """
+    Run long-lived programs from ``allowed_commands`` with streamed output.
+
+    Output arrives line by line as ``process-output`` events
+    (``{"pid", "stream", "line"}``), followed by one ``process-exit`` event
+    (``{"pid", "code"}``, ``code`` is ``None`` after a signal). They are
+    dispatched to the page as ``pyframe:process-output`` and
+    ``pyframe:process-exit`` and streamed by :func:`pyframe.events`.
+    Children still running when the app exits are killed.
+    """
+
+    async def spawn(
+        self,
+        command: str,
+        args: Optional[List[str]] = None,
+        cwd: Optional[str] = None,
+        env: Optional[Dict[str, str]] = None,
+        inherit_env: bool = False,
+    ) -> int:
+        """
+        Start a program without waiting for it.
+
+        :param command: Program name or path, exactly as listed in ``allowed_commands``.
+        :param args: Command-line arguments.
+        :param cwd: Working directory of the program.
+        :param env: Environment variables set for the program.
+        :param inherit_env: Start from this app's environment. Otherwise the
+            program only sees ``env``, and a command given by name needs
+            ``PATH`` in it.
+        :return: The process id, as used in the events and :meth:`kill`.
+        """
+        options = {"cwd": cwd, "env": env or {}, "inherit_env": inherit_env}
+        return await eventloop_event_register_typed(
+            "process_spawn", [command, args or [], options], result_type=int
+        )
+
+    async def kill(self, pid: int) -> bool:
+        """
+        Kill a program started with :meth:`spawn`.
+
+        :param pid: Its process id.
+        :return: ``False`` if it had already exited.
+        """
+        return await eventloop_event_register_typed("process_kill", [pid], result_type=bool)
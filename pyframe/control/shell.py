@@ -0,0 +1,80 @@
+from typing import List, Optional
+from pydantic import BaseModel
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class ExecResult(BaseModel):
+    """Output of :meth:`Shell.exec_command`."""
+    stdout: str
+    stderr: str
+    exit_code: int
+    timed_out: bool
+
+
+class OpenResult(BaseModel):
+    """Result of :meth:`Shell.open`."""
+    opened: bool
+    handler: Optional[str] = None
+
+
+class Shell:
+    """Hand files over to the operating system and run external programs."""
+
+    async def open_file_with_default_app(self, path: str) -> bool:
+        """
+        Open a file with the system's default application.
+
+        The file must lie inside one of the ``allowed_read_paths`` passed
+        to :func:`pyframe.launch`. Programs and scripts (``.exe``, ``.bat``,
+        ``.sh``, ``.desktop``, files with the execute bit, ...) are refused;
+        use :meth:`exec_command` for those.
+
+        :param path: Path of the file to open.
+        :return: ``True`` once the application was launched.
+        :raises Exception: If the path is not allowed or no application is
+            associated with the file type.
+        """
+        return await eventloop_event_register_typed("open_file_with_default_app", [path], result_type=bool)
+
+    async def open(self, target: str) -> OpenResult:
+        """
+        Open a URL, file or directory with the operating system's default handler.
+
+        URLs must use a scheme listed in ``open_schemes`` (``https`` and
+        ``mailto`` by default). Files, directories and ``file:`` URLs must lie
+        inside ``fs_scope``; programs and scripts are refused as with
+        :meth:`open_file_with_default_app`. Pages can call this without Python through
+        ``pyframe.invoke("__shell_open", target)``.
+
+        :param target: URL or path, e.g. ``"https://example.com/docs"``.
+        :return: ``opened`` and, on Linux, the handler's desktop entry if known.
+        :raises Exception: If the scheme or path is not allowed or nothing
+            could be launched.
+        """
+        return await eventloop_event_register_typed("shell_open", [target], result_type=OpenResult)
+
+    async def exec_command(
+        self,
+        command: str,
+        args: Optional[List[str]] = None,
+        stdin: Optional[str] = None,
+        timeout_ms: int = 10_000,
+    ) -> ExecResult:
+        """
+        Run a program listed in ``allowed_commands`` and capture its output.
+
+        The program is started directly, without a shell, so ``args`` reach
+        it as they are; ``~``, ``*`` or ``$VAR`` are not expanded.
+
+        :param command: Program name or path, exactly as listed in ``allowed_commands``.
+        :param args: Command-line arguments.
+        :param stdin: Text written to the program's standard input.
+        :param timeout_ms: Kill the program after this many milliseconds.
+        :return: The captured output; ``timed_out`` is set if it was killed.
+        """
+        return await eventloop_event_register_typed(
+            "exec_command",
+            [command, args or [], stdin, timeout_ms],
+            result_type=ExecResult,
+            timeout=timeout_ms / 1000 + 5.0,
+        )
@@ -0,0 +1,66 @@
+from typing import Any, AsyncIterator, Dict, Optional
+from ..runtime_handle import eventloop_event_register_typed, events
+
+
+class State:
+    """
+    Named JSON documents kept in memory and shared with every window's page
+    (``pyframe.state`` / ``window.appStore``).
+
+    Each change gets a revision, higher than that of every earlier change,
+    and is dispatched to the pages and streamed by :func:`pyframe.events` as
+    ``state-changed:<name>`` with ``{"name", "op", "patch", "value",
+    "revision"}``. Concurrent writers don't conflict: the last change applied
+    wins. A document may not grow beyond ``state_max_bytes`` (1 MiB).
+    """
+
+    async def get(self, name: str) -> Optional[Dict[str, Any]]:
+        """
+        Read a document.
+
+        :return: ``{"value", "revision"}``, or ``None`` if it is unset.
+        """
+        return await eventloop_event_register_typed("state_get", [name], result_type=lambda v: v)
+
+    async def set(self, name: str, value: Any) -> int:
+        """
+        Replace a document with a JSON-serializable value.
+
+        :return: The new revision.
+        """
+        return await eventloop_event_register_typed("state_set", [name, value], result_type=int)
+
+    async def update(self, name: str, patch: Any) -> int:
+        """
+        Apply a JSON merge patch (RFC 7396): objects merge key by key,
+        ``None`` removes a key, anything else replaces the value.
+
+        :return: The new revision.
+        """
+        return await eventloop_event_register_typed("state_update", [name, patch], result_type=int)
+
+    async def delete(self, name: str) -> bool:
+        """
+        Remove a document.
+
+        :return: ``False`` if it wasn't set.
+        """
+        return await eventloop_event_register_typed("state_delete", [name], result_type=bool)
+
+    async def watch(self, name: str) -> AsyncIterator[Dict[str, Any]]:
+        """
+        Yield every change of ``name`` as it happens, skipping any older
+        than one already yielded.
+
+        Usage::
+
+            async for change in State().watch("settings"):
+                print(change["revision"], change["value"])
+        """
+        event_name = f"state-changed:{name}"
+        revision = 0
+        async for event, data in events():
+            if event != event_name or data["revision"] <= revision:
+                continue
+            revision = data["revision"]
+            yield data
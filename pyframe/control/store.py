@@ -0,0 +1,49 @@
+from typing import Any, List
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Store:
+    """
+    Persistent key-value store for settings and other small data.
+
+    Kept as JSON in the platform's config directory (under ``app_id``) and
+    shared by every window. Unlike ``localStorage`` it survives
+    ``clear_browsing_data``. Each change is dispatched to the page as a
+    ``pyframe:store-changed`` event (``{"key", "value"}``, ``key`` is
+    ``None`` after :meth:`clear`) and streamed by :func:`pyframe.events`.
+    """
+
+    async def get(self, key: str) -> Any:
+        """
+        Read a value.
+
+        :return: The stored JSON value, or ``None`` if the key is unset.
+        """
+        return await eventloop_event_register_typed("store_get", [key], result_type=lambda v: v)
+
+    async def set(self, key: str, value: Any) -> bool:
+        """
+        Store a JSON-serializable value. Writes are batched and land on disk
+        shortly after, or when the app exits.
+        """
+        return await eventloop_event_register_typed("store_set", [key, value], result_type=bool)
+
+    async def delete(self, key: str) -> bool:
+        """
+        Remove a key.
+
+        :return: ``False`` if it wasn't set.
+        """
+        return await eventloop_event_register_typed("store_delete", [key], result_type=bool)
+
+    async def keys(self) -> List[str]:
+        """List the stored keys, sorted."""
+        return await eventloop_event_register_typed("store_keys", result_type=list)
+
+    async def clear(self) -> bool:
+        """
+        Remove every key.
+
+        :return: ``False`` if the store was already empty.
+        """
+        return await eventloop_event_register_typed("store_clear", result_type=bool)
@@ -0,0 +1,43 @@
+from typing import Any, List, Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Timers:
+    """
+    Native timers run by the Rust event loop.
+
+    Timers survive WebView reloads. When one fires, the frontend receives a
+    ``pyframe:timer`` event with ``{"id": ...}`` and, if ``api`` was given,
+    that API is called with ``args``.
+    """
+
+    async def set_timeout(self, ms: int, api: Optional[str] = None, args: Optional[List[Any]] = None) -> int:
+        """
+        Fire once after ``ms`` milliseconds.
+
+        :param ms: Delay in milliseconds.
+        :param api: Optional name of a native API to call when the timer fires.
+        :param args: Arguments for ``api``.
+        :return: The timer id.
+        """
+        return await eventloop_event_register_typed("set_timeout", [ms, api, args], result_type=int)
+
+    async def set_interval(self, ms: int, api: Optional[str] = None, args: Optional[List[Any]] = None) -> int:
+        """
+        Fire every ``ms`` milliseconds until cleared.
+
+        :param ms: Period in milliseconds.
+        :param api: Optional name of a native API to call on every tick.
+        :param args: Arguments for ``api``.
+        :return: The timer id.
+        """
+        return await eventloop_event_register_typed("set_interval", [ms, api, args], result_type=int)
+
+    async def clear_timer(self, timer_id: int) -> bool:
+        """
+        Cancel a timeout or interval.
+
+        :param timer_id: Id returned by :meth:`set_timeout` or :meth:`set_interval`.
+        :return: ``False`` if the timer already fired or was cleared.
+        """
+        return await eventloop_event_register_typed("clear_timer", [timer_id], result_type=bool)
@@ -0,0 +1,42 @@
+from typing import Any, Dict, List, Optional
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class Tray:
+    """
+    The tray icon's menu.
+
+    Items are dicts of ``id``, ``label``, ``kind`` (``"normal"``,
+    ``"checkbox"``, ``"radio"``, ``"separator"`` or ``"submenu"``),
+    ``enabled`` (default ``True``), ``checked``, ``group`` for radio items and
+    ``items`` for submenus. Choosing one dispatches ``pyframe:tray-menu``
+    (``{"id", "kind", "checked", "group"}``) to the page and streams it by
+    :func:`pyframe.events`; checkbox and radio items have their check mark
+    updated by then. The ids ``pyframe.tray.show`` and ``pyframe.tray.quit``
+    keep their default actions.
+    """
+
+    async def set_menu(self, items: List[Dict[str, Any]]) -> bool:
+        """
+        Replace the menu, showing the tray icon if it isn't yet. Passing the
+        same items with other labels or states updates the menu in place.
+        """
+        return await eventloop_event_register_typed("set_tray_menu", [items], result_type=bool)
+
+    async def update_item(
+        self,
+        id: str,
+        label: Optional[str] = None,
+        enabled: Optional[bool] = None,
+        checked: Optional[bool] = None,
+    ) -> bool:
+        """
+        Change one item in place; arguments left at ``None`` stay as they are.
+        Checking a radio item unchecks the rest of its group.
+        """
+        update = {"label": label, "enabled": enabled, "checked": checked}
+        return await eventloop_event_register_typed("update_tray_item", [id, update], result_type=bool)
+
+    async def get_menu(self) -> Optional[List[Dict[str, Any]]]:
+        """The items of :meth:`set_menu` with their current state, or ``None``."""
+        return await eventloop_event_register_typed("get_tray_menu", result_type=Optional[List[Dict[str, Any]]])
@@ -0,0 +1,52 @@
+from typing import Optional
+from pydantic import BaseModel
+from ..runtime_handle import eventloop_event_register_typed
+
+
+class UpdateCheck(BaseModel):
+    """Result of ``Updater.check``."""
+    available: bool
+    current_version: str
+    version: str
+    notes: Optional[str] = None
+    pub_date: Optional[str] = None
+
+
+class Downloaded(BaseModel):
+    """An update artifact whose checksum has been verified."""
+    version: str
+    path: str
+
+
+class Updater:
+    """
+    Updates from the manifest set in the ``updater`` option.
+
+    Every step raises ``ApiError`` with ``ApiError.OFFLINE`` if the server
+    can't be reached and ``ApiError.INVALID_SIGNATURE`` if the manifest or
+    the download doesn't match the configured key. Download progress
+    arrives as ``update-progress`` events (``{"downloaded", "total"}``).
+    """
+
+    async def check(self) -> UpdateCheck:
+        """Fetch the manifest and compare its version with the running one."""
+        return await eventloop_event_register_typed(
+            "updater_check", result_type=UpdateCheck.model_validate, timeout=60.0
+        )
+
+    async def download(self, timeout: float = 3600.0) -> Downloaded:
+        """Download this platform's artifact of the update ``check`` found."""
+        return await eventloop_event_register_typed(
+            "updater_download", result_type=Downloaded.model_validate, timeout=timeout
+        )
+
+    async def install_and_exit(self) -> bool:
+        """
+        Start the installer for the downloaded update and exit the app. An
+        AppImage is replaced and started again; other installers (``.msi``,
+        ``.exe``, ``.pkg``, ``.dmg``, ``.deb``, ``.rpm``) run on their own and
+        may not start the new version.
+        """
+        return await eventloop_event_register_typed(
+            "updater_install_and_exit", result_type=bool, timeout=300.0
+        )
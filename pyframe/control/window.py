@@ -1,4 +1,5 @@
-from typing import Any, Dict, Optional, Tuple
+import json
+from typing import Any, Dict, List, Optional, Tuple
 from ..runtime_handle import eventloop_event_register_typed
 
 
@@ -11,9 +12,19 @@ class Window:
     Rust runtime and returns the result.
     """
 
-    def __init__(self):
-        """Initialize a new window reference with default label ``"root"``."""
+    def __init__(self, window_id: Optional[int] = None):
+        """
+        Initialize a new window reference with default label ``"root"``.
+
+        :param window_id: The ``id`` from :meth:`get_window_state` of the window
+            native calls target; the first window if ``None``.
+        """
         self.label: str = "root"
+        self.window_id: Optional[int] = window_id
+
+    def _metadata(self) -> Optional[Dict[str, Any]]:
+        """Request metadata targeting this window, if it names one."""
+        return None if self.window_id is None else {"window_id": self.window_id}
 
     async def window_query(self, label: str) -> "Window":
         """
@@ -79,17 +90,22 @@ class Window:
 
     async def set_title(self, title: str) -> bool:
         """
-        Set the window title.
+        Set the window title. Under ``sync_title``, ``document.title`` is
+        ignored until the page navigates.
 
         :param title: New window title.
         :return: ``True`` if the operation succeeded.
         """
-        return await eventloop_event_register_typed("set_title", title, result_type=bool)
+        return await eventloop_event_register_typed("set_title", title, result_type=bool, metadata=self._metadata())
 
     async def set_fullscreen(self, fullscreen: bool) -> bool:
         """
         Enable or disable fullscreen mode.
 
+        A page element entering fullscreen, e.g. a ``<video>``, takes the
+        window along and restores it afterwards; each change is emitted as
+        ``element-fullscreen`` with ``active`` and the element's tag name.
+
         :param fullscreen: ``True`` to enter fullscreen, ``False`` to exit.
         :return: ``True`` if the operation succeeded.
         """
@@ -158,3 +174,493 @@ class Window:
     async def set_focus(self) -> bool:
         """Bring the window into focus."""
         return await eventloop_event_register_typed("window.setFocus", {"label": self.label}, result_type=bool)
+
+    async def set_content_blocking_rules(self, rules: List[Dict[str, Any]]) -> bool:
+        """
+        Replace the WebView's content blocking rules.
+
+        :param rules: Rules in WebKit Content Blocker JSON format.
+        :return: ``True`` if the browser engine enforces the rules natively,
+            ``False`` if only top-level navigations are filtered.
+        """
+        return await eventloop_event_register_typed("set_content_blocking_rules", json.dumps(rules), result_type=bool)
+
+    async def content_blocking_rules(self) -> Optional[List[Dict[str, Any]]]:
+        """Get the active content blocking rules, or ``None`` if none were set."""
+        return await eventloop_event_register_typed("get_content_blocking_rules", result_type=lambda r: r)
+
+    async def set_tick_interval(self, ms: Optional[int]) -> bool:
+        """
+        Emit ``pyframe:tick`` events every ``ms`` milliseconds.
+
+        :param ms: Tick interval, or ``None`` to stop ticking and let the
+            event loop sleep until the next event.
+        :return: ``True`` if the interval was applied.
+        """
+        return await eventloop_event_register_typed("set_tick_interval", [ms], result_type=bool)
+
+    async def viewport_size(self) -> Tuple[int, int]:
+        """Get the WebView's viewport size in CSS pixels as ``(width, height)``."""
+        return await eventloop_event_register_typed("get_viewport_size", [self.window_id], result_type=lambda r: tuple(r))
+
+    async def set_content_protection(self, enabled: bool) -> bool:
+        """
+        Hide the window contents from screenshots and screen recordings.
+
+        Supported on Windows and macOS.
+
+        :param enabled: ``True`` to enable protection.
+        :return: ``False`` on platforms without support.
+        """
+        return await eventloop_event_register_typed("set_content_protection", [enabled], result_type=bool, metadata=self._metadata())
+
+    async def run_on_main_js(self, script: str) -> Any:
+        """
+        Evaluate a JavaScript expression on the native event loop thread.
+
+        :param script: Expression to evaluate.
+        :return: The JSON-serializable result of the expression.
+        :raises Exception: If the script throws.
+        """
+        return await eventloop_event_register_typed("run_on_main_js", [script], result_type=lambda r: r)
+
+    async def state(self) -> Dict[str, Any]:
+        """
+        Get the window's focus and visibility state.
+
+        :return: A dict with ``label``, ``id``, ``focused``, ``visible``, ``minimized``,
+            ``maximized``, ``fullscreen``, ``occluded`` and ``monitor`` (the name of
+            the monitor the window is on).
+        """
+        return await eventloop_event_register_typed("get_window_state", result_type=dict, metadata=self._metadata())
+
+    async def get_navigation_state(self) -> Dict[str, Any]:
+        """
+        Get where the page's current load is.
+
+        Each step is also emitted: ``load-started``, ``dom-content-loaded``,
+        ``load-finished`` (with ``success``) and, on WebKitGTK, ``load-progress``,
+        all with ``label`` and ``url``. ``pushState``, ``replaceState`` and
+        ``popstate`` come as ``load-started`` and ``load-finished`` with
+        ``same_document`` set and a ``navigation_type``.
+
+        :return: A dict with ``label``, ``url``, ``loading``, ``dom_content_loaded``,
+            ``progress`` (0 to 1, or ``None``) and ``success`` of the last load.
+        """
+        return await eventloop_event_register_typed("get_navigation_state", result_type=dict)
+
+    async def set_webview_bounds(self, x: float, y: float, width: float, height: float) -> bool:
+        """
+        Move and resize the frame embedded with the ``parent_handle`` option.
+
+        Call it whenever the host widget moves or resizes, e.g. from a Qt
+        ``resizeEvent``. In logical pixels: relative to the parent's client
+        area on Windows, in screen coordinates on macOS (``widget.mapToGlobal``).
+
+        :return: ``True`` once applied.
+        :raises ApiError: Without ``parent_handle``.
+        """
+        bounds = {"x": x, "y": y, "width": width, "height": height}
+        return await eventloop_event_register_typed("set_webview_bounds", [bounds], result_type=bool)
+
+    async def native_handle(self) -> Dict[str, Any]:
+        """
+        Get the window's platform handle, for native renderers drawing into it.
+
+        The numbers are only valid until the window closes. A renderer on
+        another thread must be stopped before then, e.g. on ``window-closed``
+        or in a :meth:`confirm_close` handler::
+
+            handle = await window.native_handle()
+            player = mpv.MPV(wid=str(handle["window"]))  # win32, appkit, xlib
+            ...
+            player.terminate()  # before the window closes
+
+        :return: A dict with ``kind`` (``"win32"``, ``"appkit"``, ``"xlib"``,
+            ``"xcb"`` or ``"wayland"``), ``window`` (the ``HWND``, ``NSView*``,
+            X window id or ``wl_surface*``) and ``display`` (the ``HINSTANCE``,
+            ``Display*``, ``xcb_connection_t*`` or ``wl_display*``, or ``None``).
+        :raises ApiError: ``UNSUPPORTED`` on other platforms.
+        """
+        return await eventloop_event_register_typed("native_handle", result_type=dict, metadata=self._metadata())
+
+    async def find_in_page(
+        self,
+        text: str,
+        case_sensitive: bool = False,
+        backwards: bool = False,
+        wrap: bool = True,
+    ) -> str:
+        """
+        Search the page for ``text`` and highlight the matches, like Ctrl+F.
+
+        Results arrive as ``find-result`` events, on the page and from
+        ``pyframe.events()``, each time the count or the active match changes:
+        ``{window, query, matches, active, backend}``, with the zero-based
+        ``active`` index, either ``None`` until known. A new search replaces
+        the window's last one.
+
+        :param backwards: Start at the last match.
+        :param wrap: Continue at the other end after the last match.
+        :return: ``"native"`` where the WebView searches itself, cross-origin
+            iframes included (WebKitGTK), else ``"script"``, which only
+            reaches same-origin iframes.
+        """
+        options = {"case_sensitive": case_sensitive, "backwards": backwards, "wrap": wrap}
+        return await eventloop_event_register_typed("find_in_page", [text, options], result_type=str, metadata=self._metadata())
+
+    async def find_next(self, backwards: bool = False) -> bool:
+        """
+        Highlight the next match of :meth:`find_in_page`, or the previous one.
+
+        :return: ``False`` if there is no search.
+        """
+        return await eventloop_event_register_typed("find_next", [backwards], result_type=bool, metadata=self._metadata())
+
+    async def stop_find(self) -> bool:
+        """
+        End the search and remove its highlights.
+
+        :return: ``False`` if there was no search.
+        """
+        return await eventloop_event_register_typed("stop_find", result_type=bool, metadata=self._metadata())
+
+    async def navigate(self, url: str) -> bool:
+        """
+        Load ``url`` in the window, e.g. from a ``load-failed`` handler.
+
+        :return: ``True`` once the load started.
+        """
+        return await eventloop_event_register_typed("navigate", [url], result_type=bool, metadata=self._metadata())
+
+    async def set_context_menu_items(self, items: List[Dict[str, Any]]) -> bool:
+        """
+        Show native items on right-click under ``context_menu="custom"``,
+        alongside the ``context-menu`` event. Choosing one emits ``menu``
+        with its ``id``.
+
+        :param items: ``{"id", "label", "enabled"}`` dicts, or
+            ``{"separator": True}``; ``[]`` removes them.
+        :return: ``False`` if they won't be shown, i.e. on other backends
+            than WebKitGTK or without ``context_menu="custom"``.
+        """
+        return await eventloop_event_register_typed("set_context_menu_items", [items], result_type=bool)
+
+    async def confirm_close(self, allow: bool) -> bool:
+        """
+        Answer a pending ``close-requested`` event.
+
+        Only used when the webframe was launched with ``confirm_close``; the
+        window closes by itself if no answer arrives within ``close_timeout_ms``.
+
+        :param allow: ``True`` to close the window, ``False`` to keep it open.
+        :return: ``False`` if no close request was pending.
+        """
+        return await eventloop_event_register_typed("confirm_close", [allow], result_type=bool)
+
+    async def set_visible_on_all_spaces(self, enabled: bool) -> bool:
+        """
+        Show the window on every virtual desktop (macOS Spaces, Linux workspaces).
+
+        On Windows the window is kept on top instead, as there is no public
+        API for pinning to all virtual desktops.
+
+        :param enabled: ``True`` to show the window everywhere.
+        :return: ``False`` on platforms without support.
+        """
+        return await eventloop_event_register_typed("set_visible_on_all_spaces", [enabled], result_type=bool, metadata=self._metadata())
+
+    async def set_minimize_to_tray(self, enabled: bool) -> bool:
+        """
+        Hide the window into a tray icon instead of minimizing or closing it.
+
+        The tray menu offers "Show" and "Quit" unless :meth:`pyframe.Tray.set_menu`
+        replaced it; clicking the icon restores the window. Disabling shows a
+        hidden window again and removes the icon, unless it has such a menu.
+
+        :param enabled: ``True`` to enable minimize-to-tray.
+        :return: ``True`` if the setting was applied.
+        """
+        return await eventloop_event_register_typed("set_minimize_to_tray", [enabled], result_type=bool)
+
+    async def set_tabbing_identifier(self, identifier: str) -> bool:
+        """
+        Put the window into a macOS native tab group.
+
+        Windows sharing an identifier can be merged into one tabbed window.
+
+        :param identifier: Name of the tab group.
+        :return: ``False`` on platforms other than macOS.
+        """
+        return await eventloop_event_register_typed("set_tabbing_identifier", [identifier], result_type=bool, metadata=self._metadata())
+
+    async def add_tab_window(self, window_id: int) -> bool:
+        """
+        Add another window as a tab of this one (macOS only).
+
+        :param window_id: The ``id`` from that window's :meth:`state`.
+        :return: ``False`` on platforms other than macOS.
+        """
+        return await eventloop_event_register_typed("add_tab_window", [window_id], result_type=bool, metadata=self._metadata())
+
+    async def set_vibrancy(self, material: str) -> bool:
+        """
+        Give the window a frosted-glass background (macOS, Windows 11).
+
+        Only visible when the webframe was launched with ``transparent`` and
+        the page itself has a transparent background.
+
+        :param material: ``"ultra_thin"``, ``"thin"``, ``"medium"``, ``"thick"`` or ``"none"``.
+        :return: ``False`` on platforms without support.
+        """
+        return await eventloop_event_register_typed("set_vibrancy", [material], result_type=bool, metadata=self._metadata())
+
+    async def set_corner_radius(self, radius: float, shadow: Optional[bool] = None) -> str:
+        """
+        Round the corners of a frameless window, kept up on resize.
+
+        Windows 11 only offers 4 and 8 pixel corners; Windows 10 and X11 cut
+        the window to shape, without a system shadow; macOS masks it.
+
+        :param radius: In logical pixels; ``0`` for square corners.
+        :param shadow: Keep or drop the system drop shadow; unchanged if ``None``.
+        :return: ``"dwm"``, ``"region"``, ``"layer"`` or ``"shape"``, how it was done.
+        :raises UnsupportedError: On Wayland, or for a shadow the platform can't keep.
+        """
+        return await eventloop_event_register_typed(
+            "set_corner_radius", [radius, shadow], result_type=str, metadata=self._metadata()
+        )
+
+    async def set_titlebar(
+        self,
+        titlebar_transparent: Optional[bool] = None,
+        title_hidden: Optional[bool] = None,
+        fullsize_content_view: Optional[bool] = None,
+        traffic_light_offset: Optional[Tuple[float, float]] = None,
+    ) -> bool:
+        """
+        Change the macOS title bar; arguments left at ``None`` stay as they are.
+
+        :param traffic_light_offset: ``(x, y)`` of the traffic lights in logical
+            pixels from the window's top-left corner.
+        :return: ``False`` on other platforms, which have no such title bar.
+        """
+        options: Dict[str, Any] = {
+            "titlebar_transparent": titlebar_transparent,
+            "title_hidden": title_hidden,
+            "fullsize_content_view": fullsize_content_view,
+        }
+        if traffic_light_offset is not None:
+            options["traffic_light_offset"] = {"x": traffic_light_offset[0], "y": traffic_light_offset[1]}
+        return await eventloop_event_register_typed("set_titlebar", [options], result_type=bool, metadata=self._metadata())
+
+    async def get_titlebar_overlay(self) -> Optional[Dict[str, Any]]:
+        """
+        Where the native caption buttons are under ``titlebar_overlay``:
+        ``{"height", "buttons": {"x", "y", "width", "height"}, "maximized"}`` in
+        logical pixels of the content area, or ``None`` while it is off.
+        """
+        return await eventloop_event_register_typed("get_titlebar_overlay", result_type=Optional[Dict[str, Any]], metadata=self._metadata())
+
+    async def get_creation_warnings(self) -> List[str]:
+        """Creation options that were accepted but don't apply on this platform."""
+        return await eventloop_event_register_typed("get_creation_warnings", result_type=list)
+
+    async def reset_window_state(self) -> bool:
+        """
+        Delete the saved window size and position.
+
+        The next launch opens with the default geometry; changes made after
+        the reset are saved again.
+
+        :return: ``False`` if the webframe wasn't launched with ``persist_window_state``.
+        """
+        return await eventloop_event_register_typed("reset_window_state", result_type=bool)
+
+    async def list_monitors(self) -> List[Dict[str, Any]]:
+        """
+        List the connected monitors.
+
+        The ``index`` of each entry is what the ``monitor`` creation option
+        refers to; positions and sizes are in physical pixels.
+
+        :return: Dicts with ``index``, ``name``, ``x``, ``y``, ``width``,
+            ``height``, ``scale_factor`` and ``primary``.
+        """
+        return await eventloop_event_register_typed("list_monitors", result_type=list)
+
+    async def capture_window(self, format: str = "png", path: Optional[str] = None) -> Dict[str, Any]:
+        """
+        Take a screenshot of the whole window, title bar and frame included.
+
+        The image is in physical pixels of the monitor the window is on. On
+        macOS the first call asks for the screen recording permission and
+        raises ``ApiError`` with code ``ApiError.PERMISSION_DENIED`` until it
+        is granted.
+
+        :param format: ``"png"`` or ``"jpeg"``.
+        :param path: File to write the image to; if omitted, it is returned.
+        :return: A dict with ``width``, ``height``, ``format`` and either
+            ``path`` or ``data`` (the base64-encoded image).
+        """
+        return await eventloop_event_register_typed("capture_window", [format, path], result_type=dict, metadata=self._metadata())
+
+    async def set_taskbar_progress(self, progress: float, state: str = "normal") -> bool:
+        """
+        Show a progress bar on the window's taskbar button (Windows) or Dock icon (macOS).
+
+        :param progress: Progress from ``0.0`` to ``1.0``.
+        :param state: ``"none"`` (hide the bar), ``"normal"``, ``"paused"``,
+            ``"error"`` or ``"indeterminate"``.
+        :return: ``False`` on platforms without support.
+        """
+        return await eventloop_event_register_typed("set_taskbar_progress", [progress, state], result_type=bool, metadata=self._metadata())
+
+    async def extract_text(self, selector: Optional[str] = None) -> str:
+        """
+        Get the visible text of the page or of one element.
+
+        :param selector: CSS selector of the element; the whole body if omitted.
+        :return: The element's ``innerText``.
+        :raises ApiError: If no element matches or the script throws.
+        """
+        return await eventloop_event_register_typed("extract_text", [selector], result_type=str)
+
+    async def extract_html(self, selector: Optional[str] = None) -> str:
+        """
+        Get the markup of the page or of one element.
+
+        :param selector: CSS selector of the element; the whole document if omitted.
+        :return: The element's ``outerHTML``.
+        :raises ApiError: If no element matches or the script throws.
+        """
+        return await eventloop_event_register_typed("extract_html", [selector], result_type=str)
+
+    async def set_effect(self, kind: str) -> bool:
+        """
+        Apply a native background effect behind the page.
+
+        Needs the ``transparent`` option and a page with a transparent
+        background. ``"none"`` removes the effect and restores a solid background.
+
+        :param kind: ``"mica"``, ``"acrylic"`` or ``"blur"`` on Windows; an
+            ``NSVisualEffectView`` material such as ``"sidebar"``, ``"popover"``
+            or ``"hud_window"`` on macOS; or ``"none"``.
+        :return: ``True`` once applied.
+        :raises ApiError: With code ``ApiError.UNSUPPORTED`` if the platform can't render ``kind``.
+        """
+        return await eventloop_event_register_typed("set_effect", [kind], result_type=bool, metadata=self._metadata())
+
+    async def set_cursor_position(self, x: float, y: float, clamp: bool = False) -> Dict[str, float]:
+        """
+        Move the cursor within the window's content area.
+
+        For pointer lock, e.g. in a drawing canvas: call :meth:`set_cursor_grab`
+        (except on Linux) and ``set_cursor_visible(False)`` once, then on every ``mousemove`` that
+        nears the edge, warp back to the centre and use the movement since the
+        last event, not the absolute position::
+
+            await window.set_cursor_grab(True)
+            await window.set_cursor_visible(False)
+            await window.set_cursor_position(canvas_x + w / 2, canvas_y + h / 2)
+
+        :param x: Logical pixels from the left of the content area.
+        :param y: Logical pixels from the top of the content area.
+        :param clamp: Move to the nearest edge instead of failing for
+            positions outside the window.
+        :return: ``{"x", "y"}`` where the cursor went.
+        :raises UnsupportedError: On Wayland, which doesn't let apps move the cursor.
+        """
+        return await eventloop_event_register_typed(
+            "set_cursor_position", [x, y, clamp], result_type=dict, metadata=self._metadata()
+        )
+
+    async def set_cursor_grab(self, grab: bool) -> bool:
+        """
+        Confine the cursor to the window, or release it.
+
+        :raises UnsupportedError: On Linux, where it can't be confined.
+        """
+        return await eventloop_event_register_typed("set_cursor_grab", [grab], result_type=bool, metadata=self._metadata())
+
+    async def set_cursor_visible(self, visible: bool) -> bool:
+        """Show or hide the cursor while it is over the window."""
+        return await eventloop_event_register_typed("set_cursor_visible", [visible], result_type=bool, metadata=self._metadata())
+
+    async def set_ignore_cursor_events(self, flag: bool) -> bool:
+        """
+        Let mouse input pass through the window to the applications beneath it.
+
+        Meant for always-on-top, ``transparent`` overlays. Regions set with
+        :meth:`set_interactive_regions` keep receiving input.
+
+        :param flag: ``True`` to make the window click-through, ``False`` to undo it.
+        :return: ``True`` if the setting was applied.
+        """
+        return await eventloop_event_register_typed("set_ignore_cursor_events", [flag], result_type=bool)
+
+    async def set_interactive_regions(self, regions: List[Dict[str, float]]) -> bool:
+        """
+        Define the parts of a click-through window that stay interactive.
+
+        :param regions: Rectangles ``{"x", "y", "width", "height"}`` in logical
+            pixels, relative to the content area. An empty list makes the whole
+            window click-through.
+        :return: ``True`` if the regions were set.
+        """
+        return await eventloop_event_register_typed("set_interactive_regions", [regions], result_type=bool)
+
+    async def exit_kiosk(self, token: str) -> bool:
+        """
+        Leave kiosk mode and restore the window as it was before.
+
+        :param token: The ``exit_token`` of the ``kiosk`` option.
+        :return: ``True`` if kiosk mode was left, ``False`` if it wasn't on.
+        :raises ApiError: With code ``ApiError.PERMISSION_DENIED`` if ``token``
+            doesn't match or no ``exit_token`` is configured.
+        """
+        return await eventloop_event_register_typed("exit_kiosk", [token], result_type=bool)
+
+    async def autofill_form(self, selector_to_value: Dict[str, str]) -> bool:
+        """
+        Fill form fields, e.g. with credentials from a password store.
+
+        Each field gets ``input`` and ``change`` events, as if typed into.
+
+        :param selector_to_value: Values keyed by the CSS selector of their field.
+        :return: ``True`` if every selector matched a field, ``False`` if some
+            matched nothing (the others are still filled).
+        """
+        return await eventloop_event_register_typed("autofill_form", [selector_to_value], result_type=bool)
+
+    async def inject_css(self, id: str, css: str) -> bool:
+        """
+        Add a style sheet to the page, or replace the one injected under ``id``.
+
+        :param id: Stable identifier; injecting again with it updates the sheet.
+        :param css: The CSS source.
+        :return: ``True`` once injected.
+        """
+        return await eventloop_event_register_typed("inject_css", [id, css], result_type=bool)
+
+    async def remove_injected_css(self, id: str) -> bool:
+        """
+        Remove a style sheet added with :meth:`inject_css`.
+
+        :param id: Identifier the sheet was injected under.
+        :return: ``True`` if it was removed, ``False`` if there was none.
+        """
+        return await eventloop_event_register_typed("remove_injected_css", [id], result_type=bool)
+
+    async def start_drag(self, items: Any) -> bool:
+        """
+        Drag files out of the window while the mouse button is held, e.g.
+        from a ``mousedown`` the page forwarded. Pages call
+        ``pyframe.startDrag`` from their ``dragstart`` handler instead, which
+        doesn't wait for the bridge.
+
+        :param items: A path in ``fs_scope`` or a ``{"data", "filename",
+            "base64"}`` dict written to a temporary file, or a list of them.
+        :return: ``True`` once the drag began.
+        """
+        return await eventloop_event_register_typed("start_drag", [items], result_type=bool, metadata=self._metadata())
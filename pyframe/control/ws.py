@@ -0,0 +1,70 @@
+import base64
+from typing import Any, AsyncIterator, List, Optional, Tuple, Union
+from ..runtime_handle import ApiError, call_with_events, eventloop_event_register_typed
+
+
+class WsConnection:
+    """A WebSocket opened by :meth:`WebSocket.connect`; iterate it for messages."""
+
+    def __init__(self, conn_id: int, events: AsyncIterator[Tuple[str, Any]]):
+        self.id = conn_id
+        #: Subprotocol the server picked, once open.
+        self.protocol: Optional[str] = None
+        #: Close code and reason, once closed.
+        self.close_code: Optional[int] = None
+        self.close_reason: str = ""
+        self._events = events
+
+    async def send(self, data: Union[str, bytes]) -> bool:
+        """Send a text message, or a binary one for ``bytes``."""
+        binary = isinstance(data, bytes)
+        payload = base64.b64encode(data).decode("ascii") if binary else data
+        return await eventloop_event_register_typed("ws_send", [self.id, payload, binary], result_type=bool)
+
+    async def close(self, code: Optional[int] = None, reason: Optional[str] = None) -> bool:
+        """Start the closing handshake; iteration ends once it completes."""
+        return await eventloop_event_register_typed("ws_close", [self.id, code, reason], result_type=bool)
+
+    async def __aiter__(self) -> AsyncIterator[Union[str, bytes]]:
+        async for event, data in self._events:
+            if data.get("id") != self.id:
+                continue
+            if event == "ws-open":
+                self.protocol = data.get("protocol")
+            elif event == "ws-message":
+                yield base64.b64decode(data["data"]) if data.get("binary") else data["data"]
+            elif event == "ws-error":
+                raise ApiError(-1, data["message"])
+            elif event == "ws-close":
+                self.close_code = data.get("code")
+                self.close_reason = data.get("reason") or ""
+                return
+
+
+class WebSocket:
+    """
+    WebSockets opened by the native side, for networks where the webview's
+    own ``wss://`` connections fail, e.g. behind TLS interception with a
+    CA only the system trusts.
+
+    Only URLs in the ``ws_allowed_urls`` option can be opened; others
+    raise ``ApiError`` with ``ApiError.PERMISSION_DENIED``.
+
+    Usage::
+
+        conn = await WebSocket().connect("wss://chat.example.com/socket")
+        await conn.send("hello")
+        async for message in conn:
+            ...
+    """
+
+    async def connect(self, url: str, protocols: Optional[List[str]] = None) -> WsConnection:
+        """
+        Open a WebSocket. It lives on its own connection to the event loop
+        and closes when iteration over it stops.
+        """
+        events = call_with_events("ws_connect", [url, protocols or []])
+        async for event, data in events:
+            if event == "__result":
+                return WsConnection(data, events)
+        raise ApiError(-1, "Event connection closed before ws_connect answered")
@@ -0,0 +1,258 @@
+"""
+Hello exchange opening every connection, to the Rust event loop over TCP
+and from the page to the WebSocket server.
+
+The client sends ``{"type": "hello", "version", "features", "token",
+"client"}`` first; the server answers ``{"type": "hello-ack", "version", "features"}``
+with the version and the features both support, or closes the connection
+with one of the ``CLOSE_*`` codes and a reason. Connections that skip the
+hello are served as version 0 for one more release, with a
+``protocol-deprecated`` warning, unless ``PYFRAME_TOKEN`` is set: version 0
+can't present the token, so those are closed with ``CLOSE_INVALID_TOKEN``.
+
+``client`` stays the same across reconnects of one process or page and
+scopes idempotency keys: a request resent with the same
+``idempotency_key`` after a lost connection gets the first response
+instead of running again. See :class:`ReplyCache`.
+"""
+
+import asyncio
+import hmac
+import json
+import os
+import secrets
+import struct
+import time
+import uuid
+from typing import Any, Awaitable, Callable, Dict, Iterable, List, Optional, Tuple
+
+#: The newest protocol version spoken here.
+PROTOCOL_VERSION = 1
+#: The oldest version a hello may negotiate.
+MIN_PROTOCOL_VERSION = 1
+#: Environment variable with the token clients must present.
+TOKEN_ENV = "PYFRAME_TOKEN"
+
+#: Names this process in its hellos to the Rust event loop.
+CLIENT_ID = uuid.uuid4().hex
+
+#: Optional features a hello can ask for.
+FEATURES = ("compression", "msgpack", "binary", "streaming", "batch", "emit", "timing")
+#: Most requests in one batch frame, on both servers.
+MAX_BATCH_SIZE = 32
+#: First byte of a binary frame, ``[marker, JSON length, JSON, attachment]``.
+BINARY_MARKER = 0
+#: Largest attachment of a binary frame, on both servers.
+MAX_ATTACHMENT_BYTES = 64 * 1024 * 1024
+
+#: The first frame was neither a request nor a valid hello.
+CLOSE_BAD_HELLO = 4000
+CLOSE_INVALID_TOKEN = 4001
+CLOSE_UNSUPPORTED_VERSION = 4002
+
+
+class ProtocolError(ConnectionError):
+    """The server refused the hello."""
+
+    def __init__(self, code: int, reason: str):
+        super().__init__(f"[close {code}] {reason}")
+        self.code = code
+        self.reason = reason
+
+
+class RequestTiming:
+    """
+    How long a page call waited and ran, sent as the ``meta`` of its response
+    with the ``timing`` feature, like the ``meta`` of the Rust event loop.
+    Durations come from the monotonic clock, only ``server_ts`` from the wall clock.
+    """
+
+    def __init__(self) -> None:
+        self.received = time.perf_counter_ns()
+        self.started: Optional[int] = None
+        self.finished: Optional[int] = None
+
+    def start(self) -> None:
+        self.started = time.perf_counter_ns()
+
+    def stop(self) -> None:
+        self.finished = time.perf_counter_ns()
+
+    def meta(self) -> Dict[str, int]:
+        """``queued_us``, ``handler_us`` and ``total_us`` until now, and ``server_ts`` in Unix milliseconds."""
+        now = time.perf_counter_ns()
+        started = self.started if self.started is not None else now
+        finished = self.finished if self.finished is not None else started
+        return {
+            "queued_us": (started - self.received) // 1000,
+            "handler_us": (finished - started) // 1000,
+            "total_us": (now - self.received) // 1000,
+            "server_ts": time.time_ns() // 1_000_000,
+        }
+
+
+def ensure_token() -> str:
+    """Create the connection token for this launch, unless one is set."""
+    token = os.environ.get(TOKEN_ENV) or secrets.token_hex(16)
+    os.environ[TOKEN_ENV] = token
+    return token
+
+
+def hello(features: Iterable[str] = ()) -> Dict[str, Any]:
+    """The hello frame asking for ``features``."""
+    return {
+        "type": "hello",
+        "version": PROTOCOL_VERSION,
+        "features": list(features),
+        "token": os.environ.get(TOKEN_ENV),
+        "client": CLIENT_ID,
+    }
+
+
+def negotiate(frame: Any, supported: Iterable[str]) -> Dict[str, Any]:
+    """
+    Answer a client's hello.
+
+    :param frame: The decoded first frame.
+    :param supported: Features this server can provide.
+    :return: The ``hello-ack`` frame.
+    :raises ProtocolError: If the hello is refused.
+    """
+    if not isinstance(frame, dict) or frame.get("type") != "hello" or not isinstance(frame.get("version"), int):
+        raise ProtocolError(CLOSE_BAD_HELLO, "Invalid hello")
+    token = os.environ.get(TOKEN_ENV)
+    given = frame.get("token")
+    if token and not (isinstance(given, str) and hmac.compare_digest(given.encode(), token.encode())):
+        raise ProtocolError(CLOSE_INVALID_TOKEN, "Invalid token")
+    version = min(frame["version"], PROTOCOL_VERSION)
+    if version < MIN_PROTOCOL_VERSION:
+        raise ProtocolError(
+            CLOSE_UNSUPPORTED_VERSION,
+            f"Protocol version {frame['version']} is not supported, "
+            f"the server speaks {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}",
+        )
+    wanted = frame.get("features") or []
+    features: List[str] = [f for f in supported if f in wanted]
+    return {"type": "hello-ack", "version": version, "features": features}
+
+
+def check_legacy() -> None:
+    """
+    Allow a connection without a hello.
+
+    :raises ProtocolError: If a token is required, which version 0 can't present.
+    """
+    if os.environ.get(TOKEN_ENV):
+        raise ProtocolError(CLOSE_INVALID_TOKEN, "A token is required; send a hello first")
+
+
+def is_hello(frame: Any) -> bool:
+    """Whether a first frame attempts a hello, as opposed to a version 0 request."""
+    return isinstance(frame, dict) and frame.get("type") == "hello"
+
+
+async def read_frame(reader: asyncio.StreamReader) -> Any:
+    """Read a 4-byte big-endian length-prefixed JSON frame."""
+    header = await reader.readexactly(4)
+    (length,) = struct.unpack(">I", header)
+    return json.loads((await reader.readexactly(length)).decode("utf-8"))
+
+
+async def write_frame(writer: asyncio.StreamWriter, data: Any, attachment: Optional[bytes] = None) -> None:
+    """
+    Write ``data`` as a length-prefixed JSON frame, or as a binary frame
+    carrying ``attachment`` if there is one (needs the ``binary`` feature).
+    """
+    payload = json.dumps(data).encode("utf-8")
+    if attachment is not None:
+        payload = encode_binary(payload, attachment)
+    writer.write(struct.pack(">I", len(payload)) + payload)
+    await writer.drain()
+
+
+def encode_binary(payload: bytes, attachment: bytes) -> bytes:
+    """The binary frame of the JSON ``payload`` with ``attachment``."""
+    return bytes([BINARY_MARKER]) + struct.pack(">I", len(payload)) + payload + attachment
+
+
+def decode_binary(frame: bytes) -> Tuple[Any, bytes]:
+    """
+    Split a binary frame into its decoded JSON and the attachment.
+
+    :raises ValueError: If the frame is malformed.
+    """
+    if len(frame) < 5 or frame[0] != BINARY_MARKER:
+        raise ValueError("Not a binary frame")
+    (length,) = struct.unpack(">I", frame[1:5])
+    if 5 + length > len(frame):
+        raise ValueError("Binary frame shorter than its JSON length")
+    return json.loads(frame[5 : 5 + length].decode("utf-8")), frame[5 + length :]
+
+
+async def handshake(
+    reader: asyncio.StreamReader,
+    writer: asyncio.StreamWriter,
+    features: Iterable[str] = (),
+) -> Dict[str, Any]:
+    """
+    Send the hello on a fresh TCP connection to the Rust event loop.
+
+    :return: The ``hello-ack`` frame; check ``features`` for what was granted.
+    :raises ProtocolError: If the server closed the connection instead.
+    """
+    await write_frame(writer, hello(features))
+    ack: Optional[Dict[str, Any]] = await read_frame(reader)
+    if not isinstance(ack, dict) or ack.get("type") != "hello-ack":
+        ack = ack if isinstance(ack, dict) else {}
+        raise ProtocolError(ack.get("code", CLOSE_BAD_HELLO), ack.get("reason", "No hello-ack"))
+    return ack
+
+
+class ReplyCache:
+    """
+    Responses of requests with an idempotency key, per client, for the
+    WebSocket server; the Rust side keeps its own.
+
+    The first request with a key runs; a retry from the same client gets
+    its response, waiting for it if it is still running. Reusing a key for
+    another command is an error.
+
+    :param capacity: Most keys remembered; the oldest go first.
+    :param ttl: Seconds a response is replayed.
+    """
+
+    def __init__(self, capacity: int = 256, ttl: float = 60.0):
+        self.capacity = capacity
+        self.ttl = ttl
+        self._entries: Dict[Tuple[str, str], Tuple[float, str, "asyncio.Future[Any]"]] = {}
+
+    async def run(self, client: str, key: str, cmd: str, call: Callable[[], Awaitable[Any]]) -> Any:
+        """Run ``call`` once for ``(client, key)`` and return its result to every retry."""
+        now = time.monotonic()
+        for k, (at, _, future) in list(self._entries.items()):
+            if future.done() and now - at >= self.ttl:
+                del self._entries[k]
+        entry = self._entries.get((client, key))
+        if entry is not None:
+            if entry[1] != cmd:
+                raise ValueError(f"Idempotency key {key!r} was already used for {entry[1]}")
+            return await asyncio.shield(entry[2])
+
+        if len(self._entries) >= self.capacity:
+            done = [k for k, (_, _, future) in self._entries.items() if future.done()]
+            if not done:
+                return await call()
+            del self._entries[min(done, key=lambda k: self._entries[k][0])]
+        future: "asyncio.Future[Any]" = asyncio.get_running_loop().create_future()
+        self._entries[(client, key)] = (now, cmd, future)
+        try:
+            result = await call()
+        except BaseException as e:
+            # Nothing to replay; a retry may run it again.
+            del self._entries[(client, key)]
+            future.set_exception(e)
+            future.exception()
+            raise
+        future.set_result(result)
+        self._entries[(client, key)] = (time.monotonic(), cmd, future)
+        return result
@@ -7,6 +7,8 @@ from typing import (
     Callable,
     Dict,
     List,
+    Optional,
+    Set,
     Union,
     get_args,
     get_origin,
@@ -15,11 +17,22 @@ from typing import (
 
 from pydantic import ValidationError, create_model
 
+from .protocol import RequestTiming
+
 _event_callbacks: Dict[str, List[Callable[..., Union[None, Awaitable[None]]]]] = {}
 _dependency_cache: Dict[type, Any] = {}
-
-
-def command(func_or_event: Union[None, str, Callable] = None):
+#: Commands registered with ``replay=False``.
+no_replay: Set[str] = set()
+#: Warnings of commands registered with ``deprecated=...``.
+deprecations: Dict[str, str] = {}
+
+
+def command(
+    func_or_event: Union[None, str, Callable] = None,
+    *,
+    replay: bool = True,
+    deprecated: Optional[str] = None,
+):
     """
     Register a function as an event command handler.
 
@@ -30,6 +43,10 @@ def command(func_or_event: Union[None, str, Callable] = None):
     :param func_or_event: Either the function to register directly,
         or the event name as a string. If ``None``, the function name
         is used as the event key.
+    :param replay: If ``False``, a retried call with the same idempotency
+        key runs again instead of getting the first response.
+    :param deprecated: Keep serving the command but send this warning with
+        its responses, e.g. ``"use save_document"``; the page logs it once.
     :return: The decorated function.
     """
     if callable(func_or_event):
@@ -41,6 +58,10 @@ def command(func_or_event: Union[None, str, Callable] = None):
     def decorator(func: Callable):
         key = func_or_event or func.__name__
         _event_callbacks.setdefault(key, []).append(func)
+        if not replay:
+            no_replay.add(key)
+        if deprecated is not None:
+            deprecations[key] = deprecated
         return func
 
     return decorator
@@ -96,6 +117,8 @@ async def make_callback(
     result_id: int,
     error_id: int,
     data: dict,
+    attachment: Optional[bytes] = None,
+    timing: Optional[RequestTiming] = None,
 ) -> dict:
     """
     Execute a registered callback for a given event.
@@ -107,6 +130,9 @@ async def make_callback(
     :param result_id: Identifier for the success response.
     :param error_id: Identifier for error responses.
     :param data: Input data to pass to the callback function.
+    :param attachment: Bytes sent along with the call, passed to a
+        parameter annotated ``bytes`` that isn't in ``data``.
+    :param timing: Told when the callback starts and returns.
     :return: A dictionary containing either ``{"result_id": ..., "result": ...}``
         on success or ``{"error_id": ..., "error": ...}`` on failure.
     """
@@ -125,6 +151,11 @@ async def make_callback(
             if name in data:
                 data_fields[name] = (final_type, ...)
                 values[name] = data[name]
+            elif final_type is bytes:
+                if attachment is None:
+                    errors.append(f"{name} needs a binary attachment")
+                else:
+                    values[name] = attachment
             elif param.default is not inspect.Parameter.empty:
                 data_fields[name] = (final_type, param.default)
             elif is_dependency:
@@ -147,10 +178,17 @@ async def make_callback(
             except ValidationError as e:
                 return {"error_id": error_id, "error": f"Pydantic validation failed: {e}"}
 
+        if timing is not None:
+            timing.start()
         try:
             result = await func(**values) if inspect.iscoroutinefunction(func) else func(**values)
-            return {"result_id": result_id, "result": result}
+            response = {"result_id": result_id, "result": result}
         except Exception as e:
-            return {"error_id": error_id, "error": str(e)}
+            response = {"error_id": error_id, "error": str(e)}
+        if timing is not None:
+            timing.stop()
+        if event in deprecations:
+            response["deprecated"] = f"{event} is deprecated: {deprecations[event]}"
+        return response
 
     return {"error_id": error_id, "error": f"No handler registered for event '{event}'"}
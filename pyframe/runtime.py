@@ -1,51 +1,215 @@
 import asyncio
+import json
+import os
+import sys
 from multiprocessing import get_context
 from pathlib import Path
-from typing import Optional, Union
-from pygcc import create_webframe
+from typing import Any, Dict, Optional, Union
+from pygcc import BindError, UnsupportedError, create_webframe
 from .connections import create_websocket_server
+from .protocol import ensure_token
 from .core import install_signal_handlers, shutdown_all_tasks, start_tracked_task
 from .runtime_handle import gui_endless_event_loop_tasks
-from .utils import find_free_ports_and_set_env, load_html
+from .utils import load_html
+
+#: Exit code of the webframe process when an option isn't supported here.
+EXIT_UNSUPPORTED = 3
+#: Exit code of the webframe process when ``RUSTADDR`` couldn't be bound.
+EXIT_BIND = 4
+
+
+def _run_webframe(errors: Any, ready: Any, *args: Any) -> None:
+    """
+    Process target around ``create_webframe``, reporting :class:`UnsupportedError`
+    and :class:`BindError` in ``errors`` and the bound ports in ``ready``.
+    """
+    try:
+        create_webframe(*args, on_ready=ready.update)
+    except UnsupportedError as e:
+        errors.append(str(e))
+        sys.exit(EXIT_UNSUPPORTED)
+    except BindError as e:
+        errors.append(tuple(e.args))
+        sys.exit(EXIT_BIND)
 
 
 async def native_runtime(
     path: Optional[Union[Path, str]],
     host: str = "localhost",
-    port: int = 8080
+    port: int = 8080,
+    options: Optional[Dict[str, Any]] = None,
 ) -> None:
     """
     Launch the native runtime environment with WebSocket server,
     Rust event loop tasks, and a native webframe process.
 
     This function:
-      * Finds and sets free ports in the environment.
       * Installs signal handlers for graceful shutdown.
-      * Starts background tasks for the WebSocket server and GUI event loop.
-      * Spawns a separate process for the webframe.
+      * Starts the WebSocket server and waits until it listens.
+      * Spawns a separate process for the webframe and waits until its TCP bridge listens.
+      * Starts the background task forwarding to the Rust event loop.
       * Waits for the close signal from the webframe and performs cleanup.
 
+    The TCP bridge listens on ``RUSTADDR`` if set, else on a free port; the bound
+    one is put into ``RUSTADDR`` once the webframe is ready. Only the app's own
+    pages get both ports and the launch token, which ``websocket.js`` asks the
+    webframe for and adds to ``window.__PYFRAME__`` (``transport``,
+    ``socket_url``, ``ws_port``, ``tcp_port``); the ``app_metadata`` API reports
+    them with the version, process id and ``app_id``.
+
     :param html: HTML content or file path for the initial webframe view.
     :param host: Host address for the WebSocket server. Defaults to ``"localhost"``.
-    :param port: Port for the WebSocket server. Defaults to ``8080``.
+    :param port: Port for the WebSocket server. Defaults to ``8080``; ``0`` picks a free one.
+    :param options: Webframe creation options, e.g.
+        ``{"control_flow": "poll", "tick_interval_ms": 16, "forward_ticks": True}``.
+        ``control_flow`` is one of ``"wait"`` (default), ``"wait_until"`` or ``"poll"``.
+        ``allowed_read_paths`` lists the directories native file APIs may access.
+        ``fs_scope`` (``{"dirs": [...], "patterns": ["**/*.json"]}``) limits ``Fs``
+        to files inside ``dirs`` whose relative path matches one of ``patterns``, if given.
+        Directories can always be listed and watched, but removing or moving one needs
+        everything in it to match.
+        ``open_schemes`` lists the URL schemes ``Shell.open`` may launch
+        (default ``["https", "mailto"]``); files it opens must be in ``fs_scope``.
+        ``env_access`` (``{"names": [...], "prefixes": ["KIOSK_"], "args": true}``)
+        lists what ``Env`` may read; nothing by default, ``""`` as prefix exposes all.
+        ``http`` (``{"allowed_origins": ["https://api.example.com"], "max_response_bytes": ...}``)
+        lists the origins ``Http.fetch`` may reach, including after redirects; none by default.
+        ``"accept_invalid_certs": True`` there skips TLS certificate validation for them.
+        ``ws_allowed_urls`` lists what ``WebSocket.connect`` may open (``"wss://host"`` allows
+        the whole host, ``"wss://host/path"`` only paths below it).
+        ``state_max_bytes`` caps the serialized size of one ``State`` document (1 MiB by default).
+        ``updater`` (``{"manifest_url": ..., "current_version": "1.0.0", "public_key": <base64>}``)
+        enables ``Updater``; the manifest must be signed with the matching ed25519 key.
+        ``allowed_commands`` lists the programs ``Shell.exec_command`` and ``Process.spawn`` may run.
+        ``confirm_close`` turns the close button into a ``close-requested`` event, sent to the
+        pages and to ``pyframe.events()``, answered with ``Window.confirm_close`` within
+        ``close_timeout_ms`` (default 5000).
+        ``minimize_to_tray`` hides the window into a tray icon (``tray_icon``: image path).
+        ``url`` loads a remote page instead of ``html``.
+        ``local_server`` (``{}`` or ``{"root": "dist"}``) serves ``html``, or the ``root`` directory,
+        from ``http://127.0.0.1:<random port>/`` so the page has a real origin; only the webview
+        holds the cookie it answers to.
+        ``assets`` maps URI schemes to directories served from memory, e.g.
+        ``{"app": "dist"}`` for ``app://localhost/index.html``, with ETags and range requests.
+        ``splash`` (inline HTML or a file path) is shown until the page calls
+        ``pyframe.invoke("__frame_ready")`` or, unless ``splash_wait_for_ready``,
+        finishes loading; at most for ``splash_timeout_ms`` (default 10000).
+        ``transparent`` makes the window and WebView transparent (for ``Window.set_vibrancy``);
+        ``effect`` applies a background effect at creation, as ``Window.set_effect``.
+        ``persist_window_state`` restores the window geometry of the last launch,
+        stored in the config directory of ``app_id`` (default ``"pyframe"``), like ``Store``.
+        ``monitor`` (an index from ``Window.list_monitors`` or a name) picks the monitor
+        the window opens on, falling back to the primary one; ``position_strategy``
+        is ``"center"``, ``"remember"`` (the saved geometry) or ``"cascade"``.
+        ``enable_mocking`` lets ``HttpMock`` answer the page's ``fetch`` calls (tests only).
+        ``kiosk: True`` opens borderless fullscreen and always on top, without devtools,
+        context menu or new windows, with navigation limited to ``allowed_urls`` and left
+        with Ctrl+Alt+K (caught natively, out of the page's reach) or ``Window.exit_kiosk``.
+        ``allowed_urls`` entries match URLs of the same origin whose path starts with the
+        entry's path. A dict overrides single components:
+        ``monitor``, ``fullscreen``, ``always_on_top``, ``decorations``, ``devtools``,
+        ``context_menu``, ``new_windows``, ``allowed_urls``, ``exit_accelerator``
+        (``None`` disables it) and ``exit_token``.
+        ``parent_handle`` embeds the frame into a foreign window, e.g. ``int(widget.winId())``
+        of a PyQt widget: the ``HWND`` on Windows, the ``NSView*`` on macOS. The host then
+        lays it out with ``Window.set_webview_bounds`` whenever the widget resizes.
+        Can't be combined with ``kiosk`` or ``persist_window_state``.
+        ``permissions`` (``{"media": "allow" | "deny" | "ask", "geolocation": ..., "timeout_ms": 30000}``)
+        decides the page's camera, microphone and location requests, by default ``"deny"``;
+        see ``Permissions``. ``mock_geolocation`` feeds ``navigator.geolocation`` from
+        ``Geolocation.set_mock_location`` instead of the platform.
+        ``context_menu`` is ``"default"``, ``"disabled"`` or ``"custom"``, which suppresses
+        the menu and emits ``context-menu`` with the click position, selection and element
+        (tag, link, image, media) for the page to draw its own; see
+        ``Window.set_context_menu_items`` for native items.
+        ``error_page`` is the HTML shown when the page fails to load, with ``{{error}}`` and
+        ``{{url}}`` filled in and elements marked ``data-pyframe-retry`` loading it again;
+        each failure is emitted as ``load-failed``. ``load_retry`` (``{"attempts": 5,
+        "initial_delay_ms": 1000, "max_delay_ms": 30000}``) retries a failing first load
+        with backoff. Both need WebKitGTK; other backends keep their own error page.
+        ``sync_title: True`` keeps the window title in step with ``document.title``, through
+        ``title_format`` such as ``"{title} — MyApp"`` if given; an explicit ``set_title``
+        holds until the page navigates.
+        ``titlebar_transparent``, ``title_hidden``, ``fullsize_content_view`` and
+        ``traffic_light_offset`` (``{"x": 20, "y": 18}`` from the top-left corner) give the
+        inline macOS title bar; other platforms ignore them and list them in
+        ``Window.get_creation_warnings``. ``Window.set_titlebar`` changes them later.
+        ``titlebar_overlay: True`` on Windows keeps the native frame, with snap layouts and
+        the system menu, but lets the page fill the title bar; the caption buttons stay
+        native and their place arrives as ``titlebar-overlay`` events, the
+        ``--pyframe-titlebar-*`` CSS variables and ``Window.get_titlebar_overlay``. Mark the
+        page's title bar with ``data-pyframe-drag-region``.
+        ``activation_policy`` (``"regular"``, ``"accessory"`` or ``"prohibited"``) keeps a macOS
+        app out of the dock and the switcher from the start; ``reopen_on_dock_click: False``
+        leaves a hidden window hidden when the dock icon is clicked.
+        ``log`` configures the Rust internals' diagnostics: ``filter`` (``EnvFilter``
+        directives, ``"pygcc=info"`` by default; ``RUST_LOG`` takes precedence), ``json``
+        for JSON lines, and ``file`` to also write rotating files to the app's log
+        directory, limited by ``max_file_bytes``, ``max_age_days`` and ``max_files``.
+        Change the filter at runtime with ``Diagnostics.set_log_filter``.
+        ``audit`` records calls of privileged APIs as JSON lines in ``path`` (``audit.jsonl`` in
+        the log directory by default): ``methods`` (names, or prefixes ending in ``*``) picks
+        them, and ``redact`` lists argument keys, or ``"method[index]"`` positions, that are
+        replaced before anything is written. ``queue_size``, ``fsync_interval_ms`` and
+        ``recent`` tune the writer and ``Audit.query``.
+        ``transport: "ipc-only"`` opens no socket: no WebSocket server and no TCP bridge are
+        started, the page's ``invoke`` calls the native APIs over ``window.ipc``, and Python
+        reaches them only in the webframe's own process, through ``pygcc.FrameHandle`` from
+        another thread than the one in ``create_webframe``. The page may only call the APIs
+        listed in ``page_apis`` (none by default), and only while it is on the app's own
+        origin (``url``, the local server, an ``assets`` scheme or inline ``html``).
+        The ``get_transport`` API and ``window.pyframe.transport`` report which one is active.
+        Native APIs take their arguments as a list in parameter order or as a dict
+        keyed by parameter name, where a missing optional one is ``None``;
+        ``strict_args: True`` rejects names the API doesn't have instead of ignoring them.
     :return: None
+    :raises UnsupportedError: If an option isn't supported on this platform, e.g.
+        ``parent_handle`` on Linux; open a separate window instead.
+    :raises BindError: If ``port`` or ``RUSTADDR`` is taken; ``args`` are the message
+        and the port.
     """
     html = load_html(path)
-    find_free_ports_and_set_env()
+    os.environ.setdefault("RUSTADDR", "0")
+    ensure_token()
     install_signal_handlers()
 
-    start_tracked_task(create_websocket_server(host, port))
-    start_tracked_task(gui_endless_event_loop_tasks())
+    tcp = (options or {}).get("transport") != "ipc-only"
     loop = asyncio.get_running_loop()
-    tasks = [t for t in asyncio.all_tasks(loop) if t is not asyncio.current_task(loop)]
+    if tcp:
+        ws_ready: "asyncio.Future[int]" = loop.create_future()
+        start_tracked_task(create_websocket_server(host, port, ws_ready))
+        try:
+            port = await ws_ready
+        except BindError:
+            await shutdown_all_tasks()
+            raise
     ctx = get_context("spawn")
     with ctx.Manager() as manager:
         mp_event = manager.Event()
-        p = ctx.Process(target=create_webframe, args=(html, host, port, mp_event,), daemon=False)
+        errors = manager.list()
+        ready = manager.dict()
+        p = ctx.Process(target=_run_webframe, args=(errors, ready, html, host, port, mp_event, json.dumps(options or {}),), daemon=False)
         p.start()
 
+        def _wait_for_ready():
+            while not ready and not mp_event.wait(0.05):
+                if not p.is_alive():
+                    return
+
+        def _wait_for_close():
+            # The process may also die before the window opens.
+            while not mp_event.wait(0.25):
+                if not p.is_alive():
+                    return
+
+        await loop.run_in_executor(None, _wait_for_ready)
+        if tcp and ready.get("tcp_port") is not None:
+            os.environ["RUSTADDR"] = str(ready["tcp_port"])
+            start_tracked_task(gui_endless_event_loop_tasks())
+        tasks = [t for t in asyncio.all_tasks(loop) if t is not asyncio.current_task(loop)]
+
         # Wait for shutdown signal from webframe
-        await loop.run_in_executor(None, mp_event.wait)
+        await loop.run_in_executor(None, _wait_for_close)
         [task.cancel() for task in tasks]
 
         def _join_or_kill():
@@ -55,5 +219,11 @@ async def native_runtime(
                 p.join()
 
         await loop.run_in_executor(None, _join_or_kill)
+        # The manager's list goes away with it.
+        errors_seen = list(errors)
 
     await shutdown_all_tasks()
+    if p.exitcode == EXIT_UNSUPPORTED and errors_seen:
+        raise UnsupportedError(errors_seen[0])
+    if p.exitcode == EXIT_BIND and errors_seen:
+        raise BindError(*errors_seen[0])
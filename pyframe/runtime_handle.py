@@ -1,13 +1,18 @@
 import asyncio
+import contextvars
 import dataclasses
-import json
 import os
-import struct
+import time
+import warnings
+from collections import deque
 from pathlib import Path
-from typing import Any, Callable, Dict, List, Optional, Type, TypeVar, Union
+from typing import Any, AsyncIterator, Callable, Deque, Dict, List, Optional, Tuple, Type, TypeVar, Union
 
 from pydantic import BaseModel
 
+from .core import start_tracked_task
+from .protocol import MAX_ATTACHMENT_BYTES, MAX_BATCH_SIZE, handshake, read_frame, write_frame
+
 T = TypeVar("T")
 
 
@@ -60,14 +65,19 @@ class ApiRequestModel(BaseModel):
     id: int
     method: str
     args: List[Any]
+    #: Request metadata such as ``{"window_id": ...}``; omitted if ``None``.
+    metadata: Optional[Dict[str, Any]] = None
 
     def to_json_array(self) -> list:
         """
         Convert the request into a JSON-safe array format.
 
-        :return: List of ``[id, method, args]``.
+        :return: List of ``[id, method, args]``, plus ``metadata`` if set.
         """
-        return [self.id, self.method, [make_json_safe(a) for a in self.args]]
+        data = [self.id, self.method, [make_json_safe(a) for a in self.args]]
+        if self.metadata is not None:
+            data.append(make_json_safe(self.metadata))
+        return data
 
 
 class ApiResponseModel(BaseModel):
@@ -76,24 +86,43 @@ class ApiResponseModel(BaseModel):
     code: int
     msg: str
     result: Any
+    #: Warning of a deprecated API, e.g. ``"set_title is deprecated: use ..."``.
+    deprecated: Optional[str] = None
 
     @classmethod
     def from_array(cls, arr: List[Any]) -> "ApiResponseModel":
         """
         Construct a response model from an array.
 
-        :param arr: List of 4 elements ``[id, code, msg, result]``.
+        :param arr: ``[id, code, msg, result]``, followed by
+            ``{"deprecated": warning}`` for deprecated APIs.
         :return: Parsed response model.
         :raises ValueError: If the array format is invalid.
         """
-        if not isinstance(arr, list) or len(arr) != 4:
+        if not isinstance(arr, list) or len(arr) not in (4, 5):
             raise ValueError(f"Invalid ApiResponse array: {arr}")
-        return cls(id=arr[0], code=arr[1], msg=arr[2], result=arr[3])
+        meta = arr[4] if len(arr) == 5 and isinstance(arr[4], dict) else {}
+        return cls(id=arr[0], code=arr[1], msg=arr[2], result=arr[3], deprecated=meta.get("deprecated"))
 
 
 class ApiError(Exception):
     """Raised for errors reported by the Rust event loop."""
 
+    #: The OS denied a permission the API needs, e.g. screen recording on macOS.
+    PERMISSION_DENIED = -3
+    #: The platform or OS version doesn't support the request.
+    UNSUPPORTED = -4
+    #: A server the API needs couldn't be reached.
+    OFFLINE = -5
+    #: Downloaded data didn't match its signature or checksum.
+    INVALID_SIGNATURE = -6
+    #: The API succeeded but its result couldn't be serialized; a bug in the API.
+    INTERNAL = -7
+    #: The arguments don't fit the API, e.g. a missing or oversized attachment.
+    BAD_ARGS = -8
+    #: The API panicked; the webframe keeps running.
+    HANDLER_PANIC = -9
+
     def __init__(self, code: int, msg: str):
         super().__init__(f"[API-{code}] {msg}")
         self.code = code
@@ -175,34 +204,344 @@ class PendingRegistry:
         self._pending.clear()
 
 
+#: Priorities of the request lanes, highest first.
+PRIORITIES = ("interactive", "normal", "background")
+#: Seconds a request may wait before it goes ahead of higher lanes.
+AGE_LIMIT = 0.25
+#: Times a request with an ``idempotency_key`` is resent after a lost connection.
+IDEMPOTENT_RETRIES = 2
+
+#: Priority of requests sent without one, e.g. set for the duration of a
+#: command invoked from the page with ``{priority: ...}``.
+request_priority: contextvars.ContextVar[Optional[str]] = contextvars.ContextVar("request_priority", default=None)
+
+
+class RequestLanes:
+    """
+    Queue of requests for the Rust loop, one lane per priority.
+
+    :meth:`get` returns the oldest request that has waited
+    :data:`AGE_LIMIT` seconds, else the first of the highest non-empty
+    lane, the same rule the Rust side applies across connections.
+    """
+
+    def __init__(self) -> None:
+        self._lanes: Dict[str, Deque[Tuple[float, Dict[str, Any]]]] = {p: deque() for p in PRIORITIES}
+        self._ready = asyncio.Event()
+
+    async def put(self, task: Dict[str, Any], priority: Optional[str] = None) -> None:
+        """Queue ``task``; unknown priorities count as ``"normal"``."""
+        lane = self._lanes.get(priority or "normal", self._lanes["normal"])
+        lane.append((time.monotonic(), task))
+        self._ready.set()
+
+    async def get(self) -> Dict[str, Any]:
+        """Take the next task, waiting for one if all lanes are empty."""
+        while True:
+            task = self.get_nowait()
+            if task is not None:
+                return task
+            self._ready.clear()
+            await self._ready.wait()
+
+    def get_nowait(self) -> Optional[Dict[str, Any]]:
+        """Take the next task, or ``None`` if all lanes are empty."""
+        heads = [(lane[0][0], lane) for lane in self._lanes.values() if lane]
+        if not heads:
+            return None
+        oldest, lane = min(heads, key=lambda head: head[0])
+        if time.monotonic() - oldest < AGE_LIMIT:
+            lane = next(lane for lane in self._lanes.values() if lane)
+        return lane.popleft()[1]
+
+    def depths(self) -> Dict[str, int]:
+        """Number of waiting requests per lane."""
+        return {priority: len(lane) for priority, lane in self._lanes.items()}
+
+
 _pending = PendingRegistry()
-task_queue: asyncio.Queue[Dict[str, Any]] = asyncio.Queue()
+task_queue = RequestLanes()
+
+#: Most emits waiting to be written; the oldest is dropped beyond it.
+EMIT_QUEUE_CAPACITY = 256
+
+
+class Emitter:
+    """
+    Pushes events to the pages on its own TCP connection, without
+    responses, in the order they were emitted.
+
+    :meth:`emit` only queues the event. If the connection falls behind,
+    the oldest queued events are dropped with a warning; the Rust side
+    does the same while its event loop is saturated.
+    """
+
+    def __init__(self, capacity: int = EMIT_QUEUE_CAPACITY):
+        self.capacity = capacity
+        #: Events dropped since startup, on this side.
+        self.dropped = 0
+        self._frames: Deque[Dict[str, Any]] = deque()
+        self._ready = asyncio.Event()
+        self._loop: Optional[asyncio.AbstractEventLoop] = None
+        self._saturated = False
+
+    def bind(self, loop: asyncio.AbstractEventLoop) -> None:
+        """Write from ``loop``; called by :func:`gui_endless_event_loop_tasks`."""
+        self._loop = loop
+
+    def emit(self, event: str, payload: Any = None, windows: Optional[List[int]] = None) -> None:
+        """Queue ``event``; safe to call from any thread."""
+        frame: Dict[str, Any] = {"type": "emit", "event": event, "payload": make_json_safe(payload)}
+        if windows is not None:
+            frame["windows"] = list(windows)
+        loop = self._loop
+        if loop is None:
+            raise RuntimeError("emit() needs a running pyframe.launch()")
+        try:
+            running: Optional[asyncio.AbstractEventLoop] = asyncio.get_running_loop()
+        except RuntimeError:
+            running = None
+        if running is loop:
+            self._push(frame)
+        else:
+            loop.call_soon_threadsafe(self._push, frame)
+
+    def _push(self, frame: Dict[str, Any]) -> None:
+        if len(self._frames) >= self.capacity:
+            self._frames.popleft()
+            self.dropped += 1
+            if not self._saturated:
+                self._saturated = True
+                warnings.warn(f"{self.capacity} emits are waiting; dropping the oldest", RuntimeWarning, stacklevel=2)
+        self._frames.append(frame)
+        self._ready.set()
+
+    async def run(self) -> None:
+        """Write queued events until cancelled, reconnecting after errors."""
+        writer: Optional[asyncio.StreamWriter] = None
+        try:
+            while True:
+                if not self._frames:
+                    self._saturated = False
+                    self._ready.clear()
+                    await self._ready.wait()
+                    continue
+                frame = self._frames.popleft()
+                try:
+                    if writer is None:
+                        writer = await self._connect()
+                    await write_frame(writer, frame)
+                except (ConnectionError, OSError) as e:
+                    self.dropped += 1
+                    warnings.warn(f"Emit of {frame['event']!r} dropped: {e}", RuntimeWarning)
+                    if writer is not None:
+                        writer.close()
+                    writer = None
+                    await asyncio.sleep(0.1)
+        finally:
+            if writer is not None:
+                writer.close()
+
+    async def _connect(self) -> asyncio.StreamWriter:
+        port = int(os.environ.get("RUSTADDR", "9000"))
+        reader, writer = await asyncio.open_connection("127.0.0.1", port)
+        ack = await handshake(reader, writer, features=["emit"])
+        if "emit" not in ack.get("features", []):
+            writer.close()
+            raise ConnectionError("The webframe doesn't support emit")
+        return writer
+
+
+_emitter = Emitter()
+
+
+def emit(event: str, payload: Any = None, windows: Optional[List[int]] = None) -> None:
+    """
+    Push ``event`` to the pages without waiting for anything, e.g. a sensor
+    reading every 100 ms; the page gets it as a ``pyframe:<event>``
+    ``CustomEvent`` with ``payload`` as ``detail``.
+
+    Events of one process arrive in the order they were emitted. Nothing
+    confirms delivery: while the UI can't keep up, events are dropped with
+    a ``RuntimeWarning`` instead of blocking the caller. Safe to call from
+    any thread once :func:`pyframe.launch` runs.
+
+    :param event: Event name, without the ``pyframe:`` prefix.
+    :param payload: JSON-safe data for the event.
+    :param windows: The ``id``s from :meth:`Window.state` to reach; every window if ``None``.
+    """
+    _emitter.emit(event, payload, windows)
 
 
-async def send_loop_event(data: list) -> Optional[list]:
+async def send_loop_event(data: list, attachment: Optional[bytes] = None) -> Optional[list]:
     """
     Send a synchronous event to the Rust loop.
 
     A single request is sent and a single response is awaited.
 
     :param data: The request array.
+    :param attachment: Bytes for the API's ``Bytes`` parameter, sent in a
+        binary frame.
     :return: The decoded response array.
     """
     port = int(os.environ.get("RUSTADDR", "9000"))
     reader, writer = await asyncio.open_connection("127.0.0.1", port)
+    try:
+        await handshake(reader, writer, features=["binary"] if attachment is not None else [])
+        await write_frame(writer, data, attachment)
+        return await read_frame(reader)
+    finally:
+        writer.close()
+        await writer.wait_closed()
+
+
+async def send_loop_batch(requests: List[list]) -> List[list]:
+    """
+    Send several requests to the Rust loop in one frame.
 
-    payload = json.dumps(data).encode("utf-8")
-    writer.write(struct.pack(">I", len(payload)) + payload)
-    await writer.drain()
+    Each is handled on its own, so one failing doesn't affect the others.
+
+    :param requests: Request arrays, at most :data:`MAX_BATCH_SIZE`.
+    :return: Their response arrays, in the same order.
+    """
+    port = int(os.environ.get("RUSTADDR", "9000"))
+    reader, writer = await asyncio.open_connection("127.0.0.1", port)
+    try:
+        await handshake(reader, writer, features=["batch"])
+        await write_frame(writer, {"type": "batch", "requests": requests})
+        return (await read_frame(reader))["responses"]
+    finally:
+        writer.close()
+        await writer.wait_closed()
+
+
+async def events() -> AsyncIterator[Tuple[str, Dict[str, Any]]]:
+    """
+    Stream every event the Rust event loop handles.
 
-    header = await reader.readexactly(4)
-    (length,) = struct.unpack(">I", header)
-    response_bytes = await reader.readexactly(length)
+    Opens a dedicated connection and yields ``(event_type, data)`` tuples,
+    e.g. ``("tick", {"count": 3})`` or ``("menu", {"id": "..."})``, until the
+    webframe exits. A consumer that falls behind skips the oldest events.
 
-    writer.close()
-    await writer.wait_closed()
+    A panic arrives as ``("crashed", report)`` with the message, backtrace,
+    recent requests and the ``path`` of the report written to the app's log
+    directory. With ``report["fatal"]`` the webframe is going down; other
+    panics, e.g. in a background task, leave it running.
 
-    return json.loads(response_bytes.decode("utf-8"))
+    Usage::
+
+        async for event_type, data in pyframe.events():
+            ...
+
+    :return: An async iterator of ``(event_type, data)`` tuples.
+    """
+    async for event in _event_connection():
+        yield event
+
+
+async def call_with_events(method: str, args: Optional[Any] = None) -> AsyncIterator[Tuple[str, Any]]:
+    """
+    Call ``method`` on a dedicated event connection.
+
+    For APIs whose resources live as long as the calling connection, such
+    as ``fs_watch``. First yields ``("__result", result)``, then every event
+    like :func:`events`; the connection closes when iteration stops.
+
+    :param method: The API to call.
+    :param args: Its arguments.
+    :return: An async iterator of ``(event_type, data)`` tuples.
+    :raises ApiError: If the call fails.
+    """
+    async for event in _event_connection((method, normalize_args(args))):
+        yield event
+
+
+async def on_exit(name: str, callback: Callable[[], Any], timeout: float = 2.0) -> int:
+    """
+    Run ``callback`` while the webframe exits.
+
+    Exit hooks run newest first, after the window was closed but before the
+    webframe leaves its event loop; it keeps serving requests meanwhile.
+    ``callback`` may be a coroutine function. Exit goes on once it returns,
+    or after ``timeout`` seconds.
+
+    Usage::
+
+        await pyframe.on_exit("flush-db", db.flush, timeout=5)
+
+    :param name: Names the hook in the logs.
+    :param callback: Called without arguments.
+    :param timeout: Seconds exit waits for it.
+    :return: The hook's id, for the ``remove_exit_hook`` API.
+    """
+    registered: asyncio.Future = asyncio.get_event_loop().create_future()
+
+    async def run() -> None:
+        hook_id = None
+        try:
+            # Registered on the event connection, so the event can't be missed.
+            async for event_type, data in call_with_events("register_exit_hook", [name, int(timeout * 1000)]):
+                if event_type == "__result":
+                    hook_id = data
+                    registered.set_result(data)
+                elif event_type == "exit-hook" and data.get("id") == hook_id:
+                    try:
+                        result = callback()
+                        if asyncio.iscoroutine(result):
+                            await result
+                    finally:
+                        await eventloop_event_register_typed("exit_hook_done", [hook_id], result_type=bool)
+                    return
+        except Exception as e:
+            if registered.done():
+                raise
+            registered.set_exception(e)
+        if not registered.done():
+            registered.set_exception(ConnectionError("The webframe closed before the exit hook was registered"))
+
+    start_tracked_task(run())
+    return await registered
+
+
+async def _event_connection(call: Optional[Tuple[str, list]] = None) -> AsyncIterator[Tuple[str, Any]]:
+    port = int(os.environ.get("RUSTADDR", "9000"))
+    reader, writer = await asyncio.open_connection("127.0.0.1", port)
+
+    req_id = None
+    try:
+        await handshake(reader, writer, features=["streaming"])
+        await write_frame(writer, [0, "__subscribe_events", []])
+        ack = ApiResponseModel.from_array(await read_frame(reader))
+        if ack.code != 0:
+            raise ApiError(ack.code, ack.msg)
+
+        if call:
+            # Reserved like any other request, so the ID isn't handed out twice.
+            req_id = _pending.next_id()
+            _pending.register(req_id, asyncio.get_event_loop().create_future())
+            await write_frame(writer, ApiRequestModel(id=req_id, method=call[0], args=call[1]).to_json_array())
+
+        while True:
+            frame = await read_frame(reader)
+            if isinstance(frame, dict) and "event" in frame:
+                yield frame["event"], frame.get("data") or {}
+            elif isinstance(frame, list) and req_id is not None:
+                resp = ApiResponseModel.from_array(frame)
+                _pending.pop(req_id)
+                req_id = None
+                if resp.code != 0:
+                    raise ApiError(resp.code, resp.msg)
+                yield "__result", resp.result
+    except asyncio.IncompleteReadError:
+        return
+    finally:
+        if req_id is not None:
+            _pending.pop(req_id)
+        writer.close()
+
+
+#: Deprecation warnings already issued, so each is issued once.
+_deprecations_warned: set = set()
 
 
 async def handle_event_loop_response(arr: list, future: Optional[asyncio.Future] = None):
@@ -216,6 +555,9 @@ async def handle_event_loop_response(arr: list, future: Optional[asyncio.Future]
     :param future: Optional future to resolve.
     """
     resp = ApiResponseModel.from_array(arr)
+    if resp.deprecated and resp.deprecated not in _deprecations_warned:
+        _deprecations_warned.add(resp.deprecated)
+        warnings.warn(resp.deprecated, DeprecationWarning, stacklevel=2)
     if future:
         if resp.code != 0:
             future.set_exception(ApiError(resp.code, resp.msg))
@@ -233,36 +575,69 @@ async def gui_endless_event_loop_tasks():
     """
     Endless loop forwarding tasks to the Rust event loop.
 
-    Continuously processes tasks from ``task_queue`` and dispatches
-    them to the Rust backend. Pending futures are resolved with
-    results or errors.
+    Continuously processes tasks from ``task_queue`` in priority order and
+    dispatches them to the Rust backend; tasks queued meanwhile go together
+    in one batch frame. Pending futures are resolved with results or errors.
     """
+    _emitter.bind(asyncio.get_running_loop())
+    emitter = asyncio.ensure_future(_emitter.run())
     try:
         while True:
-            task = await task_queue.get()
-            future: asyncio.Future[Any] = task.pop("future", None)
-            data = task.get("data")
+            tasks = [await task_queue.get()]
+            # Attachments can't go in a batch frame.
+            while len(tasks) < MAX_BATCH_SIZE and tasks[0].get("attachment") is None:
+                task = task_queue.get_nowait()
+                if task is None:
+                    break
+                if task.get("attachment") is not None:
+                    await send_attached(task)
+                    continue
+                tasks.append(task)
+            futures: List[Optional[asyncio.Future[Any]]] = [task.pop("future", None) for task in tasks]
 
             try:
-                arr = await send_loop_event(data)
-                if arr:
-                    await handle_event_loop_response(arr, future=future)
+                if len(tasks) == 1:
+                    responses = [await send_loop_event(tasks[0].get("data"), tasks[0].get("attachment"))]
+                else:
+                    responses = await send_loop_batch([task.get("data") for task in tasks])
+                for arr, future in zip(responses, futures):
+                    if arr:
+                        await handle_event_loop_response(arr, future=future)
             except Exception as e:
-                if future and not future.done():
-                    future.set_exception(e)
+                for future in futures:
+                    if future and not future.done():
+                        future.set_exception(e)
             finally:
                 await asyncio.sleep(0.01)
     except asyncio.CancelledError:
         print("[INFO] gui_endless_event_loop_tasks() cancelled.")
     finally:
+        emitter.cancel()
         print("[INFO] gui_endless_event_loop_tasks() terminated.")
         _pending.cancel_all(RuntimeError("Event loop terminated"))
 
 
+async def send_attached(task: Dict[str, Any]) -> None:
+    """Send a task with an attachment on its own and resolve its future."""
+    future = task.pop("future", None)
+    try:
+        arr = await send_loop_event(task.get("data"), task.get("attachment"))
+        if arr:
+            await handle_event_loop_response(arr, future=future)
+    except Exception as e:
+        if future and not future.done():
+            future.set_exception(e)
+
+
 async def eventloop_event_register_typed(
     method: str,
     args: Optional[Any] = None,
     result_type: Union[Type[BaseModel], Callable[[Any], T]] = dict,
+    timeout: float = 10.0,
+    metadata: Optional[Dict[str, Any]] = None,
+    priority: Optional[str] = None,
+    idempotency_key: Optional[str] = None,
+    attachment: Optional[bytes] = None,
 ) -> T:
     """
     Send a typed request to the event loop and await its response.
@@ -275,30 +650,52 @@ async def eventloop_event_register_typed(
     :param args: Optional arguments for the request.
     :param result_type: Expected result type. Can be a Pydantic model,
         a callable transformer, or a raw type.
+    :param timeout: Seconds to wait for the response. Defaults to ``10.0``.
+    :param metadata: Request metadata, e.g. ``{"window_id": 42}`` to target a window
+        or ``{"trace_id": "..."}``, the id handlers see in their ``RequestContext``.
+    :param priority: ``"interactive"``, ``"normal"`` or ``"background"``; the
+        lane the request waits in on both sides. Defaults to
+        :data:`request_priority`, else ``"normal"``.
+    :param idempotency_key: Makes the request safe to retry: a lost
+        connection is retried up to :data:`IDEMPOTENT_RETRIES` times, and a
+        retry of a request that already ran gets its response instead of
+        running it again.
+    :param attachment: Raw bytes for the API's ``Bytes`` parameter, at most
+        :data:`MAX_ATTACHMENT_BYTES`.
     :return: The parsed response.
     :raises Exception: If the request fails or validation fails.
     """
-    req_id = _pending.next_id()
-    request = ApiRequestModel(id=req_id, method=method, args=normalize_args(args))
-    future: asyncio.Future[T] = asyncio.get_event_loop().create_future()
-    _pending.register(req_id, future)
-
-    await task_queue.put({
-        "data": request.to_json_array(),
-        "future": future,
-    })
-
-    try:
-        raw_result = await asyncio.wait_for(future, timeout=10.0)
-
-        if isinstance(result_type, type) and issubclass(result_type, BaseModel):
-            return result_type.model_validate(raw_result)
-        if callable(result_type):
-            return result_type(raw_result)
-        return raw_result
-
-    except Exception:
-        _pending.pop(req_id)
-        raise
-    finally:
-        _pending.pop(req_id, None)
+    priority = priority or request_priority.get()
+    if priority is not None:
+        metadata = {**(metadata or {}), "priority": priority}
+    if idempotency_key is not None:
+        metadata = {**(metadata or {}), "idempotency_key": idempotency_key}
+    if attachment is not None and len(attachment) > MAX_ATTACHMENT_BYTES:
+        raise ValueError(f"Attachments are limited to {MAX_ATTACHMENT_BYTES} bytes, got {len(attachment)}")
+    deadline = asyncio.get_event_loop().time() + timeout
+    for attempt in range(IDEMPOTENT_RETRIES + 1):
+        req_id = _pending.next_id()
+        request = ApiRequestModel(id=req_id, method=method, args=normalize_args(args), metadata=metadata)
+        future: asyncio.Future[T] = asyncio.get_event_loop().create_future()
+        _pending.register(req_id, future)
+
+        await task_queue.put({
+            "data": request.to_json_array(),
+            "future": future,
+            "attachment": attachment,
+        }, priority)
+
+        try:
+            raw_result = await asyncio.wait_for(future, timeout=deadline - asyncio.get_event_loop().time())
+            break
+        except (ConnectionError, asyncio.IncompleteReadError):
+            if idempotency_key is None or attempt == IDEMPOTENT_RETRIES:
+                raise
+        finally:
+            _pending.pop(req_id, None)
+
+    if isinstance(result_type, type) and issubclass(result_type, BaseModel):
+        return result_type.model_validate(raw_result)
+    if callable(result_type):
+        return result_type(raw_result)
+    return raw_result
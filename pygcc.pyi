@@ -1,6 +1,153 @@
 import multiprocessing
+from typing import Any, Callable, Dict, Iterator, List, Optional, Tuple
 
 
+class PyFrameError(RuntimeError):
+    """Base of the errors raised by pygcc."""
 
 
-def create_webframe(html: str, host: str, port: int, mp_event: multiprocessing.Event):... # type: ignore
\ No newline at end of file
+class UnsupportedError(PyFrameError):
+    """An option isn't supported on this platform, e.g. ``parent_handle`` on Linux."""
+
+
+class EventLoopError(PyFrameError):
+    """
+    ``create_webframe`` was called from another thread than the first time,
+    or while a webframe is still open; or ``launch_webframe`` on macOS, or
+    after ``create_webframe`` built the event loop.
+    """
+
+
+class BridgeConnectionError(PyFrameError):
+    """The bridge couldn't be reached or closed the connection."""
+
+
+class BridgeProtocolError(PyFrameError):
+    """The bridge refused the hello or sent a malformed frame."""
+
+
+class BridgeTimeoutError(PyFrameError):
+    """No response arrived within the timeout."""
+
+
+class BridgeApiError(PyFrameError):
+    """An API answered with an error; ``args`` are the message and the code."""
+
+
+class BindError(PyFrameError):
+    """A requested port is taken; ``args`` are the message and the port."""
+
+
+class BridgeSubscription(Iterator[Tuple[str, Any]]):
+    """``(event, data)`` tuples of :meth:`BridgeClient.subscribe`; stops when the webframe exits."""
+
+    def __next__(self) -> Tuple[str, Any]: ...
+    def close(self) -> None: ...
+
+
+class BridgeClient:
+    """
+    Blocking client of the TCP bridge of a running ``create_webframe``,
+    from any process. Releases the GIL while waiting.
+    """
+
+    @staticmethod
+    def connect(addr: str, token: Optional[str] = None, timeout: float = 5.0) -> "BridgeClient":
+        """Connect to ``addr`` (``"127.0.0.1:<RUSTADDR>"``); ``token`` defaults to ``PYFRAME_TOKEN``."""
+    def invoke(
+        self,
+        name: str,
+        args: Any = None,
+        timeout: float = 10.0,
+        attachment: Optional[bytes] = None,
+        metadata: Optional[dict] = None,
+        **kwargs: Any,
+    ) -> Any:
+        """
+        Call the API ``name`` with ``kwargs`` by parameter name, or ``args``: a list
+        of positional arguments, a dict of named ones or a single positional one.
+        Raises :class:`BridgeApiError` if it fails.
+        """
+    def subscribe(self, events: Optional[List[str]] = None, timeout: float = 5.0) -> BridgeSubscription:
+        """Stream the event loop's events, only ``events`` if given."""
+    def close(self) -> None: ...
+    def __enter__(self) -> "BridgeClient": ...
+    def __exit__(self, *exc: Any) -> None: ...
+
+
+class FrameHandle:
+    """
+    The webframe running in this process, without a socket; the only way in with
+    ``transport: "ipc-only"``. Use it from another thread than the one blocked in
+    ``create_webframe``, or any thread after ``launch_webframe``. Releases the GIL
+    while waiting.
+    """
+
+    def __init__(self) -> None: ...
+    @property
+    def running(self) -> bool:
+        """Whether a webframe is running, so calls can succeed."""
+    def invoke(self, name: str, args: Any = None, timeout: float = 10.0, **kwargs: Any) -> Any:
+        """
+        Call the API ``name``, with arguments as :meth:`BridgeClient.invoke`; raises :class:`BridgeConnectionError` if no webframe runs,
+        :class:`BridgeTimeoutError` and :class:`BridgeApiError`.
+        """
+    def emit(self, event: str, data: Any = None) -> None:
+        """Emit ``event`` with ``data`` to the pages."""
+    def close(self, force: bool = False) -> None:
+        """Close the webframe after its exit hooks, or with ``force`` without waiting for them."""
+
+
+def create_webframe(
+    html: str,
+    host: str,
+    port: int,
+    mp_event: multiprocessing.Event,  # type: ignore
+    options: Optional[str] = None,
+    on_ready: Optional[Callable[[Dict[str, Any]], Any]] = None,
+):
+    """
+    Open a webframe and block until it closes. Later calls from the same
+    thread reuse the event loop, e.g. a login window followed by the main
+    window. ``on_ready`` is called once the window is open with ``transport``,
+    ``socket_url``, ``ws_port`` and ``tcp_port``, the port the TCP bridge bound
+    from ``RUSTADDR`` (``0`` picks a free one).
+
+    :raises EventLoopError: If called from another thread than the first call.
+    :raises BindError: If ``RUSTADDR`` is taken.
+    """
+
+
+class WebFrameThread:
+    """The webframe of :func:`launch_webframe`."""
+
+    def is_alive(self) -> bool:
+        """Whether the webframe is still open."""
+    def join(self, timeout: Optional[float] = None) -> bool:
+        """
+        Wait until the webframe is closed, at most ``timeout`` seconds, and return
+        whether it is; raises what ``create_webframe`` would have. Releases the GIL
+        while waiting.
+        """
+
+
+def launch_webframe(
+    html: str,
+    host: str,
+    port: int,
+    mp_event: Optional[multiprocessing.Event] = None,  # type: ignore
+    options: Optional[str] = None,
+    on_ready: Optional[Callable[[Dict[str, Any]], Any]] = None,
+) -> WebFrameThread:
+    """
+    ``create_webframe`` without blocking: the event loop runs on a thread of its
+    own, shared by later calls, which open once the previous window closed.
+    Reach the webframe with :class:`FrameHandle` from any thread; ``on_ready``
+    runs on the event loop thread. ``mp_event``, if given, is set as it closes.
+    Windows and Linux only.
+
+    :raises EventLoopError: On macOS, where the event loop must run on the main
+        thread; call ``create_webframe`` from the main thread there, which blocks
+        until the window closes. Also if ``create_webframe`` built the event loop
+        before.
+    """
@@ -0,0 +1,408 @@
+//! Menu accelerators while the WebView has focus.
+//!
+//! A focused WebView takes key presses before the native menu sees them on
+//! some platforms: WebView2 handles keys in its own process, so a
+//! `Ctrl+S` never reaches the menu bar's accelerator table. Two paths get
+//! them there:
+//!
+//! - **native**: the platform's pre-translate hook. On Windows the event
+//!   loop's message hook runs `TranslateAcceleratorW` with the menu bar's
+//!   table; on GTK a `key-press-event` handler notes matches before the
+//!   window activates them. macOS menus get key equivalents first anyway.
+//! - **script**: `accelerators.js` matches keydowns in the page against the
+//!   [`Combo`]s pushed as the `accelerators` event, prevents the default
+//!   and posts [`ACCELERATOR`]. Plain typing into text fields is left
+//!   alone.
+//!
+//! A delivery on the other path within [`DEDUPE`] is dropped, and every
+//! delivery is logged for `get_accelerator_log`.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{context::AppContext, lock_force, menu::ItemSpec};
+
+/// Prefix of the IPC message, followed by a [`Pressed`] as JSON.
+pub const ACCELERATOR: &str = "__accelerator:";
+/// How long a second delivery of the same item counts as a duplicate.
+const DEDUPE: Duration = Duration::from_millis(150);
+/// Deliveries kept for `get_accelerator_log`.
+const LOG_SIZE: usize = 50;
+
+/// When the native hook last matched, taken by the next menu event.
+static NATIVE_HIT: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Deserialize)]
+pub struct Pressed {
+    /// The menu item of the matched [`Combo`].
+    pub id: String,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Path {
+    Native,
+    Script,
+}
+
+/// An accelerator as the page matches it against keydown events.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Combo {
+    /// The menu item it chooses.
+    pub id: String,
+    pub accelerator: String,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub meta: bool,
+    /// `KeyboardEvent.code` for letters and digits, which stays the same
+    /// when Alt or Shift change the character.
+    pub code: Option<String>,
+    /// `KeyboardEvent.key` for everything else.
+    pub key: Option<String>,
+}
+
+impl Combo {
+    /// `None` if `accelerator` has no key the page can match.
+    pub fn parse(id: &str, accelerator: &str) -> Option<Self> {
+        let mut combo = Self {
+            id: id.to_string(),
+            accelerator: accelerator.to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+            meta: false,
+            code: None,
+            key: None,
+        };
+        let mut key = None;
+        for part in accelerator.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "cmdorctrl" | "commandorcontrol" | "cmdorcontrol" | "commandorctrl" => {
+                    if cfg!(target_os = "macos") {
+                        combo.meta = true;
+                    } else {
+                        combo.ctrl = true;
+                    }
+                }
+                "ctrl" | "control" => combo.ctrl = true,
+                "alt" | "option" => combo.alt = true,
+                "shift" => combo.shift = true,
+                "meta" | "cmd" | "command" | "super" => combo.meta = true,
+                _ if key.is_none() && !part.is_empty() => key = Some(part),
+                _ => return None,
+            }
+        }
+        let key = key?;
+        match key.chars().next() {
+            Some(c) if key.len() == 1 && c.is_ascii_alphabetic() => {
+                combo.code = Some(format!("Key{}", c.to_ascii_uppercase()));
+            }
+            Some(c) if key.len() == 1 && c.is_ascii_digit() => {
+                combo.code = Some(format!("Digit{}", c));
+            }
+            _ => combo.key = Some(dom_key(key)),
+        }
+        Some(combo)
+    }
+}
+
+/// The DOM name of a key as accelerators spell it.
+fn dom_key(key: &str) -> String {
+    match key.to_ascii_lowercase().as_str() {
+        "up" => "ArrowUp",
+        "down" => "ArrowDown",
+        "left" => "ArrowLeft",
+        "right" => "ArrowRight",
+        "esc" | "escape" => "Escape",
+        "enter" | "return" => "Enter",
+        "space" => " ",
+        "del" | "delete" => "Delete",
+        "backspace" => "Backspace",
+        "tab" => "Tab",
+        "home" => "Home",
+        "end" => "End",
+        "pageup" => "PageUp",
+        "pagedown" => "PageDown",
+        "plus" => "+",
+        _ => return key.to_string(),
+    }
+    .to_string()
+}
+
+/// The accelerators of a menu bar definition.
+pub fn combos(spec: &[ItemSpec]) -> Vec<Combo> {
+    let mut combos = Vec::new();
+    for item in spec {
+        if let Some(combo) = item
+            .accelerator
+            .as_deref()
+            .and_then(|accelerator| Combo::parse(&item.id, accelerator))
+        {
+            combos.push(combo);
+        }
+        combos.extend(self::combos(&item.items));
+    }
+    combos
+}
+
+/// Sends pages the menu bar's accelerators after they changed.
+pub fn sync(ctx: &AppContext) -> anyhow::Result<()> {
+    let combos = lock_force!(ctx.menubar)
+        .state()
+        .map(|spec| combos(&spec))
+        .unwrap_or_default();
+    let mut accelerators = lock_force!(ctx.accelerators);
+    if accelerators.set(combos) {
+        ctx.emit_all("accelerators", accelerators.payload())?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct Delivery {
+    id: String,
+    path: Path,
+    /// Unix time in milliseconds.
+    at: u64,
+    /// A duplicate of the other path's delivery, not acted on.
+    dropped: bool,
+}
+
+#[derive(Default)]
+pub struct Accelerators {
+    combos: Vec<Combo>,
+    last: Option<(String, Path, Instant)>,
+    /// A script delivery on its way through the `Menu` event.
+    forwarded: Option<String>,
+    log: VecDeque<Delivery>,
+}
+
+impl Accelerators {
+    /// Replaces the registered accelerators; `false` if they didn't change.
+    pub fn set(&mut self, combos: Vec<Combo>) -> bool {
+        if combos == self.combos {
+            return false;
+        }
+        self.combos = combos;
+        true
+    }
+
+    /// The `accelerators` event payload.
+    pub fn payload(&self) -> Value {
+        json!(self.combos)
+    }
+
+    /// Whether an accelerator the page caught for `id` is to be acted on,
+    /// by sending it on as a `Menu` event.
+    pub fn script_pressed(&mut self, id: &str, now: Instant) -> bool {
+        let accepted = self.deliver(id, Path::Script, now);
+        if accepted {
+            self.forwarded = Some(id.to_string());
+        }
+        accepted
+    }
+
+    /// Whether a menu event for `id` is to be acted on; it is logged as a
+    /// native accelerator if the hook just matched one.
+    pub fn menu_event(&mut self, id: &str, now: Instant) -> bool {
+        if self.forwarded.as_deref() == Some(id) {
+            self.forwarded = None;
+            return true;
+        }
+        let hit = lock_force!(NATIVE_HIT).take();
+        if hit.is_some_and(|hit| now.duration_since(hit) < DEDUPE) {
+            return self.deliver(id, Path::Native, now);
+        }
+        // A click; only a script delivery just before makes it a duplicate.
+        !self.is_duplicate(id, Path::Native, now)
+    }
+
+    fn is_duplicate(&self, id: &str, path: Path, now: Instant) -> bool {
+        self.last.as_ref().is_some_and(|(last, last_path, at)| {
+            last == id && *last_path != path && now.duration_since(*at) < DEDUPE
+        })
+    }
+
+    fn deliver(&mut self, id: &str, path: Path, now: Instant) -> bool {
+        let dropped = self.is_duplicate(id, path, now);
+        if !dropped {
+            self.last = Some((id.to_string(), path, now));
+        }
+        if self.log.len() == LOG_SIZE {
+            self.log.pop_front();
+        }
+        self.log.push_back(Delivery {
+            id: id.to_string(),
+            path,
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            dropped,
+        });
+        !dropped
+    }
+
+    /// For `get_accelerator_log`: the registered accelerators and the
+    /// latest deliveries, oldest first.
+    pub fn describe(&self) -> Value {
+        json!({
+            "registered": self.combos,
+            "native_hook": NATIVE_HOOK,
+            "deliveries": self.log,
+        })
+    }
+}
+
+fn native_hit() {
+    *lock_force!(NATIVE_HIT) = Some(Instant::now());
+}
+
+/// Whether this platform has a pre-translate hook.
+const NATIVE_HOOK: bool = cfg!(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+));
+
+#[cfg(target_os = "windows")]
+pub use win32::{set_menu, translate};
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::{cell::RefCell, ffi::c_void};
+
+    use tray_icon::menu::Menu;
+    use windows::Win32::{
+        Foundation::HWND,
+        UI::WindowsAndMessaging::{TranslateAcceleratorW, HACCEL, MSG, WM_KEYDOWN, WM_SYSKEYDOWN},
+    };
+
+    thread_local! {
+        /// The menu bar and its window; the table changes with every
+        /// accelerator update, so it is asked for on each key.
+        static MENU: RefCell<Option<(isize, Menu)>> = const { RefCell::new(None) };
+    }
+
+    pub fn set_menu(hwnd: isize, menu: Option<Menu>) {
+        MENU.with(|slot| *slot.borrow_mut() = menu.map(|menu| (hwnd, menu)));
+    }
+
+    /// The event loop's message hook; `true` if `msg` was an accelerator.
+    pub fn translate(msg: *const c_void) -> bool {
+        let msg = unsafe { &*(msg as *const MSG) };
+        if msg.message != WM_KEYDOWN && msg.message != WM_SYSKEYDOWN {
+            return false;
+        }
+        let Some((hwnd, haccel)) = MENU.with(|slot| {
+            slot.borrow()
+                .as_ref()
+                .map(|(hwnd, menu)| (*hwnd, menu.haccel()))
+        }) else {
+            return false;
+        };
+        let translated =
+            unsafe { TranslateAcceleratorW(HWND(hwnd as _), HACCEL(haccel as _), msg) } != 0;
+        if translated {
+            super::native_hit();
+        }
+        translated
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use gtk::{hook, matches};
+
+/// Native accelerators need no hook of ours here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn hook(_window: &tao::window::Window, _accelerators: &crate::utils::ArcMut<Accelerators>) {}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod gtk {
+    use gtk::{gdk::ModifierType, glib::Propagation, prelude::WidgetExt};
+    use tao::{platform::unix::WindowExtUnix, window::Window};
+
+    use super::{Accelerators, Combo};
+    use crate::utils::ArcMut;
+
+    /// Notes key presses matching a registered accelerator, which the
+    /// window's accel group activates right after.
+    pub fn hook(window: &Window, accelerators: &ArcMut<Accelerators>) {
+        let accelerators = accelerators.clone();
+        window
+            .gtk_window()
+            .connect_key_press_event(move |_, event| {
+                let state = event.state();
+                let name = event.keyval().name().map(|name| name.to_string());
+                let matched = crate::lock_force!(accelerators)
+                    .combos
+                    .iter()
+                    .any(|combo| matches(combo, state, name.as_deref()));
+                if matched {
+                    super::native_hit();
+                }
+                Propagation::Proceed
+            });
+    }
+
+    /// Whether a key press with `state` and keyval `name` is `combo`.
+    pub fn matches(combo: &Combo, state: ModifierType, name: Option<&str>) -> bool {
+        let Some(name) = name else {
+            return false;
+        };
+        let key = match (&combo.code, &combo.key) {
+            (Some(code), _) => code.trim_start_matches("Key").trim_start_matches("Digit"),
+            (None, Some(key)) => key.as_str(),
+            (None, None) => return false,
+        };
+        combo.ctrl == state.contains(ModifierType::CONTROL_MASK)
+            && combo.alt == state.contains(ModifierType::MOD1_MASK)
+            && combo.shift == state.contains(ModifierType::SHIFT_MASK)
+            && combo.meta == state.intersects(ModifierType::SUPER_MASK | ModifierType::META_MASK)
+            && gdk_name(key).eq_ignore_ascii_case(name)
+    }
+
+    /// GDK's keyval name for a DOM key name.
+    fn gdk_name(key: &str) -> &str {
+        match key {
+            "ArrowUp" => "Up",
+            "ArrowDown" => "Down",
+            "ArrowLeft" => "Left",
+            "ArrowRight" => "Right",
+            "Enter" => "Return",
+            " " => "space",
+            "Backspace" => "BackSpace",
+            "PageUp" => "Page_Up",
+            "PageDown" => "Page_Down",
+            "+" => "plus",
+            _ => key,
+        }
+    }
+}
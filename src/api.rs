@@ -1,15 +1,1447 @@
 use anyhow::Result;
 use pyframe_macros::api;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 
-use crate::api_manager::ApiManager;
+use crate::{
+    api_manager::{ApiManager, Deferred},
+    context::AppContext,
+    http_mock::MockId,
+    lock,
+    process_stats::ProcessStats,
+    request_context::RequestContext,
+    timers::{TimerCall, TimerId},
+    window_commands::WindowCommand,
+};
 
+/// Sets the window title; under `sync_title`, `document.title` is ignored
+/// until the page navigates.
 #[api]
 fn set_title(title: String) -> Result<bool> {
-    let window = ctx.get_window()?;
+    let window = ctx.target_window(&req)?;
     window.set_title(&title);
+    lock!(ctx.title_sync)?.pause();
+    Ok(true)
+}
+
+/// Replaces the WebView's content blocking rules (WebKit Content Blocker JSON).
+///
+/// Returns `true` when the browser engine enforces the rules natively and
+/// `false` when only the navigation handler fallback applies.
+#[api]
+fn set_content_blocking_rules(rules_json: String) -> Result<bool> {
+    let webview = ctx.get_webview()?;
+    let mut blocker = lock!(ctx.content_blocker)?;
+    blocker.set_rules(&rules_json)?;
+    blocker.apply_native(&webview)
+}
+
+/// Returns the active content blocking rules, or `null` if none were set.
+#[api]
+fn get_content_blocking_rules() -> Result<Value> {
+    let blocker = lock!(ctx.content_blocker)?;
+    match blocker.rules_json() {
+        Some(json) => Ok(serde_json::from_str(json)?),
+        None => Ok(Value::Null),
+    }
+}
+
+/// CPU and memory usage of the Rust process, refreshed every 500 ms.
+#[api]
+fn get_process_stats() -> Result<ProcessStats> {
+    lock!(ctx.process_monitor)?.stats()
+}
+
+/// Sets the event loop's tick interval in milliseconds, or goes back to
+/// plain `Wait` with `null`. Returns `false` for an interval of `0`.
+#[api]
+fn set_tick_interval(ms: Option<u64>) -> Result<bool> {
+    let interval = match ms {
+        Some(0) => return Ok(false),
+        ms => ms.map(std::time::Duration::from_millis),
+    };
+    let mut ticker = lock!(ctx.ticker)?;
+    ticker.set_interval(interval);
+    *flow = ticker.control_flow();
+    Ok(true)
+}
+
+fn start_timer(
+    ctx: &crate::context::AppContext,
+    ms: u64,
+    repeat: bool,
+    api: Option<String>,
+    args: Option<Value>,
+) -> Result<TimerId> {
+    if repeat && ms == 0 {
+        anyhow::bail!("Interval must be at least 1 ms");
+    }
+    let call = api.map(|method| TimerCall {
+        method,
+        args: args.unwrap_or(Value::Null),
+    });
+    Ok(lock!(ctx.timers)?.start(
+        &ctx.rt,
+        ctx.proxy.clone(),
+        std::time::Duration::from_millis(ms),
+        repeat,
+        call,
+    ))
+}
+
+/// Fires `pyframe:timer` once after `ms` milliseconds and, if `api` is given,
+/// calls that API with `args`. Returns the timer id.
+#[api]
+fn set_timeout(ms: u64, api: Option<String>, args: Option<Value>) -> Result<TimerId> {
+    start_timer(&ctx, ms, false, api, args)
+}
+
+/// Like `set_timeout`, but fires every `ms` milliseconds until cleared.
+#[api]
+fn set_interval(ms: u64, api: Option<String>, args: Option<Value>) -> Result<TimerId> {
+    start_timer(&ctx, ms, true, api, args)
+}
+
+/// Cancels a timeout or interval. Returns `false` if it was not live.
+#[api]
+fn clear_timer(id: TimerId) -> Result<bool> {
+    Ok(lock!(ctx.timers)?.clear(id))
+}
+
+/// Size of the WebView's viewport in CSS pixels, as `[width, height]`, of
+/// the window with `window_id` from `get_window_state`, or else the
+/// request's target window.
+#[api]
+fn get_viewport_size(window_id: Option<u64>) -> Result<Deferred> {
+    let webview = match window_id {
+        Some(window_id) => ctx.get_webview_by_id(window_id)?,
+        None => ctx.target_webview(&req)?,
+    };
+    let responder = ctx.responder(&req);
+    ctx.eval_js_in(
+        &webview,
+        "[window.innerWidth, window.innerHeight]",
+        move |result| {
+            responder.send(result.and_then(|size| Ok(serde_json::from_value::<(u32, u32)>(size)?)));
+        },
+    )?;
+    Ok(Deferred)
+}
+
+/// Hides the window contents from screenshots and screen recordings.
+///
+/// | Platform | Mechanism |
+/// |----------|-----------|
+/// | Windows  | `SetWindowDisplayAffinity(WDA_EXCLUDEFROMCAPTURE)` |
+/// | macOS    | `NSWindow.sharingType = .none` |
+/// | Linux, iOS, Android | unsupported, returns `false` |
+#[api]
+fn set_content_protection(enabled: bool) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    window.set_content_protection(enabled);
+    Ok(cfg!(any(target_os = "windows", target_os = "macos")))
+}
+
+/// Evaluates `script` on the event loop thread and returns its result.
+#[api]
+fn run_on_main_js(script: String) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    ctx.eval_js(&script, move |result| responder.send(result))?;
+    Ok(Deferred)
+}
+
+/// Opens a file from `allowed_read_paths` with the system's default application.
+#[api]
+fn open_file_with_default_app(path: String) -> Result<bool> {
+    let path = crate::fs_scope::resolve_allowed(&ctx.config.allowed_read_paths, path.as_ref())?;
+    crate::shell::open_with_default_app(&path)?;
+    Ok(true)
+}
+
+/// Opens a URL, or a file or directory in `fs_scope`, with its default
+/// handler. URL schemes must be listed in `open_schemes`.
+#[api]
+fn shell_open(target: String) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    let scope = ctx.config.fs_scope.clone();
+    let schemes = ctx.config.open_schemes();
+    crate::tasks::spawn_blocking_on("shell_open", &ctx.rt, move || {
+        responder.send(crate::shell::open_target(&target, &scope, &schemes))
+    });
+    Ok(Deferred)
+}
+
+/// Focus, visibility and size state of the window.
+///
+/// `occluded` is `true` while the window is minimized; tao doesn't report
+/// windows covered by others.
+#[api]
+fn get_window_state() -> Result<crate::window_events::WindowStatus> {
+    let window = ctx.target_window(&req)?;
+    Ok(crate::window_events::WindowStatus::of(
+        &window,
+        &ctx.label_of(&window),
+    ))
+}
+
+/// Runs an allowed program off the event loop and captures its output.
+#[api]
+fn exec_command(
+    command: String,
+    args: Vec<String>,
+    stdin: Option<String>,
+    timeout_ms: u64,
+) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    let allowed = ctx.config.allowed_commands.clone();
+    let timeout = std::time::Duration::from_millis(timeout_ms);
+    crate::tasks::spawn_on("exec_command", &ctx.rt, async move {
+        let result = crate::exec::exec_command(&allowed, command, args, stdin, timeout).await;
+        responder.send(result);
+    });
+    Ok(Deferred)
+}
+
+/// Starts an allowed program and returns its pid. Its output arrives as
+/// `process-output` events, one per line, followed by `process-exit`.
+#[api]
+fn process_spawn(
+    command: String,
+    args: Vec<String>,
+    options: Option<crate::processes::SpawnOptions>,
+) -> Result<crate::processes::Pid> {
+    lock!(ctx.processes)?.spawn(
+        &ctx.config.allowed_commands,
+        command,
+        args,
+        options.unwrap_or_default(),
+        &ctx.rt,
+        ctx.proxy.clone(),
+    )
+}
+
+/// Kills a child of `process_spawn`. Returns `false` if it isn't running.
+#[api]
+fn process_kill(pid: crate::processes::Pid) -> Result<bool> {
+    Ok(lock!(ctx.processes)?.kill(pid))
+}
+
+/// Answers a pending `close-requested` event: `true` closes the window,
+/// `false` keeps it open. Returns `false` if no close was pending.
+#[api]
+fn confirm_close(allow: bool) -> Result<bool> {
+    if !lock!(ctx.close_guard)?.resolve() {
+        return Ok(false);
+    }
+    if allow {
+        ctx.proxy
+            .send_event(crate::utils::UserEvent::Exit)
+            .map_err(|_| anyhow::anyhow!("Event loop has exited"))?;
+    }
+    Ok(true)
+}
+
+/// Adds an exit hook run by the caller: when it's its turn, an `exit-hook`
+/// event `{id, name}` goes to the pages and `pyframe.events()`, and exit
+/// waits up to `timeout_ms` (default
+/// 2000) for `exit_hook_done(id)`. Hooks run newest first, see
+/// [`crate::shutdown_hooks`].
+#[api(replay = false)]
+fn register_exit_hook(
+    name: String,
+    timeout_ms: Option<u64>,
+) -> Result<crate::shutdown_hooks::HookId> {
+    let timeout = timeout_ms.map_or(
+        crate::shutdown_hooks::DEFAULT_HOOK_TIMEOUT,
+        std::time::Duration::from_millis,
+    );
+    let proxy = ctx.proxy.clone();
+    let hook_name = name.clone();
+    let mut hooks = lock!(ctx.exit_hooks)?;
+    Ok(hooks.register_remote(name, timeout, move |id| {
+        let _ = proxy.send_event(crate::utils::UserEvent::ExitHook(id, hook_name.clone()));
+    }))
+}
+
+/// Ends the `exit-hook` with this id, so exit goes on; called before its
+/// turn, the hook won't wait. Returns `false` if it's unknown or done.
+#[api]
+fn exit_hook_done(id: crate::shutdown_hooks::HookId) -> Result<bool> {
+    Ok(lock!(ctx.exit_hooks)?.done(id))
+}
+
+/// Removes an exit hook. Returns `false` if there is none with this id.
+#[api]
+fn remove_exit_hook(id: crate::shutdown_hooks::HookId) -> Result<bool> {
+    Ok(lock!(ctx.exit_hooks)?.deregister(id))
+}
+
+/// Shows the window on every virtual desktop.
+///
+/// | Platform | Mechanism |
+/// |----------|-----------|
+/// | macOS    | `NSWindowCollectionBehaviorCanJoinAllSpaces` |
+/// | Linux    | `_NET_WM_STATE_STICKY` (`gtk_window_stick`) |
+/// | Windows  | no public API; the window is made `HWND_TOPMOST` via `SetWindowPos` instead |
+/// | iOS, Android | unsupported, returns `false` |
+#[api]
+fn set_visible_on_all_spaces(enabled: bool) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    #[cfg(target_os = "windows")]
+    window.set_always_on_top(enabled);
+    #[cfg(not(target_os = "windows"))]
+    window.set_visible_on_all_workspaces(enabled);
+    Ok(cfg!(any(
+        target_os = "macos",
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))
+}
+
+/// Turns minimize-to-tray on or off. Turning it off removes the tray icon
+/// and brings a hidden window back.
+#[api]
+fn set_minimize_to_tray(enabled: bool) -> Result<bool> {
+    lock!(ctx.tray)?.set_minimize_to_tray(enabled, ctx.config.tray_icon.as_deref())?;
+    if !enabled {
+        let window = ctx.get_window()?;
+        if !window.is_visible() {
+            crate::tray::restore_from_tray(&window);
+        }
+    }
+    Ok(true)
+}
+
+/// Replaces the tray menu with `items`, showing the tray icon if needed.
+/// Chosen items arrive as `tray-menu` events; the ids `pyframe.tray.show`
+/// and `pyframe.tray.quit` keep their default actions.
+#[api]
+fn set_tray_menu(items: Vec<crate::menu::ItemSpec>) -> Result<bool> {
+    lock!(ctx.tray)?.set_menu(items, ctx.config.tray_icon.as_deref())?;
+    Ok(true)
+}
+
+/// Changes the label, enabled or checked state of a tray menu item in place.
+#[api]
+fn update_tray_item(id: String, update: crate::menu::ItemUpdate) -> Result<bool> {
+    lock!(ctx.tray)?.update_item(&id, update)?;
+    Ok(true)
+}
+
+/// The `set_tray_menu` items with their current state; `None` if unset.
+#[api]
+fn get_tray_menu() -> Result<Option<Vec<crate::menu::ItemSpec>>> {
+    Ok(lock!(ctx.tray)?.menu_items())
+}
+
+/// Replaces the menu bar, the window's or on macOS the app's, with `items`
+/// whose top-level entries are submenus. Items are defined like
+/// `set_tray_menu` items, with an optional `accelerator`; chosen ones
+/// arrive as `menu` events.
+#[api]
+fn set_menu(items: Vec<crate::menu::ItemSpec>) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    lock!(ctx.menubar)?.set_menu(&window, items)?;
+    crate::accelerators::sync(&ctx)?;
+    Ok(true)
+}
+
+/// Changes the label, enabled or checked state of a menu bar item in place.
+/// `BAD_ARGS` for an unknown id.
+#[api]
+fn update_menu_item(id: String, update: crate::menu::ItemUpdate) -> Result<bool> {
+    lock!(ctx.menubar)?.update_item(&id, update)?;
+    crate::accelerators::sync(&ctx)?;
+    Ok(true)
+}
+
+/// Sets the accelerator of a menu bar item, e.g. `"CmdOrCtrl+Shift+S"`, or
+/// removes it with `None`. `BAD_ARGS` if it doesn't parse.
+#[api]
+fn set_menu_accelerator(id: String, accelerator: Option<String>) -> Result<bool> {
+    lock!(ctx.menubar)?.set_accelerator(&id, accelerator)?;
+    crate::accelerators::sync(&ctx)?;
+    Ok(true)
+}
+
+/// The `set_menu` items with their current state; `None` if unset.
+#[api]
+fn get_menu_state() -> Result<Option<Vec<crate::menu::ItemSpec>>> {
+    Ok(lock!(ctx.menubar)?.state())
+}
+
+/// Replaces the diagnostics filter, e.g. `"pygcc=info,pygcc::connections=debug"`,
+/// until the next call or restart. Returns the previous one; `BAD_ARGS` if
+/// `spec` doesn't parse.
+#[api]
+fn set_log_filter(spec: String) -> Result<String> {
+    crate::diagnostics::set_filter(&spec)
+}
+
+/// The newest audited calls matching `filter`, oldest first, with how many
+/// entries were `dropped` before reaching the file. Fails unless the app
+/// was started with the `audit` option.
+#[api]
+fn audit_query(filter: Option<crate::audit::AuditFilter>) -> Result<serde_json::Value> {
+    let audit = ctx
+        .audit
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("The audit option is not set"))?;
+    Ok(audit.query(&filter.unwrap_or_default()))
+}
+
+/// How menu bar accelerators were delivered, for when one doesn't fire:
+/// the registered ones as pages match them, whether this platform has a
+/// native hook, and the latest deliveries with their `path`, `native` or
+/// `script`, and whether they were `dropped` as duplicates.
+#[api]
+fn get_accelerator_log() -> Result<serde_json::Value> {
+    Ok(lock!(ctx.accelerators)?.describe())
+}
+
+/// Replaces the macOS dock menu with `items`, defined like `set_tray_menu`
+/// items. Chosen items arrive as `dock-menu` events; `UNSUPPORTED`
+/// elsewhere.
+#[api]
+fn set_dock_menu(items: Vec<crate::menu::ItemSpec>) -> Result<bool> {
+    lock!(ctx.dock)?.set_menu(items)?;
+    Ok(true)
+}
+
+/// Sets whether the app shows in the dock and the Cmd+Tab switcher:
+/// `regular`, `accessory` or `prohibited`. `UNSUPPORTED` outside macOS.
+#[api]
+fn set_activation_policy(policy: crate::dock::ActivationPolicy) -> Result<bool> {
+    crate::dock::set_activation_policy(target, policy)?;
+    Ok(true)
+}
+
+/// Sets the macOS tab group of the window (`NSWindow.tabbingIdentifier`).
+/// Returns `false` on other platforms.
+#[api]
+fn set_tabbing_identifier(identifier: String) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    Ok(crate::tabbing::set_tabbing_identifier(&window, &identifier))
+}
+
+/// Opens another window with `{label, title, url, html, width, height,
+/// monitor, position_strategy}` and answers with its `id`, the one of
+/// `get_window_state` that `window_id` metadata and `close_window` take, its
+/// `label` and the `monitor` it landed on. Only an `html` page gets the
+/// bridge to the native APIs. Its window events carry its `label`, which
+/// must not be taken by another open window. Emits `window-opened` with the
+/// same, and `window-closed` when it closes.
+#[api(replay = false)]
+fn open_window(options: crate::window_commands::WindowOptions) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    let command = WindowCommand::Open(options, Box::new(move |id| responder.send(id)));
+    ctx.proxy
+        .send_event(crate::utils::UserEvent::WindowCommand(Box::new(command)))
+        .map_err(|_| anyhow::anyhow!("Event loop has exited"))?;
+    Ok(Deferred)
+}
+
+/// Closes a window; the first one exits the app like its close button.
+/// Returns `false` if there is no such window.
+#[api]
+fn close_window(window_id: u64) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    let command = WindowCommand::Close(window_id, Box::new(move |closed| responder.send(closed)));
+    ctx.proxy
+        .send_event(crate::utils::UserEvent::WindowCommand(Box::new(command)))
+        .map_err(|_| anyhow::anyhow!("Event loop has exited"))?;
+    Ok(Deferred)
+}
+
+/// Adds the window `window_id` (the `id` from `get_window_state`) as a tab
+/// of this window (`addTabbedWindow:ordered:`). Returns `false` on
+/// platforms other than macOS.
+#[api]
+fn add_tab_window(window_id: u64) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    let tab = ctx.get_window_by_id(window_id)?;
+    Ok(crate::tabbing::add_tab_window(&window, &tab))
+}
+
+/// Gives the window a frosted-glass background: `"ultra_thin"`, `"thin"`,
+/// `"medium"`, `"thick"` or `"none"`. Needs the `transparent` option.
+/// Returns `false` where vibrancy isn't supported.
+#[api]
+fn set_vibrancy(material: String) -> Result<bool> {
+    let material = crate::vibrancy::Material::parse(&material)?;
+    let window = ctx.target_window(&req)?;
+    crate::vibrancy::set_vibrancy(&window, material)
+}
+
+/// Deletes the saved window state, so the next launch uses the default
+/// size and position. Returns `false` without `persist_window_state`.
+#[api]
+fn reset_window_state() -> Result<bool> {
+    match lock!(ctx.window_state)?.as_mut() {
+        Some(store) => store.reset().map(|()| true),
+        None => Ok(false),
+    }
+}
+
+/// Moves and resizes the window embedded with `parent_handle`, see
+/// [`crate::embed::Bounds`]. For the host to call when the parent resizes.
+#[api]
+fn set_webview_bounds(bounds: crate::embed::Bounds) -> Result<bool> {
+    if ctx.config.parent_handle.is_none() {
+        anyhow::bail!("set_webview_bounds needs the parent_handle option");
+    }
+    let window = ctx.get_window()?;
+    crate::embed::set_bounds(&window, bounds)?;
+    Ok(true)
+}
+
+/// The window's platform handle as `{kind, window, display}`, for native
+/// renderers drawing into it. Valid until the window closes.
+#[api]
+fn native_handle() -> Result<crate::native_handle::NativeHandle> {
+    let window = ctx.target_window(&req)?;
+    crate::native_handle::of(&window)
+}
+
+/// Searches the page for `text` and highlights the matches; counts arrive
+/// as `find-result` events. Returns `"native"` or `"script"`, see
+/// [`crate::find`].
+#[api(replay = false)]
+fn find_in_page(
+    text: String,
+    options: Option<crate::find::FindOptions>,
+) -> Result<crate::find::Backend> {
+    let webview = ctx.target_webview(&req)?;
+    let window = AppContext::window_number(ctx.target_window(&req)?.id());
+    lock!(ctx.finds)?.start(
+        &webview,
+        window,
+        text,
+        options.unwrap_or_default(),
+        &ctx.proxy,
+    )
+}
+
+/// Highlights the next match of the last `find_in_page`, or the previous
+/// one if `backwards`. `false` without a search.
+#[api(replay = false)]
+fn find_next(backwards: Option<bool>) -> Result<bool> {
+    let webview = ctx.target_webview(&req)?;
+    let window = AppContext::window_number(ctx.target_window(&req)?.id());
+    lock!(ctx.finds)?.next(&webview, window, backwards.unwrap_or(false), &ctx.proxy)
+}
+
+/// Ends the search and removes its highlights; `false` without one.
+#[api]
+fn stop_find() -> Result<bool> {
+    let webview = ctx.target_webview(&req)?;
+    let window = AppContext::window_number(ctx.target_window(&req)?.id());
+    lock!(ctx.finds)?.stop(&webview, window)
+}
+
+/// Answers a `permission-requested` event. With `remember`, the answer
+/// decides later requests of the same kind from the origin.
+#[api(replay = false)]
+fn grant_permission(id: u64, allow: bool, remember: Option<bool>) -> Result<bool> {
+    let request = lock!(ctx.permissions)?.answer(
+        id,
+        allow,
+        remember.unwrap_or(false),
+        &mut *lock!(ctx.kv_store)?,
+        std::time::Instant::now(),
+    )?;
+    ctx.resolve_permission(&request, allow, crate::permissions::Reason::Answered)?;
+    Ok(true)
+}
+
+/// Moves the position `navigator.geolocation` reports under
+/// `mock_geolocation`, pushing it to every page's watchers. `accuracy` and
+/// `altitude` are in meters, `heading` in degrees, `speed` in m/s.
+#[api]
+fn set_mock_location(
+    latitude: f64,
+    longitude: f64,
+    accuracy: f64,
+    altitude: Option<f64>,
+    heading: Option<f64>,
+    speed: Option<f64>,
+) -> Result<bool> {
+    if !ctx.config.mock_geolocation {
+        anyhow::bail!("set_mock_location needs the mock_geolocation option");
+    }
+    let coords = crate::geolocation::Coordinates {
+        latitude,
+        longitude,
+        accuracy,
+        altitude,
+        altitude_accuracy: altitude.map(|_| accuracy),
+        heading,
+        speed,
+    };
+    coords.validate()?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis() as u64;
+    let position = crate::geolocation::Position { coords, timestamp };
+    ctx.emit_all("geolocation-position", serde_json::json!(position))?;
+    *lock!(ctx.geolocation)? = Some(position);
+    Ok(true)
+}
+
+/// Whether the page is loading, how far, and how its last load went.
+#[api]
+fn get_navigation_state() -> Result<crate::navigation::NavigationState> {
+    Ok(lock!(ctx.navigation)?.clone())
+}
+
+/// Loads `url` in the window, e.g. to retry after `load-failed`. Subject
+/// to kiosk `allowed_urls` and content blocking like any navigation.
+#[api(replay = false)]
+fn navigate(url: String) -> Result<bool> {
+    ctx.target_webview(&req)?.load_url(&url)?;
+    Ok(true)
+}
+
+/// Native items for the context menu under `context_menu: "custom"`,
+/// replacing the last ones; an empty list goes back to no native menu.
+/// `false` if they won't be shown, see [`crate::context_menu`].
+#[api]
+fn set_context_menu_items(items: Vec<crate::context_menu::NativeItem>) -> Result<bool> {
+    Ok(crate::context_menu::set_items(
+        items,
+        ctx.config.context_menu,
+    ))
+}
+
+/// The `permissions` policies, pending requests and remembered answers.
+#[api]
+fn get_permission_policy() -> Result<Value> {
+    let permissions = lock!(ctx.permissions)?;
+    Ok(permissions.describe(&mut *lock!(ctx.kv_store)?))
+}
+
+/// Every API with its `deprecated` warning, if any, and whether retries
+/// with an idempotency key are replayed.
+#[api]
+fn list_apis() -> Result<Vec<crate::api_manager::ApiInfo>> {
+    Ok(ctx.apis.get().cloned().unwrap_or_default())
+}
+
+/// The transport of this webframe, `"tcp"` or `"ipc-only"`, and whether
+/// this build has the TCP bridge at all.
+#[api]
+fn get_transport() -> Result<Value> {
+    Ok(serde_json::json!({
+        "transport": ctx.config.transport,
+        "tcp_available": cfg!(feature = "tcp"),
+    }))
+}
+
+/// The version, process and app id of this webframe, and where it can be
+/// reached: the ports are the bound ones, also when 0 picked them.
+#[api]
+fn app_metadata() -> Result<Value> {
+    let mut metadata = serde_json::to_value(crate::FrameInfo::of(&ctx.config))?;
+    metadata["version"] = env!("CARGO_PKG_VERSION").into();
+    metadata["pid"] = std::process::id().into();
+    metadata["app_id"] = ctx.config.app_id.clone().into();
+    Ok(metadata)
+}
+
+/// Who sent this request, as handlers see it through `RequestContext`: its
+/// connection, 0 in-process, `client`, whether it's `authenticated` by the
+/// launch token, its negotiated `features` and the `trace_id`.
+#[api]
+fn request_context(req: RequestContext) -> Result<Value> {
+    Ok(json!({
+        "connection": req.connection(),
+        "client": req.principal().client,
+        "authenticated": req.principal().authenticated,
+        "features": req.features(),
+        "trace_id": req.trace_id,
+        "window_id": req.window_id,
+        "received_at": req
+            .received_at
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64),
+    }))
+}
+
+/// Connected monitors in the order the `monitor` creation option indexes them.
+#[api]
+fn list_monitors() -> Result<Vec<crate::placement::MonitorInfo>> {
+    let window = ctx.get_window()?;
+    let primary = window.primary_monitor();
+    Ok(window
+        .available_monitors()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let is_primary = primary.as_ref() == Some(&monitor);
+            crate::placement::describe(index, &monitor, is_primary)
+        })
+        .collect())
+}
+
+/// Answers `fetch` calls whose URL matches the regex `url_pattern` with a
+/// canned response. Needs the `enable_mocking` option.
+#[api]
+fn mock_http_request(
+    url_pattern: String,
+    status: u16,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+) -> Result<MockId> {
+    ensure_mocking(&ctx)?;
+    lock!(ctx.mocks)?.add(&url_pattern, status, headers, body)
+}
+
+/// Removes a mock. Returns `false` if it didn't exist.
+#[api]
+fn clear_mock(handle_id: MockId) -> Result<bool> {
+    ensure_mocking(&ctx)?;
+    Ok(lock!(ctx.mocks)?.clear(handle_id))
+}
+
+#[api]
+fn clear_all_mocks() -> Result<bool> {
+    ensure_mocking(&ctx)?;
+    lock!(ctx.mocks)?.clear_all();
+    Ok(true)
+}
+
+fn ensure_mocking(ctx: &AppContext) -> Result<()> {
+    if !ctx.config.enable_mocking {
+        return Err(anyhow::anyhow!(
+            "HTTP mocking is disabled; launch with the enable_mocking option"
+        ));
+    }
+    Ok(())
+}
+
+/// Screenshot of the whole window, title bar and frame included, as `"png"`
+/// or `"jpeg"`. Writes it to `path` if given, otherwise returns it
+/// base64-encoded. On macOS a missing screen recording permission is
+/// answered with `PERMISSION_DENIED`.
+#[api]
+fn capture_window(format: String, path: Option<String>) -> Result<Deferred> {
+    let format = crate::capture::CaptureFormat::parse(&format)?;
+    let window = ctx.target_window(&req)?;
+    let image = crate::capture::capture_window(&window)?;
+    let responder = ctx.responder(&req);
+    crate::tasks::spawn_blocking_on("capture_encode", &ctx.rt, move || {
+        responder.send(crate::capture::encode(image, format, path.map(Into::into)));
+    });
+    Ok(Deferred)
+}
+
+/// Overlays a progress bar on the taskbar button, e.g. for downloads or
+/// builds. `progress` goes from `0.0` to `1.0`.
+#[api]
+fn set_taskbar_progress(
+    progress: f64,
+    state: crate::taskbar::TaskbarProgressState,
+) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    crate::taskbar::set_progress(&window, progress, state)
+}
+
+/// Sets a native background effect: `"mica"`, `"acrylic"` or `"blur"` on
+/// Windows, an `NSVisualEffectView` material such as `"sidebar"` on macOS,
+/// or `"none"` for a solid background. Needs the `transparent` option.
+/// Combinations the platform can't render answer `UNSUPPORTED`.
+#[api]
+fn set_effect(kind: String) -> Result<bool> {
+    let effect = crate::vibrancy::Effect::parse(&kind)?;
+    let window = ctx.target_window(&req)?;
+    let webview = ctx.target_webview(&req)?;
+    crate::vibrancy::set_effect(&window, &webview, effect)?;
+    Ok(true)
+}
+
+/// Rounds the corners of a frameless window by `radius` logical pixels, 0
+/// for square ones, and keeps or drops the system shadow if `shadow` is
+/// given. Returns how it was done; see [`crate::window_shape`] for what
+/// each platform can do.
+#[api]
+fn set_corner_radius(radius: f64, shadow: Option<bool>) -> Result<crate::window_shape::Mechanism> {
+    let window = ctx.target_window(&req)?;
+    lock!(ctx.window_shape)?.set(&window, radius, shadow)
+}
+
+/// Changes the macOS title bar: `titlebar_transparent`, `title_hidden`,
+/// `fullsize_content_view` and `traffic_light_offset`, each if given.
+/// `false` on other platforms.
+#[api]
+fn set_titlebar(options: crate::titlebar::TitlebarUpdate) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    Ok(crate::titlebar::update(&window, &options))
+}
+
+/// Options of the creation call that were accepted but don't apply here.
+#[api]
+fn get_creation_warnings() -> Result<Vec<String>> {
+    let mut warnings = crate::titlebar::creation_warnings(&ctx.config);
+    warnings.extend(crate::dock::creation_warnings(&ctx.config));
+    Ok(warnings)
+}
+
+/// Where the caption buttons are under `titlebar_overlay`; `None` while
+/// it is off, which it always is outside Windows.
+#[api]
+fn get_titlebar_overlay() -> Result<Option<crate::titlebar_overlay::Layout>> {
+    Ok(lock!(ctx.titlebar_overlay)?.layout())
+}
+
+/// Moves the cursor to `(x, y)` in logical pixels within the content area,
+/// clamped to the edge with `clamp` and an error otherwise. Returns where
+/// it went; `UNSUPPORTED` on Wayland.
+#[api(replay = false)]
+fn set_cursor_position(
+    x: f64,
+    y: f64,
+    clamp: Option<bool>,
+) -> Result<crate::cursor::CursorPosition> {
+    let window = ctx.target_window(&req)?;
+    crate::cursor::set_position(&window, x, y, clamp.unwrap_or(false))
+}
+
+/// Confines the cursor to the window, or releases it.
+#[api]
+fn set_cursor_grab(grab: bool) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    crate::cursor::set_grab(&window, grab)?;
+    Ok(true)
+}
+
+/// Shows or hides the cursor over the window.
+#[api]
+fn set_cursor_visible(visible: bool) -> Result<bool> {
+    ctx.target_window(&req)?.set_cursor_visible(visible);
     Ok(true)
 }
 
-pub fn register_api_instances(api_manager: &mut ApiManager) {
-    api_manager.register_api("set_title", set_title);
+/// Passes all mouse input through the window to whatever is beneath it,
+/// except inside the regions set with `set_interactive_regions`.
+#[api]
+fn set_ignore_cursor_events(flag: bool) -> Result<bool> {
+    let window = ctx.get_window()?;
+    lock!(ctx.click_through)?.set_enabled(&window, flag)?;
+    Ok(true)
+}
+
+/// Rectangles `{x, y, width, height}` in logical pixels, relative to the
+/// content area, that stay interactive while `set_ignore_cursor_events` is on.
+#[api]
+fn set_interactive_regions(regions: Vec<crate::click_through::Region>) -> Result<bool> {
+    let window = ctx.get_window()?;
+    lock!(ctx.click_through)?.set_regions(&window, regions)?;
+    Ok(true)
+}
+
+/// Leaves kiosk mode and restores the window. `token` must match the
+/// kiosk `exit_token`; without one configured this always answers
+/// `PERMISSION_DENIED`. Returns `false` if kiosk mode isn't on.
+#[api]
+fn exit_kiosk(token: Option<String>) -> Result<bool> {
+    match lock!(ctx.kiosk)?.as_ref() {
+        Some(kiosk) if kiosk.is_active() => kiosk.authorize(token.as_deref())?,
+        _ => return Ok(false),
+    }
+    ctx.exit_kiosk("api")
+}
+
+/// Plain text (`innerText`) of the element matching the CSS `selector`, or
+/// of the whole body. JavaScript errors, including a selector that matches
+/// nothing, are returned as `JsError`.
+#[api]
+fn extract_text(selector: Option<String>) -> Result<Deferred> {
+    extract(
+        &ctx,
+        &req,
+        selector.as_deref(),
+        "document.body",
+        "innerText",
+    )
+}
+
+/// Markup (`outerHTML`) of the element matching the CSS `selector`, or of
+/// the whole document.
+#[api]
+fn extract_html(selector: Option<String>) -> Result<Deferred> {
+    extract(
+        &ctx,
+        &req,
+        selector.as_deref(),
+        "document.documentElement",
+        "outerHTML",
+    )
+}
+
+/// Fills form fields, keyed by CSS selector, and fires `input` and `change`
+/// on each so frameworks pick the values up. Answers `false` if a selector
+/// matched nothing; the other fields are still filled.
+#[api]
+fn autofill_form(selector_to_value: HashMap<String, String>) -> Result<Deferred> {
+    let js = format!(
+        r#"(() => {{
+  const missing = [];
+  for (const [selector, value] of Object.entries({})) {{
+    const el = document.querySelector(selector);
+    if (!el) {{
+      missing.push(selector);
+      continue;
+    }}
+    // The prototype's setter, so frameworks that track the value see the change.
+    const setter = Object.getOwnPropertyDescriptor(Object.getPrototypeOf(el), "value")?.set;
+    setter ? setter.call(el, value) : (el.value = value);
+    el.dispatchEvent(new Event("input", {{ bubbles: true }}));
+    el.dispatchEvent(new Event("change", {{ bubbles: true }}));
+  }}
+  return missing;
+}})()"#,
+        serde_json::to_string(&selector_to_value)?
+    );
+    let responder = ctx.responder(&req);
+    ctx.eval_js(&js, move |result| {
+        responder.send(result.and_then(|missing| {
+            let missing: Vec<String> = serde_json::from_value(missing)?;
+            if !missing.is_empty() {
+                crate::log_warn!(format!("autofill_form: no element matches {:?}", missing));
+            }
+            Ok(missing.is_empty())
+        }));
+    })?;
+    Ok(Deferred)
+}
+
+/// Adds a `<style>` element with the given `id`, or replaces the CSS of the
+/// one added before, so a theme can be updated without piling up sheets.
+#[api]
+fn inject_css(id: String, css: String) -> Result<Deferred> {
+    let js = format!(
+        "(() => {{ let s = document.getElementById({0}); if (!s) {{ s = document.createElement('style'); s.id = {0}; document.head.appendChild(s); }} s.textContent = {1}; return true; }})()",
+        serde_json::to_string(&id)?,
+        serde_json::to_string(&css)?
+    );
+    eval_bool(&ctx, &req, &js)
+}
+
+/// Removes a style sheet added by `inject_css`. Answers `false` if there
+/// was none with that `id`.
+#[api]
+fn remove_injected_css(id: String) -> Result<Deferred> {
+    let js = format!(
+        "(() => {{ const s = document.getElementById({}); s?.remove(); return !!s; }})()",
+        serde_json::to_string(&id)?
+    );
+    eval_bool(&ctx, &req, &js)
+}
+
+fn eval_bool(ctx: &AppContext, req: &crate::api_manager::ApiRequest, js: &str) -> Result<Deferred> {
+    let responder = ctx.responder(req);
+    ctx.eval_js(js, move |result| {
+        responder.send(result.and_then(|value| Ok(serde_json::from_value::<bool>(value)?)));
+    })?;
+    Ok(Deferred)
+}
+
+fn extract(
+    ctx: &AppContext,
+    req: &crate::api_manager::ApiRequest,
+    selector: Option<&str>,
+    default: &str,
+    property: &str,
+) -> Result<Deferred> {
+    let element = match selector {
+        Some(selector) => {
+            let selector = serde_json::to_string(selector)?;
+            format!(
+                "(document.querySelector({0}) ?? (() => {{ throw new Error(\"No element matches \" + {0}); }})())",
+                selector
+            )
+        }
+        None => default.to_string(),
+    };
+    let responder = ctx.responder(req);
+    ctx.eval_js(&format!("{}.{}", element, property), move |result| {
+        responder.send(result.and_then(|text| Ok(serde_json::from_value::<String>(text)?)));
+    })?;
+    Ok(Deferred)
+}
+
+/// Contents of a file in `fs_scope`; base64 with `binary`.
+#[api]
+fn fs_read_file(path: String, binary: bool) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::read_file(scope, &path, binary)
+    })
+}
+
+/// Writes `contents` (base64 with `binary`) to a file in `fs_scope`,
+/// replacing it unless `append`.
+#[api]
+fn fs_write_file(path: String, contents: String, binary: bool, append: bool) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::write_file(scope, &path, contents, binary, append)
+    })
+}
+
+/// Writes the request's binary attachment to a file in `fs_scope`,
+/// replacing it.
+#[api]
+fn fs_write_file_binary(path: String, data: bytes::Bytes) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::write_bytes(scope, &path, &data, false)
+    })
+}
+
+/// Entries of a directory in `fs_scope`, sorted by name.
+#[api]
+fn fs_read_dir(path: String) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::read_dir(scope, &path)
+    })
+}
+
+#[api]
+fn fs_exists(path: String) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| crate::fs_api::exists(scope, &path))
+}
+
+#[api]
+fn fs_remove(path: String, recursive: bool) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::remove(scope, &path, recursive)
+    })
+}
+
+#[api]
+fn fs_create_dir(path: String, recursive: bool) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::create_dir(scope, &path, recursive)
+    })
+}
+
+#[api]
+fn fs_copy(from: String, to: String) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::copy(scope, &from, &to)
+    })
+}
+
+#[api]
+fn fs_rename(from: String, to: String) -> Result<Deferred> {
+    fs_task(&ctx, &req, move |scope| {
+        crate::fs_api::rename(scope, &from, &to)
+    })
+}
+
+/// Watches a file or directory in `fs_scope`. Changes arrive as
+/// `fs-change` events `{id, changes: [{kind, path}]}`, with `kind` one of
+/// `created`, `modified` and `removed`. The watcher ends with `fs_unwatch`
+/// or when the calling connection closes.
+#[api(replay = false)]
+fn fs_watch(path: String, recursive: bool) -> Result<crate::fs_watch::WatcherId> {
+    let connection = ctx.connection_of(&req);
+    lock!(ctx.fs_watchers)?.watch(
+        ctx.config.fs_scope.clone(),
+        &path,
+        recursive,
+        connection,
+        &ctx.rt,
+        ctx.proxy.clone(),
+    )
+}
+
+#[api]
+fn fs_unwatch(id: crate::fs_watch::WatcherId) -> Result<bool> {
+    Ok(lock!(ctx.fs_watchers)?.unwatch(id))
+}
+
+/// Task and thread counts of the app's Tokio runtime, e.g. to spot
+/// runaway background tasks.
+#[api]
+fn get_runtime_metrics() -> Result<crate::runtime_metrics::RuntimeMetrics> {
+    Ok(crate::runtime_metrics::RuntimeMetrics::of(
+        &ctx.rt, &ctx.lanes, &ctx.emits,
+    ))
+}
+
+/// Runs a file operation on the blocking pool. Paths outside `fs_scope`
+/// answer `PERMISSION_DENIED`.
+fn fs_task<T, F>(ctx: &AppContext, req: &crate::api_manager::ApiRequest, f: F) -> Result<Deferred>
+where
+    T: serde::Serialize,
+    F: FnOnce(&crate::fs_scope::FsScope) -> Result<T> + Send + 'static,
+{
+    let responder = ctx.responder(req);
+    let scope = ctx.config.fs_scope.clone();
+    crate::tasks::spawn_blocking_on("fs", &ctx.rt, move || responder.send(f(&scope)));
+    Ok(Deferred)
+}
+
+/// An environment variable listed in `env_access`, or `null` if unset.
+#[api]
+fn env_get(name: String) -> Result<Option<crate::env_access::EnvValue>> {
+    ctx.config.env_access.get(&name)
+}
+
+/// The variables `env_access` exposes, optionally only those starting with
+/// `prefix`.
+#[api]
+fn env_all(
+    prefix: Option<String>,
+) -> Result<std::collections::BTreeMap<String, crate::env_access::EnvValue>> {
+    Ok(ctx.config.env_access.all(prefix.as_deref().unwrap_or("")))
+}
+
+/// The native process's command-line arguments, if `env_access.args` is set.
+#[api]
+fn env_args() -> Result<Vec<crate::env_access::EnvValue>> {
+    ctx.config.env_access.args()
 }
+
+/// What `env_access` exposes and whether the environment is filtered.
+#[api]
+fn env_info() -> Result<crate::env_access::EnvInfo> {
+    Ok(ctx.config.env_access.info())
+}
+
+fn store_changed(ctx: &AppContext, key: Option<String>) -> Result<()> {
+    ctx.proxy
+        .send_event(crate::utils::UserEvent::StoreChanged(key))
+        .map_err(|_| anyhow::anyhow!("Event loop has exited"))
+}
+
+/// A value of the persistent store, or `null` if unset.
+#[api]
+fn store_get(key: String) -> Result<Option<Value>> {
+    lock!(ctx.kv_store)?.get(&key)
+}
+
+/// Stores `value` under `key` and emits `store-changed` if it changed.
+#[api]
+fn store_set(key: String, value: Value) -> Result<bool> {
+    let changed = lock!(ctx.kv_store)?.set(key.clone(), value, std::time::Instant::now())?;
+    if changed {
+        store_changed(&ctx, Some(key))?;
+    }
+    Ok(true)
+}
+
+/// Removes `key`. Returns `false` if it wasn't set.
+#[api]
+fn store_delete(key: String) -> Result<bool> {
+    let deleted = lock!(ctx.kv_store)?.delete(&key, std::time::Instant::now())?;
+    if deleted {
+        store_changed(&ctx, Some(key))?;
+    }
+    Ok(deleted)
+}
+
+/// Keys of the persistent store, sorted.
+#[api]
+fn store_keys() -> Result<Vec<String>> {
+    lock!(ctx.kv_store)?.keys()
+}
+
+/// Removes every key; `store-changed` is emitted with a `null` key.
+#[api]
+fn store_clear() -> Result<bool> {
+    let cleared = lock!(ctx.kv_store)?.clear(std::time::Instant::now())?;
+    if cleared {
+        store_changed(&ctx, None)?;
+    }
+    Ok(cleared)
+}
+
+/// Builds the dialog modal to the `parent` option's window, or the
+/// request's target window.
+fn dialog(
+    ctx: &AppContext,
+    req: &crate::api_manager::ApiRequest,
+    options: &crate::dialog::DialogOptions,
+    kind: crate::dialog::DialogKind,
+) -> Result<rfd::AsyncFileDialog> {
+    options.validate(kind)?;
+    let parent = match options.parent {
+        Some(id) => ctx.get_window_by_id(id)?,
+        None => ctx.target_window(req)?,
+    };
+    Ok(options.build(&parent))
+}
+
+/// Answers `req` once the dialog opened on the event loop thread closes.
+fn dialog_task<T, F>(
+    ctx: &AppContext,
+    req: &crate::api_manager::ApiRequest,
+    picked: F,
+) -> Result<Deferred>
+where
+    T: serde::Serialize,
+    F: std::future::Future<Output = Result<T>> + Send + 'static,
+{
+    let responder = ctx.responder(req);
+    crate::tasks::spawn_on(
+        "dialog",
+        &ctx.rt,
+        async move { responder.send(picked.await) },
+    );
+    Ok(Deferred)
+}
+
+/// Lets the user pick files. Resolves to their canonical paths, or `null`
+/// if the dialog was cancelled.
+#[api]
+fn dialog_open(options: Option<crate::dialog::DialogOptions>) -> Result<Deferred> {
+    let options = options.unwrap_or_default();
+    let dialog = dialog(&ctx, &req, &options, crate::dialog::DialogKind::Open)?;
+    if options.multiple {
+        let picked = dialog.pick_files();
+        dialog_task(&ctx, &req, async move {
+            crate::dialog::normalize_all(picked.await)
+        })
+    } else {
+        let picked = dialog.pick_file();
+        dialog_task(&ctx, &req, async move {
+            crate::dialog::normalize_all(picked.await.map(|file| vec![file]))
+        })
+    }
+}
+
+/// Asks where to save a file. Resolves to the canonical path, or `null` if
+/// the dialog was cancelled.
+#[api]
+fn dialog_save(options: Option<crate::dialog::DialogOptions>) -> Result<Deferred> {
+    let options = options.unwrap_or_default();
+    let picked = dialog(&ctx, &req, &options, crate::dialog::DialogKind::Save)?.save_file();
+    dialog_task(&ctx, &req, async move {
+        crate::dialog::normalize_one(picked.await)
+    })
+}
+
+/// Lets the user pick folders, like `dialog_open`.
+#[api]
+fn dialog_folder(options: Option<crate::dialog::DialogOptions>) -> Result<Deferred> {
+    let options = options.unwrap_or_default();
+    let dialog = dialog(&ctx, &req, &options, crate::dialog::DialogKind::Folder)?;
+    if options.multiple {
+        let picked = dialog.pick_folders();
+        dialog_task(&ctx, &req, async move {
+            crate::dialog::normalize_all(picked.await)
+        })
+    } else {
+        let picked = dialog.pick_folder();
+        dialog_task(&ctx, &req, async move {
+            crate::dialog::normalize_all(picked.await.map(|folder| vec![folder]))
+        })
+    }
+}
+
+/// Installed font families with their weights and styles, sorted by name.
+/// The first call enumerates the system fonts off the event loop.
+#[api]
+fn fonts_list() -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    crate::tasks::spawn_blocking_on("fonts", &ctx.rt, move || {
+        responder.send(Ok(crate::fonts::families()))
+    });
+    Ok(Deferred)
+}
+
+/// Whether a font family is installed, ignoring case.
+#[api]
+fn fonts_exists(family: String) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    crate::tasks::spawn_blocking_on("fonts", &ctx.rt, move || {
+        responder.send(Ok(crate::fonts::exists(&family)))
+    });
+    Ok(Deferred)
+}
+
+/// Replaces the clipboard's contents with `text`; `false` where there is
+/// no clipboard.
+#[api]
+fn clipboard_write_text(text: String) -> Result<bool> {
+    crate::clipboard::write_text(&text)
+}
+
+/// The clipboard's text, `null` if it holds none.
+#[api]
+fn clipboard_read_text() -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    crate::clipboard::read_text(move |text| responder.send(text));
+    Ok(Deferred)
+}
+
+/// Puts `html` on the clipboard with `alt_text`, or the text of `html`, for
+/// programs that only paste text. `false` where HTML isn't supported.
+#[api]
+fn clipboard_write_html(html: String, alt_text: Option<String>) -> Result<bool> {
+    crate::clipboard::write_html(&html, alt_text.as_deref())
+}
+
+/// The clipboard's HTML fragment, `null` if it holds none.
+#[api]
+fn clipboard_read_html() -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    crate::clipboard::read_html(move |html| responder.send(html));
+    Ok(Deferred)
+}
+
+/// The paths of files copied in a file manager, `null` if there are none.
+#[api]
+fn clipboard_read_files() -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    crate::clipboard::read_files(move |files| responder.send(files));
+    Ok(Deferred)
+}
+
+/// What the clipboard holds: `"text"`, `"html"`, `"files"` and `"image"`.
+#[api]
+fn clipboard_formats() -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    crate::clipboard::formats(move |formats| responder.send(formats));
+    Ok(Deferred)
+}
+
+/// Drags `items` out of the window: paths in `fs_scope` or `{data,
+/// filename}` contents, see [`crate::drag_out`]. Fails unless the primary
+/// mouse button is held; the page calls it as `pyframe.startDrag` from a
+/// `dragstart` handler, which doesn't wait for the bridge.
+#[api]
+fn start_drag(items: crate::drag_out::DragItems) -> Result<bool> {
+    let window = ctx.target_window(&req)?;
+    crate::drag_out::start(&window, items, &ctx.config.fs_scope, &ctx.rt)
+}
+
+/// An HTTP request made natively, so it isn't subject to the page's CORS
+/// rules. The URL and every redirect must be in `http.allowed_origins`;
+/// connection failures answer `OFFLINE`. With `stream`, the body follows
+/// as `http-chunk` events and a final `http-end`.
+#[api]
+fn http_fetch(request: crate::http_fetch::FetchRequest) -> Result<Deferred> {
+    let responder = ctx.responder(&req);
+    let config = ctx.config.http.clone();
+    let proxy = ctx.proxy.clone();
+    crate::tasks::spawn_on("http_fetch", &ctx.rt, async move {
+        responder.send(crate::http_fetch::fetch(&config, request, proxy).await)
+    });
+    Ok(Deferred)
+}
+
+/// Opens a WebSocket to a URL in `ws_allowed_urls` and returns its id at
+/// once. `ws-open`, `ws-message` (base64 `data` if `binary`), `ws-error`
+/// and a final `ws-close` follow, tagged with the id. The socket closes
+/// when the calling connection does.
+#[api(replay = false)]
+fn ws_connect(url: String, protocols: Option<Vec<String>>) -> Result<crate::ws_client::WsId> {
+    let owner = ctx
+        .connection_of(&req)
+        .map(crate::ws_client::Owner::Connection);
+    lock!(ctx.ws)?.connect(
+        &ctx.config.ws_allowed_urls,
+        &url,
+        &protocols.unwrap_or_default(),
+        owner,
+        &ctx.rt,
+        ctx.proxy.clone(),
+    )
+}
+
+/// Sends a text message, or with `binary` the bytes `data` holds as base64.
+#[api]
+fn ws_send(id: crate::ws_client::WsId, data: String, binary: bool) -> Result<bool> {
+    lock!(ctx.ws)?.send(id, data, binary)?;
+    Ok(true)
+}
+
+/// Starts closing a WebSocket, with close code 1000 unless given.
+#[api]
+fn ws_close(id: crate::ws_client::WsId, code: Option<u16>, reason: Option<String>) -> Result<bool> {
+    lock!(ctx.ws)?.close(id, code, reason)?;
+    Ok(true)
+}
+
+/// A shared state document as `{value, revision}`, or `null` if unset.
+#[api]
+fn state_get(name: String) -> Result<Option<crate::state_store::Document>> {
+    Ok(lock!(ctx.state_store)?.get(&name).cloned())
+}
+
+/// Replaces a shared state document and returns its new revision.
+#[api]
+fn state_set(name: String, value: Value) -> Result<u64> {
+    let change = lock!(ctx.state_store)?.set(name, value)?;
+    state_changed(&ctx, change)
+}
+
+/// Applies a JSON merge patch (RFC 7396) to a shared state document and
+/// returns its new revision.
+#[api]
+fn state_update(name: String, patch: Value) -> Result<u64> {
+    let change = lock!(ctx.state_store)?.update(name, patch)?;
+    state_changed(&ctx, change)
+}
+
+/// Removes a shared state document; `false` if it wasn't set.
+#[api]
+fn state_delete(name: String) -> Result<bool> {
+    let change = lock!(ctx.state_store)?.delete(&name);
+    let deleted = change.is_some();
+    if let Some(change) = change {
+        state_changed(&ctx, change)?;
+    }
+    Ok(deleted)
+}
+
+/// Announces `change` as `state-changed:<name>` and returns its revision.
+fn state_changed(ctx: &AppContext, change: crate::state_store::StateChange) -> Result<u64> {
+    let revision = change.revision;
+    ctx.proxy
+        .send_event(crate::utils::UserEvent::StateChanged(change))
+        .map_err(|_| anyhow::anyhow!("Event loop has exited"))?;
+    Ok(revision)
+}
+
+/// Fetches and verifies the update manifest. Fails with `OFFLINE` if the
+/// server can't be reached and `INVALID_SIGNATURE` if the manifest isn't
+/// signed by the configured key.
+#[api]
+fn updater_check() -> Result<Deferred> {
+    updater(&ctx, &req, crate::updater::UpdaterOp::Check)
+}
+
+/// Downloads this platform's artifact of the update found by
+/// `updater_check`, sending `update-progress` events. A checksum mismatch
+/// fails with `INVALID_SIGNATURE`.
+#[api]
+fn updater_download() -> Result<Deferred> {
+    updater(&ctx, &req, crate::updater::UpdaterOp::Download)
+}
+
+/// Starts the installer for the downloaded update and exits the app. An
+/// AppImage is replaced and started again; an `.msi`, `.exe`, `.pkg`,
+/// `.dmg`, `.deb` or `.rpm` is left to its installer, which may not start
+/// the new version.
+#[api]
+fn updater_install_and_exit() -> Result<Deferred> {
+    updater(&ctx, &req, crate::updater::UpdaterOp::InstallAndExit)
+}
+
+fn updater(
+    ctx: &AppContext,
+    req: &crate::api_manager::ApiRequest,
+    op: crate::updater::UpdaterOp,
+) -> Result<Deferred> {
+    let run = ctx.updater()?.run(op, ctx.proxy.clone());
+    let responder = ctx.responder(req);
+    crate::tasks::spawn_on("updater", &ctx.rt, async move { responder.send(run.await) });
+    Ok(Deferred)
+}
+
+include!(concat!(env!("OUT_DIR"), "/api_registry.rs"));
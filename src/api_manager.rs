@@ -2,17 +2,71 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Weak},
 };
 
-use crate::{context::AppContext, utils::FrameWindowTarget};
+use crate::{
+    context::AppContext,
+    request_context::{Peer, RequestContext},
+    utils::{FrameWindowTarget, PendingMap},
+};
 
 #[allow(dead_code)]
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ApiArguments(Value);
 
 impl ApiArguments {
+    /// Wraps an argument array or an object of named arguments; any other
+    /// value becomes the only element of an array.
+    pub fn new(args: Value) -> Self {
+        match args {
+            Value::Array(_) | Value::Object(_) => Self(args),
+            Value::Null => Self(json!([])),
+            arg => Self(json!([arg])),
+        }
+    }
+    /// The arguments as sent, an array or an object.
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+    /// The arguments as an array in the order of `params`, the parameter
+    /// names and whether each is an `Option`. Named arguments missing from
+    /// an object are `null`, which an `Option` takes as `None`; unknown
+    /// names are ignored, or rejected with `strict`. Arrays pass unchanged.
+    #[allow(dead_code)]
+    pub fn named(&self, params: &[(&str, bool)], strict: bool) -> Result<Self> {
+        let Value::Object(named) = &self.0 else {
+            return Ok(self.clone());
+        };
+        if let Some(unknown) = named
+            .keys()
+            .find(|key| !params.iter().any(|(name, _)| name == key))
+        {
+            let reason = format!(
+                "unknown argument `{}`, expected one of {:?}",
+                unknown,
+                params.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+            );
+            if strict {
+                return Err(anyhow::Error::new(BadArgs { reason }));
+            }
+            tracing::debug!("Ignoring {}", reason);
+        }
+        let mut args = Vec::with_capacity(params.len());
+        for (name, optional) in params {
+            match named.get(*name) {
+                Some(arg) => args.push(arg.clone()),
+                None if *optional => args.push(Value::Null),
+                None => {
+                    return Err(anyhow::Error::new(BadArgs {
+                        reason: format!("missing argument `{}`", name),
+                    }))
+                }
+            }
+        }
+        Ok(Self(Value::Array(args)))
+    }
     #[allow(dead_code)]
     pub fn single<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
         Ok(serde_json::from_value::<(T,)>(self.0.clone())?.0)
@@ -29,44 +83,400 @@ impl ApiArguments {
         Ok(serde_json::from_value(args)?)
     }
 }
+/// Optional fourth element of a request, `[id, method, args, metadata]`.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct RequestMetadata {
+    /// Window the call targets, the `id` from `get_window_state`. Handlers
+    /// using [`AppContext::target_window`] fall back to the first window.
+    pub window_id: Option<u64>,
+    /// Lane the request waits in before it reaches the event loop.
+    pub priority: crate::lanes::Priority,
+    /// Retries with the same key get the first response instead of running
+    /// the handler again, see [`crate::idempotency`].
+    pub idempotency_key: Option<String>,
+    /// Raw bytes sent after the request in a binary frame, read by `Bytes`
+    /// parameters of `#[api]` handlers.
+    #[serde(skip)]
+    pub attachment: Option<bytes::Bytes>,
+    /// Set by [`ApiManager::call`] for deprecated APIs, so every response
+    /// to the request carries the warning.
+    #[serde(skip)]
+    pub deprecation: Option<String>,
+    /// Set when the request is queued, see [`crate::timing`].
+    #[serde(skip)]
+    pub timing: Option<Arc<crate::timing::RequestTiming>>,
+    /// Correlates the request with the client's logs; a new one is made
+    /// for requests without it.
+    pub trace_id: Option<String>,
+    /// Set when the request is queued, see [`crate::request_context`].
+    #[serde(skip)]
+    pub context: Option<Box<RequestContext>>,
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize, Clone)]
-pub struct ApiRequest(pub u8, pub String, pub ApiArguments);
+pub struct ApiRequest(
+    pub u8,
+    pub String,
+    pub ApiArguments,
+    #[serde(default)] pub RequestMetadata,
+);
 
 impl ApiRequest {
     #[allow(dead_code)]
     pub fn err<C: Into<i32>, S: Into<String>>(&self, code: C, msg: S) -> ApiResponse {
-        ApiResponse(self.0, code.into(), msg.into(), json!(null))
+        ApiResponse(
+            self.0,
+            code.into(),
+            msg.into(),
+            json!(null),
+            self.3.deprecation.clone(),
+            None,
+        )
     }
+    /// `()` is sent as `null`. Data that can't be serialized, e.g. a map
+    /// with non-string keys, is answered with [`code::INTERNAL`].
     #[allow(dead_code)]
     pub fn ok<D: Serialize>(&self, data: D) -> ApiResponse {
-        ApiResponse(self.0, 0, "ok".to_string(), json!(data))
+        match serde_json::to_value(data) {
+            Ok(data) => ApiResponse(
+                self.0,
+                code::OK,
+                "ok".to_string(),
+                data,
+                self.3.deprecation.clone(),
+                None,
+            ),
+            Err(e) => self.err(
+                code::INTERNAL,
+                format!("Failed to serialize the result of {}: {}", self.1, e),
+            ),
+        }
     }
     #[allow(dead_code)]
     pub fn args(&self) -> &ApiArguments {
         &self.2
     }
+    pub fn metadata(&self) -> &RequestMetadata {
+        &self.3
+    }
+    /// Who sent the request; an authenticated caller inside the app, `"app"`,
+    /// for requests that weren't queued by a connection.
+    pub fn context(&self) -> RequestContext {
+        self.3
+            .context
+            .as_deref()
+            .cloned()
+            .unwrap_or_else(|| RequestContext::new(Peer::in_process("app"), &self.3))
+    }
+    /// The binary attachment; fails with [`code::BAD_ARGS`] without one.
+    pub fn attachment(&self) -> Result<bytes::Bytes> {
+        self.3.attachment.clone().ok_or_else(|| {
+            anyhow::Error::new(BadArgs {
+                reason: format!("{} needs a binary attachment", self.1),
+            })
+        })
+    }
 }
 
 pub type Code = i32;
+
+/// Response codes shared with the Python client.
+pub mod code {
+    use super::Code;
+
+    pub const OK: Code = 0;
+    pub const ERROR: Code = -1;
+    /// The request was rejected because too many requests are in flight.
+    pub const BUSY: Code = -2;
+    /// The OS denied a permission the API needs; see [`PermissionDenied`](super::PermissionDenied).
+    pub const PERMISSION_DENIED: Code = -3;
+    /// The platform can't do what was asked; see [`Unsupported`](super::Unsupported).
+    pub const UNSUPPORTED: Code = -4;
+    /// A server couldn't be reached; see [`Offline`](super::Offline).
+    pub const OFFLINE: Code = -5;
+    /// Downloaded data failed verification; see [`InvalidSignature`](super::InvalidSignature).
+    pub const INVALID_SIGNATURE: Code = -6;
+    /// The handler succeeded but its result couldn't be serialized.
+    pub const INTERNAL: Code = -7;
+    /// The request's arguments don't fit the API; see [`BadArgs`](super::BadArgs).
+    pub const BAD_ARGS: Code = -8;
+    /// The handler panicked; the app keeps running.
+    pub const HANDLER_PANIC: Code = -9;
+
+    /// The code an API error is answered with.
+    pub fn of(err: &anyhow::Error) -> Code {
+        if err.is::<super::PermissionDenied>() {
+            PERMISSION_DENIED
+        } else if err.is::<super::Unsupported>() {
+            UNSUPPORTED
+        } else if err.is::<super::Offline>() {
+            OFFLINE
+        } else if err.is::<super::InvalidSignature>() {
+            INVALID_SIGNATURE
+        } else if err.is::<super::BadArgs>() {
+            BAD_ARGS
+        } else {
+            ERROR
+        }
+    }
+}
+
+/// The OS denied `permission`, e.g. `"screen_recording"` on macOS. Answered
+/// with [`code::PERMISSION_DENIED`] so clients can tell it from other errors.
+#[derive(Debug)]
+pub struct PermissionDenied {
+    pub permission: &'static str,
+}
+
+impl std::fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Permission denied: {}", self.permission)
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
+/// `feature` isn't available on this platform or OS version. Answered with
+/// [`code::UNSUPPORTED`].
+#[derive(Debug)]
+pub struct Unsupported {
+    pub feature: String,
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not supported on this platform: {}", self.feature)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+/// `url` couldn't be reached: no network, DNS failure or a timeout. Answered
+/// with [`code::OFFLINE`].
+#[derive(Debug)]
+pub struct Offline {
+    pub url: String,
+}
+
+impl std::fmt::Display for Offline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cannot reach {}", self.url)
+    }
+}
+
+impl std::error::Error for Offline {}
+
+/// `what` doesn't match its signature or checksum. Answered with
+/// [`code::INVALID_SIGNATURE`].
+#[derive(Debug)]
+pub struct InvalidSignature {
+    pub what: String,
+}
+
+impl std::fmt::Display for InvalidSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Verification failed: {}", self.what)
+    }
+}
+
+impl std::error::Error for InvalidSignature {}
+
+/// The request can't be handled as sent, e.g. a missing or oversized
+/// attachment. Answered with [`code::BAD_ARGS`].
+#[derive(Debug)]
+pub struct BadArgs {
+    pub reason: String,
+}
+
+impl std::fmt::Display for BadArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bad arguments: {}", self.reason)
+    }
+}
+
+impl std::error::Error for BadArgs {}
+/// `[id, code, msg, data]`, followed by `{"deprecated": warning}` for
+/// deprecated APIs and `{"meta": ...}` for the timing of the request, in
+/// one object if there are both.
 #[allow(dead_code)]
-#[derive(Serialize, Clone)]
-pub struct ApiResponse(pub u8, Code, String, Value);
+#[derive(Clone)]
+pub struct ApiResponse(
+    pub u8,
+    Code,
+    String,
+    Value,
+    Option<String>,
+    Option<crate::timing::Meta>,
+);
+
+impl Serialize for ApiResponse {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeTuple;
+        let extra = self.4.is_some() || self.5.is_some();
+        let mut tuple = serializer.serialize_tuple(if extra { 5 } else { 4 })?;
+        tuple.serialize_element(&self.0)?;
+        tuple.serialize_element(&self.1)?;
+        tuple.serialize_element(&self.2)?;
+        tuple.serialize_element(&self.3)?;
+        if extra {
+            let mut extra = serde_json::Map::new();
+            if let Some(deprecated) = &self.4 {
+                extra.insert("deprecated".into(), json!(deprecated));
+            }
+            if let Some(meta) = &self.5 {
+                extra.insert("meta".into(), json!(meta));
+            }
+            tuple.serialize_element(&extra)?;
+        }
+        tuple.end()
+    }
+}
+
+impl ApiResponse {
+    pub fn code(&self) -> Code {
+        self.1
+    }
+    pub fn msg(&self) -> &str {
+        &self.2
+    }
+    pub fn result(&self) -> &Value {
+        &self.3
+    }
+    pub fn meta(&self) -> Option<&crate::timing::Meta> {
+        self.5.as_ref()
+    }
+    pub fn with_meta(mut self, meta: crate::timing::Meta) -> Self {
+        self.5 = Some(meta);
+        self
+    }
+    /// Drops the `meta`, for connections that didn't ask for it.
+    #[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+    pub fn without_meta(mut self) -> Self {
+        self.5 = None;
+        self
+    }
+}
 #[allow(dead_code)]
-pub type ApiInstance = std::pin::Pin<
-    Box<
-        dyn Fn(
+pub type ApiInstance = Arc<
+    dyn Fn(
             Arc<AppContext>,
             ApiRequest,
             &FrameWindowTarget,
             &mut tao::event_loop::ControlFlow,
-        ) -> Result<ApiResponse>,
-    >,
+        ) -> Result<Option<ApiResponse>>
+        + Send
+        + Sync,
 >;
+
+/// Returned by handlers that answer later through a [`Responder`].
+pub struct Deferred;
+
+/// Answers a request after its handler has returned, e.g. from a JavaScript
+/// evaluation callback.
+pub struct Responder {
+    request: ApiRequest,
+    pending: PendingMap,
+    /// Set for requests of the app itself, e.g. `"timer 3"`.
+    caller: Option<String>,
+    answered: bool,
+}
+
+impl Responder {
+    pub fn new(request: &ApiRequest, pending: PendingMap, caller: Option<String>) -> Self {
+        Self {
+            request: request.clone(),
+            pending,
+            caller,
+            answered: false,
+        }
+    }
+
+    pub fn send<T: Serialize>(mut self, result: Result<T>) {
+        let response = match result {
+            Ok(data) => self.request.ok(data),
+            Err(err) => self.request.err(code::of(&err), err.to_string()),
+        };
+        self.answered = true;
+        self.answer(response);
+    }
+
+    fn answer(&self, response: ApiResponse) {
+        match &self.caller {
+            Some(caller) => {
+                if response.code() != code::OK {
+                    crate::log_err!(format!(
+                        "{} of {} failed: {}",
+                        self.request.1,
+                        caller,
+                        response.msg()
+                    ));
+                }
+            }
+            None => crate::connections::respond(&self.pending, self.request.0, response),
+        }
+    }
+}
+
+impl Drop for Responder {
+    /// Answers with [`code::HANDLER_PANIC`] if the task holding it panics.
+    fn drop(&mut self) {
+        if self.answered || !std::thread::panicking() {
+            return;
+        }
+        let message = crate::crash::handler_panicked();
+        let response = self.request.err(
+            code::HANDLER_PANIC,
+            format!("{} panicked: {}", self.request.1, message),
+        );
+        self.answer(response);
+    }
+}
 #[allow(dead_code)]
 pub struct ApiManager {
     ctx: Option<Weak<AppContext>>,
     api_instance: HashMap<String, ApiInstance>,
+    /// Methods declared with `#[api(replay = false)]`.
+    no_replay: HashSet<String>,
+    /// Warnings of methods declared with `#[api(deprecated = "...")]`.
+    deprecated: HashMap<String, String>,
+    /// Connections already warned about a deprecated method.
+    warned: HashSet<(crate::connections::ConnectionId, String)>,
+    middleware: Vec<Middleware>,
+}
+
+/// Runs before every handler, see [`ApiManager::add_middleware`].
+pub type Middleware = Box<dyn Fn(&ApiRequest, &mut RequestContext) -> anyhow::Result<()> + Send>;
+
+/// Options of [`ApiManager::register_api_with`], as set by `#[api(...)]`.
+#[derive(Clone, Debug)]
+pub struct ApiOptions {
+    /// Still served, but answered with this warning, e.g.
+    /// `"use window.set_size"`.
+    pub deprecated: Option<String>,
+    /// See `#[api(replay = false)]`.
+    pub replay: bool,
+}
+
+impl Default for ApiOptions {
+    fn default() -> Self {
+        Self {
+            deprecated: None,
+            replay: true,
+        }
+    }
+}
+
+/// An API as listed by `list_apis`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ApiInfo {
+    pub name: String,
+    pub deprecated: Option<String>,
+    pub replay: bool,
 }
 
 impl ApiManager {
@@ -75,12 +485,30 @@ impl ApiManager {
         let _self = Self {
             ctx: None,
             api_instance: HashMap::new(),
+            no_replay: HashSet::new(),
+            deprecated: HashMap::new(),
+            warned: HashSet::new(),
+            middleware: Vec::new(),
         };
         crate::utils::arc_mut(_self)
     }
     #[allow(dead_code)]
     pub fn bind_app_context(&mut self, ctx: &Arc<AppContext>) {
         self.ctx = Some(Arc::downgrade(ctx));
+        // Handlers run with the manager locked, so `list_apis` reads a copy.
+        let _ = ctx.apis.set(self.catalog());
+    }
+    /// Makes `service` available to handlers through `ctx.services` and
+    /// `#[api(inject = [...])]`. Needs a bound context.
+    #[allow(dead_code)]
+    pub fn register_service<T: std::any::Any + Send + Sync>(&self, service: T) -> Result<()> {
+        let ctx = self
+            .ctx
+            .as_ref()
+            .and_then(|w| w.upgrade())
+            .ok_or_else(|| anyhow::anyhow!("App reference not available"))?;
+        ctx.services.insert(service);
+        Ok(())
     }
     #[allow(dead_code)]
     pub fn register_api<S: Into<String>, T: Serialize + 'static>(
@@ -93,34 +521,315 @@ impl ApiManager {
             &mut tao::event_loop::ControlFlow,
         ) -> Result<T>,
     ) {
-        let api_instance: ApiInstance =
-            Box::pin(move |ctx: Arc<AppContext>, request, target, flow| {
-                let result = api_func(ctx, request.clone(), target, flow);
+        self.register_handler(name, api_func);
+    }
+    /// [`Self::register_api`] for closures, which can own the state they
+    /// need instead of reaching it through `ctx.services`:
+    ///
+    /// ```ignore
+    /// let pool = pool.clone();
+    /// api_manager.register_handler("count_users", move |_ctx, req, _target, _flow| {
+    ///     let (table,) = req.args().get::<(String,)>()?;
+    ///     pool.count(&table)
+    /// });
+    /// ```
+    #[allow(dead_code)]
+    pub fn register_handler<S, T, F>(&mut self, name: S, handler: F)
+    where
+        S: Into<String>,
+        T: Serialize + 'static,
+        F: Fn(
+                Arc<AppContext>,
+                ApiRequest,
+                &FrameWindowTarget,
+                &mut tao::event_loop::ControlFlow,
+            ) -> Result<T>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let api_instance: ApiInstance = Arc::new(
+            move |ctx: Arc<AppContext>, request: ApiRequest, target, flow| {
+                let result = handler(ctx, request.clone(), target, flow);
                 let response = match result {
                     Ok(data) => request.ok(data),
-                    Err(err) => request.err(-1, err.to_string()),
+                    Err(err) => request.err(code::of(&err), err.to_string()),
                 };
 
-                Ok(response)
-            });
+                Ok(Some(response))
+            },
+        );
+
+        self.api_instance.insert(name.into(), api_instance);
+    }
+    /// Registers a handler that answers through a [`Responder`]. Only errors
+    /// returned from the handler itself are answered right away.
+    pub fn register_deferred_api<S: Into<String>>(
+        &mut self,
+        name: S,
+        api_func: fn(
+            Arc<AppContext>,
+            ApiRequest,
+            &FrameWindowTarget,
+            &mut tao::event_loop::ControlFlow,
+        ) -> Result<Deferred>,
+    ) {
+        let api_instance: ApiInstance = Arc::new(
+            move |ctx: Arc<AppContext>, request: ApiRequest, target, flow| match api_func(
+                ctx,
+                request.clone(),
+                target,
+                flow,
+            ) {
+                Ok(Deferred) => Ok(None),
+                Err(err) => Ok(Some(request.err(code::of(&err), err.to_string()))),
+            },
+        );
 
         self.api_instance.insert(name.into(), api_instance);
     }
+    /// Runs `name` even for retries whose idempotency key has a cached
+    /// response.
+    pub fn without_replay<S: Into<String>>(&mut self, name: S) {
+        self.no_replay.insert(name.into());
+    }
+    /// Keeps serving `name` but answers it with `warning`.
+    pub fn deprecate<S: Into<String>, W: Into<String>>(&mut self, name: S, warning: W) {
+        self.deprecated.insert(name.into(), warning.into());
+    }
+    /// [`Self::register_api`] with the options of `#[api(...)]`.
+    #[allow(dead_code)]
+    pub fn register_api_with<S: Into<String>, T: Serialize + 'static>(
+        &mut self,
+        name: S,
+        api_func: fn(
+            Arc<AppContext>,
+            ApiRequest,
+            &FrameWindowTarget,
+            &mut tao::event_loop::ControlFlow,
+        ) -> Result<T>,
+        options: ApiOptions,
+    ) {
+        let name = name.into();
+        self.register_api(name.clone(), api_func);
+        if !options.replay {
+            self.without_replay(name.clone());
+        }
+        if let Some(warning) = options.deprecated {
+            self.deprecate(name, warning);
+        }
+    }
+    /// Every registered API, sorted by name.
+    pub fn catalog(&self) -> Vec<ApiInfo> {
+        let mut apis: Vec<ApiInfo> = self
+            .api_instance
+            .keys()
+            .map(|name| ApiInfo {
+                name: name.clone(),
+                deprecated: self.deprecated.get(name).cloned(),
+                replay: !self.no_replay.contains(name),
+            })
+            .collect();
+        apis.sort_by(|a, b| a.name.cmp(&b.name));
+        apis
+    }
+    /// Runs `middleware` before every handler, in the order added, with the
+    /// request's context to attach extensions to. An error answers the
+    /// request instead of the handler, with the code of the error.
+    #[allow(dead_code)]
+    pub fn add_middleware<F>(&mut self, middleware: F)
+    where
+        F: Fn(&ApiRequest, &mut RequestContext) -> anyhow::Result<()> + Send + 'static,
+    {
+        self.middleware.push(Box::new(middleware));
+    }
+    /// Forgets which deprecations `connection` was warned about.
+    pub fn connection_closed(&mut self, connection: crate::connections::ConnectionId) {
+        self.warned.retain(|(warned, _)| *warned != connection);
+    }
+    #[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+    pub fn no_replay(&self) -> HashSet<String> {
+        self.no_replay.clone()
+    }
+    /// Runs the handler of `req`. Unknown methods and handler errors are
+    /// answered like failed calls; `None` if the handler answers later.
     #[allow(dead_code)]
     pub fn call(
         &mut self,
-        req: ApiRequest,
+        mut req: ApiRequest,
         target: &FrameWindowTarget,
         flow: &mut tao::event_loop::ControlFlow,
-    ) -> anyhow::Result<ApiResponse> {
+    ) -> Option<ApiResponse> {
+        tracing::trace!("Calling {} for request {}", req.1, req.0);
         if let Some(handler) = self.api_instance.get(&req.1) {
             if let Some(ctx) = self.ctx.as_ref().and_then(|w| w.upgrade()) {
-                handler(ctx, req.clone(), target, flow)
+                if let Some(warning) = self.deprecated.get(&req.1) {
+                    let warning = format!("{} is deprecated: {}", req.1, warning);
+                    if let Some(connection) = ctx.connection_of(&req) {
+                        if self.warned.insert((connection, req.1.clone())) {
+                            crate::log_warn!(format!("TCP client {}: {}", connection, warning));
+                        }
+                    }
+                    req.3.deprecation = Some(warning);
+                }
+                let mut context = req.context();
+                for middleware in &self.middleware {
+                    if let Err(err) = middleware(&req, &mut context) {
+                        return Some(req.err(code::of(&err), err.to_string()));
+                    }
+                }
+                req.3.context = Some(Box::new(context));
+                // A bug in one handler shouldn't take the app down.
+                match crate::crash::catch_handler(|| handler(ctx, req.clone(), target, flow)) {
+                    Ok(Ok(response)) => response,
+                    Ok(Err(err)) => Some(req.err(code::of(&err), err.to_string())),
+                    Err(message) => Some(req.err(
+                        code::HANDLER_PANIC,
+                        format!("{} panicked: {}", req.1, message),
+                    )),
+                }
             } else {
-                Err(anyhow::anyhow!("App reference not available"))
+                Some(req.err(code::ERROR, "App reference not available"))
             }
         } else {
-            Err(anyhow::anyhow!("Unknown method: {}", req.1))
+            Some(req.err(code::ERROR, format!("Unknown method: {}", req.1)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str) -> ApiRequest {
+        ApiRequest(
+            1,
+            method.to_string(),
+            ApiArguments::new(json!([])),
+            RequestMetadata::default(),
+        )
+    }
+
+    fn result(response: &ApiResponse) -> Value {
+        serde_json::to_value(response).unwrap()[3].clone()
+    }
+
+    #[test]
+    fn results_keep_their_shape() {
+        #[derive(Serialize)]
+        struct Point {
+            x: i32,
+            label: Option<String>,
+        }
+
+        let req = request("points");
+        let point = || Point { x: 1, label: None };
+        assert_eq!(result(&req.ok(point())), json!({ "x": 1, "label": null }));
+        assert_eq!(
+            result(&req.ok(vec![point(), point()])),
+            json!([{ "x": 1, "label": null }, { "x": 1, "label": null }])
+        );
+        let unit = req.ok(());
+        assert_eq!((unit.code(), result(&unit)), (code::OK, Value::Null));
+    }
+
+    #[test]
+    fn unserializable_results_are_internal_errors() {
+        let data = HashMap::from([((1u8, 2u8), "a map with tuple keys")]);
+        let response = request("points").ok(data);
+        assert_eq!(response.code(), code::INTERNAL);
+        assert_eq!(result(&response), Value::Null);
+        assert!(response.msg().contains("points"), "{}", response.msg());
+    }
+
+    const PARAMS: &[(&str, bool)] = &[("title", false), ("width", true)];
+
+    fn code_of(result: Result<ApiArguments>) -> Code {
+        code::of(&result.err().expect("the arguments were accepted"))
+    }
+
+    #[test]
+    fn positional_and_named_arguments_agree() {
+        let positional = ApiArguments::new(json!(["Title", 640]));
+        let named = ApiArguments::new(json!({ "width": 640, "title": "Title" }));
+        for args in [positional, named] {
+            let args = args.named(PARAMS, true).unwrap();
+            assert_eq!(
+                args.get::<(String, Option<u32>)>().unwrap(),
+                ("Title".to_string(), Some(640))
+            );
         }
     }
+
+    #[test]
+    fn missing_optional_arguments_are_none() {
+        let args = ApiArguments::new(json!({ "title": "Title" }))
+            .named(PARAMS, true)
+            .unwrap();
+        assert_eq!(args.value(), &json!(["Title", null]));
+        let args = ApiArguments::new(json!(["Title"]));
+        assert_eq!(
+            args.optional::<(String, Option<u32>)>(2).unwrap(),
+            ("Title".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn bad_named_arguments() {
+        let missing = ApiArguments::new(json!({ "width": 640 })).named(PARAMS, false);
+        assert_eq!(code_of(missing), code::BAD_ARGS);
+        let unknown = json!({ "title": "Title", "height": 480 });
+        assert_eq!(
+            code_of(ApiArguments::new(unknown.clone()).named(PARAMS, true)),
+            code::BAD_ARGS
+        );
+        let lenient = ApiArguments::new(unknown).named(PARAMS, false).unwrap();
+        assert_eq!(lenient.value(), &json!(["Title", null]));
+    }
+
+    #[test]
+    fn single_values_become_arrays() {
+        assert_eq!(ApiArguments::new(json!("Title")).value(), &json!(["Title"]));
+        assert_eq!(ApiArguments::new(Value::Null).value(), &json!([]));
+        assert_eq!(
+            ApiArguments::new(json!("Title"))
+                .single::<String>()
+                .unwrap(),
+            "Title"
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    #[ignore = "needs a display"]
+    fn typed_api_results() {
+        let harness = crate::testing::LoopHarness::start();
+        let response = harness.call("get_window_state", json!([]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        let state = response.result();
+        assert!(
+            state["id"].is_u64() && state["focused"].is_boolean(),
+            "{}",
+            state
+        );
+        let response = harness.call("list_monitors", json!([]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        let monitors = response.result().as_array().unwrap();
+        assert!(monitors
+            .iter()
+            .all(|monitor| monitor["scale_factor"].is_f64() && monitor["primary"].is_boolean()));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    #[ignore = "needs a display"]
+    fn api_handlers_take_both_encodings() {
+        let harness = crate::testing::LoopHarness::start();
+        let response = harness.call("set_title", json!(["Positional"]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        let response = harness.call("set_title", json!({ "title": "Named" }));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        let response = harness.call("set_title", json!({ "name": "Named" }));
+        assert_eq!(response.code(), code::BAD_ARGS);
+    }
 }
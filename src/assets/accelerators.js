@@ -0,0 +1,42 @@
+/**
+ * Delivers menu bar accelerators while the page has focus. The native menu
+ * may never see these keys, so keydowns matching the combos of the
+ * `accelerators` event are kept from the page and posted as
+ * `__accelerator:{"id"}`. Plain typing into text fields is left alone.
+ */
+(function () {
+  let combos = [];
+  window.addEventListener("pyframe:accelerators", (e) => {
+    combos = Array.isArray(e.detail) ? e.detail : [];
+  });
+
+  function editable(target) {
+    if (!(target instanceof Element)) return false;
+    if (target.isContentEditable) return true;
+    if (target instanceof HTMLTextAreaElement || target instanceof HTMLSelectElement) return true;
+    return target instanceof HTMLInputElement && !["button", "checkbox", "radio", "reset", "submit"].includes(target.type);
+  }
+
+  function matches(combo, e) {
+    if (combo.ctrl !== e.ctrlKey || combo.alt !== e.altKey) return false;
+    if (combo.shift !== e.shiftKey || combo.meta !== e.metaKey) return false;
+    if (combo.code) return combo.code === e.code;
+    return combo.key.toLowerCase() === e.key.toLowerCase();
+  }
+
+  window.addEventListener(
+    "keydown",
+    (e) => {
+      if (!window.ipc || e.isComposing || e.repeat || !combos.length) return;
+      const combo = combos.find((combo) => matches(combo, e));
+      if (!combo) return;
+      // A printable key without Ctrl, Alt or Meta is typing there.
+      const typing = !combo.ctrl && !combo.alt && !combo.meta && e.key.length === 1;
+      if (typing && editable(e.target)) return;
+      e.preventDefault();
+      e.stopPropagation();
+      window.ipc.postMessage("__accelerator:" + JSON.stringify({ id: combo.id }));
+    },
+    true,
+  );
+})();
@@ -0,0 +1,45 @@
+/**
+ * Suppresses the WebView's context menu or forwards it to the app, per the
+ * `context_menu` option. Runs in every frame, ahead of the page's handlers.
+ */
+(function () {
+  const { mode, native } = __PYFRAME_CONTEXT_MENU__;
+  function describe(target) {
+    const element = target instanceof Element ? target : target && target.parentElement;
+    if (!element) return null;
+    const tag = element.tagName.toLowerCase();
+    const link = element.closest("a[href]");
+    const image = element.closest("img");
+    const media = element.closest("video, audio");
+    return {
+      tag,
+      id: element.id || null,
+      classes: Array.from(element.classList),
+      link: link ? link.href : null,
+      image: image ? image.currentSrc || image.src : null,
+      media: media ? media.currentSrc || media.src : null,
+      editable: element.isContentEditable || tag === "input" || tag === "textarea",
+    };
+  }
+  window.addEventListener(
+    "contextmenu",
+    (e) => {
+      // WebKitGTK's menu is replaced or suppressed natively.
+      if (mode === "disabled" || !native) e.preventDefault();
+      if (mode !== "custom" || !window.ipc) return;
+      window.ipc.postMessage(
+        "__context_menu:" +
+          JSON.stringify({
+            x: e.clientX,
+            y: e.clientY,
+            screen_x: e.screenX,
+            screen_y: e.screenY,
+            top_frame: window === window.top,
+            selection: String(window.getSelection() || "") || null,
+            element: describe(e.target),
+          }),
+      );
+    },
+    true,
+  );
+})();
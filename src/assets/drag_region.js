@@ -0,0 +1,23 @@
+/**
+ * Native window dragging for elements marked `data-pyframe-drag-region`.
+ *
+ * A left mousedown on such an element starts dragging the window right
+ * away over wry's IPC channel; a double-click toggles maximize. Clicks on
+ * interactive children (buttons, inputs, links, ...) or on anything marked
+ * `data-pyframe-no-drag` are left to the page.
+ */
+(function () {
+  const INTERACTIVE =
+    "button, input, select, textarea, a[href], label, [contenteditable], [data-pyframe-no-drag]";
+
+  document.addEventListener("mousedown", (e) => {
+    if (e.button !== 0 || !window.ipc || !(e.target instanceof Element)) return;
+    const region = e.target.closest("[data-pyframe-drag-region]");
+    if (!region) return;
+    const interactive = e.target.closest(INTERACTIVE);
+    if (interactive && interactive !== region && region.contains(interactive)) return;
+
+    e.preventDefault();
+    window.ipc.postMessage(e.detail === 2 ? "__toggle_maximize" : "__drag_window");
+  });
+})();
@@ -0,0 +1,18 @@
+/**
+ * Reports HTML fullscreen changes, e.g. a `<video>`'s fullscreen button,
+ * over wry's IPC channel so the window can follow the element.
+ */
+(function () {
+  let active = false;
+  function changed() {
+    const element = document.fullscreenElement || document.webkitFullscreenElement || null;
+    if (!window.ipc || !!element === active) return;
+    active = !!element;
+    window.ipc.postMessage(
+      "__element_fullscreen:" +
+        JSON.stringify({ active, element: element ? element.tagName.toLowerCase() : null }),
+    );
+  }
+  document.addEventListener("fullscreenchange", changed);
+  document.addEventListener("webkitfullscreenchange", changed);
+})();
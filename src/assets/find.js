@@ -0,0 +1,117 @@
+/**
+ * Find in page for WebViews without a native find, driven by `find.rs`.
+ *
+ * Matches are highlighted with the CSS Custom Highlight API; the active one
+ * is scrolled into view. Same-origin iframes are searched too, cross-origin
+ * ones can't be reached from here. Every call returns `{matches, active}`.
+ */
+(function () {
+  if (window.__pyframe_find) return;
+  const ALL = "pyframe-find";
+  const ACTIVE = "pyframe-find-active";
+  const SKIP = "script, style, noscript, template";
+  let ranges = [];
+  let active = -1;
+  let wrap = true;
+
+  function documents(doc, out) {
+    out.push(doc);
+    for (const frame of doc.querySelectorAll("iframe, frame")) {
+      try {
+        if (frame.contentDocument) documents(frame.contentDocument, out);
+      } catch (e) {
+        // Cross-origin.
+      }
+    }
+    return out;
+  }
+
+  function collect(text, caseSensitive) {
+    const needle = caseSensitive ? text : text.toLowerCase();
+    const found = [];
+    for (const doc of documents(document, [])) {
+      if (!doc.body) continue;
+      const walker = doc.createTreeWalker(doc.body, NodeFilter.SHOW_TEXT, {
+        acceptNode: (node) =>
+          node.parentElement && node.parentElement.closest(SKIP)
+            ? NodeFilter.FILTER_REJECT
+            : NodeFilter.FILTER_ACCEPT,
+      });
+      for (let node = walker.nextNode(); node; node = walker.nextNode()) {
+        const haystack = caseSensitive ? node.data : node.data.toLowerCase();
+        for (let i = haystack.indexOf(needle); i !== -1; i = haystack.indexOf(needle, i + needle.length)) {
+          const range = doc.createRange();
+          range.setStart(node, i);
+          range.setEnd(node, i + needle.length);
+          found.push(range);
+        }
+      }
+    }
+    return found;
+  }
+
+  function registry(doc) {
+    const view = doc.defaultView;
+    return view && view.CSS && view.CSS.highlights && view.Highlight ? view : null;
+  }
+
+  function paint() {
+    for (const doc of documents(document, [])) {
+      const view = registry(doc);
+      if (!view) continue;
+      if (!doc.getElementById(ALL)) {
+        const style = doc.createElement("style");
+        style.id = ALL;
+        style.textContent =
+          `::highlight(${ALL}) { background: #fde04780; } ::highlight(${ACTIVE}) { background: #f97316; }`;
+        (doc.head || doc.documentElement).appendChild(style);
+      }
+      const own = ranges.filter((range) => range.startContainer.ownerDocument === doc);
+      view.CSS.highlights.set(ALL, new view.Highlight(...own));
+      view.CSS.highlights.set(ACTIVE, new view.Highlight(...own.filter((range) => range === ranges[active])));
+    }
+    const current = ranges[active];
+    if (current && current.startContainer.parentElement) {
+      current.startContainer.parentElement.scrollIntoView({ block: "center" });
+    }
+  }
+
+  function clear() {
+    for (const doc of documents(document, [])) {
+      const view = registry(doc);
+      if (!view) continue;
+      view.CSS.highlights.delete(ALL);
+      view.CSS.highlights.delete(ACTIVE);
+    }
+  }
+
+  function result() {
+    return { matches: ranges.length, active: active < 0 ? null : active };
+  }
+
+  window.__pyframe_find = {
+    search(text, options) {
+      clear();
+      wrap = options.wrap !== false;
+      ranges = text ? collect(text, !!options.case_sensitive) : [];
+      active = ranges.length ? (options.backwards ? ranges.length - 1 : 0) : -1;
+      paint();
+      return result();
+    },
+    step(backwards) {
+      if (ranges.length) {
+        const next = active + (backwards ? -1 : 1);
+        if (next >= 0 && next < ranges.length) active = next;
+        else if (wrap) active = (next + ranges.length) % ranges.length;
+        paint();
+      }
+      return result();
+    },
+    stop() {
+      clear();
+      ranges = [];
+      active = -1;
+      return result();
+    },
+  };
+})();
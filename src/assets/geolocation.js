@@ -0,0 +1,91 @@
+/**
+ * `navigator.geolocation` fed by `set_mock_location`, installed with the
+ * `mock_geolocation` option. `__PYFRAME_GEOLOCATION_POLICY__` is replaced
+ * with the `permissions.geolocation` policy.
+ *
+ * Positions arrive as `pyframe:geolocation-position` events and go to
+ * every watcher. Under `ask`, the first call posts
+ * `__geolocation_permission` and waits for the `pyframe:permission-resolved`
+ * of a geolocation request. The last position counts as current, so
+ * `getCurrentPosition` answers it right away whatever its `maximumAge`.
+ */
+(function () {
+  const POLICY = __PYFRAME_GEOLOCATION_POLICY__;
+  const PERMISSION_DENIED = 1;
+  const TIMEOUT = 3;
+  let permission = POLICY === "allow" ? "granted" : POLICY === "deny" ? "denied" : "prompt";
+  let waiting = [];
+  let last = null;
+  let nextWatch = 1;
+  const watches = new Map();
+  const once = new Set();
+
+  function error(code, message) {
+    return { code, message, PERMISSION_DENIED, POSITION_UNAVAILABLE: 2, TIMEOUT };
+  }
+
+  function denied(failure) {
+    if (failure) failure(error(PERMISSION_DENIED, "User denied Geolocation"));
+  }
+
+  function withPermission(granted, refused) {
+    if (permission === "granted") return granted();
+    if (permission === "denied") return refused();
+    waiting.push([granted, refused]);
+    if (waiting.length === 1 && window.ipc) window.ipc.postMessage("__geolocation_permission");
+  }
+
+  window.addEventListener("pyframe:permission-resolved", (e) => {
+    if (!e.detail || e.detail.kind !== "geolocation") return;
+    permission = e.detail.allowed ? "granted" : "denied";
+    const callbacks = waiting;
+    waiting = [];
+    for (const [granted, refused] of callbacks) (permission === "granted" ? granted : refused)();
+  });
+
+  window.addEventListener("pyframe:geolocation-position", (e) => {
+    last = e.detail;
+    if (permission !== "granted") return;
+    for (const [success] of watches.values()) success(last);
+    for (const request of once) {
+      once.delete(request);
+      clearTimeout(request.timer);
+      request.success(last);
+    }
+  });
+
+  const geolocation = {
+    getCurrentPosition(success, failure, options) {
+      withPermission(
+        () => {
+          if (last) return success(last);
+          const request = { success };
+          const timeout = options && options.timeout;
+          if (timeout !== undefined && timeout !== Infinity) {
+            request.timer = setTimeout(() => {
+              once.delete(request);
+              if (failure) failure(error(TIMEOUT, "Timeout expired"));
+            }, timeout);
+          }
+          once.add(request);
+        },
+        () => denied(failure),
+      );
+    },
+    watchPosition(success, failure) {
+      const id = nextWatch++;
+      withPermission(
+        () => {
+          watches.set(id, [success, failure]);
+          if (last) success(last);
+        },
+        () => denied(failure),
+      );
+      return id;
+    },
+    clearWatch(id) {
+      watches.delete(id);
+    },
+  };
+  Object.defineProperty(navigator, "geolocation", { value: geolocation, configurable: true });
+})();
@@ -9,16 +9,39 @@
  *  - Unique ID mapping for result/error callbacks.
  *  - Automatic cleanup of one-time callbacks.
  *  - Global `window.invoke` helper for command dispatch.
+ *  - `window.pyframe.listen(event, handler)` for events emitted by Rust.
+ *  - `window.pyframe.invokeBatch(calls)` for several commands in one message,
+ *    and `setAutoBatch(true)` to batch the invokes of a microtask.
+ *  - `window.pyframe.transport`: "tcp", or "ipc-only" when there is no
+ *    socket and commands are the native APIs, called over `window.ipc`.
+ *  - `window.__PYFRAME__`: the transport, set before the page loads, and
+ *    on the app's own pages `socket_url`, `ws_port` and `tcp_port` as bound
+ *    once the webframe told PyFrameConnections where its server is.
+ *  - `window.pyframe.startDrag(items, event)` in a `dragstart` handler to
+ *    drag files out of the window.
+ *  - `window.pyframe.setTiming(true)` to get `result.$meta`, the backend's
+ *    `{queued_us, handler_us, total_us, server_ts}` of each call.
  */
 (function () {
-  // Configure automatic reconnect
-  PyFrameConnections.configure({
-    reconnectInterval: 5000,
-    autoReconnect: true
-  });
+  const transport = window.__pyframe_transport || "tcp";
+  const ipcOnly = transport === "ipc-only";
+  /** Whether calls ask for their timing; kept for the tab's session. */
+  let timing = false;
+  try {
+    timing = sessionStorage.getItem("pyframe_timing") === "1";
+  } catch (err) {}
+
+  if (!ipcOnly) {
+    // Configure automatic reconnect
+    PyFrameConnections.configure({
+      reconnectInterval: 5000,
+      autoReconnect: true,
+      timing
+    });
 
-  // Start WebSocket connection
-  PyFrameConnections.connect();
+    // Start WebSocket connection
+    PyFrameConnections.connect();
+  }
 
   /**
    * Generate a random unique identifier.
@@ -50,22 +73,127 @@
     return identifier;
   }
 
+  /**
+   * Post `message` to the native side over `window.ipc` and await the
+   * `reply` event carrying the same id.
+   *
+   * @param {string} prefix - IPC message prefix, e.g. "__shell_open:".
+   * @param {string} reply - Event name of the answer.
+   * @param {object} message - Request fields; `id` is added.
+   * @returns {Promise<any>} Resolves with `result`, rejects with `error`.
+   */
+  function nativeCall(prefix, reply, message) {
+    return new Promise((resolve, reject) => {
+      const id = uid();
+      const unlisten = listen(reply, (detail) => {
+        if (detail.id !== id) return;
+        unlisten();
+        if (detail.error) reject(detail.error);
+        else resolve(withMeta(detail.result, detail.meta));
+      });
+      window.ipc.postMessage(prefix + JSON.stringify(Object.assign({ id }, message)));
+    });
+  }
+
   /**
    * Invoke a backend command via PyFrameConnections.
    *
    * @param {string} cmd - Command name.
-   * @param {any} [args] - Payload arguments (any serializable type).
+   * @param {any} [args] - Payload arguments, preferably an object keyed by
+   *   parameter name, e.g. `{ title: "Hi" }`. Native APIs in ipc-only mode
+   *   also take an array of positional arguments; only those listed in the
+   *   `page_apis` option can be called.
+   * @param {object} [options]
+   * @param {string} [options.priority] - "interactive", "normal" or
+   *   "background": the lane the command's requests to the native side wait in.
+   * @param {string} [options.idempotencyKey] - Makes the call safe to retry: it
+   *   is resent after a reconnect, and the backend answers a call it already
+   *   ran with the first response.
+   * @param {Uint8Array} [options.attachment] - Raw bytes for the command's
+   *   `bytes` parameter, at most 64 MiB, sent without base64.
    * @returns {Promise<any>} Resolves with result of any type, rejects with error.
    */
-  function invoke(cmd, args) {
+  function invoke(cmd, args, options) {
+    // Handled by the native side directly, not by the Python backend.
+    if (cmd === "__frame_ready") {
+      window.ipc.postMessage(cmd);
+      return Promise.resolve(true);
+    }
+    // Opened natively, so "Open in browser" buttons work without a handler
+    // in Python. Answered by a `shell-open` event with the same id.
+    if (cmd === "__shell_open") {
+      return nativeCall("__shell_open:", "shell-open", { target: args });
+    }
+    // Keyed calls go on their own so they can be resent, and attachments
+    // can't be batched.
+    const alone = options && (options.idempotencyKey || options.attachment);
+    if (autoBatch && !alone && PyFrameConnections.has_feature("batch")) {
+      return new Promise((resolve, reject) => {
+        if (!queued) {
+          queued = [];
+          queueMicrotask(flushQueued);
+        }
+        queued.push({ cmd, args, priority: options && options.priority, resolve, reject });
+      });
+    }
+    return send(cmd, args, options);
+  }
+
+  /** Calls with an idempotency key not answered yet, by result id. */
+  const retried = new Map();
+  /** Largest `options.attachment`; the backend rejects larger ones. */
+  const MAX_ATTACHMENT_BYTES = 64 * 1024 * 1024;
+
+  /** Send `message`, as a binary message if there is an attachment. */
+  function transmit(message, attachment) {
+    if (attachment) PyFrameConnections.send_binary(message, attachment);
+    else PyFrameConnections.send(message);
+  }
+
+  /** Send one command to the Python backend. */
+  function send(cmd, args, options) {
+    const key = options && options.idempotencyKey;
+    const attachment = options && options.attachment;
+    // No backend to send to; the native API of that name answers.
+    if (ipcOnly) {
+      if (attachment) return Promise.reject(new Error("Attachments need the tcp transport"));
+      return nativeCall("__invoke:", "invoke-reply", {
+        method: cmd,
+        args,
+        priority: options && options.priority,
+        timing
+      });
+    }
     return new Promise((resolve, reject) => {
-      if (!PyFrameConnections.is_connected()) {
+      if (!key && !PyFrameConnections.is_connected()) {
         reject(new Error("Socket is not connected or unavailable!"));
         return;
       }
+      if (attachment) {
+        if (!(attachment instanceof Uint8Array)) {
+          reject(new TypeError("options.attachment must be a Uint8Array"));
+          return;
+        }
+        if (attachment.length > MAX_ATTACHMENT_BYTES) {
+          reject(new Error(`Attachments are limited to ${MAX_ATTACHMENT_BYTES} bytes`));
+          return;
+        }
+        if (PyFrameConnections.capabilities && !PyFrameConnections.has_feature("binary")) {
+          reject(new Error("The backend doesn't accept attachments"));
+          return;
+        }
+      }
 
-      const result_id = transformCallback((result) => resolve(result), true);
-      const error_id = transformCallback((error) => reject(error), true);
+      let result_id = null;
+      let error_id = null;
+      const settle = (fn) => (value) => {
+        retried.delete(result_id);
+        Reflect.deleteProperty(window, `_${result_id}`);
+        Reflect.deleteProperty(window, `_${error_id}`);
+        fn(value);
+      };
+      result_id = transformCallback(settle(resolve), true);
+      error_id = transformCallback(settle(reject), true);
 
       const message = {
         cmd,
@@ -73,21 +201,161 @@
         error_id,
         payload: args
       };
+      if (options && options.priority) message.priority = options.priority;
+      if (key) {
+        message.idempotency_key = key;
+        retried.set(result_id, { message, attachment });
+        // Otherwise sent once the connection is back.
+        if (!PyFrameConnections.is_connected()) return;
+      }
 
-      PyFrameConnections.send(message);
+      transmit(message, attachment);
     });
   }
 
+  // Resend what a dropped connection may have lost; the backend replays
+  // answers to calls that already ran.
+  PyFrameConnections.on("ready", () => {
+    retried.forEach((call) => transmit(call.message, call.attachment));
+  });
+
+  /** Most calls in one batch; the Python backend rejects larger ones. */
+  const MAX_BATCH_SIZE = 32;
+  /** Resolvers of sent batches, by batch id. */
+  const batches = new Map();
+  /** Whether `invoke` calls of the same microtask go as one batch. */
+  let autoBatch = false;
+  /** Calls waiting for the end of the current microtask. */
+  let queued = null;
+
+  /**
+   * Run several commands with one message. Each runs on its own, so one
+   * failing doesn't affect the others.
+   *
+   * @param {Array<{cmd: string, args: any, priority: ?string}>} calls - At most 32.
+   * @returns {Promise<Array<{status: "fulfilled", value: any}|{status: "rejected", reason: any}>>}
+   *   One entry per call in the same order, like `Promise.allSettled`.
+   */
+  function invokeBatch(calls) {
+    if (calls.length > MAX_BATCH_SIZE) {
+      return Promise.reject(new Error(`Batches are limited to ${MAX_BATCH_SIZE} calls`));
+    }
+    if (!PyFrameConnections.has_feature("batch")) {
+      return Promise.allSettled(calls.map((call) => send(call.cmd, call.args, call)));
+    }
+    return new Promise((resolve, reject) => {
+      if (!PyFrameConnections.is_connected()) {
+        reject(new Error("Socket is not connected or unavailable!"));
+        return;
+      }
+      const id = uid();
+      batches.set(id, resolve);
+      PyFrameConnections.send({
+        type: "batch",
+        id,
+        calls: calls.map((call) => ({ cmd: call.cmd, payload: call.args, priority: call.priority }))
+      });
+    });
+  }
+
+  /** Send the calls `invoke` queued with `autoBatch` on. */
+  function flushQueued() {
+    const calls = queued;
+    queued = null;
+    for (let i = 0; i < calls.length; i += MAX_BATCH_SIZE) {
+      const chunk = calls.slice(i, i + MAX_BATCH_SIZE);
+      if (chunk.length === 1) {
+        send(chunk[0].cmd, chunk[0].args, chunk[0]).then(chunk[0].resolve, chunk[0].reject);
+        continue;
+      }
+      invokeBatch(chunk).then(
+        (results) =>
+          results.forEach((r, j) =>
+            r.status === "fulfilled" ? chunk[j].resolve(r.value) : chunk[j].reject(r.reason)),
+        (err) => chunk.forEach((call) => call.reject(err))
+      );
+    }
+  }
+
+  /**
+   * Send `invoke` calls made within the same microtask as one batch.
+   * @param {boolean} enabled
+   */
+  function setAutoBatch(enabled) {
+    autoBatch = !!enabled;
+  }
+
+  /**
+   * Resolve calls with `$meta`, the timing the backend measured, from now
+   * on; over the socket after a reconnect with the "timing" feature.
+   * Survives reloads of the tab, so it can be switched on before a page
+   * loads.
+   * @param {boolean} enabled
+   */
+  function setTiming(enabled) {
+    timing = !!enabled;
+    try {
+      if (timing) sessionStorage.setItem("pyframe_timing", "1");
+      else sessionStorage.removeItem("pyframe_timing");
+    } catch (err) {}
+    if (ipcOnly) return;
+    PyFrameConnections.configure({ timing });
+    // Otherwise the hello on connect asks for it.
+    if (PyFrameConnections.is_connected() && PyFrameConnections.has_feature("timing") !== timing) {
+      PyFrameConnections.reconnect();
+    }
+  }
+
+  /**
+   * Attach `meta` to an object or array result as the non-enumerable
+   * `$meta`; other results can't carry it.
+   */
+  function withMeta(result, meta) {
+    if (meta && result !== null && typeof result === "object" && Object.isExtensible(result)) {
+      Object.defineProperty(result, "$meta", { value: meta, configurable: true });
+    }
+    return result;
+  }
+
+  /** Deprecation warnings already logged in this tab's session. */
+  const warned = new Set();
+  try {
+    JSON.parse(sessionStorage.getItem("pyframe_deprecations") || "[]").forEach((w) => warned.add(w));
+  } catch (err) {}
+
+  /** `console.warn` a command's deprecation, once per session. */
+  function warnDeprecated(warning) {
+    if (!warning || warned.has(warning)) return;
+    warned.add(warning);
+    try {
+      sessionStorage.setItem("pyframe_deprecations", JSON.stringify([...warned]));
+    } catch (err) {}
+    console.warn(`[pyframe] ${warning}`);
+  }
+
   // Listen for backend messages and dispatch to registered callbacks
   PyFrameConnections.on("message", (raw) => {
     try {
       console.log(raw);
       const data = JSON.parse(raw);
+      if (data.type === "batch") {
+        const resolve = batches.get(data.id);
+        batches.delete(data.id);
+        data.results.forEach((r) => warnDeprecated(r.deprecated));
+        if (resolve) {
+          resolve(data.results.map((r) =>
+            "error" in r
+              ? { status: "rejected", reason: r.error }
+              : { status: "fulfilled", value: withMeta(r.result, r.meta) }));
+        }
+        return;
+      }
       const { result_id, error_id, result, error } = data;
+      warnDeprecated(data.deprecated);
 
       if (result_id) {
         const prop = `_${result_id}`;
-        if (window[prop]) window[prop](result);
+        if (window[prop]) window[prop](withMeta(result, data.meta));
       }
 
       if (error_id) {
@@ -99,6 +367,172 @@
     }
   });
 
+  /**
+   * Listen for `pyframe:<event>` events dispatched by the native side.
+   *
+   * @param {string} event - Event name, e.g. "tick".
+   * @param {Function} handler - Called with the event payload.
+   * @returns {Function} Removes the listener.
+   */
+  function listen(event, handler) {
+    const wrapped = (e) => handler(e.detail);
+    window.addEventListener(`pyframe:${event}`, wrapped);
+    return () => window.removeEventListener(`pyframe:${event}`, wrapped);
+  }
+
+  /**
+   * Named JSON documents shared with Python and every other window, without
+   * a round trip through the Python backend. Each change has a revision,
+   * higher for later changes.
+   */
+  const appStore = {
+    /** @returns {Promise<?{value: any, revision: number}>} `null` if unset. */
+    get: (name) => nativeCall("__state_store:", "app-store", { op: "get", name }),
+    /** @returns {Promise<number>} The new revision. */
+    set: (name, value) =>
+      nativeCall("__state_store:", "app-store", {
+        op: "set",
+        name,
+        value: value === undefined ? null : value
+      }),
+    /**
+     * Apply a JSON merge patch (RFC 7396): objects merge, `null` removes a key.
+     * @returns {Promise<number>} The new revision.
+     */
+    update: (name, patch) =>
+      nativeCall("__state_store:", "app-store", { op: "update", name, patch }),
+    /** @returns {Promise<boolean>} `false` if it wasn't set. */
+    delete: (name) => nativeCall("__state_store:", "app-store", { op: "delete", name }),
+    /**
+     * Call `cb({op, patch, value, revision})` whenever `name` changes; `value`
+     * is `null` once it is deleted. Changes older than one already seen are
+     * dropped.
+     * @returns {Function} Removes the listener.
+     */
+    subscribe: (name, cb) => {
+      let revision = 0;
+      return listen(`state-changed:${name}`, (detail) => {
+        if (detail.revision <= revision) return;
+        revision = detail.revision;
+        cb(detail);
+      });
+    }
+  };
+
+  /**
+   * HTTP requests made natively, so APIs without CORS headers can be
+   * called. Only origins in the app's `http.allowed_origins` are reachable.
+   */
+  const http = {
+    /**
+     * @param {object} request - `{url, method, headers, body, body_binary,
+     *   timeout_ms, redirect, max_redirects, binary, stream}`.
+     * @returns {Promise<{status: number, url: string, headers: Array<[string, string]>, body: ?string, binary: boolean, stream: ?number}>}
+     *   With `stream`, the body arrives as `http-chunk` events (`{stream, data}`,
+     *   base64) and ends with `http-end` (`{stream, error}`).
+     */
+    fetch: (request) => nativeCall("__http_fetch:", "http-fetch", { request })
+  };
+
+  /** Base64 of an ArrayBuffer or typed array, for binary WebSocket frames. */
+  function toBase64(data) {
+    const bytes =
+      data instanceof ArrayBuffer
+        ? new Uint8Array(data)
+        : new Uint8Array(data.buffer, data.byteOffset, data.byteLength);
+    let binary = "";
+    for (let i = 0; i < bytes.length; i++) binary += String.fromCharCode(bytes[i]);
+    return btoa(binary);
+  }
+
+  /**
+   * WebSockets opened natively, for networks where the WebView's own
+   * `wss://` fails. Only URLs in the app's `ws_allowed_urls` can be opened,
+   * and sockets close when the page navigates away.
+   */
+  const ws = {
+    /** @returns {Promise<number>} The connection id; `ws-open` follows. */
+    connect: (url, protocols) =>
+      nativeCall("__ws:", "ws", { op: "connect", url, protocols: protocols || [] }),
+    /** @param {string|ArrayBuffer|ArrayBufferView} data */
+    send: (conn, data) =>
+      typeof data === "string"
+        ? nativeCall("__ws:", "ws", { op: "send", conn, data })
+        : nativeCall("__ws:", "ws", { op: "send", conn, data: toBase64(data), binary: true }),
+    close: (conn, code, reason) => nativeCall("__ws:", "ws", { op: "close", conn, code, reason }),
+    /**
+     * Call `handlers.open({protocol})`, `.message(data)` (string or
+     * Uint8Array), `.error(message)` and `.close({code, reason})` for `conn`.
+     * @returns {Function} Removes the listeners.
+     */
+    on: (conn, handlers) => {
+      const mine = (cb) => (detail) => {
+        if (detail.id === conn && cb) cb(detail);
+      };
+      const unlisten = [
+        listen("ws-open", mine(handlers.open)),
+        listen("ws-message", mine((d) => handlers.message && handlers.message(
+          d.binary ? Uint8Array.from(atob(d.data), (c) => c.charCodeAt(0)) : d.data))),
+        listen("ws-error", mine((d) => handlers.error && handlers.error(d.message))),
+        listen("ws-close", mine((d) => {
+          unlisten.forEach((stop) => stop());
+          if (handlers.close) handlers.close(d);
+        }))
+      ];
+      return () => unlisten.forEach((stop) => stop());
+    }
+  };
+
+  /**
+   * Self-updates, handled natively like `appStore`. Errors carry `code`:
+   * -5 when offline, -6 when a signature or checksum doesn't match.
+   */
+  const updater = {
+    /** @returns {Promise<{available: boolean, current_version: string, version: string, notes: ?string, pub_date: ?string}>} */
+    check: () => nativeCall("__updater:", "updater", { op: "check" }),
+    /** @returns {Promise<{version: string, path: string}>} */
+    download: () => nativeCall("__updater:", "updater", { op: "download" }),
+    /**
+     * Starts the installer; the app exits once it has. Only an AppImage is
+     * started again, other installers may leave that to the user.
+     */
+    installAndExit: () => nativeCall("__updater:", "updater", { op: "install_and_exit" }),
+    /**
+     * Call `cb({downloaded, total})` while an update downloads; `total` is
+     * `null` if the server didn't send a length.
+     * @returns {Function} Removes the listener.
+     */
+    onProgress: (cb) => listen("update-progress", cb)
+  };
+
+  /**
+   * Drags files out of the window, into a file manager or another app.
+   * Call it from a `dragstart` handler and pass the event, whose own drag
+   * is cancelled; it fails unless the mouse button is still down.
+   * @param {string|object|Array<string|object>} items - Paths in the app's
+   *   `fs_scope`, or `{data, filename, base64}` written to a temporary file.
+   * @param {?DragEvent} event
+   * @returns {Promise<boolean>}
+   */
+  function startDrag(items, event) {
+    if (event) event.preventDefault();
+    return nativeCall("__start_drag:", "start-drag", { items });
+  }
+
   // Expose invoke globally
   window.invoke = invoke;
+  window.appStore = appStore;
+  window.pyframe = Object.assign(window.pyframe || {}, {
+    transport,
+    invoke,
+    invokeBatch,
+    setAutoBatch,
+    setTiming,
+    listen,
+    state: appStore,
+    http,
+    ws,
+    updater,
+    startDrag
+  });
 })();
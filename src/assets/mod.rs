@@ -1,2 +1,11 @@
 pub static _CONN_SCRIPT: &str = include_str!("./websocket.js");
 pub static _COMMAND_SCRIPT: &str = include_str!("./invoke.js");
+pub static _DRAG_REGION_SCRIPT: &str = include_str!("./drag_region.js");
+pub static _FIND_SCRIPT: &str = include_str!("./find.js");
+pub static _GEOLOCATION_SCRIPT: &str = include_str!("./geolocation.js");
+pub static _ELEMENT_FULLSCREEN_SCRIPT: &str = include_str!("./element_fullscreen.js");
+pub static _CONTEXT_MENU_SCRIPT: &str = include_str!("./context_menu.js");
+pub static _ERROR_PAGE: &str = include_str!("./error_page.html");
+pub static _NAVIGATION_SCRIPT: &str = include_str!("./navigation.js");
+pub static _ACCELERATORS_SCRIPT: &str = include_str!("./accelerators.js");
+pub static _TITLEBAR_OVERLAY_SCRIPT: &str = include_str!("./titlebar_overlay.js");
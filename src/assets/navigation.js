@@ -0,0 +1,32 @@
+/**
+ * Reports `DOMContentLoaded` and same-document navigations (`pushState`,
+ * `replaceState`, `popstate`) over wry's IPC channel; page loads are
+ * reported natively.
+ */
+(function () {
+  function post(kind, extra) {
+    if (!window.ipc) return;
+    window.ipc.postMessage(
+      "__navigation:" + JSON.stringify(Object.assign({ kind, url: location.href }, extra)),
+    );
+  }
+  function sameDocument(type) {
+    const extra = { same_document: true, navigation_type: type };
+    post("load-started", extra);
+    post("load-finished", Object.assign({ success: true }, extra));
+  }
+  document.addEventListener("DOMContentLoaded", () => post("dom-content-loaded"));
+  for (const [method, type] of [
+    ["pushState", "push"],
+    ["replaceState", "replace"],
+  ]) {
+    const original = history[method];
+    history[method] = function () {
+      const result = original.apply(this, arguments);
+      sameDocument(type);
+      return result;
+    };
+  }
+  // Fragment navigations fire popstate as well.
+  window.addEventListener("popstate", () => sameDocument("pop"));
+})();
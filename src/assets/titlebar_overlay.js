@@ -0,0 +1,30 @@
+/**
+ * Publishes where the native caption buttons are while `titlebar_overlay`
+ * is on, as `--pyframe-titlebar-height` and
+ * `--pyframe-titlebar-buttons-{x,y,width,height}` on the root element, and
+ * opens the system menu on right-click in a `data-pyframe-drag-region`.
+ */
+(function () {
+  function apply(layout) {
+    const style = document.documentElement.style;
+    style.setProperty("--pyframe-titlebar-height", layout.height + "px");
+    for (const key of ["x", "y", "width", "height"]) {
+      style.setProperty("--pyframe-titlebar-buttons-" + key, layout.buttons[key] + "px");
+    }
+  }
+
+  const initial = __PYFRAME_TITLEBAR_OVERLAY__;
+  if (document.documentElement) {
+    apply(initial);
+  } else {
+    document.addEventListener("DOMContentLoaded", () => apply(initial), { once: true });
+  }
+  window.addEventListener("pyframe:titlebar-overlay", (e) => apply(e.detail));
+
+  document.addEventListener("contextmenu", (e) => {
+    if (!window.ipc || !(e.target instanceof Element)) return;
+    if (!e.target.closest("[data-pyframe-drag-region]")) return;
+    e.preventDefault();
+    window.ipc.postMessage("__system_menu");
+  });
+})();
@@ -6,8 +6,17 @@
  *  - Event listener system (`on`, `off`, `offAll`)
  *  - JSON-encoded message sending
  *  - Connection status helpers (`is_connected`, `is_disconnected`)
+ *  - Hello exchange on every connect; `capabilities` holds the features
+ *    the server granted, and `ready` fires once they are known
+ *  - `client_id`, kept for the tab's session so the server can match
+ *    requests resent after a reconnect
+ *  - `configure({ timing: true })` asks for the "timing" feature, so
+ *    responses carry how long the call waited and ran
+ *  - In a webframe, the server's URL and the launch token come from a
+ *    `__bridge:` request over `window.ipc`, which only the app's own pages
+ *    get answered; neither is left in a global
  *
- * Usage example:
+ * Usage example, for a page outside a webframe:
  *
  * ```js
  * window.socket_url = "ws://localhost:8765";
@@ -17,23 +26,49 @@
  * ```
  */
 (function () {
-  var PyFrameConnections = {};
+  var PyFrameConnections = { capabilities: null };
   var ws = null;
   var config = {
     protocols: [],
     reconnectInterval: 3000,
-    autoReconnect: true
+    autoReconnect: true,
+    timing: false
   };
 
+  /** Protocol version this script speaks; see `pyframe/protocol.py`. */
+  var PROTOCOL_VERSION = 1;
+  /** Features asked for in the hello. */
+  var FEATURES = ["batch", "binary"];
+  /** First byte of a binary message; see `pyframe/protocol.py`. */
+  var BINARY_MARKER = 0;
+
+  /** Names this page in its hellos; survives reloads of the same tab. */
+  PyFrameConnections.client_id = (function () {
+    var id = null;
+    try {
+      id = window.sessionStorage.getItem("pyframe_client_id");
+    } catch (err) {}
+    if (!id) {
+      id = window.crypto.randomUUID();
+      try {
+        window.sessionStorage.setItem("pyframe_client_id", id);
+      } catch (err) {}
+    }
+    return id;
+  })();
+
   var eventListeners = {};
   var reconnectTimer = null;
   var shouldReconnect = true;
+  /** The webframe's server and launch token, once it answered `__bridge:`. */
+  var bridge = null;
+  var bridgePending = false;
 
   // === Event Handling ===
 
   /**
    * Register an event listener.
-   * @param {string} event - Event name (open, message, error, close).
+   * @param {string} event - Event name (open, ready, message, error, close).
    * @param {Function} listener - Callback to invoke when event fires.
    */
   function addEventListener(event, listener) {
@@ -82,33 +117,86 @@
    * @param {string[]} [options.protocols] - Optional WebSocket subprotocols.
    * @param {number} [options.reconnectInterval] - Reconnect delay in ms.
    * @param {boolean} [options.autoReconnect] - Whether to auto-reconnect.
+   * @param {boolean} [options.timing] - Whether the next hello asks for the
+   *   "timing" feature.
    */
   PyFrameConnections.configure = function (options) {
     config.protocols = options.protocols || config.protocols;
     config.reconnectInterval = options.reconnectInterval || config.reconnectInterval;
     config.autoReconnect = options.autoReconnect !== undefined ? options.autoReconnect : config.autoReconnect;
+    config.timing = options.timing !== undefined ? !!options.timing : config.timing;
   };
 
   /**
-   * Establish a WebSocket connection.
-   * Requires `window.socket_url` to be set.
+   * Ask the webframe where its server is, then connect. Pages that aren't
+   * the app's own get no answer.
+   */
+  function requestBridge() {
+    if (bridgePending) return;
+    bridgePending = true;
+    var id = window.crypto.getRandomValues(new Uint32Array(1))[0];
+    var onReply = function (e) {
+      if (!e.detail || e.detail.id !== id) return;
+      window.removeEventListener("pyframe:bridge", onReply);
+      bridgePending = false;
+      if (e.detail.error) {
+        console.error("PyFrameConnections: " + e.detail.error.message);
+        return;
+      }
+      bridge = e.detail.result;
+      window.__PYFRAME__ = Object.assign(window.__PYFRAME__ || {}, {
+        socket_url: bridge.socket_url,
+        ws_port: bridge.ws_port,
+        tcp_port: bridge.tcp_port
+      });
+      PyFrameConnections.connect();
+    };
+    window.addEventListener("pyframe:bridge", onReply);
+    window.ipc.postMessage("__bridge:" + JSON.stringify({ id: id }));
+  }
+
+  /**
+   * Establish a WebSocket connection, to `window.socket_url` if set and
+   * otherwise to the webframe's server.
    */
   PyFrameConnections.connect = function () {
-    if (!window.socket_url) {
+    if (!window.socket_url && !bridge && window.ipc) {
+      requestBridge();
+      return;
+    }
+    var url = window.socket_url || (bridge && bridge.socket_url);
+    if (!url) {
       console.error("PyFrameConnections: No URL configured.");
       return;
     }
 
     shouldReconnect = config.autoReconnect;
 
-    ws = new WebSocket(window.socket_url, config.protocols);
+    ws = new WebSocket(url, config.protocols);
 
     ws.onopen = function (e) {
+      PyFrameConnections.capabilities = null;
+      ws.send(JSON.stringify({
+        type: "hello",
+        version: PROTOCOL_VERSION,
+        features: config.timing ? FEATURES.concat(["timing"]) : FEATURES,
+        token: (bridge && bridge.token) || window.__pyframe_token || null,
+        client: PyFrameConnections.client_id
+      }));
       dispatchEvent('open', e);
     };
 
     ws.onmessage = function (e) {
       console.log(e.data);
+      var ack = null;
+      try {
+        ack = JSON.parse(e.data);
+      } catch (err) {}
+      if (ack && ack.type === "hello-ack") {
+        PyFrameConnections.capabilities = { version: ack.version, features: ack.features };
+        dispatchEvent('ready', PyFrameConnections.capabilities);
+        return;
+      }
       dispatchEvent('message', e.data);
     };
 
@@ -118,6 +206,11 @@
 
     ws.onclose = function (e) {
       dispatchEvent('close', e);
+      // 4000-4002: the hello was refused, which a retry won't change.
+      if (e.code >= 4000 && e.code <= 4002) {
+        console.error("PyFrameConnections: " + e.reason);
+        return;
+      }
       if (shouldReconnect) {
         reconnectTimer = setTimeout(PyFrameConnections.connect, config.reconnectInterval);
       }
@@ -136,6 +229,26 @@
     }
   };
 
+  /**
+   * Send `data` with raw bytes in one binary message:
+   * `[0, JSON length (u32 BE), JSON, attachment]`. Needs the "binary" feature.
+   * @param {Object} data - Payload to send.
+   * @param {Uint8Array} attachment - Bytes sent as they are.
+   */
+  PyFrameConnections.send_binary = function (data, attachment) {
+    if (!ws || ws.readyState !== WebSocket.OPEN) {
+      console.warn("PyFrameConnections: Connection not open.");
+      return;
+    }
+    var json = new TextEncoder().encode(JSON.stringify(data));
+    var frame = new Uint8Array(5 + json.length + attachment.length);
+    frame[0] = BINARY_MARKER;
+    new DataView(frame.buffer).setUint32(1, json.length);
+    frame.set(json, 5);
+    frame.set(attachment, 5 + json.length);
+    ws.send(frame);
+  };
+
   /**
    * Close the WebSocket connection.
    * @param {number} [code=1000] - WebSocket close code.
@@ -149,6 +262,35 @@
     }
   };
 
+  /**
+   * Replace the connection with a new one, e.g. for a hello with other
+   * features. Answers to calls still running on the old one are lost,
+   * except for calls with an idempotency key, which are resent.
+   */
+  PyFrameConnections.reconnect = function () {
+    clearTimeout(reconnectTimer);
+    if (ws) {
+      var old = ws;
+      ws = null;
+      old.onclose = function (e) {
+        dispatchEvent('close', e);
+      };
+      old.onmessage = null;
+      old.close(1000, "Reconnecting");
+    }
+    PyFrameConnections.connect();
+  };
+
+  /**
+   * Whether the server granted `feature` in its hello-ack.
+   * @param {string} feature - e.g. "binary".
+   * @returns {boolean}
+   */
+  PyFrameConnections.has_feature = function (feature) {
+    var caps = PyFrameConnections.capabilities;
+    return !!caps && caps.features.indexOf(feature) !== -1;
+  };
+
   // === Status Methods ===
 
   /**
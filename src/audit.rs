@@ -0,0 +1,310 @@
+//! The opt-in audit log of privileged API calls.
+//!
+//! With the `audit` option every call of an audited method is recorded with
+//! its connection, client, arguments, result code and latency: as a line of
+//! JSON in `audit.path` (`<log dir>/audit.jsonl` by default), and in memory
+//! for `audit_query`. Arguments are redacted when the call arrives, before
+//! the entry is kept anywhere.
+//!
+//! Entries reach the file through a bounded queue and a writer thread that
+//! fsyncs at most every `fsync_interval_ms`, so a slow disk never holds up
+//! requests; entries that don't fit in the queue are counted as `dropped`.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, SyncSender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    api_manager::{code, ApiRequest, ApiResponse, Code},
+    connections::ConnectionId,
+    lock_force,
+};
+
+/// Audited when `audit.methods` is unset; `*` matches any suffix.
+const PRIVILEGED: &[&str] = &[
+    "fs_*",
+    "exec_command",
+    "process_*",
+    "shell_open",
+    "open_file_with_default_app",
+    "http_fetch",
+    "ws_connect",
+    "env_*",
+    "updater_*",
+    "grant_permission",
+    "run_on_main_js",
+    "native_handle",
+    "set_log_filter",
+];
+const REDACTED: &str = "[redacted]";
+const DEFAULT_QUEUE_SIZE: usize = 1024;
+const DEFAULT_FSYNC_INTERVAL_MS: u64 = 1000;
+const DEFAULT_RECENT: usize = 500;
+const DEFAULT_QUERY_LIMIT: usize = 100;
+
+/// The `audit` creation option.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Methods recorded, e.g. `["fs_*", "exec_command"]`; the privileged
+    /// ones if unset.
+    pub methods: Option<Vec<String>>,
+    /// Argument fields replaced with `"[redacted]"`: object keys at any
+    /// depth, or `method[index]` for a positional argument.
+    pub redact: Vec<String>,
+    /// The NDJSON file; `<log dir>/audit.jsonl` if unset.
+    pub path: Option<PathBuf>,
+    /// Entries waiting for the writer before new ones are dropped; 1024 if
+    /// unset.
+    pub queue_size: Option<usize>,
+    /// Longest an entry stays unsynced on disk; 1 s if unset.
+    pub fsync_interval_ms: Option<u64>,
+    /// Entries kept for `audit_query`; 500 if unset.
+    pub recent: Option<usize>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditEntry {
+    /// Unix time in milliseconds the call arrived.
+    at: u64,
+    connection: ConnectionId,
+    client: String,
+    method: String,
+    args: Value,
+    /// Size of a binary attachment; its bytes aren't recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachment_bytes: Option<usize>,
+    code: Code,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    latency_ms: f64,
+}
+
+/// The `audit_query` filter; every field narrows the result.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AuditFilter {
+    /// A method name, or a prefix ending in `*`.
+    pub method: Option<String>,
+    pub connection: Option<ConnectionId>,
+    pub client: Option<String>,
+    /// Only calls that didn't answer with `OK`.
+    pub failed: bool,
+    /// Entries at or after this Unix time in milliseconds.
+    pub since: Option<u64>,
+    /// The newest this many; 100 if unset.
+    pub limit: Option<usize>,
+}
+
+pub struct AuditLog {
+    methods: Vec<String>,
+    redact: Vec<String>,
+    recent: Mutex<VecDeque<AuditEntry>>,
+    recent_size: usize,
+    lines: SyncSender<String>,
+    dropped: AtomicU64,
+}
+
+impl AuditLog {
+    /// Opens the file and starts its writer thread.
+    pub fn start(config: &AuditConfig, log_dir: Option<PathBuf>) -> Result<Self> {
+        let path = match (&config.path, log_dir) {
+            (Some(path), _) => path.clone(),
+            (None, Some(dir)) => dir.join("audit.jsonl"),
+            (None, None) => return Err(anyhow!("audit needs a path on this platform")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let (lines, rx) = mpsc::sync_channel(config.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE));
+        let interval = Duration::from_millis(
+            config
+                .fsync_interval_ms
+                .unwrap_or(DEFAULT_FSYNC_INTERVAL_MS),
+        );
+        std::thread::Builder::new()
+            .name("audit-writer".into())
+            .spawn(move || write_lines(rx, file, interval))?;
+        Ok(Self {
+            methods: config
+                .methods
+                .clone()
+                .unwrap_or_else(|| PRIVILEGED.iter().map(|method| method.to_string()).collect()),
+            redact: config.redact.clone(),
+            recent: Mutex::new(VecDeque::new()),
+            recent_size: config.recent.unwrap_or(DEFAULT_RECENT),
+            lines,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Starts the record of `req` if its method is audited.
+    pub fn begin(
+        self: &Arc<Self>,
+        req: &ApiRequest,
+        connection: ConnectionId,
+        client: &str,
+    ) -> Option<Record> {
+        if !self.methods.iter().any(|pattern| matches(pattern, &req.1)) {
+            return None;
+        }
+        let mut args = req.2.value().clone();
+        self.redact(&req.1, &mut args);
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Some(Record {
+            log: self.clone(),
+            started: Instant::now(),
+            entry: AuditEntry {
+                at,
+                connection,
+                client: client.to_string(),
+                method: req.1.clone(),
+                args,
+                attachment_bytes: req.3.attachment.as_ref().map(|bytes| bytes.len()),
+                code: code::OK,
+                error: None,
+                latency_ms: 0.0,
+            },
+        })
+    }
+
+    fn redact(&self, method: &str, args: &mut Value) {
+        for field in &self.redact {
+            let positional = field
+                .strip_prefix(method)
+                .and_then(|rest| rest.strip_prefix('['))
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|index| index.parse::<usize>().ok());
+            match positional {
+                Some(index) => {
+                    if let Some(arg) = args.get_mut(index) {
+                        *arg = json!(REDACTED);
+                    }
+                }
+                None => redact_key(args, field),
+            }
+        }
+    }
+
+    fn finish(&self, entry: AuditEntry) {
+        if let Ok(line) = serde_json::to_string(&entry) {
+            if self.lines.try_send(line).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let mut recent = lock_force!(self.recent);
+        if recent.len() >= self.recent_size {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    /// The newest entries matching `filter`, oldest first, and how many
+    /// entries never reached the file.
+    pub fn query(&self, filter: &AuditFilter) -> Value {
+        let recent = lock_force!(self.recent);
+        let mut entries: Vec<&AuditEntry> = recent
+            .iter()
+            .rev()
+            .filter(|entry| {
+                filter
+                    .method
+                    .as_ref()
+                    .is_none_or(|pattern| matches(pattern, &entry.method))
+                    && filter.connection.is_none_or(|c| c == entry.connection)
+                    && filter.client.as_ref().is_none_or(|c| *c == entry.client)
+                    && (!filter.failed || entry.code != code::OK)
+                    && filter.since.is_none_or(|since| entry.at >= since)
+            })
+            .take(filter.limit.unwrap_or(DEFAULT_QUERY_LIMIT))
+            .collect();
+        entries.reverse();
+        json!({
+            "entries": entries,
+            "dropped": self.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// A call being audited, finished with its response.
+pub struct Record {
+    log: Arc<AuditLog>,
+    started: Instant,
+    entry: AuditEntry,
+}
+
+impl Record {
+    pub fn finish(mut self, response: &ApiResponse) {
+        self.entry.code = response.code();
+        if self.entry.code != code::OK {
+            self.entry.error = Some(response.msg().to_string());
+        }
+        self.entry.latency_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        self.log.finish(self.entry);
+    }
+}
+
+fn matches(pattern: &str, method: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => method.starts_with(prefix),
+        None => pattern == method,
+    }
+}
+
+fn redact_key(value: &mut Value, key: &str) {
+    match value {
+        Value::Object(map) => {
+            for (name, field) in map.iter_mut() {
+                if name == key {
+                    *field = json!(REDACTED);
+                } else {
+                    redact_key(field, key);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_key(item, key)),
+        _ => {}
+    }
+}
+
+/// Appends lines until the log is gone, syncing at most every `interval`.
+fn write_lines(rx: mpsc::Receiver<String>, file: File, interval: Duration) {
+    let mut file = BufWriter::new(file);
+    let mut unsynced = false;
+    let mut synced_at = Instant::now();
+    loop {
+        let closed = match rx.recv_timeout(interval) {
+            Ok(line) => {
+                crate::log_if_err!(writeln!(file, "{}", line));
+                unsynced = true;
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        if unsynced && (closed || synced_at.elapsed() >= interval) {
+            crate::log_if_err!(file.flush().and_then(|_| file.get_ref().sync_data()));
+            unsynced = false;
+            synced_at = Instant::now();
+        }
+        if closed {
+            break;
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! `pygcc.BridgeClient`, a blocking client of the TCP bridge for Python code
+//! that doesn't run pyframe's asyncio side, possibly in another process than
+//! `create_webframe`.
+//!
+//! It speaks the protocol of [`crate::connections`]: the hello with the
+//! token, length-prefixed JSON frames, binary frames for attachments and
+//! `__subscribe_events` on a connection of its own for
+//! [`BridgeSubscription`]. The server doesn't negotiate compression, so
+//! frames are never compressed. Blocking I/O runs with the GIL released,
+//! and a timeout never leaves half a frame behind: partial frames stay in
+//! the [`FrameReader`] for the next read.
+
+use std::{
+    ffi::CString,
+    io::{ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use pyo3::{exceptions::PyDeprecationWarning, prelude::*, types::PyDict};
+use serde_json::{json, Value};
+
+use crate::{
+    api_manager::{code, Code},
+    errors::{BridgeApiError, BridgeConnectionError, BridgeProtocolError, BridgeTimeoutError},
+    event_stream::SUBSCRIBE_METHOD,
+    lock_force,
+    protocol::{self, Feature},
+    python::{call_args, to_json, to_py},
+};
+
+/// Largest frame accepted from the server.
+const MAX_FRAME_BYTES: usize = protocol::MAX_ATTACHMENT_BYTES + 1024 * 1024;
+/// How often a blocked [`BridgeSubscription`] checks for Ctrl+C.
+const SIGNAL_CHECK: Duration = Duration::from_millis(200);
+
+/// Why a bridge call failed, raised as the matching `pygcc` exception.
+#[derive(Debug)]
+enum BridgeError {
+    Io(std::io::Error),
+    Closed,
+    Timeout,
+    Protocol(String),
+    Api { code: Code, msg: String },
+}
+
+impl From<std::io::Error> for BridgeError {
+    fn from(e: std::io::Error) -> Self {
+        BridgeError::Io(e)
+    }
+}
+
+impl From<BridgeError> for PyErr {
+    fn from(e: BridgeError) -> Self {
+        match e {
+            BridgeError::Io(e) => BridgeConnectionError::new_err(e.to_string()),
+            BridgeError::Closed => {
+                BridgeConnectionError::new_err("The bridge closed the connection")
+            }
+            BridgeError::Timeout => BridgeTimeoutError::new_err("No response within the timeout"),
+            BridgeError::Protocol(reason) => BridgeProtocolError::new_err(reason),
+            BridgeError::Api { code, msg } => BridgeApiError::new_err((msg, code)),
+        }
+    }
+}
+
+type BridgeResult<T> = Result<T, BridgeError>;
+
+/// Splits the bytes read from a stream into frames.
+#[derive(Default)]
+struct FrameReader {
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    /// The next frame, `None` if `deadline` passes first.
+    fn next(
+        &mut self,
+        stream: &mut TcpStream,
+        deadline: Option<Instant>,
+    ) -> BridgeResult<Option<Vec<u8>>> {
+        let mut chunk = [0u8; 64 * 1024];
+        loop {
+            if let Some(header) = self.buf.get(..4) {
+                let len = u32::from_be_bytes(header.try_into().expect("4 bytes")) as usize;
+                if len > MAX_FRAME_BYTES {
+                    return Err(BridgeError::Protocol(format!(
+                        "Frame of {} bytes is too large",
+                        len
+                    )));
+                }
+                if self.buf.len() >= 4 + len {
+                    let frame = self.buf[4..4 + len].to_vec();
+                    self.buf.drain(..4 + len);
+                    return Ok(Some(frame));
+                }
+            }
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => Some(left),
+                    _ => return Ok(None),
+                },
+                None => None,
+            };
+            stream.set_read_timeout(timeout)?;
+            match stream.read(&mut chunk) {
+                Ok(0) => return Err(BridgeError::Closed),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// The next frame as JSON, see [`next`](Self::next).
+    fn next_json(
+        &mut self,
+        stream: &mut TcpStream,
+        deadline: Option<Instant>,
+    ) -> BridgeResult<Option<Value>> {
+        match self.next(stream, deadline)? {
+            Some(frame) => serde_json::from_slice(&frame)
+                .map(Some)
+                .map_err(|e| BridgeError::Protocol(format!("Invalid frame: {}", e))),
+            None => Ok(None),
+        }
+    }
+}
+
+fn write_frame(
+    stream: &mut TcpStream,
+    json: &Value,
+    attachment: Option<&[u8]>,
+) -> BridgeResult<()> {
+    let json = serde_json::to_vec(json).map_err(|e| BridgeError::Protocol(e.to_string()))?;
+    let payload = match attachment {
+        Some(attachment) => {
+            let mut payload = Vec::with_capacity(5 + json.len() + attachment.len());
+            payload.push(protocol::BINARY_MARKER);
+            payload.extend_from_slice(&(json.len() as u32).to_be_bytes());
+            payload.extend_from_slice(&json);
+            payload.extend_from_slice(attachment);
+            payload
+        }
+        None => json,
+    };
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&payload);
+    stream.write_all(&frame)?;
+    Ok(())
+}
+
+/// A connection that has been through the hello.
+struct Connection {
+    stream: TcpStream,
+    reader: FrameReader,
+    /// What the server granted.
+    features: Vec<Feature>,
+}
+
+impl Connection {
+    fn open(
+        addr: &str,
+        token: Option<&str>,
+        client: &str,
+        features: &[Feature],
+        timeout: Duration,
+    ) -> BridgeResult<Self> {
+        let deadline = Instant::now() + timeout;
+        let socket = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| BridgeError::Protocol(format!("{} has no address", addr)))?;
+        let mut stream = TcpStream::connect_timeout(&socket, timeout)?;
+        stream.set_nodelay(true)?;
+        write_frame(
+            &mut stream,
+            &json!({
+                "type": "hello",
+                "version": protocol::VERSION,
+                "features": features,
+                "token": token,
+                "client": client,
+            }),
+            None,
+        )?;
+        let mut reader = FrameReader::default();
+        let ack = reader
+            .next_json(&mut stream, Some(deadline))?
+            .ok_or(BridgeError::Timeout)?;
+        match ack["type"].as_str() {
+            Some("hello-ack") => Ok(Self {
+                features: serde_json::from_value(ack["features"].clone()).unwrap_or_default(),
+                stream,
+                reader,
+            }),
+            Some("close") => Err(BridgeError::Protocol(format!(
+                "[close {}] {}",
+                ack["code"],
+                ack["reason"].as_str().unwrap_or_default()
+            ))),
+            _ => Err(BridgeError::Protocol("No hello-ack".into())),
+        }
+    }
+
+    /// Sends `request` and waits for the response with its id, skipping
+    /// late responses to requests that timed out.
+    fn call(
+        &mut self,
+        request: &Value,
+        attachment: Option<&[u8]>,
+        deadline: Instant,
+    ) -> BridgeResult<Vec<Value>> {
+        write_frame(&mut self.stream, request, attachment)?;
+        loop {
+            let frame = self
+                .reader
+                .next_json(&mut self.stream, Some(deadline))?
+                .ok_or(BridgeError::Timeout)?;
+            if let Value::Array(response) = frame {
+                if response.first() == request.get(0) {
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
+/// Parses `[id, code, msg, result, {deprecated}?]` into the result and the
+/// deprecation warning.
+fn unpack(response: Vec<Value>) -> BridgeResult<(Value, Option<String>)> {
+    let mut response = response.into_iter().skip(1);
+    let code = response
+        .next()
+        .and_then(|c| c.as_i64())
+        .unwrap_or(code::ERROR as i64) as Code;
+    let msg = response
+        .next()
+        .and_then(|m| m.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let result = response.next().unwrap_or(Value::Null);
+    let deprecated = response
+        .next()
+        .and_then(|meta| meta.get("deprecated")?.as_str().map(str::to_string));
+    if code != code::OK {
+        return Err(BridgeError::Api { code, msg });
+    }
+    Ok((result, deprecated))
+}
+
+/// A blocking connection to the bridge of a running `create_webframe`.
+///
+/// ```python
+/// client = pygcc.BridgeClient.connect("127.0.0.1:9000", os.environ["PYFRAME_TOKEN"])
+/// client.invoke("set_title", ["Hello"])
+/// for event, data in client.subscribe(["tick"]):
+///     ...
+/// ```
+///
+/// Calls from several threads are serialized. Errors are `PyFrameError`s:
+/// `BridgeConnectionError`, `BridgeProtocolError`, `BridgeTimeoutError` and
+/// `BridgeApiError` with `args == (msg, code)`.
+#[pyclass(module = "pygcc")]
+pub struct BridgeClient {
+    addr: String,
+    token: Option<String>,
+    /// Keeps idempotency keys valid across reconnects.
+    client: String,
+    connection: Mutex<Option<Connection>>,
+    next_id: Mutex<u8>,
+}
+
+impl BridgeClient {
+    fn connection<'a>(
+        &self,
+        guard: &'a mut Option<Connection>,
+        timeout: Duration,
+    ) -> BridgeResult<&'a mut Connection> {
+        if guard.is_none() {
+            *guard = Some(Connection::open(
+                &self.addr,
+                self.token.as_deref(),
+                &self.client,
+                &[Feature::Binary],
+                timeout,
+            )?);
+        }
+        Ok(guard.as_mut().expect("just opened"))
+    }
+
+    fn next_id(&self) -> u8 {
+        let mut next_id = lock_force!(self.next_id);
+        *next_id = next_id.wrapping_add(1);
+        *next_id
+    }
+}
+
+#[pymethods]
+impl BridgeClient {
+    /// Connects to `addr`, e.g. `"127.0.0.1:9000"`, presenting `token`;
+    /// `PYFRAME_TOKEN` if `None`.
+    #[staticmethod]
+    #[pyo3(signature = (addr, token=None, timeout=5.0))]
+    fn connect(
+        py: Python<'_>,
+        addr: String,
+        token: Option<String>,
+        timeout: f64,
+    ) -> PyResult<Self> {
+        let token = token.or_else(|| {
+            std::env::var(protocol::TOKEN_ENV)
+                .ok()
+                .filter(|t| !t.is_empty())
+        });
+        let client = uuid::Uuid::new_v4().simple().to_string();
+        let connection = py.allow_threads(|| {
+            Connection::open(
+                &addr,
+                token.as_deref(),
+                &client,
+                &[Feature::Binary],
+                Duration::from_secs_f64(timeout),
+            )
+        })?;
+        Ok(Self {
+            addr,
+            token,
+            client,
+            connection: Mutex::new(Some(connection)),
+            // Apart from the ids pyframe's own client starts with.
+            next_id: Mutex::new(rand_start()),
+        })
+    }
+
+    /// Calls the API `name` and returns its result. The arguments are
+    /// keyword arguments by parameter name, or `args`: a list of the
+    /// positional ones, a dict of named ones or a single positional one.
+    /// `attachment` is the bytes for a `Bytes` parameter. Reconnects if the
+    /// connection was lost.
+    #[pyo3(signature = (name, args=None, timeout=10.0, attachment=None, metadata=None, **kwargs))]
+    #[allow(clippy::too_many_arguments)]
+    fn invoke(
+        &self,
+        py: Python<'_>,
+        name: String,
+        args: Option<&Bound<'_, PyAny>>,
+        timeout: f64,
+        attachment: Option<Vec<u8>>,
+        metadata: Option<&Bound<'_, PyAny>>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        let args = call_args(py, args, kwargs)?;
+        let mut request = json!([self.next_id(), name, args]);
+        if let Some(metadata) = metadata {
+            request
+                .as_array_mut()
+                .expect("array")
+                .push(to_json(py, metadata)?);
+        }
+        let timeout = Duration::from_secs_f64(timeout);
+        let response = py.allow_threads(|| {
+            let deadline = Instant::now() + timeout;
+            let mut guard = lock_force!(self.connection);
+            let connection = self.connection(&mut guard, timeout)?;
+            if attachment.is_some() && !connection.features.contains(&Feature::Binary) {
+                return Err(BridgeError::Protocol(
+                    "The bridge didn't grant the binary feature".into(),
+                ));
+            }
+            let result = connection.call(&request, attachment.as_deref(), deadline);
+            if matches!(result, Err(BridgeError::Io(_) | BridgeError::Closed)) {
+                *guard = None;
+            }
+            result
+        })?;
+        let (result, deprecated) = unpack(response)?;
+        if let Some(deprecated) = deprecated {
+            let message = CString::new(deprecated).unwrap_or_default();
+            PyErr::warn(py, &py.get_type::<PyDeprecationWarning>(), &message, 1)?;
+        }
+        to_py(py, &result)
+    }
+
+    /// Streams `(event, data)` tuples like `pyframe.events()`, on a
+    /// connection of its own; only `events` if given.
+    #[pyo3(signature = (events=None, timeout=5.0))]
+    fn subscribe(
+        &self,
+        py: Python<'_>,
+        events: Option<Vec<String>>,
+        timeout: f64,
+    ) -> PyResult<BridgeSubscription> {
+        let connection = py.allow_threads(|| {
+            let timeout = Duration::from_secs_f64(timeout);
+            let mut connection = Connection::open(
+                &self.addr,
+                self.token.as_deref(),
+                &self.client,
+                &[Feature::Streaming],
+                timeout,
+            )?;
+            if !connection.features.contains(&Feature::Streaming) {
+                return Err(BridgeError::Protocol(
+                    "The bridge didn't grant the streaming feature".into(),
+                ));
+            }
+            let ack = connection.call(
+                &json!([0, SUBSCRIBE_METHOD, []]),
+                None,
+                Instant::now() + timeout,
+            )?;
+            unpack(ack)?;
+            Ok(connection)
+        })?;
+        Ok(BridgeSubscription {
+            events,
+            connection: Mutex::new(Some(connection)),
+        })
+    }
+
+    /// Closes the connection; a later `invoke` opens a new one.
+    fn close(&self) {
+        if let Some(connection) = lock_force!(self.connection).take() {
+            let _ = connection.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&self, _type: PyObject, _value: PyObject, _traceback: PyObject) {
+        self.close();
+    }
+}
+
+/// A request id away from 0, which `__subscribe_events` uses, and from the
+/// low ids pyframe's asyncio client counts up from.
+fn rand_start() -> u8 {
+    128 + uuid::Uuid::new_v4().as_bytes()[0] % 64
+}
+
+/// The events of a [`BridgeClient::subscribe`] stream; iterating blocks
+/// until the next one and stops when the webframe exits.
+#[pyclass(module = "pygcc")]
+pub struct BridgeSubscription {
+    events: Option<Vec<String>>,
+    connection: Mutex<Option<Connection>>,
+}
+
+#[pymethods]
+impl BridgeSubscription {
+    fn __iter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<(String, PyObject)>> {
+        loop {
+            let frame = py.allow_threads(|| {
+                let mut guard = lock_force!(self.connection);
+                let Some(connection) = guard.as_mut() else {
+                    return Err(BridgeError::Closed);
+                };
+                let frame = connection
+                    .reader
+                    .next_json(&mut connection.stream, Some(Instant::now() + SIGNAL_CHECK));
+                if frame.is_err() {
+                    *guard = None;
+                }
+                frame
+            });
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(BridgeError::Closed) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            py.check_signals()?;
+            let Some(Value::Object(mut frame)) = frame else {
+                continue;
+            };
+            let Some(Value::String(event)) = frame.remove("event") else {
+                continue;
+            };
+            if self
+                .events
+                .as_ref()
+                .is_some_and(|events| !events.contains(&event))
+            {
+                continue;
+            }
+            let data = frame.remove("data").unwrap_or(json!({}));
+            return Ok(Some((event, to_py(py, &data)?)));
+        }
+    }
+
+    fn close(&self) {
+        if let Some(connection) = lock_force!(self.connection).take() {
+            let _ = connection.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Screenshots of the whole native window, including its title bar and
+//! frame, in physical pixels of the monitor it is on.
+
+use std::{io::Cursor, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde_json::{json, Value};
+use tao::window::Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Png,
+    Jpeg,
+}
+
+impl CaptureFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "png" => Ok(Self::Png),
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            _ => Err(anyhow!("Unsupported capture format {:?}", format)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+        }
+    }
+}
+
+/// Writes the image to `path`, or returns it base64-encoded as `data`.
+/// Meant for a blocking thread; encoding a large window takes a while.
+pub fn encode(image: RgbaImage, format: CaptureFormat, path: Option<PathBuf>) -> Result<Value> {
+    let (width, height) = image.dimensions();
+    // JPEG has no alpha channel.
+    let image = match format {
+        CaptureFormat::Png => DynamicImage::ImageRgba8(image),
+        CaptureFormat::Jpeg => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+    };
+    let image_format = match format {
+        CaptureFormat::Png => ImageFormat::Png,
+        CaptureFormat::Jpeg => ImageFormat::Jpeg,
+    };
+    let mut result = json!({ "width": width, "height": height, "format": format.name() });
+    match path {
+        Some(path) => {
+            image.save_with_format(&path, image_format)?;
+            result["path"] = json!(path);
+        }
+        None => {
+            let mut bytes = Vec::new();
+            image.write_to(&mut Cursor::new(&mut bytes), image_format)?;
+            result["data"] = json!(STANDARD.encode(bytes));
+        }
+    }
+    Ok(result)
+}
+
+/// Grabs the window's pixels, decorations included.
+///
+/// | Platform | Mechanism |
+/// |----------|-----------|
+/// | Windows  | `PrintWindow(PW_RENDERFULLCONTENT)`; `BitBlt` misses WebView2's composited content |
+/// | macOS    | `CGWindowListCreateImage`; needs the screen recording permission |
+/// | Linux (X11) | the window's frame extents read back from the root window |
+/// | Linux (Wayland) | the client area only, where the compositor allows it |
+pub fn capture_window(window: &Window) -> Result<RgbaImage> {
+    #[cfg(target_os = "windows")]
+    return win32::capture(window);
+    #[cfg(target_os = "macos")]
+    return apple::capture(window);
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    return unix::capture(window);
+    #[allow(unreachable_code)]
+    {
+        let _ = window;
+        Err(anyhow!("Window capture is not supported on this platform"))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use anyhow::{anyhow, Result};
+    use image::RgbaImage;
+    use tao::{platform::windows::WindowExtWindows, window::Window};
+    use windows::Win32::{
+        Foundation::{HWND, RECT},
+        Graphics::Gdi::{
+            CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits,
+            ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+        },
+        Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
+        UI::WindowsAndMessaging::GetWindowRect,
+    };
+
+    /// Renders DirectComposition content such as WebView2 into the bitmap.
+    const PW_RENDERFULLCONTENT: u32 = 2;
+
+    pub fn capture(window: &Window) -> Result<RgbaImage> {
+        let hwnd = HWND(window.hwnd() as _);
+        let mut rect = RECT::default();
+        unsafe { GetWindowRect(hwnd, &mut rect)? };
+        // The process is per-monitor DPI aware, so this is in physical pixels.
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        if width <= 0 || height <= 0 {
+            return Err(anyhow!("Window has no area to capture"));
+        }
+
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            let screen = GetDC(None);
+            let dc = CreateCompatibleDC(Some(screen));
+            let bitmap = CreateCompatibleBitmap(screen, width, height);
+            let previous = SelectObject(dc, bitmap.into());
+            let printed = PrintWindow(hwnd, dc, PRINT_WINDOW_FLAGS(PW_RENDERFULLCONTENT));
+            let mut info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: width,
+                    // Negative for top-down rows.
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let lines = GetDIBits(
+                dc,
+                bitmap,
+                0,
+                height as u32,
+                Some(pixels.as_mut_ptr().cast()),
+                &mut info,
+                DIB_RGB_COLORS,
+            );
+            SelectObject(dc, previous);
+            let _ = DeleteObject(bitmap.into());
+            let _ = DeleteDC(dc);
+            ReleaseDC(None, screen);
+            if !printed.as_bool() || lines == 0 {
+                return Err(anyhow!("PrintWindow failed"));
+            }
+        }
+
+        // BGRA to RGBA; GDI leaves the alpha channel empty.
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+            pixel[3] = 0xff;
+        }
+        RgbaImage::from_raw(width as u32, height as u32, pixels)
+            .ok_or_else(|| anyhow!("Invalid bitmap size"))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod apple {
+    use std::ffi::c_void;
+
+    use anyhow::{anyhow, Result};
+    use image::RgbaImage;
+    use objc2::{msg_send, runtime::AnyObject};
+    use tao::{platform::macos::WindowExtMacOS, window::Window};
+
+    use crate::api_manager::PermissionDenied;
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    struct CGSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[repr(C)]
+    struct CGRect {
+        origin: CGPoint,
+        size: CGSize,
+    }
+
+    const K_CG_WINDOW_LIST_OPTION_INCLUDING_WINDOW: u32 = 1 << 3;
+    const K_CG_WINDOW_IMAGE_BOUNDS_IGNORE_FRAMING: u32 = 1 << 0;
+    /// Captures at the backing scale factor instead of in points.
+    const K_CG_WINDOW_IMAGE_BEST_RESOLUTION: u32 = 1 << 3;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
+        fn CGRequestScreenCaptureAccess() -> bool;
+        fn CGWindowListCreateImage(
+            bounds: CGRect,
+            list_option: u32,
+            window_id: u32,
+            image_option: u32,
+        ) -> *mut c_void;
+        fn CGImageGetWidth(image: *mut c_void) -> usize;
+        fn CGImageGetHeight(image: *mut c_void) -> usize;
+        fn CGImageGetBytesPerRow(image: *mut c_void) -> usize;
+        fn CGImageGetBitsPerPixel(image: *mut c_void) -> usize;
+        fn CGImageGetDataProvider(image: *mut c_void) -> *mut c_void;
+        fn CGDataProviderCopyData(provider: *mut c_void) -> *const c_void;
+        fn CGImageRelease(image: *mut c_void);
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFDataGetBytePtr(data: *const c_void) -> *const u8;
+        fn CFDataGetLength(data: *const c_void) -> isize;
+        fn CFRelease(cf: *const c_void);
+    }
+
+    pub fn capture(window: &Window) -> Result<RgbaImage> {
+        unsafe {
+            // Without the permission the image comes back black, so ask
+            // (showing the system prompt the first time) and fail instead.
+            if !CGPreflightScreenCaptureAccess() {
+                CGRequestScreenCaptureAccess();
+                return Err(PermissionDenied {
+                    permission: "screen_recording",
+                }
+                .into());
+            }
+
+            let ns_window = &*(window.ns_window() as *const AnyObject);
+            let window_number: isize = msg_send![ns_window, windowNumber];
+            // CGRectNull: the window's own bounds.
+            let bounds = CGRect {
+                origin: CGPoint {
+                    x: f64::INFINITY,
+                    y: f64::INFINITY,
+                },
+                size: CGSize {
+                    width: 0.0,
+                    height: 0.0,
+                },
+            };
+            let image = CGWindowListCreateImage(
+                bounds,
+                K_CG_WINDOW_LIST_OPTION_INCLUDING_WINDOW,
+                window_number as u32,
+                K_CG_WINDOW_IMAGE_BOUNDS_IGNORE_FRAMING | K_CG_WINDOW_IMAGE_BEST_RESOLUTION,
+            );
+            if image.is_null() {
+                return Err(anyhow!("CGWindowListCreateImage failed"));
+            }
+            let result = to_rgba(image);
+            CGImageRelease(image);
+            result
+        }
+    }
+
+    /// Copies a 32-bit BGRA (premultiplied, little-endian) `CGImage`.
+    unsafe fn to_rgba(image: *mut c_void) -> Result<RgbaImage> {
+        let width = CGImageGetWidth(image);
+        let height = CGImageGetHeight(image);
+        let stride = CGImageGetBytesPerRow(image);
+        if CGImageGetBitsPerPixel(image) != 32 {
+            return Err(anyhow!("Unsupported window image format"));
+        }
+        let data = CGDataProviderCopyData(CGImageGetDataProvider(image));
+        if data.is_null() {
+            return Err(anyhow!("Window image has no data"));
+        }
+        let bytes =
+            std::slice::from_raw_parts(CFDataGetBytePtr(data), CFDataGetLength(data) as usize);
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for row in bytes.chunks(stride).take(height) {
+            for pixel in row[..width * 4].chunks_exact(4) {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            }
+        }
+        CFRelease(data);
+        RgbaImage::from_raw(width as u32, height as u32, pixels)
+            .ok_or_else(|| anyhow!("Invalid window image size"))
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod unix {
+    use anyhow::{anyhow, Result};
+    use gtk::{gdk, gdk_pixbuf::Pixbuf, prelude::*};
+    use image::RgbaImage;
+    use tao::{platform::unix::WindowExtUnix, window::Window};
+
+    pub fn capture(window: &Window) -> Result<RgbaImage> {
+        let gdk_window = window
+            .gtk_window()
+            .window()
+            .ok_or_else(|| anyhow!("Window is not realized"))?;
+        // On X11 the decorations belong to the window manager's frame, so
+        // read the frame's area back from the root window. GDK returns the
+        // pixbuf in device pixels, scaled by the monitor's factor.
+        let frame = gdk_window.frame_extents();
+        let from_root = gdk::Screen::default()
+            .and_then(|screen| screen.root_window())
+            .and_then(|root| root.pixbuf(frame.x(), frame.y(), frame.width(), frame.height()));
+        let pixbuf = from_root
+            .or_else(|| gdk_window.pixbuf(0, 0, gdk_window.width(), gdk_window.height()))
+            .ok_or_else(|| anyhow!("The display server doesn't allow capturing the window"))?;
+        to_rgba(&pixbuf)
+    }
+
+    fn to_rgba(pixbuf: &Pixbuf) -> Result<RgbaImage> {
+        let width = pixbuf.width() as usize;
+        let height = pixbuf.height() as usize;
+        let channels = pixbuf.n_channels() as usize;
+        let stride = pixbuf.rowstride() as usize;
+        let bytes = pixbuf.read_pixel_bytes();
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for row in bytes.chunks(stride).take(height) {
+            for pixel in row[..width * channels].chunks_exact(channels) {
+                let alpha = if channels == 4 { pixel[3] } else { 0xff };
+                pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], alpha]);
+            }
+        }
+        RgbaImage::from_raw(width as u32, height as u32, pixels)
+            .ok_or_else(|| anyhow!("Invalid pixbuf size"))
+    }
+}
@@ -0,0 +1,117 @@
+//! Click-through overlays: the window passes mouse input to whatever is
+//! beneath it, except inside interactive regions.
+//!
+//! No platform lets a window ignore the cursor only in parts of it, so with
+//! regions set the cursor is polled and cursor events are ignored while it
+//! is outside all of them. An ignoring window gets no cursor events of its
+//! own, which is why [`ClickThrough::deadline`] keeps the loop waking up.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tao::{
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
+    window::Window,
+};
+
+/// How often the cursor is checked against the interactive regions.
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+
+/// A rectangle in logical pixels, relative to the window's content area.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Default)]
+pub struct ClickThrough {
+    enabled: bool,
+    regions: Vec<Region>,
+    /// `regions` in physical pixels at the window's current scale factor.
+    hit: Vec<(PhysicalPosition<f64>, PhysicalSize<f64>)>,
+    /// Whether the window currently ignores cursor events.
+    ignoring: bool,
+    poll_at: Option<Instant>,
+}
+
+impl ClickThrough {
+    pub fn set_enabled(&mut self, window: &Window, enabled: bool) -> Result<()> {
+        self.enabled = enabled;
+        self.apply(window, enabled)?;
+        self.schedule(Instant::now());
+        Ok(())
+    }
+
+    /// Replaces the interactive regions. An empty list makes the whole
+    /// window click-through while enabled.
+    pub fn set_regions(&mut self, window: &Window, regions: Vec<Region>) -> Result<()> {
+        if let Some(region) = regions.iter().find(|r| r.width < 0.0 || r.height < 0.0) {
+            return Err(anyhow!("Region with negative size: {:?}", region));
+        }
+        self.regions = regions;
+        self.resized(window);
+        if self.enabled {
+            self.apply(window, true)?;
+        }
+        self.schedule(Instant::now());
+        Ok(())
+    }
+
+    /// Call on resize and scale factor changes.
+    pub fn resized(&mut self, window: &Window) {
+        let scale = window.scale_factor();
+        self.hit = self
+            .regions
+            .iter()
+            .map(|r| {
+                (
+                    LogicalPosition::new(r.x, r.y).to_physical(scale),
+                    LogicalSize::new(r.width, r.height).to_physical(scale),
+                )
+            })
+            .collect();
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.poll_at
+    }
+
+    /// Lets the cursor through unless it is over an interactive region.
+    pub fn poll(&mut self, now: Instant, window: &Window) {
+        if self.poll_at.is_none_or(|at| at > now) {
+            return;
+        }
+        self.schedule(now);
+        let inside = self.over_region(window).unwrap_or(false);
+        crate::log_if_err!(self.apply(window, !inside));
+    }
+
+    fn over_region(&self, window: &Window) -> Result<bool> {
+        let cursor = window.cursor_position()?;
+        let origin = window.inner_position()?;
+        let x = cursor.x - origin.x as f64;
+        let y = cursor.y - origin.y as f64;
+        Ok(self.hit.iter().any(|(position, size)| {
+            x >= position.x
+                && x < position.x + size.width
+                && y >= position.y
+                && y < position.y + size.height
+        }))
+    }
+
+    fn apply(&mut self, window: &Window, ignore: bool) -> Result<()> {
+        if ignore != self.ignoring {
+            window.set_ignore_cursor_events(ignore)?;
+            self.ignoring = ignore;
+        }
+        Ok(())
+    }
+
+    fn schedule(&mut self, now: Instant) {
+        self.poll_at = (self.enabled && !self.hit.is_empty()).then(|| now + POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,704 @@
+//! The system clipboard: plain text, HTML with a plain-text alternative,
+//! and file lists.
+//!
+//! | Format | Windows | macOS | Linux (GTK) |
+//! |--------|---------|-------|-------------|
+//! | `text` | `CF_UNICODETEXT` | `public.utf8-plain-text` | `UTF8_STRING`, `text/plain` |
+//! | `html` | `HTML Format` (CF_HTML) | `public.html` | `text/html` |
+//! | `files` | `CF_HDROP` | `public.file-url` of each item | `text/uri-list` |
+//! | `image` | `CF_DIB`, `CF_BITMAP` | `public.png`, `public.tiff` | any image target |
+//!
+//! `image` is only reported by [`formats`]. A format the platform lacks
+//! reads as `None` and writes as `false` instead of failing; iOS and
+//! Android have none.
+//!
+//! GTK answers reads asynchronously, so reads take a callback, called on
+//! the event loop thread once the owner of the clipboard answered; other
+//! platforms call it right away. On Linux the process serves what it wrote
+//! itself, which is gone once the app exits unless a clipboard manager
+//! kept a copy.
+
+use std::{borrow::Cow, path::PathBuf, sync::OnceLock};
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+
+/// What [`formats`] finds on the clipboard.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[allow(dead_code)]
+pub enum Format {
+    Text,
+    Html,
+    Files,
+    Image,
+}
+
+/// Replaces the clipboard's contents with `text`.
+pub fn write_text(text: &str) -> Result<bool> {
+    platform::write_text(text)
+}
+
+/// The clipboard's text, `None` if it holds none.
+pub fn read_text<F>(reply: F)
+where
+    F: FnOnce(Result<Option<String>>) + 'static,
+{
+    platform::read_text(reply)
+}
+
+/// Replaces the clipboard's contents with `html` and `alt_text` for
+/// programs that only paste text; without `alt_text`, the text of `html`
+/// with its tags stripped, table cells separated by tabs.
+pub fn write_html(html: &str, alt_text: Option<&str>) -> Result<bool> {
+    let alt_text = alt_text.map_or_else(|| Cow::Owned(plain_text(html)), Cow::Borrowed);
+    platform::write_html(html, &alt_text)
+}
+
+/// The clipboard's HTML fragment, `None` if it holds none.
+pub fn read_html<F>(reply: F)
+where
+    F: FnOnce(Result<Option<String>>) + 'static,
+{
+    platform::read_html(reply)
+}
+
+/// The files copied in a file manager, `None` if there are none.
+pub fn read_files<F>(reply: F)
+where
+    F: FnOnce(Result<Option<Vec<PathBuf>>>) + 'static,
+{
+    platform::read_files(reply)
+}
+
+/// The formats the clipboard holds now, in the order of [`Format`].
+pub fn formats<F>(reply: F)
+where
+    F: FnOnce(Result<Vec<Format>>) + 'static,
+{
+    platform::formats(reply)
+}
+
+/// The text of `html`, for the plain-text alternative of [`write_html`].
+fn plain_text(html: &str) -> String {
+    static PATTERNS: OnceLock<[(Regex, &str); 5]> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        let pattern = |re: &str| Regex::new(re).expect("valid pattern");
+        [
+            (
+                pattern(r"(?is)<(script|style)\b.*?</(script|style)\s*>"),
+                "",
+            ),
+            (pattern(r"(?i)</t[dh]\s*>\s*<t[dh]\b"), "\t<td"),
+            (
+                pattern(r"(?i)<br\s*/?>|</(p|div|tr|li|h[1-6]|table)\s*>"),
+                "\n",
+            ),
+            (pattern(r"(?s)<[^>]*>"), ""),
+            (pattern(r"[ \t]*\n[ \t]*\n+"), "\n"),
+        ]
+    });
+    let text = patterns
+        .iter()
+        .fold(html.to_string(), |text, (pattern, with)| {
+            pattern.replace_all(&text, *with).into_owned()
+        });
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::{
+        ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf, thread, time::Duration,
+    };
+
+    use anyhow::{anyhow, Result};
+    use windows::{
+        core::w,
+        Win32::{
+            Foundation::{HANDLE, HGLOBAL},
+            System::{
+                DataExchange::{
+                    CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable,
+                    OpenClipboard, RegisterClipboardFormatW, SetClipboardData,
+                },
+                Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
+            },
+            UI::Shell::{DragQueryFileW, HDROP},
+        },
+    };
+
+    use super::Format;
+
+    const CF_BITMAP: u32 = 2;
+    const CF_DIB: u32 = 8;
+    const CF_UNICODETEXT: u32 = 13;
+    const CF_HDROP: u32 = 15;
+
+    fn html_format() -> u32 {
+        unsafe { RegisterClipboardFormatW(w!("HTML Format")) }
+    }
+
+    /// The opened clipboard, closed again on drop.
+    struct Opened;
+
+    impl Opened {
+        /// Retries for a moment, as another program may hold it.
+        fn new() -> Result<Self> {
+            let mut attempts = 10;
+            loop {
+                match unsafe { OpenClipboard(None) } {
+                    Ok(()) => return Ok(Self),
+                    Err(e) if attempts == 0 => {
+                        return Err(anyhow!("The clipboard is in use: {}", e));
+                    }
+                    Err(_) => attempts -= 1,
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    impl Drop for Opened {
+        fn drop(&mut self) {
+            let _ = unsafe { CloseClipboard() };
+        }
+    }
+
+    fn available(format: u32) -> bool {
+        unsafe { IsClipboardFormatAvailable(format).is_ok() }
+    }
+
+    /// Adds `bytes` as `format` to the opened clipboard, which owns them
+    /// from then on.
+    fn set(format: u32, bytes: &[u8]) -> Result<()> {
+        unsafe {
+            let global = GlobalAlloc(GMEM_MOVEABLE, bytes.len())?;
+            let target = GlobalLock(global) as *mut u8;
+            if target.is_null() {
+                return Err(anyhow!("Clipboard memory not locked"));
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len());
+            let _ = GlobalUnlock(global);
+            SetClipboardData(format, Some(HANDLE(global.0)))?;
+        }
+        Ok(())
+    }
+
+    fn get(format: u32) -> Result<Option<Vec<u8>>> {
+        if !available(format) {
+            return Ok(None);
+        }
+        let _opened = Opened::new()?;
+        unsafe {
+            let global = HGLOBAL(GetClipboardData(format)?.0);
+            let source = GlobalLock(global) as *const u8;
+            if source.is_null() {
+                return Ok(None);
+            }
+            let bytes = std::slice::from_raw_parts(source, GlobalSize(global)).to_vec();
+            let _ = GlobalUnlock(global);
+            Ok(Some(bytes))
+        }
+    }
+
+    /// UTF-16 with the terminating NUL, as `CF_UNICODETEXT` wants it.
+    fn wide(text: &str) -> Vec<u8> {
+        text.encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    fn from_wide(bytes: &[u8]) -> String {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect();
+        String::from_utf16_lossy(&units)
+    }
+
+    fn header(
+        start_html: usize,
+        end_html: usize,
+        start_fragment: usize,
+        end_fragment: usize,
+    ) -> String {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            start_html, end_html, start_fragment, end_fragment
+        )
+    }
+
+    /// `html` as CF_HTML: a header with the byte offsets of the document
+    /// and the fragment, then the fragment wrapped into a document.
+    fn cf_html(html: &str) -> Vec<u8> {
+        const PREFIX: &str = "<html><body>\r\n<!--StartFragment-->";
+        const SUFFIX: &str = "<!--EndFragment-->\r\n</body></html>";
+        let start_html = header(0, 0, 0, 0).len();
+        let start_fragment = start_html + PREFIX.len();
+        let end_fragment = start_fragment + html.len();
+        let end_html = end_fragment + SUFFIX.len();
+        let mut data = header(start_html, end_html, start_fragment, end_fragment);
+        data.push_str(PREFIX);
+        data.push_str(html);
+        data.push_str(SUFFIX);
+        data.push('\0');
+        data.into_bytes()
+    }
+
+    /// The fragment of CF_HTML `data`, or its document if the fragment's
+    /// offsets are missing.
+    fn fragment(data: &[u8]) -> String {
+        let header = String::from_utf8_lossy(data);
+        let offset = |key: &str| {
+            header
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.trim().parse::<usize>().ok())
+        };
+        let range = match (offset("StartFragment:"), offset("EndFragment:")) {
+            (Some(start), Some(end)) => Some(start..end),
+            _ => offset("StartHTML:").map(|start| start..offset("EndHTML:").unwrap_or(data.len())),
+        };
+        let html = range.and_then(|range| data.get(range)).unwrap_or(data);
+        String::from_utf8_lossy(html)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    pub fn write_text(text: &str) -> Result<bool> {
+        let _opened = Opened::new()?;
+        unsafe { EmptyClipboard()? };
+        set(CF_UNICODETEXT, &wide(text))?;
+        Ok(true)
+    }
+
+    pub fn read_text<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        reply(get(CF_UNICODETEXT).map(|text| text.map(|text| from_wide(&text))))
+    }
+
+    pub fn write_html(html: &str, alt_text: &str) -> Result<bool> {
+        let _opened = Opened::new()?;
+        unsafe { EmptyClipboard()? };
+        set(html_format(), &cf_html(html))?;
+        set(CF_UNICODETEXT, &wide(alt_text))?;
+        Ok(true)
+    }
+
+    pub fn read_html<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        reply(get(html_format()).map(|html| html.map(|html| fragment(&html))))
+    }
+
+    fn files() -> Result<Option<Vec<PathBuf>>> {
+        if !available(CF_HDROP) {
+            return Ok(None);
+        }
+        let _opened = Opened::new()?;
+        unsafe {
+            let drop = HDROP(GetClipboardData(CF_HDROP)?.0);
+            let count = DragQueryFileW(drop, u32::MAX, None);
+            let files = (0..count)
+                .map(|index| {
+                    let len = DragQueryFileW(drop, index, None) as usize;
+                    let mut name = vec![0u16; len + 1];
+                    DragQueryFileW(drop, index, Some(&mut name));
+                    PathBuf::from(OsString::from_wide(&name[..len]))
+                })
+                .collect();
+            Ok(Some(files))
+        }
+    }
+
+    pub fn read_files<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<Vec<PathBuf>>>) + 'static,
+    {
+        reply(files())
+    }
+
+    pub fn formats<F>(reply: F)
+    where
+        F: FnOnce(Result<Vec<Format>>) + 'static,
+    {
+        let formats = [
+            (Format::Text, available(CF_UNICODETEXT)),
+            (Format::Html, available(html_format())),
+            (Format::Files, available(CF_HDROP)),
+            (Format::Image, available(CF_DIB) || available(CF_BITMAP)),
+        ];
+        reply(Ok(formats
+            .into_iter()
+            .filter_map(|(format, available)| available.then_some(format))
+            .collect()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+    use objc2::{class, msg_send, runtime::AnyObject};
+    use objc2_foundation::NSString;
+
+    use super::Format;
+
+    const TEXT: &str = "public.utf8-plain-text";
+    const HTML: &str = "public.html";
+    const FILE_URL: &str = "public.file-url";
+    const IMAGES: [&str; 2] = ["public.png", "public.tiff"];
+
+    fn pasteboard() -> &'static AnyObject {
+        unsafe {
+            let pasteboard: *mut AnyObject = msg_send![class!(NSPasteboard), generalPasteboard];
+            &*pasteboard
+        }
+    }
+
+    /// Replaces the contents with one item of `(type, string)` entries.
+    fn write(entries: &[(&str, &str)]) -> Result<bool> {
+        let pasteboard = pasteboard();
+        let mut written = true;
+        unsafe {
+            let _: isize = msg_send![pasteboard, clearContents];
+            for (kind, value) in entries {
+                let kind = NSString::from_str(kind);
+                let value = NSString::from_str(value);
+                let set: bool = msg_send![pasteboard, setString: &*value, forType: &*kind];
+                written &= set;
+            }
+        }
+        Ok(written)
+    }
+
+    /// The string of `kind` on the pasteboard or one of its items.
+    fn string(object: &AnyObject, kind: &str) -> Option<String> {
+        unsafe {
+            let kind = NSString::from_str(kind);
+            let value: *mut NSString = msg_send![object, stringForType: &*kind];
+            value.as_ref().map(|value| value.to_string())
+        }
+    }
+
+    /// The elements of the `NSArray` `array`.
+    fn elements(array: *mut AnyObject) -> Vec<&'static AnyObject> {
+        unsafe {
+            let Some(array) = array.as_ref() else {
+                return Vec::new();
+            };
+            let count: usize = msg_send![array, count];
+            (0..count)
+                .filter_map(|index| {
+                    let element: *mut AnyObject = msg_send![array, objectAtIndex: index];
+                    element.as_ref()
+                })
+                .collect()
+        }
+    }
+
+    pub fn write_text(text: &str) -> Result<bool> {
+        write(&[(TEXT, text)])
+    }
+
+    pub fn read_text<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        reply(Ok(string(pasteboard(), TEXT)))
+    }
+
+    pub fn write_html(html: &str, alt_text: &str) -> Result<bool> {
+        write(&[(HTML, html), (TEXT, alt_text)])
+    }
+
+    pub fn read_html<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        reply(Ok(string(pasteboard(), HTML)))
+    }
+
+    pub fn read_files<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<Vec<PathBuf>>>) + 'static,
+    {
+        let items: *mut AnyObject = unsafe { msg_send![pasteboard(), pasteboardItems] };
+        let files: Vec<PathBuf> = elements(items)
+            .into_iter()
+            .filter_map(|item| string(item, FILE_URL))
+            .filter_map(|url| url::Url::parse(&url).ok()?.to_file_path().ok())
+            .collect();
+        reply(Ok((!files.is_empty()).then_some(files)))
+    }
+
+    pub fn formats<F>(reply: F)
+    where
+        F: FnOnce(Result<Vec<Format>>) + 'static,
+    {
+        let types: *mut AnyObject = unsafe { msg_send![pasteboard(), types] };
+        let types: Vec<String> = elements(types)
+            .into_iter()
+            .map(|kind| {
+                let kind = kind as *const AnyObject as *const NSString;
+                unsafe { (*kind).to_string() }
+            })
+            .collect();
+        let has = |kind: &str| types.iter().any(|t| t == kind);
+        let formats = [
+            (Format::Text, has(TEXT)),
+            (Format::Html, has(HTML)),
+            (Format::Files, has(FILE_URL)),
+            (Format::Image, IMAGES.iter().any(|kind| has(kind))),
+        ];
+        reply(Ok(formats
+            .into_iter()
+            .filter_map(|(format, available)| available.then_some(format))
+            .collect()))
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod platform {
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+    use gtk::{gdk, Clipboard, TargetEntry, TargetFlags};
+
+    use super::Format;
+
+    const HTML: &str = "text/html";
+    /// What GTK's `set_text` offers, for the text of `write_html`.
+    const TEXT_TARGETS: [&str; 5] = [
+        "UTF8_STRING",
+        "text/plain;charset=utf-8",
+        "text/plain",
+        "STRING",
+        "TEXT",
+    ];
+
+    fn clipboard() -> Clipboard {
+        Clipboard::get(&gdk::SELECTION_CLIPBOARD)
+    }
+
+    /// Firefox offers `text/html` as UTF-16 with a byte order mark.
+    fn decode(data: &[u8]) -> String {
+        match data {
+            [0xFF, 0xFE, rest @ ..] => {
+                let units: Vec<u16> = rest
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            _ => String::from_utf8_lossy(data).into_owned(),
+        }
+        .trim_end_matches('\0')
+        .to_string()
+    }
+
+    pub fn write_text(text: &str) -> Result<bool> {
+        clipboard().set_text(text);
+        Ok(true)
+    }
+
+    pub fn read_text<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        clipboard().request_text(move |_, text| reply(Ok(text.map(str::to_string))));
+    }
+
+    pub fn write_html(html: &str, alt_text: &str) -> Result<bool> {
+        let mut targets = vec![TargetEntry::new(HTML, TargetFlags::empty(), 0)];
+        targets.extend(
+            TEXT_TARGETS
+                .iter()
+                .map(|target| TargetEntry::new(target, TargetFlags::empty(), 1)),
+        );
+        let (html, alt_text) = (html.to_string(), alt_text.to_string());
+        Ok(clipboard().set_with_data(&targets, move |_, data, info| {
+            if info == 0 {
+                data.set(&gdk::Atom::intern(HTML), 8, html.as_bytes());
+            } else {
+                data.set_text(&alt_text);
+            }
+        }))
+    }
+
+    pub fn read_html<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        clipboard().request_contents(&gdk::Atom::intern(HTML), move |_, data| {
+            reply(Ok((data.length() > 0).then(|| decode(&data.data()))))
+        });
+    }
+
+    pub fn read_files<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<Vec<PathBuf>>>) + 'static,
+    {
+        clipboard().request_uris(move |_, uris| {
+            let files: Vec<PathBuf> = uris
+                .iter()
+                .filter_map(|uri| url::Url::parse(uri).ok()?.to_file_path().ok())
+                .collect();
+            reply(Ok((!files.is_empty()).then_some(files)))
+        });
+    }
+
+    pub fn formats<F>(reply: F)
+    where
+        F: FnOnce(Result<Vec<Format>>) + 'static,
+    {
+        clipboard().request_contents(&gdk::Atom::intern("TARGETS"), move |_, data| {
+            let html = data
+                .targets()
+                .is_some_and(|targets| targets.iter().any(|target| target.name() == HTML));
+            let formats = [
+                (Format::Text, data.targets_include_text()),
+                (Format::Html, html),
+                (Format::Files, data.targets_include_uri()),
+                (Format::Image, data.targets_include_image(false)),
+            ];
+            reply(Ok(formats
+                .into_iter()
+                .filter_map(|(format, available)| available.then_some(format))
+                .collect()))
+        });
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+mod platform {
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+
+    use super::Format;
+
+    pub fn write_text(_text: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    pub fn read_text<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        reply(Ok(None))
+    }
+
+    pub fn write_html(_html: &str, _alt_text: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    pub fn read_html<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<String>>) + 'static,
+    {
+        reply(Ok(None))
+    }
+
+    pub fn read_files<F>(reply: F)
+    where
+        F: FnOnce(Result<Option<Vec<PathBuf>>>) + 'static,
+    {
+        reply(Ok(None))
+    }
+
+    pub fn formats<F>(reply: F)
+    where
+        F: FnOnce(Result<Vec<Format>>) + 'static,
+    {
+        reply(Ok(Vec::new()))
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::{api_manager::code, testing::LoopHarness};
+
+    fn call(harness: &LoopHarness, method: &str, args: Value) -> Value {
+        let response = harness.call(method, args);
+        assert_eq!(response.code(), code::OK, "{}: {}", method, response.msg());
+        response.result().clone()
+    }
+
+    // One session, since the clipboard is shared by the whole desktop.
+    #[test]
+    #[ignore = "needs a display"]
+    fn round_trips() {
+        let harness = LoopHarness::start();
+        if call(&harness, "clipboard_write_text", json!(["plain ünïcode"])) != json!(true) {
+            eprintln!("skipped: no clipboard on this platform");
+            return;
+        }
+        assert_eq!(
+            call(&harness, "clipboard_read_text", json!([])),
+            "plain ünïcode"
+        );
+        assert_eq!(
+            call(&harness, "clipboard_read_html", json!([])),
+            Value::Null
+        );
+
+        let html = "<b>bold</b> text";
+        assert_eq!(
+            call(&harness, "clipboard_write_html", json!([html, "alt"])),
+            json!(true)
+        );
+        let read = call(&harness, "clipboard_read_html", json!([]));
+        assert!(
+            read.as_str().is_some_and(|read| read.contains(html)),
+            "{}",
+            read
+        );
+        assert_eq!(call(&harness, "clipboard_read_text", json!([])), "alt");
+        let formats = call(&harness, "clipboard_formats", json!([]));
+        assert!(formats.as_array().is_some_and(|formats| {
+            formats.contains(&json!("text")) && formats.contains(&json!("html"))
+        }));
+
+        let table = "<table><tr><td>Cell</td><td>Next</td></tr></table>";
+        call(&harness, "clipboard_write_html", json!([table]));
+        assert_eq!(
+            call(&harness, "clipboard_read_text", json!([])),
+            "Cell\tNext"
+        );
+        assert_eq!(
+            call(&harness, "clipboard_read_files", json!([])),
+            Value::Null
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use crate::config::AppConfig;
+
+/// How long the app may take to answer a `close-requested` event by default.
+const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lets the app veto closing the window.
+///
+/// When enabled, `CloseRequested` only emits `close-requested`; the window
+/// closes once `confirm_close(true)` is called or the timeout passes.
+pub struct CloseGuard {
+    enabled: bool,
+    timeout: Duration,
+    deadline: Option<Instant>,
+}
+
+impl CloseGuard {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            enabled: config.confirm_close,
+            timeout: config
+                .close_timeout_ms
+                .map_or(DEFAULT_CLOSE_TIMEOUT, Duration::from_millis),
+            deadline: None,
+        }
+    }
+
+    /// Returns `false` if the window should close right away. A request
+    /// while one is pending is swallowed.
+    pub fn intercept(&mut self, now: Instant) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if self.deadline.is_none() {
+            self.deadline = Some(now + self.timeout);
+        }
+        true
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Ends the pending request. Returns `false` if none was pending.
+    pub fn resolve(&mut self) -> bool {
+        self.deadline.take().is_some()
+    }
+
+    /// Whether the pending request ran out of time; it is resolved if so.
+    pub fn expired(&mut self, now: Instant) -> bool {
+        if self.deadline.is_some_and(|deadline| deadline <= now) {
+            self.deadline = None;
+            return true;
+        }
+        false
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
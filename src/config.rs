@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{
+    env_access::EnvAccess,
+    fs_scope::FsScope,
+    kiosk::KioskOptions,
+    placement::{MonitorSelector, PositionStrategy},
+};
+
+/// How the event loop waits between events.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlFlowMode {
+    /// Sleep until the next event. Uses no CPU while idle.
+    #[default]
+    Wait,
+    /// Wake up every `tick_interval_ms` and emit a tick.
+    WaitUntil,
+    /// Never sleep. Ticks are still paced by `tick_interval_ms`, if set.
+    Poll,
+}
+
+/// Creation options passed from Python as a JSON object.
+///
+/// Every field is optional so older callers keep working.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AppConfig {
+    pub control_flow: ControlFlowMode,
+    pub tick_interval_ms: Option<u64>,
+    /// Forward ticks to the frontend as `pyframe:tick` events.
+    pub forward_ticks: bool,
+    /// Directories whose files native APIs may read or open. Empty denies all.
+    pub allowed_read_paths: Vec<PathBuf>,
+    /// Directories and glob patterns the `fs_*` APIs may access.
+    pub fs_scope: FsScope,
+    /// URL schemes `shell_open` may hand to the OS; `https` and `mailto` if
+    /// unset. Files are checked against `fs_scope` instead.
+    pub open_schemes: Option<Vec<String>>,
+    /// Environment variables and arguments the `env_*` APIs expose.
+    pub env_access: EnvAccess,
+    /// Origins and size limit for `http_fetch`.
+    pub http: crate::http_fetch::HttpConfig,
+    /// Largest serialized size of one `state_*` document; 1 MiB if unset.
+    pub state_max_bytes: Option<usize>,
+    /// Size and lifetime of the cache answering retried requests.
+    pub idempotency: crate::idempotency::IdempotencyConfig,
+    /// URLs `ws_connect` may open: `wss://host` allows the whole host,
+    /// `wss://host/path` only paths below it.
+    pub ws_allowed_urls: Vec<String>,
+    /// Manifest URL, running version and signing key for `updater_*`.
+    pub updater: Option<crate::updater::UpdaterConfig>,
+    /// Programs `exec_command` and `process_spawn` may run, matched exactly
+    /// against their `command`.
+    pub allowed_commands: Vec<String>,
+    /// Ask the app through a `close-requested` event before closing the window.
+    pub confirm_close: bool,
+    /// How long `confirm_close` may take before the window closes anyway.
+    pub close_timeout_ms: Option<u64>,
+    /// Minimizing or closing the window hides it into a tray icon.
+    pub minimize_to_tray: bool,
+    /// PNG/ICO for the tray icon; a plain circle is used if unset.
+    pub tray_icon: Option<PathBuf>,
+    /// Load this URL instead of the HTML passed to `create_webframe`.
+    pub url: Option<String>,
+    /// Serve the page from a `127.0.0.1` HTTP server instead of `with_html`.
+    /// Can't be combined with `url`.
+    pub local_server: Option<crate::local_server::LocalServerOptions>,
+    /// Port the local server is bound to, filled in at startup.
+    #[serde(skip)]
+    pub local_server_port: Option<u16>,
+    /// Directories served under custom URI schemes, e.g. `{"app": "dist"}`
+    /// for `app://localhost/index.html`. Loaded into memory at startup.
+    pub assets: HashMap<String, PathBuf>,
+    /// Inline HTML or path of an HTML file shown while the main content loads.
+    pub splash: Option<String>,
+    /// Longest the splash stays up without a readiness signal.
+    pub splash_timeout_ms: Option<u64>,
+    /// Keep the splash until the page calls `pyframe.invoke("__frame_ready")`
+    /// instead of dismissing it when the page has loaded.
+    pub splash_wait_for_ready: bool,
+    /// Foreign window the frame is created in, e.g. a Qt widget's `winId()`.
+    /// See [`crate::embed`].
+    pub parent_handle: Option<u64>,
+    /// Transparent window and WebView, e.g. to show `set_vibrancy` through.
+    pub transparent: bool,
+    /// Background effect applied at creation, as for `set_effect`.
+    pub effect: Option<String>,
+    /// Save size, position and maximized state and restore them next launch.
+    pub persist_window_state: bool,
+    /// Names the directories the window state, `store_*` data and crash
+    /// reports are kept in.
+    pub app_id: Option<String>,
+    /// Monitor the window opens on; the primary one if it isn't connected.
+    pub monitor: Option<MonitorSelector>,
+    pub position_strategy: Option<PositionStrategy>,
+    /// Install the `fetch` hook behind `mock_http_request`. For tests only.
+    pub enable_mocking: bool,
+    /// `true` or an object overriding single kiosk components.
+    #[serde(deserialize_with = "crate::kiosk::deserialize")]
+    pub kiosk: Option<KioskOptions>,
+    /// What happens when the page asks for the camera, microphone or location.
+    pub permissions: crate::permissions::PermissionsConfig,
+    /// Replace `navigator.geolocation` with positions from `set_mock_location`.
+    pub mock_geolocation: bool,
+    /// `default`, `disabled`, or `custom` to emit `context-menu` instead.
+    pub context_menu: crate::context_menu::ContextMenuMode,
+    /// HTML shown when the page fails to load, with `{{error}}` and
+    /// `{{url}}` placeholders; a built-in page if unset.
+    pub error_page: Option<String>,
+    /// Retry a failing initial load with backoff before giving up.
+    pub load_retry: Option<crate::load_error::LoadRetry>,
+    /// Keep the window title in step with `document.title`.
+    pub sync_title: bool,
+    /// Window title for `sync_title`, with a `{title}` placeholder.
+    pub title_format: Option<String>,
+    /// macOS: draw the title bar over the window background.
+    pub titlebar_transparent: bool,
+    /// macOS: hide the title text, keeping the traffic lights.
+    pub title_hidden: bool,
+    /// macOS: let the content extend under the title bar.
+    pub fullsize_content_view: bool,
+    /// macOS: where the traffic lights go.
+    pub traffic_light_offset: Option<crate::titlebar::Offset>,
+    /// Windows: keep the native frame but let the page fill the title bar,
+    /// see [`crate::titlebar_overlay`].
+    pub titlebar_overlay: bool,
+    /// macOS: whether the app shows in the dock and the Cmd+Tab switcher.
+    pub activation_policy: Option<crate::dock::ActivationPolicy>,
+    /// macOS: a dock icon click brings a hidden window back; on if unset.
+    pub reopen_on_dock_click: Option<bool>,
+    /// Filter, format and log files of the Rust internals' diagnostics.
+    pub log: crate::diagnostics::LogConfig,
+    /// Record calls of privileged APIs, see [`crate::audit`].
+    pub audit: Option<crate::audit::AuditConfig>,
+    /// `"tcp"` or `"ipc-only"`, see [`crate::transport`].
+    pub transport: crate::transport::Transport,
+    /// APIs the page may call through `pyframe.invoke` in ipc-only mode.
+    /// Empty, the default, allows none.
+    pub page_apis: Vec<String>,
+    /// The WebSocket server the page connects to, if it has one.
+    #[serde(skip)]
+    pub ws_port: Option<u16>,
+    #[serde(skip)]
+    pub socket_url: Option<String>,
+    /// Port the TCP bridge is bound to, filled in at startup.
+    #[serde(skip)]
+    pub tcp_port: Option<u16>,
+    /// Reject named arguments no parameter of the API has, instead of
+    /// ignoring them.
+    pub strict_args: bool,
+}
+
+/// Used for the storage path when no `app_id` is configured.
+const DEFAULT_APP_ID: &str = "pyframe";
+
+impl AppConfig {
+    /// `<config dir>/<app_id>`, where persisted state lives. `None` if the
+    /// platform has no config directory.
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        let app_id = self.app_id.as_deref().unwrap_or(DEFAULT_APP_ID);
+        let dirs = directories::ProjectDirs::from("", "", app_id)?;
+        Some(dirs.config_dir().to_path_buf())
+    }
+
+    /// `<data dir>/<app_id>/logs`, where crash reports and log files are
+    /// written.
+    pub fn log_dir(&self) -> Option<PathBuf> {
+        let app_id = self.app_id.as_deref().unwrap_or(DEFAULT_APP_ID);
+        let dirs = directories::ProjectDirs::from("", "", app_id)?;
+        Some(dirs.data_local_dir().join("logs"))
+    }
+
+    pub fn open_schemes(&self) -> Vec<String> {
+        match &self.open_schemes {
+            Some(schemes) => schemes.clone(),
+            None => crate::shell::DEFAULT_OPEN_SCHEMES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn from_json(json: Option<&str>) -> Result<Self> {
+        match json {
+            Some(json) if !json.trim().is_empty() => Ok(serde_json::from_str(json)?),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+/// Writes `bytes` next to `path` and renames the file into place, so a crash
+/// can't leave half a file.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid path {:?}", path))?;
+    fs::create_dir_all(dir)?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
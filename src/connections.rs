@@ -1,79 +1,784 @@
-// use serde_json::Value;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use std::sync::Arc;
 
-use crate::utils::FrameEventLoopProxy;
+use serde_json::Value;
+use tokio::sync::{broadcast, oneshot, Semaphore};
 
-/// Startet den Tokio TCP-Server
-pub async fn start_server(
-    proxy: FrameEventLoopProxy,
-    pending: super::utils::PendingMap,
-) -> tokio::io::Result<()> {
-    let port = std::env::var("RUSTADDR").unwrap_or_else(|_| "9000".to_string());
-    let addr = format!("127.0.0.1:{}", port);
-    let listener = TcpListener::bind(&addr).await?;
-    println!("[TCP] Listening on {}", addr);
+use crate::{
+    api_manager::{code, ApiRequest, ApiResponse},
+    audit::AuditLog,
+    emit::{Emit, EmitGate},
+    event_stream::EventStream,
+    idempotency::Recording,
+    lanes::{Priority, RequestLanes},
+    lock_force,
+    timing::RequestTiming,
+    utils::{FrameEventLoopProxy, PendingMap, PendingResponse, UserEvent},
+};
 
-    loop {
-        let (socket, _) = listener.accept().await?;
-        let proxy = proxy.clone();
-        let pending = pending.clone();
-        tokio::spawn(async move {
-            if let Err(e) = handle_client(socket, proxy, pending).await {
-                eprintln!("[TCP] Fehler: {:?}", e);
+// The sockets, left out of builds for `transport: "ipc-only"` only.
+#[cfg(feature = "tcp")]
+use crate::{
+    api_manager::BadArgs,
+    audit::Record,
+    event_stream::SUBSCRIBE_METHOD,
+    idempotency::{Lookup, ReplyCache},
+    protocol::{self, Capabilities, Feature, Greeting},
+    request_context::{Peer, Principal, RequestContext},
+};
+#[cfg(feature = "tcp")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tcp")]
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Weak,
+    },
+};
+#[cfg(feature = "tcp")]
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+    sync::{mpsc, Notify},
+};
+
+/// The TCP port when `RUSTADDR` is unset.
+#[cfg(feature = "tcp")]
+const DEFAULT_PORT: u16 = 9000;
+/// Maximum number of requests queued into the event loop across all connections.
+pub const MAX_IN_FLIGHT_REQUESTS: usize = 64;
+/// In-flight permits background requests leave to the other lanes.
+const BACKGROUND_RESERVE: usize = 16;
+#[cfg(feature = "tcp")]
+/// Maximum number of response frames buffered toward a single client.
+const RESPONSE_QUEUE_CAPACITY: usize = 32;
+#[cfg(feature = "tcp")]
+/// Maximum number of push events buffered toward a single client.
+const PUSH_QUEUE_CAPACITY: usize = 64;
+
+#[cfg(feature = "tcp")]
+/// Most requests in one batch frame.
+pub const MAX_BATCH_SIZE: usize = 32;
+
+/// Identifies a TCP client for as long as it stays connected.
+pub type ConnectionId = u64;
+
+#[cfg(feature = "tcp")]
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(1);
+
+#[cfg(feature = "tcp")]
+/// Push events waiting for a connection's writer.
+///
+/// Unlike responses, push events are not tied to a request, so a slow client
+/// loses the oldest event instead of stalling the sender.
+#[derive(Default)]
+pub struct PushQueue {
+    events: std::sync::Mutex<VecDeque<Value>>,
+    notify: Notify,
+}
+
+#[cfg(feature = "tcp")]
+impl PushQueue {
+    pub fn push(&self, event: Value) {
+        {
+            let mut events = lock_force!(self.events);
+            if events.len() >= PUSH_QUEUE_CAPACITY {
+                events.pop_front();
+                tracing::warn!("[TCP] Push-Queue voll, ältestes Event verworfen");
             }
-        });
+            events.push_back(event);
+        }
+        self.notify.notify_one();
+    }
+
+    fn pop(&self) -> Option<Value> {
+        lock_force!(self.events).pop_front()
+    }
+}
+
+#[cfg(feature = "tcp")]
+/// A frame from a client after the hello.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Incoming {
+    Call(Call),
+    /// Answered with nothing.
+    Emit(Box<Emit>),
+}
+
+#[cfg(feature = "tcp")]
+/// A frame answered with a response.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Call {
+    Request(ApiRequest),
+    Batch(BatchRequest),
+}
+
+#[cfg(feature = "tcp")]
+/// Requests sent in one frame, answered together by a [`BatchResponse`].
+#[derive(Deserialize)]
+#[serde(tag = "type", rename = "batch")]
+struct BatchRequest {
+    requests: Vec<ApiRequest>,
+}
+
+#[cfg(feature = "tcp")]
+/// The responses to a [`BatchRequest`], in its order.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "batch")]
+struct BatchResponse {
+    responses: Vec<ApiResponse>,
+}
+
+#[cfg(feature = "tcp")]
+/// A frame for a client, other than push events.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Outgoing {
+    Response(ApiResponse),
+    Batch(BatchResponse),
+}
+
+#[cfg(feature = "tcp")]
+impl From<ApiResponse> for Outgoing {
+    fn from(response: ApiResponse) -> Self {
+        Outgoing::Response(response)
+    }
+}
+
+/// Hands accepted requests to whatever answers them.
+///
+/// The app forwards them into the tao event loop; the headless benchmark
+/// server answers them directly. Without the `tcp` feature only
+/// [`Dispatch::dispatch`] is used, by the lanes of in-process calls.
+#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+pub trait Dispatch: Clone + Send + 'static {
+    fn dispatch(&self, req: ApiRequest) -> Result<(), String>;
+
+    /// Events for connections that send [`SUBSCRIBE_METHOD`], if supported.
+    fn subscribe(&self) -> Option<broadcast::Receiver<Value>> {
+        None
+    }
+
+    /// Pushes `emit` to the pages without waiting; dropped if unsupported.
+    fn emit(&self, _emit: Emit) {}
+
+    /// Called once `connection` has closed.
+    fn disconnected(&self, _connection: ConnectionId) {}
+
+    /// Called when `connection` skipped the hello and is served as version 0.
+    fn legacy_protocol(&self, _connection: ConnectionId) {}
+
+    /// Where calls of audited methods are recorded, if anywhere.
+    fn audit(&self) -> Option<Arc<AuditLog>> {
+        None
+    }
+}
+
+/// Forwards requests into the tao event loop and streams its events.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+pub struct AppDispatch {
+    pub proxy: FrameEventLoopProxy,
+    pub events: EventStream,
+    pub emits: Arc<EmitGate>,
+    pub audit: Option<Arc<AuditLog>>,
+}
+
+impl Dispatch for AppDispatch {
+    fn dispatch(&self, req: ApiRequest) -> Result<(), String> {
+        self.proxy
+            .send_event(UserEvent::Request(req))
+            .map_err(|_| "The event loop has exited".to_string())
+    }
+
+    fn subscribe(&self) -> Option<broadcast::Receiver<Value>> {
+        Some(self.events.subscribe())
+    }
+
+    fn emit(&self, emit: Emit) {
+        if self.emits.admit()
+            && self
+                .proxy
+                .send_event(UserEvent::Emit(Box::new(emit)))
+                .is_err()
+        {
+            self.emits.taken();
+            tracing::debug!("[TCP] Emit dropped, the event loop has exited");
+        }
+    }
+
+    // Once the loop has exited, nothing is left to release or warn about.
+    fn disconnected(&self, connection: ConnectionId) {
+        if self
+            .proxy
+            .send_event(UserEvent::Disconnected(connection))
+            .is_err()
+        {
+            tracing::debug!(
+                "[TCP] Connection {} closed after the event loop",
+                connection
+            );
+        }
+    }
+
+    fn legacy_protocol(&self, connection: ConnectionId) {
+        let _ = self.proxy.send_event(UserEvent::LegacyProtocol(connection));
+    }
+
+    fn audit(&self) -> Option<Arc<AuditLog>> {
+        self.audit.clone()
+    }
+}
+
+/// The port of `RUSTADDR` couldn't be bound.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+pub struct BindFailed {
+    pub port: u16,
+    pub error: std::io::Error,
+}
+
+impl std::fmt::Display for BindFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Can't listen on port {}: {}", self.port, self.error)
     }
 }
-pub async fn handle_client(
-    socket: tokio::net::TcpStream,
-    proxy: FrameEventLoopProxy,
-    pending: super::utils::PendingMap,
+
+impl std::error::Error for BindFailed {}
+
+#[cfg(feature = "tcp")]
+/// Binds the listener on `127.0.0.1:$RUSTADDR`, 9000 if unset; port 0
+/// picks a free one, read back with `local_addr`. Bound before the window
+/// opens, so the page's script and `app_metadata` get the actual port.
+pub fn bind() -> anyhow::Result<std::net::TcpListener> {
+    let port = match std::env::var("RUSTADDR") {
+        Ok(port) => port
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| anyhow::anyhow!("RUSTADDR must be a port number, not {:?}", port))?,
+        Err(_) => DEFAULT_PORT,
+    };
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|error| BindFailed { port, error })?;
+    Ok(listener)
+}
+
+#[cfg(feature = "tcp")]
+/// Startet den Tokio TCP-Server auf dem Listener von [`bind`].
+pub async fn start_server<D: Dispatch>(
+    listener: std::net::TcpListener,
+    dispatch: D,
+    pending: PendingMap,
+    in_flight: Arc<Semaphore>,
+    lanes: Arc<RequestLanes>,
+    replies: Arc<ReplyCache>,
 ) -> tokio::io::Result<()> {
-    let socket = std::sync::Arc::new(tokio::sync::Mutex::new(socket));
+    let listener = TcpListener::from_std(listener)?;
+    tracing::info!("[TCP] Listening on {}", listener.local_addr()?);
+    serve(listener, dispatch, pending, in_flight, lanes, replies).await
+}
 
-    loop {
-        // Länge lesen (4 Byte BE)
-        let mut len_buf = [0u8; 4];
-        if socket.lock().await.read_exact(&mut len_buf).await.is_err() {
-            break;
+#[cfg(feature = "tcp")]
+/// Accepts clients and feeds their requests through `lanes` into `dispatch`.
+pub async fn serve<D: Dispatch>(
+    listener: TcpListener,
+    dispatch: D,
+    pending: PendingMap,
+    in_flight: Arc<Semaphore>,
+    lanes: Arc<RequestLanes>,
+    replies: Arc<ReplyCache>,
+) -> tokio::io::Result<()> {
+    let accept = async {
+        loop {
+            let (socket, _) = listener.accept().await?;
+            let dispatch = dispatch.clone();
+            let pending = pending.clone();
+            let in_flight = in_flight.clone();
+            let lanes = lanes.clone();
+            let replies = replies.clone();
+            crate::tasks::spawn("tcp_client", async move {
+                let (reader, writer) = socket.into_split();
+                if let Err(e) =
+                    handle_client(reader, writer, dispatch, pending, in_flight, lanes, replies)
+                        .await
+                {
+                    tracing::error!("[TCP] Fehler: {:?}", e);
+                }
+            });
+        }
+    };
+    tokio::select! {
+        result = accept => result,
+        _ = lanes.pump(dispatch.clone(), pending.clone()) => Ok(()),
+    }
+}
+
+/// Delivers a response to the waiting connection and releases its
+/// in-flight permit.
+pub fn respond(pending: &PendingMap, key: u8, response: ApiResponse) {
+    if let Some(pending) = lock_force!(pending).remove(&key) {
+        let _ = pending.sender.send(response);
+    } else {
+        tracing::warn!("Kein Sender für Schlüssel {} gefunden", key);
+    }
+}
+
+/// The answer to a request, once there is one.
+enum Reply {
+    Ready(ApiResponse),
+    /// Waiting in the pending map, or for the request this one retries; the
+    /// fallback answers it if the sender is dropped. The response is cached
+    /// if it is recorded.
+    Pending(
+        oneshot::Receiver<ApiResponse>,
+        ApiResponse,
+        Option<Recording>,
+        Option<Arc<RequestTiming>>,
+    ),
+}
+
+impl Reply {
+    /// The response, with the request's `meta` if it was queued here.
+    async fn wait(self) -> ApiResponse {
+        match self {
+            Reply::Ready(response) => response,
+            Reply::Pending(rx, fallback, recording, timing) => match rx.await {
+                Ok(response) => {
+                    // Cached without it, so a replay doesn't carry stale times.
+                    if let Some(recording) = recording {
+                        recording.finish(&response);
+                    }
+                    match timing {
+                        Some(timing) => response.with_meta(timing.finish()),
+                        None => response,
+                    }
+                }
+                Err(_) => {
+                    tracing::warn!("[TCP] Antwort-Kanal abgebrochen");
+                    if let Some(recording) = recording {
+                        recording.abandon(&fallback);
+                    }
+                    fallback
+                }
+            },
         }
-        let len = u32::from_be_bytes(len_buf) as usize;
+    }
+}
+
+#[cfg(feature = "tcp")]
+/// A [`Reply`] with the audit record of its request, if it is audited.
+struct Audited(Reply, Option<Record>);
+
+#[cfg(feature = "tcp")]
+impl Audited {
+    /// The response, with `meta` if the connection has [`Feature::Timing`].
+    async fn wait(self, timing: bool) -> ApiResponse {
+        let response = self.0.wait().await;
+        if let Some(record) = self.1 {
+            record.finish(&response);
+        }
+        if timing {
+            response
+        } else {
+            response.without_meta()
+        }
+    }
+}
+
+#[cfg(feature = "tcp")]
+/// Answers `req` from the reply cache if it retries an earlier request of
+/// `client`, else queues it.
+fn submit(
+    req: ApiRequest,
+    client: &str,
+    connection: ConnectionId,
+    pending: &PendingMap,
+    in_flight: &Arc<Semaphore>,
+    lanes: &RequestLanes,
+    replies: &Arc<ReplyCache>,
+) -> Reply {
+    tracing::trace!("[TCP] Request {} {} from {}", req.0, req.1, client);
+    let fallback = req.err(code::ERROR, "The request was dropped without a response");
+    match replies.begin(client, &req) {
+        Lookup::Run(recording) => match queue(req, connection, pending, in_flight, lanes) {
+            Reply::Ready(response) => {
+                if let Some(recording) = recording {
+                    recording.finish(&response);
+                }
+                Reply::Ready(response)
+            }
+            Reply::Pending(rx, fallback, _, timing) => {
+                Reply::Pending(rx, fallback, recording, timing)
+            }
+        },
+        Lookup::Wait(rx) => Reply::Pending(rx, fallback, None, None),
+        Lookup::Replay(response) | Lookup::Collision(response) => Reply::Ready(response),
+    }
+}
+
+/// Queues a request from inside the process, see [`crate::transport`].
+pub fn queue_local(
+    req: ApiRequest,
+    pending: &PendingMap,
+    in_flight: &Arc<Semaphore>,
+    lanes: &RequestLanes,
+) -> impl std::future::Future<Output = ApiResponse> + Send + 'static {
+    let reply = queue(req, crate::transport::IN_PROCESS, pending, in_flight, lanes);
+    reply.wait()
+}
+
+/// Takes an in-flight permit for `req` and queues it in its lane.
+fn queue(
+    mut req: ApiRequest,
+    connection: ConnectionId,
+    pending: &PendingMap,
+    in_flight: &Arc<Semaphore>,
+    lanes: &RequestLanes,
+) -> Reply {
+    if req.metadata().priority == Priority::Background
+        && in_flight.available_permits() <= BACKGROUND_RESERVE
+    {
+        return Reply::Ready(req.err(
+            code::BUSY,
+            "Too many requests in flight for background work",
+        ));
+    }
+    let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+        return Reply::Ready(req.err(code::BUSY, "Too many requests in flight"));
+    };
+
+    // oneshot-Kanal für Antwort
+    let (tx, rx) = oneshot::channel();
+    let id = req.0;
+    let fallback = req.err(code::ERROR, "The request was dropped without a response");
+    {
+        let mut pending = lock_force!(pending);
+        // Ids are shared by all connections, e.g. of two processes.
+        if pending.contains_key(&id) {
+            return Reply::Ready(req.err(
+                code::BUSY,
+                format!("Request id {} is already waiting for a response", id),
+            ));
+        }
+        pending.insert(
+            id,
+            PendingResponse {
+                sender: tx,
+                connection,
+                permit,
+            },
+        );
+    }
+
+    let timing = RequestTiming::start();
+    req.3.timing = Some(timing.clone());
+    // Request in seine Lane einreihen
+    if let Err(req) = lanes.push(req) {
+        lock_force!(pending).remove(&id);
+        return Reply::Ready(req.err(code::BUSY, "Too many requests queued in this priority lane"));
+    }
+    Reply::Pending(rx, fallback, None, Some(timing))
+}
+
+#[cfg(feature = "tcp")]
+/// Serves one client; `reader` and `writer` are the halves of its socket,
+/// or of an in-memory stream in [`crate::testing`].
+pub async fn handle_client<D, R, W>(
+    mut reader: R,
+    mut writer: W,
+    dispatch: D,
+    pending: PendingMap,
+    in_flight: Arc<Semaphore>,
+    lanes: Arc<RequestLanes>,
+    replies: Arc<ReplyCache>,
+) -> tokio::io::Result<()>
+where
+    D: Dispatch,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let Some(first) = read_frame(&mut reader).await? else {
+        return Ok(());
+    };
+    let (capabilities, mut first) = match protocol::greet(&first) {
+        Greeting::Accepted(capabilities) => {
+            write_frame(&mut writer, &capabilities.ack()).await?;
+            (capabilities, None)
+        }
+        Greeting::Rejected(rejection) => {
+            write_frame(&mut writer, &rejection.frame()).await?;
+            return writer.shutdown().await;
+        }
+        Greeting::Legacy => (Capabilities::legacy(), Some(first)),
+    };
+    let connection = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+    if capabilities.version == 0 {
+        dispatch.legacy_protocol(connection);
+    }
+    // Clients that don't name themselves can only retry on this connection.
+    let client = capabilities
+        .client
+        .clone()
+        .unwrap_or_else(|| format!("connection-{}", connection));
+    tracing::debug!(
+        "[TCP] Connection {} opened by {} (protocol {})",
+        connection,
+        client,
+        capabilities.version
+    );
+    let timing = capabilities.has(Feature::Timing);
+    let peer = Arc::new(Peer {
+        connection,
+        principal: Principal {
+            client: client.clone(),
+            authenticated: capabilities.version > 0 && protocol::expected_token().is_some(),
+        },
+        features: capabilities
+            .features
+            .iter()
+            .filter_map(|feature| serde_json::to_value(feature).ok())
+            .filter_map(|feature| feature.as_str().map(str::to_string))
+            .collect(),
+    });
+    let (responses, response_rx) = mpsc::channel::<Outgoing>(RESPONSE_QUEUE_CAPACITY);
+    let events = Arc::new(PushQueue::default());
+    let audit = dispatch.audit();
+    let submit_audited = |mut req: ApiRequest| {
+        req.3.context = Some(Box::new(RequestContext::new(peer.clone(), &req.3)));
+        let record = audit
+            .as_ref()
+            .and_then(|audit| audit.begin(&req, connection, &client));
+        let reply = submit(
+            req, &client, connection, &pending, &in_flight, &lanes, &replies,
+        );
+        Audited(reply, record)
+    };
+    let writer_task = crate::tasks::spawn(
+        "tcp_writer",
+        write_frames(writer, response_rx, events.clone()),
+    );
 
-        // Payload lesen
-        let mut buf = vec![0u8; len];
-        socket.lock().await.read_exact(&mut buf).await?;
-        //let d:Value = serde_json::from_slice(&buf)?;
-        let req: crate::api_manager::ApiRequest = match serde_json::from_slice(&buf) {
+    loop {
+        let buf = match first.take() {
+            Some(buf) => buf,
+            None => match read_frame(&mut reader).await? {
+                Some(buf) => buf,
+                None => break,
+            },
+        };
+        let (json, attachment) = if buf.first() == Some(&protocol::BINARY_MARKER) {
+            match protocol::split_attachment(buf) {
+                Some((json, attachment)) => (json, Some(attachment)),
+                None => {
+                    tracing::warn!("[TCP] Binär-Frame zu kurz");
+                    continue;
+                }
+            }
+        } else {
+            (buf.into(), None)
+        };
+        let incoming: Incoming = match serde_json::from_slice(&json) {
             Ok(r) => r,
             Err(e) => {
-                eprintln!("[TCP] JSON-Fehler: {:?}", e);
+                tracing::warn!("[TCP] JSON-Fehler: {:?}", e);
                 continue;
             }
         };
 
-        // oneshot-Kanal für Antwort
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        {
-            let mut map = pending.lock().unwrap();
-            map.insert(req.0.clone(), tx);
-        }
+        // Emits get no response and don't wait for a slot.
+        let call = match incoming {
+            Incoming::Call(call) => call,
+            Incoming::Emit(emit) => {
+                if capabilities.has(Feature::Emit) {
+                    dispatch.emit(*emit);
+                } else {
+                    tracing::warn!("[TCP] Emit ohne emit-Feature verworfen");
+                }
+                continue;
+            }
+        };
+
+        // Platz für die Antwort reservieren, bevor der Request angenommen wird.
+        // Liest der Client seine Antworten nicht, staut sich hier der Socket.
+        let Ok(slot) = responses.clone().reserve_owned().await else {
+            break;
+        };
+
+        let mut req = match call {
+            Call::Request(req) => req,
+            Call::Batch(batch) => {
+                let replies: Vec<Audited> = if !capabilities.has(Feature::Batch) {
+                    batch
+                        .requests
+                        .iter()
+                        .map(|req| {
+                            Reply::Ready(req.err(
+                                code::UNSUPPORTED,
+                                "Batches need the batch feature in the hello",
+                            ))
+                        })
+                        .map(|reply| Audited(reply, None))
+                        .collect()
+                } else if batch.requests.len() > MAX_BATCH_SIZE {
+                    let message = format!("Batches are limited to {} requests", MAX_BATCH_SIZE);
+                    batch
+                        .requests
+                        .iter()
+                        .map(|req| Reply::Ready(req.err(code::ERROR, message.clone())))
+                        .map(|reply| Audited(reply, None))
+                        .collect()
+                } else {
+                    batch.requests.into_iter().map(submit_audited).collect()
+                };
+                crate::tasks::spawn("tcp_batch_response", async move {
+                    let mut responses = Vec::with_capacity(replies.len());
+                    for reply in replies {
+                        responses.push(reply.wait(timing).await);
+                    }
+                    slot.send(Outgoing::Batch(BatchResponse { responses }));
+                });
+                continue;
+            }
+        };
 
-        // Request in Eventloop pushen
-        let _ = proxy.send_event(crate::utils::UserEvent::Request(req));
+        if let Some(attachment) = attachment {
+            if !capabilities.has(Feature::Binary) {
+                slot.send(
+                    req.err(
+                        code::UNSUPPORTED,
+                        "Attachments need the binary feature in the hello",
+                    )
+                    .into(),
+                );
+                continue;
+            }
+            if attachment.len() > protocol::MAX_ATTACHMENT_BYTES {
+                let err = anyhow::Error::new(BadArgs {
+                    reason: format!(
+                        "The attachment has {} bytes, more than {}",
+                        attachment.len(),
+                        protocol::MAX_ATTACHMENT_BYTES
+                    ),
+                });
+                slot.send(req.err(code::of(&err), err.to_string()).into());
+                continue;
+            }
+            req.3.attachment = Some(attachment);
+        }
 
-        // Antwort synchron abwarten
-        match rx.await {
-            Ok(resp) => {
-                if let Ok(payload) = serde_json::to_vec(&resp) {
-                    let mut msg = (payload.len() as u32).to_be_bytes().to_vec();
-                    msg.extend_from_slice(&payload);
-                    socket.lock().await.write_all(&msg).await?;
+        if req.1 == SUBSCRIBE_METHOD {
+            if !capabilities.has(Feature::Streaming) {
+                slot.send(
+                    req.err(
+                        code::UNSUPPORTED,
+                        "Subscribing needs the streaming feature in the hello",
+                    )
+                    .into(),
+                );
+                continue;
+            }
+            match dispatch.subscribe() {
+                Some(rx) => {
+                    crate::tasks::spawn(
+                        "event_forwarder",
+                        forward_events(rx, Arc::downgrade(&events)),
+                    );
+                    slot.send(req.ok(true).into());
+                }
+                None => {
+                    slot.send(
+                        req.err(code::ERROR, "Event streaming is not supported")
+                            .into(),
+                    );
                 }
             }
-            Err(_) => eprintln!("[TCP] Antwort-Kanal abgebrochen"),
+            continue;
         }
+
+        let reply = submit_audited(req);
+        crate::tasks::spawn("tcp_response", async move {
+            slot.send(reply.wait(timing).await.into());
+        });
+    }
+
+    dispatch.disconnected(connection);
+    tracing::debug!("[TCP] Connection {} closed", connection);
+    // Der Writer endet, sobald alle reservierten Antworten geschrieben sind.
+    drop(responses);
+    writer_task.await.unwrap_or(Ok(()))
+}
+
+#[cfg(feature = "tcp")]
+/// Reads a length-prefixed frame; `None` once the client has closed.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> tokio::io::Result<Option<Vec<u8>>> {
+    // Länge lesen (4 Byte BE)
+    let mut len_buf = [0u8; 4];
+    if reader.read_exact(&mut len_buf).await.is_err() {
+        return Ok(None);
     }
+    let len = u32::from_be_bytes(len_buf) as usize;
 
+    // Payload lesen
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+    Ok(Some(buf))
+}
+
+#[cfg(feature = "tcp")]
+/// Pushes events to a subscribed connection until it closes.
+async fn forward_events(mut rx: broadcast::Receiver<Value>, events: Weak<PushQueue>) {
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!(
+                    "[TCP] Event-Abonnent zu langsam, {} Events übersprungen",
+                    skipped
+                );
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let Some(events) = events.upgrade() else {
+            break;
+        };
+        events.push(event);
+    }
+}
+
+#[cfg(feature = "tcp")]
+async fn write_frames(
+    mut writer: impl AsyncWrite + Unpin,
+    mut responses: mpsc::Receiver<Outgoing>,
+    events: Arc<PushQueue>,
+) -> tokio::io::Result<()> {
+    loop {
+        tokio::select! {
+            biased;
+            resp = responses.recv() => match resp {
+                Some(resp) => write_frame(&mut writer, &resp).await?,
+                None => break,
+            },
+            _ = events.notify.notified() => {
+                while let Some(event) = events.pop() {
+                    write_frame(&mut writer, &event).await?;
+                }
+            }
+        }
+    }
     Ok(())
 }
+
+#[cfg(feature = "tcp")]
+async fn write_frame<T: Serialize>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+) -> tokio::io::Result<()> {
+    let payload = serde_json::to_vec(value)?;
+    let mut msg = (payload.len() as u32).to_be_bytes().to_vec();
+    msg.extend_from_slice(&payload);
+    writer.write_all(&msg).await
+}
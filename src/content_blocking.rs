@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+/// A single rule in WebKit Content Blocker JSON format.
+#[derive(Deserialize, Clone)]
+pub struct ContentRule {
+    pub trigger: Trigger,
+    pub action: Action,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Trigger {
+    pub url_filter: String,
+    #[serde(default)]
+    pub url_filter_is_case_sensitive: bool,
+    #[serde(default)]
+    pub if_domain: Vec<String>,
+    #[serde(default)]
+    pub unless_domain: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Action {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+struct CompiledRule {
+    filter: Regex,
+    rule: ContentRule,
+}
+
+impl CompiledRule {
+    fn matches(&self, url: &str) -> bool {
+        if !self.filter.is_match(url) {
+            return false;
+        }
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let trigger = &self.rule.trigger;
+        if !trigger.if_domain.is_empty()
+            && !trigger.if_domain.iter().any(|d| domain_matches(d, &host))
+        {
+            return false;
+        }
+        !trigger
+            .unless_domain
+            .iter()
+            .any(|d| domain_matches(d, &host))
+    }
+}
+
+/// `*example.com` matches the domain and its subdomains, `example.com` only itself.
+fn domain_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix('*') {
+        Some(base) => host == base || host.ends_with(&format!(".{}", base)),
+        None => host == pattern,
+    }
+}
+
+/// Content blocking rules of the app, kept in [`AppContext`](crate::context::AppContext)
+/// so they survive WebView reloads.
+///
+/// On macOS/iOS the rules are compiled by WebKit itself. Elsewhere only the
+/// `block` and `ignore-previous-rules` actions are honoured, for top-level
+/// navigations, through the WebView's navigation handler.
+#[derive(Default)]
+pub struct ContentBlocker {
+    rules_json: Option<String>,
+    rules: Vec<CompiledRule>,
+}
+
+impl ContentBlocker {
+    pub fn set_rules(&mut self, rules_json: &str) -> Result<()> {
+        let parsed: Vec<ContentRule> = serde_json::from_str(rules_json)?;
+        let mut rules = Vec::with_capacity(parsed.len());
+        for rule in parsed {
+            let filter = RegexBuilder::new(&rule.trigger.url_filter)
+                .case_insensitive(!rule.trigger.url_filter_is_case_sensitive)
+                .build()
+                .map_err(|e| anyhow!("Invalid url-filter {:?}: {}", rule.trigger.url_filter, e))?;
+            rules.push(CompiledRule { filter, rule });
+        }
+        self.rules_json = Some(rules_json.to_string());
+        self.rules = rules;
+        Ok(())
+    }
+
+    pub fn rules_json(&self) -> Option<&str> {
+        self.rules_json.as_deref()
+    }
+
+    /// Whether a navigation to `url` may proceed. Later rules override earlier ones.
+    pub fn allows(&self, url: &str) -> bool {
+        let mut blocked = false;
+        for rule in self.rules.iter().filter(|r| r.matches(url)) {
+            match rule.rule.action.kind.as_str() {
+                "block" => blocked = true,
+                "ignore-previous-rules" => blocked = false,
+                _ => {}
+            }
+        }
+        !blocked
+    }
+
+    /// Installs the rules in the browser engine where it supports content blockers.
+    ///
+    /// Returns `false` when only the navigation handler fallback is active.
+    pub fn apply_native(&self, webview: &wry::WebView) -> Result<bool> {
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            apple::apply(webview, self.rules_json.as_deref().unwrap_or("[]"))?;
+            Ok(true)
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        {
+            let _ = webview;
+            if self.rules.iter().any(|r| {
+                !matches!(
+                    r.rule.action.kind.as_str(),
+                    "block" | "ignore-previous-rules"
+                )
+            }) {
+                crate::log_warn!("Content blocking: only block/ignore-previous-rules actions are supported on this platform");
+            }
+            crate::log_warn!("Content blocking falls back to the navigation handler; subresources are not filtered");
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod apple {
+    use anyhow::{anyhow, Result};
+    use block2::RcBlock;
+    use objc2::MainThreadMarker;
+    use objc2_foundation::{NSError, NSString};
+    use objc2_web_kit::{WKContentRuleList, WKContentRuleListStore};
+
+    const RULE_LIST_ID: &str = "pyframe-content-rules";
+
+    pub fn apply(webview: &wry::WebView, rules_json: &str) -> Result<()> {
+        #[cfg(target_os = "ios")]
+        use wry::WebViewExtIOS as _;
+        #[cfg(target_os = "macos")]
+        use wry::WebViewExtMacOS as _;
+
+        let mtm = MainThreadMarker::new().ok_or_else(|| anyhow!("Not on the main thread"))?;
+        let store = unsafe { WKContentRuleListStore::defaultStore(mtm) }
+            .ok_or_else(|| anyhow!("WKContentRuleListStore unavailable"))?;
+        let controller = unsafe { webview.webview().configuration().userContentController() };
+
+        let handler = RcBlock::new(move |list: *mut WKContentRuleList, error: *mut NSError| {
+            if let Some(error) = unsafe { error.as_ref() } {
+                crate::log_err!(format!(
+                    "Content rule compilation failed: {}",
+                    error.localizedDescription()
+                ));
+                return;
+            }
+            if let Some(list) = unsafe { list.as_ref() } {
+                unsafe {
+                    controller.removeAllContentRuleLists();
+                    controller.addContentRuleList(list);
+                }
+            }
+        });
+        unsafe {
+            store.compileContentRuleListForIdentifier_encodedContentRuleList_completionHandler(
+                Some(&NSString::from_str(RULE_LIST_ID)),
+                Some(&NSString::from_str(rules_json)),
+                Some(&handler),
+            );
+        }
+        Ok(())
+    }
+}
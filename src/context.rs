@@ -1,22 +1,201 @@
 use anyhow::{anyhow, Result};
 use std::{
     collections::HashMap,
+    hash::{Hash, Hasher},
     sync::{Arc, Mutex},
 };
 use tao::window::{Window, WindowId};
 
+use serde_json::Value;
+
+use crate::{
+    api_manager::{ApiRequest, PermissionDenied, Responder},
+    click_through::ClickThrough,
+    close_guard::CloseGuard,
+    config::AppConfig,
+    connections::ConnectionId,
+    content_blocking::ContentBlocker,
+    element_fullscreen::ElementFullscreenState,
+    emit::EmitGate,
+    find::Finds,
+    fs_watch::FsWatchers,
+    geolocation::Position,
+    http_mock::MockRegistry,
+    kiosk::Kiosk,
+    kv_store::KvStore,
+    lanes::RequestLanes,
+    load_error::LoadErrors,
+    lock_force,
+    main_thread::MainThread,
+    native_handle::RenderTarget,
+    navigation::NavigationState,
+    permissions::{PermissionRequest, Permissions, Reason},
+    process_stats::ProcessMonitor,
+    processes::Processes,
+    script,
+    services::ServiceRegistry,
+    shutdown_hooks::ShutdownHooks,
+    splash::Splash,
+    state_store::StateStore,
+    ticker::Ticker,
+    timers::Timers,
+    title_sync::TitleSync,
+    titlebar_overlay::TitlebarOverlay,
+    transport::AppOrigin,
+    tray::TrayState,
+    updater::{Updater, UpdaterOp},
+    utils::{arc_mut, ArcMut, FrameEventLoopProxy, PendingMap},
+    window_events::{WindowRouter, WINDOW_LABEL},
+    window_shape::WindowShape,
+    window_state::WindowStateStore,
+    ws_client::WsConnections,
+};
+
+pub type WindowMap = Arc<Mutex<HashMap<WindowId, (Arc<Window>, Arc<wry::WebView>)>>>;
+
 #[derive(Clone)]
 pub struct AppContext {
     first_id: WindowId,
-    pub window: Arc<Mutex<HashMap<WindowId, (Arc<Window>, Arc<wry::WebView>)>>>,
+    pub window: WindowMap,
+    pub content_blocker: ArcMut<ContentBlocker>,
+    pub process_monitor: ArcMut<ProcessMonitor>,
+    pub ticker: ArcMut<Ticker>,
+    pub timers: ArcMut<Timers>,
+    pub proxy: FrameEventLoopProxy,
+    /// Handle to the runtime owned by [`App`](crate::core::App).
+    pub rt: tokio::runtime::Handle,
+    /// Requests waiting for a response, for [`Responder`].
+    pub pending: PendingMap,
+    /// The app's own caller of the API running now, e.g. `"timer 3"`.
+    pub app_caller: ArcMut<Option<String>>,
+    pub main_thread: MainThread,
+    pub config: AppConfig,
+    pub close_guard: ArcMut<CloseGuard>,
+    pub tray: ArcMut<TrayState>,
+    /// Set while the splash screen is up.
+    pub splash: ArcMut<Option<Splash>>,
+    /// Set with `persist_window_state`.
+    pub window_state: ArcMut<Option<WindowStateStore>>,
+    /// Responses for `mock_http_request`, used with `enable_mocking`.
+    pub mocks: ArcMut<MockRegistry>,
+    pub services: ServiceRegistry,
+    pub click_through: ArcMut<ClickThrough>,
+    /// Set while kiosk mode is on.
+    pub kiosk: ArcMut<Option<Kiosk>>,
+    /// `find_in_page` searches, per window.
+    pub finds: ArcMut<Finds>,
+    /// WebView permission requests waiting for `grant_permission`.
+    pub permissions: ArcMut<Permissions>,
+    /// The last `set_mock_location` position.
+    pub geolocation: ArcMut<Option<Position>>,
+    pub element_fullscreen: ArcMut<ElementFullscreenState>,
+    pub load_errors: ArcMut<LoadErrors>,
+    pub navigation: ArcMut<NavigationState>,
+    pub title_sync: ArcMut<TitleSync>,
+    pub window_shape: ArcMut<WindowShape>,
+    pub titlebar_overlay: ArcMut<TitlebarOverlay>,
+    /// The `set_dock_menu` menu.
+    pub dock: ArcMut<crate::dock::Dock>,
+    /// The `set_menu` menu bar.
+    pub menubar: ArcMut<crate::menubar::MenuRegistry>,
+    /// The menu bar's accelerators as pages match them.
+    pub accelerators: ArcMut<crate::accelerators::Accelerators>,
+    pub fs_watchers: ArcMut<FsWatchers>,
+    pub processes: ArcMut<Processes>,
+    pub state_store: ArcMut<StateStore>,
+    pub kv_store: ArcMut<KvStore>,
+    /// Hooks run on exit, see [`crate::shutdown_hooks`].
+    pub exit_hooks: ArcMut<ShutdownHooks>,
+    pub ws: ArcMut<WsConnections>,
+    /// Where TCP requests wait for the event loop, by priority.
+    pub lanes: Arc<RequestLanes>,
+    /// `emit` frames posted to the event loop.
+    pub emits: Arc<EmitGate>,
+    /// Set with the `updater` option.
+    updater: Option<Arc<Updater>>,
+    /// Set with the `audit` option.
+    pub audit: Option<Arc<crate::audit::AuditLog>>,
+    /// What `list_apis` returns, set once the APIs are registered.
+    pub apis: std::sync::OnceLock<Vec<crate::api_manager::ApiInfo>>,
+    /// Labels and pending events of the open windows.
+    pub window_events: ArcMut<WindowRouter>,
+    /// Handed out by [`render_target`](Self::render_target), by window number.
+    render_targets: ArcMut<Vec<(u64, RenderTarget)>>,
+    /// Where the app's own pages are, set once the first WebView is built.
+    pub app_origin: std::sync::OnceLock<AppOrigin>,
 }
 
 impl AppContext {
     pub fn new(
         first_id: WindowId,
-        window: Arc<Mutex<HashMap<WindowId, (Arc<Window>, Arc<wry::WebView>)>>>,
+        window: WindowMap,
+        content_blocker: ArcMut<ContentBlocker>,
+        proxy: FrameEventLoopProxy,
+        rt: tokio::runtime::Handle,
+        pending: PendingMap,
+        config: &AppConfig,
     ) -> Result<Arc<Self>> {
-        Ok(Arc::new(Self { first_id, window }))
+        Ok(Arc::new(Self {
+            first_id,
+            window,
+            content_blocker,
+            process_monitor: arc_mut(ProcessMonitor::new()?),
+            ticker: arc_mut(Ticker::new(config)),
+            timers: arc_mut(Timers::default()),
+            main_thread: MainThread::new(proxy.clone()),
+            close_guard: arc_mut(CloseGuard::new(config)),
+            tray: arc_mut(TrayState::default()),
+            splash: arc_mut(None),
+            window_state: arc_mut(None),
+            mocks: arc_mut(MockRegistry::default()),
+            click_through: arc_mut(ClickThrough::default()),
+            kiosk: arc_mut(None),
+            finds: arc_mut(Finds::default()),
+            permissions: arc_mut(Permissions::new(&config.permissions)),
+            geolocation: arc_mut(None),
+            element_fullscreen: arc_mut(ElementFullscreenState::default()),
+            load_errors: arc_mut(LoadErrors::new(config)),
+            navigation: arc_mut(NavigationState::default()),
+            window_shape: arc_mut(WindowShape::default()),
+            titlebar_overlay: arc_mut(TitlebarOverlay::default()),
+            dock: arc_mut(crate::dock::Dock::default()),
+            menubar: arc_mut(crate::menubar::MenuRegistry::default()),
+            accelerators: arc_mut(crate::accelerators::Accelerators::default()),
+            title_sync: arc_mut(TitleSync::new(
+                config.sync_title,
+                config.title_format.clone(),
+            )),
+            fs_watchers: arc_mut(FsWatchers::default()),
+            processes: arc_mut(Processes::default()),
+            state_store: arc_mut(StateStore::new(config.state_max_bytes)),
+            kv_store: arc_mut(KvStore::new(config)),
+            exit_hooks: arc_mut(ShutdownHooks::default()),
+            ws: arc_mut(WsConnections::default()),
+            lanes: Arc::new(RequestLanes::default()),
+            emits: Arc::new(EmitGate::default()),
+            apis: std::sync::OnceLock::new(),
+            window_events: arc_mut(WindowRouter::new(Self::window_number(first_id))),
+            render_targets: arc_mut(Vec::new()),
+            app_origin: std::sync::OnceLock::new(),
+            updater: config
+                .updater
+                .clone()
+                .map(Updater::new)
+                .transpose()?
+                .map(Arc::new),
+            audit: config
+                .audit
+                .as_ref()
+                .map(|audit| crate::audit::AuditLog::start(audit, config.log_dir()))
+                .transpose()?
+                .map(Arc::new),
+            services: ServiceRegistry::default(),
+            config: config.clone(),
+            proxy,
+            rt,
+            pending,
+            app_caller: arc_mut(None),
+        }))
     }
     #[allow(dead_code)]
     pub fn get_window(&self) -> Result<Arc<Window>> {
@@ -40,6 +219,344 @@ impl AppContext {
             .map(|(_, webview)| Arc::clone(webview))
             .ok_or_else(|| anyhow!("WebView with id {:?} not found", self.first_id))
     }
+
+    /// The numeric id under which [`get_window_by_id`](Self::get_window_by_id)
+    /// finds a window; tao's `WindowId` has no numeric form of its own.
+    pub fn window_number(id: WindowId) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn is_first_window(&self, id: WindowId) -> bool {
+        id == self.first_id
+    }
+
+    /// Drops a window opened with `open_window`, which closes it. Returns
+    /// `false` for the first window or an unknown one.
+    pub fn remove_window(&self, window_id: u64) -> bool {
+        if self.is_first_window_number(window_id) {
+            return false;
+        }
+        self.close_render_targets(Some(window_id));
+        let mut windows = lock_force!(self.window);
+        let id = windows
+            .keys()
+            .find(|id| **id != self.first_id && Self::window_number(**id) == window_id)
+            .copied();
+        let removed = id.and_then(|id| windows.remove(&id)).is_some();
+        if removed {
+            lock_force!(self.window_events).close(window_id);
+        }
+        removed
+    }
+
+    fn is_first_window_number(&self, window_id: u64) -> bool {
+        Self::window_number(self.first_id) == window_id
+    }
+
+    /// [`render_target`](Self::render_target) of the first window.
+    pub fn first_render_target(&self) -> Result<RenderTarget> {
+        self.render_target(Self::window_number(self.first_id))
+    }
+
+    /// A [`RenderTarget`] for the window, closed before the window goes.
+    pub fn render_target(&self, window_id: u64) -> Result<RenderTarget> {
+        let window = self.get_window_by_id(window_id)?;
+        let target = RenderTarget::new(crate::native_handle::of(&window)?);
+        lock_force!(self.render_targets).push((window_id, target.clone()));
+        Ok(target)
+    }
+
+    /// Closes the render targets of the window, or all of them if `None`,
+    /// waiting for their paints in progress.
+    pub fn close_render_targets(&self, window_id: Option<u64>) {
+        let closing: Vec<_> = {
+            let mut targets = lock_force!(self.render_targets);
+            let (closing, open) = targets
+                .drain(..)
+                .partition(|(id, _)| window_id.is_none_or(|window_id| *id == window_id));
+            *targets = open;
+            closing
+        };
+        for (_, target) in closing {
+            target.close();
+        }
+    }
+
+    /// The label of `window`, see [`WindowRouter`].
+    pub fn label_of(&self, window: &Window) -> String {
+        lock_force!(self.window_events)
+            .label(Self::window_number(window.id()))
+            .unwrap_or(WINDOW_LABEL)
+            .to_string()
+    }
+
+    pub fn get_window_by_id(&self, window_id: u64) -> Result<Arc<Window>> {
+        Ok(self.window_entry(window_id)?.0)
+    }
+
+    /// The WebView of the window with `id` from `get_window_state`.
+    pub fn get_webview_by_id(&self, window_id: u64) -> Result<Arc<wry::WebView>> {
+        Ok(self.window_entry(window_id)?.1)
+    }
+
+    fn window_entry(&self, window_id: u64) -> Result<(Arc<Window>, Arc<wry::WebView>)> {
+        lock_force!(self.window)
+            .iter()
+            .find(|(id, _)| Self::window_number(**id) == window_id)
+            .map(|(_, (window, webview))| (Arc::clone(window), Arc::clone(webview)))
+            .ok_or_else(|| anyhow!("Window with id {} not found", window_id))
+    }
+
+    /// The window `req` targets through its `window_id` metadata, or the
+    /// first window if it names none.
+    pub fn target_window(&self, req: &ApiRequest) -> Result<Arc<Window>> {
+        match req.metadata().window_id {
+            Some(window_id) => self.get_window_by_id(window_id),
+            None => self.get_window(),
+        }
+    }
+
+    /// The WebView of the window `req` targets, as for
+    /// [`target_window`](Self::target_window).
+    pub fn target_webview(&self, req: &ApiRequest) -> Result<Arc<wry::WebView>> {
+        match req.metadata().window_id {
+            Some(window_id) => Ok(self.window_entry(window_id)?.1),
+            None => self.get_webview(),
+        }
+    }
+
+    /// Allows or denies a WebView permission request and emits
+    /// `permission-resolved`.
+    pub fn resolve_permission(
+        &self,
+        request: &PermissionRequest,
+        allow: bool,
+        reason: Reason,
+    ) -> Result<()> {
+        crate::permissions::resolve(request.id, allow);
+        self.emit(
+            "permission-resolved",
+            serde_json::json!({
+                "id": request.id,
+                "kind": request.kind,
+                "origin": request.origin,
+                "allowed": allow,
+                "reason": reason,
+            }),
+        )
+    }
+
+    /// Leaves kiosk mode and emits `kiosk-exited` with the reason. Returns
+    /// `false` if kiosk mode wasn't on.
+    pub fn exit_kiosk(&self, reason: &str) -> Result<bool> {
+        let exited = match lock_force!(self.kiosk).as_mut() {
+            Some(kiosk) => {
+                let window = self.get_window()?;
+                let webview = self.get_webview()?;
+                kiosk.exit(&window, &webview)?
+            }
+            None => false,
+        };
+        if exited {
+            self.emit("kiosk-exited", serde_json::json!({ "reason": reason }))?;
+        }
+        Ok(exited)
+    }
+
+    /// Fails with [`PermissionDenied`] unless `webview` shows one of the
+    /// app's own pages. Checked again when a page's request is handled, as
+    /// the page may have navigated elsewhere since it posted it.
+    pub fn check_app_page(&self, webview: &wry::WebView) -> Result<()> {
+        let url = webview.url()?;
+        if self
+            .app_origin
+            .get()
+            .is_some_and(|origin| origin.allows(&url))
+        {
+            return Ok(());
+        }
+        Err(PermissionDenied {
+            permission: "app_origin",
+        }
+        .into())
+    }
+
+    /// Answers `websocket.js` in `window` with a `bridge` event carrying
+    /// [`FrameInfo`](crate::FrameInfo) and the launch token, if its page is
+    /// the app's; anything else gets an error and no token.
+    pub fn bridge_for_page(&self, window: WindowId, id: u64) {
+        let number = Self::window_number(window);
+        let result = self.get_webview_by_id(number).and_then(|webview| {
+            self.check_app_page(&webview)?;
+            let mut bridge = serde_json::to_value(crate::FrameInfo::of(&self.config))?;
+            #[cfg(feature = "tcp")]
+            let token = crate::protocol::expected_token();
+            #[cfg(not(feature = "tcp"))]
+            let token: Option<&str> = None;
+            bridge["token"] = serde_json::json!(token);
+            Ok(bridge)
+        });
+        crate::log_if_err!(self.emit_to(Some(&[number]), "bridge", crate::ipc::reply(id, result)));
+    }
+
+    /// Runs `shell_open` for the page and answers it with a `shell-open`
+    /// event carrying `id` and either `result` or `error`.
+    pub fn shell_open_for_page(&self, id: u64, target: String) {
+        let scope = self.config.fs_scope.clone();
+        let schemes = self.config.open_schemes();
+        let main_thread = self.main_thread.clone();
+        let checked = self
+            .get_webview()
+            .and_then(|webview| self.check_app_page(&webview));
+        crate::tasks::spawn_blocking_on("shell_open", &self.rt, move || {
+            let result =
+                checked.and_then(|()| crate::shell::open_target(&target, &scope, &schemes));
+            let payload = crate::ipc::reply(id, result);
+            crate::log_if_err!(main_thread.run(move |ctx| {
+                crate::log_if_err!(ctx.emit("shell-open", payload));
+            }));
+        });
+    }
+
+    /// Runs `http_fetch` for the page and answers with an `http-fetch` event.
+    pub fn http_fetch_for_page(&self, id: u64, request: crate::http_fetch::FetchRequest) {
+        let config = self.config.http.clone();
+        let proxy = self.proxy.clone();
+        let main_thread = self.main_thread.clone();
+        let checked = self
+            .get_webview()
+            .and_then(|webview| self.check_app_page(&webview));
+        crate::tasks::spawn_on("http_fetch", &self.rt, async move {
+            let result = match checked {
+                Ok(()) => crate::http_fetch::fetch(&config, request, proxy).await,
+                Err(err) => Err(err),
+            };
+            let payload = crate::ipc::reply(id, result);
+            crate::log_if_err!(main_thread.run(move |ctx| {
+                crate::log_if_err!(ctx.emit("http-fetch", payload));
+            }));
+        });
+    }
+
+    /// The updater, if the `updater` option is set.
+    pub fn updater(&self) -> Result<Arc<Updater>> {
+        self.updater
+            .clone()
+            .ok_or_else(|| anyhow!("The updater option is not set"))
+    }
+
+    /// Runs an updater step for the page and answers with an `updater` event.
+    pub fn updater_for_page(&self, id: u64, op: UpdaterOp) {
+        let main_thread = self.main_thread.clone();
+        let run = self
+            .get_webview()
+            .and_then(|webview| self.check_app_page(&webview))
+            .and_then(|()| self.updater())
+            .map(|updater| updater.run(op, self.proxy.clone()));
+        crate::tasks::spawn_on("updater", &self.rt, async move {
+            let result = match run {
+                Ok(run) => run.await,
+                Err(err) => Err(err),
+            };
+            let payload = crate::ipc::reply(id, result);
+            crate::log_if_err!(main_thread.run(move |ctx| {
+                crate::log_if_err!(ctx.emit("updater", payload));
+            }));
+        });
+    }
+
+    /// Dispatches `pyframe:<event>` on the frontend's `window`, with `payload`
+    /// as the event's `detail`. Must be called on the event loop thread.
+    pub fn emit(&self, event: &str, payload: Value) -> Result<()> {
+        self.get_webview()?
+            .evaluate_script(&emit_script(event, &payload)?)?;
+        Ok(())
+    }
+
+    /// Like [`emit`](Self::emit), on every window's page.
+    pub fn emit_all(&self, event: &str, payload: Value) -> Result<()> {
+        let script = emit_script(event, &payload)?;
+        let webviews: Vec<_> = lock_force!(self.window)
+            .values()
+            .map(|(_, webview)| Arc::clone(webview))
+            .collect();
+        for webview in webviews {
+            webview.evaluate_script(&script)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`emit`](Self::emit), on the pages of `windows` (numbers from
+    /// [`window_number`](Self::window_number)), or of every window if `None`.
+    pub fn emit_to(&self, windows: Option<&[u64]>, event: &str, payload: Value) -> Result<()> {
+        let Some(windows) = windows else {
+            return self.emit_all(event, payload);
+        };
+        let script = emit_script(event, &payload)?;
+        let webviews = windows
+            .iter()
+            .map(|&id| Ok(self.window_entry(id)?.1))
+            .collect::<Result<Vec<_>>>()?;
+        for webview in webviews {
+            webview.evaluate_script(&script)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates `js` as an expression and passes its JSON result to
+    /// `callback` on the event loop thread. A thrown exception arrives as
+    /// [`JsError`](crate::script::JsError).
+    pub fn eval_js<F>(&self, js: &str, callback: F) -> Result<()>
+    where
+        F: FnOnce(Result<Value>) + Send + 'static,
+    {
+        let webview = self.get_webview()?;
+        self.eval_js_in(&webview, js, callback)
+    }
+
+    /// [`eval_js`](Self::eval_js) in `webview`, e.g. one from
+    /// [`target_webview`](Self::target_webview).
+    pub fn eval_js_in<F>(&self, webview: &wry::WebView, js: &str, callback: F) -> Result<()>
+    where
+        F: FnOnce(Result<Value>) + Send + 'static,
+    {
+        let callback = Mutex::new(Some(callback));
+        webview.evaluate_script_with_callback(&script::wrap_eval(js), move |raw| {
+            if let Some(callback) = lock_force!(callback).take() {
+                callback(script::parse_eval_result(&raw));
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Runs `f` on the event loop thread. See [`MainThread::run`].
+    #[allow(dead_code)]
+    pub fn run_on_main<T, F>(&self, f: F) -> Result<tokio::sync::oneshot::Receiver<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&AppContext) -> T + Send + 'static,
+    {
+        self.main_thread.run(f)
+    }
+
+    /// The TCP client that sent `req`, while it waits for the response.
+    pub fn connection_of(&self, req: &ApiRequest) -> Option<ConnectionId> {
+        lock_force!(self.pending)
+            .get(&req.0)
+            .map(|pending| pending.connection)
+    }
+
+    /// Answers `req` once the handler's deferred work is done. A request of
+    /// the app itself isn't in the pending map, so its answer is logged.
+    pub fn responder(&self, req: &ApiRequest) -> Responder {
+        Responder::new(
+            req,
+            self.pending.clone(),
+            lock_force!(self.app_caller).clone(),
+        )
+    }
 }
 
 impl std::fmt::Debug for AppContext {
@@ -62,3 +579,11 @@ impl std::fmt::Debug for AppContext {
             .finish()
     }
 }
+
+fn emit_script(event: &str, payload: &Value) -> Result<String> {
+    Ok(format!(
+        "window.dispatchEvent(new CustomEvent({}, {{ detail: {} }}));",
+        serde_json::to_string(&format!("pyframe:{}", event))?,
+        payload
+    ))
+}
@@ -0,0 +1,214 @@
+//! The WebView's right-click menu, set by the `context_menu` option.
+//!
+//! `disabled` suppresses it, for links, images and selections alike.
+//! `custom` suppresses it as well and emits `context-menu` with a
+//! [`ContextMenuRequest`], for the page to draw a menu of its own. On
+//! WebKitGTK, `set_context_menu_items` can show native items instead of the
+//! default menu; choosing one emits `menu` with its id, like a tray item.
+//! WebView2 and WKWebView have no such hook, so only the event is sent there.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the IPC message, followed by a [`ContextMenuRequest`] as JSON.
+pub const CONTEXT_MENU: &str = "__context_menu:";
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextMenuMode {
+    /// The WebView's own menu.
+    #[default]
+    Default,
+    Disabled,
+    /// Emit `context-menu` instead.
+    Custom,
+}
+
+/// Payload of `context-menu`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ContextMenuRequest {
+    /// Click position in CSS pixels, relative to the frame's viewport.
+    pub x: f64,
+    pub y: f64,
+    pub screen_x: f64,
+    pub screen_y: f64,
+    /// `false` if the click was inside an iframe.
+    pub top_frame: bool,
+    /// Selected text, if any.
+    pub selection: Option<String>,
+    pub element: Option<ElementInfo>,
+}
+
+/// The element that was right-clicked.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ElementInfo {
+    /// Lower-case tag name, e.g. `"img"`.
+    pub tag: String,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    /// Target of the enclosing link.
+    pub link: Option<String>,
+    pub image: Option<String>,
+    /// Source of the enclosing `<video>` or `<audio>`.
+    pub media: Option<String>,
+    pub editable: bool,
+}
+
+/// A native item for `set_context_menu_items`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct NativeItem {
+    /// Sent as the `menu` event's `id` when chosen.
+    pub id: String,
+    pub label: String,
+    /// On by default.
+    pub enabled: bool,
+    /// A separator line; `id` and `label` are ignored.
+    pub separator: bool,
+}
+
+impl Default for NativeItem {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            label: String::new(),
+            enabled: true,
+            separator: false,
+        }
+    }
+}
+
+/// The script suppressing or forwarding the menu, `None` for `default`.
+pub fn init_script(mode: ContextMenuMode) -> Result<Option<String>> {
+    if mode == ContextMenuMode::Default {
+        return Ok(None);
+    }
+    let settings = serde_json::json!({ "mode": mode, "native": NATIVE });
+    Ok(Some(crate::assets::_CONTEXT_MENU_SCRIPT.replace(
+        "__PYFRAME_CONTEXT_MENU__",
+        &serde_json::to_string(&settings)?,
+    )))
+}
+
+/// Whether the menu is controlled natively: WebKitGTK's is suppressed or
+/// replaced in its `context-menu` signal rather than by the page script.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+const NATIVE: bool = true;
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+const NATIVE: bool = false;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use gtk::{hook, set_items};
+
+/// The page script does it all here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn hook(
+    _webview: &wry::WebView,
+    _mode: ContextMenuMode,
+    _proxy: &crate::utils::FrameEventLoopProxy,
+) {
+}
+
+/// Native items can't be shown here; `false`.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn set_items(_items: Vec<NativeItem>, _mode: ContextMenuMode) -> bool {
+    false
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod gtk {
+    use std::cell::RefCell;
+
+    use tray_icon::menu::MenuId;
+    use webkit2gtk::{gio::SimpleAction, ContextMenuExt, ContextMenuItem, WebViewExt};
+    use wry::WebViewExtUnix;
+
+    use super::{ContextMenuMode, NativeItem};
+    use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+    thread_local! {
+        /// Items from `set_context_menu_items`; the signal runs on this thread.
+        static ITEMS: RefCell<Vec<NativeItem>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Suppresses `webview`'s menu, or replaces it with the native items.
+    pub fn hook(webview: &wry::WebView, mode: ContextMenuMode, proxy: &FrameEventLoopProxy) {
+        if mode == ContextMenuMode::Default {
+            return;
+        }
+        let proxy = proxy.clone();
+        webview
+            .webview()
+            .connect_context_menu(move |_, menu, _, _| {
+                if mode == ContextMenuMode::Disabled {
+                    return true;
+                }
+                ITEMS.with(|items| {
+                    let items = items.borrow();
+                    if items.is_empty() {
+                        return true;
+                    }
+                    // Whatever was clicked, link and image entries included.
+                    menu.remove_all();
+                    for (index, item) in items.iter().enumerate() {
+                        if item.separator {
+                            menu.append(&ContextMenuItem::new_separator());
+                            continue;
+                        }
+                        // Action names are restricted; the id can be anything.
+                        let action = SimpleAction::new(&format!("pyframe-item-{}", index), None);
+                        action.set_enabled(item.enabled);
+                        let (proxy, id) = (proxy.clone(), MenuId::new(&item.id));
+                        action.connect_activate(move |_, _| {
+                            let _ = proxy.send_event(UserEvent::Menu(id.clone()));
+                        });
+                        menu.append(&ContextMenuItem::from_gaction(&action, &item.label, None));
+                    }
+                    false
+                })
+            });
+    }
+
+    /// Shows `items` in place of the menu from now on; `false` unless the
+    /// mode is `custom`.
+    pub fn set_items(items: Vec<NativeItem>, mode: ContextMenuMode) -> bool {
+        ITEMS.with(|current| *current.borrow_mut() = items);
+        mode == ContextMenuMode::Custom
+    }
+}
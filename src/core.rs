@@ -1,25 +1,76 @@
 use anyhow::Result;
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use crate::{
-    api_manager::{ApiManager, ApiResponse},
-    connections::start_server,
+    api_manager::{code, ApiArguments, ApiManager, ApiRequest, ApiResponse},
+    config::AppConfig,
+    connections::AppDispatch,
+    content_blocking::ContentBlocker,
     context::AppContext,
-    lock,
-    utils::{FrameEventLoop, FrameEventLoopProxy, PendingMap, UserEvent},
+    event_stream::EventStream,
+    kiosk::{Kiosk, KioskGuard},
+    local_server::LocalServer,
+    lock, lock_force,
+    navigation::{Lifecycle, NavigationEvent},
+    process_stats::REFRESH_INTERVAL,
+    shutdown_hooks::{HookId, ShutdownHook},
+    splash::Splash,
+    ticker::wake_by,
+    timers::TimerId,
+    transport::{LocalCalls, Transport},
+    utils::{
+        arc_mut, ArcMut, FrameEventLoop, FrameEventLoopProxy, FrameWindowTarget, PendingMap,
+        UserEvent,
+    },
+    vfs::VirtualFileSystem,
+    window_commands::{OpenedWindow, WindowCommand, WindowOptions},
+    window_events::{WindowEvents, WINDOW_LABEL},
+    window_state::WindowStateStore,
+    ShutdownNotifier,
 };
+use tao::{platform::run_return::EventLoopExtRunReturn, window::WindowId};
+use tokio::{runtime::Runtime, sync::Semaphore, task::AbortHandle};
+
+/// How long the runtime may take to finish its tasks when the event loop exits.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Called on the event loop thread for every [`UserEvent::Tick`].
+pub type TickHandler = Box<dyn Fn(&AppContext, u64) + Send>;
+/// Called on the event loop thread with the name and data of every event.
+pub type EventHandler = Box<dyn Fn(&str, &serde_json::Value) + Send>;
 
-#[allow(dead_code)]
 pub struct App {
-    api_manager: Arc<std::sync::Mutex<ApiManager>>,
-    pub rt: std::sync::Arc<tokio::runtime::Runtime>,
-    pub runtime_handel: std::sync::Arc<tokio::runtime::Handle>,
-    pub proxy: FrameEventLoopProxy,
+    api_manager: ArcMut<ApiManager>,
+    /// The only owner of the runtime; taken and shut down when the event loop exits.
+    rt: Mutex<Option<Runtime>>,
+    /// Abort handles of everything spawned through [`App::spawn`].
+    tasks: Mutex<Vec<AbortHandle>>,
+    proxy: FrameEventLoopProxy,
     response_map: PendingMap,
-    pub ctx: Arc<AppContext>,
+    #[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+    in_flight: Arc<Semaphore>,
+    /// Keeps the context alive; the ApiManager only holds a Weak reference.
+    ctx: Arc<AppContext>,
+    tick_handlers: Mutex<Vec<TickHandler>>,
+    event_handlers: Mutex<Vec<EventHandler>>,
+    forward_ticks: bool,
+    /// Set once [`App::exit`] started the exit hooks.
+    exiting: AtomicBool,
+    /// Every handled [`UserEvent`], for `pyframe.events()` subscribers.
+    events: EventStream,
+    transport: Transport,
+    /// Requests of the page in ipc-only mode and of `FrameHandle`.
+    local_calls: LocalCalls,
+    /// The first page's `__PYFRAME__` script, for windows of `open_window`.
+    init_script: String,
 }
 
 impl App {
@@ -27,122 +78,1278 @@ impl App {
         event_loop: &mut FrameEventLoop,
         init_add: String,
         html: String,
+        mut config: AppConfig,
+        listener: Option<std::net::TcpListener>,
     ) -> Result<std::sync::Arc<App>> {
         let proxy = event_loop.create_proxy();
+        if config.local_server.is_some() && config.url.is_some() {
+            anyhow::bail!("The local_server and url options can't be combined");
+        }
+        let local_server = config
+            .local_server
+            .as_ref()
+            .map(|options| LocalServer::bind(options, &html))
+            .transpose()?;
+        config.local_server_port = local_server.as_ref().map(LocalServer::port);
 
-        let rt = std::sync::Arc::new(
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build()?,
-        );
+        if config.parent_handle.is_some() && (config.kiosk.is_some() || config.persist_window_state)
+        {
+            anyhow::bail!(
+                "The parent_handle option can't be combined with kiosk or persist_window_state"
+            );
+        }
 
-        let window = tao::window::WindowBuilder::new()
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .thread_name("pyframe-rt")
+            .enable_all()
+            .build()?;
+
+        let mut window_state = WindowStateStore::new(&config);
+        let window_builder = tao::window::WindowBuilder::new()
             .with_title("PyFrame")
-            .build(&event_loop)?;
+            .with_transparent(config.transparent);
+        let window_builder = crate::titlebar::apply(window_builder, &config);
+        crate::dock::apply(event_loop, &config);
+        for warning in crate::titlebar::creation_warnings(&config)
+            .into_iter()
+            .chain(crate::dock::creation_warnings(&config))
+        {
+            crate::log_warn!(warning);
+        }
+        let monitors: Vec<_> = event_loop.available_monitors().collect();
+        let window_builder = match config.parent_handle {
+            // The host lays it out with `set_webview_bounds`.
+            Some(parent) => crate::embed::child_of(window_builder, parent)?,
+            None => crate::placement::place(
+                window_builder,
+                crate::placement::Placement {
+                    monitor: config.monitor.as_ref(),
+                    strategy: config.position_strategy,
+                    size: None,
+                },
+                &monitors,
+                event_loop.primary_monitor(),
+                window_state.as_mut().and_then(WindowStateStore::load),
+                0,
+            ),
+        };
+        let window = window_builder.build(event_loop)?;
+        let mut titlebar_overlay =
+            crate::titlebar_overlay::TitlebarOverlay::install(&window, config.titlebar_overlay)?;
+
+        let content_blocker = arc_mut(ContentBlocker::default());
+        let navigation_blocker = content_blocker.clone();
+        let kiosk_guard = config
+            .kiosk
+            .as_ref()
+            .map(|options| KioskGuard::new(options, config.url.as_deref()));
+        let kiosk_exit = config
+            .kiosk
+            .as_ref()
+            .map(crate::kiosk::exit_accelerator)
+            .transpose()?
+            .flatten();
+        let navigation_kiosk = kiosk_guard.clone();
 
-        let webview = wry::WebViewBuilder::new()
-            .with_initialization_script(init_add)
+        let app_origin = crate::transport::AppOrigin::new(
+            local_server
+                .as_ref()
+                .map(LocalServer::entry_url)
+                .or_else(|| config.url.clone())
+                .as_deref(),
+            config.assets.keys().map(String::as_str),
+        );
+        let page_origin = app_origin.clone();
+        let first_id = window.id();
+        let ipc_proxy = proxy.clone();
+        let page_load_proxy = proxy.clone();
+        let title_proxy = proxy.clone();
+        let mut builder = wry::WebViewBuilder::new()
+            .with_initialization_script(init_add.clone())
             .with_initialization_script(crate::assets::_CONN_SCRIPT)
             .with_initialization_script(crate::assets::_COMMAND_SCRIPT)
-            .with_html(&html)
-            .build(&window)?;
+            .with_initialization_script(crate::assets::_DRAG_REGION_SCRIPT)
+            .with_initialization_script(crate::assets::_ELEMENT_FULLSCREEN_SCRIPT)
+            .with_initialization_script(crate::assets::_NAVIGATION_SCRIPT)
+            .with_initialization_script(crate::assets::_ACCELERATORS_SCRIPT)
+            .with_navigation_handler(move |url| {
+                navigation_kiosk
+                    .as_ref()
+                    .is_none_or(|kiosk| kiosk.allows_navigation(&url))
+                    && lock_force!(navigation_blocker).allows(&url)
+            })
+            .with_ipc_handler(move |req| {
+                let uri = req.uri().to_string();
+                if let Some(prefix) = crate::ipc::refused(req.body(), &uri, &app_origin) {
+                    crate::log_warn!(format!(
+                        "Ignored {} from {}, which isn't the app's origin",
+                        prefix.trim_end_matches(':'),
+                        uri
+                    ));
+                    return;
+                }
+                if let Some(id) = crate::ipc::bridge_request(req.body()) {
+                    let _ = ipc_proxy.send_event(UserEvent::Bridge(first_id, id));
+                    return;
+                }
+                let event = crate::ipc::event_for(req.body())
+                    .or_else(|| crate::geolocation::permission_event(req.body(), &uri));
+                if let Some(event) = event {
+                    let _ = ipc_proxy.send_event(event);
+                }
+            })
+            .with_on_page_load_handler(move |event, url| {
+                let _ = page_load_proxy.send_event(UserEvent::PageLoad(event, url));
+            })
+            .with_document_title_changed_handler(move |title| {
+                let _ = title_proxy.send_event(UserEvent::TitleChanged(title));
+            })
+            // Stays hidden under the splash until it is dismissed.
+            .with_visible(config.splash.is_none())
+            .with_transparent(config.transparent);
+        if let Some(script) = crate::context_menu::init_script(config.context_menu)? {
+            builder = builder.with_initialization_script_for_main_only(script, false);
+        }
+        if let Some(script) = titlebar_overlay.init_script()? {
+            builder = builder.with_initialization_script(script);
+        }
+        if config.mock_geolocation {
+            builder = builder.with_initialization_script(crate::geolocation::init_script(
+                config.permissions.geolocation,
+            )?);
+        }
+        if let (Some(options), Some(guard)) = (&config.kiosk, kiosk_guard.clone()) {
+            builder = builder
+                .with_initialization_script(crate::kiosk::init_script(options))
+                .with_devtools(options.devtools)
+                .with_new_window_req_handler(move |_, _| {
+                    if guard.allows_new_window() {
+                        wry::NewWindowResponse::Allow
+                    } else {
+                        wry::NewWindowResponse::Deny
+                    }
+                });
+        }
+        for (scheme, dir) in &config.assets {
+            let mut vfs = VirtualFileSystem::default();
+            vfs.add_directory_tree(dir)?;
+            builder = vfs.mount(scheme, builder);
+        }
+        if config.enable_mocking {
+            let mock_proxy = proxy.clone();
+            builder = builder
+                .with_initialization_script(crate::http_mock::init_script())
+                .with_asynchronous_custom_protocol(
+                    crate::http_mock::MOCK_SCHEME.into(),
+                    move |_, request, responder| {
+                        // The mocks live in the AppContext on the event loop thread.
+                        let task = Box::new(move |ctx: &AppContext| {
+                            responder.respond(lock_force!(ctx.mocks).respond(&request));
+                        });
+                        let _ = mock_proxy.send_event(UserEvent::Task(task));
+                    },
+                );
+        }
+        let webview = match (&local_server, &config.url) {
+            (Some(server), _) => builder.with_url(server.entry_url()),
+            (None, Some(url)) => builder.with_url(url),
+            (None, None) => builder.with_html(&html),
+        }
+        .build(&window)?;
+        crate::permissions::hook(&webview, &proxy);
+        crate::element_fullscreen::hook(&webview);
+        crate::context_menu::hook(&webview, config.context_menu, &proxy);
+        crate::load_error::hook(&webview, &proxy);
+        crate::navigation::hook(&webview, &proxy);
+        // Clips the WebView around the caption buttons.
+        titlebar_overlay.resized(&window);
+        let splash = Splash::build(&window, &config)?;
 
-        let _ctx = AppContext::new(
+        let response_map = PendingMap::default();
+        let ctx = AppContext::new(
             window.id(),
             Arc::new(Mutex::new(HashMap::from([(
                 window.id(),
                 (Arc::new(window), Arc::new(webview)),
             )]))),
+            content_blocker,
+            proxy.clone(),
+            rt.handle().clone(),
+            response_map.clone(),
+            &config,
         )?;
+        let _ = ctx.app_origin.set(page_origin);
 
-        let handle = rt.handle().clone();
-
-        let cloned_proxy = proxy.clone();
+        *lock!(ctx.splash)? = splash;
+        *lock!(ctx.window_state)? = window_state;
+        *lock!(ctx.titlebar_overlay)? = titlebar_overlay;
+        crate::accelerators::hook(&*ctx.get_window()?, &ctx.accelerators);
+        if let (Some(options), Some(guard)) = (&config.kiosk, kiosk_guard) {
+            let window = ctx.get_window()?;
+            if let Some(combo) = kiosk_exit {
+                crate::kiosk::hook_exit(&window, combo, guard.clone(), proxy.clone());
+            }
+            *lock!(ctx.kiosk)? = Some(Kiosk::enter(
+                &window,
+                options.clone(),
+                guard,
+                config.monitor.as_ref(),
+            ));
+        }
 
         let api_manager = ApiManager::new();
-        {
-            let mut api_manager = lock!(api_manager)?;
-            crate::api::register_api_instances(&mut api_manager);
-        }
+        crate::api::register_api_instances(&mut *lock!(api_manager)?);
+        #[cfg(feature = "tcp")]
+        let replies = Arc::new(crate::idempotency::ReplyCache::new(
+            &config.idempotency,
+            lock!(api_manager)?.no_replay(),
+        ));
 
+        let in_flight = Arc::new(Semaphore::new(crate::connections::MAX_IN_FLIGHT_REQUESTS));
+        let local_calls = LocalCalls::new(
+            response_map.clone(),
+            in_flight.clone(),
+            ctx.lanes.clone(),
+            rt.handle().clone(),
+            ctx.main_thread.clone(),
+            ctx.audit.clone(),
+        );
         let app = Arc::new(Self {
-            api_manager: api_manager.clone(),
-            rt: rt.clone(),
-            runtime_handel: std::sync::Arc::new(handle),
+            api_manager,
+            rt: Mutex::new(Some(rt)),
+            tasks: Mutex::new(Vec::new()),
             proxy,
-            response_map: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
-            ctx: _ctx.clone(),
+            response_map,
+            in_flight,
+            ctx,
+            tick_handlers: Mutex::new(Vec::new()),
+            event_handlers: Mutex::new(Vec::new()),
+            exiting: AtomicBool::new(false),
+            events: EventStream::default(),
+            forward_ticks: config.forward_ticks,
+            transport: config.transport,
+            local_calls,
+            init_script: init_add,
         });
 
+        crate::tray::forward_events(app.proxy.clone());
+        if config.minimize_to_tray {
+            crate::log_if_err!(
+                lock!(app.ctx.tray)?.set_minimize_to_tray(true, config.tray_icon.as_deref())
+            );
+        }
+
+        if let Some(kind) = &config.effect {
+            let window = app.ctx.get_window()?;
+            let webview = app.ctx.get_webview()?;
+            crate::log_if_err!(crate::vibrancy::Effect::parse(kind)
+                .and_then(|effect| crate::vibrancy::set_effect(&window, &webview, effect)));
+        }
+
         // Richtige Bindung: kein neues Arc erzeugen
-        {
-            let mut m = lock!(api_manager).unwrap();
-            m.bind_app_context(&_ctx);
+        lock!(app.api_manager)?.bind_app_context(&app.ctx);
+
+        let dispatch = AppDispatch {
+            proxy: app.proxy.clone(),
+            events: app.events.clone(),
+            emits: app.ctx.emits.clone(),
+            audit: app.ctx.audit.clone(),
+        };
+        match listener {
+            #[cfg(feature = "tcp")]
+            Some(listener) => app.spawn(
+                "tcp_server",
+                crate::connections::start_server(
+                    listener,
+                    dispatch,
+                    app.response_map.clone(),
+                    app.in_flight.clone(),
+                    app.ctx.lanes.clone(),
+                    replies,
+                ),
+            ),
+            // Nothing listens; in-process calls still go through the lanes.
+            _ => {
+                let lanes = app.ctx.lanes.clone();
+                let pending = app.response_map.clone();
+                app.spawn("request_lanes", async move {
+                    lanes.pump(dispatch, pending).await
+                });
+            }
+        }
+
+        if let Some(server) = local_server {
+            app.spawn("local_server", async move {
+                crate::log_if_err!(server.serve().await);
+            });
         }
-        let map = app.clone().response_map.clone();
 
-        rt.spawn(start_server(cloned_proxy.clone(), map));
+        let process_monitor = Arc::downgrade(&app.ctx.process_monitor);
+        app.spawn("process_monitor", async move {
+            let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(process_monitor) = process_monitor.upgrade() else {
+                    break;
+                };
+                lock_force!(process_monitor).refresh();
+            }
+        });
 
         Ok(app)
     }
 
+    /// Spawns a background task named `name` on the runtime and registers it
+    /// for shutdown.
+    pub fn spawn<F>(&self, name: &str, future: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match lock_force!(self.rt).as_ref() {
+            Some(rt) => {
+                let handle = crate::tasks::spawn_on(name, rt.handle(), future);
+                let mut tasks = lock_force!(self.tasks);
+                // Finished tasks have nothing left to abort at shutdown.
+                tasks.retain(|task| !task.is_finished());
+                tasks.push(handle.abort_handle());
+                drop(tasks);
+                // The panic hook writes the report; the task just stays down.
+                let name = name.to_string();
+                crate::tasks::spawn_on("task_watcher", rt.handle(), async move {
+                    if let Err(e) = handle.await {
+                        if e.is_panic() {
+                            crate::log_err!(format!(
+                                "Background task {:?} panicked, the app keeps running without it",
+                                name
+                            ));
+                        }
+                    }
+                });
+            }
+            None => {
+                crate::log_warn!("Runtime already shut down, task not started");
+            }
+        }
+    }
+
+    /// Adds a cleanup function that runs when the app exits, before the
+    /// notifier is told, with at most `timeout` to finish. Hooks run in
+    /// reverse registration order, see [`crate::shutdown_hooks`].
     #[allow(dead_code)]
-    pub fn api_manager(&self) -> Result<std::sync::MutexGuard<'_, ApiManager>> {
-        lock!(self.api_manager)
+    pub fn on_exit(&self, name: String, timeout: Duration, hook: ShutdownHook) -> HookId {
+        lock_force!(self.ctx.exit_hooks).register(name, hook, timeout)
     }
 
     #[allow(dead_code)]
-    pub fn respond(&self, key: u8, response: ApiResponse) {
-        if let Some(sender) = self.response_map.lock().unwrap().remove(&key) {
-            let _ = sender.send(response);
-        } else {
-            eprintln!("Kein Sender für Schlüssel {} gefunden", key);
+    pub fn deregister_exit_hook(&self, id: HookId) -> bool {
+        lock_force!(self.ctx.exit_hooks).deregister(id)
+    }
+
+    /// Runs the exit hooks that haven't run yet, aborts all background
+    /// tasks and shuts the runtime down.
+    ///
+    /// Called once when the event loop is destroyed, so no tokio worker
+    /// threads outlive the app.
+    pub fn shutdown(&self) {
+        crate::shutdown_hooks::run(lock_force!(self.ctx.exit_hooks).take());
+        lock_force!(self.ctx.timers).clear_all();
+        lock_force!(self.ctx.fs_watchers).clear_all();
+        lock_force!(self.ctx.processes).kill_all();
+        lock_force!(self.ctx.ws).clear_all();
+        crate::drag_out::remove_temp_files();
+        for task in lock_force!(self.tasks).drain(..) {
+            task.abort();
+        }
+        if let Some(rt) = lock_force!(self.rt).take() {
+            rt.shutdown_timeout(SHUTDOWN_TIMEOUT);
+        }
+    }
+
+    /// Registers a handler that runs on every tick.
+    #[allow(dead_code)]
+    pub fn on_tick(&self, handler: TickHandler) {
+        lock_force!(self.tick_handlers).push(handler);
+    }
+
+    /// A [`RenderTarget`](crate::RenderTarget) of the first window.
+    pub fn render_target(&self) -> Result<crate::RenderTarget> {
+        self.ctx.first_render_target()
+    }
+
+    /// Registers a handler that sees every event, see [`EventHandler`].
+    pub fn on_event(&self, handler: EventHandler) {
+        lock_force!(self.event_handlers).push(handler);
+    }
+
+    fn tick(&self, count: u64) {
+        for handler in lock_force!(self.tick_handlers).iter() {
+            handler(&self.ctx, count);
+        }
+        if self.forward_ticks {
+            crate::log_if_err!(self.ctx.emit("tick", serde_json::json!({ "count": count })));
+        }
+    }
+
+    /// Emits `pyframe:timer` and calls the timer's API, if it has one.
+    fn timer_fired(
+        &self,
+        id: TimerId,
+        target: &FrameWindowTarget,
+        control_flow: &mut tao::event_loop::ControlFlow,
+    ) {
+        let Some(call) = lock_force!(self.ctx.timers).fired(id) else {
+            return;
+        };
+        crate::log_if_err!(self.ctx.emit("timer", serde_json::json!({ "id": id })));
+        if let Some(call) = call {
+            let req = ApiRequest(
+                0,
+                call.method,
+                ApiArguments::new(call.args),
+                Default::default(),
+            );
+            // Id 0 may be a real request's, so a deferred answer must not
+            // be looked up in the pending map.
+            *lock_force!(self.ctx.app_caller) = Some(format!("timer {}", id));
+            let res = lock_force!(self.api_manager).call(req, target, control_flow);
+            *lock_force!(self.ctx.app_caller) = None;
+            if let Some(res) = res {
+                if res.code() != code::OK {
+                    crate::log_err!(format!("Timer {} API call failed: {}", id, res.msg()));
+                }
+            }
+        }
+    }
+
+    /// The ticker's control flow, shortened to wake up for throttled window
+    /// events, the splash timeout, a pending window state save, a pending
+    /// close or permission request and click-through polling.
+    fn control_flow(&self) -> tao::event_loop::ControlFlow {
+        let flow = lock_force!(self.ctx.ticker).control_flow();
+        let flow = wake_by(flow, lock_force!(self.ctx.window_events).deadline());
+        let flow = wake_by(
+            flow,
+            lock_force!(self.ctx.splash).as_ref().map(Splash::deadline),
+        );
+        let flow = wake_by(
+            flow,
+            lock_force!(self.ctx.window_state)
+                .as_ref()
+                .and_then(WindowStateStore::deadline),
+        );
+        let flow = wake_by(flow, lock_force!(self.ctx.kv_store).deadline());
+        let flow = wake_by(flow, lock_force!(self.ctx.close_guard).deadline());
+        let flow = wake_by(flow, lock_force!(self.ctx.permissions).deadline());
+        let flow = wake_by(flow, lock_force!(self.ctx.load_errors).deadline());
+        wake_by(flow, lock_force!(self.ctx.click_through).deadline())
+    }
+
+    /// Loads the URL of the error page again.
+    fn retry_load(&self) {
+        let Some(url) = lock_force!(self.ctx.load_errors).retry() else {
+            return;
+        };
+        if let Ok(webview) = self.ctx.get_webview() {
+            crate::log_if_err!(webview.load_url(&url));
+        }
+    }
+
+    /// Runs the exit hooks off the event loop thread, then leaves it with
+    /// [`UserEvent::ExitHooksDone`]; right away without hooks. Ignored while
+    /// the hooks run.
+    fn exit(
+        &self,
+        notifier: &dyn ShutdownNotifier,
+        control_flow: &mut tao::event_loop::ControlFlow,
+    ) {
+        if self.exiting.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let hooks = lock_force!(self.ctx.exit_hooks).take();
+        if hooks.is_empty() {
+            return self.leave(notifier, control_flow);
+        }
+        let proxy = self.proxy.clone();
+        let spawned = std::thread::Builder::new()
+            .name("exit-hooks".into())
+            .spawn(move || {
+                crate::shutdown_hooks::run(hooks);
+                let _ = proxy.send_event(UserEvent::ExitHooksDone);
+            });
+        if let Err(e) = spawned {
+            crate::log_err!(format!("Exit hooks not started: {}", e));
+            self.leave(notifier, control_flow);
+        }
+    }
+
+    /// Tells the notifier and leaves the event loop, the last steps of
+    /// [`App::exit`].
+    fn leave(
+        &self,
+        notifier: &dyn ShutdownNotifier,
+        control_flow: &mut tao::event_loop::ControlFlow,
+    ) {
+        notifier.notify();
+        *control_flow = tao::event_loop::ControlFlow::Exit;
+    }
+
+    /// Shows the main WebView and emits `splash-dismissed` with the reason.
+    fn dismiss_splash(&self, reason: &str) {
+        let Some(splash) = lock_force!(self.ctx.splash).take() else {
+            return;
+        };
+        crate::log_if_err!(self
+            .ctx
+            .get_webview()
+            .and_then(|webview| splash.dismiss(&webview)));
+        crate::log_if_err!(self
+            .ctx
+            .emit("splash-dismissed", serde_json::json!({ "reason": reason })));
+    }
+
+    /// With minimize-to-tray, a minimized window disappears into the tray.
+    fn hide_if_minimized(&self, window: &tao::window::Window) {
+        if window.is_minimized() && lock_force!(self.ctx.tray).minimize_to_tray {
+            crate::tray::hide_to_tray(window);
         }
     }
 
+    fn restore_from_tray(&self) {
+        if let Ok(window) = self.ctx.get_window() {
+            crate::tray::restore_from_tray(&window);
+        }
+    }
+
+    fn window_command(
+        &self,
+        command: WindowCommand,
+        target: &FrameWindowTarget,
+        notifier: &dyn ShutdownNotifier,
+        control_flow: &mut tao::event_loop::ControlFlow,
+    ) {
+        match command {
+            WindowCommand::Open(options, reply) => reply(self.open_window(options, target)),
+            WindowCommand::Close(window_id, reply) => {
+                let first = self.ctx.get_window().map(|window| window.id());
+                if first.is_ok_and(|id| AppContext::window_number(id) == window_id) {
+                    self.exit(notifier, control_flow);
+                    reply(Ok(true));
+                } else {
+                    reply(Ok(self.close_window(window_id)));
+                }
+            }
+        }
+    }
+
+    /// Opens a window with its own WebView, placed by its `monitor` and
+    /// `position_strategy` like the first one.
+    fn open_window(
+        &self,
+        options: WindowOptions,
+        target: &FrameWindowTarget,
+    ) -> Result<OpenedWindow> {
+        if let Some(label) = &options.label {
+            lock_force!(self.ctx.window_events).check_label(label)?;
+        }
+        let size = options
+            .width
+            .zip(options.height)
+            .map(|(width, height)| tao::dpi::LogicalSize::new(width, height));
+        let mut window_builder = tao::window::WindowBuilder::new()
+            .with_title(options.title.as_deref().unwrap_or("PyFrame"));
+        if let Some(size) = size {
+            window_builder = window_builder.with_inner_size(size);
+        }
+        let monitors: Vec<_> = target.available_monitors().collect();
+        let window_builder = crate::placement::place(
+            window_builder,
+            crate::placement::Placement {
+                monitor: options.monitor.as_ref(),
+                strategy: options.position_strategy,
+                size,
+            },
+            &monitors,
+            target.primary_monitor(),
+            None,
+            lock_force!(self.ctx.window).len(),
+        );
+        let window = window_builder.build(target)?;
+        let builder = wry::WebViewBuilder::new();
+        let webview = match options.url {
+            Some(url) => builder.with_url(url),
+            None => builder
+                .with_ipc_handler(self.bridge_handler(window.id()))
+                .with_initialization_script(self.init_script.clone())
+                .with_initialization_script(crate::assets::_CONN_SCRIPT)
+                .with_initialization_script(crate::assets::_COMMAND_SCRIPT)
+                .with_html(options.html.unwrap_or_default()),
+        }
+        .build(&window)?;
+        let id = AppContext::window_number(window.id());
+        let label = lock_force!(self.ctx.window_events).open(id, options.label)?;
+        let monitor = crate::placement::landed_on(&window);
+        lock_force!(self.ctx.window).insert(window.id(), (Arc::new(window), Arc::new(webview)));
+        let opened = OpenedWindow { id, label, monitor };
+        crate::log_if_err!(self
+            .ctx
+            .emit_all("window-opened", serde_json::json!(opened)));
+        Ok(opened)
+    }
+
+    /// IPC handler of a window of `open_window`, which only asks for the
+    /// bridge; the page's other messages are answered in the first window.
+    fn bridge_handler(&self, window: WindowId) -> impl Fn(wry::http::Request<String>) + 'static {
+        let proxy = self.proxy.clone();
+        let app_origin = self.ctx.app_origin.get().cloned();
+        move |req| {
+            let uri = req.uri().to_string();
+            let Some(id) = crate::ipc::bridge_request(req.body()) else {
+                return;
+            };
+            if app_origin
+                .as_ref()
+                .is_none_or(|origin| crate::ipc::refused(req.body(), &uri, origin).is_some())
+            {
+                crate::log_warn!(format!(
+                    "Ignored __bridge from {}, which isn't the app's origin",
+                    uri
+                ));
+                return;
+            }
+            let _ = proxy.send_event(UserEvent::Bridge(window, id));
+        }
+    }
+
+    /// Closes a window of `open_window` and emits `window-closed`.
+    fn close_window(&self, window_id: u64) -> bool {
+        if !self.ctx.remove_window(window_id) {
+            return false;
+        }
+        crate::log_if_err!(self
+            .ctx
+            .emit_all("window-closed", serde_json::json!({ "id": window_id })));
+        true
+    }
+
+    /// Returns `true` if closing was handed to the app through
+    /// [`UserEvent::CloseRequested`].
+    fn intercept_close(&self) -> bool {
+        let mut guard = lock_force!(self.ctx.close_guard);
+        let first = !guard.is_pending();
+        if !guard.intercept(Instant::now()) {
+            return false;
+        }
+        if first {
+            let timeout_ms = guard.timeout().as_millis() as u64;
+            drop(guard);
+            crate::log_if_err!(self.proxy.send_event(UserEvent::CloseRequested(timeout_ms)));
+        }
+        true
+    }
+
+    /// Decides a permission request, or asks through `permission-requested`.
+    fn permission_requested(&self, request: crate::permissions::PermissionRequest) {
+        let mut permissions = lock_force!(self.ctx.permissions);
+        let decided = permissions.decide(
+            request.clone(),
+            &mut lock_force!(self.ctx.kv_store),
+            Instant::now(),
+        );
+        let timeout_ms = permissions.timeout().as_millis() as u64;
+        drop(permissions);
+        match decided {
+            Some((allow, reason)) => {
+                crate::log_if_err!(self.ctx.resolve_permission(&request, allow, reason))
+            }
+            None => {
+                let mut payload = serde_json::json!(request);
+                payload["timeout_ms"] = timeout_ms.into();
+                crate::log_if_err!(self.ctx.emit("permission-requested", payload));
+            }
+        }
+    }
+
+    /// Emits `menu` for a menu bar item, unless the same accelerator was
+    /// just delivered the other way.
+    fn menubar_chosen(&self, id: &tray_icon::menu::MenuId) {
+        let deliver =
+            lock_force!(self.ctx.accelerators).menu_event(id.as_ref(), std::time::Instant::now());
+        if !deliver {
+            return;
+        }
+        // Menu bar items add their check state.
+        let payload = match lock_force!(self.ctx.menubar).clicked(id) {
+            Some(click) => serde_json::json!(click),
+            None => serde_json::json!({ "id": id.as_ref() }),
+        };
+        crate::log_if_err!(self.ctx.emit("menu", payload));
+    }
+
+    fn titlebar_overlay_resized(&self, window: &tao::window::Window) {
+        if let Some(layout) = lock_force!(self.ctx.titlebar_overlay).resized(window) {
+            let _ = self.proxy.send_event(UserEvent::TitlebarOverlay(layout));
+        }
+    }
+
+    /// Emits a window event on every page; its `label` names the window.
+    fn emit_window_event(&self, event: &str, payload: Option<serde_json::Value>) {
+        if let Some(payload) = payload {
+            crate::log_if_err!(self.ctx.emit_all(event, payload));
+        }
+    }
+
+    /// Runs `f` with the events of `window`, if it is one of ours.
+    fn with_window_events<T>(
+        &self,
+        window: WindowId,
+        f: impl FnOnce(&mut WindowEvents) -> T,
+    ) -> Option<T> {
+        lock_force!(self.ctx.window_events)
+            .events(AppContext::window_number(window))
+            .map(f)
+    }
+
+    pub fn respond(&self, key: u8, response: ApiResponse) {
+        crate::connections::respond(&self.response_map, key, response);
+    }
+
     pub fn run(
         self: Arc<Self>,
-        event_loop: FrameEventLoop,
-        _mp_event: pyo3::Py<pyo3::PyAny>,
+        event_loop: &mut FrameEventLoop,
+        notifier: Arc<dyn ShutdownNotifier>,
     ) -> Result<()> {
         let api_manager = self.api_manager.clone();
+        crate::crash::install(
+            &self.ctx.config,
+            self.events.clone(),
+            self.proxy.clone(),
+            notifier.clone(),
+        );
 
-        event_loop.run(move |event, target, control_flow| {
-            *control_flow = tao::event_loop::ControlFlow::Wait;
+        self.local_calls.install();
+
+        let app = self.clone();
+        event_loop.run_return(move |event, target, control_flow| {
+            *control_flow = self.control_flow();
             match event {
-                tao::event::Event::WindowEvent { event, .. } => match event {
-                    /*                     tao::event::WindowEvent::Destroyed => {
-                        pyo3::Python::with_gil(|py| {
-                            if let Err(e) = _mp_event.clone_ref(py).call_method0(py, "set") {
-                                e.print(py);
+                tao::event::Event::WindowEvent {
+                    window_id,
+                    event: tao::event::WindowEvent::CloseRequested,
+                    ..
+                } if !self.ctx.is_first_window(window_id) => {
+                    self.close_window(AppContext::window_number(window_id));
+                }
+                tao::event::Event::WindowEvent {
+                    window_id,
+                    event: tao::event::WindowEvent::Resized(size),
+                    ..
+                } => {
+                    let now = Instant::now();
+                    let first = self.ctx.is_first_window(window_id);
+                    let window = self
+                        .ctx
+                        .get_window_by_id(AppContext::window_number(window_id));
+                    let scale_factor = window.as_ref().map_or(1.0, |w| w.scale_factor());
+                    let payload = self.with_window_events(window_id, |events| {
+                        if let Ok(window) = &window {
+                            events.occluded(now, window.is_minimized());
+                        }
+                        events.resized(now, size, scale_factor)
+                    });
+                    if let (true, Ok(window)) = (first, &window) {
+                        self.hide_if_minimized(window);
+                        if let Some(splash) = lock_force!(self.ctx.splash).as_ref() {
+                            splash.resize(size);
+                        }
+                        if let Some(store) = lock_force!(self.ctx.window_state).as_mut() {
+                            store.changed(now);
+                        }
+                        lock_force!(self.ctx.click_through).resized(window);
+                        lock_force!(self.ctx.window_shape).resized(window);
+                        self.titlebar_overlay_resized(window);
+                    }
+                    self.emit_window_event("resized", payload.flatten());
+                    *control_flow = self.control_flow();
+                }
+                tao::event::Event::WindowEvent {
+                    window_id,
+                    event: tao::event::WindowEvent::ScaleFactorChanged { .. },
+                    ..
+                } if self.ctx.is_first_window(window_id) => {
+                    if let Ok(window) = self.ctx.get_window() {
+                        lock_force!(self.ctx.click_through).resized(&window);
+                        lock_force!(self.ctx.window_shape).resized(&window);
+                        self.titlebar_overlay_resized(&window);
+                    }
+                }
+                tao::event::Event::WindowEvent {
+                    window_id,
+                    event: tao::event::WindowEvent::Focused(focused),
+                    ..
+                } => {
+                    let now = Instant::now();
+                    let window = self
+                        .ctx
+                        .get_window_by_id(AppContext::window_number(window_id));
+                    self.with_window_events(window_id, |events| {
+                        events.focused(now, focused);
+                        if let Ok(window) = &window {
+                            events.occluded(now, window.is_minimized());
+                        }
+                    });
+                    if let (true, Ok(window)) = (self.ctx.is_first_window(window_id), &window) {
+                        self.hide_if_minimized(window);
+                    }
+                    *control_flow = self.control_flow();
+                }
+                tao::event::Event::WindowEvent {
+                    window_id,
+                    event: tao::event::WindowEvent::Moved(position),
+                    ..
+                } => {
+                    let now = Instant::now();
+                    if self.ctx.is_first_window(window_id) {
+                        if let Some(store) = lock_force!(self.ctx.window_state).as_mut() {
+                            store.changed(now);
+                        }
+                    }
+                    let scale_factor = self
+                        .ctx
+                        .get_window_by_id(AppContext::window_number(window_id))
+                        .map_or(1.0, |w| w.scale_factor());
+                    let payload = self.with_window_events(window_id, |events| {
+                        events.moved(now, position, scale_factor)
+                    });
+                    self.emit_window_event("moved", payload.flatten());
+                    *control_flow = self.control_flow();
+                }
+                tao::event::Event::WindowEvent {
+                    event: tao::event::WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    if lock_force!(self.ctx.tray).minimize_to_tray {
+                        if let Ok(window) = self.ctx.get_window() {
+                            crate::tray::hide_to_tray(&window);
+                        }
+                    } else if self.intercept_close() {
+                        *control_flow = self.control_flow();
+                    } else {
+                        self.exit(&*notifier, control_flow);
+                    }
+                }
+                tao::event::Event::UserEvent(event) => {
+                    self.events.publish(&event);
+                    let handlers = lock_force!(self.event_handlers);
+                    if !handlers.is_empty() {
+                        let (name, data) = crate::event_stream::named(&event);
+                        for handler in handlers.iter() {
+                            handler(&name, &data);
+                        }
+                    }
+                    drop(handlers);
+                    match event {
+                        UserEvent::Request(req) => {
+                            self.ctx.lanes.taken();
+                            crate::crash::record_request(req.0, &req.1);
+                            let timing = req.3.timing.clone();
+                            let call =
+                                || lock_force!(api_manager).call(req, target, control_flow);
+                            let res = match timing {
+                                Some(timing) => timing.handle(call),
+                                None => call(),
+                            };
+                            if let Some(res) = res {
+                                self.respond(res.0, res);
                             }
-                            py.check_signals().unwrap();
-                        });
-                        *control_flow = tao::event_loop::ControlFlow::Exit;
-                    } */
-                    tao::event::WindowEvent::CloseRequested => {
-                        pyo3::Python::with_gil(|py| {
-                            if let Err(e) = _mp_event.clone_ref(py).call_method0(py, "set") {
-                                e.print(py);
-                            }
-                            py.check_signals().unwrap();
-                        });
-                        *control_flow = tao::event_loop::ControlFlow::Exit;
+                        }
+                        UserEvent::Tick(count) => self.tick(count),
+                        UserEvent::Timer(id) => self.timer_fired(id, target, control_flow),
+                        UserEvent::Task(task) => task(&self.ctx),
+                        UserEvent::Exit => self.exit(&*notifier, control_flow),
+                        UserEvent::CloseRequested(timeout_ms) => {
+                            crate::log_if_err!(self.ctx.emit(
+                                "close-requested",
+                                serde_json::json!({ "label": WINDOW_LABEL, "timeout_ms": timeout_ms })
+                            ));
+                        }
+                        UserEvent::ExitHooksDone => self.leave(&*notifier, control_flow),
+                        UserEvent::Shutdown => self.leave(&*notifier, control_flow),
+                        UserEvent::WindowCommand(command) => {
+                            self.window_command(*command, target, &*notifier, control_flow)
+                        }
+                        UserEvent::ExitHook(id, name) => {
+                            let event = serde_json::json!({ "id": id, "name": name });
+                            crate::log_if_err!(self.ctx.emit_all("exit-hook", event));
+                        }
+                        UserEvent::TrayClicked => self.restore_from_tray(),
+                        UserEvent::FrameReady => self.dismiss_splash("ready"),
+                        UserEvent::DragWindow => {
+                            if let Ok(window) = self.ctx.get_window() {
+                                crate::log_if_err!(window.drag_window());
+                            }
+                        }
+                        UserEvent::FsChanged(id, changes) => {
+                            if lock_force!(self.ctx.fs_watchers).is_watching(id) {
+                                crate::log_if_err!(self.ctx.emit(
+                                    "fs-change",
+                                    serde_json::json!({ "id": id, "changes": changes })
+                                ));
+                            }
+                        }
+                        UserEvent::ProcessOutput(pid, stream, line) => {
+                            crate::log_if_err!(self.ctx.emit(
+                                "process-output",
+                                serde_json::json!({ "pid": pid, "stream": stream, "line": line })
+                            ));
+                        }
+                        UserEvent::ProcessExited(pid, code) => {
+                            lock_force!(self.ctx.processes).exited(pid);
+                            crate::log_if_err!(self.ctx.emit(
+                                "process-exit",
+                                serde_json::json!({ "pid": pid, "code": code })
+                            ));
+                        }
+                        UserEvent::Emit(emit) => {
+                            self.ctx.emits.taken();
+                            crate::log_if_err!(self.ctx.emit_to(
+                                emit.windows.as_deref(),
+                                &emit.event,
+                                emit.payload
+                            ));
+                        }
+                        UserEvent::PermissionRequested(request) => {
+                            self.permission_requested(*request)
+                        }
+                        UserEvent::FindResult(result) => {
+                            crate::log_if_err!(self.ctx.emit_to(
+                                Some(&[result.window]),
+                                "find-result",
+                                serde_json::json!(result)
+                            ));
+                        }
+                        UserEvent::Disconnected(connection) => {
+                            lock_force!(self.api_manager).connection_closed(connection);
+                            lock_force!(self.ctx.fs_watchers).drop_connection(connection);
+                            lock_force!(self.ctx.ws)
+                                .drop_owner(crate::ws_client::Owner::Connection(connection));
+                        }
+                        UserEvent::LegacyProtocol(connection) => {
+                            crate::log_warn!(format!(
+                                "TCP client {} connected without a hello; protocol version 0 is deprecated",
+                                connection
+                            ));
+                        }
+                        UserEvent::HttpFetch(id, request) => {
+                            self.ctx.http_fetch_for_page(id, *request)
+                        }
+                        UserEvent::HttpBody(stream, part) => {
+                            let (event, payload) = crate::http_fetch::describe(stream, &part);
+                            crate::log_if_err!(self.ctx.emit(event, payload));
+                        }
+                        UserEvent::WsCall(id, op) => {
+                            let result = lock_force!(self.ctx.ws).apply(
+                                op,
+                                &self.ctx.config.ws_allowed_urls,
+                                &self.ctx.rt,
+                                self.proxy.clone(),
+                            );
+                            crate::log_if_err!(self.ctx.emit("ws", crate::ipc::reply(id, result)));
+                        }
+                        UserEvent::Ws(id, event) => {
+                            if matches!(event, crate::ws_client::WsEvent::Closed { .. }) {
+                                lock_force!(self.ctx.ws).closed(id);
+                            }
+                            let (name, payload) = crate::ws_client::describe(id, &event);
+                            crate::log_if_err!(self.ctx.emit(name, payload));
+                        }
+                        UserEvent::Crashed(report) => {
+                            crate::log_if_err!(self.ctx.emit("crashed", serde_json::json!(report)));
+                        }
+                        UserEvent::Bridge(window, id) => self.ctx.bridge_for_page(window, id),
+                        UserEvent::Updater(id, op) => self.ctx.updater_for_page(id, op),
+                        UserEvent::UpdateProgress(downloaded, total) => {
+                            crate::log_if_err!(self.ctx.emit(
+                                "update-progress",
+                                serde_json::json!({ "downloaded": downloaded, "total": total })
+                            ));
+                        }
+                        UserEvent::ShellOpen(id, target) => {
+                            self.ctx.shell_open_for_page(id, target)
+                        }
+                        UserEvent::StartDrag(id, items) => {
+                            let result = self.ctx.get_window().and_then(|window| {
+                                crate::drag_out::start(
+                                    &window,
+                                    items,
+                                    &self.ctx.config.fs_scope,
+                                    &self.ctx.rt,
+                                )
+                            });
+                            crate::log_if_err!(self
+                                .ctx
+                                .emit("start-drag", crate::ipc::reply(id, result)));
+                        }
+                        UserEvent::StateStore(call) => {
+                            let applied = lock_force!(self.ctx.state_store).apply(call.op);
+                            let (result, change) = match applied {
+                                Ok((result, change)) => (Ok(result), change),
+                                Err(e) => (Err(e), None),
+                            };
+                            crate::log_if_err!(self
+                                .ctx
+                                .emit("app-store", crate::ipc::reply(call.id, result)));
+                            if let Some(change) = change {
+                                crate::log_if_err!(self
+                                    .proxy
+                                    .send_event(UserEvent::StateChanged(change)));
+                            }
+                        }
+                        UserEvent::StateChanged(change) => {
+                            crate::log_if_err!(self.ctx.emit_all(
+                                &format!("state-changed:{}", change.name),
+                                serde_json::json!(change)
+                            ));
+                        }
+                        UserEvent::StoreChanged(key) => {
+                            let value = match &key {
+                                Some(key) => lock_force!(self.ctx.kv_store).get(key).ok().flatten(),
+                                None => None,
+                            };
+                            crate::log_if_err!(self.ctx.emit(
+                                "store-changed",
+                                serde_json::json!({ "key": key, "value": value })
+                            ));
+                        }
+                        UserEvent::ExitKiosk => {
+                            crate::log_if_err!(self.ctx.exit_kiosk("accelerator"))
+                        }
+                        UserEvent::TitleChanged(title) => {
+                            let synced = lock_force!(self.ctx.title_sync).title_for(&title);
+                            if let (Some(synced), Ok(window)) = (synced, self.ctx.get_window()) {
+                                window.set_title(&synced);
+                            }
+                        }
+                        UserEvent::Navigation(event) => {
+                            if event.kind == Lifecycle::LoadStarted {
+                                lock_force!(self.ctx.title_sync).resume();
+                            }
+                            lock_force!(self.ctx.navigation).apply(&event);
+                            crate::log_if_err!(self.ctx.emit(event.kind.name(), event.payload()));
+                        }
+                        UserEvent::LoadFailed(mut failure) => {
+                            let mut errors = lock_force!(self.ctx.load_errors);
+                            errors.failed(&mut failure, Instant::now());
+                            let page = errors.render(&failure);
+                            drop(errors);
+                            if let Ok(webview) = self.ctx.get_webview() {
+                                crate::load_error::show(&webview, &page, &failure.url);
+                            }
+                            crate::log_if_err!(self
+                                .ctx
+                                .emit("load-failed", serde_json::json!(failure)));
+                        }
+                        UserEvent::RetryLoad => self.retry_load(),
+                        UserEvent::SystemMenu => {
+                            if let Ok(window) = self.ctx.get_window() {
+                                crate::log_if_err!(crate::titlebar_overlay::show_system_menu(
+                                    &lock_force!(self.ctx.titlebar_overlay),
+                                    &window
+                                ));
+                            }
+                        }
+                        UserEvent::TitlebarOverlay(layout) => {
+                            crate::log_if_err!(self
+                                .ctx
+                                .emit("titlebar-overlay", serde_json::json!(layout)));
+                        }
+                        UserEvent::ContextMenu(request) => {
+                            crate::log_if_err!(self
+                                .ctx
+                                .emit("context-menu", serde_json::json!(request)));
+                        }
+                        UserEvent::ElementFullscreen(change) => {
+                            if let Ok(window) = self.ctx.get_window() {
+                                if lock_force!(self.ctx.element_fullscreen).apply(&window, &change)
+                                {
+                                    crate::log_if_err!(self
+                                        .ctx
+                                        .emit("element-fullscreen", serde_json::json!(change)));
+                                }
+                            }
+                        }
+                        UserEvent::ToggleMaximize => {
+                            if let Ok(window) = self.ctx.get_window() {
+                                window.set_maximized(!window.is_maximized());
+                            }
+                        }
+                        UserEvent::PageLoad(wry::PageLoadEvent::Finished, url) => {
+                            let success = lock_force!(self.ctx.load_errors).finished(&url);
+                            let accelerators = lock_force!(self.ctx.accelerators).payload();
+                            crate::log_if_err!(self.ctx.emit("accelerators", accelerators));
+                            let mut event = NavigationEvent::new(Lifecycle::LoadFinished, url);
+                            event.success = Some(success);
+                            crate::log_if_err!(self
+                                .proxy
+                                .send_event(UserEvent::Navigation(Box::new(event))));
+                            let on_load = lock_force!(self.ctx.splash)
+                                .as_ref()
+                                .is_some_and(Splash::dismiss_on_load);
+                            if on_load {
+                                self.dismiss_splash("load");
+                            }
+                            if let Some(position) = lock_force!(self.ctx.geolocation).clone() {
+                                crate::log_if_err!(self
+                                    .ctx
+                                    .emit("geolocation-position", serde_json::json!(position)));
+                            }
+                        }
+                        UserEvent::PageLoad(wry::PageLoadEvent::Started, url) => {
+                            lock_force!(self.ctx.ws).drop_owner(crate::ws_client::Owner::Page);
+                            let event = NavigationEvent::new(Lifecycle::LoadStarted, url);
+                            crate::log_if_err!(self
+                                .proxy
+                                .send_event(UserEvent::Navigation(Box::new(event))));
+                        }
+                        UserEvent::Menu(id) => match id.as_ref() {
+                            crate::tray::SHOW_ITEM => self.restore_from_tray(),
+                            crate::tray::QUIT_ITEM => self.exit(&*notifier, control_flow),
+                            _ => {
+                                let tray = lock_force!(self.ctx.tray).clicked(&id);
+                                let event = match tray {
+                                    Some(click) => Some(UserEvent::TrayMenu(Box::new(click))),
+                                    None => lock_force!(self.ctx.dock)
+                                        .clicked(&id)
+                                        .map(|click| UserEvent::DockMenu(Box::new(click))),
+                                };
+                                match event {
+                                    Some(event) => {
+                                        let _ = self.proxy.send_event(event);
+                                    }
+                                    None => self.menubar_chosen(&id),
+                                }
+                            }
+                        },
+                        UserEvent::Invoke(call) => {
+                            // Pages reach the native APIs through Python
+                            // unless there is no socket to reach it by.
+                            if self.transport != Transport::IpcOnly {
+                                crate::log_warn!(format!(
+                                    "Ignored {} from the page: the transport is tcp",
+                                    call.method
+                                ));
+                            } else if !self.ctx.config.page_apis.contains(&call.method) {
+                                crate::log_warn!(format!(
+                                    "Refused {} from the page: it isn't in page_apis",
+                                    call.method
+                                ));
+                                crate::log_if_err!(self
+                                    .ctx
+                                    .emit("invoke-reply", crate::transport::refused(&call)));
+                            } else {
+                                self.local_calls.answer_page(*call);
+                            }
+                        }
+                        UserEvent::Accelerator(id) => {
+                            let deliver = lock_force!(self.ctx.accelerators)
+                                .script_pressed(&id, std::time::Instant::now());
+                            if deliver {
+                                let id = tray_icon::menu::MenuId::new(id);
+                                crate::log_if_err!(self.proxy.send_event(UserEvent::Menu(id)));
+                            }
+                        }
+                        UserEvent::TrayMenu(click) => {
+                            crate::log_if_err!(self.ctx.emit("tray-menu", serde_json::json!(click)));
+                        }
+                        UserEvent::DockMenu(click) => {
+                            crate::log_if_err!(self.ctx.emit("dock-menu", serde_json::json!(click)));
+                        }
+                        UserEvent::DockReopen(has_visible_windows) => {
+                            if !has_visible_windows
+                                && self.ctx.config.reopen_on_dock_click.unwrap_or(true)
+                            {
+                                self.restore_from_tray();
+                            }
+                            crate::log_if_err!(self.ctx.emit(
+                                "dock-reopen",
+                                serde_json::json!({ "has_visible_windows": has_visible_windows })
+                            ));
+                        }
                     }
-                    _ => {}
-                },
-                tao::event::Event::UserEvent(event) => match event {
-                    UserEvent::Request(req) => {
-                        let res = api_manager
-                            .lock()
-                            .unwrap()
-                            .call(req, target, control_flow)
-                            .unwrap();
-                        self.respond(res.0, res);
+                }
+                tao::event::Event::MainEventsCleared => {
+                    let now = Instant::now();
+                    if let Some(count) = lock_force!(self.ctx.ticker).poll(now) {
+                        crate::log_if_err!(self.proxy.send_event(UserEvent::Tick(count)));
                     }
-                },
+                    let due = lock_force!(self.ctx.window_events).poll(now);
+                    for (event, payload) in due {
+                        self.emit_window_event(event, Some(payload));
+                    }
+                    if let (Some(store), Ok(window)) = (
+                        lock_force!(self.ctx.window_state).as_mut(),
+                        self.ctx.get_window(),
+                    ) {
+                        store.poll(now, &window);
+                    }
+                    lock_force!(self.ctx.kv_store).poll(now);
+                    if let Ok(window) = self.ctx.get_window() {
+                        lock_force!(self.ctx.click_through).poll(now, &window);
+                    }
+                    let splash_expired = lock_force!(self.ctx.splash)
+                        .as_ref()
+                        .is_some_and(|splash| splash.deadline() <= now);
+                    if splash_expired {
+                        self.dismiss_splash("timeout");
+                    }
+                    if let Some(url) = lock_force!(self.ctx.load_errors).poll(now) {
+                        if let Ok(webview) = self.ctx.get_webview() {
+                            crate::log_if_err!(webview.load_url(&url));
+                        }
+                    }
+                    let expired = lock_force!(self.ctx.permissions).expired(now);
+                    for request in expired {
+                        crate::log_if_err!(self.ctx.resolve_permission(
+                            &request,
+                            false,
+                            crate::permissions::Reason::Timeout
+                        ));
+                    }
+                    if lock_force!(self.ctx.close_guard).expired(now) {
+                        crate::log_warn!("confirm_close not answered in time, closing");
+                        self.exit(&*notifier, control_flow);
+                        return;
+                    }
+                    *control_flow = self.control_flow();
+                }
+                tao::event::Event::LoopDestroyed => {
+                    self.ctx.main_thread.close();
+                    if let (Some(store), Ok(window)) = (
+                        lock_force!(self.ctx.window_state).as_mut(),
+                        self.ctx.get_window(),
+                    ) {
+                        store.flush(&window);
+                    }
+                    lock_force!(self.ctx.kv_store).flush();
+                    self.shutdown();
+                }
+                tao::event::Event::Reopen {
+                    has_visible_windows,
+                    ..
+                } => {
+                    let _ = self
+                        .proxy
+                        .send_event(UserEvent::DockReopen(has_visible_windows));
+                }
                 _ => {}
             }
         });
+        LocalCalls::uninstall();
+        // The loop outlives this app; its windows and tray icon go now.
+        app.ctx.close_render_targets(None);
+        lock_force!(app.ctx.window).clear();
+        lock_force!(app.ctx.splash).take();
+        *lock_force!(app.ctx.tray) = Default::default();
+        Ok(())
     }
 }
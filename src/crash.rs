@@ -0,0 +1,267 @@
+//! Crash reports for panics, written to `<data dir>/<app_id>/logs` and
+//! announced as a `crashed` event.
+//!
+//! A panic on the event loop thread takes the window down: the report is
+//! published to `pyframe.events()` subscribers directly, and the
+//! [`ShutdownNotifier`] (Python's exit event) is told before the thread
+//! unwinds. Panics anywhere else, such as in Tokio tasks, leave the app
+//! running and go through the event loop like any other event, with
+//! `fatal: false`.
+//!
+//! Panics in API handlers are caught instead, see [`catch_handler`]: the
+//! request is answered with `HANDLER_PANIC` and the report is not fatal.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Once, RwLock,
+    },
+    thread::ThreadId,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::AppConfig,
+    event_stream::EventStream,
+    utils::{FrameEventLoopProxy, UserEvent},
+    ShutdownNotifier,
+};
+
+/// Requests kept for the `recent_requests` of a report.
+const RECENT_REQUESTS: usize = 32;
+/// How long a fatal panic waits for subscribers to receive the report.
+const FLUSH_GRACE: Duration = Duration::from_millis(200);
+
+/// The reporter of the running webframe; each one installs its own.
+static REPORTER: RwLock<Option<Arc<Reporter>>> = RwLock::new(None);
+static HOOK: Once = Once::new();
+static RECENT: Mutex<VecDeque<RecentRequest>> = Mutex::new(VecDeque::new());
+/// Panics caught in API handlers, for `get_runtime_metrics`.
+static HANDLER_PANICS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Inside [`catch_handler`], where a panic on the event loop thread
+    /// isn't fatal.
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    /// Message of the last panic on this thread, for answers sent while
+    /// it unwinds.
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RecentRequest {
+    pub id: u8,
+    pub method: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CrashReport {
+    /// Milliseconds since the Unix epoch.
+    pub time_ms: u128,
+    pub message: String,
+    /// `file:line:column` of the panic.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    /// The [`crate::tasks`] name of the Tokio task that panicked.
+    pub task: Option<String>,
+    /// Whether the event loop itself panicked and the app is going down.
+    pub fatal: bool,
+    pub backtrace: String,
+    /// The last requests the event loop handled, oldest first.
+    pub recent_requests: Vec<RecentRequest>,
+    /// Where the report was written, if it could be.
+    pub path: Option<String>,
+}
+
+struct Reporter {
+    dir: Option<PathBuf>,
+    event_loop: ThreadId,
+    events: EventStream,
+    proxy: Mutex<FrameEventLoopProxy>,
+    exit_signal: Arc<dyn ShutdownNotifier>,
+}
+
+/// Installs the panic hook, or points it at this webframe if an earlier
+/// one installed it. Must be called on the event loop thread; the default
+/// hook still prints the panic.
+pub fn install(
+    config: &AppConfig,
+    events: EventStream,
+    proxy: FrameEventLoopProxy,
+    exit_signal: Arc<dyn ShutdownNotifier>,
+) {
+    let reporter = Reporter {
+        dir: config.log_dir(),
+        event_loop: std::thread::current().id(),
+        events,
+        proxy: Mutex::new(proxy),
+        exit_signal,
+    };
+    *crate::write_force!(REPORTER) = Some(Arc::new(reporter));
+    HOOK.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            default_hook(info);
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(message(info.payload())));
+            let reporter = crate::read_force!(REPORTER).clone();
+            if let Some(reporter) = reporter {
+                reporter.report(info);
+            }
+        }));
+    });
+}
+
+/// Runs an API handler, returning the panic message instead of unwinding
+/// further if it panics.
+pub fn catch_handler<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let outer = CATCHING.with(|catching| catching.replace(true));
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(outer));
+    result.map_err(|payload| {
+        HANDLER_PANICS.fetch_add(1, Ordering::Relaxed);
+        message(payload.as_ref())
+    })
+}
+
+/// Counts a handler panic that was caught some other way, e.g. in a task
+/// that held a `Responder`, and returns its message.
+pub fn handler_panicked() -> String {
+    HANDLER_PANICS.fetch_add(1, Ordering::Relaxed);
+    LAST_PANIC
+        .with(|last| last.borrow().clone())
+        .unwrap_or_else(|| "unknown panic".into())
+}
+
+pub fn handler_panics() -> u64 {
+    HANDLER_PANICS.load(Ordering::Relaxed)
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".into())
+}
+
+/// Remembers a request for later crash reports.
+pub fn record_request(id: u8, method: &str) {
+    let mut recent = crate::lock_force!(RECENT);
+    if recent.len() == RECENT_REQUESTS {
+        recent.pop_front();
+    }
+    recent.push_back(RecentRequest {
+        id,
+        method: method.to_string(),
+    });
+}
+
+impl Reporter {
+    fn report(&self, info: &std::panic::PanicHookInfo) {
+        let thread = std::thread::current();
+        let fatal = thread.id() == self.event_loop && !CATCHING.with(Cell::get);
+        let message = message(info.payload());
+        let mut report = CrashReport {
+            time_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default(),
+            message,
+            location: info.location().map(|l| l.to_string()),
+            thread: thread.name().map(str::to_string),
+            task: crate::tasks::current_name(),
+            fatal,
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            // The panic may have happened while the list was locked.
+            recent_requests: RECENT
+                .try_lock()
+                .map(|recent| recent.iter().cloned().collect())
+                .unwrap_or_default(),
+            path: None,
+        };
+        report.path = self.write(&report);
+
+        let event = UserEvent::Crashed(Box::new(report));
+        if !fatal {
+            if let Ok(proxy) = self.proxy.try_lock() {
+                let _ = proxy.send_event(event);
+            }
+            return;
+        }
+        // The event loop won't run again, so subscribers are told directly
+        // and given a moment to receive it before the notifier is told.
+        self.events.publish(&event);
+        std::thread::sleep(FLUSH_GRACE);
+        self.exit_signal.notify();
+    }
+
+    fn write(&self, report: &CrashReport) -> Option<String> {
+        let path = self
+            .dir
+            .as_ref()?
+            .join(format!("crash-{}.json", report.time_ms));
+        let json = serde_json::to_vec_pretty(report).ok()?;
+        crate::config::write_atomic(&path, &json).ok()?;
+        Some(path.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lock, lock_force, utils::arc_mut};
+
+    #[test]
+    fn panics_leave_locks_usable() {
+        let state = arc_mut(vec![1]);
+        let before = handler_panics();
+        for _ in 0..2 {
+            let message = catch_handler(|| {
+                let mut state = lock_force!(state);
+                state.push(2);
+                panic!("handler bug")
+            })
+            .unwrap_err();
+            assert_eq!(message, "handler bug");
+        }
+        assert!(state.is_poisoned());
+        assert!(handler_panics() >= before + 2);
+        assert_eq!(*lock!(state).unwrap(), vec![1, 2, 2]);
+        assert_eq!(catch_handler(|| lock_force!(state).len()), Ok(3));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    #[ignore = "needs a display"]
+    fn panicking_api_then_a_healthy_one() {
+        use crate::api_manager::code;
+
+        let harness = crate::testing::LoopHarness::start_with(|manager| {
+            manager.register_api(
+                "explode",
+                |ctx, _req, _target, _flow| -> anyhow::Result<bool> {
+                    let _store = lock!(ctx.state_store)?;
+                    panic!("handler bug")
+                },
+            );
+        });
+        for _ in 0..2 {
+            let response = harness.call("explode", serde_json::json!([]));
+            assert_eq!(response.code(), code::HANDLER_PANIC);
+            assert_eq!(response.msg(), "explode panicked: handler bug");
+        }
+        // The state store the panics poisoned, and the manager they ran in.
+        let response = harness.call("state_set", serde_json::json!(["doc", {"a": 1}]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        let response = harness.call("state_get", serde_json::json!(["doc"]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        assert!(!response.result().is_null());
+    }
+}
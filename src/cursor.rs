@@ -0,0 +1,110 @@
+//! Moving and confining the cursor, e.g. for pointer lock in a canvas:
+//! grab it with `set_cursor_grab`, hide it with `set_cursor_visible` and
+//! warp it back to the centre with `set_cursor_position` before it leaves.
+//!
+//! Wayland doesn't let clients move the cursor, and tao can't confine it
+//! on Linux at all; those fail with [`Unsupported`].
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tao::{dpi::LogicalPosition, error::ExternalError, window::Window};
+
+use crate::api_manager::Unsupported;
+
+/// Where the cursor was moved, in logical pixels relative to the content area.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct CursorPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Moves the cursor to `(x, y)` within the content area. Outside of it,
+/// the position is clamped to the edge with `clamp` and an error otherwise.
+pub fn set_position(window: &Window, x: f64, y: f64, clamp: bool) -> Result<CursorPosition> {
+    if is_wayland(window) {
+        return Err(anyhow::Error::new(Unsupported {
+            feature: "set_cursor_position on Wayland".into(),
+        }));
+    }
+    if !x.is_finite() || !y.is_finite() {
+        return Err(anyhow!("Invalid cursor position ({}, {})", x, y));
+    }
+    let size = window.inner_size().to_logical::<f64>(window.scale_factor());
+    // The last pixel row and column are still inside.
+    let (max_x, max_y) = ((size.width - 1.0).max(0.0), (size.height - 1.0).max(0.0));
+    let inside = (0.0..=max_x).contains(&x) && (0.0..=max_y).contains(&y);
+    let position = match (inside, clamp) {
+        (true, _) => CursorPosition { x, y },
+        (false, true) => CursorPosition {
+            x: x.clamp(0.0, max_x),
+            y: y.clamp(0.0, max_y),
+        },
+        (false, false) => {
+            return Err(anyhow!(
+                "Cursor position ({}, {}) is outside the {}x{} window",
+                x,
+                y,
+                size.width,
+                size.height
+            ))
+        }
+    };
+    window
+        .set_cursor_position(LogicalPosition::new(position.x, position.y))
+        .map_err(|e| external("set_cursor_position", e))?;
+    Ok(position)
+}
+
+/// Confines the cursor to the window while `grab` is on.
+pub fn set_grab(window: &Window, grab: bool) -> Result<()> {
+    // tao accepts it and does nothing there.
+    if cfg!(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )) {
+        return Err(anyhow::Error::new(Unsupported {
+            feature: "set_cursor_grab on Linux".into(),
+        }));
+    }
+    window
+        .set_cursor_grab(grab)
+        .map_err(|e| external("set_cursor_grab", e))
+}
+
+fn external(feature: &str, error: ExternalError) -> anyhow::Error {
+    match error {
+        ExternalError::NotSupported(_) => anyhow::Error::new(Unsupported {
+            feature: feature.into(),
+        }),
+        other => anyhow!("{} failed: {}", feature, other),
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn is_wayland(window: &Window) -> bool {
+    use gtk::{glib::prelude::ObjectExt, prelude::WidgetExt};
+    use tao::platform::unix::WindowExtUnix;
+
+    // tao would queue the warp and report success.
+    window.gtk_window().display().type_().name() == "GdkWaylandDisplay"
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn is_wayland(_window: &Window) -> bool {
+    false
+}
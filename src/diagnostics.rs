@@ -0,0 +1,226 @@
+//! `tracing` output of the Rust internals.
+//!
+//! Events go to stderr, as text or JSON lines, and with `log.file` also to
+//! size-rotated files in the app's log directory. Which ones is decided by
+//! an `EnvFilter` like `pygcc=info,pygcc::connections=debug`: `RUST_LOG`
+//! if set, else `log.filter`, and `set_log_filter` swaps it at runtime
+//! without a restart.
+//!
+//! The subscriber is installed by the first webframe of the process; later
+//! ones only apply their filter. `trace` events are compiled out of
+//! release builds (the `release_max_level_debug` feature), so per-message
+//! events cost nothing there.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    layer::{Layer, Layered, SubscriberExt},
+    reload,
+    util::SubscriberInitExt,
+    EnvFilter, Registry,
+};
+
+use crate::{config::AppConfig, lock_force};
+
+const DEFAULT_FILTER: &str = "pygcc=info";
+/// Name of the current log file; rotated ones get a timestamp.
+const FILE_PREFIX: &str = "pyframe";
+
+/// The `log` creation option.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LogConfig {
+    /// `EnvFilter` directives; `RUST_LOG` takes precedence when set.
+    pub filter: String,
+    /// JSON lines instead of text.
+    pub json: bool,
+    /// Also write to `<log dir>/pyframe.log`.
+    pub file: bool,
+    /// A file this large is rotated.
+    pub max_file_bytes: u64,
+    /// Rotated files older than this are deleted.
+    pub max_age_days: u64,
+    /// Rotated files kept at most.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: DEFAULT_FILTER.to_string(),
+            json: false,
+            file: false,
+            max_file_bytes: 10 * 1024 * 1024,
+            max_age_days: 7,
+            max_files: 5,
+        }
+    }
+}
+
+type Filtered = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+
+struct Installed {
+    handle: reload::Handle<EnvFilter, Registry>,
+    spec: Mutex<String>,
+}
+
+static INSTALLED: OnceLock<Installed> = OnceLock::new();
+
+/// Installs the subscriber, or applies the filter if it already is.
+pub fn init(config: &AppConfig) -> Result<()> {
+    let options = &config.log;
+    let spec = std::env::var("RUST_LOG")
+        .ok()
+        .filter(|spec| EnvFilter::try_new(spec).is_ok())
+        .unwrap_or_else(|| options.filter.clone());
+    if INSTALLED.get().is_some() {
+        set_filter(&spec)?;
+        return Ok(());
+    }
+    let (filter, handle) = reload::Layer::new(parse(&spec)?);
+    let mut outputs = vec![output(options.json, true, io::stderr)];
+    if let (true, Some(dir)) = (options.file, config.log_dir()) {
+        let file = RotatingFile::open(dir, options)?;
+        outputs.push(output(options.json, false, Mutex::new(file)));
+    }
+    let installed = Registry::default().with(filter).with(outputs).try_init();
+    // Another subscriber, e.g. the host application's, stays in charge.
+    if installed.is_ok() {
+        let _ = INSTALLED.set(Installed {
+            handle,
+            spec: Mutex::new(spec),
+        });
+    }
+    Ok(())
+}
+
+fn output<W>(json: bool, ansi: bool, writer: W) -> Box<dyn Layer<Filtered> + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    if json {
+        layer.json().boxed()
+    } else {
+        layer.boxed()
+    }
+}
+
+fn parse(spec: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(spec).map_err(|e| {
+        anyhow::Error::new(crate::api_manager::BadArgs {
+            reason: format!("Invalid log filter {:?}: {}", spec, e),
+        })
+    })
+}
+
+/// Replaces the filter; returns the previous directives.
+pub fn set_filter(spec: &str) -> Result<String> {
+    let installed = INSTALLED.get().ok_or_else(|| {
+        anyhow::anyhow!("Logging is handled by another tracing subscriber in this process")
+    })?;
+    installed.handle.reload(parse(spec)?)?;
+    Ok(std::mem::replace(
+        &mut *lock_force!(installed.spec),
+        spec.to_string(),
+    ))
+}
+
+/// `pyframe.log`, renamed to `pyframe.<unix time>.log` when it reaches
+/// `max_file_bytes`.
+struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    max_age: Duration,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(dir: PathBuf, options: &LogConfig) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{}.log", FILE_PREFIX)))?;
+        let written = file.metadata()?.len();
+        let rotating = Self {
+            dir,
+            file,
+            written,
+            max_bytes: options.max_file_bytes.max(1),
+            max_age: Duration::from_secs(options.max_age_days * 24 * 60 * 60),
+            max_files: options.max_files,
+        };
+        rotating.prune();
+        Ok(rotating)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let current = self.dir.join(format!("{}.log", FILE_PREFIX));
+        fs::rename(
+            &current,
+            self.dir.join(format!("{}.{}.log", FILE_PREFIX, stamp)),
+        )?;
+        self.file = OpenOptions::new().create(true).append(true).open(current)?;
+        self.written = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Deletes rotated files beyond `max_files` or older than `max_age`.
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let prefix = format!("{}.", FILE_PREFIX);
+        let current = format!("{}.log", FILE_PREFIX);
+        let mut rotated: Vec<(PathBuf, SystemTime)> = entries
+            .flatten()
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.starts_with(&prefix) && name.ends_with(".log") && name != current
+            })
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((entry.path(), modified))
+            })
+            .collect();
+        rotated.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        let now = SystemTime::now();
+        for (index, (path, modified)) in rotated.iter().enumerate() {
+            let expired = now.duration_since(*modified).unwrap_or_default() > self.max_age;
+            if index >= self.max_files || expired {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,278 @@
+//! Native file dialogs behind `dialog_open`, `dialog_save` and
+//! `dialog_folder`.
+//!
+//! Options are checked before a dialog opens, and every path a dialog
+//! returns is made absolute and canonical, so callers can compare them with
+//! `fs_scope` and each other.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use rfd::{AsyncFileDialog, FileHandle};
+use serde::Deserialize;
+use tao::window::Window;
+
+use crate::api_manager::Unsupported;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DialogKind {
+    Open,
+    Save,
+    Folder,
+}
+
+/// A named group of extensions, e.g. `{"name": "Images", "extensions": ["png", "jpg"]}`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FileFilter {
+    pub name: String,
+    /// Without dots; `["*"]` matches every file.
+    pub extensions: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DialogOptions {
+    pub title: Option<String>,
+    /// Directory the dialog starts in.
+    pub directory: Option<PathBuf>,
+    /// Pick several files or folders. Not for save dialogs.
+    pub multiple: bool,
+    /// Not for folder dialogs.
+    pub filters: Vec<FileFilter>,
+    /// File name a save dialog suggests.
+    pub default_name: Option<String>,
+    /// Ask before a save dialog overwrites a file. The native dialogs always
+    /// ask, so only `true` is supported.
+    pub confirm_overwrite: Option<bool>,
+    pub can_create_directories: Option<bool>,
+    /// Window the dialog is modal to, as `get_window_state`'s `id`; the
+    /// request's target window if unset.
+    pub parent: Option<u64>,
+}
+
+impl DialogOptions {
+    pub fn validate(&self, kind: DialogKind) -> Result<()> {
+        if self.multiple && kind == DialogKind::Save {
+            bail!("Save dialogs can't select multiple files");
+        }
+        if !self.filters.is_empty() && kind == DialogKind::Folder {
+            bail!("Folder dialogs don't take filters");
+        }
+        if kind != DialogKind::Save {
+            if self.default_name.is_some() {
+                bail!("default_name is only for save dialogs");
+            }
+            if self.confirm_overwrite.is_some() {
+                bail!("confirm_overwrite is only for save dialogs");
+            }
+        }
+        if self.confirm_overwrite == Some(false) {
+            return Err(Unsupported {
+                feature: "Saving without overwrite confirmation".into(),
+            }
+            .into());
+        }
+        if let Some(name) = &self.default_name {
+            if name.is_empty() || name.contains(['/', '\\']) {
+                bail!("default_name {:?} must be a file name, not a path", name);
+            }
+        }
+        for filter in &self.filters {
+            validate_filter(filter)?;
+        }
+        Ok(())
+    }
+
+    /// The dialog, modal to `parent`.
+    pub fn build(&self, parent: &Window) -> AsyncFileDialog {
+        let mut dialog = AsyncFileDialog::new().set_parent(parent);
+        if let Some(title) = &self.title {
+            dialog = dialog.set_title(title);
+        }
+        if let Some(directory) = &self.directory {
+            dialog = dialog.set_directory(directory);
+        }
+        if let Some(name) = &self.default_name {
+            dialog = dialog.set_file_name(name);
+        }
+        if let Some(can) = self.can_create_directories {
+            dialog = dialog.set_can_create_directories(can);
+        }
+        for filter in &self.filters {
+            dialog = dialog.add_filter(&filter.name, &filter.extensions);
+        }
+        dialog
+    }
+}
+
+fn validate_filter(filter: &FileFilter) -> Result<()> {
+    if filter.name.trim().is_empty() {
+        bail!("Filter names can't be empty");
+    }
+    if filter.extensions.is_empty() {
+        bail!("Filter {:?} has no extensions", filter.name);
+    }
+    for ext in &filter.extensions {
+        if ext == "*" {
+            continue;
+        }
+        if ext.starts_with('.') {
+            bail!(
+                "Extension {:?} of filter {:?} must be given without the dot",
+                ext,
+                filter.name
+            );
+        }
+        let valid = !ext.is_empty()
+            && !ext
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '/' | '\\' | '*' | '?' | ';' | ','));
+        if !valid {
+            bail!("Invalid extension {:?} in filter {:?}", ext, filter.name);
+        }
+    }
+    Ok(())
+}
+
+/// Absolute, canonical form of a picked path. A save target may not exist
+/// yet, so only its directory is canonicalized then.
+pub fn normalize(path: &Path) -> Result<String> {
+    let canonical = match path.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) => {
+            let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+                bail!("Cannot resolve {:?}", path);
+            };
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            dir.canonicalize()
+                .map_err(|e| anyhow!("Cannot resolve {:?}: {}", path, e))?
+                .join(name)
+        }
+    };
+    Ok(canonical.to_string_lossy().into_owned())
+}
+
+pub fn normalize_all(handles: Option<Vec<FileHandle>>) -> Result<Option<Vec<String>>> {
+    handles
+        .map(|handles| handles.iter().map(|h| normalize(h.path())).collect())
+        .transpose()
+}
+
+pub fn normalize_one(handle: Option<FileHandle>) -> Result<Option<String>> {
+    handle.map(|h| normalize(h.path())).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn options(value: serde_json::Value) -> DialogOptions {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn error(options: &DialogOptions, kind: DialogKind) -> String {
+        options.validate(kind).unwrap_err().to_string()
+    }
+
+    /// A new directory under the temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("pyframe-dialog-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn accepts_the_full_option_set() {
+        let open = options(json!({
+            "title": "Pick images",
+            "multiple": true,
+            "filters": [{"name": "Images", "extensions": ["png", "jpg"]}, {"name": "All", "extensions": ["*"]}],
+        }));
+        open.validate(DialogKind::Open).unwrap();
+        let save = options(json!({"default_name": "report.pdf", "confirm_overwrite": true}));
+        save.validate(DialogKind::Save).unwrap();
+        options(json!({"multiple": true}))
+            .validate(DialogKind::Folder)
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_dotted_extensions() {
+        let options = options(json!({"filters": [{"name": "Text", "extensions": [".txt"]}]}));
+        assert!(error(&options, DialogKind::Open).contains("without the dot"));
+    }
+
+    #[test]
+    fn rejects_empty_filters() {
+        let no_extensions = options(json!({"filters": [{"name": "Text", "extensions": []}]}));
+        assert!(error(&no_extensions, DialogKind::Open).contains("has no extensions"));
+        let no_name = options(json!({"filters": [{"name": " ", "extensions": ["txt"]}]}));
+        assert!(error(&no_name, DialogKind::Open).contains("names can't be empty"));
+        let empty_extension = options(json!({"filters": [{"name": "Text", "extensions": [""]}]}));
+        assert!(error(&empty_extension, DialogKind::Open).contains("Invalid extension"));
+        let pattern = options(json!({"filters": [{"name": "Text", "extensions": ["t?t"]}]}));
+        assert!(error(&pattern, DialogKind::Open).contains("Invalid extension"));
+    }
+
+    #[test]
+    fn rejects_multiple_with_save() {
+        let options = options(json!({"multiple": true}));
+        assert!(error(&options, DialogKind::Save).contains("multiple"));
+    }
+
+    #[test]
+    fn rejects_options_of_other_dialogs() {
+        let filters = options(json!({"filters": [{"name": "Text", "extensions": ["txt"]}]}));
+        assert!(error(&filters, DialogKind::Folder).contains("filters"));
+        let name = options(json!({"default_name": "a.txt"}));
+        assert!(error(&name, DialogKind::Open).contains("only for save dialogs"));
+        let path = options(json!({"default_name": "dir/a.txt"}));
+        assert!(error(&path, DialogKind::Save).contains("not a path"));
+        let no_confirm = options(json!({"confirm_overwrite": false}));
+        assert!(no_confirm
+            .validate(DialogKind::Save)
+            .unwrap_err()
+            .is::<Unsupported>());
+        assert!(serde_json::from_value::<DialogOptions>(json!({"multi": true})).is_err());
+    }
+
+    #[test]
+    fn normalizes_existing_paths() {
+        let dir = TempDir::new();
+        let file = dir.0.join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+        let roundabout = dir.0.join(".").join("a.txt");
+        let normalized = normalize(&roundabout).unwrap();
+        assert_eq!(normalized, file.canonicalize().unwrap().to_string_lossy());
+        assert!(Path::new(&normalized).is_absolute());
+    }
+
+    #[test]
+    fn normalizes_a_save_path_that_does_not_exist_yet() {
+        let dir = TempDir::new();
+        let target = dir.0.join(".").join("new.txt");
+        let normalized = normalize(&target).unwrap();
+        assert_eq!(
+            PathBuf::from(normalized),
+            dir.0.canonicalize().unwrap().join("new.txt")
+        );
+        assert!(normalize(&dir.0.join("missing").join("new.txt")).is_err());
+    }
+}
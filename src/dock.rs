@@ -0,0 +1,163 @@
+//! The macOS dock: a menu of its own (`set_dock_menu`), clicks on the dock
+//! icon, and the activation policy deciding whether the app shows in the
+//! dock and the Cmd+Tab switcher.
+//!
+//! Dock menu items are defined like tray menu items, see [`crate::menu`],
+//! and chosen ones arrive as `dock-menu` events. A click on the dock icon
+//! while no window is visible brings the hidden window back unless
+//! `reopen_on_dock_click` is `false`, and is reported as `dock-reopen`
+//! either way. Other platforms have no dock; the APIs fail there with
+//! `UNSUPPORTED`.
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::{
+    config::AppConfig,
+    menu::{ItemSpec, MenuClick, MenuModel},
+    utils::FrameWindowTarget,
+};
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationPolicy {
+    /// In the dock and the switcher, with a menu bar.
+    Regular,
+    /// Neither in the dock nor the switcher, like a menu bar extra.
+    Accessory,
+    /// Never activated, like a background agent.
+    Prohibited,
+}
+
+/// The `set_dock_menu` menu.
+#[derive(Default)]
+pub struct Dock {
+    menu: Option<MenuModel>,
+}
+
+impl Dock {
+    pub fn set_menu(&mut self, items: Vec<ItemSpec>) -> Result<()> {
+        #[cfg(target_os = "macos")]
+        {
+            if let Some(menu) = self.menu.as_mut() {
+                if menu.update_from(items.clone())? {
+                    return Ok(());
+                }
+            }
+            let menu = MenuModel::build(items)?;
+            macos::set_menu(&menu);
+            // Frees the old native menu, which AppKit no longer hands out.
+            self.menu = Some(menu);
+            Ok(())
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = items;
+            Err(unsupported("dock menu"))
+        }
+    }
+
+    /// `None` unless `id` is an item of the dock menu.
+    pub fn clicked(&mut self, id: &tray_icon::menu::MenuId) -> Option<MenuClick> {
+        self.menu.as_mut()?.clicked(id)
+    }
+}
+
+pub fn set_activation_policy(target: &FrameWindowTarget, policy: ActivationPolicy) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        use tao::platform::macos::EventLoopWindowTargetExtMacOS;
+        target.set_activation_policy_at_runtime(macos::policy(policy));
+        Ok(())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (target, policy);
+        Err(unsupported("activation policy"))
+    }
+}
+
+/// The dock options that were given but don't apply on this platform.
+pub fn creation_warnings(config: &AppConfig) -> Vec<String> {
+    if cfg!(target_os = "macos") || config.activation_policy.is_none() {
+        return Vec::new();
+    }
+    vec!["activation_policy only applies on macOS and was ignored".to_string()]
+}
+
+/// Applies `activation_policy` before the event loop runs.
+pub fn apply(event_loop: &mut crate::utils::FrameEventLoop, config: &AppConfig) {
+    #[cfg(target_os = "macos")]
+    if let Some(policy) = config.activation_policy {
+        use tao::platform::macos::EventLoopExtMacOS;
+        event_loop.set_activation_policy(macos::policy(policy));
+    }
+    let _ = (event_loop, config);
+}
+
+#[cfg(not(target_os = "macos"))]
+fn unsupported(feature: &str) -> anyhow::Error {
+    anyhow::Error::new(crate::api_manager::Unsupported {
+        feature: format!("{} outside macOS", feature),
+    })
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::{
+        ffi::c_void,
+        ptr,
+        sync::atomic::{AtomicPtr, Ordering},
+    };
+
+    use objc2::{
+        ffi::class_addMethod,
+        runtime::{AnyClass, AnyObject, Imp, Sel},
+        sel,
+    };
+    use tray_icon::menu::ContextMenu;
+
+    use super::ActivationPolicy;
+    use crate::menu::MenuModel;
+
+    /// The `NSMenu` AppKit gets when it asks for the dock menu; owned by
+    /// the [`MenuModel`] in [`Dock`](super::Dock).
+    static DOCK_MENU: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+    pub fn policy(policy: ActivationPolicy) -> tao::platform::macos::ActivationPolicy {
+        use tao::platform::macos::ActivationPolicy as Tao;
+        match policy {
+            ActivationPolicy::Regular => Tao::Regular,
+            ActivationPolicy::Accessory => Tao::Accessory,
+            ActivationPolicy::Prohibited => Tao::Prohibited,
+        }
+    }
+
+    pub fn set_menu(menu: &MenuModel) {
+        DOCK_MENU.store(menu.menu().ns_menu(), Ordering::Release);
+        install();
+    }
+
+    /// Adds `applicationDockMenu:` to tao's application delegate, which
+    /// doesn't implement it. Does nothing the second time.
+    fn install() {
+        let Some(class) = AnyClass::get(c"TaoAppDelegateParent") else {
+            return;
+        };
+        unsafe {
+            let imp: Imp = std::mem::transmute(
+                dock_menu as extern "C" fn(&AnyObject, Sel, *mut AnyObject) -> *mut c_void,
+            );
+            class_addMethod(
+                class as *const AnyClass as *mut AnyClass,
+                sel!(applicationDockMenu:),
+                imp,
+                c"@@:@".as_ptr(),
+            );
+        }
+    }
+
+    extern "C" fn dock_menu(_: &AnyObject, _: Sel, _sender: *mut AnyObject) -> *mut c_void {
+        DOCK_MENU.load(Ordering::Acquire)
+    }
+}
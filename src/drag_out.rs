@@ -0,0 +1,563 @@
+//! Dragging files out of the window into a file manager: `start_drag`.
+//!
+//! The page starts it in a `dragstart` handler with
+//! `pyframe.startDrag(items, event)`, which cancels the page's own drag and
+//! posts the items over `window.ipc`, so the native drag begins while the
+//! button is still down. Items are files in `fs_scope`, or `{data,
+//! filename}` contents written to a temporary directory first.
+//!
+//! | Platform | Drag | Formats |
+//! |----------|------|---------|
+//! | Windows | `SHDoDragDrop` with the shell's data object of the files; returns once dropped | `CF_HDROP`, shell ID lists, file names |
+//! | macOS | `beginDraggingSessionWithItems:event:source:` on the content view | one `NSURL` per file, `public.file-url` |
+//! | Linux | `gtk_drag_begin_with_coordinates` on the window | `text/uri-list` |
+//!
+//! The primary mouse button must be held; otherwise `start_drag` fails with [`NoGesture`]. Temporary
+//! files are removed [`TEMP_GRACE`] after the drop, as targets may copy
+//! them only afterwards, and at the latest when the app shuts down.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use base64::Engine;
+use serde::Deserialize;
+use tao::window::Window;
+
+use crate::{fs_scope::FsScope, lock_force};
+
+/// How long temporary files outlive the drop.
+pub const TEMP_GRACE: Duration = Duration::from_secs(60);
+
+/// Temporary directories of drags whose files may still be copied.
+static TEMP_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// One thing dragged out.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum DragItem {
+    /// An existing file or directory in `fs_scope`.
+    Path(PathBuf),
+    /// Written to `filename` in a temporary directory for the drag.
+    Content {
+        data: String,
+        /// A bare name; no directories.
+        filename: String,
+        /// Whether `data` is base64 rather than text.
+        #[serde(default)]
+        base64: bool,
+    },
+}
+
+/// The argument of `start_drag`: one item or several.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum DragItems {
+    Many(Vec<DragItem>),
+    One(DragItem),
+}
+
+/// `start_drag` was called while no mouse button was held.
+#[derive(Debug)]
+pub struct NoGesture;
+
+impl std::fmt::Display for NoGesture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            "start_drag needs the primary mouse button held down; call it from a \
+             dragstart or mousedown handler, not after the gesture ended",
+        )
+    }
+}
+
+impl std::error::Error for NoGesture {}
+
+/// Begins dragging `items` out of `window`. `true` once the drag began; on
+/// Windows once it ended, as the drag runs in a modal loop there.
+pub fn start(
+    window: &Window,
+    items: DragItems,
+    scope: &FsScope,
+    rt: &tokio::runtime::Handle,
+) -> Result<bool> {
+    if !platform::pressed(window) {
+        return Err(NoGesture.into());
+    }
+    let items = match items {
+        DragItems::Many(items) => items,
+        DragItems::One(item) => vec![item],
+    };
+    if items.is_empty() {
+        bail!("start_drag needs at least one item");
+    }
+    let mut temp: Option<PathBuf> = None;
+    let files = items
+        .into_iter()
+        .map(|item| match item {
+            DragItem::Path(path) => {
+                let path = scope.resolve(&path)?;
+                if !path.exists() {
+                    bail!("{:?} doesn't exist", path);
+                }
+                Ok(path)
+            }
+            DragItem::Content {
+                data,
+                filename,
+                base64,
+            } => {
+                let name = file_name(&filename)?;
+                let bytes = if base64 {
+                    base64::engine::general_purpose::STANDARD.decode(data)?
+                } else {
+                    data.into_bytes()
+                };
+                let dir = match &temp {
+                    Some(dir) => dir,
+                    None => temp.insert(temp_dir()?),
+                };
+                let path = dir.join(name);
+                std::fs::write(&path, bytes)?;
+                Ok(path)
+            }
+        })
+        .collect::<Result<Vec<_>>>();
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => {
+            if let Some(dir) = temp {
+                remove(&dir);
+            }
+            return Err(e);
+        }
+    };
+    let rt = rt.clone();
+    platform::start(
+        window,
+        &files,
+        Box::new(move || {
+            if let Some(dir) = temp {
+                crate::tasks::spawn_on("drag_out_cleanup", &rt, async move {
+                    tokio::time::sleep(TEMP_GRACE).await;
+                    remove(&dir);
+                });
+            }
+        }),
+    )?;
+    Ok(true)
+}
+
+/// Removes what drags left in the temporary directory, at shutdown.
+pub fn remove_temp_files() {
+    for dir in lock_force!(TEMP_DIRS).drain(..) {
+        crate::log_if_err!(std::fs::remove_dir_all(&dir));
+    }
+}
+
+/// `filename` if it names a file without a directory.
+fn file_name(filename: &str) -> Result<&Path> {
+    let path = Path::new(filename);
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(path),
+        _ => bail!(
+            "Invalid file name {:?}; it may not contain directories",
+            filename
+        ),
+    }
+}
+
+fn temp_dir() -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("pyframe-drag-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir(&dir)?;
+    lock_force!(TEMP_DIRS).push(dir.clone());
+    Ok(dir)
+}
+
+fn remove(dir: &Path) {
+    let mut dirs = lock_force!(TEMP_DIRS);
+    if let Some(index) = dirs.iter().position(|d| d == dir) {
+        dirs.remove(index);
+        crate::log_if_err!(std::fs::remove_dir_all(dir));
+    }
+}
+
+/// Called once the drop is done or the drag failed to start.
+type Ended = Box<dyn FnOnce() + Send>;
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::{ffi::c_void, path::PathBuf};
+
+    use anyhow::Result;
+    use tao::{platform::windows::WindowExtWindows, window::Window};
+    use windows::{
+        core::HSTRING,
+        Win32::{
+            Foundation::HWND,
+            System::{
+                Com::{CoTaskMemFree, IDataObject},
+                Ole::DROPEFFECT_COPY,
+            },
+            UI::{
+                Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LBUTTON},
+                Shell::{
+                    BHID_DataObject, Common::ITEMIDLIST, IShellItemArray,
+                    SHCreateShellItemArrayFromIDLists, SHDoDragDrop, SHParseDisplayName,
+                },
+            },
+        },
+    };
+
+    use super::Ended;
+
+    pub fn pressed(_window: &Window) -> bool {
+        unsafe { GetAsyncKeyState(VK_LBUTTON.0 as i32) as u16 & 0x8000 != 0 }
+    }
+
+    pub fn start(window: &Window, files: &[PathBuf], ended: Ended) -> Result<()> {
+        let mut ids: Vec<*mut ITEMIDLIST> = Vec::with_capacity(files.len());
+        let result = drag(window, files, &mut ids);
+        for id in ids {
+            unsafe { CoTaskMemFree(Some(id as *const c_void)) };
+        }
+        ended();
+        result
+    }
+
+    /// Runs the drag with a default drop source until the drop.
+    fn drag(window: &Window, files: &[PathBuf], ids: &mut Vec<*mut ITEMIDLIST>) -> Result<()> {
+        unsafe {
+            for file in files {
+                let mut id = std::ptr::null_mut();
+                SHParseDisplayName(&HSTRING::from(file.as_os_str()), None, &mut id, 0, None)?;
+                ids.push(id);
+            }
+            let list: Vec<*const ITEMIDLIST> = ids.iter().map(|id| *id as *const _).collect();
+            let items: IShellItemArray = SHCreateShellItemArrayFromIDLists(&list)?;
+            let data: IDataObject = items.BindToHandler(None, &BHID_DataObject)?;
+            SHDoDragDrop(Some(HWND(window.hwnd() as _)), &data, None, DROPEFFECT_COPY)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::{
+        ffi::c_void,
+        path::PathBuf,
+        sync::{Mutex, OnceLock},
+    };
+
+    use anyhow::{anyhow, Result};
+    use objc2::{
+        class, msg_send,
+        rc::{Allocated, Retained},
+        runtime::{AnyObject, ClassBuilder, Sel},
+        sel, Encode, Encoding,
+    };
+    use objc2_foundation::NSString;
+    use tao::{platform::macos::WindowExtMacOS, window::Window};
+
+    use super::{Ended, NoGesture};
+    use crate::lock_force;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Size {
+        width: f64,
+        height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Rect {
+        origin: Point,
+        size: Size,
+    }
+
+    unsafe impl Encode for Point {
+        const ENCODING: Encoding = Encoding::Struct("CGPoint", &[f64::ENCODING, f64::ENCODING]);
+    }
+
+    unsafe impl Encode for Size {
+        const ENCODING: Encoding = Encoding::Struct("CGSize", &[f64::ENCODING, f64::ENCODING]);
+    }
+
+    unsafe impl Encode for Rect {
+        const ENCODING: Encoding = Encoding::Struct("CGRect", &[Point::ENCODING, Size::ENCODING]);
+    }
+
+    /// `NSEventTypeLeftMouseDown` and `NSEventTypeLeftMouseDragged`.
+    const LEFT_MOUSE_DOWN: usize = 1;
+    const LEFT_MOUSE_DRAGGED: usize = 6;
+    /// `NSDragOperationCopy`.
+    const DRAG_OPERATION_COPY: usize = 1;
+    /// Side of the file icons dragged, in points.
+    const ICON_SIZE: f64 = 32.0;
+
+    /// What to do when the running drag ends; AppKit runs one at a time.
+    static ENDED: Mutex<Option<Ended>> = Mutex::new(None);
+
+    pub fn pressed(_window: &Window) -> bool {
+        let buttons: usize = unsafe { msg_send![class!(NSEvent), pressedMouseButtons] };
+        buttons & 1 != 0
+    }
+
+    pub fn start(window: &Window, files: &[PathBuf], ended: Ended) -> Result<()> {
+        let result = begin(window, files);
+        match result {
+            Ok(()) => {
+                if let Some(previous) = lock_force!(ENDED).replace(ended) {
+                    previous();
+                }
+            }
+            Err(_) => ended(),
+        }
+        result
+    }
+
+    fn begin(window: &Window, files: &[PathBuf]) -> Result<()> {
+        unsafe {
+            let view = &*(window.ns_view() as *const AnyObject);
+            let event = mouse_event(view)?;
+            let event = &*event;
+            let location: Point = msg_send![event, locationInWindow];
+            let point: Point =
+                msg_send![view, convertPoint: location, fromView: std::ptr::null::<AnyObject>()];
+            let workspace: *mut AnyObject = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let items: *mut AnyObject = msg_send![class!(NSMutableArray), array];
+            for (index, file) in files.iter().enumerate() {
+                let path = NSString::from_str(&file.to_string_lossy());
+                let url: *mut AnyObject = msg_send![class!(NSURL), fileURLWithPath: &*path];
+                let item: Allocated<AnyObject> = msg_send![class!(NSDraggingItem), alloc];
+                let item: Retained<AnyObject> = msg_send![item, initWithPasteboardWriter: url];
+                let icon: *mut AnyObject = msg_send![workspace, iconForFile: &*path];
+                // Fanned out a little, so several files show as several.
+                let offset = index as f64 * ICON_SIZE / 4.0;
+                let frame = Rect {
+                    origin: Point {
+                        x: point.x - ICON_SIZE / 2.0 + offset,
+                        y: point.y - ICON_SIZE / 2.0 - offset,
+                    },
+                    size: Size {
+                        width: ICON_SIZE,
+                        height: ICON_SIZE,
+                    },
+                };
+                let _: () = msg_send![&*item, setDraggingFrame: frame, contents: icon];
+                let _: () = msg_send![items, addObject: &*item];
+            }
+            let session: *mut AnyObject = msg_send![
+                view,
+                beginDraggingSessionWithItems: items,
+                event: event,
+                source: source()?
+            ];
+            if session.is_null() {
+                return Err(anyhow!("AppKit didn't begin the drag"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The mouse event AppKit is handling, or as the IPC message arrives
+    /// after it, a left-mouse-dragged event at the pointer.
+    unsafe fn mouse_event(view: &AnyObject) -> Result<Retained<AnyObject>> {
+        let app: *mut AnyObject = msg_send![class!(NSApplication), sharedApplication];
+        let current: *mut AnyObject = msg_send![app, currentEvent];
+        if let Some(event) = current.as_ref() {
+            let kind: usize = msg_send![event, r#type];
+            if kind == LEFT_MOUSE_DOWN || kind == LEFT_MOUSE_DRAGGED {
+                return Retained::retain(current).ok_or_else(|| NoGesture.into());
+            }
+        }
+        let window: *mut AnyObject = msg_send![view, window];
+        let window = window.as_ref().ok_or(NoGesture)?;
+        let location: Point = msg_send![window, mouseLocationOutsideOfEventStream];
+        let number: isize = msg_send![window, windowNumber];
+        let info: *mut AnyObject = msg_send![class!(NSProcessInfo), processInfo];
+        let uptime: f64 = msg_send![info, systemUptime];
+        let event: *mut AnyObject = msg_send![
+            class!(NSEvent),
+            mouseEventWithType: LEFT_MOUSE_DRAGGED,
+            location: location,
+            modifierFlags: 0usize,
+            timestamp: uptime,
+            windowNumber: number,
+            context: std::ptr::null::<AnyObject>(),
+            eventNumber: 0isize,
+            clickCount: 1isize,
+            pressure: 1.0f32
+        ];
+        Retained::retain(event).ok_or_else(|| NoGesture.into())
+    }
+
+    /// The `NSDraggingSource` of every drag, an instance of a class made
+    /// for it on first use.
+    fn source() -> Result<&'static AnyObject> {
+        static SOURCE: OnceLock<usize> = OnceLock::new();
+        let source = *SOURCE.get_or_init(|| unsafe {
+            let Some(mut builder) = ClassBuilder::new(c"PyframeDragSource", class!(NSObject))
+            else {
+                return 0;
+            };
+            builder.add_method(
+                sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+                operation_mask as extern "C" fn(&AnyObject, Sel, *mut AnyObject, isize) -> usize,
+            );
+            builder.add_method(
+                sel!(draggingSession:endedAtPoint:operation:),
+                session_ended as extern "C" fn(&AnyObject, Sel, *mut AnyObject, Point, usize),
+            );
+            let class = builder.register();
+            // Lives as long as the app, as AppKit holds drag sources weakly.
+            let source: Retained<AnyObject> = msg_send![class, new];
+            Retained::into_raw(source) as *const c_void as usize
+        });
+        unsafe { (source as *const AnyObject).as_ref() }
+            .ok_or_else(|| anyhow!("The drag source couldn't be created"))
+    }
+
+    extern "C" fn operation_mask(
+        _: &AnyObject,
+        _: Sel,
+        _session: *mut AnyObject,
+        _context: isize,
+    ) -> usize {
+        DRAG_OPERATION_COPY
+    }
+
+    extern "C" fn session_ended(
+        _: &AnyObject,
+        _: Sel,
+        _session: *mut AnyObject,
+        _at: Point,
+        _operation: usize,
+    ) {
+        if let Some(ended) = lock_force!(ENDED).take() {
+            ended();
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod platform {
+    use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+    use anyhow::{anyhow, Result};
+    use gtk::{
+        gdk,
+        glib::{prelude::ObjectExt, SignalHandlerId},
+        prelude::{SeatExt, WidgetExt},
+        TargetEntry, TargetFlags, TargetList,
+    };
+    use tao::{platform::unix::WindowExtUnix, window::Window};
+
+    use super::Ended;
+
+    pub fn pressed(window: &Window) -> bool {
+        let gtk_window = window.gtk_window();
+        let (Some(gdk_window), Some(pointer)) = (
+            gtk_window.window(),
+            gtk_window
+                .display()
+                .default_seat()
+                .and_then(|seat| seat.pointer()),
+        ) else {
+            return false;
+        };
+        let (_, _, _, state) = gdk_window.device_position(&pointer);
+        state.contains(gdk::ModifierType::BUTTON1_MASK)
+    }
+
+    pub fn start(window: &Window, files: &[PathBuf], ended: Ended) -> Result<()> {
+        let widget = window.gtk_window();
+        let uris: Vec<String> = files
+            .iter()
+            .filter_map(|file| url::Url::from_file_path(file).ok())
+            .map(String::from)
+            .collect();
+        let targets =
+            TargetList::new(&[TargetEntry::new("text/uri-list", TargetFlags::OTHER_APP, 0)]);
+        let data = widget.connect_drag_data_get(move |_, _, data, _, _| {
+            let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+            data.set_uris(&uris);
+        });
+        // Both handlers go once the drag ends.
+        let handlers: Rc<RefCell<Vec<SignalHandlerId>>> = Rc::new(RefCell::new(vec![data]));
+        let ended = Rc::new(RefCell::new(Some(ended)));
+        let finish = {
+            let handlers = handlers.clone();
+            let ended = ended.clone();
+            move |widget: &gtk::ApplicationWindow| {
+                for handler in handlers.borrow_mut().drain(..) {
+                    widget.disconnect(handler);
+                }
+                if let Some(ended) = ended.borrow_mut().take() {
+                    ended();
+                }
+            }
+        };
+        let end = widget.connect_drag_end({
+            let finish = finish.clone();
+            move |widget, _| finish(widget)
+        });
+        handlers.borrow_mut().push(end);
+        let context =
+            widget.drag_begin_with_coordinates(&targets, gdk::DragAction::COPY, 1, None, -1, -1);
+        if context.is_none() {
+            // GTK emits no drag-end for a drag that never began.
+            finish(widget);
+            return Err(anyhow!("GTK didn't begin the drag"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+mod platform {
+    use std::path::PathBuf;
+
+    use anyhow::Result;
+    use tao::window::Window;
+
+    use super::Ended;
+    use crate::api_manager::Unsupported;
+
+    pub fn pressed(_window: &Window) -> bool {
+        true
+    }
+
+    pub fn start(_window: &Window, _files: &[PathBuf], ended: Ended) -> Result<()> {
+        ended();
+        Err(anyhow::Error::new(Unsupported {
+            feature: "start_drag".into(),
+        }))
+    }
+}
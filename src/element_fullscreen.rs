@@ -0,0 +1,103 @@
+//! The window follows an HTML element into fullscreen, e.g. a `<video>`'s
+//! fullscreen button.
+//!
+//! `element_fullscreen.js` posts [`ELEMENT_FULLSCREEN`] on every
+//! `fullscreenchange`; the window goes borderless fullscreen and comes back
+//! with the size, position and maximized state it had. Escape leaves the
+//! element's fullscreen in the WebView, which brings the window back the
+//! same way. Each change is emitted as `element-fullscreen`. A window that
+//! was already fullscreen, e.g. in kiosk mode, is left as it is.
+//!
+//! WebKitGTK would fullscreen the GTK window on its own; that is turned off
+//! so the state to restore is taken first. WKWebView shows fullscreen
+//! elements in a window of its own, so macOS only gets the events.
+
+use serde::{Deserialize, Serialize};
+use tao::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    window::{Fullscreen, Window},
+};
+
+/// Prefix of the IPC message, followed by an [`ElementFullscreen`] as JSON.
+pub const ELEMENT_FULLSCREEN: &str = "__element_fullscreen:";
+
+/// Payload of `element-fullscreen`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ElementFullscreen {
+    pub active: bool,
+    /// Lower-case tag name of the fullscreen element, e.g. `"video"`.
+    pub element: Option<String>,
+}
+
+/// The window state fullscreen replaced.
+struct Restore {
+    maximized: bool,
+    position: Option<PhysicalPosition<i32>>,
+    size: PhysicalSize<u32>,
+}
+
+#[derive(Default)]
+pub struct ElementFullscreenState {
+    active: bool,
+    /// `None` if the window was fullscreen already.
+    restore: Option<Restore>,
+}
+
+impl ElementFullscreenState {
+    /// Applies `change` to `window`; `false` if nothing changed.
+    pub fn apply(&mut self, window: &Window, change: &ElementFullscreen) -> bool {
+        if change.active == self.active {
+            return false;
+        }
+        self.active = change.active;
+        if cfg!(target_os = "macos") {
+            return true;
+        }
+        if change.active {
+            if window.fullscreen().is_none() {
+                self.restore = Some(Restore {
+                    maximized: window.is_maximized(),
+                    position: window.outer_position().ok(),
+                    size: window.inner_size(),
+                });
+                window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+            }
+        } else if let Some(restore) = self.restore.take() {
+            window.set_fullscreen(None);
+            if !restore.maximized {
+                if let Some(position) = restore.position {
+                    window.set_outer_position(position);
+                }
+                window.set_inner_size(restore.size);
+            }
+            window.set_maximized(restore.maximized);
+        }
+        true
+    }
+}
+
+/// Keeps WebKitGTK from fullscreening the GTK window behind tao's back.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub fn hook(webview: &wry::WebView) {
+    use webkit2gtk::WebViewExt;
+    use wry::WebViewExtUnix;
+
+    let webview = webview.webview();
+    webview.connect_enter_fullscreen(|_| true);
+    webview.connect_leave_fullscreen(|_| true);
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn hook(_webview: &wry::WebView) {}
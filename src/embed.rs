@@ -0,0 +1,87 @@
+//! Embedding the window into a window of another toolkit, e.g. a PyQt main
+//! window, with the `parent_handle` option.
+//!
+//! | Platform | `parent_handle` | The frame becomes |
+//! |----------|-----------------|-------------------|
+//! | Windows  | `HWND`, e.g. `QWidget.winId()` | a `WS_CHILD` window in the parent's client area |
+//! | macOS    | `NSView*`, e.g. `QWidget.winId()` | a borderless child `NSWindow` of the view's window |
+//! | Linux    | | unsupported: GTK can't adopt a foreign X11 or Wayland window |
+//!
+//! The host owns the layout: it calls `set_webview_bounds` whenever the
+//! parent resizes. Unsupported platforms fail creation with
+//! [`Unsupported`], so the caller can open a separate window instead.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tao::{
+    dpi::{LogicalPosition, LogicalSize},
+    window::{Window, WindowBuilder},
+};
+
+#[allow(unused_imports)]
+use crate::api_manager::Unsupported;
+
+/// Where the embedded window goes, in logical pixels: relative to the
+/// parent's client area on Windows, in screen coordinates on macOS, where
+/// child windows are positioned on the screen.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// `builder` for a borderless child of the foreign window `parent`.
+pub fn child_of(builder: WindowBuilder, parent: u64) -> Result<WindowBuilder> {
+    if parent == 0 {
+        return Err(anyhow!("parent_handle must not be 0"));
+    }
+    let builder = builder.with_decorations(false);
+    #[cfg(target_os = "windows")]
+    {
+        use tao::platform::windows::WindowBuilderExtWindows;
+        // An invalid HWND fails `build` with the error of CreateWindowEx.
+        Ok(builder.with_parent_window(parent as isize))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use tao::platform::macos::WindowBuilderExtMacOS;
+        Ok(builder.with_parent_window(macos::window_of(parent)?))
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = builder;
+        Err(anyhow::Error::new(Unsupported {
+            feature: "parent_handle".into(),
+        }))
+    }
+}
+
+pub fn set_bounds(window: &Window, bounds: Bounds) -> Result<()> {
+    if bounds.width < 0.0 || bounds.height < 0.0 {
+        return Err(anyhow!("Bounds with negative size: {:?}", bounds));
+    }
+    window.set_outer_position(LogicalPosition::new(bounds.x, bounds.y));
+    window.set_inner_size(LogicalSize::new(bounds.width, bounds.height));
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::c_void;
+
+    use anyhow::{anyhow, Result};
+    use objc2::{msg_send, runtime::AnyObject};
+
+    /// The `NSWindow` of the `NSView` at `view`.
+    pub fn window_of(view: u64) -> Result<*mut c_void> {
+        let view = unsafe { &*(view as *const AnyObject) };
+        let window: *mut AnyObject = unsafe { msg_send![view, window] };
+        if window.is_null() {
+            // Qt creates the NSWindow when the widget is first shown.
+            return Err(anyhow!("parent_handle is not in a window yet"));
+        }
+        Ok(window.cast())
+    }
+}
@@ -0,0 +1,73 @@
+//! Events Python pushes to the pages without a request: `{"type": "emit",
+//! "event", "payload", "windows"}` frames, with [`Feature::Emit`]
+//! (crate::protocol::Feature::Emit).
+//!
+//! An emit gets no response and takes no in-flight permit. The emits of
+//! one connection reach the pages in the order they were sent, as
+//! `pyframe:<event>` like [`AppContext::emit`](crate::context::AppContext::emit).
+//! While [`MAX_QUEUED`] emits wait for the event loop, further ones are
+//! dropped with a warning instead of piling up behind a saturated UI.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Most emits posted to the event loop and not yet handled.
+pub const MAX_QUEUED: usize = 64;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename = "emit")]
+pub struct Emit {
+    pub event: String,
+    #[serde(default)]
+    pub payload: Value,
+    /// The `id`s from `get_window_state`; every window if unset.
+    pub windows: Option<Vec<u64>>,
+}
+
+/// Counts the emits waiting for the event loop.
+#[derive(Default)]
+pub struct EmitGate {
+    queued: AtomicUsize,
+    dropped: AtomicU64,
+    /// Set from the first drop until an emit gets through again, so a
+    /// burst is reported once.
+    saturated: AtomicBool,
+}
+
+impl EmitGate {
+    /// Takes a slot for an emit, or counts it as dropped.
+    pub fn admit(&self) -> bool {
+        let admitted = self
+            .queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < MAX_QUEUED).then_some(n + 1)
+            })
+            .is_ok();
+        if admitted {
+            self.saturated.store(false, Ordering::Release);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            if !self.saturated.swap(true, Ordering::AcqRel) {
+                crate::log_warn!(format!(
+                    "{} emits are waiting for the event loop; dropping emits until it catches up",
+                    MAX_QUEUED
+                ));
+            }
+        }
+        admitted
+    }
+
+    /// Called by the event loop for every emit it handles.
+    pub fn taken(&self) {
+        let _ = self
+            .queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+    }
+
+    /// Emits dropped since startup.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
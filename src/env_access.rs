@@ -0,0 +1,97 @@
+//! Environment variables and command-line arguments behind the `env_*`
+//! APIs, limited by the `env_access` option. Nothing is exposed by default,
+//! so secrets in the environment stay out of page JavaScript.
+
+use std::{collections::BTreeMap, ffi::OsStr};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::api_manager::PermissionDenied;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct EnvAccess {
+    /// Variables readable by exact name.
+    pub names: Vec<String>,
+    /// Variables readable by prefix, e.g. `"KIOSK_"`. `""` exposes the whole
+    /// environment.
+    pub prefixes: Vec<String>,
+    /// Expose the process's command-line arguments.
+    pub args: bool,
+}
+
+/// A value as UTF-8; `lossy` if invalid bytes were replaced.
+#[derive(Serialize, Debug)]
+pub struct EnvValue {
+    pub value: String,
+    pub lossy: bool,
+}
+
+impl EnvValue {
+    fn new(value: &OsStr) -> Self {
+        match value.to_str() {
+            Some(value) => Self {
+                value: value.to_string(),
+                lossy: false,
+            },
+            None => Self {
+                value: value.to_string_lossy().into_owned(),
+                lossy: true,
+            },
+        }
+    }
+}
+
+/// What `env_info` reports.
+#[derive(Serialize, Debug)]
+pub struct EnvInfo {
+    /// `false` only if the whole environment is exposed.
+    pub filtered: bool,
+    #[serde(flatten)]
+    pub access: EnvAccess,
+}
+
+impl EnvAccess {
+    pub fn allows(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+            || self.prefixes.iter().any(|p| name.starts_with(p.as_str()))
+    }
+
+    /// `None` if the variable is unset; an error if it isn't exposed.
+    pub fn get(&self, name: &str) -> Result<Option<EnvValue>> {
+        if !self.allows(name) {
+            return Err(anyhow::Error::new(PermissionDenied { permission: "env" })
+                .context(format!("{:?} is not in env_access", name)));
+        }
+        Ok(std::env::var_os(name).map(|value| EnvValue::new(&value)))
+    }
+
+    /// The exposed variables starting with `prefix`, by name.
+    pub fn all(&self, prefix: &str) -> BTreeMap<String, EnvValue> {
+        std::env::vars_os()
+            .filter_map(|(name, value)| {
+                // Names that aren't UTF-8 can't match the allowlist anyway.
+                let name = name.into_string().ok()?;
+                (name.starts_with(prefix) && self.allows(&name))
+                    .then(|| (name, EnvValue::new(&value)))
+            })
+            .collect()
+    }
+
+    /// Arguments of the native process, starting with the program.
+    pub fn args(&self) -> Result<Vec<EnvValue>> {
+        if !self.args {
+            return Err(anyhow::Error::new(PermissionDenied { permission: "env" })
+                .context("Command-line arguments are not in env_access"));
+        }
+        Ok(std::env::args_os().map(|arg| EnvValue::new(&arg)).collect())
+    }
+
+    pub fn info(&self) -> EnvInfo {
+        EnvInfo {
+            filtered: !self.prefixes.iter().any(String::is_empty),
+            access: self.clone(),
+        }
+    }
+}
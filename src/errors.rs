@@ -0,0 +1,68 @@
+//! Exceptions raised into Python, all below `pygcc.PyFrameError`.
+
+use pyo3::{create_exception, exceptions::PyRuntimeError, prelude::*};
+
+create_exception!(
+    pygcc,
+    PyFrameError,
+    PyRuntimeError,
+    "Base of the errors raised by pygcc."
+);
+create_exception!(
+    pygcc,
+    UnsupportedError,
+    PyFrameError,
+    "An option isn't supported on this platform, e.g. `parent_handle` on Linux."
+);
+create_exception!(
+    pygcc,
+    EventLoopError,
+    PyFrameError,
+    "No event loop for a webframe: `create_webframe` was called from another thread than the first time, or while a webframe is still open."
+);
+create_exception!(
+    pygcc,
+    BridgeConnectionError,
+    PyFrameError,
+    "The bridge couldn't be reached or closed the connection."
+);
+create_exception!(
+    pygcc,
+    BridgeProtocolError,
+    PyFrameError,
+    "The bridge refused the hello or sent a malformed frame."
+);
+create_exception!(
+    pygcc,
+    BridgeTimeoutError,
+    PyFrameError,
+    "No response arrived within the timeout."
+);
+create_exception!(
+    pygcc,
+    BridgeApiError,
+    PyFrameError,
+    "An API answered with an error; `args` are the message and the code."
+);
+create_exception!(
+    pygcc,
+    BindError,
+    PyFrameError,
+    "A requested port is taken; `args` are the message and the port."
+);
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("PyFrameError", py.get_type::<PyFrameError>())?;
+    m.add("UnsupportedError", py.get_type::<UnsupportedError>())?;
+    m.add("EventLoopError", py.get_type::<EventLoopError>())?;
+    m.add(
+        "BridgeConnectionError",
+        py.get_type::<BridgeConnectionError>(),
+    )?;
+    m.add("BridgeProtocolError", py.get_type::<BridgeProtocolError>())?;
+    m.add("BridgeTimeoutError", py.get_type::<BridgeTimeoutError>())?;
+    m.add("BridgeApiError", py.get_type::<BridgeApiError>())?;
+    m.add("BindError", py.get_type::<BindError>())?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! Every [`UserEvent`] the event loop handles, broadcast to TCP clients
+//! that sent [`SUBSCRIBE_METHOD`]. Backs `pyframe.events()` in Python.
+
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use crate::{utils::UserEvent, window_commands::WindowCommand, window_events::WINDOW_LABEL};
+
+/// Turns a connection into an event subscriber instead of calling an API.
+#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+pub const SUBSCRIBE_METHOD: &str = "__subscribe_events";
+/// Events buffered per subscriber; a slower one skips the oldest.
+const CAPACITY: usize = 256;
+
+#[derive(Clone)]
+pub struct EventStream {
+    sender: broadcast::Sender<Value>,
+}
+
+impl Default for EventStream {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl EventStream {
+    /// Sends `{"event": ..., "data": {...}}` to every subscriber.
+    pub fn publish(&self, event: &UserEvent) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let (name, data) = named(event);
+        let _ = self.sender.send(json!({ "event": name, "data": data }));
+    }
+
+    #[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+    pub fn subscribe(&self) -> broadcast::Receiver<Value> {
+        self.sender.subscribe()
+    }
+}
+
+/// The name and data subscribers receive for `event`.
+pub fn named(event: &UserEvent) -> (String, Value) {
+    let (name, data) = describe(event);
+    // Named per document, so watchers needn't filter every change.
+    let name = match event {
+        UserEvent::StateChanged(change) => format!("{}:{}", name, change.name),
+        _ => name.to_string(),
+    };
+    (name, data)
+}
+
+fn describe(event: &UserEvent) -> (&'static str, Value) {
+    match event {
+        UserEvent::Request(req) => ("request", json!({ "id": req.0, "method": req.1 })),
+        UserEvent::Tick(count) => ("tick", json!({ "count": count })),
+        UserEvent::Timer(id) => ("timer", json!({ "id": id })),
+        UserEvent::Task(_) => ("task", json!({})),
+        UserEvent::Exit => ("exit", json!({})),
+        UserEvent::CloseRequested(timeout_ms) => (
+            "close-requested",
+            json!({ "label": WINDOW_LABEL, "timeout_ms": timeout_ms }),
+        ),
+        UserEvent::ExitHooksDone => ("exit-hooks-done", json!({})),
+        UserEvent::ExitHook(id, name) => ("exit-hook", json!({ "id": id, "name": name })),
+        UserEvent::Shutdown => ("shutdown", json!({})),
+        UserEvent::WindowCommand(command) => match command.as_ref() {
+            WindowCommand::Open(options, _) => (
+                "window-command",
+                json!({ "op": "open", "url": options.url, "title": options.title }),
+            ),
+            WindowCommand::Close(id, _) => ("window-command", json!({ "op": "close", "id": id })),
+        },
+        UserEvent::TrayClicked => ("tray-clicked", json!({})),
+        UserEvent::Menu(id) => ("menu", json!({ "id": id.as_ref() })),
+        UserEvent::Accelerator(id) => ("accelerator", json!({ "id": id })),
+        UserEvent::Invoke(call) => ("invoke", json!({ "id": call.id, "method": call.method })),
+        UserEvent::TrayMenu(click) => ("tray-menu", json!(click)),
+        UserEvent::DockMenu(click) => ("dock-menu", json!(click)),
+        UserEvent::DockReopen(has_visible_windows) => (
+            "dock-reopen",
+            json!({ "has_visible_windows": has_visible_windows }),
+        ),
+        UserEvent::FrameReady => ("frame-ready", json!({})),
+        UserEvent::DragWindow => ("drag-window", json!({})),
+        UserEvent::ToggleMaximize => ("toggle-maximize", json!({})),
+        UserEvent::ElementFullscreen(change) => ("element-fullscreen", json!(change)),
+        UserEvent::SystemMenu => ("system-menu", json!({})),
+        UserEvent::TitlebarOverlay(layout) => ("titlebar-overlay", json!(layout)),
+        UserEvent::ContextMenu(request) => ("context-menu", json!(request)),
+        UserEvent::LoadFailed(failure) => ("load-failed", json!(failure)),
+        UserEvent::TitleChanged(title) => ("title-changed", json!({ "title": title })),
+        UserEvent::Navigation(event) => (event.kind.name(), event.payload()),
+        UserEvent::RetryLoad => ("retry-load", json!({})),
+        UserEvent::ShellOpen(id, target) => ("shell-open", json!({ "id": id, "target": target })),
+        UserEvent::StartDrag(id, _) => ("start-drag", json!({ "id": id })),
+        UserEvent::StateStore(call) => ("state-store", json!({ "id": call.id })),
+        UserEvent::StateChanged(change) => ("state-changed", json!(change)),
+        UserEvent::StoreChanged(key) => ("store-changed", json!({ "key": key })),
+        UserEvent::ExitKiosk => ("exit-kiosk", json!({})),
+        UserEvent::FsChanged(id, changes) => ("fs-change", json!({ "id": id, "changes": changes })),
+        UserEvent::ProcessOutput(pid, stream, line) => (
+            "process-output",
+            json!({ "pid": pid, "stream": stream, "line": line }),
+        ),
+        UserEvent::ProcessExited(pid, code) => {
+            ("process-exit", json!({ "pid": pid, "code": code }))
+        }
+        UserEvent::Bridge(window, id) => (
+            "bridge",
+            json!({ "id": id, "window": crate::context::AppContext::window_number(*window) }),
+        ),
+        UserEvent::Updater(id, op) => ("updater", json!({ "id": id, "op": format!("{:?}", op) })),
+        UserEvent::UpdateProgress(downloaded, total) => (
+            "update-progress",
+            json!({ "downloaded": downloaded, "total": total }),
+        ),
+        UserEvent::HttpFetch(id, request) => {
+            ("http-fetch", json!({ "id": id, "url": request.url }))
+        }
+        UserEvent::HttpBody(stream, part) => crate::http_fetch::describe(*stream, part),
+        UserEvent::WsCall(id, _) => ("ws-call", json!({ "id": id })),
+        UserEvent::Ws(id, event) => crate::ws_client::describe(*id, event),
+        UserEvent::Crashed(report) => ("crashed", json!(report)),
+        UserEvent::PermissionRequested(request) => ("permission-requested", json!(request)),
+        UserEvent::FindResult(result) => ("find-result", json!(result)),
+        UserEvent::Emit(emit) => ("emit", json!({ "event": emit.event })),
+        UserEvent::Disconnected(connection) => {
+            ("disconnected", json!({ "connection": connection }))
+        }
+        UserEvent::LegacyProtocol(connection) => (
+            "protocol-deprecated",
+            json!({
+                "connection": connection,
+                "message": "Connected without a hello; version 0 is removed in the next release",
+            }),
+        ),
+        UserEvent::PageLoad(event, url) => (
+            "page-load",
+            json!({
+                "event": match event {
+                    wry::PageLoadEvent::Started => "started",
+                    wry::PageLoadEvent::Finished => "finished",
+                },
+                "url": url,
+            }),
+        ),
+    }
+}
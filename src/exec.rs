@@ -0,0 +1,181 @@
+use std::{
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    process::Command,
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::lock_force;
+
+/// How long output is still read once the process exited or was killed.
+/// A child it started may keep the pipes open for much longer.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+pub struct ExecResult {
+    pub stdout: String,
+    pub stderr: String,
+    /// `-1` if the process was killed or ended by a signal.
+    pub exit_code: i32,
+    pub timed_out: bool,
+}
+
+/// Checks `command` against `allowed`. Commands are started directly,
+/// never through a shell, so `args` reach the program as they are.
+pub fn check_allowed(allowed: &[String], command: &str) -> Result<()> {
+    if !allowed.iter().any(|c| c == command) {
+        bail!("Command {:?} is not in allowed_commands", command);
+    }
+    Ok(())
+}
+
+/// Output of a pipe, read in the background.
+struct Output {
+    buf: Arc<Mutex<Vec<u8>>>,
+    reader: JoinHandle<()>,
+}
+
+impl Output {
+    fn read<R: AsyncRead + Unpin + Send + 'static>(pipe: Option<R>) -> Self {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let read = Arc::clone(&buf);
+        let reader = crate::tasks::spawn("exec_output", async move {
+            let Some(mut pipe) = pipe else {
+                return;
+            };
+            let mut chunk = [0; 8192];
+            while let Ok(n @ 1..) = pipe.read(&mut chunk).await {
+                lock_force!(read).extend_from_slice(&chunk[..n]);
+            }
+        });
+        Self { buf, reader }
+    }
+
+    /// What was read until the pipe closed, or until `deadline`.
+    async fn finish(mut self, deadline: Instant) -> String {
+        if tokio::time::timeout_at(deadline, &mut self.reader)
+            .await
+            .is_err()
+        {
+            self.reader.abort();
+        }
+        String::from_utf8_lossy(&lock_force!(self.buf)).into_owned()
+    }
+}
+
+/// Runs `command` from `allowed` with `args`, optionally feeding `stdin`.
+///
+/// On timeout the process is killed and whatever it printed so far is
+/// returned with `timed_out` set. Output is read for at most
+/// [`DRAIN_TIMEOUT`] after the process ended.
+pub async fn exec_command(
+    allowed: &[String],
+    command: String,
+    args: Vec<String>,
+    stdin: Option<String>,
+    timeout: Duration,
+) -> Result<ExecResult> {
+    check_allowed(allowed, &command)?;
+
+    let mut child = Command::new(&command)
+        .args(&args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start {:?}: {}", command, e))?;
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        crate::tasks::spawn("exec_stdin", async move {
+            let _ = pipe.write_all(input.as_bytes()).await;
+        });
+    }
+    let stdout = Output::read(child.stdout.take());
+    let stderr = Output::read(child.stderr.take());
+
+    let (exit_code, timed_out) = match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => (status?.code().unwrap_or(-1), false),
+        Err(_) => {
+            let _ = child.kill().await;
+            (-1, true)
+        }
+    };
+
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    Ok(ExecResult {
+        stdout: stdout.finish(deadline).await,
+        stderr: stderr.finish(deadline).await,
+        exit_code,
+        timed_out,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn allowed() -> Vec<String> {
+        vec!["sh".to_string()]
+    }
+
+    #[tokio::test]
+    async fn args_are_passed_as_they_are() {
+        let args = [
+            "-c",
+            r#"printf '%s|' "$0" "$1""#,
+            "~/*.txt",
+            r"C:\Temp\a?.log",
+        ];
+        let result = exec_command(
+            &allowed(),
+            "sh".into(),
+            args.iter().map(|arg| arg.to_string()).collect(),
+            None,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.stdout, r"~/*.txt|C:\Temp\a?.log|");
+        assert_eq!(result.exit_code, 0);
+    }
+
+    #[tokio::test]
+    async fn a_child_holding_the_pipes_does_not_block_the_result() {
+        let started = std::time::Instant::now();
+        let result = exec_command(
+            &allowed(),
+            "sh".into(),
+            vec!["-c".into(), "echo started; sleep 5 & sleep 5".into()],
+            None,
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.stdout, "started\n");
+        assert!(
+            started.elapsed() < Duration::from_secs(3),
+            "{:?}",
+            started.elapsed()
+        );
+    }
+
+    #[test]
+    fn only_allowed_commands() {
+        check_allowed(&allowed(), "sh").unwrap();
+        assert!(check_allowed(&allowed(), "bash").is_err());
+    }
+}
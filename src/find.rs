@@ -0,0 +1,348 @@
+//! Find in page: `find_in_page`, `find_next` and `stop_find`.
+//!
+//! On WebKitGTK the WebView's own find controller searches and highlights,
+//! cross-origin iframes included. Elsewhere `find.js` is evaluated in the
+//! page with the same results, but it only reaches same-origin iframes;
+//! WKWebView's native find tells whether something matched, not how often,
+//! so it isn't used.
+//!
+//! Progress arrives as `find-result` events on the page and for
+//! `pyframe.events()`: a [`FindResult`] whenever the count or the active
+//! match changes.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    /// Start at the last match instead of the first.
+    pub backwards: bool,
+    /// Continue at the other end after the last match; on by default.
+    pub wrap: bool,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        Self {
+            case_sensitive: false,
+            backwards: false,
+            wrap: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Native,
+    Script,
+}
+
+/// Payload of a `find-result` event.
+#[derive(Serialize, Clone, Debug)]
+pub struct FindResult {
+    pub window: u64,
+    pub query: String,
+    /// `None` until counted, and on WebKitGTK beyond 1000 matches.
+    pub matches: Option<u32>,
+    /// Zero-based index of the highlighted match in page order.
+    pub active: Option<u32>,
+    pub backend: Backend,
+}
+
+impl FindResult {
+    fn new(window: u64, query: &str, backend: Backend) -> Self {
+        Self {
+            window,
+            query: query.to_string(),
+            matches: None,
+            active: None,
+            backend,
+        }
+    }
+
+    fn send(&self, proxy: &FrameEventLoopProxy) {
+        let _ = proxy.send_event(UserEvent::FindResult(Box::new(self.clone())));
+    }
+}
+
+enum Search {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    Native(gtk::Session),
+    Script(String),
+}
+
+/// The search of each window, by [`window_number`](crate::context::AppContext::window_number).
+#[derive(Default)]
+pub struct Finds {
+    searches: HashMap<u64, Search>,
+}
+
+impl Finds {
+    /// Searches `webview`'s page for `query`, replacing the window's last search.
+    pub fn start(
+        &mut self,
+        webview: &wry::WebView,
+        window: u64,
+        query: String,
+        options: FindOptions,
+        proxy: &FrameEventLoopProxy,
+    ) -> Result<Backend> {
+        self.stop(webview, window)?;
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        if let Some(session) = gtk::Session::start(webview, window, &query, &options, proxy) {
+            self.searches.insert(window, Search::Native(session));
+            return Ok(Backend::Native);
+        }
+        let call = format!(
+            "search({}, {})",
+            serde_json::to_string(&query)?,
+            serde_json::json!({
+                "case_sensitive": options.case_sensitive,
+                "backwards": options.backwards,
+                "wrap": options.wrap,
+            })
+        );
+        let result = FindResult::new(window, &query, Backend::Script);
+        script::run(webview, &call, result, proxy)?;
+        self.searches.insert(window, Search::Script(query));
+        Ok(Backend::Script)
+    }
+
+    /// Moves to the next match, or the previous one in page order if
+    /// `backwards`. `false` if the window has no search.
+    pub fn next(
+        &mut self,
+        webview: &wry::WebView,
+        window: u64,
+        backwards: bool,
+        proxy: &FrameEventLoopProxy,
+    ) -> Result<bool> {
+        match self.searches.get_mut(&window) {
+            None => return Ok(false),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            Some(Search::Native(session)) => session.step(backwards),
+            Some(Search::Script(query)) => script::run(
+                webview,
+                &format!("step({})", backwards),
+                FindResult::new(window, query, Backend::Script),
+                proxy,
+            )?,
+        }
+        Ok(true)
+    }
+
+    /// Ends the window's search and removes the highlights. `false` if it
+    /// had none.
+    pub fn stop(&mut self, webview: &wry::WebView, window: u64) -> Result<bool> {
+        match self.searches.remove(&window) {
+            None => return Ok(false),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            Some(Search::Native(session)) => session.finish(),
+            Some(Search::Script(_)) => {
+                webview.evaluate_script("window.__pyframe_find && window.__pyframe_find.stop()")?
+            }
+        }
+        Ok(true)
+    }
+}
+
+mod script {
+    use anyhow::Result;
+    use serde::Deserialize;
+
+    use super::FindResult;
+    use crate::utils::FrameEventLoopProxy;
+
+    #[derive(Deserialize)]
+    struct Counts {
+        matches: u32,
+        active: Option<u32>,
+    }
+
+    /// Calls `window.__pyframe_find.<call>` and sends its counts as `result`.
+    pub fn run(
+        webview: &wry::WebView,
+        call: &str,
+        result: FindResult,
+        proxy: &FrameEventLoopProxy,
+    ) -> Result<()> {
+        // Defines the finder unless this page already has it.
+        webview.evaluate_script(crate::assets::_FIND_SCRIPT)?;
+        let proxy = proxy.clone();
+        webview.evaluate_script_with_callback(
+            &crate::script::wrap_eval(&format!("window.__pyframe_find.{}", call)),
+            move |raw| {
+                let counts = crate::script::parse_eval_result(&raw)
+                    .and_then(|value| Ok(serde_json::from_value::<Counts>(value)?));
+                match counts {
+                    Ok(counts) => {
+                        let mut result = result.clone();
+                        result.matches = Some(counts.matches);
+                        result.active = counts.active;
+                        result.send(&proxy);
+                    }
+                    Err(e) => {
+                        crate::log_err!(format!("find_in_page failed: {}", e));
+                    }
+                }
+            },
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod gtk {
+    use std::{cell::RefCell, rc::Rc};
+
+    use webkit2gtk::{
+        glib::{prelude::ObjectExt, SignalHandlerId},
+        FindController, FindControllerExt, FindOptions as Flags, WebViewExt,
+    };
+    use wry::WebViewExtUnix;
+
+    use super::{Backend, FindOptions, FindResult};
+    use crate::utils::FrameEventLoopProxy;
+
+    /// Most matches WebKit counts and highlights.
+    const MAX_MATCHES: u32 = 1000;
+
+    /// A search on the WebView's find controller, whose signals report
+    /// counts but not which match is active; that is tracked here.
+    pub struct Session {
+        controller: FindController,
+        handlers: Vec<SignalHandlerId>,
+        progress: Rc<RefCell<FindResult>>,
+        options: FindOptions,
+    }
+
+    impl Session {
+        /// `None` if the WebView has no find controller.
+        pub fn start(
+            webview: &wry::WebView,
+            window: u64,
+            query: &str,
+            options: &FindOptions,
+            proxy: &FrameEventLoopProxy,
+        ) -> Option<Self> {
+            let controller = webview.webview().find_controller()?;
+            let progress = Rc::new(RefCell::new(FindResult::new(
+                window,
+                query,
+                Backend::Native,
+            )));
+
+            let (found, found_proxy, backwards) =
+                (progress.clone(), proxy.clone(), options.backwards);
+            let on_found = controller.connect_found_text(move |_, count| {
+                let mut progress = found.borrow_mut();
+                // More than MAX_MATCHES is reported as u32::MAX.
+                progress.matches = (count != u32::MAX).then_some(count);
+                if progress.active.is_none() {
+                    progress.active = Some(match progress.matches {
+                        Some(count) if backwards => count.saturating_sub(1),
+                        _ => 0,
+                    });
+                }
+                progress.send(&found_proxy);
+            });
+            let (failed, failed_proxy) = (progress.clone(), proxy.clone());
+            let on_failed = controller.connect_failed_to_find_text(move |_| {
+                let mut progress = failed.borrow_mut();
+                // Without wrapping, stepping past the end fails too.
+                if progress.active.is_none() {
+                    progress.matches = Some(0);
+                }
+                progress.send(&failed_proxy);
+            });
+
+            let mut flags = Flags::NONE;
+            if !options.case_sensitive {
+                flags |= Flags::CASE_INSENSITIVE;
+            }
+            if options.backwards {
+                flags |= Flags::BACKWARDS;
+            }
+            if options.wrap {
+                flags |= Flags::WRAP_AROUND;
+            }
+            controller.search(query, flags.bits(), MAX_MATCHES);
+            Some(Self {
+                controller,
+                handlers: vec![on_found, on_failed],
+                progress,
+                options: options.clone(),
+            })
+        }
+
+        pub fn step(&mut self, backwards: bool) {
+            {
+                let mut progress = self.progress.borrow_mut();
+                if let (Some(matches), Some(active)) = (progress.matches, progress.active) {
+                    progress.active = Some(step(active, matches, backwards, self.options.wrap));
+                }
+            }
+            // The controller moves in the direction of the search.
+            if backwards == self.options.backwards {
+                self.controller.search_next();
+            } else {
+                self.controller.search_previous();
+            }
+        }
+
+        pub fn finish(self) {
+            self.controller.search_finish();
+            for handler in self.handlers {
+                self.controller.disconnect(handler);
+            }
+        }
+    }
+
+    fn step(active: u32, matches: u32, backwards: bool, wrap: bool) -> u32 {
+        match (backwards, wrap) {
+            _ if matches == 0 => 0,
+            (true, _) if active > 0 => active - 1,
+            (true, true) => matches - 1,
+            (false, _) if active + 1 < matches => active + 1,
+            (false, true) => 0,
+            (_, false) => active,
+        }
+    }
+}
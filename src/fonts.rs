@@ -0,0 +1,79 @@
+//! Installed font families behind `fonts_list` and `fonts_exists`.
+//!
+//! Enumerating system fonts takes seconds on Windows machines with many
+//! fonts, so it happens once, on the blocking pool, and is cached for the
+//! rest of the process.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::OnceLock,
+};
+
+use serde::Serialize;
+
+static FAMILIES: OnceLock<Vec<FontFamily>> = OnceLock::new();
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FontStyle {
+    pub weight: u16,
+    /// `"normal"`, `"italic"` or `"oblique"`.
+    pub style: &'static str,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FontFamily {
+    pub family: String,
+    /// Sorted by weight.
+    pub styles: Vec<FontStyle>,
+    pub monospaced: bool,
+}
+
+/// Every installed family, sorted by name. Blocks on the first call.
+pub fn families() -> &'static [FontFamily] {
+    FAMILIES.get_or_init(load)
+}
+
+/// Whether `family` is installed, ignoring case.
+pub fn exists(family: &str) -> bool {
+    let family = family.to_lowercase();
+    families().iter().any(|f| f.family.to_lowercase() == family)
+}
+
+fn load() -> Vec<FontFamily> {
+    let mut db = fontdb::Database::new();
+    db.load_system_fonts();
+    let mut families: BTreeMap<String, (BTreeSet<FontStyle>, bool)> = BTreeMap::new();
+    for face in db.faces() {
+        // fontdb decodes name tables lossily; a replacement character means
+        // the name can't be given to CSS as it is in the font.
+        let Some((family, _)) = face.families.first() else {
+            continue;
+        };
+        if family.is_empty() || family.contains(char::REPLACEMENT_CHARACTER) {
+            crate::log_warn!(format!(
+                "Skipping font {:?}: family name isn't valid Unicode",
+                face.post_script_name
+            ));
+            continue;
+        }
+        let style = FontStyle {
+            weight: face.weight.0,
+            style: match face.style {
+                fontdb::Style::Normal => "normal",
+                fontdb::Style::Italic => "italic",
+                fontdb::Style::Oblique => "oblique",
+            },
+        };
+        let entry = families.entry(family.clone()).or_default();
+        entry.0.insert(style);
+        entry.1 |= face.monospaced;
+    }
+    families
+        .into_iter()
+        .map(|(family, (styles, monospaced))| FontFamily {
+            family,
+            styles: styles.into_iter().collect(),
+            monospaced,
+        })
+        .collect()
+}
@@ -0,0 +1,90 @@
+//! `pygcc.FrameHandle`, Python's way to the APIs of a webframe in the same
+//! process, without a socket; the only one with `transport: "ipc-only"`.
+
+use std::time::Duration;
+
+use pyo3::{prelude::*, types::PyDict};
+use serde_json::json;
+
+use crate::{
+    api_manager::code,
+    errors::{BridgeApiError, BridgeConnectionError, BridgeTimeoutError},
+    python::{call_args, to_json, to_py},
+    transport::LocalCalls,
+};
+
+/// The running webframe of this process, for threads other than the one
+/// blocked in `create_webframe`, or any after `launch_webframe`.
+///
+/// ```python
+/// handle = pygcc.FrameHandle()
+/// handle.invoke("set_title", title="Hello")
+/// handle.emit("progress", {"done": 3})
+/// ```
+///
+/// Calls raise `BridgeConnectionError` while no webframe runs,
+/// `BridgeTimeoutError` and `BridgeApiError` with `args == (msg, code)`.
+#[pyclass(module = "pygcc")]
+pub struct FrameHandle;
+
+fn current() -> PyResult<LocalCalls> {
+    LocalCalls::current()
+        .ok_or_else(|| BridgeConnectionError::new_err("No webframe is running in this process"))
+}
+
+#[pymethods]
+impl FrameHandle {
+    #[new]
+    fn new() -> Self {
+        Self
+    }
+
+    /// Whether a webframe is running, so calls can succeed.
+    #[getter]
+    fn running(&self) -> bool {
+        LocalCalls::current().is_some()
+    }
+
+    /// Calls the API `name` and returns its result, with the arguments of
+    /// `BridgeClient.invoke`.
+    #[pyo3(signature = (name, args=None, timeout=10.0, **kwargs))]
+    fn invoke(
+        &self,
+        py: Python<'_>,
+        name: String,
+        args: Option<&Bound<'_, PyAny>>,
+        timeout: f64,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        let args = call_args(py, args, kwargs)?;
+        let calls = current()?;
+        let response = py
+            .allow_threads(|| calls.call_blocking(&name, args, Duration::from_secs_f64(timeout)))
+            .ok_or_else(|| BridgeTimeoutError::new_err("No response within the timeout"))?;
+        if response.code() != code::OK {
+            return Err(BridgeApiError::new_err((
+                response.msg().to_string(),
+                response.code(),
+            )));
+        }
+        to_py(py, response.result())
+    }
+
+    /// Closes the webframe as its close button does, or with `force` without
+    /// waiting for the exit hooks.
+    #[pyo3(signature = (force=false))]
+    fn close(&self, force: bool) -> PyResult<()> {
+        current()?
+            .close(force)
+            .map_err(|e| BridgeConnectionError::new_err(e.to_string()))
+    }
+
+    /// Emits `event` with `data` to the pages, like `pyframe.emit`.
+    #[pyo3(signature = (event, data=None))]
+    fn emit(&self, py: Python<'_>, event: String, data: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+        let data = data.map(|data| to_json(py, data)).transpose()?;
+        current()?
+            .emit(event, data.unwrap_or(json!({})))
+            .map_err(|e| BridgeConnectionError::new_err(e.to_string()))
+    }
+}
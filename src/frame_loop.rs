@@ -0,0 +1,170 @@
+//! The process-wide event loop shared by successive webframes.
+//!
+//! tao allows one event loop per process, on the thread that created it.
+//! Each `create_webframe` borrows it with [`take`] and returns it with
+//! [`put_back`] once its windows are closed, so an app can show a login
+//! window, close it and open the main window in the same process.
+//!
+//! On Windows and Linux the loop may also live on a thread of its own,
+//! started by [`spawn`], so the caller's thread stays free. macOS only
+//! allows it on the main thread.
+
+use std::{
+    cell::{Cell, RefCell},
+    sync::{mpsc, Mutex, OnceLock},
+    thread::ThreadId,
+};
+
+use crate::{
+    lock_force,
+    utils::{FrameEventLoop, FrameEventLoopBuilder},
+};
+
+/// Whether tao can run the event loop on a thread other than the main one.
+const ANY_THREAD: bool = cfg!(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+));
+
+/// The thread the event loop was built on.
+static OWNER: OnceLock<ThreadId> = OnceLock::new();
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The thread started by [`spawn`], and its queue of webframes.
+static LOOP_THREAD: Mutex<Option<(ThreadId, mpsc::Sender<Job>)>> = Mutex::new(None);
+
+thread_local! {
+    static EVENT_LOOP: RefCell<Option<FrameEventLoop>> = const { RefCell::new(None) };
+    /// Whether [`take`] built the event loop already.
+    static BUILT: Cell<bool> = const { Cell::new(false) };
+    /// Whether this is the thread of [`spawn`].
+    static IS_LOOP_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Why [`take`] has no event loop to hand out.
+#[derive(Debug)]
+pub struct LoopUnavailable(pub String);
+
+impl std::fmt::Display for LoopUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for LoopUnavailable {}
+
+/// The event loop, built on the first call. Fails on any thread but the
+/// first caller's, and if a previous webframe didn't return the loop.
+pub fn take() -> Result<FrameEventLoop, LoopUnavailable> {
+    let current = std::thread::current().id();
+    let owner = *OWNER.get_or_init(|| current);
+    if owner != current {
+        return Err(LoopUnavailable(
+            "Webframes must be created on the thread that created the first one \
+             (the main thread on macOS)"
+                .into(),
+        ));
+    }
+    EVENT_LOOP.with(|slot| {
+        if let Some(event_loop) = slot.borrow_mut().take() {
+            return Ok(event_loop);
+        }
+        if BUILT.with(|built| built.replace(true)) {
+            return Err(LoopUnavailable(
+                "The event loop is still running a webframe or was lost to a panic".into(),
+            ));
+        }
+        let mut builder = FrameEventLoopBuilder::with_user_event();
+        if IS_LOOP_THREAD.with(Cell::get) {
+            allow_any_thread(&mut builder);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            use tao::platform::windows::EventLoopBuilderExtWindows;
+            // Menu bar accelerators and the kiosk exit accelerator, which WebView2
+            // keys never reach otherwise.
+            builder.with_msg_hook(|msg| {
+                crate::kiosk::translate(msg) || crate::accelerators::translate(msg)
+            });
+        }
+        Ok(builder.build())
+    })
+}
+
+/// Returns the event loop for the next webframe.
+pub fn put_back(event_loop: FrameEventLoop) {
+    EVENT_LOOP.with(|slot| *slot.borrow_mut() = Some(event_loop));
+}
+
+/// Runs `job` on the event loop's own thread, started on the first call;
+/// later jobs wait for the earlier ones, which share the loop. Fails where
+/// the platform needs the loop on the main thread, and if a webframe built
+/// it on another thread already.
+pub fn spawn(job: Job) -> Result<(), LoopUnavailable> {
+    if !ANY_THREAD {
+        return Err(LoopUnavailable(
+            "The event loop must run on the main thread on this platform (macOS); \
+             call create_webframe from the main thread, which blocks until the window closes"
+                .into(),
+        ));
+    }
+    let mut thread = lock_force!(LOOP_THREAD);
+    if let Some(owner) = OWNER.get() {
+        if thread.as_ref().is_none_or(|(id, _)| id != owner) {
+            return Err(LoopUnavailable(
+                "The event loop already runs on the thread of create_webframe".into(),
+            ));
+        }
+    }
+    if thread.is_none() {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let handle = std::thread::Builder::new()
+            .name("pyframe-event-loop".into())
+            .spawn(move || {
+                IS_LOOP_THREAD.with(|flag| flag.set(true));
+                for job in queue {
+                    job();
+                }
+            })
+            .map_err(|e| LoopUnavailable(format!("Event loop thread not started: {}", e)))?;
+        *thread = Some((handle.thread().id(), jobs));
+    }
+    let Some((_, jobs)) = thread.as_ref() else {
+        unreachable!("started above");
+    };
+    jobs.send(job)
+        .map_err(|_| LoopUnavailable("The event loop thread was lost to a panic".into()))
+}
+
+#[cfg(target_os = "windows")]
+fn allow_any_thread(builder: &mut FrameEventLoopBuilder) {
+    use tao::platform::windows::EventLoopBuilderExtWindows;
+    builder.with_any_thread(true);
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn allow_any_thread(builder: &mut FrameEventLoopBuilder) {
+    use tao::platform::unix::EventLoopBuilderExtUnix;
+    builder.with_any_thread(true);
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn allow_any_thread(_builder: &mut FrameEventLoopBuilder) {}
@@ -0,0 +1,132 @@
+//! File operations behind the `fs_*` APIs. Every path goes through
+//! [`FsScope`] first; these run on the blocking pool.
+
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use serde::Serialize;
+
+use crate::fs_scope::FsScope;
+
+/// Text by default; with `binary`, file contents travel as base64 strings.
+fn encode(bytes: Vec<u8>, binary: bool) -> Result<String> {
+    if binary {
+        Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+    } else {
+        String::from_utf8(bytes).map_err(|_| anyhow!("File is not valid UTF-8, read it as binary"))
+    }
+}
+
+fn decode(contents: String, binary: bool) -> Result<Vec<u8>> {
+    if binary {
+        Ok(base64::engine::general_purpose::STANDARD.decode(contents)?)
+    } else {
+        Ok(contents.into_bytes())
+    }
+}
+
+pub fn read_file(scope: &FsScope, path: &str, binary: bool) -> Result<String> {
+    let path = scope.resolve(Path::new(path))?;
+    encode(fs::read(path)?, binary)
+}
+
+pub fn write_file(
+    scope: &FsScope,
+    path: &str,
+    contents: String,
+    binary: bool,
+    append: bool,
+) -> Result<bool> {
+    write_bytes(scope, path, &decode(contents, binary)?, append)
+}
+
+pub fn write_bytes(scope: &FsScope, path: &str, bytes: &[u8], append: bool) -> Result<bool> {
+    let path = scope.resolve(Path::new(path))?;
+    if append {
+        use std::io::Write;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(bytes)?;
+    } else {
+        fs::write(path, bytes)?;
+    }
+    Ok(true)
+}
+
+#[derive(Serialize)]
+pub struct DirEntry {
+    name: String,
+    path: String,
+    is_dir: bool,
+    is_file: bool,
+    is_symlink: bool,
+    size: u64,
+}
+
+/// Entries are listed as they are; following a symlink entry is checked
+/// against the scope when it is used.
+pub fn read_dir(scope: &FsScope, path: &str) -> Result<Vec<DirEntry>> {
+    let path = scope.resolve_listing(Path::new(path))?;
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        entries.push(DirEntry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path: entry.path().to_string_lossy().into_owned(),
+            is_dir: metadata.is_dir(),
+            is_file: metadata.is_file(),
+            is_symlink: metadata.is_symlink(),
+            size: metadata.len(),
+        });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+pub fn exists(scope: &FsScope, path: &str) -> Result<bool> {
+    Ok(scope.resolve_listing(Path::new(path))?.exists())
+}
+
+/// Removes a file, a symlink (not its target) or, with `recursive`, a
+/// directory and its contents, all of which must be in scope.
+pub fn remove(scope: &FsScope, path: &str, recursive: bool) -> Result<bool> {
+    let path = scope.resolve_entry(Path::new(path))?;
+    let metadata = path.symlink_metadata()?;
+    match (metadata.is_dir(), recursive) {
+        (true, true) => fs::remove_dir_all(path)?,
+        (true, false) => fs::remove_dir(path)?,
+        (false, _) => fs::remove_file(path)?,
+    }
+    Ok(true)
+}
+
+pub fn create_dir(scope: &FsScope, path: &str, recursive: bool) -> Result<bool> {
+    let path = scope.resolve_dir(Path::new(path))?;
+    if recursive {
+        fs::create_dir_all(path)?;
+    } else {
+        fs::create_dir(path)?;
+    }
+    Ok(true)
+}
+
+/// Copies a file. Both ends must be in scope.
+pub fn copy(scope: &FsScope, from: &str, to: &str) -> Result<bool> {
+    let from = scope.resolve(Path::new(from))?;
+    let to = scope.resolve(Path::new(to))?;
+    fs::copy(from, to)?;
+    Ok(true)
+}
+
+/// Moves a file, directory or symlink. Both ends must be in scope, a
+/// directory with all its contents.
+pub fn rename(scope: &FsScope, from: &str, to: &str) -> Result<bool> {
+    let from = scope.resolve_entry(Path::new(from))?;
+    let to = scope.resolve_entry(Path::new(to))?;
+    fs::rename(from, to)?;
+    Ok(true)
+}
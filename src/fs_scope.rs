@@ -0,0 +1,191 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use crate::api_manager::PermissionDenied;
+
+/// Canonicalizes `path` and checks that it lies inside one of `allowed`.
+///
+/// Symlinks are resolved first, so a link inside an allowed directory can't
+/// point outside it. An empty list allows nothing.
+pub fn resolve_allowed(allowed: &[PathBuf], path: &Path) -> Result<PathBuf> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| anyhow!("Cannot resolve {:?}: {}", path, e))?;
+    let permitted = allowed
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| canonical.starts_with(dir));
+    if !permitted {
+        bail!("{:?} is outside the allowed paths", canonical);
+    }
+    Ok(canonical)
+}
+
+/// Where the `fs_*` APIs may go, set by the `fs_scope` option.
+///
+/// A path is in scope if it lies inside one of `dirs` and, when `patterns`
+/// are given, its path relative to that directory matches one of them.
+/// Patterns only restrict files: a directory is in scope when everything
+/// in it is, and any directory inside `dirs` may be listed and watched.
+/// Patterns use `/` as separator; `*` and `?` stay within a path
+/// component, `**` matches any number of them. Empty `dirs` allow nothing.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FsScope {
+    pub dirs: Vec<PathBuf>,
+    pub patterns: Vec<String>,
+}
+
+impl FsScope {
+    /// Resolves `path`, following symlinks, so the file actually read or
+    /// written is the one checked. The path may not exist yet, but its
+    /// nearest existing ancestor must resolve into the scope. A directory
+    /// is checked with all its contents.
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        let resolved = Self::canonicalize(path)?;
+        let dir = resolved.is_dir();
+        self.check(path, resolved, dir, dir)
+    }
+
+    /// Like [`FsScope::resolve`], but a directory inside `dirs` is in scope
+    /// whatever it holds, for looking at it: listing, watching, `fs_exists`.
+    pub fn resolve_listing(&self, path: &Path) -> Result<PathBuf> {
+        let resolved = Self::canonicalize(path)?;
+        let dir = resolved.is_dir();
+        self.check(path, resolved, dir, false)
+    }
+
+    /// Like [`FsScope::resolve_listing`] for a directory that may not exist
+    /// yet.
+    pub fn resolve_dir(&self, path: &Path) -> Result<PathBuf> {
+        self.check(path, Self::canonicalize(path)?, true, false)
+    }
+
+    fn canonicalize(path: &Path) -> Result<PathBuf> {
+        let mut existing = path;
+        let mut rest = Vec::new();
+        let canonical = loop {
+            match existing.canonicalize() {
+                Ok(canonical) => break canonical,
+                Err(e) => {
+                    let (Some(parent), Some(name)) = (existing.parent(), existing.file_name())
+                    else {
+                        bail!("Cannot resolve {:?}: {}", path, e);
+                    };
+                    rest.push(name);
+                    existing = if parent.as_os_str().is_empty() {
+                        Path::new(".")
+                    } else {
+                        parent
+                    };
+                }
+            }
+        };
+        // `file_name` is `None` for `..`, so the rest can't climb back out.
+        Ok(rest
+            .iter()
+            .rev()
+            .fold(canonical, |path, name| path.join(name)))
+    }
+
+    /// Resolves `path` without following a symlink at its end, for removing
+    /// or renaming the link itself. A directory is checked with all its
+    /// contents.
+    pub fn resolve_entry(&self, path: &Path) -> Result<PathBuf> {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => {
+                let parent = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+                let resolved = self.resolve_dir(parent)?.join(name);
+                let dir = resolved.symlink_metadata().is_ok_and(|m| m.is_dir());
+                self.check(path, resolved, dir, dir)
+            }
+            _ => self.resolve(path),
+        }
+    }
+
+    /// With `contents`, every entry below the directory `resolved` must be
+    /// in scope too.
+    fn check(&self, path: &Path, resolved: PathBuf, dir: bool, contents: bool) -> Result<PathBuf> {
+        if !self.permits(&resolved, dir) {
+            return Err(denied(format!("{:?} is outside the fs scope", path)));
+        }
+        if contents && !self.patterns.is_empty() {
+            self.check_contents(path, &resolved)?;
+        }
+        Ok(resolved)
+    }
+
+    fn permits(&self, resolved: &Path, dir: bool) -> bool {
+        self.dirs
+            .iter()
+            .filter_map(|base| base.canonicalize().ok())
+            .any(|base| match resolved.strip_prefix(&base) {
+                Ok(relative) => {
+                    let relative = relative.to_string_lossy().replace('\\', "/");
+                    dir || self.patterns.is_empty()
+                        || self.patterns.iter().any(|p| glob_matches(p, &relative))
+                }
+                Err(_) => false,
+            })
+    }
+
+    /// Walks `dir` without following symlinks; a link is checked as the
+    /// entry itself.
+    fn check_contents(&self, path: &Path, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?.path();
+            let is_dir = entry.symlink_metadata()?.is_dir();
+            if !self.permits(&entry, is_dir) {
+                return Err(denied(format!(
+                    "{:?} holds {:?}, which is outside the fs scope",
+                    path, entry
+                )));
+            }
+            if is_dir {
+                self.check_contents(path, &entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn denied(message: String) -> anyhow::Error {
+    anyhow::Error::new(PermissionDenied {
+        permission: "fs_scope",
+    })
+    .context(message)
+}
+
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    match_components(&pattern, &path)
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(component, path)| {
+            match_component(first.as_bytes(), component.as_bytes()) && match_components(rest, path)
+        }),
+    }
+}
+
+fn match_component(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_component(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_component(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_component(rest, &name[1..]),
+    }
+}
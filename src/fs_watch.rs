@@ -0,0 +1,188 @@
+//! Watchers behind `fs_watch`. Changes are coalesced per path for
+//! [`DEBOUNCE`], then delivered as [`UserEvent::FsChanged`]; the event loop
+//! emits them to the frontend as `pyframe:fs-change` and `pyframe.events()`
+//! streams them to Python.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use serde::Serialize;
+use tokio::{runtime::Handle, sync::mpsc, task::AbortHandle};
+
+use crate::{
+    connections::ConnectionId,
+    fs_scope::FsScope,
+    utils::{FrameEventLoopProxy, UserEvent},
+};
+
+/// Quiet period after the last change before a batch is delivered.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+pub type WatcherId = u64;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FsChange {
+    pub kind: ChangeKind,
+    pub path: String,
+}
+
+struct Watch {
+    // Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+    debounce: AbortHandle,
+    connection: Option<ConnectionId>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.debounce.abort();
+    }
+}
+
+#[derive(Default)]
+pub struct FsWatchers {
+    last_id: WatcherId,
+    watches: HashMap<WatcherId, Watch>,
+}
+
+impl FsWatchers {
+    /// Watches `path`, which must be in `scope`. Changes to paths outside
+    /// the scope, e.g. files no pattern matches, are left out. The watcher
+    /// is dropped when `connection` disconnects.
+    pub fn watch(
+        &mut self,
+        scope: FsScope,
+        path: &str,
+        recursive: bool,
+        connection: Option<ConnectionId>,
+        rt: &Handle,
+        proxy: FrameEventLoopProxy,
+    ) -> Result<WatcherId> {
+        let path = scope.resolve_listing(Path::new(path))?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(&path, mode)?;
+
+        self.last_id += 1;
+        let id = self.last_id;
+        let debounce = crate::tasks::spawn_on("fs_watch", rt, debounce(id, rx, scope, proxy));
+        self.watches.insert(
+            id,
+            Watch {
+                _watcher: watcher,
+                debounce: debounce.abort_handle(),
+                connection,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn unwatch(&mut self, id: WatcherId) -> bool {
+        self.watches.remove(&id).is_some()
+    }
+
+    /// Whether changes of watcher `id` should still be delivered.
+    pub fn is_watching(&self, id: WatcherId) -> bool {
+        self.watches.contains_key(&id)
+    }
+
+    pub fn drop_connection(&mut self, connection: ConnectionId) {
+        self.watches
+            .retain(|_, watch| watch.connection != Some(connection));
+    }
+
+    pub fn clear_all(&mut self) {
+        self.watches.clear();
+    }
+}
+
+async fn debounce(
+    id: WatcherId,
+    mut rx: mpsc::UnboundedReceiver<notify::Result<notify::Event>>,
+    scope: FsScope,
+    proxy: FrameEventLoopProxy,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut pending = Vec::new();
+        collect(&mut pending, first);
+        loop {
+            match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                Ok(Some(event)) => collect(&mut pending, event),
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        let changes: Vec<_> = pending
+            .into_iter()
+            .filter(|(path, _)| scope.resolve_listing(path).is_ok())
+            .map(|(path, kind)| FsChange {
+                kind,
+                path: path.to_string_lossy().into_owned(),
+            })
+            .collect();
+        if !changes.is_empty() && proxy.send_event(UserEvent::FsChanged(id, changes)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Merges `event` into `pending`, keeping one change per path in the order
+/// paths were first seen.
+fn collect(pending: &mut Vec<(PathBuf, ChangeKind)>, event: notify::Result<notify::Event>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            crate::log_warn!(format!("File watcher error: {}", e));
+            return;
+        }
+    };
+    let kinds: Vec<ChangeKind> = match event.kind {
+        EventKind::Create(_) => vec![ChangeKind::Created],
+        EventKind::Remove(_) => vec![ChangeKind::Removed],
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => vec![ChangeKind::Removed],
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => vec![ChangeKind::Created],
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            vec![ChangeKind::Removed, ChangeKind::Created]
+        }
+        EventKind::Modify(_) | EventKind::Any => vec![ChangeKind::Modified],
+        EventKind::Access(_) | EventKind::Other => return,
+    };
+    for (index, path) in event.paths.into_iter().enumerate() {
+        let kind = kinds[index.min(kinds.len() - 1)];
+        match pending.iter().position(|(p, _)| *p == path) {
+            Some(at) => match (pending[at].1, kind) {
+                // Never seen by the frontend, so nothing to report.
+                (ChangeKind::Created, ChangeKind::Removed) => {
+                    pending.remove(at);
+                }
+                (ChangeKind::Created, _) => {}
+                (ChangeKind::Removed, ChangeKind::Created) => pending[at].1 = ChangeKind::Modified,
+                (_, kind) => pending[at].1 = kind,
+            },
+            None => pending.push((path, kind)),
+        }
+    }
+}
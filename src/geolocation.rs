@@ -0,0 +1,77 @@
+//! Positions for `navigator.geolocation` from `set_mock_location`, for
+//! kiosks at a fixed place or a GPS receiver read by Python.
+//!
+//! With the `mock_geolocation` option, `geolocation.js` replaces the page's
+//! `navigator.geolocation`. Access is asked for with an IPC message and
+//! decided by the `permissions.geolocation` policy like any WebView
+//! request, see [`crate::permissions`]. Each position is pushed to the
+//! pages as `geolocation-position` and sent again after a page load.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::permissions::{next_id, origin_of, PermissionKind, PermissionRequest, Policy};
+use crate::utils::UserEvent;
+
+/// Posted by `geolocation.js` on a page's first location call under `ask`.
+pub const PERMISSION_REQUEST: &str = "__geolocation_permission";
+
+/// As `GeolocationPosition` in the page.
+#[derive(Serialize, Clone, Debug)]
+pub struct Position {
+    pub coords: Coordinates,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// In meters.
+    pub accuracy: f64,
+    pub altitude: Option<f64>,
+    pub altitude_accuracy: Option<f64>,
+    /// Degrees clockwise from true north.
+    pub heading: Option<f64>,
+    /// Meters per second.
+    pub speed: Option<f64>,
+}
+
+impl Coordinates {
+    pub fn validate(&self) -> Result<()> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            bail!("Latitude {} is outside -90 to 90", self.latitude);
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            bail!("Longitude {} is outside -180 to 180", self.longitude);
+        }
+        if !self.accuracy.is_finite() || self.accuracy < 0.0 {
+            bail!("Accuracy must be a non-negative number of meters");
+        }
+        Ok(())
+    }
+}
+
+/// `geolocation.js` with `policy` filled in.
+pub fn init_script(policy: Policy) -> Result<String> {
+    Ok(crate::assets::_GEOLOCATION_SCRIPT.replace(
+        "__PYFRAME_GEOLOCATION_POLICY__",
+        &serde_json::to_string(&policy)?,
+    ))
+}
+
+/// The permission request for a [`PERMISSION_REQUEST`] message from the
+/// page at `url`.
+pub fn permission_event(body: &str, url: &str) -> Option<UserEvent> {
+    (body == PERMISSION_REQUEST).then(|| {
+        UserEvent::PermissionRequested(Box::new(PermissionRequest {
+            id: next_id(),
+            kind: PermissionKind::Geolocation,
+            origin: origin_of(url),
+            audio: false,
+            video: false,
+        }))
+    })
+}
@@ -0,0 +1,114 @@
+//! The TCP bridge without a window, for the benchmarks in `benches/`.
+//!
+//! Requests go through the same framing, in-flight bound, pending map
+//! and per-connection writer as in the app, but are answered by an echo
+//! dispatcher instead of the tao event loop.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+};
+
+use crate::{
+    api_manager::ApiRequest,
+    connections::{respond, serve, Dispatch, MAX_IN_FLIGHT_REQUESTS},
+    idempotency::ReplyCache,
+    lanes::RequestLanes,
+    protocol,
+    utils::PendingMap,
+};
+
+/// Answers every request with its own argument array.
+#[derive(Clone)]
+struct Echo {
+    pending: PendingMap,
+    lanes: Arc<RequestLanes>,
+}
+
+impl Dispatch for Echo {
+    fn dispatch(&self, req: ApiRequest) -> Result<(), String> {
+        self.lanes.taken();
+        let response = req.ok(req.args());
+        respond(&self.pending, req.0, response);
+        Ok(())
+    }
+}
+
+/// Starts an echo server on an ephemeral localhost port.
+///
+/// Must be called from within a tokio runtime; the server runs until the
+/// runtime shuts down.
+pub async fn spawn_echo_server() -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let pending = PendingMap::default();
+    let lanes = Arc::new(RequestLanes::default());
+    let echo = Echo {
+        pending: pending.clone(),
+        lanes: lanes.clone(),
+    };
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT_REQUESTS));
+    crate::tasks::spawn(
+        "tcp_server",
+        serve(
+            listener,
+            echo,
+            pending,
+            in_flight,
+            lanes,
+            Arc::new(ReplyCache::default()),
+        ),
+    );
+    Ok(addr)
+}
+
+/// Minimal client speaking the 4-byte length-prefixed JSON protocol.
+pub struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    /// Connects and exchanges the hello, without optional features.
+    pub async fn connect(addr: SocketAddr) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let mut client = Self { stream };
+        let hello = json!({
+            "type": "hello",
+            "version": protocol::VERSION,
+            "features": [],
+            "token": protocol::expected_token(),
+        });
+        client.write(&serde_json::to_vec(&hello)?).await?;
+        let ack: Value = serde_json::from_slice(&client.read().await?)?;
+        if ack["type"] != "hello-ack" {
+            return Err(std::io::Error::other(format!("Hello refused: {}", ack)));
+        }
+        Ok(client)
+    }
+
+    /// Sends `[id, method, args]` and returns the raw response payload.
+    pub async fn call(&mut self, id: u8, method: &str, args: &Value) -> std::io::Result<Vec<u8>> {
+        self.write(&serde_json::to_vec(&(id, method, args))?)
+            .await?;
+        self.read().await
+    }
+
+    async fn write(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        let mut msg = (payload.len() as u32).to_be_bytes().to_vec();
+        msg.extend_from_slice(payload);
+        self.stream.write_all(&msg).await
+    }
+
+    async fn read(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut len_buf = [0u8; 4];
+        self.stream.read_exact(&mut len_buf).await?;
+        let mut buf = vec![0u8; u32::from_be_bytes(len_buf) as usize];
+        self.stream.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+}
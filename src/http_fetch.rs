@@ -0,0 +1,337 @@
+//! `http_fetch`: HTTP requests made natively, so the page can call APIs that
+//! don't send CORS headers without a proxy in Python.
+//!
+//! Requests and every redirect must stay within `http.allowed_origins`.
+//! Bodies up to `max_response_bytes` come back in the response; larger ones
+//! need `stream`, which sends them as `http-chunk` events ending with
+//! `http-end`.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    api_manager::{Offline, PermissionDenied},
+    utils::{FrameEventLoopProxy, UserEvent},
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+/// Size of the `http-chunk` events of a streamed body.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+pub type StreamId = u64;
+
+static NEXT_STREAM: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Origins (`scheme://host[:port]`) `http_fetch` may reach. Empty denies all.
+    pub allowed_origins: Vec<String>,
+    /// Largest body returned in a response; 16 MiB if unset.
+    pub max_response_bytes: Option<u64>,
+    /// Skip TLS certificate validation, e.g. for a local development server.
+    /// Only an app option, so a page can't turn it on for its own requests.
+    pub accept_invalid_certs: bool,
+}
+
+impl HttpConfig {
+    fn allows(&self, url: &Url) -> bool {
+        let origin = url.origin().ascii_serialization();
+        self.allowed_origins.iter().any(|allowed| {
+            Url::parse(allowed)
+                .is_ok_and(|allowed| allowed.origin().ascii_serialization() == origin)
+        })
+    }
+
+    fn check(&self, url: &Url) -> Result<()> {
+        if self.allows(url) {
+            return Ok(());
+        }
+        Err(anyhow::Error::new(PermissionDenied {
+            permission: "http_origin",
+        })
+        .context(format!("{} is not in allowed_origins", url)))
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectPolicy {
+    /// Follow redirects within `allowed_origins`, up to `max_redirects`.
+    #[default]
+    Follow,
+    /// Return the 3xx response as it is.
+    Manual,
+    /// Fail on any redirect.
+    Error,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FetchRequest {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    /// `body` is base64 of the bytes to send.
+    #[serde(default)]
+    pub body_binary: bool,
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub redirect: RedirectPolicy,
+    pub max_redirects: Option<usize>,
+    /// Return the body as base64 instead of text.
+    #[serde(default)]
+    pub binary: bool,
+    /// Send the body as `http-chunk` events instead of in the response.
+    #[serde(default)]
+    pub stream: bool,
+}
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+#[derive(Serialize, Debug)]
+pub struct FetchResponse {
+    pub status: u16,
+    /// The final URL, after redirects.
+    pub url: String,
+    /// In the order received; repeated headers appear several times.
+    pub headers: Vec<(String, String)>,
+    /// Text, or base64 with `binary`. `None` when streamed.
+    pub body: Option<String>,
+    pub binary: bool,
+    /// Id of the `http-chunk` events carrying the body.
+    pub stream: Option<StreamId>,
+}
+
+/// A piece of a streamed body, sent as [`UserEvent::HttpBody`].
+#[derive(Debug)]
+pub enum BodyPart {
+    /// Base64 of the next bytes.
+    Chunk(String),
+    /// The body is complete, or failed with the message.
+    End(Option<String>),
+}
+
+/// Event name and payload of a streamed body part.
+pub fn describe(stream: StreamId, part: &BodyPart) -> (&'static str, serde_json::Value) {
+    match part {
+        BodyPart::Chunk(data) => (
+            "http-chunk",
+            serde_json::json!({ "stream": stream, "data": data }),
+        ),
+        BodyPart::End(error) => (
+            "http-end",
+            serde_json::json!({ "stream": stream, "error": error }),
+        ),
+    }
+}
+
+pub async fn fetch(
+    config: &HttpConfig,
+    request: FetchRequest,
+    proxy: FrameEventLoopProxy,
+) -> Result<FetchResponse> {
+    let url = Url::parse(&request.url)?;
+    config.check(&url)?;
+    let method = reqwest::Method::from_bytes(request.method.to_uppercase().as_bytes())?;
+    let client = reqwest::Client::builder()
+        .redirect(redirect_policy(config, &request))
+        .danger_accept_invalid_certs(config.accept_invalid_certs)
+        .build()?;
+    let mut builder = client.request(method, url.as_str()).timeout(
+        request
+            .timeout_ms
+            .map_or(DEFAULT_TIMEOUT, Duration::from_millis),
+    );
+    for (name, value) in &request.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    if let Some(body) = request.body {
+        builder = if request.body_binary {
+            builder.body(base64::engine::general_purpose::STANDARD.decode(body)?)
+        } else {
+            builder.body(body)
+        };
+    }
+    let response = builder
+        .send()
+        .await
+        .map_err(|e| http_error(url.as_str(), e))?;
+
+    let status = response.status().as_u16();
+    let final_url = response.url().to_string();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.as_str().to_string(), value)
+        })
+        .collect();
+    let mut fetched = FetchResponse {
+        status,
+        url: final_url,
+        headers,
+        body: None,
+        binary: request.binary,
+        stream: None,
+    };
+
+    if request.stream {
+        let id = NEXT_STREAM.fetch_add(1, Ordering::Relaxed);
+        fetched.stream = Some(id);
+        crate::tasks::spawn("http_stream", stream_body(id, response, proxy));
+        return Ok(fetched);
+    }
+
+    let limit = config
+        .max_response_bytes
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+    if response.content_length().is_some_and(|len| len > limit) {
+        return Err(too_large(limit));
+    }
+    let mut body = Vec::new();
+    let mut chunks = response.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| http_error(url.as_str(), e))?;
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(too_large(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    fetched.body = Some(if request.binary {
+        base64::engine::general_purpose::STANDARD.encode(&body)
+    } else {
+        String::from_utf8_lossy(&body).into_owned()
+    });
+    Ok(fetched)
+}
+
+fn too_large(limit: u64) -> anyhow::Error {
+    anyhow!(
+        "Response body is larger than max_response_bytes ({}), use stream",
+        limit
+    )
+}
+
+fn redirect_policy(config: &HttpConfig, request: &FetchRequest) -> reqwest::redirect::Policy {
+    match request.redirect {
+        RedirectPolicy::Manual => reqwest::redirect::Policy::none(),
+        RedirectPolicy::Error => reqwest::redirect::Policy::custom(|attempt| {
+            let message = format!("Redirected to {}", attempt.url());
+            attempt.error(message)
+        }),
+        RedirectPolicy::Follow => {
+            let config = Arc::new(config.clone());
+            let max = request.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS);
+            reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > max {
+                    let message = format!("More than {} redirects", max);
+                    attempt.error(message)
+                } else if !config.allows(attempt.url()) {
+                    attempt.error(PermissionDenied {
+                        permission: "http_origin",
+                    })
+                } else {
+                    attempt.follow()
+                }
+            })
+        }
+    }
+}
+
+/// Sends the body in [`CHUNK_SIZE`] pieces until it ends or the event loop
+/// is gone.
+async fn stream_body(id: StreamId, response: reqwest::Response, proxy: FrameEventLoopProxy) {
+    let mut chunks = response.bytes_stream();
+    let mut pending = Vec::new();
+    let send = |part| proxy.send_event(UserEvent::HttpBody(id, part)).is_ok();
+    let error = loop {
+        match chunks.next().await {
+            Some(Ok(chunk)) => {
+                pending.extend_from_slice(&chunk);
+                if pending.len() < CHUNK_SIZE {
+                    continue;
+                }
+            }
+            Some(Err(e)) => break Some(e.to_string()),
+            None => break None,
+        }
+        let data = base64::engine::general_purpose::STANDARD.encode(&pending);
+        pending.clear();
+        if !send(BodyPart::Chunk(data)) {
+            return;
+        }
+    };
+    if !pending.is_empty() {
+        let data = base64::engine::general_purpose::STANDARD.encode(&pending);
+        if !send(BodyPart::Chunk(data)) {
+            return;
+        }
+    }
+    send(BodyPart::End(error));
+}
+
+/// Connection failures and timeouts become [`Offline`], redirects out of
+/// `allowed_origins` [`PermissionDenied`].
+pub fn http_error(url: &str, err: reqwest::Error) -> anyhow::Error {
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        if cause.is::<PermissionDenied>() {
+            return anyhow::Error::new(PermissionDenied {
+                permission: "http_origin",
+            })
+            .context(format!("{} redirected out of allowed_origins", url));
+        }
+        source = cause.source();
+    }
+    if err.is_connect() || err.is_timeout() {
+        anyhow::Error::new(Offline {
+            url: url.to_string(),
+        })
+        .context(err.to_string())
+    } else {
+        err.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_app_accepts_invalid_certs() {
+        let config: HttpConfig = serde_json::from_value(serde_json::json!({
+            "allowed_origins": ["https://localhost:8443"],
+            "accept_invalid_certs": true,
+        }))
+        .unwrap();
+        assert!(config.accept_invalid_certs);
+        assert!(!HttpConfig::default().accept_invalid_certs);
+
+        let request = serde_json::json!({
+            "url": "https://localhost:8443/",
+            "accept_invalid_certs": true,
+        });
+        assert!(serde_json::from_value::<FetchRequest>(request).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Canned responses for `fetch` calls, for integration tests that load web
+//! content without a server. Enabled by `enable_mocking`.
+//!
+//! wry can't intercept `http(s)` requests on every platform, so an
+//! initialization script routes each `fetch` through the `pyframemock`
+//! custom protocol first. A request no mock matches is answered with
+//! [`MISS_HEADER`] and goes to the network as usual. Navigations and
+//! `XMLHttpRequest` are not mocked.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use wry::http::{Request, Response};
+
+pub const MOCK_SCHEME: &str = "pyframemock";
+/// Set on the protocol response when no mock matched.
+const MISS_HEADER: &str = "x-pyframe-mock";
+
+/// Windows and Android expose custom protocols as `http://<scheme>.<host>`.
+#[cfg(any(target_os = "windows", target_os = "android"))]
+const MOCK_BASE: &str = "http://pyframemock.localhost/";
+#[cfg(not(any(target_os = "windows", target_os = "android")))]
+const MOCK_BASE: &str = "pyframemock://localhost/";
+
+pub type MockId = u64;
+
+struct Mock {
+    pattern: Regex,
+    status: u16,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct MockRegistry {
+    last_id: MockId,
+    mocks: Vec<(MockId, Mock)>,
+}
+
+impl MockRegistry {
+    /// Answers requests whose absolute URL matches the regex `url_pattern`.
+    /// The most recently added matching mock wins.
+    pub fn add(
+        &mut self,
+        url_pattern: &str,
+        status: u16,
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    ) -> Result<MockId> {
+        let pattern = Regex::new(url_pattern)
+            .map_err(|e| anyhow!("Invalid url_pattern {:?}: {}", url_pattern, e))?;
+        if !(100..=599).contains(&status) {
+            return Err(anyhow!("Invalid HTTP status {}", status));
+        }
+        self.last_id += 1;
+        self.mocks.push((
+            self.last_id,
+            Mock {
+                pattern,
+                status,
+                headers,
+                body,
+            },
+        ));
+        Ok(self.last_id)
+    }
+
+    pub fn clear(&mut self, id: MockId) -> bool {
+        let before = self.mocks.len();
+        self.mocks.retain(|(mock_id, _)| *mock_id != id);
+        self.mocks.len() != before
+    }
+
+    pub fn clear_all(&mut self) {
+        self.mocks.clear();
+    }
+
+    /// Builds the protocol response for a request to [`MOCK_BASE`]`?url=<url>`.
+    pub fn respond(&self, request: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+        let target = url::Url::parse(&request.uri().to_string())
+            .ok()
+            .and_then(|u| {
+                u.query_pairs()
+                    .find(|(key, _)| key == "url")
+                    .map(|(_, value)| value.into_owned())
+            })
+            .unwrap_or_default();
+        let mock = self
+            .mocks
+            .iter()
+            .rev()
+            .map(|(_, mock)| mock)
+            .find(|mock| mock.pattern.is_match(&target));
+
+        let builder = Response::builder()
+            .header("Access-Control-Allow-Origin", "*")
+            .header("Access-Control-Expose-Headers", "*");
+        let response = match mock {
+            Some(mock) => mock
+                .headers
+                .iter()
+                .fold(builder.status(mock.status), |builder, (name, value)| {
+                    builder.header(name, value)
+                })
+                .body(Cow::Owned(mock.body.clone())),
+            None => builder
+                .status(404)
+                .header(MISS_HEADER, "miss")
+                .body(Cow::Borrowed(&[][..])),
+        };
+        response.unwrap_or_else(|e| {
+            crate::log_err!(format!("Invalid mock response for {}: {}", target, e));
+            Response::builder()
+                .status(500)
+                .header(MISS_HEADER, "miss")
+                .body(Cow::Borrowed(&[][..]))
+                .unwrap()
+        })
+    }
+}
+
+/// Patches `window.fetch` to ask the mock protocol before the network.
+pub fn init_script() -> String {
+    format!(
+        r#"(function () {{
+  const base = {base};
+  const realFetch = window.fetch.bind(window);
+  window.fetch = async function (input, init) {{
+    const url = input instanceof Request ? input.url : new URL(input, location.href).href;
+    const mocked = await realFetch(base + "?url=" + encodeURIComponent(url));
+    if (mocked.headers.get("{miss}") === "miss") {{
+      return realFetch(input, init);
+    }}
+    return mocked;
+  }};
+}})();"#,
+        base = serde_json::to_string(MOCK_BASE).unwrap(),
+        miss = MISS_HEADER,
+    )
+}
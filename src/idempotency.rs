@@ -0,0 +1,409 @@
+//! Replies to retried requests that carry an `idempotency_key`.
+//!
+//! A client that lost its connection can't know whether a request ran, so
+//! it sends it again with the same key. The first request with a key runs
+//! the handler; a retry that arrives while it runs waits for its response,
+//! one that arrives later gets the cached response with its own id. Keys
+//! are scoped to the client named in the hello, so two clients can't see
+//! each other's replies.
+//!
+//! Entries expire after `idempotency.ttl_ms` and the oldest finished ones
+//! are evicted beyond `idempotency.capacity`. `BUSY` and dropped responses
+//! aren't cached, since the handler didn't run. Handlers declared with
+//! `#[api(replay = false)]` always run.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::sync::oneshot;
+
+use crate::{
+    api_manager::{code, ApiRequest, ApiResponse},
+    lock_force,
+};
+
+const DEFAULT_CAPACITY: usize = 256;
+const DEFAULT_TTL_MS: u64 = 60_000;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    /// Most keys remembered across all clients; 256 if unset.
+    pub capacity: Option<usize>,
+    /// How long a response is replayed; 60 s if unset.
+    pub ttl_ms: Option<u64>,
+}
+
+/// The client named in the hello and the request's key.
+type Key = (String, String);
+
+enum Entry {
+    /// The handler is running; retries wait here with their ids.
+    Running {
+        method: String,
+        since: Instant,
+        waiters: Vec<(u8, oneshot::Sender<ApiResponse>)>,
+    },
+    Done {
+        method: String,
+        response: ApiResponse,
+        at: Instant,
+    },
+}
+
+impl Entry {
+    fn method(&self) -> &str {
+        match self {
+            Entry::Running { method, .. } | Entry::Done { method, .. } => method,
+        }
+    }
+
+    fn since(&self) -> Instant {
+        match self {
+            Entry::Running { since, .. } => *since,
+            Entry::Done { at, .. } => *at,
+        }
+    }
+}
+
+/// What to do with a request, from [`ReplyCache::begin`].
+pub enum Lookup {
+    /// Run the handler, then pass the response to [`Recording::finish`].
+    Run(Option<Recording>),
+    /// A retry of a request still running.
+    Wait(oneshot::Receiver<ApiResponse>),
+    Replay(ApiResponse),
+    /// The key was used for another method.
+    Collision(ApiResponse),
+}
+
+/// A request whose response is to be cached under its key.
+pub struct Recording {
+    cache: Arc<ReplyCache>,
+    key: Key,
+}
+
+impl Recording {
+    /// Caches `response` and hands it to retries waiting for it.
+    pub fn finish(self, response: &ApiResponse) {
+        let cacheable = response.code() != code::BUSY;
+        self.cache.complete(self.key, response, cacheable);
+    }
+
+    /// Forgets the key; the request never got a response.
+    pub fn abandon(self, response: &ApiResponse) {
+        self.cache.complete(self.key, response, false);
+    }
+}
+
+pub struct ReplyCache {
+    capacity: usize,
+    ttl: Duration,
+    /// Methods registered with `#[api(replay = false)]`.
+    no_replay: HashSet<String>,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl Default for ReplyCache {
+    fn default() -> Self {
+        Self::new(&IdempotencyConfig::default(), HashSet::new())
+    }
+}
+
+impl ReplyCache {
+    pub fn new(config: &IdempotencyConfig, no_replay: HashSet<String>) -> Self {
+        Self {
+            capacity: config.capacity.unwrap_or(DEFAULT_CAPACITY),
+            ttl: Duration::from_millis(config.ttl_ms.unwrap_or(DEFAULT_TTL_MS)),
+            no_replay,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Looks up `req`'s key for `client`, claiming it if it is new.
+    pub fn begin(self: &Arc<Self>, client: &str, req: &ApiRequest) -> Lookup {
+        let Some(key) = req.metadata().idempotency_key.clone() else {
+            return Lookup::Run(None);
+        };
+        if self.capacity == 0 || self.no_replay.contains(&req.1) {
+            return Lookup::Run(None);
+        }
+        let key = (client.to_string(), key);
+        let mut entries = lock_force!(self.entries);
+        entries.retain(|_, entry| match entry {
+            Entry::Running { .. } => true,
+            Entry::Done { at, .. } => at.elapsed() < self.ttl,
+        });
+
+        if let Some(entry) = entries.get_mut(&key) {
+            if entry.method() != req.1 {
+                return Lookup::Collision(req.err(
+                    code::ERROR,
+                    format!(
+                        "Idempotency key {:?} was already used for {}",
+                        key.1,
+                        entry.method()
+                    ),
+                ));
+            }
+            return match entry {
+                Entry::Running { waiters, .. } => {
+                    let (tx, rx) = oneshot::channel();
+                    waiters.push((req.0, tx));
+                    Lookup::Wait(rx)
+                }
+                Entry::Done { response, .. } => Lookup::Replay(with_id(response, req.0)),
+            };
+        }
+
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .filter(|(_, entry)| matches!(entry, Entry::Done { .. }))
+                .min_by_key(|(_, entry)| entry.since())
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => {
+                    entries.remove(&oldest);
+                }
+                // Every slot is taken by a running request.
+                None => return Lookup::Run(None),
+            }
+        }
+        entries.insert(
+            key.clone(),
+            Entry::Running {
+                method: req.1.clone(),
+                since: Instant::now(),
+                waiters: Vec::new(),
+            },
+        );
+        Lookup::Run(Some(Recording {
+            cache: self.clone(),
+            key,
+        }))
+    }
+
+    fn complete(&self, key: Key, response: &ApiResponse, cacheable: bool) {
+        let mut entries = lock_force!(self.entries);
+        let Some(Entry::Running {
+            method, waiters, ..
+        }) = entries.remove(&key)
+        else {
+            return;
+        };
+        for (id, waiter) in waiters {
+            let _ = waiter.send(with_id(response, id));
+        }
+        if cacheable {
+            entries.insert(
+                key,
+                Entry::Done {
+                    method,
+                    response: response.clone(),
+                    at: Instant::now(),
+                },
+            );
+        }
+    }
+}
+
+/// `response` as the answer to request `id`.
+fn with_id(response: &ApiResponse, id: u8) -> ApiResponse {
+    let mut response = response.clone();
+    response.0 = id;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::api_manager::{ApiArguments, RequestMetadata};
+
+    use super::*;
+
+    fn charge(id: u8, key: &str) -> ApiRequest {
+        ApiRequest(
+            id,
+            "charge".to_string(),
+            ApiArguments::new(json!([])),
+            RequestMetadata {
+                idempotency_key: Some(key.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn data(response: &ApiResponse) -> Value {
+        serde_json::to_value(response).unwrap()[3].clone()
+    }
+
+    /// Runs `req` as the first request with its key, answering `data`.
+    fn run(cache: &Arc<ReplyCache>, client: &str, req: &ApiRequest, data: Value) {
+        let Lookup::Run(Some(recording)) = cache.begin(client, req) else {
+            panic!("request {} of {} did not run", req.0, client);
+        };
+        recording.finish(&req.ok(data));
+    }
+
+    #[test]
+    fn retry_gets_the_first_response() {
+        let cache = Arc::new(ReplyCache::default());
+        run(&cache, "shop", &charge(1, "order-1"), json!("charged"));
+
+        // E.g. sent again on a new connection.
+        let Lookup::Replay(response) = cache.begin("shop", &charge(2, "order-1")) else {
+            panic!("the retry ran again");
+        };
+        assert_eq!((response.0, data(&response)), (2, json!("charged")));
+        assert!(matches!(
+            cache.begin("shop", &charge(3, "order-2")),
+            Lookup::Run(Some(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn retry_while_running_waits_for_the_first() {
+        let cache = Arc::new(ReplyCache::default());
+        let first = charge(1, "order-1");
+        let Lookup::Run(Some(recording)) = cache.begin("shop", &first) else {
+            panic!("the first request did not run");
+        };
+        let Lookup::Wait(retry) = cache.begin("shop", &charge(2, "order-1")) else {
+            panic!("the retry did not wait");
+        };
+        recording.finish(&first.ok("charged"));
+        let response = retry.await.unwrap();
+        assert_eq!((response.0, data(&response)), (2, json!("charged")));
+    }
+
+    #[test]
+    fn keys_are_scoped_to_the_client() {
+        let cache = Arc::new(ReplyCache::default());
+        run(&cache, "shop", &charge(1, "order-1"), json!("shop"));
+        run(&cache, "other", &charge(2, "order-1"), json!("other"));
+        let Lookup::Replay(response) = cache.begin("shop", &charge(3, "order-1")) else {
+            panic!("the retry ran again");
+        };
+        assert_eq!(data(&response), json!("shop"));
+    }
+
+    #[test]
+    fn busy_responses_are_not_cached() {
+        let cache = Arc::new(ReplyCache::default());
+        let req = charge(1, "order-1");
+        let Lookup::Run(Some(recording)) = cache.begin("shop", &req) else {
+            panic!("the first request did not run");
+        };
+        recording.finish(&req.err(code::BUSY, "Too many requests"));
+        assert!(matches!(
+            cache.begin("shop", &charge(2, "order-1")),
+            Lookup::Run(Some(_))
+        ));
+    }
+
+    /// The same over the TCP bridge, with a new connection for a retry.
+    #[cfg(feature = "tcp")]
+    mod bridge {
+        use std::{
+            sync::atomic::{AtomicU64, Ordering},
+            time::Duration,
+        };
+
+        use crate::{
+            protocol,
+            testing::{FakeApp, TestClient},
+        };
+
+        use super::*;
+
+        const WAIT: Duration = Duration::from_secs(5);
+
+        /// An app whose `charge` answers how many times it ran.
+        fn app() -> FakeApp {
+            let app = FakeApp::new();
+            let runs = Arc::new(AtomicU64::new(0));
+            app.handle("charge", move |_| {
+                Ok(json!(runs.fetch_add(1, Ordering::SeqCst) + 1))
+            });
+            app
+        }
+
+        async fn connect(app: &FakeApp, client: &str) -> TestClient {
+            app.connect_with(json!({
+                "type": "hello",
+                "version": protocol::VERSION,
+                "features": [],
+                "token": protocol::expected_token(),
+                "client": client,
+            }))
+            .await
+            .unwrap()
+        }
+
+        async fn charge_over(client: &mut TestClient, id: u8, key: &str) -> Value {
+            let response = client
+                .request(json!([id, "charge", [], { "idempotency_key": key }]), WAIT)
+                .await
+                .unwrap();
+            assert_eq!(response[0], id);
+            assert_eq!(response[1], code::OK, "{}", response);
+            response[3].clone()
+        }
+
+        #[tokio::test]
+        async fn retry_after_reconnecting_gets_the_first_response() {
+            let app = app();
+            let mut client = connect(&app, "shop").await;
+            assert_eq!(charge_over(&mut client, 1, "order-1").await, 1);
+            client.close().await.unwrap();
+
+            let mut client = connect(&app, "shop").await;
+            assert_eq!(charge_over(&mut client, 2, "order-1").await, 1);
+            assert_eq!(charge_over(&mut client, 3, "order-2").await, 2);
+            assert_eq!(app.calls().len(), 2);
+            client.close().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn retry_on_another_connection_waits_for_the_first() {
+            let app = app();
+            app.hold("charge");
+            let mut first = connect(&app, "shop").await;
+            first
+                .send(&json!([1, "charge", [], { "idempotency_key": "order-1" }]))
+                .await
+                .unwrap();
+            let mut retry = connect(&app, "shop").await;
+            let response = tokio::spawn(async move {
+                let data = charge_over(&mut retry, 2, "order-1").await;
+                (data, retry)
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(app.release(json!("charged")), 1);
+            let (data, retry) = tokio::time::timeout(WAIT, response).await.unwrap().unwrap();
+            assert_eq!(data, "charged");
+            assert_eq!(app.calls().len(), 1);
+            retry.close().await.unwrap();
+            first.close().await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn clients_over_the_bridge_have_their_own_keys() {
+            let app = app();
+            let mut shop = connect(&app, "shop").await;
+            let mut other = connect(&app, "other").await;
+            assert_eq!(charge_over(&mut shop, 1, "order-1").await, 1);
+            assert_eq!(charge_over(&mut other, 2, "order-1").await, 2);
+            assert_eq!(charge_over(&mut shop, 3, "order-1").await, 1);
+            assert_eq!(charge_over(&mut other, 4, "order-1").await, 2);
+            shop.close().await.unwrap();
+            other.close().await.unwrap();
+        }
+    }
+}
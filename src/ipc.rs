@@ -0,0 +1,248 @@
+//! Messages the injected scripts post through `window.ipc` for things that
+//! must not wait for a round trip through the TCP bridge.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{transport::AppOrigin, utils::UserEvent};
+
+/// Posted on mousedown in a `data-pyframe-drag-region`.
+pub const DRAG_WINDOW: &str = "__drag_window";
+/// Posted on double-click in a `data-pyframe-drag-region`.
+pub const TOGGLE_MAXIMIZE: &str = "__toggle_maximize";
+
+/// Prefix of `pyframe.invoke("__shell_open", target)`, followed by a
+/// [`ShellOpen`] as JSON. Answered with a `shell-open` event.
+pub const SHELL_OPEN: &str = "__shell_open:";
+
+/// Prefix of `pyframe.updater` calls, followed by an [`UpdaterCall`] as
+/// JSON. Answered with an `updater` event.
+pub const UPDATER: &str = "__updater:";
+
+/// Prefix of `pyframe.http.fetch` calls, followed by a [`FetchCall`] as
+/// JSON. Answered with an `http-fetch` event.
+pub const HTTP_FETCH: &str = "__http_fetch:";
+
+/// Prefix of `pyframe.startDrag` calls, followed by a [`StartDrag`] as
+/// JSON. Answered with a `start-drag` event.
+pub const START_DRAG: &str = "__start_drag:";
+
+/// Posted by `websocket.js` for the WebSocket server and the launch token,
+/// followed by an object with the request's `id`. Answered with a `bridge`
+/// event.
+pub const BRIDGE: &str = "__bridge:";
+
+/// Prefixes of the messages that reach native APIs, which only the app's
+/// own pages may post; see [`AppOrigin`].
+const PRIVILEGED: &[&str] = &[
+    crate::transport::INVOKE,
+    SHELL_OPEN,
+    UPDATER,
+    HTTP_FETCH,
+    START_DRAG,
+    WS,
+    crate::state_store::STATE_STORE,
+    BRIDGE,
+];
+
+/// The prefix of `body` if it is a privileged message and the page at
+/// `uri` isn't the app's, so the message must be dropped.
+pub fn refused(body: &str, uri: &str, app_origin: &AppOrigin) -> Option<&'static str> {
+    let prefix = PRIVILEGED
+        .iter()
+        .copied()
+        .find(|prefix| body.starts_with(prefix))?;
+    (!app_origin.allows(uri)).then_some(prefix)
+}
+
+#[derive(Deserialize)]
+struct BridgeRequest {
+    id: u64,
+}
+
+/// The id of a [`BRIDGE`] request. Answered in the window that posted it,
+/// so it isn't an [`event_for`] event.
+pub fn bridge_request(body: &str) -> Option<u64> {
+    let request = body.strip_prefix(BRIDGE)?;
+    serde_json::from_str::<BridgeRequest>(request)
+        .ok()
+        .map(|request| request.id)
+}
+
+#[derive(Deserialize)]
+struct StartDrag {
+    id: u64,
+    items: crate::drag_out::DragItems,
+}
+
+#[derive(Deserialize)]
+struct FetchCall {
+    id: u64,
+    request: crate::http_fetch::FetchRequest,
+}
+
+/// Prefix of `pyframe.ws` calls, followed by an object with the call's
+/// `id` and a [`WsOp`](crate::ws_client::WsOp). Answered with a `ws` event.
+pub const WS: &str = "__ws:";
+
+#[derive(Deserialize)]
+struct WsCall {
+    id: u64,
+    #[serde(flatten)]
+    op: crate::ws_client::WsOp,
+}
+
+#[derive(Deserialize)]
+struct UpdaterCall {
+    id: u64,
+    op: crate::updater::UpdaterOp,
+}
+
+#[derive(Deserialize)]
+struct ShellOpen {
+    id: u64,
+    target: String,
+}
+
+/// The event an IPC message is handled as, if it is one of ours.
+pub fn event_for(body: &str) -> Option<UserEvent> {
+    if let Some(request) = body.strip_prefix(SHELL_OPEN) {
+        let request: ShellOpen = serde_json::from_str(request).ok()?;
+        return Some(UserEvent::ShellOpen(request.id, request.target));
+    }
+    if let Some(call) = body.strip_prefix(HTTP_FETCH) {
+        let call: FetchCall = serde_json::from_str(call).ok()?;
+        return Some(UserEvent::HttpFetch(call.id, Box::new(call.request)));
+    }
+    if let Some(call) = body.strip_prefix(START_DRAG) {
+        let call: StartDrag = serde_json::from_str(call).ok()?;
+        return Some(UserEvent::StartDrag(call.id, call.items));
+    }
+    if let Some(call) = body.strip_prefix(WS) {
+        let call: WsCall = serde_json::from_str(call).ok()?;
+        return Some(UserEvent::WsCall(call.id, call.op));
+    }
+    if let Some(call) = body.strip_prefix(UPDATER) {
+        let call: UpdaterCall = serde_json::from_str(call).ok()?;
+        return Some(UserEvent::Updater(call.id, call.op));
+    }
+    if let Some(event) = body.strip_prefix(crate::navigation::NAVIGATION) {
+        return crate::navigation::NavigationEvent::from_page(event);
+    }
+    if let Some(call) = body.strip_prefix(crate::transport::INVOKE) {
+        return serde_json::from_str(call)
+            .ok()
+            .map(|call| UserEvent::Invoke(Box::new(call)));
+    }
+    if let Some(call) = body.strip_prefix(crate::accelerators::ACCELERATOR) {
+        let pressed: crate::accelerators::Pressed = serde_json::from_str(call).ok()?;
+        return Some(UserEvent::Accelerator(pressed.id));
+    }
+    if body == crate::titlebar_overlay::SYSTEM_MENU {
+        return Some(UserEvent::SystemMenu);
+    }
+    if body == crate::load_error::RETRY_LOAD {
+        return Some(UserEvent::RetryLoad);
+    }
+    if let Some(request) = body.strip_prefix(crate::context_menu::CONTEXT_MENU) {
+        return serde_json::from_str(request)
+            .ok()
+            .map(|request| UserEvent::ContextMenu(Box::new(request)));
+    }
+    if let Some(change) = body.strip_prefix(crate::element_fullscreen::ELEMENT_FULLSCREEN) {
+        return serde_json::from_str(change)
+            .ok()
+            .map(UserEvent::ElementFullscreen);
+    }
+    if let Some(call) = body.strip_prefix(crate::state_store::STATE_STORE) {
+        return serde_json::from_str(call).ok().map(UserEvent::StateStore);
+    }
+    match body {
+        crate::splash::FRAME_READY => Some(UserEvent::FrameReady),
+        DRAG_WINDOW => Some(UserEvent::DragWindow),
+        TOGGLE_MAXIMIZE => Some(UserEvent::ToggleMaximize),
+        _ => None,
+    }
+}
+
+/// Payload answering the page's call `id`, as `nativeCall` in `invoke.js`
+/// expects it.
+pub fn reply<T: Serialize>(id: u64, result: Result<T>) -> Value {
+    match result {
+        Ok(result) => json!({ "id": id, "result": result }),
+        Err(err) => json!({
+            "id": id,
+            "error": { "code": crate::api_manager::code::of(&err), "message": err.to_string() },
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_origin() -> AppOrigin {
+        AppOrigin::new(Some("https://app.example/index.html"), ["app"])
+    }
+
+    #[test]
+    fn privileged_messages_need_the_app_origin() {
+        let app_origin = app_origin();
+        for prefix in PRIVILEGED {
+            let body = format!("{}{{\"id\":1}}", prefix);
+            for uri in [
+                "https://evil.example/",
+                "https://app.example.evil.example/",
+                "http://app.example/",
+                "file:///home/user/page.html",
+            ] {
+                assert_eq!(
+                    refused(&body, uri, &app_origin),
+                    Some(*prefix),
+                    "{} from {}",
+                    prefix,
+                    uri
+                );
+            }
+            for uri in [
+                "https://app.example/settings",
+                "app://localhost/index.html",
+                "http://app.localhost/index.html",
+            ] {
+                assert_eq!(
+                    refused(&body, uri, &app_origin),
+                    None,
+                    "{} from {}",
+                    prefix,
+                    uri
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn other_messages_are_not_refused() {
+        let app_origin = app_origin();
+        for body in [DRAG_WINDOW, TOGGLE_MAXIMIZE, crate::splash::FRAME_READY] {
+            assert_eq!(refused(body, "https://evil.example/", &app_origin), None);
+        }
+    }
+
+    #[test]
+    fn bridge_requests_carry_their_id() {
+        assert_eq!(bridge_request(r#"__bridge:{"id":7}"#), Some(7));
+        assert_eq!(bridge_request(r#"__bridge:{}"#), None);
+        assert_eq!(bridge_request(r#"__shell_open:{"id":7}"#), None);
+    }
+
+    #[test]
+    fn inline_html_is_the_app() {
+        let app_origin = AppOrigin::new(None, []);
+        assert_eq!(refused(SHELL_OPEN, "about:blank", &app_origin), None);
+        assert_eq!(
+            refused(SHELL_OPEN, "https://evil.example/", &app_origin),
+            Some(SHELL_OPEN)
+        );
+    }
+}
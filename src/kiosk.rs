@@ -0,0 +1,558 @@
+//! Kiosk mode for unattended deployments, enabled by the `kiosk` option.
+//!
+//! `kiosk: true` turns every component on. An object overrides single
+//! components, e.g. `{"always_on_top": false}`. Kiosk mode ends with the
+//! exit accelerator or `exit_kiosk(token)`, both of which put the window
+//! back the way it was before kiosk mode.
+//!
+//! The exit accelerator is caught natively, before the WebView sees the
+//! key, so a page can neither trigger it nor keep it from working.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer};
+use tao::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    window::{Fullscreen, Window},
+};
+use url::Url;
+
+use crate::{
+    accelerators::Combo, api_manager::PermissionDenied, placement::MonitorSelector,
+    utils::FrameEventLoopProxy,
+};
+
+const DEFAULT_EXIT_ACCELERATOR: &str = "Ctrl+Alt+K";
+
+/// Components of kiosk mode. Each defaults to its kiosk setting.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct KioskOptions {
+    /// Monitor to go fullscreen on; the `monitor` option if unset.
+    pub monitor: Option<MonitorSelector>,
+    /// Borderless fullscreen.
+    pub fullscreen: bool,
+    pub always_on_top: bool,
+    pub decorations: bool,
+    pub devtools: bool,
+    pub context_menu: bool,
+    /// Let `window.open` and `target="_blank"` links open windows.
+    pub new_windows: bool,
+    /// URLs the page may navigate to, besides the initial page: a URL is
+    /// allowed if it has an entry's origin and its path is the entry's path
+    /// or lies below it, so `/app` covers `/app/settings` but not
+    /// `/application`. Empty allows none.
+    pub allowed_urls: Vec<String>,
+    /// Keys that leave kiosk mode, e.g. `"Ctrl+Shift+Q"`. `None` disables them.
+    pub exit_accelerator: Option<String>,
+    /// Token `exit_kiosk` must be called with. Without one, kiosk mode can't
+    /// be left through the API.
+    pub exit_token: Option<String>,
+}
+
+impl Default for KioskOptions {
+    fn default() -> Self {
+        Self {
+            monitor: None,
+            fullscreen: true,
+            always_on_top: true,
+            decorations: false,
+            devtools: false,
+            context_menu: false,
+            new_windows: false,
+            allowed_urls: Vec::new(),
+            exit_accelerator: Some(DEFAULT_EXIT_ACCELERATOR.into()),
+            exit_token: None,
+        }
+    }
+}
+
+/// Reads `kiosk` as either `true`/`false` or a [`KioskOptions`] object.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<KioskOptions>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Setting {
+        Enabled(bool),
+        Options(KioskOptions),
+    }
+    Ok(match Option::<Setting>::deserialize(deserializer)? {
+        Some(Setting::Enabled(true)) => Some(KioskOptions::default()),
+        Some(Setting::Enabled(false)) | None => None,
+        Some(Setting::Options(options)) => Some(options),
+    })
+}
+
+/// Whether `path` is `prefix` or lies below it: `/app` covers `/app/settings`
+/// but not `/application`.
+fn within(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// What the WebView handlers check while kiosk mode is on. Cloned into the
+/// handlers before the WebView exists.
+#[derive(Clone)]
+pub struct KioskGuard {
+    active: Arc<AtomicBool>,
+    new_windows: bool,
+    allowed_urls: Vec<Url>,
+}
+
+impl KioskGuard {
+    pub fn new(options: &KioskOptions, initial_url: Option<&str>) -> Self {
+        let allowed_urls = options
+            .allowed_urls
+            .iter()
+            .map(String::as_str)
+            .chain(initial_url)
+            .filter_map(|entry| match Url::parse(entry) {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    crate::log_warn!(format!("Kiosk: ignoring allowed URL {:?}: {}", entry, e));
+                    None
+                }
+            })
+            .collect();
+        Self {
+            active: Arc::new(AtomicBool::new(true)),
+            new_windows: options.new_windows,
+            allowed_urls,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn allows_navigation(&self, url: &str) -> bool {
+        if !self.is_active() || url == "about:blank" {
+            return true;
+        }
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+        self.allowed_urls
+            .iter()
+            .any(|entry| entry.origin() == url.origin() && within(url.path(), entry.path()))
+    }
+
+    pub fn allows_new_window(&self) -> bool {
+        !self.is_active() || self.new_windows
+    }
+}
+
+/// The window settings kiosk mode replaced.
+struct Restore {
+    fullscreen: Option<Fullscreen>,
+    decorated: bool,
+    always_on_top: bool,
+    maximized: bool,
+    position: Option<PhysicalPosition<i32>>,
+    size: PhysicalSize<u32>,
+}
+
+pub struct Kiosk {
+    options: KioskOptions,
+    guard: KioskGuard,
+    restore: Option<Restore>,
+}
+
+impl Kiosk {
+    /// Puts `window` into kiosk mode. `monitor` is the `monitor` option.
+    pub fn enter(
+        window: &Window,
+        options: KioskOptions,
+        guard: KioskGuard,
+        monitor: Option<&MonitorSelector>,
+    ) -> Self {
+        let restore = Restore {
+            fullscreen: window.fullscreen(),
+            decorated: window.is_decorated(),
+            always_on_top: window.is_always_on_top(),
+            maximized: window.is_maximized(),
+            position: window.outer_position().ok(),
+            size: window.inner_size(),
+        };
+        if options.fullscreen {
+            let monitors: Vec<_> = window.available_monitors().collect();
+            let monitor = crate::placement::select_monitor(
+                options.monitor.as_ref().or(monitor),
+                &monitors,
+                window.primary_monitor(),
+            );
+            window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+        }
+        window.set_decorations(options.decorations);
+        window.set_always_on_top(options.always_on_top);
+        Self {
+            options,
+            guard,
+            restore: Some(restore),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.restore.is_some()
+    }
+
+    /// Checks `token` against `exit_token` for `exit_kiosk`.
+    pub fn authorize(&self, token: Option<&str>) -> Result<()> {
+        match (&self.options.exit_token, token) {
+            (Some(expected), Some(token)) if expected == token => Ok(()),
+            _ => Err(PermissionDenied {
+                permission: "kiosk_exit",
+            }
+            .into()),
+        }
+    }
+
+    /// Restores the window and lifts the navigation and new-window
+    /// restrictions. Returns `false` if kiosk mode was already left.
+    pub fn exit(&mut self, window: &Window, webview: &wry::WebView) -> Result<bool> {
+        let Some(restore) = self.restore.take() else {
+            return Ok(false);
+        };
+        self.guard.active.store(false, Ordering::Relaxed);
+        window.set_fullscreen(restore.fullscreen);
+        window.set_decorations(restore.decorated);
+        window.set_always_on_top(restore.always_on_top);
+        if !restore.maximized {
+            if let Some(position) = restore.position {
+                window.set_outer_position(position);
+            }
+            window.set_inner_size(restore.size);
+        }
+        window.set_maximized(restore.maximized);
+        webview.evaluate_script("window.__PYFRAME_KIOSK__ = false;")?;
+        Ok(true)
+    }
+}
+
+/// Blocks the context menu for as long as kiosk mode is on.
+pub fn init_script(options: &KioskOptions) -> String {
+    format!(
+        r#"(function () {{
+  window.__PYFRAME_KIOSK__ = true;
+  const blockContextMenu = {block};
+  document.addEventListener("contextmenu", (e) => {{
+    if (window.__PYFRAME_KIOSK__ && blockContextMenu) e.preventDefault();
+  }}, true);
+}})();"#,
+        block = !options.context_menu,
+    )
+}
+
+/// The parsed `exit_accelerator`, checked before the window opens.
+pub fn exit_accelerator(options: &KioskOptions) -> Result<Option<Combo>> {
+    options
+        .exit_accelerator
+        .as_deref()
+        .map(|accelerator| {
+            Combo::parse("kiosk-exit", accelerator)
+                .ok_or_else(|| anyhow!("Invalid exit_accelerator {:?}", accelerator))
+        })
+        .transpose()
+}
+
+/// Sends [`UserEvent::ExitKiosk`](crate::utils::UserEvent::ExitKiosk) when
+/// `combo` is pressed in `window` while `guard` is active. The key press
+/// doesn't reach the page.
+pub fn hook_exit(window: &Window, combo: Combo, guard: KioskGuard, proxy: FrameEventLoopProxy) {
+    platform::hook_exit(window, combo, guard, proxy);
+}
+
+#[cfg(target_os = "windows")]
+pub use platform::translate;
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::{cell::RefCell, ffi::c_void};
+
+    use tao::window::Window;
+    use windows::Win32::UI::{
+        Input::KeyboardAndMouse::{
+            GetKeyState, VIRTUAL_KEY, VK_BACK, VK_CONTROL, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE,
+            VK_F1, VK_HOME, VK_LEFT, VK_LWIN, VK_MENU, VK_NEXT, VK_PRIOR, VK_RETURN, VK_RIGHT,
+            VK_RWIN, VK_SHIFT, VK_SPACE, VK_TAB, VK_UP,
+        },
+        WindowsAndMessaging::{MSG, WM_KEYDOWN, WM_SYSKEYDOWN},
+    };
+
+    use super::KioskGuard;
+    use crate::{accelerators::Combo, utils::FrameEventLoopProxy, utils::UserEvent};
+
+    thread_local! {
+        static EXIT: RefCell<Option<(u16, Combo, KioskGuard, FrameEventLoopProxy)>> =
+            const { RefCell::new(None) };
+    }
+
+    pub fn hook_exit(
+        _window: &Window,
+        combo: Combo,
+        guard: KioskGuard,
+        proxy: FrameEventLoopProxy,
+    ) {
+        let Some(vk) = virtual_key(&combo) else {
+            crate::log_warn!(format!(
+                "Kiosk: exit_accelerator {:?} has no Windows key code",
+                combo.accelerator
+            ));
+            return;
+        };
+        EXIT.with(|slot| *slot.borrow_mut() = Some((vk, combo, guard, proxy)));
+    }
+
+    /// Part of the event loop's message hook; `true` if `msg` was the exit
+    /// accelerator, which then isn't dispatched to the WebView.
+    pub fn translate(msg: *const c_void) -> bool {
+        let msg = unsafe { &*(msg as *const MSG) };
+        if msg.message != WM_KEYDOWN && msg.message != WM_SYSKEYDOWN {
+            return false;
+        }
+        EXIT.with(|slot| {
+            let slot = slot.borrow();
+            let Some((vk, combo, guard, proxy)) = slot.as_ref() else {
+                return false;
+            };
+            let matched = guard.is_active()
+                && msg.wParam.0 == *vk as usize
+                && combo.ctrl == pressed(VK_CONTROL)
+                && combo.alt == pressed(VK_MENU)
+                && combo.shift == pressed(VK_SHIFT)
+                && combo.meta == (pressed(VK_LWIN) || pressed(VK_RWIN));
+            if matched {
+                let _ = proxy.send_event(UserEvent::ExitKiosk);
+            }
+            matched
+        })
+    }
+
+    fn pressed(key: VIRTUAL_KEY) -> bool {
+        (unsafe { GetKeyState(key.0 as i32) }) < 0
+    }
+
+    /// The virtual key of `combo`; letters and digits are their ASCII
+    /// uppercase codes.
+    fn virtual_key(combo: &Combo) -> Option<u16> {
+        if let Some(code) = &combo.code {
+            let key = code.trim_start_matches("Key").trim_start_matches("Digit");
+            return key.bytes().next().map(u16::from);
+        }
+        let key = combo.key.as_deref()?;
+        if let Some(n) = key.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+            return (1..=24).contains(&n).then(|| VK_F1.0 + n - 1);
+        }
+        Some(
+            match key {
+                "ArrowUp" => VK_UP,
+                "ArrowDown" => VK_DOWN,
+                "ArrowLeft" => VK_LEFT,
+                "ArrowRight" => VK_RIGHT,
+                "Escape" => VK_ESCAPE,
+                "Enter" => VK_RETURN,
+                " " => VK_SPACE,
+                "Delete" => VK_DELETE,
+                "Backspace" => VK_BACK,
+                "Tab" => VK_TAB,
+                "Home" => VK_HOME,
+                "End" => VK_END,
+                "PageUp" => VK_PRIOR,
+                "PageDown" => VK_NEXT,
+                _ => return None,
+            }
+            .0,
+        )
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod platform {
+    use gtk::{glib::Propagation, prelude::WidgetExt};
+    use tao::{platform::unix::WindowExtUnix, window::Window};
+
+    use super::KioskGuard;
+    use crate::{accelerators::Combo, utils::FrameEventLoopProxy, utils::UserEvent};
+
+    pub fn hook_exit(window: &Window, combo: Combo, guard: KioskGuard, proxy: FrameEventLoopProxy) {
+        window
+            .gtk_window()
+            .connect_key_press_event(move |_, event| {
+                let name = event.keyval().name().map(|name| name.to_string());
+                if guard.is_active()
+                    && crate::accelerators::matches(&combo, event.state(), name.as_deref())
+                {
+                    let _ = proxy.send_event(UserEvent::ExitKiosk);
+                    return Propagation::Stop;
+                }
+                Propagation::Proceed
+            });
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use block2::RcBlock;
+    use objc2::{class, msg_send, runtime::AnyObject};
+    use objc2_foundation::NSString;
+    use tao::window::Window;
+
+    use super::KioskGuard;
+    use crate::{accelerators::Combo, utils::FrameEventLoopProxy, utils::UserEvent};
+
+    /// `NSEventMaskKeyDown`.
+    const KEY_DOWN_MASK: u64 = 1 << 10;
+    /// `NSEventModifierFlag{Shift,Control,Option,Command}`.
+    const SHIFT: usize = 1 << 17;
+    const CONTROL: usize = 1 << 18;
+    const OPTION: usize = 1 << 19;
+    const COMMAND: usize = 1 << 20;
+
+    /// A local key-down monitor, which runs before the key is sent to the
+    /// WebView and swallows the exit accelerator.
+    pub fn hook_exit(
+        _window: &Window,
+        combo: Combo,
+        guard: KioskGuard,
+        proxy: FrameEventLoopProxy,
+    ) {
+        let Some(key) = characters(&combo) else {
+            crate::log_warn!(format!(
+                "Kiosk: exit_accelerator {:?} has no macOS key",
+                combo.accelerator
+            ));
+            return;
+        };
+        let handler = RcBlock::new(move |event: *mut AnyObject| -> *mut AnyObject {
+            let Some(event_ref) = (unsafe { event.as_ref() }) else {
+                return event;
+            };
+            let flags: usize = unsafe { msg_send![event_ref, modifierFlags] };
+            let chars: *mut NSString = unsafe { msg_send![event_ref, charactersIgnoringModifiers] };
+            let chars = unsafe { chars.as_ref() }.map(|chars| chars.to_string());
+            let matched = guard.is_active()
+                && combo.ctrl == (flags & CONTROL != 0)
+                && combo.alt == (flags & OPTION != 0)
+                && combo.shift == (flags & SHIFT != 0)
+                && combo.meta == (flags & COMMAND != 0)
+                && chars.is_some_and(|chars| chars.eq_ignore_ascii_case(&key));
+            if matched {
+                let _ = proxy.send_event(UserEvent::ExitKiosk);
+                return std::ptr::null_mut();
+            }
+            event
+        });
+        // The monitor stays installed for the life of the app; the guard
+        // turns it off when kiosk mode ends.
+        let _: *mut AnyObject = unsafe {
+            msg_send![class!(NSEvent), addLocalMonitorForEventsMatchingMask: KEY_DOWN_MASK, handler: &*handler]
+        };
+    }
+
+    /// What `charactersIgnoringModifiers` reports for the key of `combo`.
+    fn characters(combo: &Combo) -> Option<String> {
+        if let Some(code) = &combo.code {
+            let key = code.trim_start_matches("Key").trim_start_matches("Digit");
+            return Some(key.to_ascii_lowercase());
+        }
+        let key = combo.key.as_deref()?;
+        if let Some(n) = key.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+            // NSF1FunctionKey onwards.
+            return (1..=24)
+                .contains(&n)
+                .then(|| char::from_u32(0xF704 + n - 1))
+                .flatten()
+                .map(String::from);
+        }
+        Some(
+            match key {
+                "ArrowUp" => "\u{F700}",
+                "ArrowDown" => "\u{F701}",
+                "ArrowLeft" => "\u{F702}",
+                "ArrowRight" => "\u{F703}",
+                "Escape" => "\u{1b}",
+                "Enter" => "\r",
+                "Tab" => "\t",
+                "Backspace" => "\u{7f}",
+                "Delete" => "\u{F728}",
+                "Home" => "\u{F729}",
+                "End" => "\u{F72B}",
+                "PageUp" => "\u{F72C}",
+                "PageDown" => "\u{F72D}",
+                _ => key,
+            }
+            .to_string(),
+        )
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+mod platform {
+    use tao::window::Window;
+
+    use super::KioskGuard;
+    use crate::{accelerators::Combo, utils::FrameEventLoopProxy};
+
+    pub fn hook_exit(
+        _window: &Window,
+        combo: Combo,
+        _guard: KioskGuard,
+        _proxy: FrameEventLoopProxy,
+    ) {
+        crate::log_warn!(format!(
+            "Kiosk: exit_accelerator {:?} isn't supported on this platform",
+            combo.accelerator
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(allowed: &[&str]) -> KioskGuard {
+        let options = KioskOptions {
+            allowed_urls: allowed.iter().map(|url| url.to_string()).collect(),
+            ..Default::default()
+        };
+        KioskGuard::new(&options, None)
+    }
+
+    #[test]
+    fn allowed_paths_match_whole_segments() {
+        let guard = guard(&["https://example.com/app"]);
+        assert!(guard.allows_navigation("https://example.com/app"));
+        assert!(guard.allows_navigation("https://example.com/app/settings"));
+        assert!(!guard.allows_navigation("https://example.com/application"));
+        assert!(!guard.allows_navigation("https://example.com/"));
+    }
+
+    #[test]
+    fn a_trailing_slash_covers_the_directory() {
+        let guard = guard(&["https://example.com/"]);
+        assert!(guard.allows_navigation("https://example.com/anything"));
+        assert!(!guard.allows_navigation("https://other.example.com/"));
+    }
+}
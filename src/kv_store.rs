@@ -0,0 +1,137 @@
+//! Persistent key-value store behind the `store_*` APIs, kept in
+//! `<config dir>/<app_id>/store.json`.
+//!
+//! Unlike `localStorage` it survives `clear_browsing_data`. All windows and
+//! clients share the one store on the event loop thread. Changes are written
+//! once they settle for [`SAVE_DELAY`], and flushed on exit.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::config::AppConfig;
+
+const STORE_FILE: &str = "store.json";
+/// Quiet period after the last change before the store is written.
+const SAVE_DELAY: Duration = Duration::from_millis(300);
+
+pub struct KvStore {
+    path: Option<PathBuf>,
+    /// Loaded on first use.
+    values: Option<BTreeMap<String, Value>>,
+    save_at: Option<Instant>,
+}
+
+impl KvStore {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            path: config.config_dir().map(|dir| dir.join(STORE_FILE)),
+            values: None,
+            save_at: None,
+        }
+    }
+
+    fn values(&mut self) -> Result<&mut BTreeMap<String, Value>> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("No config directory to keep the store in"))?;
+        Ok(self.values.get_or_insert_with(|| load(path)))
+    }
+
+    pub fn get(&mut self, key: &str) -> Result<Option<Value>> {
+        Ok(self.values()?.get(key).cloned())
+    }
+
+    /// Returns `false` if `key` already held `value`.
+    pub fn set(&mut self, key: String, value: Value, now: Instant) -> Result<bool> {
+        let changed = self.values()?.insert(key, value.clone()) != Some(value);
+        if changed {
+            self.changed(now);
+        }
+        Ok(changed)
+    }
+
+    /// Returns `false` if `key` wasn't set.
+    pub fn delete(&mut self, key: &str, now: Instant) -> Result<bool> {
+        let deleted = self.values()?.remove(key).is_some();
+        if deleted {
+            self.changed(now);
+        }
+        Ok(deleted)
+    }
+
+    pub fn keys(&mut self) -> Result<Vec<String>> {
+        Ok(self.values()?.keys().cloned().collect())
+    }
+
+    /// Returns `false` if the store was already empty.
+    pub fn clear(&mut self, now: Instant) -> Result<bool> {
+        let values = self.values()?;
+        let cleared = !values.is_empty();
+        values.clear();
+        if cleared {
+            self.changed(now);
+        }
+        Ok(cleared)
+    }
+
+    fn changed(&mut self, now: Instant) {
+        self.save_at = Some(now + SAVE_DELAY);
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.save_at
+    }
+
+    /// Saves the store once changes have settled.
+    pub fn poll(&mut self, now: Instant) {
+        if self.save_at.is_some_and(|at| at <= now) {
+            self.flush();
+        }
+    }
+
+    /// Saves a pending change right away, e.g. at shutdown.
+    pub fn flush(&mut self) {
+        if self.save_at.take().is_none() {
+            return;
+        }
+        if let Err(e) = self.save() {
+            crate::log_err!(format!("Failed to save store: {}", e));
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let (Some(path), Some(values)) = (&self.path, &self.values) else {
+            return Ok(());
+        };
+        crate::config::write_atomic(path, &serde_json::to_vec_pretty(values)?)
+    }
+}
+
+/// A missing file is an empty store. A corrupt one is moved aside to
+/// `store.json.corrupt` rather than overwritten by the next save.
+fn load(path: &Path) -> BTreeMap<String, Value> {
+    let Ok(json) = fs::read(path) else {
+        return BTreeMap::new();
+    };
+    match serde_json::from_slice(&json) {
+        Ok(values) => values,
+        Err(e) => {
+            crate::log_warn!(format!(
+                "Store {:?} is corrupt, starting empty: {}",
+                path, e
+            ));
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(".corrupt");
+            crate::log_if_err!(fs::rename(path, aside));
+            BTreeMap::new()
+        }
+    }
+}
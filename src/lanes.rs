@@ -0,0 +1,153 @@
+//! Priority lanes between the TCP connections and the event loop.
+//!
+//! Accepted requests wait in one bounded queue per [`Priority`] and are
+//! posted to the [`Dispatch`] a few at a time, interactive ones first, so a
+//! click isn't stuck behind a burst of telemetry already sitting in the
+//! event loop's queue. A request that has waited [`AGE_LIMIT`] goes before
+//! younger ones of higher lanes, which keeps lower lanes from starving.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::{
+    api_manager::{code, ApiRequest},
+    connections::{respond, Dispatch},
+    lock_force,
+    utils::PendingMap,
+};
+
+/// Requests a lane holds before rejecting more with [`code::BUSY`].
+const LANE_CAPACITY: usize = 32;
+/// Most requests posted to the event loop and not yet picked up by it.
+const MAX_POSTED: usize = 2;
+/// How long a request may wait before it is served regardless of its lane.
+const AGE_LIMIT: Duration = Duration::from_millis(250);
+
+/// The `priority` of a request's metadata.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Direct responses to the user, e.g. opening a dialog on click.
+    Interactive,
+    #[default]
+    Normal,
+    /// Telemetry and other work nobody waits for. Also gets no in-flight
+    /// permit once few are left.
+    Background,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [
+        Priority::Interactive,
+        Priority::Normal,
+        Priority::Background,
+    ];
+
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+struct Queued {
+    request: ApiRequest,
+    since: Instant,
+}
+
+/// Queue depths, for `get_runtime_metrics`.
+#[derive(Serialize, Debug, Default)]
+pub struct LaneDepths {
+    pub interactive: usize,
+    pub normal: usize,
+    pub background: usize,
+    /// Posted to the event loop and not yet handled.
+    pub posted: usize,
+}
+
+#[derive(Default)]
+pub struct RequestLanes {
+    lanes: Mutex<[VecDeque<Queued>; 3]>,
+    posted: AtomicUsize,
+    /// A request was queued or the event loop picked one up.
+    notify: Notify,
+}
+
+impl RequestLanes {
+    /// Queues `request`, or hands it back if its lane is full.
+    pub fn push(&self, request: ApiRequest) -> Result<(), Box<ApiRequest>> {
+        {
+            let mut lanes = lock_force!(self.lanes);
+            let lane = &mut lanes[request.metadata().priority.lane()];
+            if lane.len() >= LANE_CAPACITY {
+                return Err(Box::new(request));
+            }
+            lane.push_back(Queued {
+                request,
+                since: Instant::now(),
+            });
+        }
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Called by whatever the requests are posted to once it starts on one.
+    pub fn taken(&self) {
+        // Saturating: requests from before the lanes existed aren't counted.
+        let _ = self
+            .posted
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+        self.notify.notify_one();
+    }
+
+    pub fn depths(&self) -> LaneDepths {
+        let lanes = lock_force!(self.lanes);
+        LaneDepths {
+            interactive: lanes[Priority::Interactive.lane()].len(),
+            normal: lanes[Priority::Normal.lane()].len(),
+            background: lanes[Priority::Background.lane()].len(),
+            posted: self.posted.load(Ordering::Acquire),
+        }
+    }
+
+    /// The oldest request past [`AGE_LIMIT`], else the first of the highest
+    /// non-empty lane.
+    fn pop(&self) -> Option<ApiRequest> {
+        let mut lanes = lock_force!(self.lanes);
+        let aged = Priority::ALL
+            .iter()
+            .filter_map(|priority| {
+                let head = lanes[priority.lane()].front()?;
+                (head.since.elapsed() >= AGE_LIMIT).then_some((head.since, priority.lane()))
+            })
+            .min()
+            .map(|(_, lane)| lane);
+        let lane = aged.or_else(|| lanes.iter().position(|lane| !lane.is_empty()))?;
+        lanes[lane].pop_front().map(|queued| queued.request)
+    }
+
+    /// Posts queued requests to `dispatch` for as long as the server runs.
+    pub async fn pump<D: Dispatch>(&self, dispatch: D, pending: PendingMap) {
+        loop {
+            let notified = self.notify.notified();
+            if self.posted.load(Ordering::Acquire) < MAX_POSTED {
+                if let Some(request) = self.pop() {
+                    self.posted.fetch_add(1, Ordering::AcqRel);
+                    if let Err(e) = dispatch.dispatch(request.clone()) {
+                        self.taken();
+                        respond(&pending, request.0, request.err(code::ERROR, e));
+                    }
+                    continue;
+                }
+            }
+            notified.await;
+        }
+    }
+}
@@ -1,33 +1,106 @@
-use anyhow::Result;
-use pyo3::prelude::*;
-
-use crate::utils::FrameEventLoopBuilder;
+// Window and WebView live on the event loop thread; the Arcs only share them
+// between the main-thread owners, never across threads.
+#![allow(clippy::arc_with_non_send_sync)]
+mod accelerators;
 mod api;
 mod api_manager;
 mod assets;
+mod audit;
+#[cfg(all(feature = "python", feature = "tcp"))]
+mod bridge_client;
+mod capture;
+mod click_through;
+mod clipboard;
+mod close_guard;
+mod config;
 mod connections;
+mod content_blocking;
 mod context;
+mod context_menu;
 mod core;
+mod crash;
+mod cursor;
+mod diagnostics;
+mod dialog;
+mod dock;
+mod drag_out;
+mod element_fullscreen;
+mod embed;
+// Emit frames only come from TCP clients.
+#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+mod emit;
+mod env_access;
+#[cfg(feature = "python")]
+mod errors;
+mod event_stream;
+mod exec;
+mod find;
+mod fonts;
+#[cfg(feature = "python")]
+mod frame_handle;
+mod frame_loop;
+mod fs_api;
+mod fs_scope;
+mod fs_watch;
+mod geolocation;
+#[cfg(feature = "bench")]
+pub mod headless;
+mod http_fetch;
+mod http_mock;
+// Only TCP clients retry with idempotency keys.
+#[cfg_attr(not(feature = "tcp"), allow(dead_code))]
+mod idempotency;
+mod ipc;
+mod kiosk;
+mod kv_store;
+mod lanes;
+mod load_error;
+mod local_server;
+mod main_thread;
+mod menu;
+mod menubar;
+mod native_handle;
+mod navigation;
+mod permissions;
+mod placement;
+mod process_stats;
+mod processes;
+#[cfg(feature = "tcp")]
+mod protocol;
+#[cfg(feature = "python")]
+mod python;
+mod request_context;
+mod runtime_metrics;
+mod script;
+mod services;
+mod shell;
+mod shutdown_hooks;
+mod splash;
+mod state_store;
+mod tabbing;
+mod taskbar;
+mod tasks;
+// Also built for the crate's own tests, which use its harnesses.
+#[cfg(all(feature = "tcp", any(test, feature = "testing")))]
+pub mod testing;
+mod ticker;
+mod timers;
+mod timing;
+mod title_sync;
+mod titlebar;
+mod titlebar_overlay;
+mod transport;
+mod tray;
+mod updater;
 mod utils;
+mod vfs;
+mod vibrancy;
+mod webframe;
+mod window_commands;
+mod window_events;
+mod window_shape;
+mod window_state;
+mod ws_client;
 
-#[pyfunction]
-fn create_webframe(html: String, host: String, port: u16, mp_event: Py<PyAny>) -> Result<()> {
-    let addrs = format!("ws://{}:{}/ws", host, port);
-
-    let json = serde_json::to_string(&addrs).unwrap();
-
-    let websocket_init_add = format!("window.socket_url = {};", json);
-
-    let mut event_loop = FrameEventLoopBuilder::with_user_event().build();
-
-    let app = core::App::new(&mut event_loop, websocket_init_add, html)?;
-
-    app.run(event_loop, mp_event)
-}
-
-/// A Python module implemented in Rust.
-#[pymodule]
-fn pygcc(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(create_webframe, m)?)?;
-    Ok(())
-}
+pub use native_handle::{NativeHandle, RenderTarget};
+pub use webframe::{FrameInfo, RunningFrame, ShutdownNotifier, WebFrame, WebFrameBuilder};
@@ -0,0 +1,232 @@
+//! The page shown when the main WebView fails to load.
+//!
+//! A failed load is emitted as `load-failed` with a [`LoadFailure`], and the
+//! WebView shows `error_page`, or a plain built-in page, with `{{error}}`
+//! and `{{url}}` filled in. Clicking an element with `data-pyframe-retry`
+//! loads the URL again, as `navigate` would. With `load_retry`, failures
+//! before the first page loaded are retried on their own, backing off up
+//! to `max_delay_ms`, e.g. for kiosks that boot before the network is up.
+//!
+//! Only WebKitGTK reports failed loads; WebView2 and WKWebView keep their
+//! own error pages and nothing is emitted there.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+
+/// IPC message of a `data-pyframe-retry` click.
+pub const RETRY_LOAD: &str = "__retry_load";
+
+/// Posts [`RETRY_LOAD`] on `data-pyframe-retry` clicks; added to every error page.
+const RETRY_SCRIPT: &str = r#"<script>
+document.addEventListener("click", (e) => {
+  if (e.target.closest && e.target.closest("[data-pyframe-retry]")) window.ipc.postMessage("__retry_load");
+});
+</script>"#;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LoadRetry {
+    /// Retries before the error page stays; 5 if unset.
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for each one after it.
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for LoadRetry {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// Payload of `load-failed`.
+#[derive(Serialize, Clone, Debug)]
+pub struct LoadFailure {
+    pub url: String,
+    /// The error's code within its `domain`, e.g. `g-resolver-error-quark`
+    /// or `WebKitNetworkError`.
+    pub code: i32,
+    pub domain: String,
+    pub message: String,
+    /// Number of the automatic retry that follows, `None` if none does.
+    pub attempt: Option<u32>,
+    pub retry_in_ms: Option<u64>,
+}
+
+pub struct LoadErrors {
+    page: String,
+    retry: Option<LoadRetry>,
+    /// No page has loaded yet, so `load_retry` applies.
+    initial: bool,
+    attempts: u32,
+    /// The URL an error page is shown for.
+    failed: Option<String>,
+    due: Option<Instant>,
+}
+
+impl LoadErrors {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            page: config
+                .error_page
+                .clone()
+                .unwrap_or_else(|| crate::assets::_ERROR_PAGE.to_string()),
+            retry: config.load_retry.clone(),
+            initial: true,
+            attempts: 0,
+            failed: None,
+            due: None,
+        }
+    }
+
+    /// Records `failure` and schedules the automatic retry, if one is left.
+    pub fn failed(&mut self, failure: &mut LoadFailure, now: Instant) {
+        self.failed = Some(failure.url.clone());
+        self.due = None;
+        let Some(retry) = self.retry.as_ref().filter(|_| self.initial) else {
+            return;
+        };
+        if self.attempts >= retry.attempts {
+            return;
+        }
+        let delay = retry
+            .initial_delay_ms
+            .saturating_mul(1 << self.attempts.min(16))
+            .min(retry.max_delay_ms);
+        self.attempts += 1;
+        self.due = Some(now + Duration::from_millis(delay));
+        failure.attempt = Some(self.attempts);
+        failure.retry_in_ms = Some(delay);
+    }
+
+    /// A load of `url` finished; `false` if it failed. The failed load and
+    /// its error page finish too, under the failed URL; anything else loaded.
+    pub fn finished(&mut self, url: &str) -> bool {
+        if self.failed.as_deref() == Some(url) {
+            return false;
+        }
+        self.initial = false;
+        self.failed = None;
+        self.due = None;
+        true
+    }
+
+    /// The URL to load again for a retry, `None` without an error page.
+    pub fn retry(&mut self) -> Option<String> {
+        self.due = None;
+        self.failed.take()
+    }
+
+    /// The URL whose automatic retry is due.
+    pub fn poll(&mut self, now: Instant) -> Option<String> {
+        if self.due.is_some_and(|due| due <= now) {
+            self.retry()
+        } else {
+            None
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.due
+    }
+
+    /// The error page for `failure`.
+    pub fn render(&self, failure: &LoadFailure) -> String {
+        let page = self
+            .page
+            .replace("{{error}}", &escape(&failure.message))
+            .replace("{{url}}", &escape(&failure.url));
+        format!("{}{}", page, RETRY_SCRIPT)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use gtk::{hook, show};
+
+/// Failed loads aren't reported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn hook(_webview: &wry::WebView, _proxy: &crate::utils::FrameEventLoopProxy) {}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn show(_webview: &wry::WebView, _html: &str, _url: &str) {}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod gtk {
+    use webkit2gtk::{glib::translate::ToGlibPtr, NetworkError, PolicyError, WebViewExt};
+    use wry::WebViewExtUnix;
+
+    use super::LoadFailure;
+    use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+    /// Sends `webview`'s failed loads to the event loop instead of showing
+    /// WebKit's error page.
+    pub fn hook(webview: &wry::WebView, proxy: &FrameEventLoopProxy) {
+        let proxy = proxy.clone();
+        webview
+            .webview()
+            .connect_load_failed(move |_, _, uri, error| {
+                // Navigating away and downloads aren't failures to show.
+                if error.matches(NetworkError::Cancelled)
+                    || error.matches(PolicyError::FrameLoadInterruptedByPolicyChange)
+                {
+                    return false;
+                }
+                let raw: *const webkit2gtk::glib::ffi::GError = error.to_glib_none().0;
+                let failure = LoadFailure {
+                    url: uri.to_string(),
+                    // SAFETY: `raw` points at `error`, alive for this call.
+                    code: unsafe { (*raw).code },
+                    domain: error.domain().as_str().to_string(),
+                    message: error.message().to_string(),
+                    attempt: None,
+                    retry_in_ms: None,
+                };
+                proxy
+                    .send_event(UserEvent::LoadFailed(Box::new(failure)))
+                    .is_ok()
+            });
+    }
+
+    /// Shows `html` as the page of the failed `url`, so going back skips it.
+    pub fn show(webview: &wry::WebView, html: &str, url: &str) {
+        webview.webview().load_alternate_html(html, url, Some(url));
+    }
+}
@@ -0,0 +1,165 @@
+//! The `local_server` option: the app served from `http://127.0.0.1:<port>/`
+//! instead of loaded with `with_html`, so the page has a real origin, and
+//! with it service workers and origin-scoped storage.
+//!
+//! Other local processes can reach the port too, so only requests carrying
+//! a cookie with a random token are answered. The WebView gets the cookie
+//! by loading [`AUTH_PATH`]`<token>` first, which redirects to `/`. Requests
+//! must also name the server in `Host`, which stops DNS rebinding.
+
+use std::{convert::Infallible, path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    header, Method, Request, Response, StatusCode,
+};
+use serde::Deserialize;
+
+use crate::vfs::VirtualFileSystem;
+
+/// Where the WebView picks up the auth cookie, followed by the token.
+const AUTH_PATH: &str = "/__pyframe_auth/";
+const AUTH_COOKIE: &str = "pyframe_auth";
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalServerOptions {
+    /// Directory to serve; the HTML passed to `create_webframe` as
+    /// `/index.html` if unset.
+    pub root: Option<PathBuf>,
+}
+
+/// Bound when the app starts, so the port is known before the WebView is
+/// built; serving starts with [`LocalServer::serve`].
+pub struct LocalServer {
+    listener: std::net::TcpListener,
+    port: u16,
+    site: Arc<Site>,
+}
+
+struct Site {
+    files: VirtualFileSystem,
+    token: String,
+    /// Accepted `Host` headers.
+    hosts: [String; 2],
+}
+
+impl LocalServer {
+    pub fn bind(options: &LocalServerOptions, html: &str) -> Result<Self> {
+        let mut files = VirtualFileSystem::default();
+        match &options.root {
+            Some(root) => {
+                files.add_directory_tree(root)?;
+            }
+            None => files.add_bytes("index.html", html.as_bytes().to_vec(), "text/html"),
+        }
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        Ok(Self {
+            listener,
+            port,
+            site: Arc::new(Site {
+                files,
+                token: uuid::Uuid::new_v4().simple().to_string(),
+                hosts: [format!("127.0.0.1:{}", port), format!("localhost:{}", port)],
+            }),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The first URL the WebView loads; it sets the cookie and redirects to `/`.
+    pub fn entry_url(&self) -> String {
+        format!(
+            "http://127.0.0.1:{}{}{}",
+            self.port, AUTH_PATH, self.site.token
+        )
+    }
+
+    /// Accepts connections until the task is aborted at shutdown.
+    pub async fn serve(self) -> Result<()> {
+        let listener = tokio::net::TcpListener::from_std(self.listener)?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let site = self.site.clone();
+            crate::tasks::spawn("local_server_connection", async move {
+                let service = hyper::service::service_fn(move |request: Request<Incoming>| {
+                    let response = site.respond(request);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                let io = hyper_util::rt::TokioIo::new(stream);
+                if let Err(e) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(io, service)
+                    .await
+                {
+                    crate::log_warn!(format!("Local server connection failed: {}", e));
+                }
+            });
+        }
+    }
+}
+
+impl Site {
+    fn respond(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        let (parts, _) = request.into_parts();
+        let host = parts
+            .headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok());
+        if !host.is_some_and(|host| self.hosts.iter().any(|h| h == host)) {
+            return status(StatusCode::FORBIDDEN);
+        }
+        if let Some(token) = parts.uri.path().strip_prefix(AUTH_PATH) {
+            if token != self.token {
+                return status(StatusCode::FORBIDDEN);
+            }
+            return Response::builder()
+                .status(StatusCode::SEE_OTHER)
+                .header(header::LOCATION, "/")
+                .header(
+                    header::SET_COOKIE,
+                    format!(
+                        "{}={}; Path=/; HttpOnly; SameSite=Strict",
+                        AUTH_COOKIE, self.token
+                    ),
+                )
+                .body(Full::new(Bytes::new()))
+                .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+        if !self.authorized(&parts.headers) {
+            return status(StatusCode::FORBIDDEN);
+        }
+        if parts.method != Method::GET && parts.method != Method::HEAD {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        let (mut parts, body) = self
+            .files
+            .respond(&Request::from_parts(parts, Vec::new()))
+            .into_parts();
+        // Only the app's own pages may read its files.
+        parts.headers.remove(header::ACCESS_CONTROL_ALLOW_ORIGIN);
+        Response::from_parts(parts, Full::new(Bytes::from(body.into_owned())))
+    }
+
+    fn authorized(&self, headers: &hyper::HeaderMap) -> bool {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|cookies| cookies.to_str().ok())
+            .flat_map(|cookies| cookies.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .any(|(name, value)| name == AUTH_COOKIE && value == self.token)
+    }
+}
+
+fn status(code: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = code;
+    response
+}
@@ -0,0 +1,105 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::oneshot;
+
+use crate::{
+    context::AppContext,
+    utils::{FrameEventLoopProxy, UserEvent},
+};
+
+/// A closure run on the event loop thread through [`UserEvent::Task`].
+pub type MainTask = Box<dyn FnOnce(&AppContext) + Send>;
+
+/// Runs closures on the event loop thread, where the Window and WebView live.
+///
+/// Cheap to clone and `Send`, so tokio tasks can hold one while the
+/// [`AppContext`] itself stays on the main thread.
+#[derive(Clone)]
+pub struct MainThread {
+    proxy: FrameEventLoopProxy,
+    closed: Arc<AtomicBool>,
+}
+
+impl MainThread {
+    pub fn new(proxy: FrameEventLoopProxy) -> Self {
+        Self {
+            proxy,
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Queues `f` behind the events already in the loop and returns a
+    /// receiver for its result.
+    ///
+    /// Fails once the loop has exited. A task still queued when the loop
+    /// exits is dropped, so its receiver errors instead of hanging.
+    pub fn run<T, F>(&self, f: F) -> Result<oneshot::Receiver<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&AppContext) -> T + Send + 'static,
+    {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(anyhow!("Event loop has exited"));
+        }
+        let (tx, rx) = oneshot::channel();
+        let task: MainTask = Box::new(move |ctx| {
+            let _ = tx.send(f(ctx));
+        });
+        self.proxy
+            .send_event(UserEvent::Task(task))
+            .map_err(|_| anyhow!("Event loop has exited"))?;
+        Ok(rx)
+    }
+
+    /// Queues `event` for the loop; fails once it has exited.
+    pub fn send(&self, event: UserEvent) -> Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(anyhow!("Event loop has exited"));
+        }
+        self.proxy
+            .send_event(event)
+            .map_err(|_| anyhow!("Event loop has exited"))
+    }
+
+    /// Called when the loop is destroyed; later tasks are rejected.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use crate::testing::LoopHarness;
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn tokio_workers_reach_the_window() {
+        let harness = LoopHarness::start();
+        let main_thread = harness.main_thread();
+        let worker = harness.runtime().spawn(async move {
+            let title = main_thread.run(|ctx| {
+                let window = ctx.get_window()?;
+                window.set_title("From a worker");
+                anyhow::Ok(window.title())
+            });
+            title.unwrap().await.unwrap()
+        });
+        let title = harness.runtime().block_on(worker).unwrap();
+        assert_eq!(title.unwrap(), "From a worker");
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn tasks_fail_once_the_loop_exits() {
+        let harness = LoopHarness::start();
+        let main_thread = harness.main_thread();
+        assert!(main_thread.run(|_| ()).is_ok());
+        drop(harness);
+        let err = main_thread.run(|_| ()).unwrap_err();
+        assert_eq!(err.to_string(), "Event loop has exited");
+    }
+}
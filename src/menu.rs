@@ -0,0 +1,443 @@
+//! Native menus built from JSON item definitions, as used by
+//! `set_tray_menu`, `set_dock_menu` and `set_menu`.
+//!
+//! A [`MenuModel`] owns the native items by id, so `update_item` changes
+//! a label or a check mark in place instead of rebuilding the menu, and
+//! [`MenuModel::update_from`] does the same for a new definition of the
+//! same shape. Checkbox and radio state lives here, not in the native
+//! item: a click toggles the checkbox or moves the radio group's check,
+//! and the [`MenuClick`] reports the result.
+//!
+//! Unknown ids and accelerators that don't parse fail with
+//! [`BadArgs`](crate::api_manager::BadArgs).
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tray_icon::menu::{
+    accelerator::Accelerator, CheckMenuItem, IsMenuItem, Menu, MenuId, MenuItem,
+    PredefinedMenuItem, Submenu,
+};
+
+use crate::api_manager::BadArgs;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    #[default]
+    Normal,
+    Checkbox,
+    /// Checked alone among the radio items of its `group`.
+    Radio,
+    Separator,
+    Submenu,
+}
+
+/// One entry of a menu definition.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ItemSpec {
+    /// Reported when chosen; needed by all but separators and submenus.
+    pub id: String,
+    pub label: String,
+    pub kind: ItemKind,
+    /// On by default.
+    pub enabled: bool,
+    pub checked: bool,
+    /// For radio items.
+    pub group: Option<String>,
+    /// Shortcut such as `"CmdOrCtrl+S"`; not for submenus.
+    pub accelerator: Option<String>,
+    /// For submenus.
+    pub items: Vec<ItemSpec>,
+}
+
+impl Default for ItemSpec {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            label: String::new(),
+            kind: ItemKind::Normal,
+            enabled: true,
+            checked: false,
+            group: None,
+            accelerator: None,
+            items: Vec::new(),
+        }
+    }
+}
+
+/// Arguments of `update_item`; only the given ones change.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ItemUpdate {
+    pub label: Option<String>,
+    pub enabled: Option<bool>,
+    pub checked: Option<bool>,
+}
+
+/// A chosen item, with its check state after the click.
+#[derive(Serialize, Clone, Debug)]
+pub struct MenuClick {
+    pub id: String,
+    pub kind: ItemKind,
+    /// For checkbox and radio items.
+    pub checked: Option<bool>,
+    pub group: Option<String>,
+}
+
+enum Handle {
+    Normal(MenuItem),
+    Check(CheckMenuItem),
+    Submenu(Submenu),
+}
+
+struct Entry {
+    handle: Handle,
+    kind: ItemKind,
+    group: Option<String>,
+}
+
+pub struct MenuModel {
+    menu: Menu,
+    spec: Vec<ItemSpec>,
+    entries: HashMap<String, Entry>,
+}
+
+impl MenuModel {
+    pub fn build(mut spec: Vec<ItemSpec>) -> Result<Self> {
+        validate(&spec, &mut Vec::new())?;
+        normalize_radios(&mut spec);
+        let menu = Menu::new();
+        let mut entries = HashMap::new();
+        for item in &spec {
+            let native = build_item(item, &mut entries)?;
+            menu.append(native.as_ref())?;
+        }
+        Ok(Self {
+            menu,
+            spec,
+            entries,
+        })
+    }
+
+    /// The native menu; it stays the same across updates.
+    pub fn menu(&self) -> &Menu {
+        &self.menu
+    }
+
+    /// The current definition, with every update applied.
+    pub fn spec(&self) -> &[ItemSpec] {
+        &self.spec
+    }
+
+    /// Applies `spec` in place if it has the same items as the current
+    /// one, with at most other labels, enabled and checked states.
+    /// `false` if it has to be rebuilt instead.
+    pub fn update_from(&mut self, mut spec: Vec<ItemSpec>) -> Result<bool> {
+        if !same_shape(&self.spec, &spec) {
+            return Ok(false);
+        }
+        validate(&spec, &mut Vec::new())?;
+        normalize_radios(&mut spec);
+        let (mut changes, mut accelerators) = (Vec::new(), Vec::new());
+        diff(&self.spec, &spec, &mut changes, &mut accelerators);
+        for (id, accelerator) in accelerators {
+            self.set_accelerator(&id, accelerator)?;
+        }
+        for (id, update) in changes {
+            self.apply(&id, &update);
+        }
+        self.spec = spec;
+        Ok(true)
+    }
+
+    /// Sets or, with `None`, removes the accelerator of `id`.
+    pub fn set_accelerator(&mut self, id: &str, accelerator: Option<String>) -> Result<()> {
+        let entry = self.entry(id)?;
+        let parsed = parse_accelerator(accelerator.as_deref())?;
+        match &entry.handle {
+            Handle::Normal(item) => item.set_accelerator(parsed)?,
+            Handle::Check(item) => item.set_accelerator(parsed)?,
+            Handle::Submenu(_) => {
+                return Err(bad_args(format!("Submenu {:?} has no accelerator", id)));
+            }
+        }
+        if let Some(item) = find_mut(&mut self.spec, id) {
+            item.accelerator = accelerator;
+        }
+        Ok(())
+    }
+
+    fn entry(&self, id: &str) -> Result<&Entry> {
+        self.entries
+            .get(id)
+            .ok_or_else(|| bad_args(format!("No menu item {:?}", id)))
+    }
+
+    pub fn update_item(&mut self, id: &str, update: ItemUpdate) -> Result<()> {
+        let entry = self.entry(id)?;
+        if update.checked.is_some() && !matches!(entry.kind, ItemKind::Checkbox | ItemKind::Radio) {
+            return Err(bad_args(format!("Menu item {:?} has no check mark", id)));
+        }
+        if update.checked == Some(true) && entry.kind == ItemKind::Radio {
+            // Takes the check from the rest of the group.
+            self.check_radio(id);
+        }
+        self.apply(id, &update);
+        if let Some(item) = find_mut(&mut self.spec, id) {
+            if let Some(label) = update.label {
+                item.label = label;
+            }
+            if let Some(enabled) = update.enabled {
+                item.enabled = enabled;
+            }
+            if let Some(checked) = update.checked {
+                item.checked = checked;
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the check state after `id` was chosen; `None` if it isn't
+    /// one of this menu's items.
+    pub fn clicked(&mut self, id: &MenuId) -> Option<MenuClick> {
+        let id = id.as_ref();
+        let entry = self.entries.get(id)?;
+        let (kind, group) = (entry.kind, entry.group.clone());
+        let checked = match kind {
+            ItemKind::Checkbox => {
+                let checked = !find_mut(&mut self.spec, id)?.checked;
+                self.update_item(id, checked_update(checked)).ok()?;
+                Some(checked)
+            }
+            ItemKind::Radio => {
+                // Stays checked when chosen again; the native item may
+                // have unchecked itself.
+                self.update_item(id, checked_update(true)).ok()?;
+                Some(true)
+            }
+            _ => None,
+        };
+        Some(MenuClick {
+            id: id.to_string(),
+            kind,
+            checked,
+            group,
+        })
+    }
+
+    fn check_radio(&mut self, id: &str) {
+        let Some(group) = self.entries.get(id).and_then(|entry| entry.group.clone()) else {
+            return;
+        };
+        let others: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(other, entry)| {
+                other.as_str() != id
+                    && entry.kind == ItemKind::Radio
+                    && entry.group.as_ref() == Some(&group)
+            })
+            .map(|(other, _)| other.clone())
+            .collect();
+        for other in others {
+            self.apply(&other, &checked_update(false));
+            if let Some(item) = find_mut(&mut self.spec, &other) {
+                item.checked = false;
+            }
+        }
+    }
+
+    /// Changes the native item only.
+    fn apply(&self, id: &str, update: &ItemUpdate) {
+        let Some(entry) = self.entries.get(id) else {
+            return;
+        };
+        match &entry.handle {
+            Handle::Normal(item) => {
+                if let Some(label) = &update.label {
+                    item.set_text(label);
+                }
+                if let Some(enabled) = update.enabled {
+                    item.set_enabled(enabled);
+                }
+            }
+            Handle::Check(item) => {
+                if let Some(label) = &update.label {
+                    item.set_text(label);
+                }
+                if let Some(enabled) = update.enabled {
+                    item.set_enabled(enabled);
+                }
+                if let Some(checked) = update.checked {
+                    item.set_checked(checked);
+                }
+            }
+            Handle::Submenu(item) => {
+                if let Some(label) = &update.label {
+                    item.set_text(label);
+                }
+                if let Some(enabled) = update.enabled {
+                    item.set_enabled(enabled);
+                }
+            }
+        }
+    }
+}
+
+fn checked_update(checked: bool) -> ItemUpdate {
+    ItemUpdate {
+        checked: Some(checked),
+        ..Default::default()
+    }
+}
+
+fn bad_args(reason: String) -> anyhow::Error {
+    anyhow::Error::new(BadArgs { reason })
+}
+
+fn parse_accelerator(accelerator: Option<&str>) -> Result<Option<Accelerator>> {
+    accelerator
+        .map(|text| {
+            text.parse::<Accelerator>()
+                .map_err(|e| bad_args(format!("Invalid accelerator {:?}: {}", text, e)))
+        })
+        .transpose()
+}
+
+fn build_item(
+    item: &ItemSpec,
+    entries: &mut HashMap<String, Entry>,
+) -> Result<Box<dyn IsMenuItem>> {
+    let accelerator = parse_accelerator(item.accelerator.as_deref())?;
+    let (native, handle): (Box<dyn IsMenuItem>, Handle) = match item.kind {
+        ItemKind::Separator => return Ok(Box::new(PredefinedMenuItem::separator())),
+        ItemKind::Normal => {
+            let native =
+                MenuItem::with_id(item.id.as_str(), &item.label, item.enabled, accelerator);
+            (Box::new(native.clone()), Handle::Normal(native))
+        }
+        ItemKind::Checkbox | ItemKind::Radio => {
+            let native = CheckMenuItem::with_id(
+                item.id.as_str(),
+                &item.label,
+                item.enabled,
+                item.checked,
+                accelerator,
+            );
+            (Box::new(native.clone()), Handle::Check(native))
+        }
+        ItemKind::Submenu => {
+            let native = if item.id.is_empty() {
+                Submenu::new(&item.label, item.enabled)
+            } else {
+                Submenu::with_id(item.id.as_str(), &item.label, item.enabled)
+            };
+            for child in &item.items {
+                native.append(build_item(child, entries)?.as_ref())?;
+            }
+            (Box::new(native.clone()), Handle::Submenu(native))
+        }
+    };
+    if !item.id.is_empty() {
+        entries.insert(
+            item.id.clone(),
+            Entry {
+                handle,
+                kind: item.kind,
+                group: item.group.clone(),
+            },
+        );
+    }
+    Ok(native)
+}
+
+/// Ids are unique and present where needed; radio items have a group;
+/// accelerators parse.
+fn validate<'a>(spec: &'a [ItemSpec], seen: &mut Vec<&'a str>) -> Result<()> {
+    for item in spec {
+        match item.kind {
+            ItemKind::Separator => continue,
+            ItemKind::Submenu if item.id.is_empty() => {}
+            _ if item.id.is_empty() => {
+                return Err(bad_args(format!("Menu item {:?} needs an id", item.label)));
+            }
+            _ if seen.contains(&item.id.as_str()) => {
+                return Err(bad_args(format!(
+                    "Menu item id {:?} is used twice",
+                    item.id
+                )));
+            }
+            _ => seen.push(&item.id),
+        }
+        if item.kind == ItemKind::Radio && item.group.is_none() {
+            return Err(bad_args(format!("Radio item {:?} needs a group", item.id)));
+        }
+        parse_accelerator(item.accelerator.as_deref())?;
+        validate(&item.items, seen)?;
+    }
+    Ok(())
+}
+
+/// Leaves the first checked radio item of each group checked.
+fn normalize_radios(spec: &mut [ItemSpec]) {
+    fn walk(spec: &mut [ItemSpec], checked: &mut Vec<String>) {
+        for item in spec {
+            if let (ItemKind::Radio, true, Some(group)) = (item.kind, item.checked, &item.group) {
+                if checked.contains(group) {
+                    item.checked = false;
+                } else {
+                    checked.push(group.clone());
+                }
+            }
+            walk(&mut item.items, checked);
+        }
+    }
+    walk(spec, &mut Vec::new());
+}
+
+fn same_shape(a: &[ItemSpec], b: &[ItemSpec]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.id == b.id && a.kind == b.kind && a.group == b.group && same_shape(&a.items, &b.items)
+        })
+}
+
+/// What changed between two definitions of the same shape, by item id.
+fn diff(
+    old: &[ItemSpec],
+    new: &[ItemSpec],
+    changes: &mut Vec<(String, ItemUpdate)>,
+    accelerators: &mut Vec<(String, Option<String>)>,
+) {
+    for (old, new) in old.iter().zip(new) {
+        let update = ItemUpdate {
+            label: (old.label != new.label).then(|| new.label.clone()),
+            enabled: (old.enabled != new.enabled).then_some(new.enabled),
+            checked: (old.checked != new.checked).then_some(new.checked),
+        };
+        if !new.id.is_empty()
+            && (update.label.is_some() || update.enabled.is_some() || update.checked.is_some())
+        {
+            changes.push((new.id.clone(), update));
+        }
+        if !new.id.is_empty() && old.accelerator != new.accelerator {
+            accelerators.push((new.id.clone(), new.accelerator.clone()));
+        }
+        diff(&old.items, &new.items, changes, accelerators);
+    }
+}
+
+fn find_mut<'a>(spec: &'a mut [ItemSpec], id: &str) -> Option<&'a mut ItemSpec> {
+    for item in spec {
+        if item.id == id {
+            return Some(item);
+        }
+        if let Some(found) = find_mut(&mut item.items, id) {
+            return Some(found);
+        }
+    }
+    None
+}
@@ -0,0 +1,154 @@
+//! The native menu bar: the window's on Windows and Linux, the app's on
+//! macOS. Set with `set_menu` from [`ItemSpec`]s, whose top-level entries
+//! are the menus ("File", "View", ...).
+//!
+//! [`MenuRegistry`] owns it: `update_menu_item` and
+//! `set_menu_accelerator` change the native items in place, so the bar
+//! doesn't flicker, and `get_menu_state` reports the state after every
+//! change. Chosen items arrive as `menu` events with their check state.
+
+use anyhow::Result;
+use tao::window::Window;
+
+use crate::menu::{ItemSpec, ItemUpdate, MenuClick, MenuModel};
+
+#[derive(Default)]
+pub struct MenuRegistry {
+    model: Option<MenuModel>,
+}
+
+impl MenuRegistry {
+    /// Replaces the menu bar; one with the same items as the current one
+    /// is updated in place.
+    pub fn set_menu(&mut self, window: &Window, items: Vec<ItemSpec>) -> Result<()> {
+        if let Some(model) = self.model.as_mut() {
+            if model.update_from(items.clone())? {
+                return Ok(());
+            }
+        }
+        let model = MenuModel::build(items)?;
+        if let Some(old) = &self.model {
+            platform::detach(window, old)?;
+        }
+        platform::attach(window, &model)?;
+        self.model = Some(model);
+        Ok(())
+    }
+
+    pub fn update_item(&mut self, id: &str, update: ItemUpdate) -> Result<()> {
+        self.model_mut()?.update_item(id, update)
+    }
+
+    pub fn set_accelerator(&mut self, id: &str, accelerator: Option<String>) -> Result<()> {
+        self.model_mut()?.set_accelerator(id, accelerator)
+    }
+
+    /// The `set_menu` items with their current state; `None` if unset.
+    pub fn state(&self) -> Option<Vec<ItemSpec>> {
+        self.model.as_ref().map(|model| model.spec().to_vec())
+    }
+
+    /// `None` unless `id` is an item of the menu bar.
+    pub fn clicked(&mut self, id: &tray_icon::menu::MenuId) -> Option<MenuClick> {
+        self.model.as_mut()?.clicked(id)
+    }
+
+    fn model_mut(&mut self) -> Result<&mut MenuModel> {
+        self.model.as_mut().ok_or_else(|| {
+            anyhow::Error::new(crate::api_manager::BadArgs {
+                reason: "No menu bar was set with set_menu".into(),
+            })
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::Result;
+    use tao::{platform::windows::WindowExtWindows, window::Window};
+
+    use crate::menu::MenuModel;
+
+    pub fn attach(window: &Window, model: &MenuModel) -> Result<()> {
+        unsafe { model.menu().init_for_hwnd(window.hwnd() as _)? };
+        crate::accelerators::set_menu(window.hwnd() as _, Some(model.menu().clone()));
+        Ok(())
+    }
+
+    pub fn detach(window: &Window, model: &MenuModel) -> Result<()> {
+        unsafe { model.menu().remove_for_hwnd(window.hwnd() as _)? };
+        crate::accelerators::set_menu(window.hwnd() as _, None);
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::Result;
+    use tao::window::Window;
+
+    use crate::menu::MenuModel;
+
+    /// The app's main menu; a new one simply takes its place.
+    pub fn attach(_window: &Window, model: &MenuModel) -> Result<()> {
+        model.menu().init_for_nsapp();
+        Ok(())
+    }
+
+    pub fn detach(_window: &Window, _model: &MenuModel) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod platform {
+    use anyhow::Result;
+    use tao::{platform::unix::WindowExtUnix, window::Window};
+
+    use crate::menu::MenuModel;
+
+    /// Goes at the top of tao's box, above the WebView.
+    pub fn attach(window: &Window, model: &MenuModel) -> Result<()> {
+        model
+            .menu()
+            .init_for_gtk_window(window.gtk_window(), window.default_vbox())?;
+        Ok(())
+    }
+
+    pub fn detach(window: &Window, model: &MenuModel) -> Result<()> {
+        model.menu().remove_for_gtk_window(window.gtk_window())?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+mod platform {
+    use anyhow::Result;
+    use tao::window::Window;
+
+    use crate::{api_manager::Unsupported, menu::MenuModel};
+
+    pub fn attach(_window: &Window, _model: &MenuModel) -> Result<()> {
+        Err(anyhow::Error::new(Unsupported {
+            feature: "menu bar".into(),
+        }))
+    }
+
+    pub fn detach(_window: &Window, _model: &MenuModel) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+//! The platform handles of a window, for `native_handle`, so native
+//! renderers like libmpv can draw into it.
+//!
+//! The numbers are raw pointers or ids owned by the window: they are valid
+//! until the window closes and must not be used after its `window-closed`
+//! event. Nothing here is thread-safe on its own; a renderer using the
+//! handle from its own thread has to do so the way its platform allows,
+//! e.g. libmpv's `wid` option on Win32, AppKit and X11, and stop before the
+//! window goes. In Rust, [`RenderTarget`] does the latter.
+
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Context, Result};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use serde::Serialize;
+use tao::window::Window;
+
+use crate::{api_manager::Unsupported, read_force, write_force};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NativeHandle {
+    /// `"win32"`, `"appkit"`, `"xlib"`, `"xcb"` or `"wayland"`.
+    pub kind: &'static str,
+    /// The `HWND`, `NSView*`, X window id or `wl_surface*`.
+    pub window: u64,
+    /// The `HINSTANCE`, `Display*`, `xcb_connection_t*` or `wl_display*`,
+    /// where there is one.
+    pub display: Option<u64>,
+}
+
+pub fn of(window: &Window) -> Result<NativeHandle> {
+    let raw = window
+        .window_handle()
+        .context("The window has no native handle")?
+        .as_raw();
+    let display = window
+        .display_handle()
+        .ok()
+        .and_then(|display| match display.as_raw() {
+            RawDisplayHandle::Xlib(xlib) => xlib.display.map(|p| p.as_ptr() as u64),
+            RawDisplayHandle::Xcb(xcb) => xcb.connection.map(|p| p.as_ptr() as u64),
+            RawDisplayHandle::Wayland(wayland) => Some(wayland.display.as_ptr() as u64),
+            _ => None,
+        });
+    let (kind, handle, display) = match raw {
+        RawWindowHandle::Win32(win32) => (
+            "win32",
+            win32.hwnd.get() as u64,
+            win32.hinstance.map(|h| h.get() as u64),
+        ),
+        RawWindowHandle::AppKit(appkit) => ("appkit", appkit.ns_view.as_ptr() as u64, None),
+        // `c_ulong` is only 64 bits wide on some targets.
+        #[allow(clippy::useless_conversion)]
+        RawWindowHandle::Xlib(xlib) => ("xlib", u64::from(xlib.window), display),
+        RawWindowHandle::Xcb(xcb) => ("xcb", u64::from(xcb.window.get()), display),
+        // The toplevel surface GTK draws into; renderers need a subsurface
+        // of it and the display to create one.
+        RawWindowHandle::Wayland(wayland) => ("wayland", wayland.surface.as_ptr() as u64, display),
+        other => {
+            return Err(anyhow::Error::new(Unsupported {
+                feature: format!("native_handle for {:?}", other),
+            }))
+        }
+    };
+    Ok(NativeHandle {
+        kind,
+        window: handle,
+        display,
+    })
+}
+
+/// The [`NativeHandle`] of a window for a renderer on its own thread, from
+/// [`WebFrameBuilder::on_render_target`](crate::WebFrameBuilder::on_render_target).
+///
+/// [`paint`](Self::paint) runs while the window can't go away: the event
+/// loop closes the target before it destroys the window, waiting for a
+/// paint in progress, and every later paint returns `None`. A paint must not
+/// wait for the event loop thread, which may be waiting for it to finish.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// pygcc::WebFrame::builder()
+///     .html("<h1>Video</h1>")
+///     .on_render_target(|target| {
+///         std::thread::spawn(move || {
+///             while target.paint(|handle| draw_frame(handle.window)).is_some() {
+///                 std::thread::sleep(Duration::from_millis(16));
+///             }
+///         });
+///     })
+///     .run()?;
+/// # fn draw_frame(_window: u64) {}
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct RenderTarget {
+    handle: NativeHandle,
+    closed: Arc<RwLock<bool>>,
+}
+
+impl RenderTarget {
+    pub(crate) fn new(handle: NativeHandle) -> Self {
+        Self {
+            handle,
+            closed: Arc::default(),
+        }
+    }
+
+    /// Runs `paint` with the handle, unless the window is gone.
+    pub fn paint<T>(&self, paint: impl FnOnce(&NativeHandle) -> T) -> Option<T> {
+        let closed = read_force!(self.closed);
+        (!*closed).then(|| paint(&self.handle))
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *read_force!(self.closed)
+    }
+
+    /// Waits for a [`paint`](Self::paint) in progress and refuses later
+    /// ones. Called on the event loop thread before the window goes.
+    pub(crate) fn close(&self) {
+        *write_force!(self.closed) = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn serialized_shape() {
+        let handle = NativeHandle {
+            kind: "xlib",
+            window: 0x3a0_0007,
+            display: None,
+        };
+        assert_eq!(
+            serde_json::to_value(handle).unwrap(),
+            json!({ "kind": "xlib", "window": 0x3a0_0007, "display": null })
+        );
+    }
+
+    #[test]
+    fn closing_waits_for_the_paint_in_progress() {
+        use std::{sync::mpsc, thread, time::Duration};
+
+        let target = RenderTarget::new(NativeHandle {
+            kind: "xlib",
+            window: 7,
+            display: Some(1),
+        });
+        let (painting_tx, painting) = mpsc::channel();
+        let (done_tx, done) = mpsc::channel();
+        let renderer = thread::spawn({
+            let target = target.clone();
+            move || {
+                let mut frames = 0;
+                while let Some(window) = target.paint(|handle| {
+                    let _ = painting_tx.send(());
+                    thread::sleep(Duration::from_millis(50));
+                    // Still inside the paint, so the window is still open.
+                    let _ = done_tx.send(());
+                    handle.window
+                }) {
+                    assert_eq!(window, 7);
+                    frames += 1;
+                }
+                frames
+            }
+        });
+
+        painting.recv().unwrap();
+        target.close();
+        // The paint that was running when close was called has finished.
+        done.try_recv().unwrap();
+        assert!(target.is_closed());
+        assert!(target.paint(|_| ()).is_none());
+        assert!(renderer.join().unwrap() >= 1);
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    #[ignore = "needs a display"]
+    fn handle_of_the_window() {
+        use crate::api_manager::code;
+
+        let harness = crate::testing::LoopHarness::start();
+        let response = harness.call("native_handle", json!([]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        let handle = response.result();
+        let kinds: &[&str] = if cfg!(target_os = "windows") {
+            &["win32"]
+        } else if cfg!(target_os = "macos") {
+            &["appkit"]
+        } else {
+            &["xlib", "xcb", "wayland"]
+        };
+        assert!(
+            kinds.iter().any(|kind| handle["kind"] == *kind),
+            "{}",
+            handle
+        );
+        assert_ne!(handle["window"].as_u64(), Some(0));
+        if handle["kind"] != "appkit" {
+            assert!(handle["display"]
+                .as_u64()
+                .is_some_and(|display| display != 0));
+        }
+
+        let response = harness.call("native_handle", json!([{ "window_id": 999 }]));
+        assert_ne!(response.code(), code::OK);
+    }
+}
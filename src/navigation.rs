@@ -0,0 +1,193 @@
+//! The main page's navigation lifecycle: `load-started`,
+//! `dom-content-loaded`, `load-finished` and, on WebKitGTK, `load-progress`,
+//! on the page and for `pyframe.events()`. `get_navigation_state` returns
+//! where the current load is.
+//!
+//! Page loads come from wry; `load-finished` has `success: false` when the
+//! error page of [`crate::load_error`] is shown instead. `navigation.js`
+//! adds `DOMContentLoaded` and the same-document navigations of
+//! `pushState`, `replaceState` and `popstate`, which come as a
+//! `load-started` and `load-finished` pair with `same_document: true`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{utils::UserEvent, window_events::WINDOW_LABEL};
+
+/// Prefix of the IPC message, followed by a [`NavigationEvent`] as JSON.
+pub const NAVIGATION: &str = "__navigation:";
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lifecycle {
+    LoadStarted,
+    DomContentLoaded,
+    LoadFinished,
+    LoadProgress,
+}
+
+impl Lifecycle {
+    /// Name of the event.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::LoadStarted => "load-started",
+            Self::DomContentLoaded => "dom-content-loaded",
+            Self::LoadFinished => "load-finished",
+            Self::LoadProgress => "load-progress",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NavigationEvent {
+    pub kind: Lifecycle,
+    pub url: String,
+    /// `pushState`, `replaceState` or `popstate` instead of a page load.
+    #[serde(default)]
+    pub same_document: bool,
+    /// For same-document navigations: `"push"`, `"replace"` or `"pop"`.
+    pub navigation_type: Option<String>,
+    /// For `load-finished`: `false` if the page failed to load.
+    pub success: Option<bool>,
+    /// For `load-progress`: the backend's estimate from 0 to 1.
+    pub progress: Option<f64>,
+}
+
+impl NavigationEvent {
+    pub fn new(kind: Lifecycle, url: String) -> Self {
+        Self {
+            kind,
+            url,
+            same_document: false,
+            navigation_type: None,
+            success: None,
+            progress: None,
+        }
+    }
+
+    /// The event from a [`NAVIGATION`] message. Pages only report what
+    /// isn't known natively.
+    pub fn from_page(body: &str) -> Option<UserEvent> {
+        let event: Self = serde_json::from_str(body).ok()?;
+        (event.kind == Lifecycle::DomContentLoaded || event.same_document)
+            .then(|| UserEvent::Navigation(Box::new(event)))
+    }
+
+    /// Payload of the event.
+    pub fn payload(&self) -> Value {
+        let mut payload = json!({
+            "label": WINDOW_LABEL,
+            "url": self.url,
+            "same_document": self.same_document,
+        });
+        for (key, value) in [
+            ("navigation_type", json!(self.navigation_type)),
+            ("success", json!(self.success)),
+            ("progress", json!(self.progress)),
+        ] {
+            if !value.is_null() {
+                payload[key] = value;
+            }
+        }
+        payload
+    }
+}
+
+/// Returned by `get_navigation_state`.
+#[derive(Serialize, Clone, Debug)]
+pub struct NavigationState {
+    pub label: &'static str,
+    pub url: Option<String>,
+    pub loading: bool,
+    pub dom_content_loaded: bool,
+    /// `None` until the backend estimated it.
+    pub progress: Option<f64>,
+    /// Whether the last load succeeded; `None` while loading.
+    pub success: Option<bool>,
+}
+
+impl Default for NavigationState {
+    fn default() -> Self {
+        Self {
+            label: WINDOW_LABEL,
+            url: None,
+            loading: false,
+            dom_content_loaded: false,
+            progress: None,
+            success: None,
+        }
+    }
+}
+
+impl NavigationState {
+    pub fn apply(&mut self, event: &NavigationEvent) {
+        self.url = Some(event.url.clone());
+        if event.same_document {
+            return;
+        }
+        match event.kind {
+            Lifecycle::LoadStarted => {
+                self.loading = true;
+                self.dom_content_loaded = false;
+                self.progress = None;
+                self.success = None;
+            }
+            Lifecycle::DomContentLoaded => self.dom_content_loaded = true,
+            Lifecycle::LoadProgress => self.progress = event.progress,
+            Lifecycle::LoadFinished => {
+                self.loading = false;
+                self.progress = self.progress.map(|_| 1.0);
+                self.success = event.success;
+            }
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use gtk::hook;
+
+/// No progress estimate is available here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn hook(_webview: &wry::WebView, _proxy: &crate::utils::FrameEventLoopProxy) {}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod gtk {
+    use webkit2gtk::WebViewExt;
+    use wry::WebViewExtUnix;
+
+    use super::{Lifecycle, NavigationEvent};
+    use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+    /// Sends WebKit's load progress estimates as `load-progress`.
+    pub fn hook(webview: &wry::WebView, proxy: &FrameEventLoopProxy) {
+        let proxy = proxy.clone();
+        webview
+            .webview()
+            .connect_estimated_load_progress_notify(move |webview| {
+                let mut event = NavigationEvent::new(
+                    Lifecycle::LoadProgress,
+                    webview.uri().map(String::from).unwrap_or_default(),
+                );
+                event.progress = Some(webview.estimated_load_progress());
+                let _ = proxy.send_event(UserEvent::Navigation(Box::new(event)));
+            });
+    }
+}
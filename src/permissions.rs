@@ -0,0 +1,357 @@
+//! Camera, microphone and location requests from `getUserMedia` and
+//! `navigator.geolocation`, decided by the `permissions` option.
+//!
+//! Each kind has a [`Policy`]. Under `ask`, a [`PermissionRequest`] goes out
+//! as `permission-requested` to `pyframe.events()` and, with `timeout_ms`,
+//! to the page; `grant_permission(id, allow, remember)` answers it, and it
+//! is denied once the timeout passes. A remembered answer is kept per
+//! origin in the `store_*` store and decides later requests without asking.
+//! The page sees every decision as `permission-resolved`.
+//!
+//! Only WebKitGTK hands requests to the app. WebView2 and WKWebView keep
+//! their own prompts; `get_permission_policy` reports `routed: false` there.
+//! With `mock_geolocation` the location goes through this on every
+//! backend, see [`crate::geolocation`].
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::kv_store::KvStore;
+
+/// How long an `ask` request waits for `grant_permission` by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Prefix of remembered answers in the key-value store, followed by
+/// `<kind>:<origin>`.
+const STORE_PREFIX: &str = "permission:";
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    Allow,
+    #[default]
+    Deny,
+    /// Emit `permission-requested` and wait for `grant_permission`.
+    Ask,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PermissionsConfig {
+    /// Camera and microphone; `deny` if unset.
+    pub media: Policy,
+    /// `navigator.geolocation`; `deny` if unset.
+    pub geolocation: Policy,
+    /// How long an `ask` request waits; 30 s if unset.
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionKind {
+    Media,
+    Geolocation,
+}
+
+pub type RequestId = u64;
+
+/// A new request id.
+pub fn next_id() -> RequestId {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A request from the WebView, as the `permission-requested` payload.
+#[derive(Serialize, Clone, Debug)]
+pub struct PermissionRequest {
+    pub id: RequestId,
+    pub kind: PermissionKind,
+    /// Origin of the page asking; `None` for `about:blank` and the like.
+    pub origin: Option<String>,
+    /// For media, which devices are asked for.
+    pub audio: bool,
+    pub video: bool,
+}
+
+/// How a request was decided, for `permission-resolved`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    Policy,
+    Remembered,
+    Answered,
+    Timeout,
+}
+
+struct Pending {
+    request: PermissionRequest,
+    deadline: Instant,
+}
+
+pub struct Permissions {
+    config: PermissionsConfig,
+    timeout: Duration,
+    pending: HashMap<RequestId, Pending>,
+}
+
+impl Permissions {
+    pub fn new(config: &PermissionsConfig) -> Self {
+        Self {
+            config: config.clone(),
+            timeout: config
+                .timeout_ms
+                .map_or(DEFAULT_TIMEOUT, Duration::from_millis),
+            pending: HashMap::new(),
+        }
+    }
+
+    fn policy(&self, kind: PermissionKind) -> Policy {
+        match kind {
+            PermissionKind::Media => self.config.media,
+            PermissionKind::Geolocation => self.config.geolocation,
+        }
+    }
+
+    /// Decides `request` from the policy or a remembered answer, or keeps it
+    /// for `grant_permission` and returns `None`.
+    pub fn decide(
+        &mut self,
+        request: PermissionRequest,
+        store: &mut KvStore,
+        now: Instant,
+    ) -> Option<(bool, Reason)> {
+        match self.policy(request.kind) {
+            Policy::Allow => return Some((true, Reason::Policy)),
+            Policy::Deny => return Some((false, Reason::Policy)),
+            Policy::Ask => {}
+        }
+        if let Some(key) = store_key(&request) {
+            if let Ok(Some(Value::Bool(allow))) = store.get(&key) {
+                return Some((allow, Reason::Remembered));
+            }
+        }
+        self.pending.insert(
+            request.id,
+            Pending {
+                request,
+                deadline: now + self.timeout,
+            },
+        );
+        None
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Takes the pending request `id` for `grant_permission`, remembering
+    /// the answer for its origin if asked to.
+    pub fn answer(
+        &mut self,
+        id: RequestId,
+        allow: bool,
+        remember: bool,
+        store: &mut KvStore,
+        now: Instant,
+    ) -> Result<PermissionRequest> {
+        let pending = self
+            .pending
+            .remove(&id)
+            .ok_or_else(|| anyhow!("No permission request {} is pending", id))?;
+        if remember {
+            let key = store_key(&pending.request)
+                .ok_or_else(|| anyhow!("The request has no origin to remember it for"))?;
+            store.set(key, Value::Bool(allow), now)?;
+        }
+        Ok(pending.request)
+    }
+
+    /// Pending requests past their deadline, which are to be denied.
+    pub fn expired(&mut self, now: Instant) -> Vec<PermissionRequest> {
+        let ids: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| self.pending.remove(&id))
+            .map(|pending| pending.request)
+            .collect()
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.deadline).min()
+    }
+
+    /// For `get_permission_policy`: the policies, pending requests and
+    /// remembered answers by kind and origin.
+    pub fn describe(&self, store: &mut KvStore) -> Value {
+        let mut remembered: HashMap<String, HashMap<String, bool>> = HashMap::new();
+        for key in store.keys().unwrap_or_default() {
+            let Some((kind, origin)) = key
+                .strip_prefix(STORE_PREFIX)
+                .and_then(|rest| rest.split_once(':'))
+            else {
+                continue;
+            };
+            if let Ok(Some(Value::Bool(allow))) = store.get(&key) {
+                remembered
+                    .entry(kind.to_string())
+                    .or_default()
+                    .insert(origin.to_string(), allow);
+            }
+        }
+        let pending: Vec<_> = self.pending.values().map(|p| &p.request).collect();
+        json!({
+            "media": self.config.media,
+            "geolocation": self.config.geolocation,
+            "timeout_ms": self.timeout.as_millis() as u64,
+            "routed": ROUTED,
+            "pending": pending,
+            "remembered": remembered,
+        })
+    }
+}
+
+fn store_key(request: &PermissionRequest) -> Option<String> {
+    let kind = serde_json::to_value(request.kind).ok()?;
+    Some(format!(
+        "{}{}:{}",
+        STORE_PREFIX,
+        kind.as_str()?,
+        request.origin.as_deref()?
+    ))
+}
+
+/// `scheme://host[:port]` of `url`, `None` for opaque origins.
+pub fn origin_of(url: &str) -> Option<String> {
+    let origin = url::Url::parse(url).ok()?.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+const ROUTED: bool = true;
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+const ROUTED: bool = false;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use gtk::{hook, resolve};
+
+/// Requests go to the backend's own prompt here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn hook(_webview: &wry::WebView, _proxy: &crate::utils::FrameEventLoopProxy) {}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn resolve(_id: RequestId, _allow: bool) {}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod gtk {
+    use std::{cell::RefCell, collections::HashMap};
+
+    use webkit2gtk::{
+        glib::prelude::{Cast, ObjectExt},
+        GeolocationPermissionRequest, PermissionRequest as Request, PermissionRequestExt,
+        UserMediaPermissionRequest, UserMediaPermissionRequestExt, WebViewExt,
+    };
+    use wry::WebViewExtUnix;
+
+    use super::{next_id, origin_of, PermissionKind, PermissionRequest, RequestId};
+    use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+    thread_local! {
+        /// Requests the event loop hasn't decided yet. GObjects stay on
+        /// this thread; the event carries the id.
+        static WAITING: RefCell<HashMap<RequestId, Request>> = RefCell::new(HashMap::new());
+    }
+
+    /// Hands `webview`'s media and location requests to the event loop.
+    pub fn hook(webview: &wry::WebView, proxy: &FrameEventLoopProxy) {
+        let proxy = proxy.clone();
+        webview
+            .webview()
+            .connect_permission_request(move |webview, request| {
+                let (kind, audio, video) = if let Some(media) =
+                    request.downcast_ref::<UserMediaPermissionRequest>()
+                {
+                    let (audio, video) = (media.is_for_audio_device(), media.is_for_video_device());
+                    (PermissionKind::Media, audio, video)
+                } else if request.is::<GeolocationPermissionRequest>() {
+                    (PermissionKind::Geolocation, false, false)
+                } else {
+                    // Not ours; WebKit's default applies.
+                    return false;
+                };
+                let id = next_id();
+                let event = PermissionRequest {
+                    id,
+                    kind,
+                    origin: webview.uri().and_then(|uri| origin_of(&uri)),
+                    audio,
+                    video,
+                };
+                WAITING.with(|waiting| waiting.borrow_mut().insert(id, request.clone()));
+                if proxy
+                    .send_event(UserEvent::PermissionRequested(Box::new(event)))
+                    .is_err()
+                {
+                    resolve(id, false);
+                }
+                true
+            });
+    }
+
+    /// Allows or denies the request `id`; nothing if it was already decided.
+    pub fn resolve(id: RequestId, allow: bool) {
+        let Some(request) = WAITING.with(|waiting| waiting.borrow_mut().remove(&id)) else {
+            return;
+        };
+        if allow {
+            request.allow();
+        } else {
+            request.deny();
+        }
+    }
+}
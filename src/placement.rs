@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use tao::{
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    monitor::MonitorHandle,
+    window::{Window, WindowBuilder},
+};
+
+use crate::window_state::WindowState;
+
+/// Size of a placed window that has no saved geometry.
+const DEFAULT_SIZE: LogicalSize<f64> = LogicalSize::new(800.0, 600.0);
+/// Offset between cascaded windows.
+const CASCADE_STEP: f64 = 32.0;
+
+/// A monitor by its index in `list_monitors` or by its name.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MonitorSelector {
+    Index(usize),
+    Name(String),
+}
+
+/// Where on its monitor a new window opens.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionStrategy {
+    Center,
+    /// The geometry saved by `persist_window_state`; centered without one.
+    Remember,
+    /// Offset from the monitor's top-left corner by the window's index.
+    Cascade,
+}
+
+/// The requested monitor, or the primary one if it isn't connected.
+pub fn select_monitor(
+    selector: Option<&MonitorSelector>,
+    monitors: &[MonitorHandle],
+    primary: Option<MonitorHandle>,
+) -> Option<MonitorHandle> {
+    let selected = match selector {
+        Some(MonitorSelector::Index(index)) => monitors.get(*index),
+        Some(MonitorSelector::Name(name)) => monitors
+            .iter()
+            .find(|m| m.name().as_deref() == Some(name.as_str())),
+        None => None,
+    };
+    if selected.is_none() {
+        if let Some(selector) = selector {
+            crate::log_warn!(format!(
+                "Monitor {:?} not connected, using the primary monitor",
+                selector
+            ));
+        }
+    }
+    selected
+        .cloned()
+        .or(primary)
+        .or_else(|| monitors.first().cloned())
+}
+
+/// Where a window asks to open: the `monitor` and `position_strategy`
+/// options of the app or of `open_window`, and the window's logical size.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Placement<'a> {
+    pub monitor: Option<&'a MonitorSelector>,
+    pub strategy: Option<PositionStrategy>,
+    /// [`DEFAULT_SIZE`] if unset.
+    pub size: Option<LogicalSize<f64>>,
+}
+
+/// Applies `placement` to `builder`. `cascade` is the window's index for
+/// [`PositionStrategy::Cascade`].
+///
+/// Position and size are computed in the target monitor's physical pixels,
+/// so a window opened on a monitor with a different scale factor than the
+/// primary one still gets its logical size and lands where it should.
+pub fn place(
+    builder: WindowBuilder,
+    placement: Placement,
+    monitors: &[MonitorHandle],
+    primary: Option<MonitorHandle>,
+    saved: Option<WindowState>,
+    cascade: usize,
+) -> WindowBuilder {
+    let strategy = placement.strategy;
+    if let Some(saved) = saved {
+        if matches!(strategy, None | Some(PositionStrategy::Remember)) {
+            return saved.restore(builder, monitors, primary);
+        }
+    }
+    if placement.monitor.is_none() && strategy.is_none() {
+        return builder;
+    }
+    let Some(monitor) = select_monitor(placement.monitor, monitors, primary) else {
+        return builder;
+    };
+    let (size, position) = layout(
+        monitor.position(),
+        monitor.size(),
+        monitor.scale_factor(),
+        placement.size.unwrap_or(DEFAULT_SIZE),
+        strategy,
+        cascade,
+    );
+    builder.with_inner_size(size).with_position(position)
+}
+
+/// Size and position of a window of logical `size` on a monitor at
+/// `origin` with `bounds` and `scale`, kept inside the monitor.
+fn layout(
+    origin: PhysicalPosition<i32>,
+    bounds: PhysicalSize<u32>,
+    scale: f64,
+    size: LogicalSize<f64>,
+    strategy: Option<PositionStrategy>,
+    cascade: usize,
+) -> (PhysicalSize<u32>, PhysicalPosition<i32>) {
+    let size: PhysicalSize<u32> = size.to_physical(scale);
+    let size = PhysicalSize::new(size.width.min(bounds.width), size.height.min(bounds.height));
+    let position = match strategy {
+        Some(PositionStrategy::Cascade) => {
+            let offset = (CASCADE_STEP * scale) as i32 * (cascade as i32 + 1);
+            let max_x = (bounds.width - size.width) as i32;
+            let max_y = (bounds.height - size.height) as i32;
+            PhysicalPosition::new(origin.x + offset.min(max_x), origin.y + offset.min(max_y))
+        }
+        _ => PhysicalPosition::new(
+            origin.x + ((bounds.width - size.width) / 2) as i32,
+            origin.y + ((bounds.height - size.height) / 2) as i32,
+        ),
+    };
+    (size, position)
+}
+
+/// Index, name and geometry of a monitor, as listed by `list_monitors`.
+/// Positions and sizes are physical pixels.
+#[derive(Serialize, Debug)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+pub fn describe(index: usize, monitor: &MonitorHandle, primary: bool) -> MonitorInfo {
+    let position = monitor.position();
+    let size = monitor.size();
+    MonitorInfo {
+        index,
+        name: monitor.name(),
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        scale_factor: monitor.scale_factor(),
+        primary,
+    }
+}
+
+/// The monitor `window` landed on, as `list_monitors` lists it.
+pub fn landed_on(window: &Window) -> Option<MonitorInfo> {
+    let monitor = window.current_monitor()?;
+    let index = window
+        .available_monitors()
+        .position(|other| other == monitor)?;
+    let primary = window.primary_monitor().as_ref() == Some(&monitor);
+    Some(describe(index, &monitor, primary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FHD: PhysicalSize<u32> = PhysicalSize::new(1920, 1080);
+
+    #[test]
+    fn centered_on_its_monitor() {
+        let (size, position) = layout(
+            PhysicalPosition::new(1920, 0),
+            FHD,
+            1.0,
+            LogicalSize::new(640.0, 480.0),
+            Some(PositionStrategy::Center),
+            0,
+        );
+        assert_eq!(size, PhysicalSize::new(640, 480));
+        assert_eq!(position, PhysicalPosition::new(1920 + 640, 300));
+    }
+
+    #[test]
+    fn logical_size_on_a_scaled_monitor() {
+        let (size, position) = layout(
+            PhysicalPosition::new(-3840, 0),
+            PhysicalSize::new(3840, 2160),
+            2.0,
+            DEFAULT_SIZE,
+            None,
+            0,
+        );
+        assert_eq!(size, PhysicalSize::new(1600, 1200));
+        assert_eq!(position, PhysicalPosition::new(-3840 + 1120, 480));
+    }
+
+    #[test]
+    fn cascade_steps_and_stays_inside() {
+        let cascade = |index| {
+            layout(
+                PhysicalPosition::new(0, 0),
+                FHD,
+                1.5,
+                LogicalSize::new(800.0, 600.0),
+                Some(PositionStrategy::Cascade),
+                index,
+            )
+        };
+        assert_eq!(cascade(0).1, PhysicalPosition::new(48, 48));
+        assert_eq!(cascade(1).1, PhysicalPosition::new(96, 96));
+        // 1200x900 physical leaves 720x180 to move in.
+        assert_eq!(cascade(10).1, PhysicalPosition::new(528, 180));
+    }
+
+    #[test]
+    fn larger_than_the_monitor_is_clamped() {
+        let (size, position) = layout(
+            PhysicalPosition::new(0, 0),
+            PhysicalSize::new(1280, 720),
+            1.0,
+            LogicalSize::new(1600.0, 900.0),
+            Some(PositionStrategy::Center),
+            0,
+        );
+        assert_eq!(size, PhysicalSize::new(1280, 720));
+        assert_eq!(position, PhysicalPosition::new(0, 0));
+    }
+
+    #[test]
+    fn window_options_take_monitor_and_strategy() {
+        let options: crate::window_commands::WindowOptions =
+            serde_json::from_value(serde_json::json!({
+                "label": "output",
+                "monitor": "HDMI-1",
+                "position_strategy": "cascade",
+            }))
+            .unwrap();
+        assert_eq!(
+            options.monitor,
+            Some(MonitorSelector::Name("HDMI-1".into()))
+        );
+        assert_eq!(options.position_strategy, Some(PositionStrategy::Cascade));
+        let options: crate::window_commands::WindowOptions =
+            serde_json::from_value(serde_json::json!({ "monitor": 1 })).unwrap();
+        assert_eq!(options.monitor, Some(MonitorSelector::Index(1)));
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// How often the background task refreshes the process information.
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Resource usage of the Rust process.
+#[derive(Serialize, Clone, Debug)]
+pub struct ProcessStats {
+    /// CPU usage since the previous refresh; 100.0 is one fully used core.
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub virtual_bytes: u64,
+    /// Only reported on Linux, 0 elsewhere.
+    pub thread_count: u32,
+}
+
+/// Owns the `sysinfo::System` so APIs read cached values instead of
+/// refreshing on every call.
+pub struct ProcessMonitor {
+    system: System,
+    pid: Pid,
+}
+
+impl ProcessMonitor {
+    pub fn new() -> Result<Self> {
+        let pid = sysinfo::get_current_pid().map_err(|e| anyhow!(e))?;
+        let mut monitor = Self {
+            system: System::new(),
+            pid,
+        };
+        monitor.refresh();
+        Ok(monitor)
+    }
+
+    pub fn refresh(&mut self) {
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[self.pid]),
+            true,
+            ProcessRefreshKind::nothing()
+                .with_cpu()
+                .with_memory()
+                .with_tasks(),
+        );
+    }
+
+    pub fn stats(&self) -> Result<ProcessStats> {
+        let process = self
+            .system
+            .process(self.pid)
+            .ok_or_else(|| anyhow!("Process {:?} not found", self.pid))?;
+        Ok(ProcessStats {
+            cpu_percent: process.cpu_usage() as f64,
+            rss_bytes: process.memory(),
+            virtual_bytes: process.virtual_memory(),
+            thread_count: process.tasks().map_or(0, |tasks| tasks.len() as u32),
+        })
+    }
+}
@@ -0,0 +1,186 @@
+//! Long-running child processes behind `process_spawn`. Output is streamed
+//! line by line as [`UserEvent::ProcessOutput`] and the exit status as
+//! [`UserEvent::ProcessExited`]; the event loop emits them to the frontend as
+//! `pyframe:process-output` and `pyframe:process-exit`.
+//!
+//! Lines end at `\n` or `\r`, so progress lines that tools like ffmpeg keep
+//! rewriting arrive one by one.
+
+use std::{collections::HashMap, path::PathBuf, process::Stdio};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::{Child, Command},
+    runtime::Handle,
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+pub type Pid = u32;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SpawnOptions {
+    pub cwd: Option<PathBuf>,
+    /// Variables set for the child.
+    pub env: HashMap<String, String>,
+    /// Start from this process's environment instead of an empty one. Without
+    /// it a command listed by name needs `PATH` in `env` to be found.
+    pub inherit_env: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Kill switches of the running children. Dropping one kills its child.
+#[derive(Default)]
+pub struct Processes {
+    running: HashMap<Pid, oneshot::Sender<()>>,
+}
+
+impl Processes {
+    /// Starts `command`, which must be in `allowed`, and returns its pid.
+    pub fn spawn(
+        &mut self,
+        allowed: &[String],
+        command: String,
+        args: Vec<String>,
+        options: SpawnOptions,
+        rt: &Handle,
+        proxy: FrameEventLoopProxy,
+    ) -> Result<Pid> {
+        crate::exec::check_allowed(allowed, &command)?;
+        let mut cmd = Command::new(&command);
+        cmd.args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Covers tasks dropped by the runtime shutting down.
+            .kill_on_drop(true);
+        if !options.inherit_env {
+            cmd.env_clear();
+        }
+        cmd.envs(&options.env);
+        if let Some(cwd) = &options.cwd {
+            cmd.current_dir(cwd);
+        }
+        // `Command::spawn` registers the child with the runtime's reaper.
+        let _guard = rt.enter();
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start {:?}: {}", command, e))?;
+        let pid = child
+            .id()
+            .ok_or_else(|| anyhow!("{:?} exited immediately", command))?;
+
+        let stdout = stream_lines(pid, OutputStream::Stdout, child.stdout.take(), &proxy);
+        let stderr = stream_lines(pid, OutputStream::Stderr, child.stderr.take(), &proxy);
+        let (kill, killed) = oneshot::channel();
+        crate::tasks::spawn_on(
+            "process_wait",
+            rt,
+            wait(pid, child, killed, [stdout, stderr], proxy),
+        );
+        self.running.insert(pid, kill);
+        Ok(pid)
+    }
+
+    /// Returns `false` if `pid` isn't a running child of `process_spawn`.
+    pub fn kill(&mut self, pid: Pid) -> bool {
+        match self.running.remove(&pid) {
+            Some(kill) => {
+                let _ = kill.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets `pid` once its exit was delivered.
+    pub fn exited(&mut self, pid: Pid) {
+        self.running.remove(&pid);
+    }
+
+    pub fn kill_all(&mut self) {
+        self.running.clear();
+    }
+}
+
+/// Waits for `child`, or kills it once `killed` resolves or its sender is
+/// dropped, and reports the exit after the last line of output.
+async fn wait(
+    pid: Pid,
+    mut child: Child,
+    killed: oneshot::Receiver<()>,
+    output: [JoinHandle<()>; 2],
+    proxy: FrameEventLoopProxy,
+) {
+    let status = tokio::select! {
+        status = child.wait() => status,
+        _ = killed => {
+            let _ = child.start_kill();
+            child.wait().await
+        }
+    };
+    for stream in output {
+        let _ = stream.await;
+    }
+    let code = match status {
+        Ok(status) => status.code(),
+        Err(e) => {
+            crate::log_warn!(format!("Waiting for process {} failed: {}", pid, e));
+            None
+        }
+    };
+    let _ = proxy.send_event(UserEvent::ProcessExited(pid, code));
+}
+
+fn stream_lines<R: AsyncRead + Unpin + Send + 'static>(
+    pid: Pid,
+    stream: OutputStream,
+    reader: Option<R>,
+    proxy: &FrameEventLoopProxy,
+) -> JoinHandle<()> {
+    let proxy = proxy.clone();
+    crate::tasks::spawn("process_output", async move {
+        let Some(mut reader) = reader else {
+            return;
+        };
+        let send = |line: &[u8]| {
+            let line = String::from_utf8_lossy(line).into_owned();
+            proxy
+                .send_event(UserEvent::ProcessOutput(pid, stream, line))
+                .is_ok()
+        };
+        let mut pending = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let read = match reader.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            for &byte in &buf[..read] {
+                if byte == b'\n' || byte == b'\r' {
+                    // Empty lines are dropped, so `\r\n` ends one line, not two.
+                    if !pending.is_empty() && !send(&pending) {
+                        return;
+                    }
+                    pending.clear();
+                } else {
+                    pending.push(byte);
+                }
+            }
+        }
+        if !pending.is_empty() {
+            send(&pending);
+        }
+    })
+}
@@ -0,0 +1,239 @@
+//! Version and feature negotiation at the start of a TCP connection.
+//!
+//! A client's first frame is a [`Hello`]: the newest protocol version it
+//! speaks, the optional [`Feature`]s it wants and the `PYFRAME_TOKEN` the
+//! app was launched with. The server answers with `hello-ack`, carrying the
+//! version and the features both sides support, or with a `close` frame
+//! and ends the connection. Code that depends on a feature checks the
+//! connection's [`Capabilities`] instead of growing flags of its own.
+//! With [`Feature::Binary`], a request may be sent as a binary frame
+//! (see [`split_attachment`]) to carry raw bytes for `Bytes` parameters.
+//! With [`Feature::Emit`], `emit` frames push events to the pages without
+//! a response. With [`Feature::Timing`], responses carry how long the
+//! request waited and ran.
+//! The hello may also name the `client`, which keeps its idempotency keys
+//! valid across reconnects.
+//!
+//! A connection that starts with a request instead is served as version 0
+//! and reported as `protocol-deprecated`, but only if the app was started
+//! without a token: version 0 has no way to present one, so with a token
+//! it is closed with [`close::INVALID_TOKEN`]. That goes away in the next
+//! release.
+
+use std::sync::OnceLock;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// The newest version this server speaks.
+pub const VERSION: u32 = 1;
+/// The oldest version a [`Hello`] may negotiate.
+pub const MIN_VERSION: u32 = 1;
+/// Environment variable with the token clients must present.
+pub const TOKEN_ENV: &str = "PYFRAME_TOKEN";
+
+/// Features the server can negotiate.
+const SUPPORTED: &[Feature] = &[
+    Feature::Streaming,
+    Feature::Batch,
+    Feature::Binary,
+    Feature::Emit,
+    Feature::Timing,
+];
+
+/// First byte of a binary frame; JSON frames start with `[` or `{`.
+pub const BINARY_MARKER: u8 = 0;
+/// Largest attachment of a binary frame.
+pub const MAX_ATTACHMENT_BYTES: usize = 64 * 1024 * 1024;
+
+/// Reason codes of a `close` frame, in the range WebSockets leave to
+/// applications, so the Python WebSocket server can reuse them.
+pub mod close {
+    /// The first frame was neither a request nor a valid hello.
+    pub const BAD_HELLO: u16 = 4000;
+    pub const INVALID_TOKEN: u16 = 4001;
+    pub const UNSUPPORTED_VERSION: u16 = 4002;
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Compression,
+    Msgpack,
+    /// Requests with a binary attachment, see [`split_attachment`].
+    Binary,
+    /// Push events on the connection, i.e. `__subscribe_events`.
+    Streaming,
+    /// Several requests in one `{"type": "batch", "requests": [...]}` frame.
+    Batch,
+    /// `{"type": "emit", ...}` frames pushing events to the pages, see
+    /// [`crate::emit`].
+    Emit,
+    /// `meta` with the timing of the request in every response, see
+    /// [`crate::timing`].
+    Timing,
+    /// Anything newer than this server; never negotiated.
+    #[serde(other, skip_serializing)]
+    Unknown,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename = "hello")]
+pub struct Hello {
+    pub version: u32,
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    pub token: Option<String>,
+    /// Stays the same across reconnects; scopes idempotency keys.
+    pub client: Option<String>,
+}
+
+/// What a connection negotiated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: u32,
+    pub features: Vec<Feature>,
+    /// From the hello; `None` for version 0.
+    pub client: Option<String>,
+}
+
+impl Capabilities {
+    /// What clients got before there was a hello.
+    pub fn legacy() -> Self {
+        Self {
+            version: 0,
+            features: vec![Feature::Streaming],
+            client: None,
+        }
+    }
+
+    pub fn has(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// The `hello-ack` frame.
+    pub fn ack(&self) -> Value {
+        json!({ "type": "hello-ack", "version": self.version, "features": self.features })
+    }
+}
+
+/// Why a hello was refused, sent as a `close` frame.
+#[derive(Debug)]
+pub struct Rejection {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl Rejection {
+    fn new(code: u16, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn frame(&self) -> Value {
+        json!({ "type": "close", "code": self.code, "reason": self.reason })
+    }
+}
+
+/// How a connection's first frame is treated.
+pub enum Greeting {
+    Accepted(Capabilities),
+    Rejected(Rejection),
+    /// A version 0 request, to be handled like any later one.
+    Legacy,
+}
+
+/// Negotiates from the first frame of a connection.
+pub fn greet(frame: &[u8]) -> Greeting {
+    let Ok(value) = serde_json::from_slice::<Value>(frame) else {
+        return Greeting::Rejected(Rejection::new(close::BAD_HELLO, "First frame is not JSON"));
+    };
+    if value.is_array() {
+        if expected_token().is_some() {
+            return Greeting::Rejected(Rejection::new(
+                close::INVALID_TOKEN,
+                "A token is required; send a hello first",
+            ));
+        }
+        return Greeting::Legacy;
+    }
+    let hello = match serde_json::from_value::<Hello>(value) {
+        Ok(hello) => hello,
+        Err(e) => {
+            return Greeting::Rejected(Rejection::new(
+                close::BAD_HELLO,
+                format!("Invalid hello: {}", e),
+            ))
+        }
+    };
+    match negotiate(&hello, expected_token()) {
+        Ok(capabilities) => Greeting::Accepted(capabilities),
+        Err(rejection) => Greeting::Rejected(rejection),
+    }
+}
+
+fn negotiate(hello: &Hello, token: Option<&str>) -> Result<Capabilities, Rejection> {
+    if token.is_some_and(|token| {
+        !hello
+            .token
+            .as_deref()
+            .is_some_and(|t| tokens_match(t, token))
+    }) {
+        return Err(Rejection::new(close::INVALID_TOKEN, "Invalid token"));
+    }
+    let version = hello.version.min(VERSION);
+    if version < MIN_VERSION {
+        return Err(Rejection::new(
+            close::UNSUPPORTED_VERSION,
+            format!(
+                "Protocol version {} is not supported, the server speaks {} to {}",
+                hello.version, MIN_VERSION, VERSION
+            ),
+        ));
+    }
+    let features = SUPPORTED
+        .iter()
+        .copied()
+        .filter(|feature| hello.features.contains(feature))
+        .collect();
+    Ok(Capabilities {
+        version,
+        features,
+        client: hello.client.clone(),
+    })
+}
+
+/// Compares `given` with `expected` in a time that doesn't depend on where
+/// they differ, so the token can't be guessed byte by byte.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    let diff = given
+        .iter()
+        .zip(expected)
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+    std::hint::black_box(diff) == 0 && given.len() == expected.len()
+}
+
+/// The token clients must present, `None` if the app was started without one.
+pub fn expected_token() -> Option<&'static str> {
+    static TOKEN: OnceLock<Option<String>> = OnceLock::new();
+    TOKEN
+        .get_or_init(|| std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()))
+        .as_deref()
+}
+
+/// Splits a binary frame, `[BINARY_MARKER, JSON length (u32 BE), request
+/// JSON, attachment]`, into the request and the attachment. `None` if the
+/// frame is too short for its length.
+pub fn split_attachment(frame: Vec<u8>) -> Option<(Bytes, Bytes)> {
+    let frame = Bytes::from(frame);
+    let len = u32::from_be_bytes(frame.get(1..5)?.try_into().ok()?) as usize;
+    let end = 5usize.checked_add(len)?;
+    if end > frame.len() {
+        return None;
+    }
+    Some((frame.slice(5..end), frame.slice(end..)))
+}
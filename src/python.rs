@@ -0,0 +1,222 @@
+//! The `pygcc` extension module, over [`crate::WebFrame`].
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use pyo3::{exceptions::PyTypeError, prelude::*, types::PyDict};
+use serde_json::{json, Value};
+
+use crate::{
+    api_manager::Unsupported, connections::BindFailed, errors, frame_loop::LoopUnavailable,
+    lock_force,
+};
+
+/// A Python object as JSON, through the `json` module.
+pub(crate) fn to_json(py: Python<'_>, obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let text: String = py
+        .import("json")?
+        .call_method1("dumps", (obj,))?
+        .extract()?;
+    serde_json::from_str(&text).map_err(|e| errors::BridgeProtocolError::new_err(e.to_string()))
+}
+
+/// The arguments of an `invoke`: `kwargs` by name, or `args`, a list of
+/// positional arguments, a dict of named ones or a single positional one.
+pub(crate) fn call_args(
+    py: Python<'_>,
+    args: Option<&Bound<'_, PyAny>>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Value> {
+    let args = args.map(|args| to_json(py, args)).transpose()?;
+    let kwargs = kwargs
+        .filter(|kwargs| !kwargs.is_empty())
+        .map(|kwargs| to_json(py, kwargs.as_any()))
+        .transpose()?;
+    match (args, kwargs) {
+        (Some(_), Some(_)) => Err(PyTypeError::new_err(
+            "invoke takes args or keyword arguments, not both",
+        )),
+        (_, Some(named)) => Ok(named),
+        (None | Some(Value::Null), None) => Ok(json!([])),
+        (Some(args @ (Value::Array(_) | Value::Object(_))), None) => Ok(args),
+        (Some(arg), None) => Ok(json!([arg])),
+    }
+}
+
+pub(crate) fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(py
+        .import("json")?
+        .call_method1("loads", (value.to_string(),))?
+        .unbind())
+}
+
+/// Sets Python's `multiprocessing.Event`, and keeps the `KeyboardInterrupt`
+/// or other signal error raised meanwhile for `create_webframe` to raise.
+struct ExitEvent {
+    event: Py<PyAny>,
+    interrupted: Mutex<Option<PyErr>>,
+}
+
+impl crate::ShutdownNotifier for ExitEvent {
+    fn notify(&self) {
+        Python::with_gil(|py| {
+            if let Err(e) = self.event.call_method0(py, "set") {
+                e.print(py);
+            }
+            if let Err(e) = py.check_signals() {
+                *lock_force!(self.interrupted) = Some(e);
+            }
+        });
+    }
+}
+
+/// The webframe of `create_webframe` and `launch_webframe`.
+fn builder(
+    html: String,
+    host: String,
+    port: u16,
+    options: Option<String>,
+    on_ready: Option<Py<PyAny>>,
+) -> crate::WebFrameBuilder {
+    let mut builder = crate::WebFrame::builder().html(html).host(host).port(port);
+    if let Some(options) = options {
+        builder = builder.options_json(options);
+    }
+    if let Some(on_ready) = on_ready {
+        builder = builder.on_ready(move |info| {
+            Python::with_gil(|py| {
+                let result = serde_json::to_value(info)
+                    .map_err(|e| errors::BridgeProtocolError::new_err(e.to_string()))
+                    .and_then(|info| to_py(py, &info))
+                    .and_then(|info| on_ready.call1(py, (info,)));
+                if let Err(e) = result {
+                    e.print(py);
+                }
+            });
+        });
+    }
+    builder
+}
+
+/// The Python exception for an error of the webframe.
+fn py_error(e: anyhow::Error) -> anyhow::Error {
+    if let Some(bind) = e.downcast_ref::<BindFailed>() {
+        errors::BindError::new_err((e.to_string(), bind.port)).into()
+    } else if e.is::<LoopUnavailable>() {
+        errors::EventLoopError::new_err(e.to_string()).into()
+    } else if e.is::<Unsupported>() {
+        errors::UnsupportedError::new_err(e.to_string()).into()
+    } else {
+        e
+    }
+}
+
+/// `options` is a JSON object with the fields of [`crate::config::AppConfig`].
+/// `on_ready` is called with a dict of [`crate::FrameInfo`] once the window
+/// is open. Returns once the frame is closed; the next call opens a new one
+/// on the same event loop, from the same thread.
+#[pyfunction]
+#[pyo3(signature = (html, host, port, mp_event, options=None, on_ready=None))]
+fn create_webframe(
+    py: Python<'_>,
+    html: String,
+    host: String,
+    port: u16,
+    mp_event: Py<PyAny>,
+    options: Option<String>,
+    on_ready: Option<Py<PyAny>>,
+) -> Result<()> {
+    let exit_event = Arc::new(ExitEvent {
+        event: mp_event,
+        interrupted: Mutex::new(None),
+    });
+    let builder = builder(html, host, port, options, on_ready).on_shutdown(exit_event.clone());
+    // Released so `FrameHandle` can be used from other threads meanwhile.
+    py.allow_threads(|| builder.run()).map_err(py_error)?;
+    let interrupted = lock_force!(exit_event.interrupted).take();
+    match interrupted {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+/// `create_webframe` on the event loop's own thread, returning right away;
+/// Windows and Linux only. `mp_event`, if given, is set as the webframe
+/// closes.
+#[pyfunction]
+#[pyo3(signature = (html, host, port, mp_event=None, options=None, on_ready=None))]
+fn launch_webframe(
+    html: String,
+    host: String,
+    port: u16,
+    mp_event: Option<Py<PyAny>>,
+    options: Option<String>,
+    on_ready: Option<Py<PyAny>>,
+) -> Result<WebFrameThread> {
+    let mut builder = builder(html, host, port, options, on_ready);
+    if let Some(event) = mp_event {
+        builder = builder.on_shutdown(ExitEvent {
+            event,
+            interrupted: Mutex::new(None),
+        });
+    }
+    let frame = builder.spawn().map_err(py_error)?;
+    Ok(WebFrameThread {
+        frame: Mutex::new(Some(frame)),
+    })
+}
+
+/// The webframe of `launch_webframe`.
+#[pyclass(module = "pygcc")]
+struct WebFrameThread {
+    /// `None` once its result was raised or returned.
+    frame: Mutex<Option<crate::RunningFrame>>,
+}
+
+#[pymethods]
+impl WebFrameThread {
+    /// Whether the webframe is still open.
+    fn is_alive(&self) -> bool {
+        lock_force!(self.frame)
+            .as_ref()
+            .is_some_and(|frame| !frame.is_closed())
+    }
+
+    /// Waits until the webframe is closed, at most `timeout` seconds, and
+    /// returns whether it is; raises what `create_webframe` would have.
+    #[pyo3(signature = (timeout=None))]
+    fn join(&self, py: Python<'_>, timeout: Option<f64>) -> Result<bool> {
+        let result = py.allow_threads(|| {
+            let mut frame = lock_force!(self.frame);
+            let running = frame.as_ref()?;
+            let result = match timeout {
+                Some(timeout) => running.wait_timeout(Duration::from_secs_f64(timeout.max(0.0)))?,
+                None => running.wait(),
+            };
+            frame.take();
+            Some(result)
+        });
+        match result {
+            Some(result) => result.map_err(py_error).map(|()| true),
+            None => Ok(lock_force!(self.frame).is_none()),
+        }
+    }
+}
+
+/// A Python module implemented in Rust.
+#[pymodule]
+fn pygcc(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(create_webframe, m)?)?;
+    m.add_function(wrap_pyfunction!(launch_webframe, m)?)?;
+    m.add_class::<WebFrameThread>()?;
+    m.add_class::<crate::frame_handle::FrameHandle>()?;
+    #[cfg(feature = "tcp")]
+    m.add_class::<crate::bridge_client::BridgeClient>()?;
+    #[cfg(feature = "tcp")]
+    m.add_class::<crate::bridge_client::BridgeSubscription>()?;
+    errors::register(m)?;
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//! Who sent a request, for handlers that declare a `RequestContext`
+//! parameter.
+//!
+//! The connection's part, its id, principal and negotiated features, is
+//! made once per connection as a [`Peer`]; every request adds its trace id,
+//! target window and the time it arrived. Middleware registered with
+//! `ApiManager::add_middleware` sees the context before the handler and can
+//! attach typed [`Extensions`] to it, e.g. a rate limiter's bucket for the
+//! client, or refuse the request.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use crate::{api_manager::RequestMetadata, connections::ConnectionId, transport::IN_PROCESS};
+
+/// Whom a connection speaks for.
+#[derive(Clone, Debug)]
+pub struct Principal {
+    /// The `client` of the hello, `connection-<id>` if it named none,
+    /// `"page"` or `"frame-handle"` in-process, `"app"` for the app's own
+    /// calls such as timers.
+    pub client: String,
+    /// Whether it presented the launch's `PYFRAME_TOKEN` in its hello.
+    /// In-process callers are always authenticated.
+    pub authenticated: bool,
+}
+
+/// The sender of a connection's requests.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub connection: ConnectionId,
+    pub principal: Principal,
+    /// Features negotiated in the hello, e.g. `"binary"`; none in-process.
+    pub features: Vec<String>,
+}
+
+impl Peer {
+    /// A caller inside the process.
+    pub fn in_process(client: &str) -> Arc<Self> {
+        Arc::new(Self {
+            connection: IN_PROCESS,
+            principal: Principal {
+                client: client.to_string(),
+                authenticated: true,
+            },
+            features: Vec::new(),
+        })
+    }
+}
+
+/// Typed values middleware attaches to a request, one per type.
+#[derive(Clone, Default)]
+pub struct Extensions(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+#[allow(dead_code)]
+impl Extensions {
+    /// Adds `value`, replacing a previous one of the same type.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.0.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> bool {
+        self.0.remove(&TypeId::of::<T>()).is_some()
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Extensions({})", self.0.len())
+    }
+}
+
+/// What a handler knows about the request besides its arguments.
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    peer: Arc<Peer>,
+    /// The `trace_id` of the request's metadata, or a new one.
+    pub trace_id: String,
+    /// The window the call targets, the `id` from `get_window_state`;
+    /// `None` for the first.
+    pub window_id: Option<u64>,
+    /// When the request arrived, wall-clock time.
+    pub received_at: SystemTime,
+    #[allow(dead_code)]
+    pub extensions: Extensions,
+}
+
+impl RequestContext {
+    /// The context of a request from `peer` that arrived now.
+    pub fn new(peer: Arc<Peer>, metadata: &RequestMetadata) -> Self {
+        Self {
+            peer,
+            trace_id: metadata
+                .trace_id
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()),
+            window_id: metadata.window_id,
+            received_at: SystemTime::now(),
+            extensions: Extensions::default(),
+        }
+    }
+
+    pub fn connection(&self) -> ConnectionId {
+        self.peer.connection
+    }
+
+    pub fn principal(&self) -> &Principal {
+        &self.peer.principal
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.peer.features
+    }
+
+    #[allow(dead_code)]
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.peer.features.iter().any(|f| f == feature)
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use serde_json::{json, Value};
+
+    use crate::{protocol, testing::FakeApp};
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn hello(client: &str, features: &[&str]) -> Value {
+        json!({
+            "type": "hello",
+            "version": protocol::VERSION,
+            "features": features,
+            "token": protocol::expected_token(),
+            "client": client,
+        })
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_get_their_own_context() {
+        let app = FakeApp::new();
+        app.handle("whoami", |req| {
+            let context = req.context();
+            Ok(json!({
+                "connection": context.connection(),
+                "client": context.principal().client,
+                "features": context.features(),
+                "trace_id": context.trace_id,
+                "window_id": context.window_id,
+            }))
+        });
+        let mut a = app.connect_with(hello("a", &["batch"])).await.unwrap();
+        let mut b = app.connect_with(hello("b", &[])).await.unwrap();
+
+        // Everything is queued before the first answer. Ids are unique
+        // across connections while they wait.
+        a.send(&json!([1, "whoami", [], { "trace_id": "a-1", "window_id": 7 }]))
+            .await
+            .unwrap();
+        a.send(&json!([2, "whoami", []])).await.unwrap();
+        a.send(&json!([3, "whoami", []])).await.unwrap();
+        b.send(&json!([4, "whoami", [], { "trace_id": "b-1" }]))
+            .await
+            .unwrap();
+        let mut seen = HashMap::new();
+        for _ in 0..3 {
+            let frame = a.next_frame(WAIT).await.unwrap();
+            seen.insert(("a", frame[0].as_u64().unwrap()), frame[3].clone());
+        }
+        let frame = b.next_frame(WAIT).await.unwrap();
+        seen.insert(("b", frame[0].as_u64().unwrap()), frame[3].clone());
+
+        let a1 = &seen[&("a", 1)];
+        let (a2, a3, b1) = (&seen[&("a", 2)], &seen[&("a", 3)], &seen[&("b", 4)]);
+        assert_eq!(a1["client"], "a");
+        assert_eq!(a1["features"], json!(["batch"]));
+        assert_eq!(a1["trace_id"], "a-1");
+        assert_eq!(a1["window_id"], 7);
+        assert_eq!(a2["window_id"], Value::Null);
+        assert_eq!(a1["connection"], a2["connection"]);
+        // Requests without a trace id get one each.
+        assert_ne!(a2["trace_id"], a3["trace_id"]);
+        assert_eq!(b1["client"], "b");
+        assert_eq!(b1["features"], json!([]));
+        assert_eq!(b1["trace_id"], "b-1");
+        assert_ne!(a1["connection"], b1["connection"]);
+        a.close().await.unwrap();
+        b.close().await.unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn middleware_extensions_stay_with_their_request() {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        };
+
+        use crate::{api_manager::code, lanes::Priority, testing::LoopHarness};
+
+        struct Seq(u64);
+
+        let harness = LoopHarness::start_with(|manager| {
+            let next = Arc::new(AtomicU64::new(0));
+            manager.add_middleware(move |_req, context| {
+                context
+                    .extensions
+                    .insert(Seq(next.fetch_add(1, Ordering::Relaxed)));
+                Ok(())
+            });
+            manager.register_api("seq", |_ctx, req, _target, _flow| {
+                let context = req.context();
+                Ok(json!([
+                    context.extensions.get::<Seq>().map(|seq| seq.0),
+                    context.trace_id,
+                ]))
+            });
+        });
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                harness
+                    .calls()
+                    .call("frame-handle", "seq", json!([]), Priority::Normal)
+            })
+            .collect();
+        let responses = harness
+            .runtime()
+            .block_on(async { futures::future::join_all(calls).await });
+        let mut seqs = Vec::new();
+        let mut traces = Vec::new();
+        for response in &responses {
+            assert_eq!(response.code(), code::OK, "{}", response.msg());
+            seqs.push(response.result()[0].as_u64().unwrap());
+            traces.push(response.result()[1].as_str().unwrap().to_string());
+        }
+        seqs.sort_unstable();
+        traces.sort();
+        traces.dedup();
+        assert_eq!(seqs, (0..8).collect::<Vec<_>>());
+        assert_eq!(traces.len(), 8);
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Serialize;
+use tokio::runtime::Handle;
+
+use crate::{
+    emit::EmitGate,
+    lanes::{LaneDepths, RequestLanes},
+};
+
+/// Counters of the app's Tokio runtime, for `get_runtime_metrics`.
+///
+/// Fields that Tokio only exposes with `--cfg tokio_unstable` are `None`
+/// in other builds.
+#[derive(Serialize, Debug)]
+pub struct RuntimeMetrics {
+    /// Tasks spawned and not yet finished.
+    pub active_tasks: u64,
+    /// Tasks waiting in the injection queue for a worker.
+    pub injected_count: u64,
+    pub worker_thread_count: u64,
+    /// Threads of the blocking pool. Many of them point at blocking work
+    /// that should be moved off the async workers, or at a leak.
+    pub blocking_thread_count: Option<u64>,
+    /// Tasks scheduled from outside the runtime, e.g. by the event loop.
+    pub remote_schedule_count: Option<u64>,
+    /// TCP requests waiting for the event loop, per priority lane.
+    pub request_lanes: LaneDepths,
+    /// API handler panics answered with `HANDLER_PANIC` since startup.
+    pub handler_panics: u64,
+    /// `emit` frames dropped because the event loop fell behind.
+    pub emits_dropped: u64,
+    /// How long requests waited for and ran on the event loop, the numbers
+    /// `meta` reports to connections with the `timing` feature.
+    pub latency: crate::timing::LatencyMetrics,
+}
+
+impl RuntimeMetrics {
+    pub fn of(handle: &Handle, lanes: &RequestLanes, emits: &EmitGate) -> Self {
+        let metrics = handle.metrics();
+        #[cfg(tokio_unstable)]
+        let (blocking_thread_count, remote_schedule_count) = (
+            Some(metrics.num_blocking_threads() as u64),
+            Some(metrics.remote_schedule_count()),
+        );
+        #[cfg(not(tokio_unstable))]
+        let (blocking_thread_count, remote_schedule_count) = (None, None);
+        Self {
+            active_tasks: metrics.num_alive_tasks() as u64,
+            injected_count: metrics.global_queue_depth() as u64,
+            worker_thread_count: metrics.num_workers() as u64,
+            blocking_thread_count,
+            remote_schedule_count,
+            request_lanes: lanes.depths(),
+            handler_panics: crate::crash::handler_panics(),
+            emits_dropped: emits.dropped(),
+            latency: crate::timing::latency(),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// An exception thrown by a script run through
+/// [`AppContext::eval_js`](crate::context::AppContext::eval_js).
+#[derive(Debug)]
+pub struct JsError {
+    pub message: String,
+}
+
+impl std::fmt::Display for JsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "JavaScript error: {}", self.message)
+    }
+}
+
+impl std::error::Error for JsError {}
+
+/// Wraps an expression so exceptions come back as data instead of being
+/// swallowed by the WebView.
+pub fn wrap_eval(js: &str) -> String {
+    format!(
+        "(() => {{ try {{ return {{ ok: ({}) }}; }} catch (e) {{ return {{ error: String(e && e.message !== undefined ? e.message : e) }}; }} }})()",
+        js
+    )
+}
+
+/// Parses the JSON the WebView hands back for a [`wrap_eval`] script.
+///
+/// `undefined` and non-serializable results become `null`.
+pub fn parse_eval_result(raw: &str) -> Result<Value> {
+    if raw.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    let mut result: Value =
+        serde_json::from_str(raw).map_err(|e| anyhow!("Invalid script result: {}", e))?;
+    // Some backends return the JSON as a string literal.
+    if let Value::String(inner) = &result {
+        if let Ok(parsed) = serde_json::from_str::<Value>(inner) {
+            result = parsed;
+        }
+    }
+    if let Some(message) = result.get("error") {
+        let message = match message {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        return Err(JsError { message }.into());
+    }
+    Ok(result.get_mut("ok").map(Value::take).unwrap_or(Value::Null))
+}
@@ -0,0 +1,82 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{read_force, write_force};
+
+/// Shared objects handlers need besides the window, such as a database
+/// connection or an HTTP client, keyed by type.
+///
+/// Registered through `ApiManager::register_service` and reached as
+/// `ctx.services`, or injected with `#[api(inject = [MyDatabase])]` or a
+/// [`State`] parameter.
+#[derive(Clone, Default)]
+pub struct ServiceRegistry {
+    services: Arc<RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl ServiceRegistry {
+    /// Adds `service`, replacing a previous one of the same type.
+    pub fn insert<T: Any + Send + Sync>(&self, service: T) {
+        write_force!(self.services).insert(TypeId::of::<T>(), Arc::new(service));
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let service = read_force!(self.services).get(&TypeId::of::<T>())?.clone();
+        service.downcast::<T>().ok()
+    }
+
+    /// Like [`get`](Self::get), but a missing service is an error.
+    #[allow(dead_code)]
+    pub fn require<T: Any + Send + Sync>(&self) -> Result<Arc<T>> {
+        self.get::<T>()
+            .ok_or_else(|| anyhow!("Service {} is not registered", type_name::<T>()))
+    }
+}
+
+/// A service as a parameter of an `#[api]` handler, e.g. `db: State<MyDatabase>`,
+/// taken from `ctx.services`; a missing service fails the request.
+#[allow(dead_code)]
+pub struct State<T>(pub Arc<T>);
+
+#[allow(dead_code)]
+impl<T: Any + Send + Sync> State<T> {
+    pub fn from_services(services: &ServiceRegistry) -> Result<Self> {
+        services.require::<T>().map(Self)
+    }
+}
+
+impl<T> std::ops::Deref for State<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_panic_while_writing_leaves_the_registry_usable() {
+        let registry = ServiceRegistry::default();
+        registry.insert(1_u32);
+        let poisoner = registry.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.services.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(registry.services.is_poisoned());
+
+        assert_eq!(registry.get::<u32>().as_deref(), Some(&1));
+        registry.insert("db".to_string());
+        assert_eq!(registry.require::<String>().unwrap().as_str(), "db");
+        assert!(registry.require::<i64>().is_err());
+    }
+}
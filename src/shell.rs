@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::{api_manager::PermissionDenied, fs_scope::FsScope};
+
+/// URL schemes `shell_open` hands to the OS unless `open_schemes` is set.
+pub const DEFAULT_OPEN_SCHEMES: &[&str] = &["https", "mailto"];
+
+/// The OS knows no application for the file's type.
+#[derive(Debug)]
+#[cfg_attr(not(any(target_os = "windows", target_os = "linux")), allow(dead_code))]
+pub struct NoAssociatedApplication {
+    pub extension: Option<String>,
+}
+
+impl NoAssociatedApplication {
+    #[cfg_attr(not(any(target_os = "windows", target_os = "linux")), allow(dead_code))]
+    fn for_path(path: &Path) -> Self {
+        Self {
+            extension: path
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+impl std::fmt::Display for NoAssociatedApplication {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.extension {
+            Some(ext) => write!(f, "No application is associated with .{} files", ext),
+            None => write!(
+                f,
+                "No application is associated with files without extension"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NoAssociatedApplication {}
+
+/// `ERROR_NO_ASSOCIATION`, reported by `ShellExecuteExW`.
+#[cfg(target_os = "windows")]
+const ERROR_NO_ASSOCIATION: i32 = 1155;
+
+/// Extensions the OS runs rather than opens in a viewer.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "app", "appimage", "bat", "cmd", "com", "command", "cpl", "desktop", "exe", "hta", "jar", "js",
+    "jse", "lnk", "msc", "msi", "pif", "ps1", "py", "pyw", "reg", "run", "scr", "sh", "url", "vbe",
+    "vbs", "wsf", "wsh",
+];
+
+/// Opens `path` with the system's default application: `ShellExecuteW` on
+/// Windows, `open` (NSWorkspace) on macOS and `xdg-open` on Linux.
+///
+/// Executables, by extension or by their execute bit, are refused with
+/// [`PermissionDenied`]: the default application of a program is the
+/// program itself. A missing association is reported as
+/// [`NoAssociatedApplication`] on Windows and Linux; on macOS the
+/// launcher's failure is returned as is.
+pub fn open_with_default_app(path: &Path) -> Result<()> {
+    if is_executable(path) {
+        return Err(anyhow::Error::new(PermissionDenied {
+            permission: "open_executable",
+        })
+        .context(format!("{:?} is a program and may not be opened", path)));
+    }
+    #[cfg(target_os = "linux")]
+    if !has_default_app(path) {
+        return Err(NoAssociatedApplication::for_path(path).into());
+    }
+    match open::that(path) {
+        Ok(()) => Ok(()),
+        #[cfg(target_os = "windows")]
+        Err(e) if e.raw_os_error() == Some(ERROR_NO_ASSOCIATION) => {
+            Err(NoAssociatedApplication::for_path(path).into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    let by_extension = path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_ascii_lowercase();
+        EXECUTABLE_EXTENSIONS.contains(&ext.as_str())
+    });
+    #[cfg(unix)]
+    let by_mode = {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    #[cfg(not(unix))]
+    let by_mode = false;
+    by_extension || by_mode
+}
+
+/// What `shell_open` launched.
+#[derive(Serialize, Debug)]
+pub struct Opened {
+    pub opened: bool,
+    /// Desktop entry of the handler, e.g. `"firefox.desktop"`. Only known on
+    /// Linux, and only if `xdg-mime` has a default for the type.
+    pub handler: Option<String>,
+}
+
+/// Opens a URL, or a file or directory in `scope`, with its default handler.
+///
+/// URLs must use one of `schemes`. `file:` URLs and plain paths are checked
+/// against `scope` instead, whatever `schemes` says, and must exist.
+pub fn open_target(target: &str, scope: &FsScope, schemes: &[String]) -> Result<Opened> {
+    let scheme = scheme_of(target).map(str::to_ascii_lowercase);
+    let path = match scheme.as_deref() {
+        None => Some(PathBuf::from(target)),
+        Some("file") => Some(file_url_path(target)?),
+        Some(scheme) => {
+            if !schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+                return Err(anyhow::Error::new(PermissionDenied {
+                    permission: "open_scheme",
+                })
+                .context(format!("{:?} URLs may not be opened", scheme)));
+            }
+            None
+        }
+    };
+    match path {
+        Some(path) => {
+            let path = scope.resolve(&path)?;
+            if !path.exists() {
+                bail!("{:?} does not exist", path);
+            }
+            open_with_default_app(&path)?;
+            Ok(Opened {
+                opened: true,
+                handler: default_handler(&mime_of(&path)),
+            })
+        }
+        None => {
+            open::that(target)?;
+            let scheme = scheme.unwrap_or_default();
+            Ok(Opened {
+                opened: true,
+                handler: default_handler(&format!("x-scheme-handler/{}", scheme)),
+            })
+        }
+    }
+}
+
+/// The scheme of a URL like `https://...` or `mailto:...`. Single letters
+/// are Windows drive letters, not schemes.
+fn scheme_of(target: &str) -> Option<&str> {
+    let (scheme, _) = target.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = scheme.len() > 1
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// The local path of a `file:` URL. URLs naming another host are rejected.
+fn file_url_path(url: &str) -> Result<PathBuf> {
+    let rest = &url["file:".len()..];
+    let path = match rest.strip_prefix("//") {
+        Some(rest) => rest.strip_prefix("localhost").unwrap_or(rest),
+        None => rest,
+    };
+    if !path.starts_with('/') {
+        bail!("{:?} is not a local file URL", url);
+    }
+    let path = crate::vfs::percent_decode(path)
+        .ok_or_else(|| anyhow!("{:?} is not a valid file URL", url))?;
+    // `file:///C:/dir` names `C:/dir`.
+    #[cfg(target_os = "windows")]
+    let path = match path.get(1..3) {
+        Some(drive) if drive.ends_with(':') => path[1..].to_string(),
+        _ => path,
+    };
+    Ok(PathBuf::from(path))
+}
+
+fn mime_of(path: &Path) -> String {
+    if path.is_dir() {
+        return "inode/directory".into();
+    }
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+/// The default application `xdg-mime` has for `mime`; `Some("")` if it has
+/// none and `None` if it can't be asked.
+#[cfg(target_os = "linux")]
+fn query_default_app(mime: &str) -> Option<String> {
+    std::process::Command::new("xdg-mime")
+        .args(["query", "default", mime])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(out.stdout.trim_ascii()).into_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn default_handler(mime: &str) -> Option<String> {
+    query_default_app(mime).filter(|app| !app.is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_handler(_mime: &str) -> Option<String> {
+    None
+}
+
+/// Asks `xdg-mime` for the default application of the file's MIME type.
+/// Unknown types and a missing `xdg-mime` are left to `xdg-open` to decide.
+#[cfg(target_os = "linux")]
+fn has_default_app(path: &Path) -> bool {
+    if path.is_dir() {
+        return true;
+    }
+    let Some(mime) = mime_guess::from_path(path).first_raw() else {
+        return true;
+    };
+    query_default_app(mime).is_none_or(|app| !app.is_empty())
+}
@@ -0,0 +1,236 @@
+//! Cleanup that runs when the app exits.
+//!
+//! Closing the window, `close`, `confirm_close(true)` or the tray's quit item
+//! first run the hooks in reverse registration order while the event loop
+//! keeps serving requests, then tell the `ShutdownNotifier` and leave the
+//! loop. Each hook gets its own thread and timeout, so a hung one only costs
+//! its timeout. Hooks registered later run when the event loop is destroyed.
+
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+
+pub type HookId = u64;
+pub type ShutdownHook = Box<dyn Fn() -> Result<()> + Send + Sync>;
+
+/// How long a hook may run without an explicit timeout.
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Cleanup functions run by `App::exit` and [`App::shutdown`](crate::core::App::shutdown).
+#[derive(Default)]
+pub struct ShutdownHooks {
+    last_id: HookId,
+    hooks: Vec<(HookId, String, ShutdownHook, Duration)>,
+    /// Hooks of `register_exit_hook`, done once `exit_hook_done` is called.
+    remote: HashMap<HookId, mpsc::Sender<()>>,
+}
+
+impl ShutdownHooks {
+    pub fn register(&mut self, name: String, hook: ShutdownHook, timeout: Duration) -> HookId {
+        self.last_id += 1;
+        self.hooks.push((self.last_id, name, hook, timeout));
+        self.last_id
+    }
+
+    /// A hook run elsewhere, e.g. by Python: `notify` is called with its id
+    /// when it's its turn, and the hook ends with [`Self::done`].
+    pub fn register_remote<F>(&mut self, name: String, timeout: Duration, notify: F) -> HookId
+    where
+        F: Fn(HookId) + Send + Sync + 'static,
+    {
+        let (done, wait) = mpsc::channel();
+        let wait = Mutex::new(wait);
+        let id = self.last_id + 1;
+        self.remote.insert(id, done);
+        self.register(
+            name,
+            Box::new(move || {
+                notify(id);
+                // Gives up with the timeout; the runner has moved on by then.
+                let _ = crate::lock_force!(wait).recv_timeout(timeout);
+                Ok(())
+            }),
+            timeout,
+        )
+    }
+
+    /// Ends a hook of [`Self::register_remote`], or lets it end right away
+    /// if it hasn't run yet. Returns `false` if it's unknown or done.
+    pub fn done(&mut self, id: HookId) -> bool {
+        self.remote
+            .remove(&id)
+            .is_some_and(|done| done.send(()).is_ok())
+    }
+
+    /// Returns `false` if no hook with this id was registered.
+    pub fn deregister(&mut self, id: HookId) -> bool {
+        let len = self.hooks.len();
+        self.hooks.retain(|(hook_id, _, _, _)| *hook_id != id);
+        self.remote.remove(&id);
+        self.hooks.len() != len
+    }
+
+    /// Takes the hooks out in reverse registration order, so they can run
+    /// without the registry locked.
+    pub fn take(&mut self) -> Vec<(String, ShutdownHook, Duration)> {
+        self.hooks
+            .drain(..)
+            .rev()
+            .map(|(_, name, hook, timeout)| (name, hook, timeout))
+            .collect()
+    }
+}
+
+/// Runs `hooks` one after another, each on its own thread. A hook that
+/// fails, panics or outlives its timeout is logged and the next one runs;
+/// a hung hook's thread is left behind.
+pub fn run(hooks: Vec<(String, ShutdownHook, Duration)>) {
+    for (name, hook, timeout) in hooks {
+        let (tx, rx) = mpsc::channel();
+        let spawned = std::thread::Builder::new()
+            .name(format!("exit-hook:{}", name))
+            .spawn(move || {
+                let _ = tx.send(hook());
+            });
+        if let Err(e) = spawned {
+            tracing::error!(hook = %name, error = %e, "Exit hook not started");
+            continue;
+        }
+        match rx.recv_timeout(timeout) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                tracing::error!(hook = %name, error = %e, "Exit hook failed");
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                tracing::warn!(hook = %name, ?timeout, "Exit hook didn't finish in time, skipped");
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                tracing::error!(hook = %name, "Exit hook panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    fn recorder(order: &Arc<Mutex<Vec<String>>>, name: &str) -> ShutdownHook {
+        let (order, name) = (order.clone(), name.to_string());
+        Box::new(move || {
+            order.lock().unwrap().push(name.clone());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn hooks_run_in_reverse_registration_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = ShutdownHooks::default();
+        for name in ["watchers", "tray", "server"] {
+            hooks.register(name.into(), recorder(&order, name), DEFAULT_HOOK_TIMEOUT);
+        }
+        run(hooks.take());
+        assert_eq!(*order.lock().unwrap(), ["server", "tray", "watchers"]);
+        assert!(hooks.take().is_empty());
+    }
+
+    #[test]
+    fn deregistered_hooks_do_not_run() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = ShutdownHooks::default();
+        let first = hooks.register(
+            "first".into(),
+            recorder(&order, "first"),
+            DEFAULT_HOOK_TIMEOUT,
+        );
+        hooks.register(
+            "second".into(),
+            recorder(&order, "second"),
+            DEFAULT_HOOK_TIMEOUT,
+        );
+        assert!(hooks.deregister(first));
+        assert!(!hooks.deregister(first));
+        run(hooks.take());
+        assert_eq!(*order.lock().unwrap(), ["second"]);
+    }
+
+    #[test]
+    fn hung_hook_only_costs_its_timeout() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = ShutdownHooks::default();
+        hooks.register(
+            "last".into(),
+            recorder(&order, "last"),
+            DEFAULT_HOOK_TIMEOUT,
+        );
+        hooks.register(
+            "hung".into(),
+            Box::new(|| {
+                std::thread::sleep(Duration::from_secs(30));
+                Ok(())
+            }),
+            Duration::from_millis(100),
+        );
+        let started = Instant::now();
+        run(hooks.take());
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+        assert_eq!(*order.lock().unwrap(), ["last"]);
+    }
+
+    #[test]
+    fn failing_and_panicking_hooks_do_not_stop_the_rest() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = ShutdownHooks::default();
+        hooks.register(
+            "last".into(),
+            recorder(&order, "last"),
+            DEFAULT_HOOK_TIMEOUT,
+        );
+        hooks.register(
+            "panics".into(),
+            Box::new(|| panic!("hook bug")),
+            DEFAULT_HOOK_TIMEOUT,
+        );
+        hooks.register(
+            "fails".into(),
+            Box::new(|| Err(anyhow::anyhow!("cleanup failed"))),
+            DEFAULT_HOOK_TIMEOUT,
+        );
+        run(hooks.take());
+        assert_eq!(*order.lock().unwrap(), ["last"]);
+    }
+
+    #[test]
+    fn remote_hook_waits_for_done_or_its_timeout() {
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = ShutdownHooks::default();
+        let seen = notified.clone();
+        let acked = hooks.register_remote("python".into(), Duration::from_secs(30), move |id| {
+            seen.lock().unwrap().push(id)
+        });
+        let seen = notified.clone();
+        let silent =
+            hooks.register_remote("silent".into(), Duration::from_millis(100), move |id| {
+                seen.lock().unwrap().push(id)
+            });
+        // Done before its turn, so it ends as soon as it is notified.
+        assert!(hooks.done(acked));
+        assert!(!hooks.done(acked));
+        let started = Instant::now();
+        run(hooks.take());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(*notified.lock().unwrap(), [silent, acked]);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use tao::{dpi::PhysicalSize, window::Window};
+
+use crate::config::AppConfig;
+
+/// IPC message the main page sends through `pyframe.invoke("__frame_ready")`.
+pub const FRAME_READY: &str = "__frame_ready";
+/// The splash is dismissed after this long even without a readiness signal.
+const DEFAULT_SPLASH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A WebView over the window that shows the splash while the main WebView
+/// loads hidden underneath.
+pub struct Splash {
+    webview: wry::WebView,
+    deadline: Instant,
+    wait_for_ready: bool,
+}
+
+impl Splash {
+    /// Builds the splash from `config.splash`, which is either inline HTML
+    /// or the path of an HTML file.
+    pub fn build(window: &Window, config: &AppConfig) -> Result<Option<Self>> {
+        let Some(splash) = config.splash.as_deref() else {
+            return Ok(None);
+        };
+        let html = if Path::new(splash).is_file() {
+            std::fs::read_to_string(splash)
+                .map_err(|e| anyhow!("Cannot read splash {:?}: {}", splash, e))?
+        } else {
+            splash.to_string()
+        };
+        let builder = wry::WebViewBuilder::new().with_html(html);
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        let webview = {
+            use tao::platform::unix::WindowExtUnix;
+            use wry::WebViewBuilderExtUnix;
+            // The main WebView is hidden, so the splash fills the window's box.
+            let vbox = window
+                .default_vbox()
+                .ok_or_else(|| anyhow!("Window has no GTK container"))?;
+            builder.build_gtk(vbox)?
+        };
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        let webview = builder
+            .with_bounds(full_bounds(window.inner_size()))
+            .build_as_child(window)?;
+
+        Ok(Some(Self {
+            webview,
+            deadline: Instant::now()
+                + config
+                    .splash_timeout_ms
+                    .map_or(DEFAULT_SPLASH_TIMEOUT, Duration::from_millis),
+            wait_for_ready: config.splash_wait_for_ready,
+        }))
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Whether the main page finishing its load is enough to dismiss the
+    /// splash, or the page announces readiness itself.
+    pub fn dismiss_on_load(&self) -> bool {
+        !self.wait_for_ready
+    }
+
+    pub fn resize(&self, size: PhysicalSize<u32>) {
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        crate::log_if_err!(self.webview.set_bounds(full_bounds(size)));
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        let _ = size;
+    }
+
+    /// Reveals the main WebView; the splash WebView is destroyed on drop.
+    pub fn dismiss(self, main: &wry::WebView) -> Result<()> {
+        main.set_visible(true)?;
+        self.webview.set_visible(false)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn full_bounds(size: PhysicalSize<u32>) -> wry::Rect {
+    wry::Rect {
+        position: wry::dpi::PhysicalPosition::new(0, 0).into(),
+        size: wry::dpi::PhysicalSize::new(size.width, size.height).into(),
+    }
+}
@@ -0,0 +1,204 @@
+//! Named JSON documents shared by Python, Rust and every window's page,
+//! behind the `state_*` APIs and `window.appStore` / `pyframe.state`.
+//!
+//! The page's calls arrive over `window.ipc` as [`STATE_STORE`] messages and
+//! are answered with an `app-store` event. Every change gets the next
+//! revision of one counter for the whole store and is announced as
+//! [`UserEvent::StateChanged`](crate::utils::UserEvent::StateChanged), which
+//! reaches all windows and `pyframe.events()` as `state-changed:<name>`.
+//!
+//! Changes are applied in arrival order on the event loop thread, so the
+//! last writer wins; subscribers drop events with a revision older than
+//! the one they have.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Prefix of `window.appStore` calls, followed by a [`StoreCall`] as JSON.
+pub const STATE_STORE: &str = "__state_store:";
+/// Largest serialized size of one document unless `state_max_bytes` is set.
+const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+
+#[derive(Deserialize, Debug)]
+pub struct StoreCall {
+    pub id: u64,
+    #[serde(flatten)]
+    pub op: StoreOp,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum StoreOp {
+    Get {
+        name: String,
+    },
+    Set {
+        name: String,
+        value: Value,
+    },
+    /// RFC 7396 JSON merge patch.
+    Update {
+        name: String,
+        patch: Value,
+    },
+    Delete {
+        name: String,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Set,
+    Update,
+    Delete,
+}
+
+/// A change, as sent with `state-changed:<name>`.
+#[derive(Serialize, Clone, Debug)]
+pub struct StateChange {
+    pub name: String,
+    pub op: ChangeKind,
+    /// The new value for `set`, the merge patch for `update`, `null` for
+    /// `delete`.
+    pub patch: Value,
+    /// The document after the change.
+    pub value: Value,
+    pub revision: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Document {
+    pub value: Value,
+    /// Revision of the document's last change.
+    pub revision: u64,
+}
+
+pub struct StateStore {
+    documents: HashMap<String, Document>,
+    revision: u64,
+    max_bytes: usize,
+}
+
+impl Default for StateStore {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl StateStore {
+    pub fn new(max_bytes: Option<usize>) -> Self {
+        Self {
+            documents: HashMap::new(),
+            revision: 0,
+            max_bytes: max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Document> {
+        self.documents.get(name)
+    }
+
+    pub fn set(&mut self, name: String, value: Value) -> Result<StateChange> {
+        self.store(name, ChangeKind::Set, value.clone(), value)
+    }
+
+    pub fn update(&mut self, name: String, patch: Value) -> Result<StateChange> {
+        let mut value = self
+            .documents
+            .get(&name)
+            .map(|document| document.value.clone())
+            .unwrap_or(Value::Null);
+        merge_patch(&mut value, &patch);
+        self.store(name, ChangeKind::Update, patch, value)
+    }
+
+    /// `None` if `name` wasn't set.
+    pub fn delete(&mut self, name: &str) -> Option<StateChange> {
+        self.documents.remove(name)?;
+        self.revision += 1;
+        Some(StateChange {
+            name: name.to_string(),
+            op: ChangeKind::Delete,
+            patch: Value::Null,
+            value: Value::Null,
+            revision: self.revision,
+        })
+    }
+
+    fn store(
+        &mut self,
+        name: String,
+        op: ChangeKind,
+        patch: Value,
+        value: Value,
+    ) -> Result<StateChange> {
+        let size = serde_json::to_vec(&value)?.len();
+        if size > self.max_bytes {
+            bail!(
+                "State {:?} would be {} bytes, over state_max_bytes ({})",
+                name,
+                size,
+                self.max_bytes
+            );
+        }
+        self.revision += 1;
+        self.documents.insert(
+            name.clone(),
+            Document {
+                value: value.clone(),
+                revision: self.revision,
+            },
+        );
+        Ok(StateChange {
+            name,
+            op,
+            patch,
+            value,
+            revision: self.revision,
+        })
+    }
+
+    /// Runs `op` and returns its result and the change it made, if any.
+    pub fn apply(&mut self, op: StoreOp) -> Result<(Value, Option<StateChange>)> {
+        Ok(match op {
+            StoreOp::Get { name } => (json!(self.get(&name)), None),
+            StoreOp::Set { name, value } => {
+                let change = self.set(name, value)?;
+                (json!(change.revision), Some(change))
+            }
+            StoreOp::Update { name, patch } => {
+                let change = self.update(name, patch)?;
+                (json!(change.revision), Some(change))
+            }
+            StoreOp::Delete { name } => {
+                let change = self.delete(&name);
+                (json!(change.is_some()), change)
+            }
+        })
+    }
+}
+
+/// Applies an RFC 7396 merge patch: objects merge key by key, `null`
+/// removes a key, anything else replaces the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
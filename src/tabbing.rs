@@ -0,0 +1,67 @@
+//! Native window tabbing on macOS (`NSWindow` tab groups).
+
+use tao::window::Window;
+
+/// Puts the window into the tab group `identifier`; windows sharing an
+/// identifier may be merged into one tabbed window.
+///
+/// Returns `false` on platforms without native tabbing.
+pub fn set_tabbing_identifier(window: &Window, identifier: &str) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        macos::set_tabbing_identifier(window, identifier);
+        true
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (window, identifier);
+        false
+    }
+}
+
+/// Adds `tab` as a tab of `window`'s tab group, right after `window`.
+///
+/// Returns `false` on platforms without native tabbing.
+pub fn add_tab_window(window: &Window, tab: &Window) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        macos::add_tab_window(window, tab);
+        true
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (window, tab);
+        false
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use objc2::{msg_send, runtime::AnyObject};
+    use objc2_foundation::NSString;
+    use tao::{platform::macos::WindowExtMacOS, window::Window};
+
+    /// `NSWindowOrderingMode.above`
+    const NS_WINDOW_ABOVE: isize = 1;
+
+    fn ns_window(window: &Window) -> &AnyObject {
+        unsafe { &*(window.ns_window() as *const AnyObject) }
+    }
+
+    pub fn set_tabbing_identifier(window: &Window, identifier: &str) {
+        let identifier = NSString::from_str(identifier);
+        unsafe {
+            let _: () = msg_send![ns_window(window), setTabbingIdentifier: &*identifier];
+        }
+    }
+
+    pub fn add_tab_window(window: &Window, tab: &Window) {
+        unsafe {
+            let _: () = msg_send![
+                ns_window(window),
+                addTabbedWindow: ns_window(tab),
+                ordered: NS_WINDOW_ABOVE
+            ];
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tao::window::{ProgressBarState, ProgressState, Window};
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskbarProgressState {
+    /// Removes the progress bar.
+    None,
+    Normal,
+    Paused,
+    Error,
+    /// An animated bar that ignores the progress value.
+    Indeterminate,
+}
+
+impl From<TaskbarProgressState> for ProgressState {
+    fn from(state: TaskbarProgressState) -> Self {
+        match state {
+            TaskbarProgressState::None => ProgressState::None,
+            TaskbarProgressState::Normal => ProgressState::Normal,
+            TaskbarProgressState::Paused => ProgressState::Paused,
+            TaskbarProgressState::Error => ProgressState::Error,
+            TaskbarProgressState::Indeterminate => ProgressState::Indeterminate,
+        }
+    }
+}
+
+/// Shows `progress` (`0.0` to `1.0`) on the window's taskbar button.
+///
+/// | Platform | Mechanism |
+/// |----------|-----------|
+/// | Windows  | `ITaskbarList3::SetProgressValue` / `SetProgressState` |
+/// | macOS    | a progress bar on the Dock icon, app-wide; paused and error look normal |
+/// | Linux, iOS, Android | unsupported, returns `false` |
+pub fn set_progress(window: &Window, progress: f64, state: TaskbarProgressState) -> Result<bool> {
+    if !(0.0..=1.0).contains(&progress) {
+        return Err(anyhow!(
+            "Progress must be between 0 and 1, got {}",
+            progress
+        ));
+    }
+    window.set_progress_bar(ProgressBarState {
+        state: Some(state.into()),
+        progress: Some((progress * 100.0).round() as u64),
+        desktop_filename: None,
+    });
+    Ok(cfg!(any(target_os = "windows", target_os = "macos")))
+}
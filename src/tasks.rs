@@ -0,0 +1,68 @@
+//! Named Tokio tasks, so tokio-console and `tokio-metrics` can tell them
+//! apart.
+//!
+//! Task names need a build with `RUSTFLAGS="--cfg tokio_unstable"` and the
+//! `tokio-console` feature; otherwise the names are dropped and these are
+//! plain `spawn` calls. Either way the name is kept in a task-local for
+//! [`current_name`], so crash reports can say which task panicked.
+
+use std::future::Future;
+
+use tokio::{runtime::Handle, task::JoinHandle};
+
+tokio::task_local! {
+    static TASK_NAME: String;
+}
+
+/// Name of the task running on this thread, if it was spawned through here.
+pub fn current_name() -> Option<String> {
+    TASK_NAME.try_with(String::clone).ok()
+}
+
+/// Spawns `future` on the current runtime as task `name`.
+pub fn spawn<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    spawn_on(name, &Handle::current(), future)
+}
+
+/// Spawns `future` on `handle`'s runtime as task `name`.
+pub fn spawn_on<F>(name: &str, handle: &Handle, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = TASK_NAME.scope(name.to_string(), future);
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn_on(future, handle)
+        .expect("Tokio runtime is shut down");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    {
+        let _ = name;
+        handle.spawn(future)
+    }
+}
+
+/// Runs `f` on `handle`'s blocking pool as task `name`.
+pub fn spawn_blocking_on<F, R>(name: &str, handle: &Handle, f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let task = name.to_string();
+    let f = move || TASK_NAME.sync_scope(task, f);
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn_blocking_on(f, handle)
+        .expect("Tokio runtime is shut down");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    {
+        let _ = name;
+        handle.spawn_blocking(f)
+    }
+}
@@ -0,0 +1,808 @@
+//! The TCP bridge in-process, for testing clients and the protocol without
+//! a window or a socket (the `testing` feature).
+//!
+//! [`FakeApp`] stands in for the tao event loop: requests go through the
+//! same framing, hello, batches, lanes, in-flight bound, pending map and
+//! reply cache as in the app, and are then answered by handlers scripted
+//! per method. [`TestClient`] speaks the frame protocol over an in-memory
+//! duplex stream. Handlers of `#[api]` methods need a window and the event
+//! loop, so their behavior is scripted with [`FakeApp::handle`]:
+//!
+//! - `app.handle("set_title", |req| Ok(json!(true)))`, then
+//!   `client.invoke("set_title", json!(["Title"])).await` gets `true`;
+//! - a handler's `Err` is answered with its code, as `Responder` does;
+//! - [`FakeApp::hold`] keeps requests of a method unanswered, to test
+//!   timeouts and what happens when the client goes away;
+//! - [`FakeApp::push_event`] streams an event to subscribed clients, which
+//!   [`TestClient::expect_event`] waits for.
+//!
+//! Every wait takes a timeout and fails with `TimedOut` instead of hanging.
+//!
+//! [`LoopHarness`] runs the real handlers instead: a hidden window on the
+//! event loop with an [`AppContext`] around it, but no `App`, so tests can
+//! add handlers of their own next to the `#[api]` ones. It needs a display,
+//! so the tests using it are `#[ignore]`d and run with `--ignored`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt, DuplexStream, WriteHalf},
+    sync::{broadcast, mpsc, oneshot, Notify, Semaphore},
+    task::JoinHandle,
+};
+
+use crate::{
+    api_manager::{code, ApiManager, ApiRequest, ApiResponse, Code},
+    config::AppConfig,
+    connections::{handle_client, respond, ConnectionId, Dispatch, MAX_IN_FLIGHT_REQUESTS},
+    content_blocking::ContentBlocker,
+    context::AppContext,
+    emit::Emit,
+    event_stream::SUBSCRIBE_METHOD,
+    idempotency::ReplyCache,
+    lanes::RequestLanes,
+    lock_force,
+    main_thread::MainThread,
+    protocol,
+    transport::LocalCalls,
+    utils::PendingMap,
+    utils::{arc_mut, FrameEventLoop, FrameEventLoopProxy, UserEvent},
+};
+
+/// Buffer of the in-memory stream in each direction.
+const STREAM_BUFFER: usize = 1024 * 1024;
+
+pub type Handler = Arc<dyn Fn(&ApiRequest) -> anyhow::Result<Value> + Send + Sync>;
+
+/// A request that reached the fake event loop.
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub id: u8,
+    pub method: String,
+    pub args: Value,
+    pub connection: Option<ConnectionId>,
+}
+
+#[derive(Default)]
+struct Script {
+    handlers: HashMap<String, Handler>,
+    held_methods: Vec<String>,
+    held: Vec<ApiRequest>,
+    calls: Vec<Call>,
+    emits: Vec<(String, Value)>,
+    disconnected: Vec<ConnectionId>,
+    legacy: Vec<ConnectionId>,
+}
+
+/// The event loop side of the bridge, answering requests from a script.
+#[derive(Clone)]
+pub struct FakeApp {
+    pending: PendingMap,
+    lanes: Arc<RequestLanes>,
+    in_flight: Arc<Semaphore>,
+    replies: Arc<ReplyCache>,
+    events: broadcast::Sender<Value>,
+    script: Arc<Mutex<Script>>,
+    changed: Arc<Notify>,
+    /// Moves requests from the lanes to the script, started by the first
+    /// connection.
+    pump: Arc<std::sync::OnceLock<JoinHandle<()>>>,
+}
+
+impl Default for FakeApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeApp {
+    pub fn new() -> Self {
+        Self {
+            pending: PendingMap::default(),
+            lanes: Arc::new(RequestLanes::default()),
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT_REQUESTS)),
+            replies: Arc::new(ReplyCache::default()),
+            events: broadcast::channel(256).0,
+            script: Arc::default(),
+            changed: Arc::default(),
+            pump: Arc::default(),
+        }
+    }
+
+    /// Answers requests for `method` with `handler`'s result.
+    pub fn handle<F>(&self, method: &str, handler: F) -> &Self
+    where
+        F: Fn(&ApiRequest) -> anyhow::Result<Value> + Send + Sync + 'static,
+    {
+        lock_force!(self.script)
+            .handlers
+            .insert(method.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Leaves requests for `method` unanswered until [`release`](Self::release).
+    pub fn hold(&self, method: &str) -> &Self {
+        lock_force!(self.script)
+            .held_methods
+            .push(method.to_string());
+        self
+    }
+
+    /// Answers every held request with `data`; returns how many there were.
+    pub fn release(&self, data: Value) -> usize {
+        let held = std::mem::take(&mut lock_force!(self.script).held);
+        for req in &held {
+            respond(&self.pending, req.0, req.ok(&data));
+        }
+        held.len()
+    }
+
+    /// Streams `{"event": name, "data": data}` to subscribed clients, as
+    /// the app does for `pyframe.events()`.
+    pub fn push_event(&self, name: &str, data: Value) {
+        let _ = self.events.send(json!({ "event": name, "data": data }));
+    }
+
+    /// Every request that reached the fake event loop, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        lock_force!(self.script).calls.clone()
+    }
+
+    /// The `emit` frames received, as `(event, payload)`.
+    pub fn emits(&self) -> Vec<(String, Value)> {
+        lock_force!(self.script).emits.clone()
+    }
+
+    /// Requests of `connection` waiting for a response.
+    pub fn pending_for(&self, connection: ConnectionId) -> usize {
+        lock_force!(self.pending)
+            .values()
+            .filter(|pending| pending.connection == connection)
+            .count()
+    }
+
+    /// The connection of the latest request, to identify a client's.
+    pub fn last_connection(&self) -> Option<ConnectionId> {
+        lock_force!(self.script)
+            .calls
+            .last()
+            .and_then(|call| call.connection)
+    }
+
+    /// Waits until `connection` was reported closed.
+    pub async fn wait_disconnected(
+        &self,
+        connection: ConnectionId,
+        timeout: Duration,
+    ) -> std::io::Result<()> {
+        within(timeout, async {
+            loop {
+                let changed = self.changed.notified();
+                if lock_force!(self.script).disconnected.contains(&connection) {
+                    return Ok(());
+                }
+                changed.await;
+            }
+        })
+        .await?
+    }
+
+    /// Connections served as protocol version 0.
+    pub fn legacy_connections(&self) -> Vec<ConnectionId> {
+        lock_force!(self.script).legacy.clone()
+    }
+
+    /// Opens a connection and exchanges the hello with every feature.
+    pub async fn connect(&self) -> std::io::Result<TestClient> {
+        self.connect_with(json!({
+            "type": "hello",
+            "version": protocol::VERSION,
+            "features": ["binary", "streaming", "batch", "emit"],
+            "token": protocol::expected_token(),
+            "client": "test-client",
+        }))
+        .await
+    }
+
+    /// Opens a connection with a custom first frame, e.g. a hello without
+    /// features; fails if the server refuses it.
+    pub async fn connect_with(&self, hello: Value) -> std::io::Result<TestClient> {
+        let mut client = self.open();
+        client.send(&hello).await?;
+        let ack = client.next_frame(Duration::from_secs(5)).await?;
+        if ack["type"] != "hello-ack" {
+            return Err(std::io::Error::other(format!("Hello refused: {}", ack)));
+        }
+        Ok(client)
+    }
+
+    /// Opens a connection without sending anything.
+    pub fn open(&self) -> TestClient {
+        self.pump.get_or_init(|| {
+            let (lanes, app, pending) = (self.lanes.clone(), self.clone(), self.pending.clone());
+            tokio::spawn(async move { lanes.pump(app, pending).await })
+        });
+        let (client, server) = tokio::io::duplex(STREAM_BUFFER);
+        let (reader, writer) = tokio::io::split(server);
+        let server = tokio::spawn(handle_client(
+            reader,
+            writer,
+            self.clone(),
+            self.pending.clone(),
+            self.in_flight.clone(),
+            self.lanes.clone(),
+            self.replies.clone(),
+        ));
+        TestClient::new(client, server)
+    }
+
+    fn answer(&self, req: ApiRequest) {
+        let mut script = lock_force!(self.script);
+        let connection = lock_force!(self.pending)
+            .get(&req.0)
+            .map(|pending| pending.connection);
+        script.calls.push(Call {
+            id: req.0,
+            method: req.1.clone(),
+            args: req.args().value().clone(),
+            connection,
+        });
+        if script.held_methods.contains(&req.1) {
+            script.held.push(req);
+            return;
+        }
+        // Unlocked, so handlers can use the app.
+        let handler = script.handlers.get(&req.1).cloned();
+        drop(script);
+        let response = match handler {
+            Some(handler) => match handler(&req) {
+                Ok(data) => req.ok(data),
+                Err(err) => req.err(code::of(&err), err.to_string()),
+            },
+            None => req.err(code::ERROR, format!("Unknown method: {}", req.1)),
+        };
+        respond(&self.pending, req.0, response);
+    }
+
+    fn record(&self, update: impl FnOnce(&mut Script)) {
+        update(&mut lock_force!(self.script));
+        self.changed.notify_waiters();
+    }
+}
+
+impl Dispatch for FakeApp {
+    fn dispatch(&self, req: ApiRequest) -> Result<(), String> {
+        self.lanes.taken();
+        self.answer(req);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> Option<broadcast::Receiver<Value>> {
+        Some(self.events.subscribe())
+    }
+
+    fn emit(&self, emit: Emit) {
+        self.record(|script| script.emits.push((emit.event, emit.payload)));
+    }
+
+    fn disconnected(&self, connection: ConnectionId) {
+        self.record(|script| script.disconnected.push(connection));
+    }
+
+    fn legacy_protocol(&self, connection: ConnectionId) {
+        self.record(|script| script.legacy.push(connection));
+    }
+}
+
+/// An error response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallError {
+    pub code: Code,
+    pub message: String,
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for CallError {}
+
+/// A client connection over an in-memory stream.
+pub struct TestClient {
+    writer: WriteHalf<DuplexStream>,
+    frames: mpsc::UnboundedReceiver<Value>,
+    responses: Arc<Mutex<HashMap<u8, oneshot::Sender<Value>>>>,
+    events: mpsc::UnboundedReceiver<Value>,
+    reader: JoinHandle<()>,
+    server: JoinHandle<std::io::Result<()>>,
+    next_id: u8,
+}
+
+impl TestClient {
+    fn new(stream: DuplexStream, server: JoinHandle<std::io::Result<()>>) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        let (frames_tx, frames) = mpsc::unbounded_channel();
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let responses: Arc<Mutex<HashMap<u8, oneshot::Sender<Value>>>> = Arc::default();
+        let reader = tokio::spawn(route_frames(
+            reader,
+            responses.clone(),
+            frames_tx,
+            events_tx,
+        ));
+        Self {
+            writer,
+            frames,
+            responses,
+            events,
+            reader,
+            server,
+            next_id: 1,
+        }
+    }
+
+    /// Calls `method` and returns the response's data, or its code and
+    /// message as a [`CallError`].
+    pub async fn invoke(&mut self, method: &str, args: Value) -> anyhow::Result<Value> {
+        self.invoke_timeout(method, args, Duration::from_secs(5))
+            .await
+    }
+
+    pub async fn invoke_timeout(
+        &mut self,
+        method: &str,
+        args: Value,
+        timeout: Duration,
+    ) -> anyhow::Result<Value> {
+        let id = self.take_id();
+        let response = self.request(json!([id, method, args]), timeout).await?;
+        let code = response[1].as_i64().unwrap_or(code::ERROR.into()) as Code;
+        if code != code::OK {
+            return Err(CallError {
+                code,
+                message: response[2].as_str().unwrap_or_default().to_string(),
+            }
+            .into());
+        }
+        Ok(response[3].clone())
+    }
+
+    /// Sends `request`, an `[id, method, args, metadata?]` array, and
+    /// returns the raw response array.
+    pub async fn request(&mut self, request: Value, timeout: Duration) -> std::io::Result<Value> {
+        let id = request[0]
+            .as_u64()
+            .ok_or_else(|| std::io::Error::other("The request has no numeric id"))?
+            as u8;
+        let (tx, rx) = oneshot::channel();
+        lock_force!(self.responses).insert(id, tx);
+        self.send(&request).await?;
+        within(timeout, rx)
+            .await?
+            .map_err(|_| std::io::Error::other("The connection closed before the response"))
+    }
+
+    /// Subscribes to pushed events, like `pyframe.events()`.
+    pub async fn subscribe(&mut self) -> anyhow::Result<()> {
+        self.invoke(SUBSCRIBE_METHOD, json!([])).await.map(|_| ())
+    }
+
+    /// Waits for the next pushed event called `name`, skipping others, and
+    /// returns its data.
+    pub async fn expect_event(&mut self, name: &str, timeout: Duration) -> std::io::Result<Value> {
+        within(timeout, async {
+            while let Some(event) = self.events.recv().await {
+                if event["event"] == name {
+                    return Ok(event["data"].clone());
+                }
+            }
+            Err(std::io::Error::other("The connection closed"))
+        })
+        .await?
+    }
+
+    /// Sends an `emit` frame.
+    pub async fn emit(&mut self, event: &str, payload: Value) -> std::io::Result<()> {
+        self.send(&json!({ "type": "emit", "event": event, "payload": payload }))
+            .await
+    }
+
+    /// Sends any value as a frame.
+    pub async fn send(&mut self, frame: &Value) -> std::io::Result<()> {
+        self.send_raw(&serde_json::to_vec(frame)?).await
+    }
+
+    /// Sends `payload` with the length prefix, e.g. malformed JSON.
+    pub async fn send_raw(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        let mut msg = (payload.len() as u32).to_be_bytes().to_vec();
+        msg.extend_from_slice(payload);
+        self.writer.write_all(&msg).await
+    }
+
+    /// The next frame that isn't a response to [`request`](Self::request)
+    /// or a pushed event, e.g. the hello-ack, a `close` frame or a batch
+    /// response.
+    pub async fn next_frame(&mut self, timeout: Duration) -> std::io::Result<Value> {
+        within(timeout, self.frames.recv())
+            .await?
+            .ok_or_else(|| std::io::Error::other("The connection closed"))
+    }
+
+    /// Closes the connection and waits for the server side to finish.
+    pub async fn close(mut self) -> std::io::Result<()> {
+        self.writer.shutdown().await?;
+        drop(self.writer);
+        let result = within(Duration::from_secs(5), self.server)
+            .await?
+            .map_err(std::io::Error::other)?;
+        self.reader.abort();
+        result
+    }
+
+    fn take_id(&mut self) -> u8 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        id
+    }
+}
+
+/// Sends responses to their waiting [`TestClient::request`], events to
+/// [`TestClient::expect_event`] and anything else to `frames`.
+async fn route_frames(
+    mut reader: impl AsyncRead + Unpin,
+    responses: Arc<Mutex<HashMap<u8, oneshot::Sender<Value>>>>,
+    frames: mpsc::UnboundedSender<Value>,
+    events: mpsc::UnboundedSender<Value>,
+) {
+    loop {
+        let mut len = [0u8; 4];
+        if reader.read_exact(&mut len).await.is_err() {
+            break;
+        }
+        let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+        if reader.read_exact(&mut buf).await.is_err() {
+            break;
+        }
+        let Ok(frame) = serde_json::from_slice::<Value>(&buf) else {
+            continue;
+        };
+        let waiting = frame[0]
+            .as_u64()
+            .and_then(|id| lock_force!(responses).remove(&(id as u8)));
+        match waiting {
+            Some(waiting) => {
+                let _ = waiting.send(frame);
+            }
+            None if frame.get("event").is_some() => {
+                let _ = events.send(frame);
+            }
+            None => {
+                let _ = frames.send(frame);
+            }
+        }
+    }
+}
+
+/// How long [`LoopHarness::call`] waits for a response.
+const HARNESS_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Register = Box<dyn FnOnce(&mut ApiManager) + Send>;
+
+/// A hidden window with the `#[api]` handlers, on the event loop's thread.
+/// One session runs at a time; a second [`start`](Self::start) waits for
+/// the first harness to be dropped.
+pub struct LoopHarness {
+    calls: LocalCalls,
+    proxy: FrameEventLoopProxy,
+    main_thread: MainThread,
+    done: std::sync::mpsc::Receiver<()>,
+    // Last, so the loop is gone before its runtime.
+    rt: tokio::runtime::Runtime,
+}
+
+impl LoopHarness {
+    /// Opens the window; panics without a display, or on a platform that
+    /// keeps the loop on the main thread (macOS).
+    pub fn start() -> Self {
+        Self::start_with(|_| {})
+    }
+
+    /// [`start`](Self::start) with more handlers, e.g. ones that panic.
+    pub fn start_with(register: impl FnOnce(&mut ApiManager) + Send + 'static) -> Self {
+        if cfg!(all(unix, not(target_os = "macos")))
+            && ["DISPLAY", "WAYLAND_DISPLAY"]
+                .iter()
+                .all(|var| std::env::var_os(var).is_none_or(|value| value.is_empty()))
+        {
+            panic!("LoopHarness needs a display to open a window on");
+        }
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .expect("a runtime for LoopHarness");
+        let (ready_tx, ready) = std::sync::mpsc::channel();
+        let (done_tx, done) = std::sync::mpsc::channel();
+        let handle = rt.handle().clone();
+        let register: Register = Box::new(register);
+        let job = Box::new(move || {
+            let result = crate::frame_loop::take()
+                .map_err(anyhow::Error::new)
+                .and_then(|mut event_loop| {
+                    let result = serve(&mut event_loop, handle, register, &ready_tx);
+                    crate::frame_loop::put_back(event_loop);
+                    result
+                });
+            if let Err(err) = result {
+                let _ = ready_tx.send(Err(err.to_string()));
+            }
+            let _ = done_tx.send(());
+        });
+        if let Err(err) = crate::frame_loop::spawn(job) {
+            panic!("LoopHarness can't run the event loop: {}", err);
+        }
+        match ready.recv() {
+            Ok(Ok((calls, proxy, main_thread))) => Self {
+                calls,
+                proxy,
+                main_thread,
+                done,
+                rt,
+            },
+            Ok(Err(err)) => panic!("LoopHarness can't open its window: {}", err),
+            Err(_) => panic!("LoopHarness's event loop exited"),
+        }
+    }
+
+    /// Calls `method` as `pyframe.frame()` handles do; panics without a
+    /// response in time.
+    pub fn call(&self, method: &str, args: Value) -> ApiResponse {
+        self.calls
+            .call_blocking(method, args, HARNESS_TIMEOUT)
+            .unwrap_or_else(|| panic!("No response to {} within {:?}", method, HARNESS_TIMEOUT))
+    }
+
+    /// The in-process calls, for requests sent concurrently.
+    pub fn calls(&self) -> &LocalCalls {
+        &self.calls
+    }
+
+    pub fn main_thread(&self) -> MainThread {
+        self.main_thread.clone()
+    }
+
+    pub fn runtime(&self) -> &tokio::runtime::Handle {
+        self.rt.handle()
+    }
+}
+
+impl Drop for LoopHarness {
+    fn drop(&mut self) {
+        if self.proxy.send_event(UserEvent::Exit).is_ok() {
+            let _ = self.done.recv_timeout(HARNESS_TIMEOUT);
+        }
+    }
+}
+
+type Ready = std::sync::mpsc::Sender<Result<(LocalCalls, FrameEventLoopProxy, MainThread), String>>;
+
+/// The part of `App::run` that answers requests and runs tasks.
+fn serve(
+    event_loop: &mut FrameEventLoop,
+    rt: tokio::runtime::Handle,
+    register: Register,
+    ready: &Ready,
+) -> anyhow::Result<()> {
+    use tao::{event::Event, event_loop::ControlFlow, platform::run_return::EventLoopExtRunReturn};
+
+    let window = tao::window::WindowBuilder::new()
+        .with_title("LoopHarness")
+        .with_visible(false)
+        .build(event_loop)?;
+    let webview = wry::WebViewBuilder::new()
+        .with_html("<!doctype html><title>LoopHarness</title>")
+        .build(&window)?;
+    let first_id = window.id();
+    let proxy = event_loop.create_proxy();
+    let pending = PendingMap::default();
+    let ctx = AppContext::new(
+        first_id,
+        Arc::new(Mutex::new(HashMap::from([(
+            first_id,
+            (Arc::new(window), Arc::new(webview)),
+        )]))),
+        arc_mut(ContentBlocker::default()),
+        proxy.clone(),
+        rt.clone(),
+        pending.clone(),
+        &AppConfig::default(),
+    )?;
+    let api_manager = ApiManager::new();
+    {
+        let mut manager = lock_force!(api_manager);
+        crate::api::register_api_instances(&mut manager);
+        register(&mut manager);
+        manager.bind_app_context(&ctx);
+    }
+    let calls = LocalCalls::new(
+        pending.clone(),
+        Arc::new(Semaphore::new(MAX_IN_FLIGHT_REQUESTS)),
+        ctx.lanes.clone(),
+        rt.clone(),
+        ctx.main_thread.clone(),
+        None,
+    );
+    let pump = rt.spawn({
+        let (lanes, pending) = (ctx.lanes.clone(), pending.clone());
+        let dispatch = LoopDispatch(proxy.clone());
+        async move { lanes.pump(dispatch, pending).await }
+    });
+    let _ = ready.send(Ok((calls, proxy, ctx.main_thread.clone())));
+    event_loop.run_return(|event, target, flow| {
+        *flow = ControlFlow::Wait;
+        let Event::UserEvent(event) = event else {
+            return;
+        };
+        match event {
+            UserEvent::Request(req) => {
+                ctx.lanes.taken();
+                if let Some(res) = lock_force!(api_manager).call(req, target, flow) {
+                    respond(&pending, res.0, res);
+                }
+            }
+            UserEvent::Task(task) => task(&ctx),
+            UserEvent::Exit => *flow = ControlFlow::Exit,
+            _ => {}
+        }
+    });
+    ctx.main_thread.close();
+    pump.abort();
+    lock_force!(ctx.window).clear();
+    Ok(())
+}
+
+/// Hands the lanes' requests to the harness's event loop.
+#[derive(Clone)]
+struct LoopDispatch(FrameEventLoopProxy);
+
+impl Dispatch for LoopDispatch {
+    fn dispatch(&self, req: ApiRequest) -> Result<(), String> {
+        self.0
+            .send_event(UserEvent::Request(req))
+            .map_err(|_| "The event loop has exited".to_string())
+    }
+}
+
+async fn within<F: std::future::Future>(
+    timeout: Duration,
+    future: F,
+) -> std::io::Result<F::Output> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "Timed out"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_manager::PermissionDenied;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn code_of(err: &anyhow::Error) -> Code {
+        err.downcast_ref::<CallError>()
+            .map(|err| err.code)
+            .unwrap_or(code::OK)
+    }
+
+    #[tokio::test]
+    async fn set_title_round_trip() {
+        let app = FakeApp::new();
+        app.handle("set_title", |req| {
+            let (title,) = req.args().get::<(String,)>()?;
+            Ok(json!(title == "Title"))
+        });
+        let mut client = app.connect().await.unwrap();
+
+        assert_eq!(
+            client.invoke("set_title", json!(["Title"])).await.unwrap(),
+            json!(true)
+        );
+        let calls = app.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].method, "set_title");
+        assert_eq!(calls[0].args, json!(["Title"]));
+        assert!(calls[0].connection.is_some());
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn errors_keep_their_code() {
+        let app = FakeApp::new();
+        app.handle("set_title", |_| {
+            Err(PermissionDenied {
+                permission: "window",
+            }
+            .into())
+        });
+        app.handle("fails", |_| Err(anyhow::anyhow!("Broken")));
+        let mut client = app.connect().await.unwrap();
+
+        let err = client
+            .invoke("set_title", json!(["Title"]))
+            .await
+            .unwrap_err();
+        assert_eq!(code_of(&err), code::PERMISSION_DENIED);
+        let err = client.invoke("fails", json!([])).await.unwrap_err();
+        assert_eq!(code_of(&err), code::ERROR);
+        assert!(err.to_string().contains("Broken"));
+        let err = client.invoke("no_such_api", json!([])).await.unwrap_err();
+        assert_eq!(code_of(&err), code::ERROR);
+        assert!(err.to_string().contains("Unknown method"));
+        // A bad argument answers that request only.
+        app.handle("echo", |req| Ok(req.args().get::<(u32,)>()?.0.into()));
+        assert!(client.invoke("echo", json!(["one"])).await.is_err());
+        assert_eq!(client.invoke("echo", json!([1])).await.unwrap(), json!(1));
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn disconnect_leaves_nothing_pending() {
+        let app = FakeApp::new();
+        app.hold("slow");
+        app.handle("set_title", |_| Ok(json!(true)));
+        let mut client = app.connect().await.unwrap();
+        let err = client
+            .invoke_timeout("slow", json!([]), Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Timed out"));
+        let connection = app.last_connection().unwrap();
+        assert_eq!(app.pending_for(connection), 1);
+
+        // The server side ends once the held request is answered.
+        let closed = tokio::spawn(client.close());
+        app.wait_disconnected(connection, WAIT).await.unwrap();
+        assert_eq!(app.release(json!(null)), 1);
+        assert_eq!(app.pending_for(connection), 0);
+        within(WAIT, closed).await.unwrap().unwrap().unwrap();
+
+        let mut client = app.connect().await.unwrap();
+        assert_eq!(
+            client.invoke("set_title", json!(["Title"])).await.unwrap(),
+            json!(true)
+        );
+        assert_ne!(app.last_connection(), Some(connection));
+        client.close().await.unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn real_set_title_handler() {
+        let harness = LoopHarness::start();
+        let response = harness.call("set_title", json!(["Harness"]));
+        assert_eq!(response.code(), code::OK, "{}", response.msg());
+        assert_eq!(response.result(), &json!(true));
+        let title = harness
+            .runtime()
+            .block_on(async {
+                harness
+                    .main_thread()
+                    .run(|ctx| ctx.get_window().map(|window| window.title()))
+                    .unwrap()
+                    .await
+            })
+            .unwrap();
+        assert_eq!(title.unwrap(), "Harness");
+
+        let response = harness.call("set_title", json!([]));
+        assert_eq!(response.code(), code::ERROR);
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use tao::event_loop::ControlFlow;
+
+use crate::config::{AppConfig, ControlFlowMode};
+
+/// Decides the event loop's control flow and when a tick is due.
+pub struct Ticker {
+    mode: ControlFlowMode,
+    interval: Option<Duration>,
+    next: Instant,
+    count: u64,
+}
+
+impl Ticker {
+    pub fn new(config: &AppConfig) -> Self {
+        let interval = config.tick_interval_ms.map(Duration::from_millis);
+        let mode = match (config.control_flow, interval) {
+            (ControlFlowMode::WaitUntil, None) => {
+                crate::log_warn!("control_flow wait_until without tick_interval_ms, using wait");
+                ControlFlowMode::Wait
+            }
+            (ControlFlowMode::Wait, Some(_)) => ControlFlowMode::WaitUntil,
+            (mode, _) => mode,
+        };
+        Self {
+            mode,
+            interval,
+            next: Instant::now() + interval.unwrap_or_default(),
+            count: 0,
+        }
+    }
+
+    pub fn control_flow(&self) -> ControlFlow {
+        match self.mode {
+            ControlFlowMode::Wait => ControlFlow::Wait,
+            ControlFlowMode::WaitUntil => ControlFlow::WaitUntil(self.next),
+            ControlFlowMode::Poll => ControlFlow::Poll,
+        }
+    }
+
+    /// Changes the tick interval at runtime. `None` goes back to `Wait`.
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+        self.mode = match interval {
+            Some(interval) => {
+                self.next = Instant::now() + interval;
+                ControlFlowMode::WaitUntil
+            }
+            None => ControlFlowMode::Wait,
+        };
+    }
+
+    /// Returns the tick number if a tick is due at `now`.
+    ///
+    /// The next deadline is scheduled from the previous one so intervals
+    /// don't drift, unless the loop fell more than an interval behind.
+    pub fn poll(&mut self, now: Instant) -> Option<u64> {
+        let due = match (self.mode, self.interval) {
+            (ControlFlowMode::Wait, _) | (_, None) => false,
+            (_, Some(interval)) => {
+                if now < self.next {
+                    false
+                } else {
+                    self.next += interval;
+                    if self.next <= now {
+                        self.next = now + interval;
+                    }
+                    true
+                }
+            }
+        };
+        due.then(|| {
+            self.count += 1;
+            self.count
+        })
+    }
+}
+
+/// Shortens `flow` so the loop wakes up at `deadline`. `Poll` and `Exit`
+/// are left alone.
+pub fn wake_by(flow: ControlFlow, deadline: Option<Instant>) -> ControlFlow {
+    match (flow, deadline) {
+        (ControlFlow::Wait, Some(deadline)) => ControlFlow::WaitUntil(deadline),
+        (ControlFlow::WaitUntil(current), Some(deadline)) => {
+            ControlFlow::WaitUntil(current.min(deadline))
+        }
+        (flow, _) => flow,
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde_json::Value;
+use tokio::{runtime::Handle, task::AbortHandle, time::Instant};
+
+use crate::utils::{FrameEventLoopProxy, UserEvent};
+
+pub type TimerId = u32;
+
+/// An API to call when a timer fires, with its argument array.
+#[derive(Clone)]
+pub struct TimerCall {
+    pub method: String,
+    pub args: Value,
+}
+
+struct Timer {
+    task: AbortHandle,
+    repeat: bool,
+    call: Option<TimerCall>,
+}
+
+/// Native timeouts and intervals, kept in [`AppContext`](crate::context::AppContext)
+/// so they survive WebView reloads.
+///
+/// Each timer is a tokio task that posts [`UserEvent::Timer`] through the
+/// event loop proxy, so firing is serialized with all other user events.
+#[derive(Default)]
+pub struct Timers {
+    last_id: TimerId,
+    live: HashMap<TimerId, Timer>,
+}
+
+impl Timers {
+    pub fn start(
+        &mut self,
+        rt: &Handle,
+        proxy: FrameEventLoopProxy,
+        period: Duration,
+        repeat: bool,
+        call: Option<TimerCall>,
+    ) -> TimerId {
+        let id = self.next_id();
+        let task = crate::tasks::spawn_on("timer", rt, async move {
+            // Deadlines are absolute, so a late wake-up doesn't shift the
+            // following ones.
+            let mut deadline = Instant::now() + period;
+            loop {
+                tokio::time::sleep_until(deadline).await;
+                if proxy.send_event(UserEvent::Timer(id)).is_err() || !repeat {
+                    break;
+                }
+                deadline += period;
+            }
+        });
+        self.live.insert(
+            id,
+            Timer {
+                task: task.abort_handle(),
+                repeat,
+                call,
+            },
+        );
+        id
+    }
+
+    /// Ids are never reused while a timer with the same id is live.
+    fn next_id(&mut self) -> TimerId {
+        loop {
+            self.last_id = self.last_id.wrapping_add(1).max(1);
+            if !self.live.contains_key(&self.last_id) {
+                return self.last_id;
+            }
+        }
+    }
+
+    pub fn clear(&mut self, id: TimerId) -> bool {
+        match self.live.remove(&id) {
+            Some(timer) => {
+                timer.task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Called when [`UserEvent::Timer`] arrives. Returns `None` for timers
+    /// cleared after the event was posted; one-shot timers are removed.
+    pub fn fired(&mut self, id: TimerId) -> Option<Option<TimerCall>> {
+        let timer = self.live.get(&id)?;
+        if timer.repeat {
+            return Some(timer.call.clone());
+        }
+        self.live.remove(&id).map(|timer| timer.call)
+    }
+
+    pub fn clear_all(&mut self) {
+        for (_, timer) in self.live.drain() {
+            timer.task.abort();
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! How long requests wait and run, measured where they are queued.
+//!
+//! Every queued request gets a [`RequestTiming`]; the event loop marks when
+//! its handler starts and returns, and the connection finishes it when the
+//! response arrives. Those durations feed the `latency` histograms of
+//! `get_runtime_metrics`, and a connection with the `timing` feature in its
+//! hello also gets them in every response as `meta`: `queued_us`,
+//! `handler_us`, `total_us` and `server_ts`.
+//!
+//! Durations come from the monotonic clock; only `server_ts`, the Unix time
+//! in milliseconds the response was ready, is wall-clock time.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::lock_force;
+
+/// Upper bounds of the histogram buckets in microseconds; the last bucket
+/// counts everything slower.
+const BOUNDS_US: [u64; 12] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+];
+
+/// The timing of one request.
+#[derive(Debug)]
+pub struct RequestTiming {
+    queued: Instant,
+    /// When the handler started, and how long it ran.
+    handler: Mutex<Option<(Instant, Duration)>>,
+}
+
+impl RequestTiming {
+    pub fn start() -> Arc<Self> {
+        Arc::new(Self {
+            queued: Instant::now(),
+            handler: Mutex::new(None),
+        })
+    }
+
+    /// Runs the handler of the request. A deferred handler's time ends when
+    /// it returns; the wait for its answer only counts in `total_us`.
+    pub fn handle<T>(&self, handler: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = handler();
+        *lock_force!(self.handler) = Some((started, started.elapsed()));
+        result
+    }
+
+    /// The timing now that the response is there, also recorded in the
+    /// histograms.
+    pub fn finish(&self) -> Meta {
+        let total = self.queued.elapsed();
+        let (queued, handler) = match *lock_force!(self.handler) {
+            Some((started, handler)) => (started.duration_since(self.queued), handler),
+            // Never reached the event loop, e.g. the app closed.
+            None => (total, Duration::ZERO),
+        };
+        let meta = Meta {
+            queued_us: micros(queued),
+            handler_us: micros(handler),
+            total_us: micros(total),
+            server_ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+        };
+        LATENCY.queued.record(meta.queued_us);
+        LATENCY.handler.record(meta.handler_us);
+        LATENCY.total.record(meta.total_us);
+        meta
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
+
+/// The `meta` of a response.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct Meta {
+    /// From queueing until the handler started.
+    pub queued_us: u64,
+    pub handler_us: u64,
+    /// From queueing until the response was ready.
+    pub total_us: u64,
+    pub server_ts: u64,
+}
+
+struct Histogram {
+    buckets: [AtomicU64; BOUNDS_US.len() + 1],
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BOUNDS_US.len() + 1],
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, us: u64) {
+        let bucket = BOUNDS_US
+            .iter()
+            .position(|bound| us <= *bound)
+            .unwrap_or(BOUNDS_US.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        HistogramSnapshot {
+            bounds_us: &BOUNDS_US,
+            count: counts.iter().sum(),
+            counts,
+            sum_us: self.sum_us.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Latency {
+    queued: Histogram,
+    handler: Histogram,
+    total: Histogram,
+}
+
+static LATENCY: Latency = Latency {
+    queued: Histogram::new(),
+    handler: Histogram::new(),
+    total: Histogram::new(),
+};
+
+#[derive(Serialize, Debug)]
+pub struct HistogramSnapshot {
+    /// Upper bounds of `counts` but the last one, which has no bound.
+    pub bounds_us: &'static [u64],
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum_us: u64,
+}
+
+/// Request latencies since startup, from the same measurements as `meta`.
+#[derive(Serialize, Debug)]
+pub struct LatencyMetrics {
+    pub queued: HistogramSnapshot,
+    pub handler: HistogramSnapshot,
+    pub total: HistogramSnapshot,
+}
+
+pub fn latency() -> LatencyMetrics {
+    LatencyMetrics {
+        queued: LATENCY.queued.snapshot(),
+        handler: LATENCY.handler.snapshot(),
+        total: LATENCY.total.snapshot(),
+    }
+}
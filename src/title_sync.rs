@@ -0,0 +1,47 @@
+//! With `sync_title`, the window title follows `document.title`, formatted
+//! by `title_format`, e.g. `"{title} — MyApp"`.
+//!
+//! wry reports title changes on every backend, so no observer is injected.
+//! A `set_title` call pauses the sync until the next navigation, same-document
+//! ones included, so the title it set stays.
+
+/// The `{title}` placeholder of `title_format`.
+const PLACEHOLDER: &str = "{title}";
+
+pub struct TitleSync {
+    enabled: bool,
+    format: Option<String>,
+    paused: bool,
+}
+
+impl TitleSync {
+    pub fn new(enabled: bool, format: Option<String>) -> Self {
+        Self {
+            enabled,
+            format,
+            paused: false,
+        }
+    }
+
+    /// The window title for the document title `title`, `None` if the
+    /// window keeps its own.
+    pub fn title_for(&self, title: &str) -> Option<String> {
+        if !self.enabled || self.paused || title.is_empty() {
+            return None;
+        }
+        Some(match &self.format {
+            Some(format) => format.replace(PLACEHOLDER, title),
+            None => title.to_string(),
+        })
+    }
+
+    /// A title was set explicitly.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// The page navigated.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+}
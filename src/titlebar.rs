@@ -0,0 +1,135 @@
+//! The macOS inline title bar: `titlebar_transparent`, `title_hidden`,
+//! `fullsize_content_view` and `traffic_light_offset`, at creation and
+//! through `set_titlebar`.
+//!
+//! Other platforms accept the options so one options dict works
+//! everywhere; they are ignored with a creation warning, see
+//! [`creation_warnings`], and `set_titlebar` returns `false`.
+
+use serde::Deserialize;
+use tao::window::{Window, WindowBuilder};
+
+use crate::config::AppConfig;
+
+/// Where the traffic lights go, in logical pixels from the window's
+/// top-left corner.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct Offset {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Arguments of `set_titlebar`; only the given ones change.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TitlebarUpdate {
+    pub titlebar_transparent: Option<bool>,
+    pub title_hidden: Option<bool>,
+    pub fullsize_content_view: Option<bool>,
+    pub traffic_light_offset: Option<Offset>,
+}
+
+fn requested(config: &AppConfig) -> Vec<&'static str> {
+    [
+        ("titlebar_transparent", config.titlebar_transparent),
+        ("title_hidden", config.title_hidden),
+        ("fullsize_content_view", config.fullsize_content_view),
+        (
+            "traffic_light_offset",
+            config.traffic_light_offset.is_some(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, set)| set.then_some(name))
+    .collect()
+}
+
+/// The title bar options that were given but don't apply on this platform.
+pub fn creation_warnings(config: &AppConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !cfg!(target_os = "macos") {
+        warnings.extend(
+            requested(config)
+                .into_iter()
+                .map(|name| format!("{} only applies on macOS and was ignored", name)),
+        );
+    }
+    if config.titlebar_overlay && !cfg!(target_os = "windows") {
+        warnings.push("titlebar_overlay only applies on Windows and was ignored".to_string());
+    }
+    warnings
+}
+
+pub fn apply(builder: WindowBuilder, config: &AppConfig) -> WindowBuilder {
+    #[cfg(target_os = "macos")]
+    {
+        use tao::{dpi::LogicalPosition, platform::macos::WindowBuilderExtMacOS};
+
+        let builder = builder
+            .with_titlebar_transparent(config.titlebar_transparent)
+            .with_title_hidden(config.title_hidden)
+            .with_fullsize_content_view(config.fullsize_content_view);
+        match config.traffic_light_offset {
+            Some(offset) => {
+                builder.with_traffic_light_inset(LogicalPosition::new(offset.x, offset.y))
+            }
+            None => builder,
+        }
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = config;
+        builder
+    }
+}
+
+/// Changes the title bar at runtime; `false` where there is no such title bar.
+pub fn update(window: &Window, update: &TitlebarUpdate) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        macos::update(window, update);
+        true
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (window, update);
+        false
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use objc2::{msg_send, runtime::AnyObject};
+    use tao::{dpi::LogicalPosition, platform::macos::WindowExtMacOS, window::Window};
+
+    use super::TitlebarUpdate;
+
+    /// `NSWindowTitleVisibility`
+    const NS_WINDOW_TITLE_VISIBLE: isize = 0;
+    const NS_WINDOW_TITLE_HIDDEN: isize = 1;
+
+    pub fn update(window: &Window, update: &TitlebarUpdate) {
+        if let Some(transparent) = update.titlebar_transparent {
+            window.set_titlebar_transparent(transparent);
+        }
+        if let Some(fullsize) = update.fullsize_content_view {
+            window.set_fullsize_content_view(fullsize);
+        }
+        if let Some(hidden) = update.title_hidden {
+            // tao only sets it at creation.
+            let visibility = if hidden {
+                NS_WINDOW_TITLE_HIDDEN
+            } else {
+                NS_WINDOW_TITLE_VISIBLE
+            };
+            unsafe {
+                let ns_window = &*(window.ns_window() as *const AnyObject);
+                let _: () = msg_send![ns_window, setTitleVisibility: visibility];
+            }
+        }
+        if let Some(offset) = update.traffic_light_offset {
+            window.set_traffic_light_inset(LogicalPosition::new(offset.x, offset.y));
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! `titlebar_overlay` on Windows: the native frame stays, so snapping,
+//! snap layouts, aero shake and the system menu keep working, but the page
+//! extends up into the title bar.
+//!
+//! The window is subclassed: `WM_NCCALCSIZE` drops the caption from the
+//! non-client area, DWM keeps drawing and hit-testing the caption buttons
+//! in the extended frame, and the WebView is clipped around them so they
+//! show and take clicks. `data-pyframe-drag-region` elements drag the
+//! window as usual; a right-click on one opens the system menu.
+//!
+//! Where the buttons are goes to the page as a [`Layout`], in the
+//! `titlebar-overlay` event and as the `--pyframe-titlebar-*` CSS
+//! variables, so content can keep clear of them. Other platforms ignore
+//! the option with a creation warning.
+
+use anyhow::Result;
+use serde::Serialize;
+use tao::window::Window;
+
+/// Posted on right-click in a `data-pyframe-drag-region`.
+pub const SYSTEM_MENU: &str = "__system_menu";
+
+/// Logical pixels in the window's content area.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Payload of `titlebar-overlay` and `get_titlebar_overlay`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+pub struct Layout {
+    /// Height of the title bar from the top of the content area.
+    pub height: f64,
+    /// The minimize, maximize and close buttons.
+    pub buttons: Rect,
+    /// Maximized windows have no top resize edge.
+    pub maximized: bool,
+}
+
+/// The overlay of the window; off unless `titlebar_overlay` applies here.
+#[derive(Default)]
+pub struct TitlebarOverlay {
+    layout: Option<Layout>,
+}
+
+impl TitlebarOverlay {
+    /// Subclasses `window` if `enabled` and on Windows.
+    pub fn install(window: &Window, enabled: bool) -> Result<Self> {
+        #[cfg(target_os = "windows")]
+        if enabled {
+            win32::install(window)?;
+            return Ok(Self {
+                layout: Some(win32::layout(window)?),
+            });
+        }
+        let _ = (window, enabled);
+        Ok(Self::default())
+    }
+
+    /// `None` while the overlay is off.
+    pub fn layout(&self) -> Option<Layout> {
+        self.layout
+    }
+
+    /// Publishes the initial layout before the page could get the event.
+    pub fn init_script(&self) -> Result<Option<String>> {
+        let Some(layout) = self.layout else {
+            return Ok(None);
+        };
+        Ok(Some(crate::assets::_TITLEBAR_OVERLAY_SCRIPT.replace(
+            "__PYFRAME_TITLEBAR_OVERLAY__",
+            &serde_json::to_string(&layout)?,
+        )))
+    }
+
+    /// Call on resize, maximize and scale factor changes, and once the
+    /// WebView exists. Returns the layout if it changed.
+    pub fn resized(&mut self, window: &Window) -> Option<Layout> {
+        self.layout?;
+        #[cfg(target_os = "windows")]
+        match win32::layout(window) {
+            Ok(layout) if Some(layout) != self.layout => {
+                self.layout = Some(layout);
+                return Some(layout);
+            }
+            Ok(_) => {}
+            Err(e) => crate::log_err!(format!("titlebar_overlay: {}", e)),
+        }
+        let _ = window;
+        None
+    }
+}
+
+/// Opens the system menu at the cursor; nothing while the overlay is off.
+pub fn show_system_menu(overlay: &TitlebarOverlay, window: &Window) -> Result<()> {
+    if overlay.layout.is_none() {
+        return Ok(());
+    }
+    #[cfg(target_os = "windows")]
+    win32::show_system_menu(window)?;
+    let _ = window;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::ffi::c_void;
+
+    use anyhow::{anyhow, Result};
+    use tao::{platform::windows::WindowExtWindows, window::Window};
+    use windows::Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+        Graphics::{
+            Dwm::{
+                DwmDefWindowProc, DwmExtendFrameIntoClientArea, DwmGetWindowAttribute,
+                DWMWA_CAPTION_BUTTON_BOUNDS,
+            },
+            Gdi::{
+                ClientToScreen, CombineRgn, CreateRectRgn, DeleteObject, ScreenToClient,
+                SetWindowRgn, RGN_COPY, RGN_DIFF,
+            },
+        },
+        UI::{
+            Controls::MARGINS,
+            HiDpi::{GetDpiForWindow, GetSystemMetricsForDpi},
+            Shell::{DefSubclassProc, SetWindowSubclass},
+            WindowsAndMessaging::{
+                EnableMenuItem, GetClientRect, GetCursorPos, GetSystemMenu, GetWindow,
+                GetWindowRect, IsZoomed, PostMessageW, SetWindowPos, TrackPopupMenu, GW_CHILD,
+                GW_HWNDNEXT, HTCLIENT, HTTOP, MF_BYCOMMAND, MF_ENABLED, MF_GRAYED,
+                NCCALCSIZE_PARAMS, SC_MAXIMIZE, SC_MOVE, SC_RESTORE, SC_SIZE, SM_CXPADDEDBORDER,
+                SM_CYFRAME, SWP_FRAMECHANGED, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, SWP_NOZORDER,
+                TPM_RETURNCMD, TPM_RIGHTBUTTON, WM_NCCALCSIZE, WM_NCHITTEST, WM_SYSCOMMAND,
+            },
+        },
+    };
+
+    use super::{Layout, Rect};
+
+    /// Tells our subclass apart from others on the window.
+    const SUBCLASS_ID: usize = 0x7079_6672;
+
+    pub fn install(window: &Window) -> Result<()> {
+        let hwnd = HWND(window.hwnd() as _);
+        unsafe {
+            if !SetWindowSubclass(hwnd, Some(subclass), SUBCLASS_ID, 0).as_bool() {
+                return Err(anyhow!("Could not subclass the window"));
+            }
+            // Runs WM_NCCALCSIZE again, now through the subclass.
+            SetWindowPos(
+                hwnd,
+                None,
+                0,
+                0,
+                0,
+                0,
+                SWP_FRAMECHANGED | SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Measures the caption buttons, extends the frame under them and
+    /// clips the WebView around them.
+    pub fn layout(window: &Window) -> Result<Layout> {
+        let hwnd = HWND(window.hwnd() as _);
+        let scale = window.scale_factor();
+        unsafe {
+            let maximized = IsZoomed(hwnd).as_bool();
+            let mut bounds = RECT::default();
+            DwmGetWindowAttribute(
+                hwnd,
+                DWMWA_CAPTION_BUTTON_BOUNDS,
+                &mut bounds as *mut _ as *mut c_void,
+                std::mem::size_of::<RECT>() as u32,
+            )?;
+            // DWM measures from the window's corner, which a maximized
+            // window has off-screen.
+            let mut frame = RECT::default();
+            GetWindowRect(hwnd, &mut frame)?;
+            let mut origin = POINT::default();
+            let _ = ClientToScreen(hwnd, &mut origin);
+            let (dx, dy) = (origin.x - frame.left, origin.y - frame.top);
+            let buttons = RECT {
+                left: bounds.left - dx,
+                top: (bounds.top - dy).max(0),
+                right: bounds.right - dx,
+                bottom: bounds.bottom - dy,
+            };
+
+            DwmExtendFrameIntoClientArea(
+                hwnd,
+                &MARGINS {
+                    cxLeftWidth: 0,
+                    cxRightWidth: 0,
+                    cyTopHeight: buttons.bottom,
+                    cyBottomHeight: 0,
+                },
+            )?;
+            let edge = if maximized { 0 } else { frame_thickness(hwnd) };
+            clip_children(hwnd, &buttons, edge)?;
+
+            let logical = |px: i32| px as f64 / scale;
+            Ok(Layout {
+                height: logical(buttons.bottom),
+                buttons: Rect {
+                    x: logical(buttons.left),
+                    y: logical(buttons.top),
+                    width: logical(buttons.right - buttons.left),
+                    height: logical(buttons.bottom - buttons.top),
+                },
+                maximized,
+            })
+        }
+    }
+
+    /// Cuts the caption buttons and the top resize edge out of the
+    /// window's children, i.e. wry's WebView host, which fills the client
+    /// area from its corner.
+    unsafe fn clip_children(hwnd: HWND, buttons: &RECT, edge: i32) -> Result<()> {
+        let mut client = RECT::default();
+        GetClientRect(hwnd, &mut client)?;
+        let region = CreateRectRgn(0, 0, client.right, client.bottom);
+        for (left, top, right, bottom) in [
+            (buttons.left, buttons.top, buttons.right, buttons.bottom),
+            (0, 0, client.right, edge),
+        ] {
+            let hole = CreateRectRgn(left, top, right, bottom);
+            CombineRgn(Some(region), Some(region), Some(hole), RGN_DIFF);
+            let _ = DeleteObject(hole.into());
+        }
+        let mut child = GetWindow(hwnd, GW_CHILD);
+        while let Ok(current) = child {
+            // Each window owns the region it is given.
+            let copy = CreateRectRgn(0, 0, 0, 0);
+            CombineRgn(Some(copy), Some(region), None, RGN_COPY);
+            SetWindowRgn(current, Some(copy), true);
+            child = GetWindow(current, GW_HWNDNEXT);
+        }
+        let _ = DeleteObject(region.into());
+        Ok(())
+    }
+
+    fn frame_thickness(hwnd: HWND) -> i32 {
+        unsafe {
+            let dpi = GetDpiForWindow(hwnd);
+            GetSystemMetricsForDpi(SM_CYFRAME, dpi) + GetSystemMetricsForDpi(SM_CXPADDEDBORDER, dpi)
+        }
+    }
+
+    unsafe extern "system" fn subclass(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        _data: usize,
+    ) -> LRESULT {
+        // The caption buttons: hover, snap layouts and clicks.
+        let mut result = LRESULT(0);
+        if DwmDefWindowProc(hwnd, msg, wparam, lparam, &mut result).as_bool() {
+            return result;
+        }
+        match msg {
+            WM_NCCALCSIZE if wparam.0 != 0 => {
+                let params = &mut *(lparam.0 as *mut NCCALCSIZE_PARAMS);
+                let top = params.rgrc[0].top;
+                let result = DefSubclassProc(hwnd, msg, wparam, lparam);
+                // Keeps the side and bottom borders, drops the caption.
+                params.rgrc[0].top = top;
+                if IsZoomed(hwnd).as_bool() {
+                    // Maximized, the frame hangs off the monitor's edge
+                    // and would cut into the content.
+                    params.rgrc[0].top += frame_thickness(hwnd);
+                }
+                result
+            }
+            WM_NCHITTEST => {
+                let hit = DefSubclassProc(hwnd, msg, wparam, lparam);
+                if hit.0 as u32 != HTCLIENT || IsZoomed(hwnd).as_bool() {
+                    return hit;
+                }
+                let mut point = POINT {
+                    x: (lparam.0 & 0xffff) as i16 as i32,
+                    y: ((lparam.0 >> 16) & 0xffff) as i16 as i32,
+                };
+                let _ = ScreenToClient(hwnd, &mut point);
+                if point.y < frame_thickness(hwnd) {
+                    LRESULT(HTTOP as isize)
+                } else {
+                    hit
+                }
+            }
+            _ => DefSubclassProc(hwnd, msg, wparam, lparam),
+        }
+    }
+
+    pub fn show_system_menu(window: &Window) -> Result<()> {
+        let hwnd = HWND(window.hwnd() as _);
+        unsafe {
+            let menu = GetSystemMenu(hwnd, false);
+            let maximized = IsZoomed(hwnd).as_bool();
+            for (item, enabled) in [
+                (SC_RESTORE, maximized),
+                (SC_MOVE, !maximized),
+                (SC_SIZE, !maximized),
+                (SC_MAXIMIZE, !maximized),
+            ] {
+                let state = if enabled { MF_ENABLED } else { MF_GRAYED };
+                let _ = EnableMenuItem(menu, item, MF_BYCOMMAND | state);
+            }
+            let mut cursor = POINT::default();
+            GetCursorPos(&mut cursor)?;
+            let command = TrackPopupMenu(
+                menu,
+                TPM_RETURNCMD | TPM_RIGHTBUTTON,
+                cursor.x,
+                cursor.y,
+                None,
+                hwnd,
+                None,
+            );
+            if command.0 != 0 {
+                PostMessageW(
+                    Some(hwnd),
+                    WM_SYSCOMMAND,
+                    WPARAM(command.0 as usize),
+                    LPARAM(0),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+//! How requests reach the event loop.
+//!
+//! With `transport: "tcp"` (the default) Python talks to the event loop
+//! over the TCP bridge and the page to Python over its WebSocket server.
+//! `"ipc-only"` opens no socket at all: the page calls the native APIs
+//! through `window.ipc` and gets its answers as `invoke-reply` events, and
+//! Python calls them in-process through `pygcc.FrameHandle`. Built without
+//! the `tcp` cargo feature, only `"ipc-only"` is available.
+//!
+//! Both in-process callers queue their requests like a TCP connection
+//! does, through the same lanes, in-flight bound and pending map, as
+//! [`IN_PROCESS`].
+//!
+//! The page only reaches the APIs listed in `page_apis`, and only from the
+//! app's own [`AppOrigin`]; pages it navigates to elsewhere can't call
+//! anything.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{runtime::Handle, sync::Semaphore};
+use url::Url;
+
+use crate::{
+    api_manager::{code, ApiArguments, ApiRequest, ApiResponse, RequestMetadata},
+    audit::AuditLog,
+    connections::ConnectionId,
+    lanes::{Priority, RequestLanes},
+    lock_force,
+    main_thread::MainThread,
+    request_context::{Peer, RequestContext},
+    utils::PendingMap,
+};
+
+/// Prefix of `pyframe.invoke` calls in ipc-only mode, followed by a
+/// [`PageCall`] as JSON. Answered with an `invoke-reply` event.
+pub const INVOKE: &str = "__invoke:";
+
+/// The connection of in-process requests; TCP connections count from 1.
+pub const IN_PROCESS: ConnectionId = 0;
+
+/// The `transport` creation option.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    Tcp,
+    IpcOnly,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        if cfg!(feature = "tcp") {
+            Transport::Tcp
+        } else {
+            Transport::IpcOnly
+        }
+    }
+}
+
+impl Transport {
+    pub fn name(self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::IpcOnly => "ipc-only",
+        }
+    }
+
+    /// Fails for `tcp` in a build without the `tcp` feature.
+    pub fn check(self) -> Result<()> {
+        if self == Transport::Tcp && !cfg!(feature = "tcp") {
+            return Err(anyhow::Error::new(crate::api_manager::Unsupported {
+                feature: "transport \"tcp\" (built without the tcp feature)".into(),
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// A `pyframe.invoke` from the page in ipc-only mode.
+#[derive(Deserialize, Debug)]
+pub struct PageCall {
+    /// The page's id of the call, echoed in the `invoke-reply`.
+    pub id: u64,
+    pub method: String,
+    #[serde(default)]
+    pub args: Value,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Whether the reply carries the `meta` of [`crate::timing`].
+    #[serde(default)]
+    pub timing: bool,
+}
+
+/// The answer to a `call` of an API that isn't in `page_apis`.
+pub fn refused(call: &PageCall) -> Value {
+    json!({
+        "id": call.id,
+        "error": {
+            "message": format!("{} is not in page_apis", call.method),
+            "code": code::PERMISSION_DENIED,
+        },
+    })
+}
+
+/// Where the app's own pages come from: the `url` option or the local
+/// server, the `assets` schemes, and `about:blank` for the `html` option.
+#[derive(Clone, Debug)]
+pub struct AppOrigin {
+    entry: Option<url::Origin>,
+    html: bool,
+    schemes: Vec<String>,
+}
+
+impl AppOrigin {
+    /// `entry` is the URL the WebView starts at, `None` for inline HTML.
+    pub fn new<'a>(entry: Option<&str>, schemes: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            entry: entry
+                .and_then(|url| Url::parse(url).ok())
+                .map(|url| url.origin()),
+            html: entry.is_none(),
+            schemes: schemes.into_iter().map(str::to_ascii_lowercase).collect(),
+        }
+    }
+
+    /// Whether a page at `uri`, as the IPC handler reports it, is the app's.
+    pub fn allows(&self, uri: &str) -> bool {
+        let Ok(url) = Url::parse(uri) else {
+            // Inline HTML has no URL of its own on some platforms.
+            return self.html;
+        };
+        if self.html && url.as_str() == "about:blank" {
+            return true;
+        }
+        if self.entry.as_ref() == Some(&url.origin()) && url.origin().is_tuple() {
+            return true;
+        }
+        // `app://localhost/...`, or `http://app.localhost/...` where the
+        // WebView can't serve custom schemes directly (WebView2).
+        self.schemes.iter().any(|scheme| {
+            url.scheme() == scheme
+                || (matches!(url.scheme(), "http" | "https")
+                    && url.host_str() == Some(&format!("{}.localhost", scheme)))
+        })
+    }
+}
+
+/// Queues requests from inside the process.
+#[derive(Clone)]
+pub struct LocalCalls {
+    pending: PendingMap,
+    in_flight: Arc<Semaphore>,
+    lanes: Arc<RequestLanes>,
+    rt: Handle,
+    main_thread: MainThread,
+    audit: Option<Arc<AuditLog>>,
+}
+
+/// The calls of the running webframe, for `FrameHandle`.
+static CURRENT: Mutex<Option<LocalCalls>> = Mutex::new(None);
+
+impl LocalCalls {
+    pub fn new(
+        pending: PendingMap,
+        in_flight: Arc<Semaphore>,
+        lanes: Arc<RequestLanes>,
+        rt: Handle,
+        main_thread: MainThread,
+        audit: Option<Arc<AuditLog>>,
+    ) -> Self {
+        Self {
+            pending,
+            in_flight,
+            lanes,
+            rt,
+            main_thread,
+            audit,
+        }
+    }
+
+    /// Makes these the calls of `FrameHandle`, until [`LocalCalls::uninstall`].
+    pub fn install(&self) {
+        *lock_force!(CURRENT) = Some(self.clone());
+    }
+
+    pub fn uninstall() {
+        lock_force!(CURRENT).take();
+    }
+
+    /// The running webframe's calls, if there is one.
+    #[allow(dead_code)]
+    pub fn current() -> Option<Self> {
+        lock_force!(CURRENT).clone()
+    }
+
+    /// Queues `method(args)` of `client`, `"page"` or `"frame-handle"` in
+    /// the audit log; the future resolves with its response.
+    pub fn call(
+        &self,
+        client: &str,
+        method: &str,
+        args: Value,
+        priority: Priority,
+    ) -> impl Future<Output = ApiResponse> + Send + 'static {
+        let id = {
+            let pending = lock_force!(self.pending);
+            // Shared with TCP clients, which pick their own ids; a taken one
+            // is answered with BUSY below.
+            (0..=u8::MAX)
+                .find(|id| !pending.contains_key(id))
+                .unwrap_or(0)
+        };
+        let mut req = ApiRequest(
+            id,
+            method.to_string(),
+            ApiArguments::new(args),
+            RequestMetadata {
+                priority,
+                ..Default::default()
+            },
+        );
+        req.3.context = Some(Box::new(RequestContext::new(
+            Peer::in_process(client),
+            &req.3,
+        )));
+        let record = self
+            .audit
+            .as_ref()
+            .and_then(|audit| audit.begin(&req, IN_PROCESS, client));
+        let response =
+            crate::connections::queue_local(req, &self.pending, &self.in_flight, &self.lanes);
+        async move {
+            let response = response.await;
+            if let Some(record) = record {
+                record.finish(&response);
+            }
+            response
+        }
+    }
+
+    /// [`LocalCalls::call`] from a thread outside the runtime; `None` if
+    /// there was no response within `timeout`.
+    #[allow(dead_code)]
+    pub fn call_blocking(
+        &self,
+        method: &str,
+        args: Value,
+        timeout: Duration,
+    ) -> Option<ApiResponse> {
+        let response = self.call("frame-handle", method, args, Priority::Normal);
+        self.rt
+            .block_on(async { tokio::time::timeout(timeout, response).await })
+            .ok()
+    }
+
+    /// Answers `call` from the page with an `invoke-reply` event.
+    pub fn answer_page(&self, call: PageCall) {
+        let response = self.call("page", &call.method, call.args, call.priority);
+        let main_thread = self.main_thread.clone();
+        crate::tasks::spawn_on("page_invoke", &self.rt, async move {
+            let response = response.await;
+            let mut reply = if response.code() == code::OK {
+                json!({ "id": call.id, "result": response.result() })
+            } else {
+                json!({
+                    "id": call.id,
+                    "error": { "message": response.msg(), "code": response.code() },
+                })
+            };
+            if let Some(meta) = response.meta().filter(|_| call.timing) {
+                reply["meta"] = json!(meta);
+            }
+            let _ = main_thread.run(move |ctx| {
+                crate::log_if_err!(ctx.emit("invoke-reply", reply));
+            });
+        });
+    }
+
+    /// Closes the webframe through its exit hooks, or with `force` without
+    /// waiting for them.
+    #[allow(dead_code)]
+    pub fn close(&self, force: bool) -> Result<()> {
+        self.main_thread.send(if force {
+            crate::utils::UserEvent::Shutdown
+        } else {
+            crate::utils::UserEvent::Exit
+        })
+    }
+
+    /// Emits `event` to the pages, as Python's `emit` frames do.
+    #[allow(dead_code)]
+    pub fn emit(&self, event: String, data: Value) -> Result<()> {
+        self.main_thread.run(move |ctx| {
+            crate::log_if_err!(ctx.emit(&event, data));
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use std::{path::Path, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use tao::window::Window;
+use tray_icon::{
+    menu::{Menu, MenuEvent, MenuItem},
+    Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent,
+};
+
+use crate::{
+    lock_force,
+    menu::{ItemSpec, ItemUpdate, MenuClick, MenuModel},
+    utils::{FrameEventLoopProxy, UserEvent},
+};
+
+/// Menu item ids of the default tray menu.
+pub const SHOW_ITEM: &str = "pyframe.tray.show";
+pub const QUIT_ITEM: &str = "pyframe.tray.quit";
+
+const DEFAULT_ICON_SIZE: u32 = 32;
+
+/// The tray icon and whether minimizing/closing hides the window into it.
+#[derive(Default)]
+pub struct TrayState {
+    pub minimize_to_tray: bool,
+    icon: Option<TrayIcon>,
+    /// Set with `set_tray_menu`, replacing "Show" and "Quit".
+    menu: Option<MenuModel>,
+}
+
+impl TrayState {
+    /// Creates the tray icon on first use and removes it when disabled,
+    /// unless it has a menu of its own.
+    pub fn set_minimize_to_tray(&mut self, enabled: bool, icon: Option<&Path>) -> Result<()> {
+        if enabled && self.icon.is_none() {
+            self.icon = Some(build_tray_icon(icon, self.native_menu()?)?);
+        } else if !enabled && self.menu.is_none() {
+            self.icon = None;
+        }
+        self.minimize_to_tray = enabled;
+        Ok(())
+    }
+
+    /// Replaces the tray menu, showing the icon if it isn't yet. A menu
+    /// with the same items as the current one is updated in place.
+    pub fn set_menu(&mut self, items: Vec<ItemSpec>, icon: Option<&Path>) -> Result<()> {
+        if let Some(menu) = self.menu.as_mut() {
+            if menu.update_from(items.clone())? {
+                return Ok(());
+            }
+        }
+        let menu = MenuModel::build(items)?;
+        match &self.icon {
+            Some(tray) => tray.set_menu(Some(Box::new(menu.menu().clone()))),
+            None => self.icon = Some(build_tray_icon(icon, menu.menu().clone())?),
+        }
+        // Frees the old native menu.
+        self.menu = Some(menu);
+        Ok(())
+    }
+
+    pub fn update_item(&mut self, id: &str, update: ItemUpdate) -> Result<()> {
+        self.menu
+            .as_mut()
+            .ok_or_else(|| anyhow!("The tray has no menu set with set_tray_menu"))?
+            .update_item(id, update)
+    }
+
+    /// The `set_tray_menu` items with their current state.
+    pub fn menu_items(&self) -> Option<Vec<ItemSpec>> {
+        self.menu.as_ref().map(|menu| menu.spec().to_vec())
+    }
+
+    /// `None` unless `id` is an item of the `set_tray_menu` menu.
+    pub fn clicked(&mut self, id: &tray_icon::menu::MenuId) -> Option<MenuClick> {
+        self.menu.as_mut()?.clicked(id)
+    }
+
+    fn native_menu(&self) -> Result<Menu> {
+        if let Some(menu) = &self.menu {
+            return Ok(menu.menu().clone());
+        }
+        let menu = Menu::new();
+        menu.append(&MenuItem::with_id(SHOW_ITEM, "Show", true, None))?;
+        menu.append(&MenuItem::with_id(QUIT_ITEM, "Quit", true, None))?;
+        Ok(menu)
+    }
+}
+
+fn build_tray_icon(icon: Option<&Path>, menu: Menu) -> Result<TrayIcon> {
+    Ok(TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_menu_on_left_click(false)
+        .with_icon(load_icon(icon)?)
+        .with_tooltip("PyFrame")
+        .build()?)
+}
+
+fn load_icon(path: Option<&Path>) -> Result<Icon> {
+    let (rgba, width, height) = match path {
+        Some(path) => {
+            let image = image::open(path)
+                .map_err(|e| anyhow!("Cannot load tray icon {:?}: {}", path, e))?
+                .into_rgba8();
+            let (width, height) = image.dimensions();
+            (image.into_raw(), width, height)
+        }
+        None => (default_icon_rgba(), DEFAULT_ICON_SIZE, DEFAULT_ICON_SIZE),
+    };
+    Ok(Icon::from_rgba(rgba, width, height)?)
+}
+
+/// A plain filled circle, used when no icon file is configured.
+fn default_icon_rgba() -> Vec<u8> {
+    let size = DEFAULT_ICON_SIZE as i32;
+    let radius = size / 2;
+    let mut rgba = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (dx, dy) = (x - radius, y - radius);
+            let alpha = if dx * dx + dy * dy < radius * radius {
+                0xff
+            } else {
+                0
+            };
+            rgba.extend_from_slice(&[0x3b, 0x82, 0xf6, alpha]);
+        }
+    }
+    rgba
+}
+
+/// Routes tray clicks and menu selections into the event loop.
+pub fn forward_events(proxy: FrameEventLoopProxy) {
+    let tray_proxy = Mutex::new(proxy.clone());
+    TrayIconEvent::set_event_handler(Some(move |event| {
+        if let TrayIconEvent::Click {
+            button: MouseButton::Left,
+            button_state: MouseButtonState::Up,
+            ..
+        } = event
+        {
+            let _ = lock_force!(tray_proxy).send_event(UserEvent::TrayClicked);
+        }
+    }));
+    let menu_proxy = Mutex::new(proxy);
+    MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+        let _ = lock_force!(menu_proxy).send_event(UserEvent::Menu(event.id));
+    }));
+}
+
+fn set_skip_taskbar(window: &Window, skip: bool) {
+    #[cfg(target_os = "windows")]
+    {
+        use tao::platform::windows::WindowExtWindows;
+        crate::log_if_err!(window.set_skip_taskbar(skip));
+    }
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        use tao::platform::unix::WindowExtUnix;
+        crate::log_if_err!(window.set_skip_taskbar(skip));
+    }
+    #[cfg(not(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    let _ = (window, skip);
+}
+
+pub fn hide_to_tray(window: &Window) {
+    window.set_visible(false);
+    set_skip_taskbar(window, true);
+}
+
+pub fn restore_from_tray(window: &Window) {
+    set_skip_taskbar(window, false);
+    window.set_visible(true);
+    window.set_minimized(false);
+    window.set_focus();
+}
@@ -0,0 +1,483 @@
+//! Self-updates from a signed manifest, enabled by the `updater` option.
+//!
+//! `manifest_url` serves `{"manifest": "<JSON>", "signature": "<base64>"}`,
+//! where the signature is ed25519 over the manifest string's UTF-8 bytes,
+//! made with the key whose public half is `public_key`. The manifest lists
+//! one artifact per `<os>-<arch>` target (e.g. `linux-x86_64`) with its
+//! SHA-256, so the signature covers the downloads too:
+//!
+//! ```json
+//! {"version": "1.2.0", "notes": "...", "pub_date": "2025-01-31",
+//!  "platforms": {"windows-x86_64": {"url": "https://.../app.msi", "sha256": "..."}}}
+//! ```
+//!
+//! Nothing unverified is reported or installed: a bad signature or checksum
+//! is an [`InvalidSignature`] error, an unreachable server
+//! [`Offline`](crate::api_manager::Offline).
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    api_manager::{InvalidSignature, Unsupported},
+    http_fetch::http_error,
+    lock_force,
+    utils::{FrameEventLoopProxy, UserEvent},
+};
+
+/// Longest gap between two `update-progress` events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UpdaterConfig {
+    pub manifest_url: String,
+    /// Version of the running app, compared with the manifest's as semver.
+    pub current_version: String,
+    /// Base64 of the raw 32-byte ed25519 public key.
+    pub public_key: String,
+}
+
+#[derive(Deserialize)]
+struct SignedManifest {
+    manifest: String,
+    signature: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Manifest {
+    version: String,
+    notes: Option<String>,
+    pub_date: Option<String>,
+    platforms: HashMap<String, Artifact>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Artifact {
+    url: String,
+    /// Hex-encoded SHA-256 of the file.
+    sha256: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UpdateCheck {
+    pub available: bool,
+    pub current_version: String,
+    pub version: String,
+    pub notes: Option<String>,
+    pub pub_date: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Downloaded {
+    pub version: String,
+    pub path: String,
+}
+
+/// A step of the update, from Python or from the page.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdaterOp {
+    Check,
+    Download,
+    /// Installs the download and exits; see [`Installer`].
+    InstallAndExit,
+}
+
+#[derive(Default)]
+struct State {
+    /// The newer manifest the last check found.
+    available: Option<Manifest>,
+    downloading: bool,
+    /// Version, file and expected SHA-256 of the last download.
+    downloaded: Option<(String, PathBuf, String)>,
+}
+
+pub struct Updater {
+    config: UpdaterConfig,
+    current: semver::Version,
+    key: VerifyingKey,
+    client: reqwest::Client,
+    state: Mutex<State>,
+}
+
+impl Updater {
+    pub fn new(config: UpdaterConfig) -> Result<Self> {
+        let key: [u8; 32] = base64::engine::general_purpose::STANDARD
+            .decode(config.public_key.trim())?
+            .try_into()
+            .map_err(|_| anyhow!("updater public_key must be 32 bytes"))?;
+        Ok(Self {
+            current: parse_version(&config.current_version)?,
+            key: VerifyingKey::from_bytes(&key)?,
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()?,
+            state: Mutex::new(State::default()),
+            config,
+        })
+    }
+
+    /// Runs `op`. Progress of a download is sent as
+    /// [`UserEvent::UpdateProgress`]; a successful install exits the app.
+    pub async fn run(self: Arc<Self>, op: UpdaterOp, proxy: FrameEventLoopProxy) -> Result<Value> {
+        Ok(match op {
+            UpdaterOp::Check => serde_json::to_value(self.check().await?)?,
+            UpdaterOp::Download => serde_json::to_value(self.download(&proxy).await?)?,
+            UpdaterOp::InstallAndExit => {
+                let updater = Arc::clone(&self);
+                let handle = tokio::runtime::Handle::current();
+                crate::tasks::spawn_blocking_on("updater_install", &handle, move || {
+                    updater.install()
+                })
+                .await??;
+                proxy
+                    .send_event(UserEvent::Exit)
+                    .map_err(|_| anyhow!("Event loop has exited"))?;
+                Value::Bool(true)
+            }
+        })
+    }
+
+    async fn check(&self) -> Result<UpdateCheck> {
+        let url = &self.config.manifest_url;
+        let body = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| http_error(url, e))?
+            .error_for_status()?
+            .text()
+            .await
+            .map_err(|e| http_error(url, e))?;
+        let manifest = self.verify(&body)?;
+        let version = parse_version(&manifest.version)?;
+        let available = version > self.current;
+        let check = UpdateCheck {
+            available,
+            current_version: self.current.to_string(),
+            version: version.to_string(),
+            notes: manifest.notes.clone(),
+            pub_date: manifest.pub_date.clone(),
+        };
+        lock_force!(self.state).available = available.then_some(manifest);
+        Ok(check)
+    }
+
+    fn verify(&self, body: &str) -> Result<Manifest> {
+        let invalid = |what: &str| {
+            anyhow::Error::new(InvalidSignature {
+                what: format!("update manifest ({})", what),
+            })
+        };
+        let signed: SignedManifest =
+            serde_json::from_str(body).map_err(|_| invalid("not a signed manifest"))?;
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(signed.signature.trim())
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| invalid("malformed signature"))?;
+        self.key
+            .verify_strict(signed.manifest.as_bytes(), &signature)
+            .map_err(|_| invalid("signature mismatch"))?;
+        Ok(serde_json::from_str(&signed.manifest)?)
+    }
+
+    async fn download(&self, proxy: &FrameEventLoopProxy) -> Result<Downloaded> {
+        let manifest = {
+            let mut state = lock_force!(self.state);
+            if state.downloading {
+                bail!("An update is already being downloaded");
+            }
+            let manifest = state
+                .available
+                .clone()
+                .ok_or_else(|| anyhow!("No update available, call check first"))?;
+            state.downloading = true;
+            manifest
+        };
+        let result = self.fetch(&manifest, proxy).await;
+        let mut state = lock_force!(self.state);
+        state.downloading = false;
+        let (path, sha256) = result?;
+        let previous = state
+            .downloaded
+            .replace((manifest.version.clone(), path.clone(), sha256));
+        if let Some(dir) = previous.as_ref().and_then(|(_, path, _)| path.parent()) {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        Ok(Downloaded {
+            version: manifest.version,
+            path: path.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// Downloads the artifact into a new [`private_dir`] and checks it.
+    /// Returns the file and its expected SHA-256.
+    async fn fetch(
+        &self,
+        manifest: &Manifest,
+        proxy: &FrameEventLoopProxy,
+    ) -> Result<(PathBuf, String)> {
+        let target = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+        let artifact = manifest.platforms.get(&target).ok_or_else(|| Unsupported {
+            feature: format!("Updates for {}", target),
+        })?;
+        let name = artifact
+            .url
+            .split(['?', '#'])
+            .next()
+            .and_then(|url| url.rsplit('/').next())
+            .filter(|name| !name.is_empty() && *name != "..")
+            .ok_or_else(|| anyhow!("Artifact URL {:?} names no file", artifact.url))?;
+        let dir = private_dir(&parse_version(&manifest.version)?.to_string())?;
+        let path = dir.join(name);
+        let result = self.fetch_into(artifact, name, &path, proxy).await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+        }
+        result?;
+        Ok((path, artifact.sha256.trim().to_string()))
+    }
+
+    async fn fetch_into(
+        &self,
+        artifact: &Artifact,
+        name: &str,
+        path: &Path,
+        proxy: &FrameEventLoopProxy,
+    ) -> Result<()> {
+        let response = self
+            .client
+            .get(&artifact.url)
+            .send()
+            .await
+            .map_err(|e| http_error(&artifact.url, e))?
+            .error_for_status()?;
+        let total = response.content_length();
+        let mut stream = response.bytes_stream();
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .await?;
+        let mut hasher = Sha256::new();
+        let mut downloaded = 0;
+        let mut reported = Instant::now();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| http_error(&artifact.url, e))?;
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            if reported.elapsed() >= PROGRESS_INTERVAL {
+                reported = Instant::now();
+                let _ = proxy.send_event(UserEvent::UpdateProgress(downloaded, total));
+            }
+        }
+        file.flush().await?;
+        let _ = proxy.send_event(UserEvent::UpdateProgress(downloaded, total));
+
+        if !hex(&hasher.finalize()).eq_ignore_ascii_case(artifact.sha256.trim()) {
+            return Err(InvalidSignature {
+                what: format!("{} (SHA-256 mismatch)", name),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Starts the [`Installer`] of the downloaded artifact. The file is
+    /// checked against the manifest's SHA-256 once more right before.
+    fn install(&self) -> Result<()> {
+        let (_, path, sha256) = lock_force!(self.state)
+            .downloaded
+            .clone()
+            .ok_or_else(|| anyhow!("No update downloaded, call download first"))?;
+        let installer = Installer::of(&path)?;
+        if !sha256_of(&path)?.eq_ignore_ascii_case(&sha256) {
+            return Err(InvalidSignature {
+                what: format!("{} (changed since the download)", file_name(&path)),
+            }
+            .into());
+        }
+        let spawned = match installer {
+            Installer::AppImage => replace_appimage(&path),
+            Installer::Run(mut command) => command.spawn().map(drop),
+            Installer::Open => open::that(&path),
+        };
+        spawned.map_err(|e| anyhow!("Failed to start the installer for {:?}: {}", path, e))
+    }
+}
+
+/// How a downloaded artifact is installed, by its extension. Only an
+/// AppImage is started again; the others are handed to the platform's
+/// installer while the app exits, and whether the new version starts
+/// afterwards is up to that installer.
+#[derive(Debug)]
+enum Installer {
+    /// Swapped in for the running AppImage, which is then started.
+    AppImage,
+    /// `msiexec /i` for `.msi`, the file itself for `.exe`.
+    Run(std::process::Command),
+    /// Opened with the system's handler: Installer for `.pkg`, Finder for
+    /// `.dmg`, the software center for `.deb` and `.rpm`.
+    Open,
+}
+
+impl Installer {
+    fn of(path: &Path) -> Result<Self> {
+        let name = file_name(path);
+        Ok(if name.ends_with(".appimage") {
+            Self::AppImage
+        } else if name.ends_with(".msi") {
+            let mut command = std::process::Command::new("msiexec");
+            command.arg("/i").arg(path).arg("/passive");
+            Self::Run(command)
+        } else if name.ends_with(".exe") {
+            Self::Run(std::process::Command::new(path))
+        } else if [".pkg", ".dmg", ".deb", ".rpm"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+        {
+            Self::Open
+        } else {
+            return Err(Unsupported {
+                feature: format!("Installing {:?}", name),
+            }
+            .into());
+        })
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// A new directory for a download, which only the current user can
+/// enter: `0700` on Unix, and inside the per-user temp directory on
+/// Windows. Its name is random, so it can't be created ahead of us.
+fn private_dir(version: &str) -> std::io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "pyframe-update-{}-{}",
+        version,
+        uuid::Uuid::new_v4().simple()
+    ));
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+fn sha256_of(path: &Path) -> Result<String> {
+    use std::io::Read;
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Replaces `$APPIMAGE` (set by the AppImage runtime) with `update` and
+/// starts it. The old image keeps running until the app exits.
+fn replace_appimage(update: &Path) -> std::io::Result<()> {
+    let current = std::env::var_os("APPIMAGE")
+        .map(PathBuf::from)
+        .ok_or_else(|| std::io::Error::other("Not running from an AppImage"))?;
+    // Copied next to the target first: the temp dir may be on another
+    // filesystem, and the rename keeps the swap atomic.
+    let mut staged = current.as_os_str().to_owned();
+    staged.push(".update");
+    std::fs::copy(update, &staged)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    std::fs::rename(&staged, &current)?;
+    std::process::Command::new(&current).spawn().map(drop)
+}
+
+fn parse_version(version: &str) -> Result<semver::Version> {
+    let trimmed = version.trim().trim_start_matches('v');
+    semver::Version::parse(trimmed).map_err(|e| anyhow!("Invalid version {:?}: {}", version, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    fn installer(name: &str) -> Result<Installer> {
+        Installer::of(&Path::new("updates").join(name))
+    }
+
+    #[test]
+    fn installers_run_msi_and_exe() {
+        let Installer::Run(msi) = installer("App-1.2.0.MSI").unwrap() else {
+            panic!("an .msi is run");
+        };
+        assert_eq!(msi.get_program(), "msiexec");
+        let path = Path::new("updates").join("App-1.2.0.MSI");
+        let args: Vec<_> = msi.get_args().collect();
+        assert_eq!(
+            args,
+            [OsStr::new("/i"), path.as_os_str(), OsStr::new("/passive")]
+        );
+
+        let Installer::Run(exe) = installer("setup.exe").unwrap() else {
+            panic!("an .exe is run");
+        };
+        assert_eq!(exe.get_program(), Path::new("updates").join("setup.exe"));
+        assert_eq!(exe.get_args().count(), 0);
+    }
+
+    #[test]
+    fn packages_are_opened() {
+        for name in [
+            "App.pkg",
+            "App.dmg",
+            "app_1.2.0_amd64.deb",
+            "app-1.2.0.x86_64.rpm",
+        ] {
+            assert!(matches!(installer(name), Ok(Installer::Open)), "{}", name);
+        }
+        assert!(matches!(installer("App.AppImage"), Ok(Installer::AppImage)));
+    }
+
+    #[test]
+    fn other_artifacts_are_unsupported() {
+        let err = installer("app.tar.gz").unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some(), "{}", err);
+    }
+}
@@ -17,10 +17,130 @@ pub type FrameEventLoopProxy = EventLoopProxy<UserEvent>;
 #[allow(dead_code)]
 pub type FrameWindowTarget = EventLoopWindowTarget<UserEvent>;
 #[allow(dead_code)]
-pub type PendingMap = Arc<Mutex<HashMap<u8, tokio::sync::oneshot::Sender<ApiResponse>>>>;
+pub type PendingMap = Arc<Mutex<HashMap<u8, PendingResponse>>>;
+/// A request waiting for its response from the event loop.
+///
+/// The permit counts against the in-flight request bound and is released
+/// when the entry is removed from the [`PendingMap`].
+pub struct PendingResponse {
+    pub sender: tokio::sync::oneshot::Sender<ApiResponse>,
+    /// The connection that sent the request.
+    pub connection: crate::connections::ConnectionId,
+    // Only held for its Drop.
+    #[allow(dead_code)]
+    pub permit: tokio::sync::OwnedSemaphorePermit,
+}
 #[allow(dead_code)]
 pub enum UserEvent {
     Request(ApiRequest),
+    /// A tick of the configured tick interval, numbered from 1.
+    Tick(u64),
+    /// A timer started with `set_timeout`/`set_interval` fired.
+    Timer(crate::timers::TimerId),
+    /// A closure queued through [`MainThread`](crate::main_thread::MainThread).
+    Task(crate::main_thread::MainTask),
+    /// Run the normal exit path, e.g. after `confirm_close(true)`.
+    Exit,
+    /// Closing the window was handed to the app, which has the timeout in
+    /// ms to call `confirm_close`.
+    CloseRequested(u64),
+    /// The exit hooks are done; leave the event loop.
+    ExitHooksDone,
+    /// It's the turn of a `register_exit_hook` hook, by id and name.
+    ExitHook(crate::shutdown_hooks::HookId, String),
+    /// Leave the event loop without waiting for the exit hooks, e.g.
+    /// `FrameHandle.close(force=True)`; they run as the loop is destroyed.
+    Shutdown,
+    /// Open or close a window, see [`crate::window_commands`].
+    WindowCommand(Box<crate::window_commands::WindowCommand>),
+    /// The tray icon was left-clicked.
+    TrayClicked,
+    /// A tray or window menu item was selected.
+    Menu(tray_icon::menu::MenuId),
+    /// The page caught a menu bar accelerator for the item.
+    Accelerator(String),
+    /// `pyframe.invoke` from the page in ipc-only mode.
+    Invoke(Box<crate::transport::PageCall>),
+    /// An item of the `set_tray_menu` menu was chosen.
+    TrayMenu(Box<crate::menu::MenuClick>),
+    /// An item of the `set_dock_menu` menu was chosen.
+    DockMenu(Box<crate::menu::MenuClick>),
+    /// The dock icon was clicked; whether a window was visible.
+    DockReopen(bool),
+    /// The main page called `pyframe.invoke("__frame_ready")`.
+    FrameReady,
+    /// The main WebView started or finished loading a page.
+    PageLoad(wry::PageLoadEvent, String),
+    /// Mousedown in a `data-pyframe-drag-region`.
+    DragWindow,
+    /// Double-click in a `data-pyframe-drag-region`.
+    ToggleMaximize,
+    /// An element of the main page entered or left fullscreen.
+    ElementFullscreen(crate::element_fullscreen::ElementFullscreen),
+    /// The main page's `document.title` changed.
+    TitleChanged(String),
+    /// A step of the main page's navigation lifecycle.
+    Navigation(Box<crate::navigation::NavigationEvent>),
+    /// The main WebView failed to load a page.
+    LoadFailed(Box<crate::load_error::LoadFailure>),
+    /// A `data-pyframe-retry` element of the error page was clicked.
+    RetryLoad,
+    /// Right-click in a `data-pyframe-drag-region` under `titlebar_overlay`.
+    SystemMenu,
+    /// The caption buttons moved under `titlebar_overlay`.
+    TitlebarOverlay(crate::titlebar_overlay::Layout),
+    /// A right-click under `context_menu: "custom"`.
+    ContextMenu(Box<crate::context_menu::ContextMenuRequest>),
+    /// The page called `pyframe.invoke("__shell_open", target)`; answered
+    /// under the id.
+    ShellOpen(u64, String),
+    /// The page called `pyframe.startDrag`; answered under the id.
+    StartDrag(u64, crate::drag_out::DragItems),
+    /// A `window.appStore` call from the page.
+    StateStore(crate::state_store::StoreCall),
+    /// A document of the [`StateStore`](crate::state_store::StateStore) changed.
+    StateChanged(crate::state_store::StateChange),
+    /// A key of the persistent store changed; `None` after `store_clear`.
+    StoreChanged(Option<String>),
+    /// The kiosk exit accelerator was pressed.
+    ExitKiosk,
+    /// Debounced changes seen by an `fs_watch` watcher.
+    FsChanged(crate::fs_watch::WatcherId, Vec<crate::fs_watch::FsChange>),
+    /// A line a `process_spawn` child wrote.
+    ProcessOutput(
+        crate::processes::Pid,
+        crate::processes::OutputStream,
+        String,
+    ),
+    /// A `process_spawn` child exited; `None` if it was ended by a signal.
+    ProcessExited(crate::processes::Pid, Option<i32>),
+    /// `websocket.js` in the window asked for the server and the launch
+    /// token; answered under the id.
+    Bridge(tao::window::WindowId, u64),
+    /// A `pyframe.updater` call from the page; answered under the id.
+    Updater(u64, crate::updater::UpdaterOp),
+    /// Bytes of the update downloaded so far, and the total if known.
+    UpdateProgress(u64, Option<u64>),
+    /// A `pyframe.http.fetch` call from the page; answered under the id.
+    HttpFetch(u64, Box<crate::http_fetch::FetchRequest>),
+    /// Part of a streamed `http_fetch` body.
+    HttpBody(crate::http_fetch::StreamId, crate::http_fetch::BodyPart),
+    /// A `pyframe.ws` call from the page; answered under the id.
+    WsCall(u64, crate::ws_client::WsOp),
+    /// Something happened on a `ws_connect` WebSocket.
+    Ws(crate::ws_client::WsId, crate::ws_client::WsEvent),
+    /// Something panicked; see [`crate::crash`].
+    Crashed(Box<crate::crash::CrashReport>),
+    /// A TCP client went away; resources it owned are released.
+    Disconnected(crate::connections::ConnectionId),
+    /// An `emit` frame from Python, for the pages.
+    Emit(Box<crate::emit::Emit>),
+    /// The WebView asked for a permission, see [`crate::permissions`].
+    PermissionRequested(Box<crate::permissions::PermissionRequest>),
+    /// Progress of a `find_in_page` search.
+    FindResult(Box<crate::find::FindResult>),
+    /// A TCP client connected without a hello; see [`crate::protocol`].
+    LegacyProtocol(crate::connections::ConnectionId),
 }
 #[allow(dead_code)]
 pub type ArcMut<T> = Arc<Mutex<T>>;
@@ -87,17 +207,43 @@ macro_rules! set_property {
 
 #[macro_export]
 macro_rules! lock {
+    ($value:expr) => {
+        // A caught handler panic may have poisoned the lock; the state is
+        // still used, as it was before panics were caught.
+        Ok::<_, anyhow::Error>(
+            $value
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! lock_force {
     ($value:expr) => {
         $value
             .lock()
-            .map_err(|_| anyhow::anyhow!("Failed to lock {}.", stringify!($value)))
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     };
 }
 
+/// [`lock_force!`] for reading an `RwLock`.
 #[macro_export]
-macro_rules! lock_force {
+macro_rules! read_force {
     ($value:expr) => {
-        $value.lock().unwrap()
+        $value
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    };
+}
+
+/// [`lock_force!`] for writing an `RwLock`.
+#[macro_export]
+macro_rules! write_force {
+    ($value:expr) => {
+        $value
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     };
 }
 
@@ -123,7 +269,7 @@ macro_rules! logical_try {
 macro_rules! log_if_err {
     ($result:expr) => {
         if let Err(e) = $result {
-            println!("[Error]: {}", e);
+            ::tracing::error!("{}", e);
         }
     };
 }
@@ -131,14 +277,21 @@ macro_rules! log_if_err {
 #[macro_export]
 macro_rules! log {
     ($result:expr) => {
-        println!("[Info]: {}", $result);
+        ::tracing::info!("{}", $result);
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($result:expr) => {
+        ::tracing::warn!("{}", $result);
     };
 }
 
 #[macro_export]
 macro_rules! log_err {
     ($result:expr) => {
-        println!("[Error]: {}", $result);
+        ::tracing::error!("{}", $result);
     };
 }
 #[allow(dead_code)]
@@ -172,7 +325,7 @@ macro_rules! try_or_log_err {
         match (move || -> anyhow::Result<()> { $body })() {
             Ok(_) => {}
             Err(e) => {
-                crate::log_err!(e);
+                $crate::log_err!(e);
             }
         }
     };
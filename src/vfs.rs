@@ -0,0 +1,188 @@
+//! In-memory files served to the WebView through a custom URI scheme, set
+//! up by the `assets` option.
+//!
+//! Responses carry an `ETag` of the content, so reloads are answered with
+//! `304 Not Modified`, and honour single `Range` requests for seeking in
+//! media files.
+
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use wry::{
+    http::{header, Request, Response, StatusCode},
+    WebViewBuilder,
+};
+
+/// Served for requests to a directory.
+const INDEX_FILE: &str = "index.html";
+
+struct VirtualFile {
+    bytes: Vec<u8>,
+    mime: String,
+    etag: String,
+}
+
+#[derive(Default)]
+pub struct VirtualFileSystem {
+    files: HashMap<String, VirtualFile>,
+}
+
+impl VirtualFileSystem {
+    /// Adds `bytes` at `path`, typed by the path's extension.
+    pub fn add_file(&mut self, path: &str, bytes: Vec<u8>) {
+        let mime = mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string();
+        self.add_bytes(path, bytes, &mime);
+    }
+
+    pub fn add_bytes(&mut self, path: &str, bytes: Vec<u8>, mime: &str) {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        self.files.insert(
+            normalize(path),
+            VirtualFile {
+                bytes,
+                mime: mime.to_string(),
+                etag: format!("\"{:016x}\"", hasher.finish()),
+            },
+        );
+    }
+
+    /// Loads every file below `base_dir`, keyed by its path relative to it.
+    pub fn add_directory_tree(&mut self, base_dir: &Path) -> Result<usize> {
+        let mut count = 0;
+        let mut dirs = vec![base_dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let entries =
+                fs::read_dir(&dir).map_err(|e| anyhow!("Cannot read {:?}: {}", dir, e))?;
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let relative = path
+                    .strip_prefix(base_dir)?
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                self.add_file(&relative, fs::read(&path)?);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Serves the files as `<scheme>://localhost/<path>` (or
+    /// `http://<scheme>.localhost/<path>` on Windows and Android).
+    pub fn mount<'a>(self, scheme: &str, builder: WebViewBuilder<'a>) -> WebViewBuilder<'a> {
+        builder.with_custom_protocol(scheme.to_string(), move |_, request| self.respond(&request))
+    }
+
+    pub fn respond(&self, request: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+        let mut path = normalize(request.uri().path());
+        if path.is_empty() || path.ends_with('/') {
+            path.push_str(INDEX_FILE);
+        }
+        let Some(file) = self.files.get(&path) else {
+            return status(StatusCode::NOT_FOUND);
+        };
+
+        let builder = Response::builder()
+            .header(header::CONTENT_TYPE, &file.mime)
+            .header(header::ETAG, &file.etag)
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+        let headers = request.headers();
+        let not_modified = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|tags| {
+                tags.split(',')
+                    .any(|tag| tag.trim() == "*" || tag.trim() == file.etag)
+            });
+        if not_modified {
+            return builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Cow::Borrowed(&[][..]))
+                .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+
+        let len = file.bytes.len();
+        let range = headers
+            .get(header::RANGE)
+            .and_then(|v| v.to_str().ok())
+            .map(|range| parse_range(range, len));
+        let response = match range {
+            None => builder
+                .status(StatusCode::OK)
+                .body(Cow::Owned(file.bytes.clone())),
+            Some(Some((start, end))) => builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end, len),
+                )
+                .body(Cow::Owned(file.bytes[start..=end].to_vec())),
+            Some(None) => builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Cow::Borrowed(&[][..])),
+        };
+        response.unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let path = path.trim_start_matches('/');
+    percent_decode(path).unwrap_or_else(|| path.to_string())
+}
+
+pub fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = path.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// The inclusive byte range of a single `bytes=` range, or `None` if it
+/// can't be satisfied. Multiple ranges aren't supported.
+fn parse_range(range: &str, len: usize) -> Option<(usize, usize)> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') || len == 0 {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            (len.checked_sub(suffix.min(len))?, len - 1)
+        }
+        (start, "") => (start.parse().ok()?, len - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<usize>().ok()?.min(len - 1)),
+    };
+    (start <= end && start < len).then_some((start, end))
+}
+
+fn status(status: StatusCode) -> Response<Cow<'static, [u8]>> {
+    let mut response = Response::new(Cow::Borrowed(&[][..]));
+    *response.status_mut() = status;
+    response
+}
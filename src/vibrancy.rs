@@ -0,0 +1,224 @@
+//! Frosted-glass window backgrounds through the `window-vibrancy` crate.
+//!
+//! The effect only shows through where the page and the WebView are
+//! transparent, so the window must be created with `transparent`.
+//! [`set_vibrancy`] picks a material by thickness on any supported
+//! platform; [`set_effect`] selects a specific native effect.
+
+use anyhow::{anyhow, Result};
+use tao::window::Window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Material {
+    UltraThin,
+    Thin,
+    Medium,
+    Thick,
+    None,
+}
+
+impl Material {
+    pub fn parse(material: &str) -> Result<Self> {
+        Ok(match material {
+            "ultra_thin" => Self::UltraThin,
+            "thin" => Self::Thin,
+            "medium" => Self::Medium,
+            "thick" => Self::Thick,
+            "none" => Self::None,
+            _ => return Err(anyhow!("Unknown vibrancy material {:?}", material)),
+        })
+    }
+}
+
+/// Applies `material` behind the window, replacing any previous effect.
+///
+/// | Platform | Mechanism |
+/// |----------|-----------|
+/// | macOS    | `NSVisualEffectView`, from `HudWindow` (ultra thin) to `WindowBackground` (thick) |
+/// | Windows 11 | Acrylic with a dark tint that gets more opaque the thicker the material |
+/// | Windows 10 and older, Linux, iOS, Android | unsupported, returns `false` |
+pub fn set_vibrancy(window: &Window, material: Material) -> Result<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        use window_vibrancy::NSVisualEffectMaterial;
+
+        window_vibrancy::clear_vibrancy(window)?;
+        let effect = match material {
+            Material::UltraThin => NSVisualEffectMaterial::HudWindow,
+            Material::Thin => NSVisualEffectMaterial::Popover,
+            Material::Medium => NSVisualEffectMaterial::Sidebar,
+            Material::Thick => NSVisualEffectMaterial::WindowBackground,
+            Material::None => return Ok(true),
+        };
+        window_vibrancy::apply_vibrancy(window, effect, None, None)?;
+        Ok(true)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use window_vibrancy::Error;
+
+        let alpha = match material {
+            Material::UltraThin => 0x10,
+            Material::Thin => 0x40,
+            Material::Medium => 0x80,
+            Material::Thick => 0xc0,
+            Material::None => {
+                return match window_vibrancy::clear_acrylic(window) {
+                    Ok(()) => Ok(true),
+                    Err(Error::UnsupportedPlatformVersion(_)) => Ok(false),
+                    Err(e) => Err(e.into()),
+                };
+            }
+        };
+        match window_vibrancy::apply_acrylic(window, Some((18, 18, 18, alpha))) {
+            Ok(()) => Ok(true),
+            Err(Error::UnsupportedPlatformVersion(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let _ = (window, material);
+        Ok(false)
+    }
+}
+
+/// `NSVisualEffectView` materials accepted by [`Effect::parse`] on macOS.
+const MATERIALS: &[&str] = &[
+    "titlebar",
+    "selection",
+    "menu",
+    "popover",
+    "sidebar",
+    "header_view",
+    "sheet",
+    "window_background",
+    "hud_window",
+    "full_screen_ui",
+    "tooltip",
+    "content_background",
+    "under_window_background",
+    "under_page_background",
+];
+
+/// A native background effect for `set_effect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    None,
+    /// Windows 11.
+    Mica,
+    /// Windows 10 and 11.
+    Acrylic,
+    /// Windows 7 to 10.
+    Blur,
+    /// One of [`MATERIALS`], macOS only.
+    Material(&'static str),
+}
+
+impl Effect {
+    pub fn parse(kind: &str) -> Result<Self> {
+        Ok(match kind {
+            "none" => Self::None,
+            "mica" => Self::Mica,
+            "acrylic" => Self::Acrylic,
+            "blur" => Self::Blur,
+            _ => match MATERIALS.iter().find(|m| **m == kind) {
+                Some(material) => Self::Material(material),
+                None => return Err(anyhow!("Unknown effect {:?}", kind)),
+            },
+        })
+    }
+}
+
+/// Replaces the window's background effect. [`Effect::None`] removes it and
+/// gives the WebView an opaque white background again; any other effect
+/// clears the WebView's background so the effect shows through.
+///
+/// Kinds the platform or OS version can't render fail with
+/// [`Unsupported`](crate::api_manager::Unsupported).
+pub fn set_effect(window: &Window, webview: &wry::WebView, effect: Effect) -> Result<()> {
+    clear_effects(window);
+    if effect != Effect::None {
+        apply_effect(window, effect)?;
+    }
+    let background = match effect {
+        Effect::None => (255, 255, 255, 255),
+        _ => (0, 0, 0, 0),
+    };
+    webview.set_background_color(background)?;
+    Ok(())
+}
+
+fn clear_effects(window: &Window) {
+    #[cfg(target_os = "macos")]
+    let _ = window_vibrancy::clear_vibrancy(window);
+    #[cfg(target_os = "windows")]
+    {
+        let _ = window_vibrancy::clear_mica(window);
+        let _ = window_vibrancy::clear_acrylic(window);
+        let _ = window_vibrancy::clear_blur(window);
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let _ = window;
+}
+
+fn unsupported(effect: Effect) -> anyhow::Error {
+    let kind = match effect {
+        Effect::None => "none",
+        Effect::Mica => "mica",
+        Effect::Acrylic => "acrylic",
+        Effect::Blur => "blur",
+        Effect::Material(material) => material,
+    };
+    crate::api_manager::Unsupported {
+        feature: format!("effect {:?}", kind),
+    }
+    .into()
+}
+
+#[cfg(target_os = "windows")]
+fn apply_effect(window: &Window, effect: Effect) -> Result<()> {
+    let result = match effect {
+        Effect::Mica => window_vibrancy::apply_mica(window, None),
+        Effect::Acrylic => window_vibrancy::apply_acrylic(window, None),
+        Effect::Blur => window_vibrancy::apply_blur(window, None),
+        Effect::None | Effect::Material(_) => return Err(unsupported(effect)),
+    };
+    match result {
+        Err(window_vibrancy::Error::UnsupportedPlatformVersion(_)) => Err(unsupported(effect)),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn apply_effect(window: &Window, effect: Effect) -> Result<()> {
+    use window_vibrancy::NSVisualEffectMaterial as M;
+
+    let Effect::Material(material) = effect else {
+        return Err(unsupported(effect));
+    };
+    let material = match material {
+        "titlebar" => M::Titlebar,
+        "selection" => M::Selection,
+        "menu" => M::Menu,
+        "popover" => M::Popover,
+        "sidebar" => M::Sidebar,
+        "header_view" => M::HeaderView,
+        "sheet" => M::Sheet,
+        "window_background" => M::WindowBackground,
+        "hud_window" => M::HudWindow,
+        "full_screen_ui" => M::FullScreenUI,
+        "tooltip" => M::Tooltip,
+        "content_background" => M::ContentBackground,
+        "under_window_background" => M::UnderWindowBackground,
+        "under_page_background" => M::UnderPageBackground,
+        _ => return Err(unsupported(effect)),
+    };
+    window_vibrancy::apply_vibrancy(window, material, None, None)?;
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn apply_effect(_: &Window, effect: Effect) -> Result<()> {
+    Err(unsupported(effect))
+}
@@ -0,0 +1,292 @@
+//! The webframe as a Rust API, without Python.
+//!
+//! ```no_run
+//! pygcc::WebFrame::builder()
+//!     .html("<h1>Hello</h1>")
+//!     .port(8000)
+//!     .on_event(|name, data| println!("{}: {}", name, data))
+//!     .run()?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! `create_webframe` in Python is a thin wrapper around [`WebFrameBuilder`],
+//! with the `multiprocessing.Event` it's given as the [`ShutdownNotifier`];
+//! `launch_webframe` around [`WebFrameBuilder::spawn`].
+
+use std::{
+    sync::{mpsc, Arc, Weak},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{config::AppConfig, core::EventHandler};
+
+/// Told when the webframe is about to close, by the user, `close`, the
+/// tray's quit item or a fatal panic on the event loop thread.
+///
+/// Called on the event loop thread, or in the panic hook of the thread that
+/// panicked, before [`WebFrameBuilder::run`] returns.
+pub trait ShutdownNotifier: Send + Sync {
+    fn notify(&self);
+}
+
+impl<T: ShutdownNotifier + ?Sized> ShutdownNotifier for Arc<T> {
+    fn notify(&self) {
+        (**self).notify()
+    }
+}
+
+/// For a webframe nobody waits on.
+struct NoNotifier;
+
+impl ShutdownNotifier for NoNotifier {
+    fn notify(&self) {}
+}
+
+/// Where the running webframe can be reached, as passed to
+/// [`WebFrameBuilder::on_ready`] and answered to the `__bridge:` request of
+/// the app's own pages.
+#[derive(Serialize, Clone, Debug)]
+pub struct FrameInfo {
+    /// `"tcp"` or `"ipc-only"`.
+    pub transport: &'static str,
+    /// The WebSocket server the page connects to.
+    pub socket_url: Option<String>,
+    pub ws_port: Option<u16>,
+    /// The TCP bridge, bound before the window opens; the actual port when
+    /// `RUSTADDR` is 0.
+    pub tcp_port: Option<u16>,
+}
+
+impl FrameInfo {
+    pub(crate) fn of(config: &AppConfig) -> Self {
+        Self {
+            transport: config.transport.name(),
+            socket_url: config.socket_url.clone(),
+            ws_port: config.ws_port,
+            tcp_port: config.tcp_port,
+        }
+    }
+}
+
+type ReadyHandler = Box<dyn FnOnce(&FrameInfo) + Send>;
+type RenderHandler = Box<dyn FnOnce(crate::RenderTarget) + Send>;
+
+pub struct WebFrame;
+
+impl WebFrame {
+    pub fn builder() -> WebFrameBuilder {
+        WebFrameBuilder::default()
+    }
+}
+
+pub struct WebFrameBuilder {
+    html: String,
+    host: String,
+    port: u16,
+    options: Option<String>,
+    event_handlers: Vec<EventHandler>,
+    notifier: Arc<dyn ShutdownNotifier>,
+    ready: Option<ReadyHandler>,
+    render: Option<RenderHandler>,
+}
+
+impl Default for WebFrameBuilder {
+    fn default() -> Self {
+        Self {
+            html: String::new(),
+            host: "127.0.0.1".into(),
+            port: 0,
+            options: None,
+            event_handlers: Vec::new(),
+            notifier: Arc::new(NoNotifier),
+            ready: None,
+            render: None,
+        }
+    }
+}
+
+impl WebFrameBuilder {
+    /// The page, unless the `url` or `local_server` option is set.
+    pub fn html(mut self, html: impl Into<String>) -> Self {
+        self.html = html.into();
+        self
+    }
+
+    /// Host of the WebSocket server the page connects to; `127.0.0.1` by
+    /// default. Unused with `transport: "ipc-only"`, like [`Self::port`].
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Port of the WebSocket server the page connects to, as bound; the
+    /// server runs outside the webframe. The TCP bridge listens on
+    /// `RUSTADDR`, where 0 picks a free port, see [`Self::on_ready`].
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// The creation options, an object with the fields of `AppConfig` as in
+    /// Python's `native_runtime`.
+    pub fn options(self, options: Value) -> Self {
+        self.options_json(options.to_string())
+    }
+
+    /// [`Self::options`] as JSON text.
+    pub fn options_json(mut self, options: impl Into<String>) -> Self {
+        self.options = Some(options.into());
+        self
+    }
+
+    /// Called on the event loop thread with the name and data of every
+    /// event, as `pyframe.events()` subscribers receive them.
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str, &Value) + Send + 'static,
+    {
+        self.event_handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Called once the window is open and the TCP bridge listens, before
+    /// the event loop runs, with the ports it got.
+    pub fn on_ready<F>(mut self, handler: F) -> Self
+    where
+        F: FnOnce(&FrameInfo) + Send + 'static,
+    {
+        self.ready = Some(Box::new(handler));
+        self
+    }
+
+    /// Called on the event loop thread once the window is open, with its
+    /// native handle for a renderer on another thread; see
+    /// [`RenderTarget`](crate::RenderTarget).
+    pub fn on_render_target<F>(mut self, handler: F) -> Self
+    where
+        F: FnOnce(crate::RenderTarget) + Send + 'static,
+    {
+        self.render = Some(Box::new(handler));
+        self
+    }
+
+    pub fn on_shutdown(mut self, notifier: impl ShutdownNotifier + 'static) -> Self {
+        self.notifier = Arc::new(notifier);
+        self
+    }
+
+    /// Opens the window and runs the event loop on this thread until it's
+    /// closed. A later call opens a new webframe on the same event loop, from
+    /// the same thread.
+    pub fn run(self) -> Result<()> {
+        let mut config = AppConfig::from_json(self.options.as_deref())?;
+        crate::diagnostics::init(&config)?;
+
+        config.transport.check()?;
+
+        // Bound first, so the page's script has the port.
+        let listener = bind(&config)?;
+        if let Some(listener) = &listener {
+            config.tcp_port = Some(listener.local_addr()?.port());
+            config.ws_port = Some(self.port);
+            config.socket_url = Some(format!("ws://{}:{}/ws", self.host, self.port));
+        }
+        let info = FrameInfo::of(&config);
+
+        // Where the server is, and the token, only go to the app's own
+        // pages, through `AppContext::bridge_for_page`.
+        let init_script = format!(
+            "window.__PYFRAME__ = {}; window.__pyframe_transport = {};",
+            serde_json::json!({ "transport": info.transport }),
+            serde_json::to_string(info.transport)?
+        );
+
+        let mut event_loop = crate::frame_loop::take()?;
+        let ready = self.ready;
+        let render = self.render;
+        let result =
+            crate::core::App::new(&mut event_loop, init_script, self.html, config, listener)
+                .and_then(|app| {
+                    for handler in self.event_handlers {
+                        app.on_event(handler);
+                    }
+                    if let Some(ready) = ready {
+                        ready(&info);
+                    }
+                    if let Some(render) = render {
+                        render(app.render_target()?);
+                    }
+                    app.run(&mut event_loop, self.notifier)
+                });
+        crate::frame_loop::put_back(event_loop);
+        result
+    }
+
+    /// [`Self::run`] on the event loop's own thread, returning right away.
+    /// Only on Windows and Linux; macOS needs the loop on the main thread.
+    /// Webframes spawned while one is open run once it's closed. Everything
+    /// else reaches the running webframe through its proxy, as `FrameHandle`
+    /// does, and the handlers are called on the event loop thread.
+    pub fn spawn(self) -> Result<RunningFrame> {
+        let (done, wait) = mpsc::channel();
+        // Dropped with the job, also when it panics.
+        let open = Arc::new(());
+        let closed = Arc::downgrade(&open);
+        crate::frame_loop::spawn(Box::new(move || {
+            let _open = open;
+            let _ = done.send(self.run());
+        }))?;
+        Ok(RunningFrame { done: wait, closed })
+    }
+}
+
+/// A webframe started with [`WebFrameBuilder::spawn`].
+pub struct RunningFrame {
+    done: mpsc::Receiver<Result<()>>,
+    closed: Weak<()>,
+}
+
+impl RunningFrame {
+    /// Whether the webframe has closed, or failed to open.
+    pub fn is_closed(&self) -> bool {
+        self.closed.strong_count() == 0
+    }
+
+    /// Waits until the webframe is closed and returns what
+    /// [`WebFrameBuilder::run`] returned. Only the first wait that returns
+    /// gets it.
+    pub fn wait(&self) -> Result<()> {
+        self.done.recv().unwrap_or_else(|_| Err(lost()))
+    }
+
+    /// [`Self::wait`] for at most `timeout`; `None` while it's still open.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<Result<()>> {
+        match self.done.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Some(Err(lost())),
+        }
+    }
+}
+
+fn lost() -> anyhow::Error {
+    anyhow!("The webframe's result was taken already, or the event loop thread panicked")
+}
+
+/// The TCP bridge's listener, with `transport: "tcp"`.
+#[cfg(feature = "tcp")]
+fn bind(config: &AppConfig) -> Result<Option<std::net::TcpListener>> {
+    match config.transport {
+        crate::transport::Transport::Tcp => crate::connections::bind().map(Some),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "tcp"))]
+fn bind(_config: &AppConfig) -> Result<Option<std::net::TcpListener>> {
+    Ok(None)
+}
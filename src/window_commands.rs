@@ -0,0 +1,51 @@
+//! Opening and closing windows besides the first one.
+//!
+//! `open_window` and `close_window` queue a [`WindowCommand`] for the event
+//! loop, which owns the windows. An opened window gets a WebView with the
+//! bridge scripts of the first one when it shows `html`; a page loaded from
+//! `url` gets none, so it can't reach the native APIs. Closing the first
+//! window exits the app as the close button does.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::placement::{MonitorInfo, MonitorSelector, PositionStrategy};
+
+pub type Reply<T> = Box<dyn FnOnce(Result<T>) + Send>;
+
+pub enum WindowCommand {
+    Open(WindowOptions, Reply<OpenedWindow>),
+    /// Answered with `false` if there is no such window.
+    Close(u64, Reply<bool>),
+}
+
+/// The options of `open_window`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct WindowOptions {
+    /// Names the window in its events' `label`; `window-<id>` if unset.
+    pub label: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub html: Option<String>,
+    /// Inner size in logical pixels; both or neither.
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// As the app's `monitor` option; the primary monitor if it isn't
+    /// connected.
+    pub monitor: Option<MonitorSelector>,
+    /// As the app's `position_strategy` option. `remember` centers, as only
+    /// the first window's geometry is saved.
+    pub position_strategy: Option<PositionStrategy>,
+}
+
+/// What `open_window` answers with.
+#[derive(Serialize, Debug)]
+pub struct OpenedWindow {
+    /// The `id` of `get_window_state`, `window_id` metadata and `close_window`.
+    pub id: u64,
+    pub label: String,
+    /// The monitor the window landed on, which is the primary one if the
+    /// requested monitor isn't connected.
+    pub monitor: Option<MonitorInfo>,
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tao::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    window::Window,
+};
+
+/// Label of the first window, as used by the Python `Window` wrapper.
+pub const WINDOW_LABEL: &str = "root";
+/// A window's state, as returned by `get_window_state`.
+#[derive(Serialize, Debug)]
+pub struct WindowStatus {
+    pub label: String,
+    pub id: u64,
+    pub focused: bool,
+    pub visible: bool,
+    pub minimized: bool,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub occluded: bool,
+    /// Name of the monitor the window is on.
+    pub monitor: Option<String>,
+}
+
+impl WindowStatus {
+    pub fn of(window: &Window, label: &str) -> Self {
+        let minimized = window.is_minimized();
+        Self {
+            label: label.to_string(),
+            id: crate::context::AppContext::window_number(window.id()),
+            focused: window.is_focused(),
+            visible: window.is_visible(),
+            minimized,
+            maximized: window.is_maximized(),
+            fullscreen: window.fullscreen().is_some(),
+            occluded: minimized,
+            monitor: window.current_monitor().and_then(|m| m.name()),
+        }
+    }
+}
+
+/// Resize and move events are forwarded at most this often (~60 Hz).
+const EMIT_INTERVAL: Duration = Duration::from_millis(16);
+/// tao doesn't report the end of an interactive resize, so it is assumed
+/// once no resize arrived for this long.
+const RESIZE_END_DELAY: Duration = Duration::from_millis(200);
+/// Focus and occlusion changes are forwarded once they settled for this long,
+/// so alt-tabbing through windows doesn't flood the frontend.
+const SETTLE_DELAY: Duration = Duration::from_millis(50);
+
+/// Forwards the first event at once, then at most one per [`EMIT_INTERVAL`],
+/// always keeping the latest payload.
+#[derive(Default)]
+struct Throttle {
+    last_emit: Option<Instant>,
+    pending: Option<Value>,
+}
+
+impl Throttle {
+    fn push(&mut self, now: Instant, payload: Value) -> Option<Value> {
+        match self.last_emit {
+            Some(last) if now < last + EMIT_INTERVAL => {
+                self.pending = Some(payload);
+                None
+            }
+            _ => {
+                self.last_emit = Some(now);
+                self.pending = None;
+                Some(payload)
+            }
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref()?;
+        self.last_emit.map(|last| last + EMIT_INTERVAL)
+    }
+
+    fn flush(&mut self, now: Instant) -> Option<Value> {
+        if self.deadline()? > now {
+            return None;
+        }
+        self.last_emit = Some(now);
+        self.pending.take()
+    }
+}
+
+/// Forwards a boolean state once it stopped changing, and only if it differs
+/// from what was forwarded last.
+#[derive(Default)]
+struct Settle {
+    emitted: Option<bool>,
+    latest: Option<(bool, Instant)>,
+}
+
+impl Settle {
+    fn set(&mut self, now: Instant, value: bool) {
+        self.latest = Some((value, now + SETTLE_DELAY));
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.latest.map(|(_, deadline)| deadline)
+    }
+
+    fn flush(&mut self, now: Instant) -> Option<bool> {
+        let (value, deadline) = self.latest?;
+        if deadline > now {
+            return None;
+        }
+        self.latest = None;
+        if self.emitted == Some(value) {
+            return None;
+        }
+        self.emitted = Some(value);
+        Some(value)
+    }
+}
+
+/// Prepares window events for the frontend: throttles `Resized`/`Moved`,
+/// detects the end of a resize and coalesces focus and occlusion changes.
+///
+/// tao has no occlusion event, so a minimized window counts as occluded.
+#[derive(Default)]
+pub struct WindowEvents {
+    label: String,
+    resize: Throttle,
+    moved: Throttle,
+    last_size: Option<Value>,
+    resize_end: Option<Instant>,
+    focus: Settle,
+    occluded: Settle,
+}
+
+impl WindowEvents {
+    pub fn new(label: String) -> Self {
+        Self {
+            label,
+            ..Self::default()
+        }
+    }
+
+    pub fn resized(
+        &mut self,
+        now: Instant,
+        size: PhysicalSize<u32>,
+        scale_factor: f64,
+    ) -> Option<Value> {
+        let logical = size.to_logical::<f64>(scale_factor);
+        let payload = json!({
+            "label": self.label,
+            "physical": { "width": size.width, "height": size.height },
+            "logical": { "width": logical.width, "height": logical.height },
+            "scale_factor": scale_factor,
+        });
+        self.last_size = Some(payload.clone());
+        self.resize_end = Some(now + RESIZE_END_DELAY);
+        self.resize.push(now, payload)
+    }
+
+    pub fn moved(
+        &mut self,
+        now: Instant,
+        position: PhysicalPosition<i32>,
+        scale_factor: f64,
+    ) -> Option<Value> {
+        let logical = position.to_logical::<f64>(scale_factor);
+        let payload = json!({
+            "label": self.label,
+            "physical": { "x": position.x, "y": position.y },
+            "logical": { "x": logical.x, "y": logical.y },
+            "scale_factor": scale_factor,
+        });
+        self.moved.push(now, payload)
+    }
+
+    pub fn focused(&mut self, now: Instant, focused: bool) {
+        self.focus.set(now, focused);
+    }
+
+    pub fn occluded(&mut self, now: Instant, occluded: bool) {
+        self.occluded.set(now, occluded);
+    }
+
+    /// Returns the events that became due, in the order they should be emitted.
+    pub fn poll(&mut self, now: Instant) -> Vec<(&'static str, Value)> {
+        let mut due = Vec::new();
+        if let Some(focused) = self.focus.flush(now) {
+            let event = if focused { "focus" } else { "blur" };
+            due.push((event, json!({ "label": self.label })));
+        }
+        if let Some(occluded) = self.occluded.flush(now) {
+            due.push((
+                "occlusion",
+                json!({ "label": self.label, "occluded": occluded }),
+            ));
+        }
+        if let Some(payload) = self.resize.flush(now) {
+            due.push(("resized", payload));
+        }
+        if let Some(payload) = self.moved.flush(now) {
+            due.push(("moved", payload));
+        }
+        if self.resize_end.is_some_and(|end| end <= now) {
+            self.resize_end = None;
+            if let Some(payload) = self.last_size.take() {
+                due.push(("resize-ended", payload));
+            }
+        }
+        due
+    }
+
+    /// When the next throttled event becomes due, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        [
+            self.resize.deadline(),
+            self.moved.deadline(),
+            self.resize_end,
+            self.focus.deadline(),
+            self.occluded.deadline(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+}
+
+/// The [`WindowEvents`] of every open window, by the number of
+/// [`AppContext::window_number`](crate::context::AppContext::window_number),
+/// which also name the window's label.
+#[derive(Default)]
+pub struct WindowRouter {
+    windows: HashMap<u64, WindowEvents>,
+}
+
+impl WindowRouter {
+    /// With the first window, labelled [`WINDOW_LABEL`].
+    pub fn new(first: u64) -> Self {
+        Self {
+            windows: HashMap::from([(first, WindowEvents::new(WINDOW_LABEL.to_string()))]),
+        }
+    }
+
+    /// Fails if an open window has `label`; checked before opening one.
+    pub fn check_label(&self, label: &str) -> Result<()> {
+        if self.windows.values().any(|events| events.label == label) {
+            bail!("A window labelled {:?} is already open", label);
+        }
+        Ok(())
+    }
+
+    /// Adds a window of `open_window`, labelled `label` or `window-<number>`,
+    /// and returns the label.
+    pub fn open(&mut self, window: u64, label: Option<String>) -> Result<String> {
+        let label = label.unwrap_or_else(|| format!("window-{}", window));
+        self.check_label(&label)?;
+        self.windows
+            .insert(window, WindowEvents::new(label.clone()));
+        Ok(label)
+    }
+
+    pub fn close(&mut self, window: u64) {
+        self.windows.remove(&window);
+    }
+
+    pub fn label(&self, window: u64) -> Option<&str> {
+        self.windows
+            .get(&window)
+            .map(|events| events.label.as_str())
+    }
+
+    /// The events of `window`; `None` for windows that aren't ours, like
+    /// the splash screen's.
+    pub fn events(&mut self, window: u64) -> Option<&mut WindowEvents> {
+        self.windows.get_mut(&window)
+    }
+
+    /// [`WindowEvents::poll`] of every window.
+    pub fn poll(&mut self, now: Instant) -> Vec<(&'static str, Value)> {
+        self.windows
+            .values_mut()
+            .flat_map(|events| events.poll(now))
+            .collect()
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.windows
+            .values()
+            .filter_map(WindowEvents::deadline)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST: u64 = 1;
+    const SECOND: u64 = 2;
+
+    fn label_of(payload: &Value) -> &str {
+        payload["label"].as_str().unwrap()
+    }
+
+    #[test]
+    fn events_carry_the_label_of_their_window() {
+        let mut router = WindowRouter::new(FIRST);
+        assert_eq!(
+            router.open(SECOND, Some("settings".into())).unwrap(),
+            "settings"
+        );
+        let now = Instant::now();
+        let first = router
+            .events(FIRST)
+            .unwrap()
+            .resized(now, PhysicalSize::new(800, 600), 1.0)
+            .unwrap();
+        let second = router
+            .events(SECOND)
+            .unwrap()
+            .moved(now, PhysicalPosition::new(10, 20), 2.0)
+            .unwrap();
+        assert_eq!(label_of(&first), WINDOW_LABEL);
+        assert_eq!(label_of(&second), "settings");
+        assert_eq!(second["logical"], json!({ "x": 5.0, "y": 10.0 }));
+    }
+
+    #[test]
+    fn every_window_is_polled() {
+        let mut router = WindowRouter::new(FIRST);
+        router.open(SECOND, None).unwrap();
+        let now = Instant::now();
+        router.events(FIRST).unwrap().focused(now, false);
+        router.events(SECOND).unwrap().focused(now, true);
+        assert_eq!(router.deadline(), Some(now + SETTLE_DELAY));
+
+        let mut due = router.poll(now + SETTLE_DELAY);
+        due.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(
+            due,
+            [
+                ("blur", json!({ "label": WINDOW_LABEL })),
+                ("focus", json!({ "label": "window-2" })),
+            ]
+        );
+        assert_eq!(router.deadline(), None);
+    }
+
+    #[test]
+    fn labels_are_unique_while_open() {
+        let mut router = WindowRouter::new(FIRST);
+        assert!(router.open(SECOND, Some(WINDOW_LABEL.into())).is_err());
+        router.open(SECOND, Some("settings".into())).unwrap();
+        assert!(router.check_label("settings").is_err());
+        assert_eq!(router.label(SECOND), Some("settings"));
+
+        router.close(SECOND);
+        assert_eq!(router.label(SECOND), None);
+        assert!(router.events(SECOND).is_none());
+        router.check_label("settings").unwrap();
+    }
+}
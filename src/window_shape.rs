@@ -0,0 +1,269 @@
+//! Rounded corners for frameless windows: `set_corner_radius`.
+//!
+//! | Platform | Mechanism |
+//! |----------|-----------|
+//! | Windows 11 | DWM corner preference: 8 px, or 4 px for radii up to 4; the system shadow follows the corners |
+//! | Windows 10 | a window region, rebuilt on resize; no system shadow |
+//! | macOS | corner radius of the content view's layer; the shadow is recomputed |
+//! | Linux on X11 | the window's shape, rebuilt on resize; hard edges and no system shadow |
+//! | Wayland, iOS, Android | unsupported |
+//!
+//! Corners the platform can't shape, or a shadow it can't keep, fail with
+//! [`Unsupported`].
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tao::window::Window;
+
+#[allow(unused_imports)]
+use crate::api_manager::Unsupported;
+
+/// How the corners were shaped, as returned by `set_corner_radius`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[allow(dead_code)]
+pub enum Mechanism {
+    /// Windows 11's corner preference.
+    Dwm,
+    /// A window region on Windows 10.
+    Region,
+    /// The content view's layer on macOS.
+    Layer,
+    /// The X11 window shape.
+    Shape,
+}
+
+impl Mechanism {
+    /// Whether the shape has to be rebuilt when the window's size changes.
+    fn follows_size(self) -> bool {
+        matches!(self, Self::Region | Self::Shape)
+    }
+}
+
+#[derive(Default)]
+pub struct WindowShape {
+    /// In logical pixels; 0 for square corners.
+    radius: f64,
+    mechanism: Option<Mechanism>,
+}
+
+impl WindowShape {
+    /// Rounds the corners by `radius` logical pixels, and keeps or drops
+    /// the system shadow if `shadow` is given.
+    pub fn set(&mut self, window: &Window, radius: f64, shadow: Option<bool>) -> Result<Mechanism> {
+        if !radius.is_finite() || radius < 0.0 {
+            return Err(anyhow!("Invalid corner radius {}", radius));
+        }
+        let mechanism = platform::round(window, radius)?;
+        if let Some(shadow) = shadow {
+            platform::set_shadow(window, mechanism, shadow)?;
+        }
+        self.radius = radius;
+        self.mechanism = Some(mechanism);
+        Ok(mechanism)
+    }
+
+    /// Call on resize and scale factor changes.
+    pub fn resized(&self, window: &Window) {
+        if self.mechanism.is_some_and(Mechanism::follows_size) {
+            crate::log_if_err!(platform::round(window, self.radius));
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ffi::c_void;
+
+    use anyhow::Result;
+    use tao::{platform::windows::WindowExtWindows, window::Window};
+    use windows::Win32::{
+        Foundation::HWND,
+        Graphics::{
+            Dwm::{
+                DwmSetWindowAttribute, DWMWA_WINDOW_CORNER_PREFERENCE, DWMWCP_DONOTROUND,
+                DWMWCP_ROUND, DWMWCP_ROUNDSMALL, DWM_WINDOW_CORNER_PREFERENCE,
+            },
+            Gdi::{CreateRoundRectRgn, SetWindowRgn},
+        },
+    };
+
+    use super::{Mechanism, Unsupported};
+
+    /// Largest radius Windows 11 draws with `DWMWCP_ROUNDSMALL`.
+    const SMALL_RADIUS: f64 = 4.0;
+
+    pub fn round(window: &Window, radius: f64) -> Result<Mechanism> {
+        let hwnd = HWND(window.hwnd() as _);
+        let preference: DWM_WINDOW_CORNER_PREFERENCE = match radius {
+            r if r == 0.0 => DWMWCP_DONOTROUND,
+            r if r <= SMALL_RADIUS => DWMWCP_ROUNDSMALL,
+            _ => DWMWCP_ROUND,
+        };
+        // Windows 10 doesn't know the attribute.
+        let dwm = unsafe {
+            DwmSetWindowAttribute(
+                hwnd,
+                DWMWA_WINDOW_CORNER_PREFERENCE,
+                &preference as *const _ as *const c_void,
+                std::mem::size_of::<DWM_WINDOW_CORNER_PREFERENCE>() as u32,
+            )
+        };
+        if dwm.is_ok() {
+            return Ok(Mechanism::Dwm);
+        }
+        let size = window.outer_size();
+        let diameter = (radius * window.scale_factor() * 2.0).round() as i32;
+        unsafe {
+            if diameter == 0 {
+                SetWindowRgn(hwnd, None, true);
+            } else {
+                // The window owns the region from here on.
+                let region = CreateRoundRectRgn(
+                    0,
+                    0,
+                    size.width as i32 + 1,
+                    size.height as i32 + 1,
+                    diameter,
+                    diameter,
+                );
+                SetWindowRgn(hwnd, Some(region), true);
+            }
+        }
+        Ok(Mechanism::Region)
+    }
+
+    pub fn set_shadow(window: &Window, mechanism: Mechanism, shadow: bool) -> Result<()> {
+        if shadow && mechanism == Mechanism::Region {
+            // The region clips DWM's shadow away.
+            return Err(anyhow::Error::new(Unsupported {
+                feature: "window shadow with rounded corners on Windows 10".into(),
+            }));
+        }
+        window.set_undecorated_shadow(shadow);
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::Result;
+    use objc2::{class, msg_send, runtime::AnyObject};
+    use tao::{platform::macos::WindowExtMacOS, window::Window};
+
+    use super::Mechanism;
+
+    pub fn round(window: &Window, radius: f64) -> Result<Mechanism> {
+        unsafe {
+            let view = &*(window.ns_view() as *const AnyObject);
+            let ns_window = &*(window.ns_window() as *const AnyObject);
+            let _: () = msg_send![view, setWantsLayer: true];
+            let layer: *mut AnyObject = msg_send![view, layer];
+            if let Some(layer) = layer.as_ref() {
+                let _: () = msg_send![layer, setCornerRadius: radius];
+                let _: () = msg_send![layer, setMasksToBounds: radius > 0.0];
+            }
+            // The corners outside the layer have to show through.
+            let clear: *mut AnyObject = msg_send![class!(NSColor), clearColor];
+            let _: () = msg_send![ns_window, setOpaque: false];
+            let _: () = msg_send![ns_window, setBackgroundColor: clear];
+            let _: () = msg_send![ns_window, invalidateShadow];
+        }
+        Ok(Mechanism::Layer)
+    }
+
+    pub fn set_shadow(window: &Window, _mechanism: Mechanism, shadow: bool) -> Result<()> {
+        window.set_has_shadow(shadow);
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod platform {
+    use anyhow::Result;
+    use gtk::{
+        cairo::{RectangleInt, Region},
+        glib::prelude::ObjectExt,
+        prelude::{GtkWindowExt, WidgetExt},
+    };
+    use tao::{platform::unix::WindowExtUnix, window::Window};
+
+    use super::{Mechanism, Unsupported};
+
+    pub fn round(window: &Window, radius: f64) -> Result<Mechanism> {
+        let gtk_window = window.gtk_window();
+        // Wayland compositors don't take window shapes.
+        if gtk_window.display().type_().name() != "GdkX11Display" {
+            return Err(anyhow::Error::new(Unsupported {
+                feature: "set_corner_radius on Wayland".into(),
+            }));
+        }
+        if radius == 0.0 {
+            gtk_window.shape_combine_region(None);
+            return Ok(Mechanism::Shape);
+        }
+        // GTK measures the shape in logical pixels.
+        let (width, height) = gtk_window.size();
+        gtk_window.shape_combine_region(Some(&rounded_rect(width, height, radius)?));
+        Ok(Mechanism::Shape)
+    }
+
+    /// A `width` by `height` rectangle with its corners cut to quarter
+    /// circles, one row of pixels at a time.
+    fn rounded_rect(width: i32, height: i32, radius: f64) -> Result<Region> {
+        let radius = (radius.round() as i32).min(width / 2).min(height / 2);
+        let region = Region::create();
+        region.union_rectangle(&RectangleInt::new(0, radius, width, height - 2 * radius))?;
+        for row in 0..radius {
+            let dy = radius as f64 - row as f64 - 0.5;
+            let r = radius as f64;
+            let inset = (r - (r * r - dy * dy).max(0.0).sqrt()).round() as i32;
+            let span = width - 2 * inset;
+            region.union_rectangle(&RectangleInt::new(inset, row, span, 1))?;
+            region.union_rectangle(&RectangleInt::new(inset, height - 1 - row, span, 1))?;
+        }
+        Ok(region)
+    }
+
+    pub fn set_shadow(_window: &Window, _mechanism: Mechanism, shadow: bool) -> Result<()> {
+        // Frameless X11 windows have no shadow of their own to keep.
+        if shadow {
+            return Err(anyhow::Error::new(Unsupported {
+                feature: "window shadow for frameless windows on Linux".into(),
+            }));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+mod platform {
+    use anyhow::Result;
+    use tao::window::Window;
+
+    use super::{Mechanism, Unsupported};
+
+    pub fn round(_window: &Window, _radius: f64) -> Result<Mechanism> {
+        Err(anyhow::Error::new(Unsupported {
+            feature: "set_corner_radius".into(),
+        }))
+    }
+
+    pub fn set_shadow(_window: &Window, _mechanism: Mechanism, _shadow: bool) -> Result<()> {
+        Ok(())
+    }
+}
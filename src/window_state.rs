@@ -0,0 +1,194 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tao::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::MonitorHandle,
+    window::{Window, WindowBuilder},
+};
+
+use crate::config::AppConfig;
+
+const STATE_FILE: &str = "window-state.json";
+/// Quiet period after the last resize or move before the state is written.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Size and position of the window, in physical pixels. While maximized,
+/// the size and position of the restored window are kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    pub monitor: Option<String>,
+}
+
+impl WindowState {
+    /// Applies the state to `builder`, moved and shrunk onto one of
+    /// `monitors` if the saved display is gone or its layout changed.
+    pub fn restore(
+        &self,
+        builder: WindowBuilder,
+        monitors: &[MonitorHandle],
+        primary: Option<MonitorHandle>,
+    ) -> WindowBuilder {
+        let (position, size) = self.clamp(monitors, primary);
+        builder
+            .with_position(position)
+            .with_inner_size(size)
+            .with_maximized(self.maximized)
+    }
+
+    fn clamp(
+        &self,
+        monitors: &[MonitorHandle],
+        primary: Option<MonitorHandle>,
+    ) -> (PhysicalPosition<i32>, PhysicalSize<u32>) {
+        let position = PhysicalPosition::new(self.x, self.y);
+        let size = PhysicalSize::new(self.width, self.height);
+        let overlapping = |m: &&MonitorHandle| overlaps(m, position, size);
+        let monitor = monitors
+            .iter()
+            .filter(overlapping)
+            .find(|m| m.name() == self.monitor)
+            .or_else(|| monitors.iter().find(overlapping))
+            .cloned()
+            .or(primary)
+            .or_else(|| monitors.first().cloned());
+        let Some(monitor) = monitor else {
+            return (position, size);
+        };
+
+        let origin = monitor.position();
+        let bounds = monitor.size();
+        let width = size.width.min(bounds.width);
+        let height = size.height.min(bounds.height);
+        let x = position
+            .x
+            .clamp(origin.x, origin.x + (bounds.width - width) as i32);
+        let y = position
+            .y
+            .clamp(origin.y, origin.y + (bounds.height - height) as i32);
+        (
+            PhysicalPosition::new(x, y),
+            PhysicalSize::new(width, height),
+        )
+    }
+}
+
+fn overlaps(
+    monitor: &MonitorHandle,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> bool {
+    let origin = monitor.position();
+    let bounds = monitor.size();
+    position.x < origin.x + bounds.width as i32
+        && origin.x < position.x + size.width as i32
+        && position.y < origin.y + bounds.height as i32
+        && origin.y < position.y + size.height as i32
+}
+
+/// Saves the window state to `<config dir>/<app_id>/window-state.json`,
+/// debounced behind [`SAVE_DELAY`]. Enabled by `persist_window_state`.
+pub struct WindowStateStore {
+    path: PathBuf,
+    last: Option<WindowState>,
+    save_at: Option<Instant>,
+}
+
+impl WindowStateStore {
+    /// `None` unless `persist_window_state` is set and the platform has a
+    /// config directory.
+    pub fn new(config: &AppConfig) -> Option<Self> {
+        if !config.persist_window_state {
+            return None;
+        }
+        Some(Self {
+            path: config.config_dir()?.join(STATE_FILE),
+            last: None,
+            save_at: None,
+        })
+    }
+
+    /// The saved state; a missing or corrupt file counts as none.
+    pub fn load(&mut self) -> Option<WindowState> {
+        let json = fs::read_to_string(&self.path).ok()?;
+        self.last = serde_json::from_str(&json).ok();
+        self.last.clone()
+    }
+
+    /// Call on resize or move; the state is saved once the window settles.
+    pub fn changed(&mut self, now: Instant) {
+        self.save_at = Some(now + SAVE_DELAY);
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.save_at
+    }
+
+    /// Saves the state if the window has settled.
+    pub fn poll(&mut self, now: Instant, window: &Window) {
+        if self.save_at.is_some_and(|at| at <= now) {
+            self.flush(window);
+        }
+    }
+
+    /// Saves a pending change right away, e.g. at shutdown.
+    pub fn flush(&mut self, window: &Window) {
+        if self.save_at.take().is_none() {
+            return;
+        }
+        if let Err(e) = self.save(window) {
+            crate::log_err!(format!("Failed to save window state: {}", e));
+        }
+    }
+
+    fn save(&mut self, window: &Window) -> Result<()> {
+        // A minimized window reports a bogus position on Windows.
+        if window.is_minimized() {
+            return Ok(());
+        }
+        let maximized = window.is_maximized();
+        let state = match (&self.last, maximized) {
+            (Some(last), true) => WindowState {
+                maximized,
+                monitor: window.current_monitor().and_then(|m| m.name()),
+                ..last.clone()
+            },
+            _ => {
+                let position = window.outer_position()?;
+                let size = window.inner_size();
+                WindowState {
+                    x: position.x,
+                    y: position.y,
+                    width: size.width,
+                    height: size.height,
+                    maximized,
+                    monitor: window.current_monitor().and_then(|m| m.name()),
+                }
+            }
+        };
+        crate::config::write_atomic(&self.path, &serde_json::to_vec_pretty(&state)?)?;
+        self.last = Some(state);
+        Ok(())
+    }
+
+    /// Deletes the saved state and drops a pending save. Later changes are
+    /// saved again.
+    pub fn reset(&mut self) -> Result<()> {
+        self.save_at = None;
+        self.last = None;
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
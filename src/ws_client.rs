@@ -0,0 +1,310 @@
+//! Outbound WebSockets behind `ws_connect`, `ws_send` and `ws_close`, made
+//! natively so they work where the WebView's own `wss://` is intercepted.
+//!
+//! Only URLs under `ws_allowed_urls` may be opened. A connection reports
+//! `ws-open`, `ws-message`, `ws-error` and finally `ws-close`, each tagged
+//! with its id. Connections opened by the page close when it navigates
+//! away, those opened over TCP when their client disconnects, and all of
+//! them at shutdown.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{runtime::Handle, sync::mpsc};
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    http::HeaderValue,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use url::Url;
+
+use crate::{
+    api_manager::PermissionDenied,
+    connections::ConnectionId,
+    utils::{FrameEventLoopProxy, UserEvent},
+};
+
+pub type WsId = u64;
+
+/// What a connection's lifetime is tied to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    /// The current page; closed when it navigates.
+    Page,
+    Connection(ConnectionId),
+}
+
+#[derive(Debug)]
+pub enum WsEvent {
+    Open {
+        protocol: Option<String>,
+    },
+    Text(String),
+    Binary(Vec<u8>),
+    Error(String),
+    /// Always the last event; `code` is `None` if the socket just dropped.
+    Closed {
+        code: Option<u16>,
+        reason: String,
+    },
+}
+
+/// A `pyframe.ws` call from the page.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum WsOp {
+    Connect {
+        url: String,
+        #[serde(default)]
+        protocols: Vec<String>,
+    },
+    Send {
+        conn: WsId,
+        data: String,
+        #[serde(default)]
+        binary: bool,
+    },
+    Close {
+        conn: WsId,
+        code: Option<u16>,
+        reason: Option<String>,
+    },
+}
+
+struct WsConnection {
+    owner: Option<Owner>,
+    // Dropping it ends the connection.
+    outgoing: mpsc::UnboundedSender<Message>,
+}
+
+#[derive(Default)]
+pub struct WsConnections {
+    last_id: WsId,
+    connections: HashMap<WsId, WsConnection>,
+}
+
+impl WsConnections {
+    /// Starts connecting and returns the id at once; `ws-open` follows.
+    /// Messages sent before then are queued.
+    pub fn connect(
+        &mut self,
+        allowed: &[String],
+        url: &str,
+        protocols: &[String],
+        owner: Option<Owner>,
+        rt: &Handle,
+        proxy: FrameEventLoopProxy,
+    ) -> Result<WsId> {
+        let parsed = Url::parse(url)?;
+        if !matches!(parsed.scheme(), "ws" | "wss") {
+            bail!("{} is not a ws:// or wss:// URL", url);
+        }
+        if !allows(allowed, &parsed) {
+            return Err(anyhow::Error::new(PermissionDenied {
+                permission: "ws_url",
+            })
+            .context(format!("{} is not in ws_allowed_urls", url)));
+        }
+        let mut request = url.into_client_request()?;
+        if !protocols.is_empty() {
+            request.headers_mut().insert(
+                "Sec-WebSocket-Protocol",
+                HeaderValue::from_str(&protocols.join(", "))?,
+            );
+        }
+
+        self.last_id += 1;
+        let id = self.last_id;
+        let (outgoing, receiver) = mpsc::unbounded_channel();
+        crate::tasks::spawn_on("ws_client", rt, run(id, request, receiver, proxy));
+        self.connections
+            .insert(id, WsConnection { owner, outgoing });
+        Ok(id)
+    }
+
+    /// `data` is base64 with `binary`.
+    pub fn send(&self, id: WsId, data: String, binary: bool) -> Result<()> {
+        let message = if binary {
+            Message::binary(base64::engine::general_purpose::STANDARD.decode(data)?)
+        } else {
+            Message::text(data)
+        };
+        self.outgoing(id)?
+            .send(message)
+            .map_err(|_| anyhow!("WebSocket {} is closed", id))
+    }
+
+    /// Starts the closing handshake; `ws-close` follows once it completes.
+    pub fn close(&self, id: WsId, code: Option<u16>, reason: Option<String>) -> Result<()> {
+        let frame = CloseFrame {
+            code: CloseCode::from(code.unwrap_or(1000)),
+            reason: reason.unwrap_or_default().into(),
+        };
+        // The task is already gone if the socket closed meanwhile.
+        let _ = self.outgoing(id)?.send(Message::Close(Some(frame)));
+        Ok(())
+    }
+
+    fn outgoing(&self, id: WsId) -> Result<&mpsc::UnboundedSender<Message>> {
+        self.connections
+            .get(&id)
+            .map(|connection| &connection.outgoing)
+            .ok_or_else(|| anyhow!("No WebSocket {}", id))
+    }
+
+    /// Runs a call from the page, which then owns new connections.
+    pub fn apply(
+        &mut self,
+        op: WsOp,
+        allowed: &[String],
+        rt: &Handle,
+        proxy: FrameEventLoopProxy,
+    ) -> Result<Value> {
+        Ok(match op {
+            WsOp::Connect { url, protocols } => {
+                json!(self.connect(allowed, &url, &protocols, Some(Owner::Page), rt, proxy)?)
+            }
+            WsOp::Send { conn, data, binary } => {
+                self.send(conn, data, binary)?;
+                json!(true)
+            }
+            WsOp::Close { conn, code, reason } => {
+                self.close(conn, code, reason)?;
+                json!(true)
+            }
+        })
+    }
+
+    /// Forgets a connection after its `ws-close`.
+    pub fn closed(&mut self, id: WsId) {
+        self.connections.remove(&id);
+    }
+
+    /// Drops the connections `owner` opened.
+    pub fn drop_owner(&mut self, owner: Owner) {
+        self.connections
+            .retain(|_, connection| connection.owner != Some(owner));
+    }
+
+    pub fn clear_all(&mut self) {
+        self.connections.clear();
+    }
+}
+
+/// Whether an entry of `ws_allowed_urls` has `url`'s origin and a prefix of
+/// its path; `wss://host` allows every path on the host.
+fn allows(allowed: &[String], url: &Url) -> bool {
+    allowed.iter().any(|entry| {
+        Url::parse(entry).is_ok_and(|entry| {
+            entry.origin() == url.origin() && url.path().starts_with(entry.path())
+        })
+    })
+}
+
+async fn run(
+    id: WsId,
+    request: tokio_tungstenite::tungstenite::http::Request<()>,
+    mut outgoing: mpsc::UnboundedReceiver<Message>,
+    proxy: FrameEventLoopProxy,
+) {
+    // `false` once the event loop has exited, which ends the socket.
+    let send = |event| proxy.send_event(UserEvent::Ws(id, event)).is_ok();
+    let (socket, response) = match tokio_tungstenite::connect_async(request).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            if send(WsEvent::Error(e.to_string())) {
+                send(WsEvent::Closed {
+                    code: None,
+                    reason: String::new(),
+                });
+            }
+            return;
+        }
+    };
+    let protocol = response
+        .headers()
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if !send(WsEvent::Open { protocol }) {
+        return;
+    }
+
+    let (mut sink, mut stream) = socket.split();
+    let mut close = (None, String::new());
+    loop {
+        tokio::select! {
+            message = outgoing.recv() => {
+                // `None`: the connection was dropped by its owner.
+                let Some(message) = message else {
+                    let _ = sink.close().await;
+                    break;
+                };
+                if let Err(e) = sink.send(message).await {
+                    send(WsEvent::Error(e.to_string()));
+                    break;
+                }
+            }
+            incoming = stream.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    if !send(WsEvent::Text(text.as_str().to_string())) {
+                        let _ = sink.close().await;
+                        return;
+                    }
+                }
+                Some(Ok(Message::Binary(bytes))) => {
+                    if !send(WsEvent::Binary(bytes.to_vec())) {
+                        let _ = sink.close().await;
+                        return;
+                    }
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    if let Some(frame) = frame {
+                        close = (Some(u16::from(frame.code)), frame.reason.as_str().to_string());
+                    }
+                    break;
+                }
+                // Pings are answered by tungstenite.
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    send(WsEvent::Error(e.to_string()));
+                    break;
+                }
+                None => break,
+            },
+        }
+    }
+    send(WsEvent::Closed {
+        code: close.0,
+        reason: close.1,
+    });
+}
+
+/// Event name and payload of `event`.
+pub fn describe(id: WsId, event: &WsEvent) -> (&'static str, Value) {
+    match event {
+        WsEvent::Open { protocol } => ("ws-open", json!({ "id": id, "protocol": protocol })),
+        WsEvent::Text(text) => (
+            "ws-message",
+            json!({ "id": id, "binary": false, "data": text }),
+        ),
+        WsEvent::Binary(bytes) => (
+            "ws-message",
+            json!({
+                "id": id,
+                "binary": true,
+                "data": base64::engine::general_purpose::STANDARD.encode(bytes),
+            }),
+        ),
+        WsEvent::Error(message) => ("ws-error", json!({ "id": id, "message": message })),
+        WsEvent::Closed { code, reason } => (
+            "ws-close",
+            json!({ "id": id, "code": code, "reason": reason }),
+        ),
+    }
+}